@@ -0,0 +1,144 @@
+//! Timings of the main structures on synthetic data.
+//!
+//! Run with
+//!
+//! ```text
+//! cargo bench
+//! ```
+//!
+//! or `cargo bench -- rank1` for one group. Each benchmark answers a
+//! fixed batch of pseudo-random queries, so runs are repeatable, and
+//! reports the time per query. Within a group the structures see the
+//! same bits and the same queries, so they can be compared directly.
+
+#[macro_use]
+extern crate criterion;
+extern crate succinct;
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+
+use succinct::*;
+
+// The bit length of every structure.
+const LEN: u64 = 1 << 22;
+
+// The queries answered per iteration.
+const QUERIES: usize = 1 << 10;
+
+const DENSITIES: &[f64] = &[0.01, 0.5, 0.99];
+
+/// A xorshift generator, so the inputs are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Rng(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, limit: u64) -> u64 {
+        self.next() % limit
+    }
+
+    fn batch(&mut self, limit: u64) -> Vec<u64> {
+        (0 .. QUERIES).map(|_| self.below(limit)).collect()
+    }
+}
+
+fn random_bits(rng: &mut Rng, density: f64) -> BitVector<u64> {
+    let threshold = (density * u64::MAX as f64) as u64;
+    let mut result = BitVector::with_capacity(LEN);
+    for _ in 0 .. LEN {
+        result.push_bit(rng.next() < threshold);
+    }
+    result
+}
+
+/// Answers `query` for each of `inputs`, summing the answers so that
+/// none is optimized away.
+fn run<F: Fn(u64) -> u64>(inputs: &[u64], query: F) -> u64 {
+    inputs.iter().fold(0, |sum, &input| sum.wrapping_add(query(black_box(input))))
+}
+
+fn int_vector_get(c: &mut Criterion) {
+    let mut rng = Rng::new();
+    let mut group = c.benchmark_group("IntVector get");
+    group.throughput(Throughput::Elements(QUERIES as u64));
+
+    for &bits in &[1, 7, 8, 13, 32, 64] {
+        let mut v = IntVector::<u64>::with_capacity(bits, LEN);
+        for _ in 0 .. LEN {
+            v.push(rng.next() & u64::MAX >> (64 - bits));
+        }
+        let inputs = rng.batch(LEN);
+
+        group.bench_with_input(BenchmarkId::from_parameter(bits), &inputs, |b, inputs| {
+            b.iter(|| run(inputs, |i| v.get(i)))
+        });
+    }
+
+    group.finish();
+}
+
+fn rank1(c: &mut Criterion) {
+    let mut rng = Rng::new();
+    let mut group = c.benchmark_group("rank1");
+    group.throughput(Throughput::Elements(QUERIES as u64));
+
+    for &density in DENSITIES {
+        let bits = random_bits(&mut rng, density);
+        let positions = rng.batch(LEN);
+
+        let jacobson = JacobsonRank::new(bits.clone());
+        group.bench_with_input(BenchmarkId::new("JacobsonRank", density), &positions,
+                               |b, positions| b.iter(|| run(positions, |i| jacobson.rank1(i))));
+
+        let rank9 = Rank9::new(bits.clone());
+        group.bench_with_input(BenchmarkId::new("Rank9", density), &positions,
+                               |b, positions| b.iter(|| run(positions, |i| rank9.rank1(i))));
+
+        let narrow = Rank9::try_new_narrow(bits).unwrap();
+        group.bench_with_input(BenchmarkId::new("Rank9 narrow", density), &positions,
+                               |b, positions| b.iter(|| run(positions, |i| narrow.rank1(i))));
+    }
+
+    group.finish();
+}
+
+fn select1(c: &mut Criterion) {
+    let mut rng = Rng::new();
+    let mut group = c.benchmark_group("select1");
+    group.throughput(Throughput::Elements(QUERIES as u64));
+
+    for &density in DENSITIES {
+        let bits = random_bits(&mut rng, density);
+        let rank9 = Rank9::new(bits);
+        let ones = rank9.rank1(LEN - 1);
+        let indices = rng.batch(ones);
+
+        let bin_search = BinSearchSelect::new(rank9.clone());
+        group.bench_with_input(BenchmarkId::new("BinSearchSelect", density), &indices,
+                               |b, indices| {
+            b.iter(|| run(indices, |k| bin_search.select1(k).unwrap()))
+        });
+
+        let select9 = Select9::new(rank9);
+        group.bench_with_input(BenchmarkId::new("Select9", density), &indices,
+                               |b, indices| {
+            b.iter(|| run(indices, |k| select9.select1(k).unwrap()))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, int_vector_get, rank1, select1);
+criterion_main!(benches);
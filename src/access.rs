@@ -0,0 +1,220 @@
+//! Random access to any sequence in the crate.
+//!
+//! [`Access`](trait.Access.html) is the least an algorithm needs to read
+//! a sequence: its length and its `i`th item. Every
+//! [`IntVec`](../int_vec/trait.IntVec.html) has it, so packed vectors,
+//! Elias–Fano sequences and wavelet matrices do, and so do slices. A
+//! search or a scan written against `Access` then runs on any of them:
+//!
+//! ```
+//! use succinct::access::Access;
+//! use succinct::int_vec::{DeltaVector, EliasFano};
+//! use succinct::wavelet::WaveletMatrix;
+//!
+//! fn first_at_least<A: Access<u64>>(seq: &A, value: u64) -> u64 {
+//!     seq.partition_point(|&x| x < value)
+//! }
+//!
+//! let values = vec![1, 4, 4, 9, 16];
+//! assert_eq!(3, first_at_least(&DeltaVector::new(4, values.clone()), 5));
+//! assert_eq!(3, first_at_least(&EliasFano::new_multiset(20, values.clone()), 5));
+//! assert_eq!(3, first_at_least(&WaveletMatrix::new(values), 5));
+//! ```
+//!
+//! Types that are `IntVec`s have methods of the same names, so call
+//! these through the trait, as in `Access::get(&v, i)`, where both are
+//! in scope. `Vec`s are reached through their slices, so that their
+//! own `get` and `iter` aren’t shadowed; slices have `argmin` too, but
+//! the provided methods that need `Self: Sized`, which keeps the trait
+//! usable as `dyn Access`, are left to the slice’s own.
+
+use std::ops::Range;
+
+use int_vec::IntVec;
+
+/// A sequence of `Item`s with random access.
+pub trait Access<Item> {
+    /// The number of items.
+    fn len(&self) -> u64;
+
+    /// Is the sequence empty?
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `index`th item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    fn get(&self, index: u64) -> Item;
+
+    /// Iterates over the items in order.
+    fn iter(&self) -> AccessIter<'_, Self, Item>
+        where Self: Sized {
+
+        AccessIter::new(self)
+    }
+
+    /// The first index whose item fails `pred`, given that the items
+    /// that pass it all come before those that fail.
+    fn partition_point<P>(&self, mut pred: P) -> u64
+        where Self: Sized,
+              P: FnMut(&Item) -> bool {
+
+        let mut start = 0;
+        let mut end = self.len();
+        while start < end {
+            let mid = start + (end - start) / 2;
+            if pred(&self.get(mid)) {
+                start = mid + 1;
+            } else {
+                end = mid;
+            }
+        }
+        start
+    }
+
+    /// The index of the leftmost least item in `range`, if it isn’t
+    /// empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    fn argmin(&self, range: Range<u64>) -> Option<u64>
+        where Item: Ord {
+
+        assert!(range.end <= self.len(), "Access::argmin: out of bounds");
+        let mut best: Option<(u64, Item)> = None;
+        for index in range {
+            let item = self.get(index);
+            if best.as_ref().map_or(true, |(_, least)| item < *least) {
+                best = Some((index, item));
+            }
+        }
+        best.map(|(index, _)| index)
+    }
+}
+
+impl<V: IntVec + ?Sized> Access<V::Block> for V {
+    fn len(&self) -> u64 {
+        IntVec::len(self)
+    }
+
+    fn get(&self, index: u64) -> V::Block {
+        IntVec::get(self, index)
+    }
+}
+
+impl<T: Clone> Access<T> for [T] {
+    fn len(&self) -> u64 {
+        <[T]>::len(self) as u64
+    }
+
+    fn get(&self, index: u64) -> T {
+        self[index as usize].clone()
+    }
+}
+
+/// The items of an [`Access`](trait.Access.html) sequence, in order.
+#[derive(Debug)]
+pub struct AccessIter<'a, A: ?Sized + 'a, Item> {
+    data: &'a A,
+    // The items in `front .. back` remain.
+    front: u64,
+    back: u64,
+    marker: ::std::marker::PhantomData<fn() -> Item>,
+}
+
+impl<'a, A: Access<Item> + ?Sized, Item> AccessIter<'a, A, Item> {
+    /// Iterates over the items of `data`.
+    pub fn new(data: &'a A) -> Self {
+        AccessIter::with_range(data, 0 .. data.len())
+    }
+
+    /// Iterates over the items of `data` in `range`.
+    pub(crate) fn with_range(data: &'a A, range: Range<u64>) -> Self {
+        AccessIter { data, front: range.start, back: range.end, marker: ::std::marker::PhantomData }
+    }
+}
+
+impl<'a, A: ?Sized, Item> Clone for AccessIter<'a, A, Item> {
+    fn clone(&self) -> Self {
+        AccessIter { data: self.data, front: self.front, back: self.back, marker: self.marker }
+    }
+}
+
+impl<'a, A: Access<Item> + ?Sized, Item> Iterator for AccessIter<'a, A, Item> {
+    type Item = Item;
+
+    fn next(&mut self) -> Option<Item> {
+        if self.front >= self.back { return None; }
+        self.front += 1;
+        Some(self.data.get(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.back - self.front) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Item> {
+        self.front = self.front.saturating_add(n as u64).min(self.back);
+        self.next()
+    }
+
+    impl_exact_size_count_last!(Item);
+}
+
+impl<'a, A: Access<Item> + ?Sized, Item> ExactSizeIterator for AccessIter<'a, A, Item> {}
+
+impl<'a, A: Access<Item> + ?Sized, Item> DoubleEndedIterator for AccessIter<'a, A, Item> {
+    impl_index_range_back!(Item, front .. back, |iter, index| iter.data.get(index));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use int_vec::{DeltaVector, IntVector};
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn qc_same_over_containers() {
+        fn prop(values: Vec<u16>, start: u8, end: u8) -> bool {
+            let values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            let packed: IntVector<u64> = values.iter().cloned().collect();
+            let len = values.len() as u64;
+            let range = (start as u64).min(len) .. (end as u64).min(len).max((start as u64).min(len));
+            let expected = range.clone().min_by_key(|&i| values[i as usize]);
+
+            let mut sorted = values.clone();
+            sorted.sort();
+            let deltas = DeltaVector::new(4, sorted.iter().cloned());
+            let probe = values.first().cloned().unwrap_or(0);
+            let point = sorted.iter().filter(|&&x| x < probe).count() as u64;
+
+            Access::argmin(&values[..], range.clone()) == expected
+                && Access::argmin(&packed, range) == expected
+                && Access::partition_point(&deltas, |&x| x < probe) == point
+                && Access::iter(&deltas).eq(sorted.iter().cloned())
+                && Access::iter(&packed).rev().eq(values.iter().cloned().rev())
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u16>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            let packed: IntVector<u64> = values.iter().cloned().collect();
+            skips_agree(Access::iter(&packed), &values, n, step)
+                && skips_agree(AccessIter::new(&values[..]), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+}
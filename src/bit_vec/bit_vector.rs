@@ -1,16 +1,29 @@
 use std::fmt;
+use std::iter::FromIterator;
+use std::io::{self, Write};
 
 #[cfg(target_pointer_width = "32")]
 use num::ToPrimitive;
 
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use dump::{Diff, Dump, Snapshot};
+use error::Error;
+use internal::encoding;
 use internal::vector_base::{VectorBase, self};
+use persist::{self, Component, Persist};
 use space_usage::SpaceUsage;
-use storage::BlockType;
+use storage::{BlockStore, BlockType, SharedStore};
 use super::traits::*;
 
 /// Uncompressed vector of bits.
+///
+/// `Store` holds the blocks, as for
+/// [`IntVector`](../int_vec/struct.IntVector.html).
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct BitVector<Block: BlockType = usize>(VectorBase<Block>);
+#[cfg_attr(feature = "rkyv", derive(::rkyv::Archive, ::rkyv::Serialize, ::rkyv::Deserialize))]
+pub struct BitVector<Block: BlockType = usize, Store = Vec<Block>>
+    (VectorBase<Block, Store>);
 
 impl<Block: BlockType> BitVector<Block> {
     /// Creates a new, empty bit vector.
@@ -30,6 +43,13 @@ impl<Block: BlockType> BitVector<Block> {
         BitVector(VectorBase::with_capacity(1, capacity))
     }
 
+    /// Creates a new, empty bit vector with space allocated for `capacity`
+    /// bits, or returns an error if the size overflows or the storage
+    /// can’t be allocated.
+    pub fn try_with_capacity(capacity: u64) -> Result<Self, Error> {
+        VectorBase::try_with_capacity(1, capacity).map(BitVector)
+    }
+
     /// Creates a new, empty bit vector with space allocated for `capacity`
     /// blocks.
     pub fn block_with_capacity(capacity: usize) -> Self {
@@ -52,11 +72,75 @@ impl<Block: BlockType> BitVector<Block> {
         result
     }
 
+    /// Creates a new bit vector of `len` bits initialized to `value`, or
+    /// returns an error if the size overflows or the storage can’t be
+    /// allocated.
+    pub fn try_with_fill(len: u64, value: bool) -> Result<Self, Error> {
+        let block_len = Block::checked_ceil_div_nbits(len)
+                             .ok_or(Error::Overflow)?;
+        let block_value = if value {!Block::zero()} else {Block::zero()};
+        let mut result = BitVector(
+            VectorBase::try_block_with_fill(1, block_len, block_value)?);
+        result.0.truncate(1, len);
+        Ok(result)
+    }
+
     /// Creates a new bit vector of `block_len` blocks initialized to `value`.
     pub fn block_with_fill(block_len: usize, value: Block) -> Self {
         BitVector(VectorBase::block_with_fill(1, block_len, value))
     }
 
+    /// Creates a bit vector holding the bits `bits` yields, in order.
+    pub fn from_bits<I>(bits: I) -> Self
+        where I: IntoIterator<Item = bool> {
+
+        let bits = bits.into_iter();
+        let mut result = Self::with_capacity(bits.size_hint().0 as u64);
+        for bit in bits {
+            result.push_bit(bit);
+        }
+        result
+    }
+
+    /// Creates a bit vector of `len` bits with ones at `positions`, which
+    /// may come in any order and repeat.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a position isn’t less than `len`.
+    pub fn from_ones<I>(len: u64, positions: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        let mut result = Self::with_fill(len, false);
+        for position in positions {
+            assert!(position < len, "BitVector::from_ones: out of bounds");
+            result.set_bit(position, true);
+        }
+        result
+    }
+
+    /// Moves the bits to a [`SharedStore`](../storage/struct.SharedStore.html),
+    /// after which the vector clones in constant time.
+    pub fn into_shared(self) -> BitVector<Block, SharedStore<Block>> {
+        BitVector(self.0.map_store(SharedStore::from))
+    }
+
+    /// The heap bytes a bit vector of `bit_len` bits takes, without
+    /// building it.
+    pub fn estimate_size(bit_len: u64) -> usize {
+        Block::ceil_div_nbits(bit_len) * ::std::mem::size_of::<Block>()
+    }
+}
+
+impl<Block, Store> BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    /// Creates a bit vector that adopts the blocks already in `store`.
+    pub fn with_store(store: Store) -> Self {
+        BitVector(VectorBase::with_store(1, store))
+    }
+
     /// How many bits the bit vector can hold without reallocating.
     pub fn capacity(&self) -> u64 {
         self.0.capacity(1)
@@ -67,6 +151,11 @@ impl<Block: BlockType> BitVector<Block> {
         self.0.block_capacity()
     }
 
+    /// Sets every bit to `value`, a block at a time.
+    pub fn fill(&mut self, value: bool) {
+        self.0.fill(1, if value {Block::one()} else {Block::zero()});
+    }
+
     /// Resizes the bit vector to the given number of elements,
     /// filling if necessary.
     ///
@@ -176,12 +265,20 @@ impl<Block: BlockType> BitVector<Block> {
     }
 
     /// Returns an iterator over the bits of the bit vector
-    pub fn iter(&self) -> Iter<Block> {
+    pub fn iter(&self) -> Iter<'_, Block, Store> {
         Iter(vector_base::Iter::new(1, &self.0))
     }
+
+    /// Compares the bits of two bit vectors.
+    pub fn diff(&self, other: &Self) -> Diff {
+        Diff::of_bits(self, other)
+    }
 }
 
-impl<Block: BlockType> BitVec for BitVector<Block> {
+impl<Block, Store> BitVec for BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     type Block = Block;
 
     #[inline]
@@ -199,7 +296,10 @@ impl<Block: BlockType> BitVec for BitVector<Block> {
     }
 }
 
-impl<Block: BlockType> BitVecMut for BitVector<Block> {
+impl<Block, Store> BitVecMut for BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn set_bit(&mut self, index: u64, value: bool) {
         self.0.set_bit(index, value);
     }
@@ -210,7 +310,10 @@ impl<Block: BlockType> BitVecMut for BitVector<Block> {
     }
 }
 
-impl<Block: BlockType> BitVecPush for BitVector<Block> {
+impl<Block, Store> BitVecPush for BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn push_bit(&mut self, value: bool) {
         self.0.push_bit(value);
     }
@@ -224,7 +327,10 @@ impl<Block: BlockType> BitVecPush for BitVector<Block> {
     }
 }
 
-impl<Block: BlockType> fmt::Binary for BitVector<Block> {
+impl<Block, Store> fmt::Binary for BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         for bit in self {
             let bit = if bit {"1"} else {"0"};
@@ -235,7 +341,122 @@ impl<Block: BlockType> fmt::Binary for BitVector<Block> {
     }
 }
 
-impl<Block: BlockType> SpaceUsage for BitVector<Block> {
+/// Lists the bits in order.
+impl<Block, Store> Snapshot for BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    type Item = bool;
+    type Iter<'a> = Iter<'a, Block, Store> where Self: 'a;
+
+    fn dump_iter(&self) -> Iter<'_, Block, Store> {
+        self.iter()
+    }
+}
+
+impl<Block: BlockType> Dump for BitVector<Block> {
+    fn dump(&self) -> String {
+        format!("{}:h{}", self.bit_len(), encoding::encode_hex(self))
+    }
+
+    fn dump_base64(&self) -> String {
+        format!("{}:b{}", self.bit_len(), encoding::encode_base64(self))
+    }
+
+    fn restore(text: &str) -> io::Result<Self> {
+        let who = "BitVector::restore";
+        let mut fields = text.splitn(2, ':');
+
+        let len: u64 = encoding::parse_field(who, &mut fields)?;
+        let payload = fields.next().unwrap_or("");
+
+        let block_len = match Block::checked_ceil_div_nbits(len) {
+            Some(block_len) => block_len,
+            None => return encoding::bad_dump(who, "length overflow"),
+        };
+
+        let blocks = encoding::decode_payload(who, payload, block_len)?;
+
+        match VectorBase::from_blocks(1, len, blocks) {
+            Some(base) => Ok(BitVector(base)),
+            None => encoding::bad_dump(who, "bits set past the end"),
+        }
+    }
+}
+
+/// Reads the bits in place in a buffer a `BitVector` was archived to
+/// with `rkyv` (with the `rkyv` feature).
+///
+/// # Example
+///
+/// ```
+/// # extern crate rkyv;
+/// # extern crate succinct;
+/// use succinct::{BitVec, BitVector};
+///
+/// # fn main() {
+/// let bools: Vec<bool> = (0 .. 200).map(|i| i % 3 == 0).collect();
+/// let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&BitVector::<u64>::from(&bools[..]))
+///     .unwrap();
+///
+/// let bits = rkyv::access::<rkyv::Archived<BitVector<u64>>, rkyv::rancor::Error>(&bytes)
+///     .unwrap();
+/// assert_eq!(200, bits.bit_len());
+/// assert!(bits.get_bit(99));
+/// # }
+/// ```
+#[cfg(feature = "rkyv")]
+impl<Block> BitVec for ArchivedBitVector<Block, Vec<Block>>
+    where Block: BlockType + ::rkyv::Archive,
+          ::rkyv::vec::ArchivedVec<::rkyv::Archived<Block>>: BitVec<Block = Block> {
+
+    type Block = Block;
+
+    fn bit_len(&self) -> u64 {
+        self.0.len()
+    }
+
+    fn get_block(&self, position: usize) -> Block {
+        self.0.blocks().get_block(position)
+    }
+}
+
+/// The bit length as a `u64`, then the blocks.
+impl<Block: BlockType> Persist for BitVector<Block> {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u64::<LittleEndian>(self.bit_len())?;
+        encoding::write_blocks(self, sink)
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let who = "BitVector::read_from";
+        let len = bytes.read_u64::<LittleEndian>()?;
+
+        let block_len = match Block::checked_ceil_div_nbits(len) {
+            Some(block_len) => block_len,
+            None => return encoding::bad_dump(who, "length overflow"),
+        };
+
+        let blocks = encoding::read_blocks(who, &mut bytes, block_len)?;
+        persist::expect_end(who, bytes)?;
+
+        match VectorBase::from_blocks(1, len, blocks) {
+            Some(base) => Ok(BitVector(base)),
+            None => encoding::bad_dump(who, "bits set past the end"),
+        }
+    }
+}
+
+impl<Block: BlockType> Component for BitVector<Block> {
+    fn tag() -> String {
+        format!("BitVector<u{}>", Block::nbits())
+    }
+}
+
+impl<Block, Store> SpaceUsage for BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn is_stack_only() -> bool { false }
 
     fn heap_bytes(&self) -> usize {
@@ -243,18 +464,36 @@ impl<Block: BlockType> SpaceUsage for BitVector<Block> {
     }
 }
 
-impl<Block: BlockType> Default for BitVector<Block> {
+impl<Block, Store> Default for BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn default() -> Self {
-        BitVector::new()
+        BitVector(VectorBase::new())
+    }
+}
+
+impl<'a, Block: BlockType> From<&'a [bool]> for BitVector<Block> {
+    fn from(bits: &'a [bool]) -> Self {
+        Self::from_bits(bits.iter().cloned())
+    }
+}
+
+impl<Block: BlockType> FromIterator<bool> for BitVector<Block> {
+    fn from_iter<I: IntoIterator<Item = bool>>(bits: I) -> Self {
+        Self::from_bits(bits)
     }
 }
 
 /// Iterator over `BitVector`.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Iter<'a, Block: BlockType + 'a = usize>
-    (vector_base::Iter<'a, Block>);
+pub struct Iter<'a, Block: BlockType + 'a = usize, Store: 'a = Vec<Block>>
+    (vector_base::Iter<'a, Block, Store>);
+
+impl<'a, Block, Store> Iterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
 
-impl<'a, Block: BlockType> Iterator for Iter<'a, Block> {
     type Item = bool;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -279,30 +518,118 @@ impl<'a, Block: BlockType> Iterator for Iter<'a, Block> {
 }
 
 #[cfg(target_pointer_width = "64")]
-impl<'a, Block: BlockType> ExactSizeIterator for Iter<'a, Block> {
+impl<'a, Block, Store> ExactSizeIterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn len(&self) -> usize {
         self.0.len()
     }
 }
 
-impl<'a, Block: BlockType> DoubleEndedIterator for Iter<'a, Block> {
+impl<'a, Block, Store> DoubleEndedIterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn next_back(&mut self) -> Option<Self::Item> {
         self.0.next_back().map(|bit| bit != Block::zero())
     }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.0.nth_back(n).map(|bit| bit != Block::zero())
+    }
 }
 
-impl<'a, Block: BlockType + 'a> IntoIterator for &'a BitVector<Block> {
+impl<'a, Block, Store> IntoIterator for &'a BitVector<Block, Store>
+    where Block: BlockType + 'a,
+          Store: BlockStore<Block = Block> + 'a {
+
     type Item = bool;
-    type IntoIter = Iter<'a, Block>;
+    type IntoIter = Iter<'a, Block, Store>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
+/// An iterator that moves out of a `BitVector`.
+#[derive(Clone, Debug)]
+pub struct IntoIter<Block: BlockType = usize, Store = Vec<Block>> {
+    vector: BitVector<Block, Store>,
+    start: u64,
+    limit: u64,
+}
+
+impl<Block, Store> Iterator for IntoIter<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start < self.limit {
+            self.start += 1;
+            Some(self.vector.get_bit(self.start - 1))
+        } else { None }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.limit - self.start;
+        if len > usize::MAX as u64 {
+            (usize::MAX, None)
+        } else {
+            (len as usize, Some(len as usize))
+        }
+    }
+
+    fn count(self) -> usize {
+        self.size_hint().0
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.start = self.start.saturating_add(n as u64).min(self.limit);
+        self.next()
+    }
+}
+
+#[cfg(target_pointer_width = "64")]
+impl<Block, Store> ExactSizeIterator for IntoIter<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    fn len(&self) -> usize {
+        (self.limit - self.start) as usize
+    }
+}
+
+impl<Block, Store> DoubleEndedIterator for IntoIter<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    impl_index_range_back!(Self::Item, start .. limit, |iter, index| iter.vector.get_bit(index));
+}
+
+impl<Block, Store> IntoIterator for BitVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    type Item = bool;
+    type IntoIter = IntoIter<Block, Store>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let limit = self.bit_len();
+        IntoIter { vector: self, start: 0, limit }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use bit_vec::*;
+    use quickcheck::quickcheck;
 
     macro_rules! assert_bv {
         ($expected:expr, $actual:expr) => {
@@ -326,6 +653,21 @@ mod test {
         assert_eq!(96, bit_vector.capacity());
     }
 
+    #[test]
+    fn into_shared() {
+        let mut bit_vector: BitVector<u16> = BitVector::with_fill(20, true);
+        bit_vector.set_bit(3, false);
+
+        let shared = bit_vector.clone().into_shared();
+        let mut copy = shared.clone();
+        copy.push_bit(false);
+
+        assert_bv!("11101111111111111111", shared);
+        assert_bv!("111011111111111111110", copy);
+        assert_eq!(bit_vector.iter().collect::<Vec<_>>(),
+                   shared.iter().collect::<Vec<_>>());
+    }
+
     #[test]
     fn push_binary() {
         let mut bit_vector: BitVector = BitVector::new();
@@ -490,6 +832,55 @@ mod test {
 
         bit_vector.push_block(0b11111111);
         assert_bv!("1010000011111111", bit_vector);
+
+        bit_vector.resize(19, false);
+        bit_vector.fill(true);
+        assert_bv!("1111111111111111111", bit_vector);
+        assert_eq!(0b111, bit_vector.get_block(2));
+    }
+
+    #[test]
+    fn try_constructors() {
+        use error::Error;
+
+        let bv: BitVector<u8> = BitVector::try_with_fill(13, true).unwrap();
+        assert_bv!("1111111111111", bv);
+        assert_eq!(Some(Error::Allocation),
+                   BitVector::<u8>::try_with_capacity(!0).err());
+        assert_eq!(Some(Error::Allocation),
+                   BitVector::<u64>::try_with_fill(1 << 62, false).err());
+    }
+
+    #[test]
+    fn dump_restore() {
+        use dump::Dump;
+
+        let mut bit_vector: BitVector<u16> = BitVector::new();
+        for i in 0 .. 50 {
+            bit_vector.push_bit(i % 3 == 0);
+        }
+
+        assert_eq!(bit_vector,
+                   BitVector::restore(&bit_vector.dump()).unwrap());
+        assert_eq!(bit_vector,
+                   BitVector::restore(&bit_vector.dump_base64()).unwrap());
+
+        let small: BitVector<u8> = BitVector::restore("5:h15").unwrap();
+        assert_bv!("10101", small);
+        assert_eq!("5:h15", small.dump());
+        assert!(BitVector::<u8>::restore("4:h15").is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let mut bv0: BitVector = BitVector::with_fill(200, false);
+        let bv1: BitVector = BitVector::with_fill(150, false);
+        bv0.set_bit(70, true);
+        bv0.set_bit(170, true);
+
+        let diff = bv0.diff(&bv1);
+        assert_eq!(Some(70), diff.first);
+        assert_eq!(51, diff.count);
     }
 
     #[test]
@@ -510,4 +901,70 @@ mod test {
         bit_vector.block_resize(2, 0);
         assert_bv!("1010000010100010", bit_vector);
     }
+
+    #[test]
+    fn from_bools() {
+        let bit_vector: BitVector<u8> = BitVector::from(&[true, false, true, true][..]);
+        assert_bv!("1011", bit_vector);
+        assert_eq!(0, BitVector::<u64>::from(&[][..]).bit_len());
+    }
+
+    #[test]
+    fn into_iter() {
+        let bits = [true, false, false, true, true, false, true];
+        let bit_vector: BitVector<u8> = BitVector::from(&bits[..]);
+
+        let mut iter = bit_vector.into_iter();
+        assert_eq!(7, iter.len());
+        assert_eq!(Some(true), iter.next_back());
+        assert_eq!(Some(true), iter.nth(3));
+        assert_eq!(vec![true, false], iter.collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn qc_from_iterators() {
+        fn prop(bools: Vec<bool>) -> bool {
+            let expected = BitVector::<u16>::from(&bools[..]);
+            let ones = (0 .. bools.len()).rev().filter(|&i| bools[i]).map(|i| i as u64);
+
+            BitVector::from_bits(bools.iter().cloned()) == expected
+                && bools.iter().cloned().collect::<BitVector<u16>>() == expected
+                && BitVector::from_ones(bools.len() as u64, ones) == expected
+        }
+
+        quickcheck(prop as fn(Vec<bool>) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<bool>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let bit_vector: BitVector<u8> = BitVector::from(&values[..]);
+            skips_agree(bit_vector.iter(), &values, n, step)
+                && skips_agree(bit_vector.clone().into_iter(), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<bool>, u8, u8) -> bool);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn qc_archived() {
+        use rkyv::rancor::Error;
+
+        fn prop(bits: Vec<bool>) -> bool {
+            let bit_vector: BitVector<u32> = BitVector::from(&bits[..]);
+            let bytes = rkyv::to_bytes::<Error>(&bit_vector).unwrap();
+            let archived = rkyv::access::<rkyv::Archived<BitVector<u32>>, Error>(&bytes).unwrap();
+            let back: BitVector<u32> = rkyv::deserialize::<_, Error>(archived).unwrap();
+
+            archived.bit_len() == bits.len() as u64
+                && (0 .. bits.len()).all(|i| archived.get_bit(i as u64) == bits[i])
+                && back == bit_vector
+        }
+
+        quickcheck(prop as fn(Vec<bool>) -> bool);
+    }
 }
@@ -0,0 +1,185 @@
+use std::io::{self, Error, ErrorKind};
+use std::marker::PhantomData;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use internal::unaligned;
+use space_usage::SpaceUsage;
+use storage::BlockType;
+
+/// A read-only bit vector queried in place in a byte buffer, in the
+/// layout [`BitVector`](struct.BitVector.html)’s
+/// [`Persist`](../persist/trait.Persist.html) implementation writes.
+///
+/// The buffer is anything that derefs to bytes: a `Vec<u8>`, a borrowed
+/// section of a [`Container`](../persist/struct.Container.html), a
+/// memory map, or a `bytes::Bytes` fetched from object storage. Opening
+/// checks the header and the length and nothing else, so it takes
+/// constant time; blocks are then read from the buffer as they are
+/// needed. The buffer needn’t be aligned: each block is assembled from
+/// its bytes, which is safe on targets that fault on misaligned loads.
+///
+/// Rank and select structures are generic over their bits, so a
+/// [`Rank9`](../rank/struct.Rank9.html) can be built over one of these,
+/// keeping only its counts in memory.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{BitVec, BitVector};
+/// use succinct::bit_vec::ByteBitVector;
+/// use succinct::persist::Persist;
+/// use succinct::rank::{BitRankSupport, Rank9};
+///
+/// let bools: Vec<bool> = (0 .. 200).map(|i| i % 3 == 0).collect();
+/// let bits = BitVector::<u64>::from(&bools[..]);
+/// let mut bytes = Vec::new();
+/// bits.write_to(&mut bytes).unwrap();
+///
+/// let view = ByteBitVector::<_, u64>::open(bytes).unwrap();
+/// assert_eq!(200, view.bit_len());
+/// assert!(view.get_bit(99));
+///
+/// let rank = Rank9::new(view);
+/// assert_eq!(34, rank.rank1(99));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ByteBitVector<B, Block = u64> {
+    bytes: B,
+    // The offset of the first block in `bytes`.
+    start: usize,
+    len: u64,
+    marker: PhantomData<Block>,
+}
+
+// The bit length before the blocks.
+const HEADER_BYTES: usize = 8;
+
+impl<B: AsRef<[u8]>, Block: BlockType> ByteBitVector<B, Block> {
+    /// Opens the bit vector that `BitVector::<Block>::write_to` wrote
+    /// to `bytes`.
+    ///
+    /// Fails with `ErrorKind::InvalidData` if the bytes are the wrong
+    /// length for the bit length they start with, or
+    /// `ErrorKind::UnexpectedEof` if there is no header.
+    pub fn open(bytes: B) -> io::Result<Self> {
+        let who = "ByteBitVector::open";
+        let buffer = bytes.as_ref();
+        if buffer.len() < HEADER_BYTES {
+            return Err(Error::new(ErrorKind::UnexpectedEof,
+                                  format!("{}: could not load: no header", who)));
+        }
+
+        let len = LittleEndian::read_u64(buffer);
+        Self::from_parts(who, bytes, HEADER_BYTES, len)
+    }
+
+    /// Views the `len` bits in the blocks at `start` in `bytes`, which
+    /// must run exactly to the end, with any bits past `len` clear.
+    pub(crate) fn from_parts(who: &str, bytes: B, start: usize, len: u64) -> io::Result<Self> {
+        let result = ByteBitVector { bytes, start, len, marker: PhantomData };
+
+        let expected = Block::checked_ceil_div_nbits(len)
+            .and_then(|block_len| block_len.checked_mul(unaligned::block_bytes::<Block>()))
+            .and_then(|nbytes| nbytes.checked_add(start));
+        if expected != Some(result.bytes.as_ref().len()) {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("{}: could not load: wrong length", who)));
+        }
+
+        let extra = Block::mod_nbits(len);
+        if extra > 0 {
+            let last = result.get_block(result.block_len() - 1);
+            if last >> extra != Block::zero() {
+                return Err(Error::new(ErrorKind::InvalidData,
+                                      format!("{}: could not load: bits set past the end", who)));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// The buffer.
+    pub fn bytes(&self) -> &B {
+        &self.bytes
+    }
+
+    /// Returns the buffer.
+    pub fn into_bytes(self) -> B {
+        self.bytes
+    }
+
+    /// Copies the bits into an owned bit vector.
+    pub fn to_bit_vector(&self) -> BitVector<Block> {
+        let mut result = BitVector::block_with_fill(self.block_len(), Block::zero());
+        for i in 0 .. self.block_len() {
+            result.set_block(i, self.get_block(i));
+        }
+        result.truncate(self.len);
+        result
+    }
+}
+
+impl<B: AsRef<[u8]>, Block: BlockType> BitVec for ByteBitVector<B, Block> {
+    type Block = Block;
+
+    fn bit_len(&self) -> u64 {
+        self.len
+    }
+
+    fn get_block(&self, position: usize) -> Block {
+        assert!(position < self.block_len(), "ByteBitVector::get_block: out of bounds");
+        unaligned::read_block(&self.bytes.as_ref()[self.start ..], position)
+    }
+}
+
+/// Counts the whole buffer, for whatever owns it.
+impl<B: AsRef<[u8]>, Block> SpaceUsage for ByteBitVector<B, Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.bytes.as_ref().len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use persist::Persist;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn malformed() {
+        let bits = BitVector::<u16>::with_fill(20, true);
+        let mut bytes = Vec::new();
+        bits.write_to(&mut bytes).unwrap();
+
+        assert!(ByteBitVector::<_, u16>::open(&bytes[.. 4]).is_err());
+        assert!(ByteBitVector::<_, u16>::open(&bytes[.. bytes.len() - 1]).is_err());
+        assert!(ByteBitVector::<_, u64>::open(&bytes[..]).is_err());
+
+        let last = bytes.len() - 1;
+        bytes[last] |= 0x80;
+        assert!(ByteBitVector::<_, u16>::open(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn qc_matches_owned() {
+        fn prop(bools: Vec<bool>, offset: u8) -> bool {
+            let bits = BitVector::<u32>::from(&bools[..]);
+            // Shift the section within the buffer so its blocks are
+            // misaligned.
+            let offset = offset as usize % 4;
+            let mut buffer = vec![0xFF; offset];
+            bits.write_to(&mut buffer).unwrap();
+
+            let view = ByteBitVector::<_, u32>::open(&buffer[offset ..]).unwrap();
+            view.bit_len() == bits.bit_len()
+                && (0 .. bits.block_len()).all(|i| view.get_block(i) == bits.get_block(i))
+                && view.to_bit_vector() == bits
+        }
+
+        quickcheck(prop as fn(Vec<bool>, u8) -> bool);
+    }
+}
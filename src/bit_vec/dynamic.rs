@@ -0,0 +1,729 @@
+use std::mem;
+
+use bit_vec::{BitVec, BitVecMut, BitVecPush};
+use broadword;
+use rank::{BitRankSupport, RankSupport};
+use select::{Select0Support, Select1Support};
+use space_usage::SpaceUsage;
+
+// Chunks split when they reach this many words, and merge with a
+// neighbor when both would fit in half of it.
+const CHUNK_WORDS: usize = 32;
+const CHUNK_BITS: usize = 64 * CHUNK_WORDS;
+
+// Nodes split when they have more children than this, and merge with a
+// neighbor when they have fewer than half as many.
+const MAX_CHILDREN: usize = 16;
+const MIN_CHILDREN: usize = MAX_CHILDREN / 2;
+
+#[derive(Clone, Debug, Default)]
+struct Chunk {
+    // Bits past `len` are kept 0.
+    words: Vec<u64>,
+    len: usize,
+    ones: u64,
+}
+
+impl Chunk {
+    fn get(&self, offset: usize) -> bool {
+        self.words[offset / 64] >> (offset % 64) & 1 == 1
+    }
+
+    fn set(&mut self, offset: usize, value: bool) {
+        let old = self.get(offset);
+        let mask = 1 << (offset % 64);
+        if value { self.words[offset / 64] |= mask; }
+        else { self.words[offset / 64] &= !mask; }
+        self.ones = self.ones + value as u64 - old as u64;
+    }
+
+    // The number of ones before `offset`.
+    fn rank1(&self, offset: usize) -> u64 {
+        let full: u64 = self.words[.. offset / 64].iter()
+            .map(|word| word.count_ones() as u64).sum();
+        let partial = match offset % 64 {
+            0 => 0,
+            bits => (self.words[offset / 64] << (64 - bits)).count_ones() as u64,
+        };
+        full + partial
+    }
+
+    // The offset of the `index`th bit with value `value`.
+    fn select(&self, mut index: u64, value: bool) -> Option<usize> {
+        for (i, &word) in self.words.iter().enumerate() {
+            let word = if value {word} else {!word};
+            let count = word.count_ones() as u64;
+            if index < count {
+                let offset = 64 * i + broadword::select1(index as usize, word)?;
+                return if offset < self.len {Some(offset)} else {None};
+            }
+            index -= count;
+        }
+        None
+    }
+
+    fn insert(&mut self, offset: usize, value: bool) {
+        if self.len % 64 == 0 { self.words.push(0); }
+
+        let word = offset / 64;
+        for i in (word + 1 .. self.words.len()).rev() {
+            self.words[i] = self.words[i] << 1 | self.words[i - 1] >> 63;
+        }
+
+        let low = low_mask(offset % 64);
+        let old = self.words[word];
+        self.words[word] = old & low | (old & !low) << 1
+            | (value as u64) << (offset % 64);
+
+        self.len += 1;
+        self.ones += value as u64;
+    }
+
+    fn remove(&mut self, offset: usize) -> bool {
+        let value = self.get(offset);
+
+        let word = offset / 64;
+        let low = low_mask(offset % 64);
+        let old = self.words[word];
+        self.words[word] = old & low | (old >> 1) & !low;
+        for i in word + 1 .. self.words.len() {
+            self.words[i - 1] |= self.words[i] << 63;
+            self.words[i] >>= 1;
+        }
+
+        self.len -= 1;
+        self.ones -= value as u64;
+        if self.len % 64 == 0 { self.words.pop(); }
+        value
+    }
+
+    // Moves the second half of the words to a new chunk.
+    fn split(&mut self) -> Chunk {
+        let half = self.words.len() / 2;
+        let words = self.words.split_off(half);
+        let len = self.len - 64 * half;
+        let ones = words.iter().map(|word| word.count_ones() as u64).sum();
+
+        self.len -= len;
+        self.ones -= ones;
+        Chunk { words, len, ones }
+    }
+
+    fn append(&mut self, other: &Chunk) {
+        for offset in 0 .. other.len {
+            let len = self.len;
+            self.insert(len, other.get(offset));
+        }
+    }
+}
+
+fn low_mask(bits: usize) -> u64 {
+    if bits == 0 {0} else {!0 >> (64 - bits)}
+}
+
+// The lengths and ones counts that a node keeps of its children.
+trait Summary {
+    fn bits(&self) -> u64;
+    fn ones(&self) -> u64;
+
+    fn count(&self, value: bool) -> u64 {
+        if value {self.ones()} else {self.bits() - self.ones()}
+    }
+}
+
+impl Summary for Chunk {
+    fn bits(&self) -> u64 { self.len as u64 }
+    fn ones(&self) -> u64 { self.ones }
+}
+
+impl Summary for Node {
+    fn bits(&self) -> u64 { self.len }
+    fn ones(&self) -> u64 { self.ones }
+}
+
+// The child holding `position`, and the position within it; a position
+// at the end belongs to the last child.
+fn find<T: Summary>(children: &[T], position: u64) -> (usize, u64) {
+    let mut position = position;
+    let last = children.len() - 1;
+    for (i, child) in children[.. last].iter().enumerate() {
+        if position < child.bits() { return (i, position); }
+        position -= child.bits();
+    }
+    (last, position)
+}
+
+// The child holding the `index`th bit with value `value`, the index of
+// that bit among the child’s, and the position where the child starts.
+fn find_count<T: Summary>(children: &[T], index: u64, value: bool)
+                          -> Option<(usize, u64, u64)> {
+    let mut index = index;
+    let mut start = 0;
+    for (i, child) in children.iter().enumerate() {
+        let count = child.count(value);
+        if index < count { return Some((i, index, start)); }
+        index -= count;
+        start += child.bits();
+    }
+    None
+}
+
+fn ones_before<T: Summary>(children: &[T], end: usize) -> u64 {
+    children[.. end].iter().map(Summary::ones).sum()
+}
+
+// Splits `items` into as few runs of at most `MAX_CHILDREN` as will do,
+// as even as possible, so that each has at least `MIN_CHILDREN` if there
+// is more than one.
+fn groups<T>(items: Vec<T>) -> Vec<Vec<T>> {
+    let count = items.len().div_ceil(MAX_CHILDREN).max(1);
+    let mut items = items.into_iter();
+    (0 .. count).map(|i| {
+        let size = items.len() / (count - i);
+        items.by_ref().take(size).collect()
+    }).collect()
+}
+
+// A node of the tree. Its children are all chunks or all nodes, and
+// every chunk is at the same depth.
+#[derive(Clone, Debug)]
+struct Node {
+    len: u64,
+    ones: u64,
+    children: Children,
+}
+
+#[derive(Clone, Debug)]
+enum Children {
+    Chunks(Vec<Chunk>),
+    Nodes(Vec<Node>),
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node::from_chunks(Vec::new())
+    }
+}
+
+impl Node {
+    fn from_chunks(chunks: Vec<Chunk>) -> Self {
+        Node {
+            len: chunks.iter().map(Summary::bits).sum(),
+            ones: ones_before(&chunks, chunks.len()),
+            children: Children::Chunks(chunks),
+        }
+    }
+
+    fn from_nodes(nodes: Vec<Node>) -> Self {
+        Node {
+            len: nodes.iter().map(Summary::bits).sum(),
+            ones: ones_before(&nodes, nodes.len()),
+            children: Children::Nodes(nodes),
+        }
+    }
+
+    // Builds a tree with `chunks` as its leaves, as shallow as the
+    // bounds on children allow.
+    fn build(chunks: Vec<Chunk>) -> Self {
+        let mut nodes: Vec<Node> =
+            groups(chunks).into_iter().map(Node::from_chunks).collect();
+        while nodes.len() > 1 {
+            nodes = groups(nodes).into_iter().map(Node::from_nodes).collect();
+        }
+        nodes.pop().unwrap_or_default()
+    }
+
+    fn degree(&self) -> usize {
+        match self.children {
+            Children::Chunks(ref chunks) => chunks.len(),
+            Children::Nodes(ref nodes) => nodes.len(),
+        }
+    }
+
+    fn get(&self, position: u64) -> bool {
+        match self.children {
+            Children::Chunks(ref chunks) => {
+                let (i, offset) = find(chunks, position);
+                chunks[i].get(offset as usize)
+            }
+            Children::Nodes(ref nodes) => {
+                let (i, offset) = find(nodes, position);
+                nodes[i].get(offset)
+            }
+        }
+    }
+
+    // Sets the bit at `position`, returning the old value.
+    fn set(&mut self, position: u64, value: bool) -> bool {
+        let old = match self.children {
+            Children::Chunks(ref mut chunks) => {
+                let (i, offset) = find(chunks, position);
+                let old = chunks[i].get(offset as usize);
+                chunks[i].set(offset as usize, value);
+                old
+            }
+            Children::Nodes(ref mut nodes) => {
+                let (i, offset) = find(nodes, position);
+                nodes[i].set(offset, value)
+            }
+        };
+        self.ones = self.ones + value as u64 - old as u64;
+        old
+    }
+
+    // The number of ones before `end`, which must be less than `len`.
+    fn rank1(&self, end: u64) -> u64 {
+        match self.children {
+            Children::Chunks(ref chunks) => {
+                let (i, offset) = find(chunks, end);
+                ones_before(chunks, i) + chunks[i].rank1(offset as usize)
+            }
+            Children::Nodes(ref nodes) => {
+                let (i, offset) = find(nodes, end);
+                ones_before(nodes, i) + nodes[i].rank1(offset)
+            }
+        }
+    }
+
+    fn select(&self, index: u64, value: bool) -> Option<u64> {
+        match self.children {
+            Children::Chunks(ref chunks) => {
+                let (i, index, start) = find_count(chunks, index, value)?;
+                chunks[i].select(index, value).map(|offset| start + offset as u64)
+            }
+            Children::Nodes(ref nodes) => {
+                let (i, index, start) = find_count(nodes, index, value)?;
+                nodes[i].select(index, value).map(|offset| start + offset)
+            }
+        }
+    }
+
+    // Inserts `value` before `position`, returning a new right sibling
+    // if this node had to split.
+    fn insert(&mut self, position: u64, value: bool) -> Option<Node> {
+        match self.children {
+            Children::Chunks(ref mut chunks) => {
+                if chunks.is_empty() { chunks.push(Chunk::default()); }
+                let (i, offset) = find(chunks, position);
+                chunks[i].insert(offset as usize, value);
+                if chunks[i].len == CHUNK_BITS {
+                    let rest = chunks[i].split();
+                    chunks.insert(i + 1, rest);
+                }
+            }
+            Children::Nodes(ref mut nodes) => {
+                let (i, offset) = find(nodes, position);
+                if let Some(rest) = nodes[i].insert(offset, value) {
+                    nodes.insert(i + 1, rest);
+                }
+            }
+        }
+
+        self.len += 1;
+        self.ones += value as u64;
+        if self.degree() > MAX_CHILDREN {Some(self.split())} else {None}
+    }
+
+    // Removes and returns the bit at `position`. Children left too small
+    // are merged with a neighbor, but this node may be left small itself.
+    fn remove(&mut self, position: u64) -> bool {
+        let value = match self.children {
+            Children::Chunks(ref mut chunks) => {
+                let (i, offset) = find(chunks, position);
+                let value = chunks[i].remove(offset as usize);
+
+                if chunks[i].len == 0 {
+                    chunks.remove(i);
+                } else if i + 1 < chunks.len()
+                    && chunks[i].len + chunks[i + 1].len <= CHUNK_BITS / 2 {
+                    let next = chunks.remove(i + 1);
+                    chunks[i].append(&next);
+                } else if i > 0
+                    && chunks[i - 1].len + chunks[i].len <= CHUNK_BITS / 2 {
+                    let this = chunks.remove(i);
+                    chunks[i - 1].append(&this);
+                }
+                value
+            }
+            Children::Nodes(ref mut nodes) => {
+                let (i, offset) = find(nodes, position);
+                let value = nodes[i].remove(offset);
+                if nodes[i].degree() < MIN_CHILDREN && nodes.len() > 1 {
+                    // Merge with a neighbor, then split again if that
+                    // makes too many children.
+                    let left = if i + 1 < nodes.len() {i} else {i - 1};
+                    let right = nodes.remove(left + 1);
+                    nodes[left].absorb(right);
+                    if nodes[left].degree() > MAX_CHILDREN {
+                        let rest = nodes[left].split();
+                        nodes.insert(left + 1, rest);
+                    }
+                }
+                value
+            }
+        };
+
+        self.len -= 1;
+        self.ones -= value as u64;
+        value
+    }
+
+    // Moves the second half of the children to a new node.
+    fn split(&mut self) -> Node {
+        let rest = match self.children {
+            Children::Chunks(ref mut chunks) => {
+                let half = chunks.len() / 2;
+                Node::from_chunks(chunks.split_off(half))
+            }
+            Children::Nodes(ref mut nodes) => {
+                let half = nodes.len() / 2;
+                Node::from_nodes(nodes.split_off(half))
+            }
+        };
+        self.len -= rest.len;
+        self.ones -= rest.ones;
+        rest
+    }
+
+    // Appends the children of `other`, a node at the same depth.
+    fn absorb(&mut self, other: Node) {
+        self.len += other.len;
+        self.ones += other.ones;
+        match (&mut self.children, other.children) {
+            (&mut Children::Chunks(ref mut chunks), Children::Chunks(more)) =>
+                chunks.extend(more),
+            (&mut Children::Nodes(ref mut nodes), Children::Nodes(more)) =>
+                nodes.extend(more),
+            _ => unreachable!("DynamicBitVector: leaves at different depths"),
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self.children {
+            Children::Chunks(ref chunks) =>
+                chunks.iter().map(|chunk| chunk.words.heap_bytes()).sum::<usize>()
+                    + chunks.capacity() * mem::size_of::<Chunk>(),
+            Children::Nodes(ref nodes) =>
+                nodes.iter().map(Node::heap_bytes).sum::<usize>()
+                    + nodes.capacity() * mem::size_of::<Node>(),
+        }
+    }
+}
+
+/// A bit vector that supports inserting and removing bits anywhere, as
+/// well as rank and select.
+///
+/// The bits are kept in chunks of up to 2048, each knowing how many
+/// ones it holds; edits shift bits within one chunk, splitting or
+/// merging as needed. The chunks are the leaves of a B-tree whose nodes
+/// record the length and the ones of each subtree, so finding a
+/// position, or the *k*th one or zero, descends the tree: every
+/// operation takes *O*(log *n*) time, plus a chunk’s worth of word
+/// operations. This is what the levels of a
+/// [`DynamicWaveletMatrix`](../wavelet/struct.DynamicWaveletMatrix.html)
+/// are made of.
+///
+/// # Example
+///
+/// ```
+/// use succinct::BitVec;
+/// use succinct::bit_vec::DynamicBitVector;
+/// use succinct::rank::BitRankSupport;
+/// use succinct::select::Select1Support;
+///
+/// let mut bits = DynamicBitVector::new();
+/// bits.insert(0, true);
+/// bits.insert(0, false);
+/// bits.insert(1, true);
+///
+/// assert_eq!(3, bits.bit_len());
+/// assert!(!bits.get_bit(0));
+/// assert_eq!(2, bits.rank1(2));
+/// assert_eq!(Some(2), bits.select1(1));
+///
+/// assert!(bits.remove(1));
+/// assert_eq!(1, bits.rank1(1));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DynamicBitVector {
+    root: Node,
+}
+
+impl DynamicBitVector {
+    /// Creates an empty bit vector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a bit vector holding the given bits.
+    ///
+    /// The tree is built bottom up, in linear time, with its chunks half
+    /// full to leave room for insertions.
+    pub fn from_bits<I>(bits: I) -> Self
+        where I: IntoIterator<Item = bool> {
+
+        let mut chunks = Vec::new();
+        let mut chunk = Chunk::default();
+        for bit in bits {
+            if chunk.len == CHUNK_BITS / 2 {
+                chunks.push(mem::take(&mut chunk));
+            }
+            let len = chunk.len;
+            chunk.insert(len, bit);
+        }
+        if chunk.len > 0 { chunks.push(chunk); }
+
+        DynamicBitVector { root: Node::build(chunks) }
+    }
+
+    /// The number of ones.
+    pub fn count_ones(&self) -> u64 {
+        self.root.ones
+    }
+
+    /// Inserts `value` before position `position`, or at the end if
+    /// `position == self.bit_len()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.bit_len()`.
+    pub fn insert(&mut self, position: u64, value: bool) {
+        assert!(position <= self.bit_len(), "DynamicBitVector::insert: out of bounds");
+
+        if let Some(rest) = self.root.insert(position, value) {
+            let left = mem::take(&mut self.root);
+            self.root = Node::from_nodes(vec![left, rest]);
+        }
+    }
+
+    /// Removes and returns the bit at `position`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position >= self.bit_len()`.
+    pub fn remove(&mut self, position: u64) -> bool {
+        assert!(position < self.bit_len(), "DynamicBitVector::remove: out of bounds");
+
+        let value = self.root.remove(position);
+        // A root left with one child gives way to it.
+        loop {
+            let child = match self.root.children {
+                Children::Nodes(ref mut nodes) if nodes.len() == 1 => nodes.pop(),
+                _ => None,
+            };
+            match child {
+                Some(child) => self.root = child,
+                None => break,
+            }
+        }
+        value
+    }
+
+    /// Removes every bit.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// The number of ones before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.bit_len()`.
+    pub fn rank1_before(&self, end: u64) -> u64 {
+        assert!(end <= self.bit_len(), "DynamicBitVector::rank1_before: out of bounds");
+        if end == self.bit_len() {self.root.ones} else {self.root.rank1(end)}
+    }
+
+    fn select(&self, index: u64, value: bool) -> Option<u64> {
+        self.root.select(index, value)
+    }
+}
+
+impl BitVec for DynamicBitVector {
+    type Block = u64;
+
+    fn bit_len(&self) -> u64 {
+        self.root.len
+    }
+
+    fn get_bit(&self, position: u64) -> bool {
+        assert!(position < self.bit_len(), "DynamicBitVector::get_bit: out of bounds");
+        self.root.get(position)
+    }
+}
+
+impl BitVecMut for DynamicBitVector {
+    fn set_bit(&mut self, position: u64, value: bool) {
+        assert!(position < self.bit_len(), "DynamicBitVector::set_bit: out of bounds");
+        self.root.set(position, value);
+    }
+}
+
+impl BitVecPush for DynamicBitVector {
+    fn push_bit(&mut self, value: bool) {
+        let len = self.bit_len();
+        self.insert(len, value);
+    }
+
+    fn pop_bit(&mut self) -> Option<bool> {
+        if self.bit_len() == 0 { return None; }
+        let last = self.bit_len() - 1;
+        Some(self.remove(last))
+    }
+}
+
+impl RankSupport for DynamicBitVector {
+    type Over = bool;
+
+    fn rank(&self, position: u64, value: bool) -> u64 {
+        if value {self.rank1(position)} else {self.rank0(position)}
+    }
+
+    fn limit(&self) -> u64 {
+        self.bit_len()
+    }
+}
+
+impl BitRankSupport for DynamicBitVector {
+    fn rank1(&self, position: u64) -> u64 {
+        assert!(position < self.bit_len(), "DynamicBitVector::rank1: out of bounds");
+        self.rank1_before(position + 1)
+    }
+}
+
+impl Select1Support for DynamicBitVector {
+    fn select1(&self, index: u64) -> Option<u64> {
+        self.select(index, true)
+    }
+}
+
+impl Select0Support for DynamicBitVector {
+    fn select0(&self, index: u64) -> Option<u64> {
+        self.select(index, false)
+    }
+}
+
+impl SpaceUsage for DynamicBitVector {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.root.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let mut bits = DynamicBitVector::new();
+        assert_eq!(0, bits.bit_len());
+        assert_eq!(0, bits.rank1_before(0));
+        assert_eq!(None, bits.select1(0));
+        assert_eq!(None, bits.pop_bit());
+    }
+
+    // Checks the summaries and the bounds on children below `node`,
+    // returning its height.
+    fn check(node: &Node, root: bool) -> usize {
+        assert!(node.degree() <= MAX_CHILDREN);
+        assert!(root || node.degree() >= MIN_CHILDREN);
+        let (height, len, ones) = match node.children {
+            Children::Chunks(ref chunks) => {
+                assert!(chunks.iter().all(|chunk| chunk.len > 0 && chunk.len < CHUNK_BITS));
+                (0, chunks.iter().map(Summary::bits).sum(), ones_before(chunks, chunks.len()))
+            }
+            Children::Nodes(ref nodes) => {
+                let heights: Vec<usize> = nodes.iter().map(|node| check(node, false)).collect();
+                assert!(heights.windows(2).all(|pair| pair[0] == pair[1]));
+                (heights[0] + 1, nodes.iter().map(Summary::bits).sum(), ones_before(nodes, nodes.len()))
+            }
+        };
+        assert_eq!((node.len, node.ones), (len, ones));
+        height
+    }
+
+    #[test]
+    fn split_and_merge() {
+        let mut bits = DynamicBitVector::from_bits((0 .. 10_000).map(|i| i % 3 == 0));
+        assert!(bits.root.degree() > 4);
+        assert_eq!(3334, bits.count_ones());
+        assert_eq!(Some(2999), bits.select0(1999));
+
+        for _ in 0 .. 9_990 {
+            bits.remove(5);
+        }
+        assert_eq!(1, bits.root.degree());
+        assert_eq!(vec![true, false, false, true, false, false, true, false, false, true],
+                   (0 .. 10).map(|i| bits.get_bit(i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn grow_and_shrink() {
+        let mut bits = DynamicBitVector::from_bits((0 .. 300_000).map(|i| i % 5 == 0));
+        assert_eq!(2, check(&bits.root, true));
+
+        for i in 0 .. 300_000 {
+            bits.insert(i * 7 % (bits.bit_len() + 1), true);
+        }
+        assert_eq!(360_000, bits.count_ones());
+        assert!(check(&bits.root, true) >= 2);
+        let position = bits.select0(100_000).unwrap();
+        assert_eq!(100_000, position - bits.rank1_before(position));
+
+        for i in 0 .. 599_000 {
+            let position = i * 13 % bits.bit_len();
+            bits.remove(position);
+            if i % 50_000 == 0 { check(&bits.root, true); }
+        }
+        assert_eq!(1000, bits.bit_len());
+        assert_eq!(0, check(&bits.root, true));
+        assert_eq!(bits.count_ones(), bits.rank1_before(1000));
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_oob() {
+        DynamicBitVector::new().insert(1, true);
+    }
+
+    #[test]
+    fn qc_matches_vec() {
+        // Inserts where the flag is set, removes otherwise.
+        fn prop(ops: Vec<(bool, u16, bool)>) -> bool {
+            let mut bits = DynamicBitVector::new();
+            let mut naive = Vec::new();
+
+            for (insert, position, value) in ops.into_iter().flat_map(|op| vec![op; 40]) {
+                if insert || naive.is_empty() {
+                    let position = position as usize % (naive.len() + 1);
+                    naive.insert(position, value);
+                    bits.insert(position as u64, value);
+                } else {
+                    let position = position as usize % naive.len();
+                    if naive.remove(position) != bits.remove(position as u64) {
+                        return false;
+                    }
+                }
+            }
+
+            let ones: Vec<u64> = (0 .. naive.len() as u64)
+                .filter(|&i| naive[i as usize]).collect();
+            let zeros: Vec<u64> = (0 .. naive.len() as u64)
+                .filter(|&i| !naive[i as usize]).collect();
+
+            bits.bit_len() == naive.len() as u64
+                && (0 .. naive.len()).all(|i| bits.get_bit(i as u64) == naive[i])
+                && (0 .. naive.len() + 1).all(|i| {
+                    bits.rank1_before(i as u64)
+                        == naive[.. i].iter().filter(|&&b| b).count() as u64
+                })
+                && (0 .. ones.len() + 1).all(|k| bits.select1(k as u64) == ones.get(k).cloned())
+                && (0 .. zeros.len() + 1).all(|k| bits.select0(k as u64) == zeros.get(k).cloned())
+        }
+
+        quickcheck(prop as fn(Vec<(bool, u16, bool)>) -> bool);
+    }
+}
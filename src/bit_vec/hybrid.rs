@@ -0,0 +1,396 @@
+use bit_vec::{BitVecMut, BitVector};
+use error::Error;
+use int_vec::{EliasFano, IntVec};
+use internal::search::binary_search_function;
+use rank::{BitRankSupport, Rank9, RankSupport};
+use select::{Select0Support, Select1Support, Select9};
+use space_usage::SpaceUsage;
+
+/// The bits of universe each chunk covers.
+const CHUNK_BITS: usize = 16;
+
+const CHUNK_SIZE: u64 = 1 << CHUNK_BITS;
+
+/// How a chunk of a [`HybridBitmap`](struct.HybridBitmap.html) is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChunkKind {
+    /// All 2<sup>16</sup> bits, with a rank and select directory.
+    Plain,
+    /// The runs of ones, as start and end offsets.
+    Runs,
+    /// The offsets of the ones, in Elias–Fano coding.
+    Sparse,
+}
+
+/// A compressed bitmap that stores each 2<sup>16</sup>-bit chunk of its
+/// universe whichever way is smallest, with rank and select over the
+/// whole.
+///
+/// Like a Roaring bitmap, empty chunks take no space, dense ones are
+/// plain bits, clustered ones are runs, and sparse ones are lists of
+/// offsets, here in Elias–Fano coding. A directory records the ones
+/// before each chunk, so `rank` is a binary search over the chunks and a
+/// rank within one, and `select` the same over the counts.
+///
+/// # Example
+///
+/// ```
+/// use succinct::bit_vec::{ChunkKind, HybridBitmap};
+/// use succinct::rank::BitRankSupport;
+/// use succinct::select::Select1Support;
+///
+/// let positions = (0 .. 40_000).chain((100_000 .. 130_000).step_by(1000))
+///                              .chain((200_000 .. 300_000).step_by(2));
+/// let bitmap = HybridBitmap::new(1 << 20, positions);
+///
+/// assert_eq!(Some(ChunkKind::Runs), bitmap.chunk_kind(5));
+/// assert_eq!(Some(ChunkKind::Sparse), bitmap.chunk_kind(100_000));
+/// assert_eq!(Some(ChunkKind::Plain), bitmap.chunk_kind(250_000));
+/// assert_eq!(None, bitmap.chunk_kind(500_000));
+///
+/// assert_eq!(40_001, bitmap.rank1(100_000));
+/// assert_eq!(Some(101_000), bitmap.select1(40_001));
+/// assert!(bitmap.get(200_004));
+/// ```
+#[derive(Clone, Debug)]
+pub struct HybridBitmap {
+    universe: u64,
+    // The numbers of the nonempty chunks, and the ones before each, with
+    // the total at the end.
+    keys: Vec<u64>,
+    ranks: Vec<u64>,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Clone, Debug)]
+enum Chunk {
+    Plain(Select9<BitVector<u64>>),
+    // Inclusive start and end offsets, and the ones before each run.
+    Runs(Vec<(u16, u16)>, Vec<u32>),
+    Sparse(EliasFano),
+}
+
+impl Chunk {
+    // Picks the smallest representation of the sorted `offsets`.
+    fn new(offsets: &[u16]) -> Self {
+        let mut runs: Vec<(u16, u16)> = Vec::new();
+        for &offset in offsets {
+            match runs.last_mut() {
+                Some(run) if run.1 as u32 + 1 == offset as u32 => run.1 = offset,
+                _ => runs.push((offset, offset)),
+            }
+        }
+
+        let plain_bytes = BitVector::<u64>::estimate_size(CHUNK_SIZE)
+            + Rank9::estimate_size(CHUNK_SIZE);
+        let runs_bytes = 8 * runs.len();
+        let sparse_bytes = EliasFano::estimate_size(CHUNK_SIZE, offsets.len() as u64);
+
+        if runs_bytes <= sparse_bytes && runs_bytes <= plain_bytes {
+            let mut before = Vec::with_capacity(runs.len());
+            let mut ones = 0;
+            for &(start, end) in &runs {
+                before.push(ones);
+                ones += (end - start) as u32 + 1;
+            }
+            Chunk::Runs(runs, before)
+        } else if sparse_bytes <= plain_bytes {
+            Chunk::Sparse(EliasFano::new(CHUNK_SIZE, offsets.iter().map(|&offset| offset as u64)))
+        } else {
+            let mut bits = BitVector::with_fill(CHUNK_SIZE, false);
+            for &offset in offsets {
+                bits.set_bit(offset as u64, true);
+            }
+            Chunk::Plain(Select9::new(Rank9::new(bits)))
+        }
+    }
+
+    fn kind(&self) -> ChunkKind {
+        match *self {
+            Chunk::Plain(_) => ChunkKind::Plain,
+            Chunk::Runs(..) => ChunkKind::Runs,
+            Chunk::Sparse(_) => ChunkKind::Sparse,
+        }
+    }
+
+    // The ones at or before `offset`.
+    fn rank1(&self, offset: u64) -> u64 {
+        match *self {
+            Chunk::Plain(ref bits) => bits.rank1(offset),
+            Chunk::Runs(ref runs, ref before) => {
+                let run = runs.partition_point(|&(start, _)| start as u64 <= offset);
+                if run == 0 { return 0; }
+                let (start, end) = runs[run - 1];
+                before[run - 1] as u64 + offset.min(end as u64) - start as u64 + 1
+            }
+            Chunk::Sparse(ref ef) => ef.count_less(offset + 1),
+        }
+    }
+
+    // The offset of the `index`th one, which must exist.
+    fn select1(&self, index: u64) -> u64 {
+        match *self {
+            Chunk::Plain(ref bits) => bits.select1(index).expect("HybridBitmap: bad directory"),
+            Chunk::Runs(ref runs, ref before) => {
+                let run = before.partition_point(|&ones| ones as u64 <= index) - 1;
+                runs[run].0 as u64 + index - before[run] as u64
+            }
+            Chunk::Sparse(ref ef) => ef.get(index),
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match *self {
+            Chunk::Plain(ref bits) => bits.heap_bytes(),
+            Chunk::Runs(ref runs, ref before) => runs.heap_bytes() + before.heap_bytes(),
+            Chunk::Sparse(ref ef) => ef.heap_bytes(),
+        }
+    }
+}
+
+impl HybridBitmap {
+    /// Creates a bitmap of `universe` bits with ones at `positions`,
+    /// which must be strictly increasing and less than `universe`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the positions are out of order or out of range.
+    pub fn new<I>(universe: u64, positions: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        Self::try_new(universe, positions).expect("HybridBitmap::new")
+    }
+
+    /// Creates a bitmap holding the bits `bits` yields, in order.
+    pub fn from_bits<I>(bits: I) -> Self
+        where I: IntoIterator<Item = bool> {
+
+        // Only the ones’ positions are kept, and the length must be
+        // known before the chunks are built.
+        let mut universe = 0;
+        let mut positions = Vec::new();
+        for bit in bits {
+            if bit { positions.push(universe); }
+            universe += 1;
+        }
+        HybridBitmap::new(universe, positions)
+    }
+
+    /// Creates a bitmap of `universe` bits with ones at `positions`, or
+    /// returns `Error::NotIncreasing` if they aren’t strictly increasing,
+    /// or `Error::ValueTooLarge` if one isn’t less than `universe`.
+    pub fn try_new<I>(universe: u64, positions: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = u64> {
+
+        let mut result = HybridBitmap {
+            universe,
+            keys: Vec::new(),
+            ranks: vec![0],
+            chunks: Vec::new(),
+        };
+
+        let mut offsets: Vec<u16> = Vec::new();
+        let mut last = None;
+        for position in positions {
+            if position >= universe { return Err(Error::ValueTooLarge); }
+            if last.is_some_and(|last| position <= last) { return Err(Error::NotIncreasing); }
+            last = Some(position);
+
+            let key = position >> CHUNK_BITS;
+            if result.keys.last() != Some(&key) {
+                result.finish_chunk(&mut offsets);
+                result.keys.push(key);
+            }
+            offsets.push(position as u16);
+        }
+        result.finish_chunk(&mut offsets);
+        Ok(result)
+    }
+
+    fn finish_chunk(&mut self, offsets: &mut Vec<u16>) {
+        if offsets.is_empty() { return; }
+        let total = *self.ranks.last().unwrap() + offsets.len() as u64;
+        self.chunks.push(Chunk::new(offsets));
+        self.ranks.push(total);
+        offsets.clear();
+    }
+
+    /// The number of bits.
+    pub fn universe(&self) -> u64 {
+        self.universe
+    }
+
+    /// The number of ones.
+    pub fn count_ones(&self) -> u64 {
+        *self.ranks.last().unwrap()
+    }
+
+    /// The number of nonempty chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// How the chunk holding `position` is stored, or `None` if it is
+    /// empty.
+    pub fn chunk_kind(&self, position: u64) -> Option<ChunkKind> {
+        self.keys.binary_search(&(position >> CHUNK_BITS)).ok()
+            .map(|chunk| self.chunks[chunk].kind())
+    }
+
+    /// Is the bit at `position` set?
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position` is out of bounds.
+    pub fn get(&self, position: u64) -> bool {
+        let ones = self.rank1(position);
+        ones > 0 && (position == 0 || self.rank1(position - 1) < ones)
+    }
+}
+
+impl Default for HybridBitmap {
+    /// An empty bitmap over an empty universe.
+    fn default() -> Self {
+        HybridBitmap::new(0, vec![])
+    }
+}
+
+impl<'a> From<&'a [bool]> for HybridBitmap {
+    fn from(bits: &'a [bool]) -> Self {
+        Self::from_bits(bits.iter().cloned())
+    }
+}
+
+impl RankSupport for HybridBitmap {
+    type Over = bool;
+
+    fn rank(&self, position: u64, value: bool) -> u64 {
+        if value {self.rank1(position)} else {self.rank0(position)}
+    }
+
+    fn limit(&self) -> u64 {
+        self.universe
+    }
+}
+
+impl BitRankSupport for HybridBitmap {
+    fn rank1(&self, position: u64) -> u64 {
+        assert!(position < self.universe, "HybridBitmap::rank1: out of bounds");
+
+        let key = position >> CHUNK_BITS;
+        let chunk = self.keys.partition_point(|&k| k <= key);
+        if chunk > 0 && self.keys[chunk - 1] == key {
+            self.ranks[chunk - 1] + self.chunks[chunk - 1].rank1(position % CHUNK_SIZE)
+        } else {
+            self.ranks[chunk]
+        }
+    }
+}
+
+impl Select1Support for HybridBitmap {
+    fn select1(&self, index: u64) -> Option<u64> {
+        if index >= self.count_ones() { return None; }
+
+        let chunk = self.ranks.partition_point(|&ones| ones <= index) - 1;
+        let offset = self.chunks[chunk].select1(index - self.ranks[chunk]);
+        Some(self.keys[chunk] << CHUNK_BITS | offset)
+    }
+}
+
+impl Select0Support for HybridBitmap {
+    fn select0(&self, index: u64) -> Option<u64> {
+        binary_search_function(0, self.universe, index + 1, |position| self.rank0(position))
+    }
+}
+
+impl SpaceUsage for HybridBitmap {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.keys.heap_bytes() + self.ranks.heap_bytes()
+            + self.chunks.iter().map(Chunk::heap_bytes).sum::<usize>()
+            + self.chunks.capacity() * ::std::mem::size_of::<Chunk>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let bitmap = HybridBitmap::new(10, vec![]);
+        assert_eq!(0, bitmap.rank1(9));
+        assert_eq!(None, bitmap.select1(0));
+        assert_eq!(Some(3), bitmap.select0(3));
+        assert_eq!(0, bitmap.chunk_count());
+        assert_eq!(0, HybridBitmap::default().universe());
+
+        let bitmap = HybridBitmap::from(&[false, true, true, false][..]);
+        assert_eq!((4, 2), (bitmap.universe(), bitmap.count_ones()));
+        assert_eq!(Some(2), bitmap.select1(1));
+    }
+
+    #[test]
+    fn bad_positions() {
+        assert_eq!(Some(Error::NotIncreasing), HybridBitmap::try_new(10, vec![3, 3]).err());
+        assert_eq!(Some(Error::ValueTooLarge), HybridBitmap::try_new(10, vec![10]).err());
+    }
+
+    #[test]
+    fn from_bits() {
+        let bits = (0 .. 100_000u64).map(|i| i % 7 == 2 || (40_000 .. 70_000).contains(&i));
+        let bitmap = HybridBitmap::from_bits(bits);
+        assert_eq!(100_000, bitmap.rank1(99_999) + bitmap.rank0(99_999));
+        assert_eq!(Some(2), bitmap.select1(0));
+        assert!(bitmap.get(50_000) && !bitmap.get(70_001));
+        assert_eq!(0, HybridBitmap::from_bits(None).count_ones());
+    }
+
+    #[test]
+    fn small_for_its_kind() {
+        let runs = HybridBitmap::new(1 << 24, 0 .. 1 << 23);
+        assert_eq!(Some(ChunkKind::Runs), runs.chunk_kind(1 << 20));
+        assert!(runs.heap_bytes() < 128 * 256);
+        let dense = HybridBitmap::new(1 << 20, (0 .. 1 << 20).step_by(2));
+        assert_eq!(Some(ChunkKind::Plain), dense.chunk_kind(1 << 19));
+        assert!(dense.heap_bytes() < (1 << 20) / 8 * 3 / 2);
+    }
+
+    #[test]
+    fn qc_matches_set() {
+        fn prop(parts: Vec<(u8, u16, u8)>, probes: Vec<u32>) -> bool {
+            // Each part is a chunk number, a start, and a pattern.
+            let mut set = BTreeSet::new();
+            for (chunk, start, pattern) in parts {
+                let base = (chunk as u64 % 8) << CHUNK_BITS;
+                let (step, count) = match pattern % 3 {
+                    0 => (1, 3000),
+                    1 => (3, 20_000),
+                    _ => (997, 30),
+                };
+                for i in 0 .. count {
+                    let offset = start as u64 + i * step;
+                    if offset < CHUNK_SIZE { set.insert(base + offset); }
+                }
+            }
+
+            let universe = 8 << CHUNK_BITS;
+            let bitmap = HybridBitmap::new(universe, set.iter().cloned());
+            let sorted: Vec<u64> = set.iter().cloned().collect();
+
+            bitmap.count_ones() == sorted.len() as u64
+                && sorted.iter().enumerate().all(|(i, &p)| bitmap.select1(i as u64) == Some(p))
+                && probes.into_iter().all(|probe| {
+                    let position = probe as u64 % universe;
+                    let rank = set.range(.. position + 1).count() as u64;
+                    bitmap.rank1(position) == rank
+                        && bitmap.get(position) == set.contains(&position)
+                })
+        }
+
+        quickcheck(prop as fn(Vec<(u8, u16, u8)>, Vec<u32>) -> bool);
+    }
+}
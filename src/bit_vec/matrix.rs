@@ -0,0 +1,344 @@
+use num_traits::ToPrimitive;
+
+use bit_vec::{BitSlice, BitVec, BitVecMut, BitVector};
+use broadword;
+use internal::search::binary_search_function;
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9};
+use space_usage::SpaceUsage;
+
+/// Rows of equal-length bit vectors with rank and select in each row,
+/// sharing one directory.
+///
+/// The rows are stored one after another, each padded to whole words,
+/// in a single `Select9`. A count of the ones before each row turns rank
+/// and select over the whole into rank and select within a row, so `r`
+/// rows cost one directory built in one pass rather than `r` separate
+/// structures.
+///
+/// # Example
+///
+/// ```
+/// use succinct::bit_vec::BitMatrix;
+///
+/// // Three flags per item, one row per flag.
+/// let flags = vec![0b001, 0b011, 0b100, 0b111, 0b010u64];
+/// let matrix = BitMatrix::from_columns(3, flags);
+///
+/// assert_eq!((3, 5), (matrix.rows(), matrix.cols()));
+/// assert!(matrix.get(1, 3));
+/// assert_eq!(3, matrix.rank1(1, 4));
+/// assert_eq!(Some(4), matrix.select1(1, 2));
+/// assert_eq!(Some(4), matrix.select0(0, 1));
+/// assert_eq!(0b100, matrix.column(2));
+/// ```
+#[derive(Clone, Debug)]
+pub struct BitMatrix {
+    rows: u64,
+    cols: u64,
+    // The bits in each padded row.
+    stride: u64,
+    // The ones before each row, with the total at the end.
+    before: Vec<u64>,
+    bits: Select9<BitVector<u64>>,
+}
+
+impl BitMatrix {
+    /// Creates a matrix whose rows are `rows`, each of `cols` bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a row isn’t `cols` bits long.
+    pub fn from_rows<I, B>(cols: u64, rows: I) -> Self
+        where I: IntoIterator<Item = B>,
+              B: BitVec {
+
+        let stride = cols.div_ceil(64) * 64;
+        let mut bits: BitVector<u64> = BitVector::new();
+        let mut count = 0u64;
+        for row in rows {
+            assert_eq!(cols, row.bit_len(), "BitMatrix::from_rows: ragged rows");
+            let start = count * stride;
+            bits.resize(start + stride, false);
+            for col in 0 .. cols {
+                if row.get_bit(col) { bits.set_bit(start + col, true); }
+            }
+            count += 1;
+        }
+        Self::build(count, cols, bits)
+    }
+
+    /// Creates a matrix of `rows` rows from its columns, where bit `i` of
+    /// each column is its bit in row `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rows` exceeds 64 or a column has higher bits set.
+    pub fn from_columns<I>(rows: u64, columns: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        assert!(rows <= 64, "BitMatrix::from_columns: more than 64 rows");
+        let columns: Vec<u64> = columns.into_iter().collect();
+        let cols = columns.len() as u64;
+        let stride = cols.div_ceil(64) * 64;
+
+        let mut bits: BitVector<u64> = BitVector::with_fill(rows * stride, false);
+        for (col, &column) in columns.iter().enumerate() {
+            assert!(rows == 64 || column >> rows == 0,
+                    "BitMatrix::from_columns: column too tall");
+            let mut rest = column;
+            while rest != 0 {
+                let row = rest.trailing_zeros() as u64;
+                bits.set_bit(row * stride + col as u64, true);
+                rest &= rest - 1;
+            }
+        }
+        Self::build(rows, cols, bits)
+    }
+
+    fn build(rows: u64, cols: u64, bits: BitVector<u64>) -> Self {
+        let stride = cols.div_ceil(64) * 64;
+        let bits = Select9::new(Rank9::new(bits));
+
+        let mut before = Vec::with_capacity(rows as usize + 1);
+        before.push(0);
+        for row in 1 ..= rows {
+            before.push(if stride == 0 {0} else {bits.rank1(row * stride - 1)});
+        }
+        BitMatrix { rows, cols, stride, before, bits }
+    }
+
+    /// The number of rows.
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// The number of columns, which is the length of each row.
+    pub fn cols(&self) -> u64 {
+        self.cols
+    }
+
+    fn start(&self, row: u64) -> u64 {
+        assert!(row < self.rows, "BitMatrix: row out of bounds");
+        row * self.stride
+    }
+
+    /// The bit in `row` at `col`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` or `col` is out of bounds.
+    pub fn get(&self, row: u64, col: u64) -> bool {
+        assert!(col < self.cols, "BitMatrix::get: column out of bounds");
+        self.bits.get_bit(self.start(row) + col)
+    }
+
+    /// Borrows `row` as a bit vector.
+    pub fn row(&self, row: u64) -> BitSlice<'_, Select9<BitVector<u64>>> {
+        let start = self.start(row);
+        BitSlice::new(&self.bits, start .. start + self.cols)
+    }
+
+    /// The bits of column `col`, where bit `i` is its bit in row `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 64 rows or `col` is out of bounds.
+    pub fn column(&self, col: u64) -> u64 {
+        assert!(self.rows <= 64, "BitMatrix::column: more than 64 rows");
+        assert!(col < self.cols, "BitMatrix::column: out of bounds");
+        (0 .. self.rows).filter(|&row| self.get(row, col))
+                        .fold(0, |column, row| column | 1 << row)
+    }
+
+    /// The number of ones in `row`.
+    pub fn row_ones(&self, row: u64) -> u64 {
+        assert!(row < self.rows, "BitMatrix::row_ones: out of bounds");
+        self.before[row as usize + 1] - self.before[row as usize]
+    }
+
+    /// The ones in `row` up to and including `col`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` or `col` is out of bounds.
+    pub fn rank1(&self, row: u64, col: u64) -> u64 {
+        assert!(col < self.cols, "BitMatrix::rank1: column out of bounds");
+        self.bits.rank1(self.start(row) + col) - self.before[row as usize]
+    }
+
+    /// The zeros in `row` up to and including `col`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row` or `col` is out of bounds.
+    pub fn rank0(&self, row: u64, col: u64) -> u64 {
+        col + 1 - self.rank1(row, col)
+    }
+
+    /// The column of the `index`th one in `row`, if there is one.
+    pub fn select1(&self, row: u64, index: u64) -> Option<u64> {
+        if index >= self.row_ones(row) { return None; }
+        self.bits.select1(self.before[row as usize] + index).map(|position| position - self.start(row))
+    }
+
+    /// The column of the `index`th zero in `row`, if there is one.
+    pub fn select0(&self, row: u64, index: u64) -> Option<u64> {
+        if index >= self.cols - self.row_ones(row) { return None; }
+        binary_search_function(0, self.cols, index + 1, |col| self.rank0(row, col))
+    }
+
+    /// Swaps rows and columns.
+    ///
+    /// Works on 64 × 64 tiles with
+    /// [`transpose64`](../broadword/fn.transpose64.html).
+    pub fn transpose(&self) -> BitMatrix {
+        let rows = self.cols;
+        let cols = self.rows;
+        let row_words = cols.div_ceil(64);
+
+        let mut bits: BitVector<u64> = BitVector::with_fill(rows * row_words * 64, false);
+        self.for_each_tile(|row_word, col_word, tile| {
+            for (j, &word) in tile.iter().enumerate() {
+                let row = col_word * 64 + j as u64;
+                if row < rows {
+                    bits.set_block(checked_index(row * row_words + row_word), word);
+                }
+            }
+        });
+        Self::build(rows, cols, bits)
+    }
+
+    /// The number of ones in each column.
+    pub fn column_ones(&self) -> Vec<u64> {
+        let mut counts = vec![0; checked_index(self.cols)];
+        self.for_each_tile(|_, col_word, tile| {
+            for (j, &word) in tile.iter().enumerate() {
+                if let Some(count) = counts.get_mut(checked_index(col_word * 64) + j) {
+                    *count += word.count_ones() as u64;
+                }
+            }
+        });
+        counts
+    }
+
+    // Calls `visit` with each 64 × 64 tile transposed, with the word
+    // offsets of its rows in a column and of its columns in a row.
+    fn for_each_tile<F>(&self, mut visit: F)
+        where F: FnMut(u64, u64, &[u64; 64]) {
+
+        let row_words = self.stride / 64;
+        for row_word in 0 .. self.rows.div_ceil(64) {
+            for col_word in 0 .. row_words {
+                let mut tile = [0; 64];
+                for (i, word) in tile.iter_mut().enumerate() {
+                    let row = row_word * 64 + i as u64;
+                    if row < self.rows {
+                        *word = self.bits.get_block(checked_index(row * row_words + col_word));
+                    }
+                }
+                broadword::transpose64(&mut tile);
+                visit(row_word, col_word, &tile);
+            }
+        }
+    }
+}
+
+fn checked_index(index: u64) -> usize {
+    index.to_usize().expect("BitMatrix: index overflow")
+}
+
+impl SpaceUsage for BitMatrix {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.before.heap_bytes() + self.bits.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let matrix = BitMatrix::from_columns(4, vec![]);
+        assert_eq!((4, 0), (matrix.rows(), matrix.cols()));
+        assert_eq!(None, matrix.select0(3, 0));
+        assert_eq!(0, BitMatrix::from_rows(10, Vec::<Vec<u64>>::new()).rows());
+    }
+
+    #[test]
+    fn rows_and_columns_agree() {
+        let columns: Vec<u64> = (0 .. 300).map(|i| i * 7 % 16).collect();
+        let by_columns = BitMatrix::from_columns(4, columns.iter().cloned());
+        let rows: Vec<BitVector<u64>> = (0 .. 4).map(|row| {
+            let mut bits = BitVector::with_fill(300, false);
+            for (col, &column) in columns.iter().enumerate() {
+                bits.set_bit(col as u64, column >> row & 1 == 1);
+            }
+            bits
+        }).collect();
+        let by_rows = BitMatrix::from_rows(300, rows);
+
+        assert!((0 .. 300).all(|col| by_rows.column(col) == columns[col as usize]));
+        assert!((0 .. 4).all(|row| by_rows.row_ones(row) == by_columns.row_ones(row)));
+        assert_eq!(by_columns.select1(3, 20), by_rows.select1(3, 20));
+    }
+
+    #[test]
+    fn transpose() {
+        let rows: Vec<BitVector<u64>> = (0 .. 70u64).map(|row| {
+            let mut bits = BitVector::with_fill(130, false);
+            for col in 0 .. 130 { bits.set_bit(col, (row * col) % 7 == 1); }
+            bits
+        }).collect();
+        let matrix = BitMatrix::from_rows(130, rows);
+        let transposed = matrix.transpose();
+
+        assert_eq!((130, 70), (transposed.rows(), transposed.cols()));
+        assert!((0 .. 70).all(|row| (0 .. 130).all(|col| {
+            matrix.get(row, col) == transposed.get(col, row)
+        })));
+        let ones: Vec<u64> = (0 .. 130).map(|row| transposed.row_ones(row)).collect();
+        assert_eq!(ones, matrix.column_ones());
+        assert_eq!(transposed.transpose().row_ones(69), matrix.row_ones(69));
+        assert_eq!(0, BitMatrix::from_columns(3, vec![]).transpose().rows());
+    }
+
+    #[test]
+    #[should_panic]
+    fn ragged() {
+        BitMatrix::from_rows(64, vec![vec![0u64], vec![0u64; 2]]);
+    }
+
+    #[test]
+    fn qc_matches_rows() {
+        fn prop(columns: Vec<u8>, probes: Vec<(u8, u16)>) -> bool {
+            let matrix = BitMatrix::from_columns(8, columns.iter().map(|&c| c as u64));
+            let rows: Vec<Vec<bool>> = (0 .. 8).map(|row| {
+                columns.iter().map(|&c| c >> row & 1 == 1).collect()
+            }).collect();
+            if columns.is_empty() { return matrix.cols() == 0; }
+
+            probes.into_iter().all(|(row, col)| {
+                let row = row as u64 % 8;
+                let col = col as u64 % matrix.cols();
+                let bits = &rows[row as usize];
+                let ones = bits[.. col as usize + 1].iter().filter(|&&b| b).count() as u64;
+                let nth = |value: bool, n: u64| bits.iter().enumerate()
+                    .filter(|&(_, &b)| b == value).nth(n as usize).map(|(i, _)| i as u64);
+
+                matrix.get(row, col) == bits[col as usize]
+                    && matrix.row(row).get_bit(col) == bits[col as usize]
+                    && matrix.column(col) == columns[col as usize] as u64
+                    && matrix.rank1(row, col) == ones
+                    && matrix.select1(row, col) == nth(true, col)
+                    && matrix.select0(row, col) == nth(false, col)
+            })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<(u8, u16)>) -> bool);
+    }
+}
@@ -9,5 +9,20 @@ pub use self::bit_vector::*;
 mod bit_slice;
 pub use self::bit_slice::*;
 
+mod byte_backed;
+pub use self::byte_backed::*;
+
+mod dynamic;
+pub use self::dynamic::*;
+
+mod hybrid;
+pub use self::hybrid::*;
+
+mod matrix;
+pub use self::matrix::*;
+
 mod prim;
 pub use self::prim::*;
+
+mod tombstones;
+pub use self::tombstones::*;
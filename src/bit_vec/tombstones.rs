@@ -0,0 +1,198 @@
+use bit_vec::{BitVec, BitVecMut, DynamicBitVector};
+use rank::{BitRankSupport, RankSupport};
+use select::Select0Support;
+use space_usage::SpaceUsage;
+
+/// A deletion overlay for a static structure: a bitmap of the ids that
+/// have been deleted, with rank and select.
+///
+/// Static structures can’t drop an entry without being rebuilt, but a
+/// few deletions can be laid over one: queries filter out the ids
+/// marked here, and once enough have piled up, the structure is rebuilt
+/// from the live ids alone. The marks are kept in a
+/// [`DynamicBitVector`](struct.DynamicBitVector.html), whose B-tree of
+/// counts makes deleting an id, and finding its rank among the live
+/// ones (its id in the rebuilt structure), take time logarithmic in the
+/// number of ids, plus a scan of one 2048-bit chunk.
+///
+/// # Example
+///
+/// ```
+/// use succinct::bit_vec::Tombstones;
+///
+/// let mut deleted = Tombstones::new(10);
+/// assert!(deleted.delete(3));
+/// assert!(!deleted.delete(3));
+/// deleted.delete(7);
+///
+/// assert_eq!(8, deleted.live_count());
+/// assert_eq!(5, deleted.live_before(6));
+/// assert_eq!(Some(8), deleted.live_id(6));
+/// assert_eq!(vec![0, 1, 2, 4, 5], deleted.live_ids().take(5).collect::<Vec<_>>());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Tombstones {
+    deleted: DynamicBitVector,
+}
+
+impl Tombstones {
+    /// An overlay on `len` ids, none of them deleted.
+    pub fn new(len: u64) -> Self {
+        Tombstones {
+            deleted: DynamicBitVector::from_bits((0 .. len).map(|_| false)),
+        }
+    }
+
+    /// The number of ids, live or deleted.
+    pub fn len(&self) -> u64 {
+        self.deleted.bit_len()
+    }
+
+    /// Are there no ids?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of deleted ids.
+    pub fn deleted_count(&self) -> u64 {
+        self.deleted.count_ones()
+    }
+
+    /// The number of live ids.
+    pub fn live_count(&self) -> u64 {
+        self.len() - self.deleted_count()
+    }
+
+    /// Has `id` been deleted?
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id >= self.len()`.
+    pub fn is_deleted(&self, id: u64) -> bool {
+        self.deleted.get_bit(id)
+    }
+
+    /// Deletes `id`, returning whether it was live.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id >= self.len()`.
+    pub fn delete(&mut self, id: u64) -> bool {
+        let was_live = !self.is_deleted(id);
+        self.deleted.set_bit(id, true);
+        was_live
+    }
+
+    /// Brings `id` back, returning whether it was deleted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id >= self.len()`.
+    pub fn restore(&mut self, id: u64) -> bool {
+        let was_deleted = self.is_deleted(id);
+        self.deleted.set_bit(id, false);
+        was_deleted
+    }
+
+    /// The number of live ids below `id`, which is the id that `id`
+    /// would have once the deleted ones are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id > self.len()`.
+    pub fn live_before(&self, id: u64) -> u64 {
+        id - self.deleted.rank1_before(id)
+    }
+
+    /// The `index`th live id, if there are that many.
+    pub fn live_id(&self, index: u64) -> Option<u64> {
+        self.deleted.select0(index)
+    }
+
+    /// Iterates over the live ids in order.
+    pub fn live_ids(&self) -> LiveIds<'_> {
+        LiveIds { data: self, next: 0 }
+    }
+}
+
+/// Ranks count deleted ids as ones and live ids as zeros.
+impl RankSupport for Tombstones {
+    type Over = bool;
+
+    fn rank(&self, position: u64, value: bool) -> u64 {
+        self.deleted.rank(position, value)
+    }
+
+    fn limit(&self) -> u64 {
+        self.len()
+    }
+}
+
+impl BitRankSupport for Tombstones {}
+
+impl SpaceUsage for Tombstones {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.deleted.heap_bytes()
+    }
+}
+
+/// The live ids of a [`Tombstones`](struct.Tombstones.html) overlay, in
+/// order.
+#[derive(Clone, Debug)]
+pub struct LiveIds<'a> {
+    data: &'a Tombstones,
+    next: u64,
+}
+
+impl<'a> Iterator for LiveIds<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while self.next < self.data.len() {
+            self.next += 1;
+            if !self.data.is_deleted(self.next - 1) {
+                return Some(self.next - 1);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(len: u8, deletes: Vec<u8>, restores: Vec<u8>) -> bool {
+            let len = len as u64;
+            if len == 0 { return Tombstones::new(0).live_ids().next().is_none(); }
+
+            let mut deleted = Tombstones::new(len);
+            let mut naive = vec![false; len as usize];
+            for &id in &deletes {
+                let id = id as u64 % len;
+                if deleted.delete(id) == naive[id as usize] { return false; }
+                naive[id as usize] = true;
+            }
+            for &id in &restores {
+                let id = id as u64 % len;
+                if deleted.restore(id) != naive[id as usize] { return false; }
+                naive[id as usize] = false;
+            }
+
+            let live: Vec<u64> = (0 .. len).filter(|&id| !naive[id as usize]).collect();
+            deleted.live_ids().collect::<Vec<_>>() == live
+                && deleted.live_count() == live.len() as u64
+                && (0 .. len + 1).all(|id| {
+                    deleted.live_before(id) == live.iter().filter(|&&i| i < id).count() as u64
+                })
+                && (0 .. live.len() + 1).all(|k| deleted.live_id(k as u64) == live.get(k).cloned())
+        }
+
+        quickcheck(prop as fn(u8, Vec<u8>, Vec<u8>) -> bool);
+    }
+}
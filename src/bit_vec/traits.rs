@@ -1,4 +1,8 @@
 use num_traits::{One, Zero, ToPrimitive};
+#[cfg(feature = "rkyv")]
+use rkyv::primitive::{ArchivedU16, ArchivedU32, ArchivedU64};
+#[cfg(feature = "rkyv")]
+use rkyv::vec::ArchivedVec;
 
 use storage::{Address, BlockType};
 
@@ -309,6 +313,34 @@ impl<Block: BlockType> BitVec for Vec<Block> {
     }
 }
 
+// Blocks archived with `rkyv`, read in place (with the `rkyv` feature).
+#[cfg(feature = "rkyv")]
+macro_rules! impl_archived_bit_vec {
+    ( $( $block:ty : $archived:ty ),* ) => { $(
+        impl BitVec for ArchivedVec<$archived> {
+            type Block = $block;
+
+            #[inline]
+            fn bit_len(&self) -> u64 {
+                self.len() as u64 * <$block>::nbits() as u64
+            }
+
+            #[inline]
+            fn block_len(&self) -> usize {
+                self.len()
+            }
+
+            #[inline]
+            fn get_block(&self, position: usize) -> $block {
+                self[position].into()
+            }
+        }
+    )* };
+}
+
+#[cfg(feature = "rkyv")]
+impl_archived_bit_vec!(u8: u8, u16: ArchivedU16, u32: ArchivedU32, u64: ArchivedU64);
+
 impl<Block: BlockType> BitVecMut for Vec<Block> {
     #[inline]
     fn set_block(&mut self, position: usize, value: Block) {
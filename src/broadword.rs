@@ -105,6 +105,104 @@ pub fn u_nz8(x: u64) -> u64 {
     (((x | H8) - L8) | x) & H8
 }
 
+/// Counts the ones in each byte of `x`, leaving each count in its byte.
+///
+/// This is the first phase of [count_ones](fn.count_ones.html); the
+/// byte counts sum to `count_ones(x)`.
+pub fn byte_counts(x: u64) -> u64 {
+    let mut s = x - ((x & 0xAAAA_AAAA_AAAA_AAAA) >> 1);
+    s = (s & 0x3333_3333_3333_3333) + ((s >> 2) & 0x3333_3333_3333_3333);
+    (s + (s >> 4)) & 0x0F0F_0F0F_0F0F_0F0F
+}
+
+/// Counts the bytes of `x`, read as `u8`s, that are ≤ `y`.
+///
+/// When `x` holds increasing cumulative counts, as produced by
+/// `byte_counts(x).wrapping_mul(L8)`, this is the index of the byte in
+/// which the count passes `y`, which is the step that select takes.
+pub fn leq_step_8(x: u64, y: u8) -> usize {
+    ((u_le8(x, (y as u64).wrapping_mul(L8)) >> 7).wrapping_mul(L8) >> 56)
+        as usize
+}
+
+/// Finds the index of the most significant one bit in `x`, or `None` if
+/// `x` is 0.
+pub fn msb(x: u64) -> Option<usize> {
+    if x == 0 {None} else {Some(63 - x.leading_zeros() as usize)}
+}
+
+/// Finds the index of the least significant one bit in `x`, or `None` if
+/// `x` is 0.
+pub fn lsb(x: u64) -> Option<usize> {
+    if x == 0 {None} else {Some(x.trailing_zeros() as usize)}
+}
+
+/// Has the highest bit of each `width`-bit lane set, for as many whole
+/// lanes as fit in a `u64`, starting from the low end.
+///
+/// For `width` 8 this is [H8](constant.H8.html).
+pub fn lane_highs(width: usize) -> u64 {
+    debug_assert!(width > 0 && width <= 64, "lane_highs: bad width");
+    let mut highs = 0;
+    let mut top = width;
+    while top <= 64 {
+        highs |= 1 << (top - 1);
+        top += width;
+    }
+    highs
+}
+
+/// Repeats `value`, which must fit in `width` bits, in each whole
+/// `width`-bit lane.
+pub fn splat(value: u64, width: usize) -> u64 {
+    value.wrapping_mul(lane_highs(width) >> (width - 1))
+}
+
+/// Parallel <, treating a `u64` as a vector of `width`-bit unsigned
+/// lanes: sets the high bit of each lane in which `x` is less than `y`.
+///
+/// Any bits above the last whole lane must be clear in both.
+pub fn u_lt_lanes(x: u64, y: u64, width: usize) -> u64 {
+    let highs = lane_highs(width);
+    // The high bit of each lane survives subtracting the low bits of
+    // `y` from those of `x`, set high, iff they are at least `y`’s.
+    let low_ge = (x | highs).wrapping_sub(y & !highs);
+    ((!x & y) | (!(x ^ y) & !low_ge)) & highs
+}
+
+/// Parallel =0, treating a `u64` as a vector of `width`-bit lanes: sets
+/// the high bit of each lane of `x` that is zero.
+pub fn zero_lanes(x: u64, width: usize) -> u64 {
+    let highs = lane_highs(width);
+    let lows = (highs >> (width - 1)).wrapping_mul((1 << (width - 1)) - 1);
+    // Adding all ones to the low bits carries into the high bit unless
+    // they were all zero, and can’t carry further.
+    !(((x & lows) + lows) | x) & highs
+}
+
+/// Transposes a 64 × 64 bit matrix in place, where bit `j` of
+/// `block[i]` is the entry in row `i` and column `j`.
+///
+/// Swaps ever smaller off-diagonal quadrants, so it takes 6 rounds of 32
+/// masked word swaps.
+pub fn transpose64(block: &mut [u64; 64]) {
+    let mut width = 32;
+    let mut mask = 0x0000_0000_FFFF_FFFF;
+    while width != 0 {
+        let mut start = 0;
+        while start < 64 {
+            for i in start .. start + width {
+                let swap = ((block[i] >> width) ^ block[i + width]) & mask;
+                block[i] ^= swap << width;
+                block[i + width] ^= swap;
+            }
+            start += 2 * width;
+        }
+        width >>= 1;
+        mask ^= mask << width;
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::hash::{Hash, Hasher};
@@ -114,6 +212,37 @@ mod test {
     use super::*;
     use select::{BinSearchSelect, Select1Support};
 
+    #[test]
+    fn qc_lanes() {
+        fn prop(values: Vec<(u64, u64)>, width: u8) -> bool {
+            let width = width as usize % 64 + 1;
+            let lanes = 64 / width;
+            let mask = if width == 64 {!0} else {(1 << width) - 1};
+            let (mut x, mut y) = (0, 0);
+            for (i, &(a, b)) in values.iter().take(lanes).enumerate() {
+                x |= (a & mask) << (i * width);
+                y |= (b & mask) << (i * width);
+            }
+
+            let lane = |word: u64, i: usize| word >> (i * width) & mask;
+            let high = 1 << (width - 1);
+            lane_highs(width).count_ones() as usize == lanes
+                && (0 .. lanes).all(|i| {
+                    lane(splat(lane(y, 0), width), i) == lane(y, 0)
+                        && (lane(u_lt_lanes(x, y, width), i) == high) == (lane(x, i) < lane(y, i))
+                        && (lane(zero_lanes(x, width), i) == high) == (lane(x, i) == 0)
+                })
+        }
+
+        quickcheck(prop as fn(Vec<(u64, u64)>, u8) -> bool);
+    }
+
+    #[test]
+    fn lane_highs_8() {
+        assert_eq!(H8, lane_highs(8));
+        assert_eq!(0x4924_9249_2492_4924, lane_highs(3));
+    }
+
     #[test]
     fn count_ones_0() {
         assert_eq!(0, count_ones(0));
@@ -370,6 +499,48 @@ mod test {
                    u( 7,  3, 24,  1,  0,  0,  5,  1)));
     }
 
+    #[test]
+    fn byte_counts_qc() {
+        fn prop(x: u64) -> bool {
+            let counts = byte_counts(x);
+            (0 .. 8).all(|i| (counts >> (8 * i)) & 0xFF
+                                == ((x >> (8 * i)) & 0xFF).count_ones() as u64)
+        }
+
+        quickcheck(prop as fn(u64) -> bool);
+    }
+
+    #[test]
+    fn leq_step_8_qc() {
+        fn prop(x: u64, y: u8) -> bool {
+            let naive = (0 .. 8).filter(|&i| (x >> (8 * i)) as u8 <= y).count();
+            leq_step_8(x, y) == naive
+        }
+
+        quickcheck(prop as fn(u64, u8) -> bool);
+    }
+
+    #[test]
+    fn msb_lsb() {
+        assert_eq!(None, msb(0));
+        assert_eq!(None, lsb(0));
+        assert_eq!(Some(0), msb(1));
+        assert_eq!(Some(63), msb(!0));
+        assert_eq!(Some(0), lsb(!0));
+        assert_eq!(Some(4), msb(0b10110));
+        assert_eq!(Some(1), lsb(0b10110));
+    }
+
+    #[test]
+    fn msb_lsb_qc() {
+        fn prop(x: u64) -> bool {
+            msb(x) == (0 .. 64).rev().find(|&i| x & (1 << i) != 0)
+                && lsb(x) == (0 .. 64).find(|&i| x & (1 << i) != 0)
+        }
+
+        quickcheck(prop as fn(u64) -> bool);
+    }
+
     /// Helpers for creating u64s.
 
     fn b(a: u64, b: u64, c: u64, d: u64,
@@ -396,6 +567,25 @@ mod test {
           e as u8, f as u8, g as u8, h as u8)
     }
 
+    #[test]
+    fn qc_transpose64() {
+        fn prop(seed: u64) -> bool {
+            let mut block = [0u64; 64];
+            let mut state = seed | 1;
+            for word in block.iter_mut() {
+                state ^= state << 13; state ^= state >> 7; state ^= state << 17;
+                *word = state;
+            }
+            let original = block;
+            transpose64(&mut block);
+            (0 .. 64).all(|i| (0 .. 64).all(|j| {
+                (block[i] >> j & 1) == (original[j] >> i & 1)
+            }))
+        }
+
+        quickcheck(prop as fn(u64) -> bool);
+    }
+
     fn hash<T: Hash>(t: &T) -> u64 {
         let mut s = DefaultHasher::new();
         t.hash(&mut s);
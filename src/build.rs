@@ -0,0 +1,211 @@
+//! Hooks for watching long constructions.
+//!
+//! Building a rank directory over billions of bits, a suffix array or a
+//! wavelet matrix can take minutes. Their `with_monitor` constructors
+//! take a [`Monitor`](struct.Monitor.html), which calls back with a
+//! [`Progress`](struct.Progress.html) every so often, so that an
+//! application can show a progress bar instead of hanging silently. Each
+//! report carries a phase name and a count of units done out of a total,
+//! which map directly onto a bar’s message, position and length.
+//!
+//! A monitor can also [cancel](struct.Monitor.html#method.cancel_on) a
+//! construction: when its flag is set, the constructor stops at its next
+//! report and returns `Error::Cancelled`. That lets a service abandon a
+//! build whose request has gone away.
+//!
+//! Finally, a monitor can hold a [memory
+//! budget](struct.Monitor.html#method.with_memory_budget). Each
+//! constructor estimates its peak memory before allocating anything
+//! large and fails fast with `Error::OverBudget` if that exceeds the
+//! budget, rather than taking the host down.
+//!
+//! ```
+//! use std::sync::atomic::AtomicBool;
+//!
+//! use succinct::Error;
+//! use succinct::build::Monitor;
+//! use succinct::wavelet::WaveletMatrix;
+//!
+//! let mut reports = Vec::new();
+//! {
+//!     let mut monitor = Monitor::new().on_progress(|progress| {
+//!         reports.push((progress.phase, progress.done, progress.total));
+//!     });
+//!     WaveletMatrix::with_monitor(3, vec![5, 1, 7, 2], &mut monitor).unwrap();
+//! }
+//! assert_eq!(("wavelet levels", 3, 3), *reports.last().unwrap());
+//!
+//! let cancelled = AtomicBool::new(true);
+//! let mut monitor = Monitor::new().cancel_on(&cancelled);
+//! assert_eq!(Some(Error::Cancelled),
+//!            WaveletMatrix::with_monitor(3, vec![5, 1], &mut monitor).err());
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use error::Error;
+
+/// How far a construction has got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Progress {
+    /// What is being built, such as `"rank directory"`.
+    pub phase: &'static str,
+    /// The units of work done so far.
+    pub done: u64,
+    /// The units of work in the phase; `done` reaches it when the phase
+    /// ends.
+    pub total: u64,
+}
+
+/// What to tell the caller of a long construction as it goes, and
+/// whether to stop.
+///
+/// The default tells nothing and never stops.
+#[derive(Default)]
+pub struct Monitor<'a> {
+    progress: Option<Box<dyn FnMut(Progress) + 'a>>,
+    cancel: Option<&'a AtomicBool>,
+    budget: Option<u64>,
+}
+
+impl<'a> Monitor<'a> {
+    /// A monitor that tells nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `callback` with each report.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+        where F: FnMut(Progress) + 'a {
+
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Stops the construction with `Error::Cancelled` once `flag` is
+    /// set, from this or any other thread.
+    pub fn cancel_on(mut self, flag: &'a AtomicBool) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Fails the construction with `Error::OverBudget` if it expects to
+    /// need more than `bytes` bytes of heap at once for its own state,
+    /// scratch space and output; the input it is given doesn’t count.
+    pub fn with_memory_budget(mut self, bytes: u64) -> Self {
+        self.budget = Some(bytes);
+        self
+    }
+
+    /// The memory budget, if any.
+    pub fn memory_budget(&self) -> Option<u64> {
+        self.budget
+    }
+
+    /// Checks an estimate of the peak heap use against the budget.
+    pub(crate) fn reserve(&self, bytes: u64) -> Result<(), Error> {
+        match self.budget {
+            Some(budget) if bytes > budget => Err(Error::OverBudget(bytes)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Has the construction been cancelled?
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Reports that `done` of the `total` units of `phase` are done, or
+    /// returns `Error::Cancelled` to stop.
+    pub(crate) fn report(&mut self, phase: &'static str, done: u64, total: u64)
+                         -> Result<(), Error> {
+        if self.is_cancelled() { return Err(Error::Cancelled); }
+        if let Some(ref mut callback) = self.progress {
+            callback(Progress { phase, done, total });
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Debug for Monitor<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.debug_struct("Monitor")
+                 .field("progress", &self.progress.is_some())
+                 .field("cancelled", &self.is_cancelled())
+                 .field("budget", &self.budget)
+                 .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::BitVector;
+    use rank::Rank9;
+    use text::SuffixArrayBuilder;
+
+    fn reports<F: FnOnce(&mut Monitor)>(build: F) -> Vec<Progress> {
+        let mut result = Vec::new();
+        build(&mut Monitor::new().on_progress(|progress| result.push(progress)));
+        result
+    }
+
+    #[test]
+    fn ends_with_total() {
+        let bits: BitVector<u64> = BitVector::with_fill(200_000 * 64, true);
+        let rank = reports(|monitor| {
+            let _: Rank9<BitVector<u64>> = Rank9::with_monitor(bits, monitor).unwrap();
+        });
+        assert_eq!(vec![0, 65536, 131072, 196608, 200_000],
+                   rank.iter().map(|progress| progress.done).collect::<Vec<_>>());
+        assert!(rank.iter().all(|progress| progress.total == 200_000));
+
+        let text = b"abracadabra abracadabra";
+        let suffixes = reports(|monitor| SuffixArrayBuilder::new(text).run(monitor).unwrap());
+        let last = suffixes.last().unwrap();
+        assert_eq!(("suffix array", last.total), (last.phase, last.done));
+        assert!(suffixes.windows(2).all(|pair| pair[0].done < pair[1].done));
+    }
+
+    #[test]
+    fn cancel_keeps_builder() {
+        let flag = AtomicBool::new(false);
+        let text = b"mississippi";
+        let mut builder = SuffixArrayBuilder::new(text);
+        {
+            let mut monitor = Monitor::new().cancel_on(&flag).on_progress(|progress| {
+                if progress.done == 1 { flag.store(true, Ordering::Relaxed); }
+            });
+            assert_eq!(Err(Error::Cancelled), builder.run(&mut monitor));
+        }
+        assert!(!builder.is_done());
+        assert_eq!(::text::suffix_array(text), builder.finish());
+
+        let bits: BitVector<u64> = BitVector::with_fill(64, true);
+        let result: Result<Rank9<_>, _> = Rank9::with_monitor(bits, &mut Monitor::new().cancel_on(&flag));
+        assert_eq!(Some(Error::Cancelled), result.err());
+    }
+
+    #[test]
+    fn budgets() {
+        // 1616 bytes, or half that with narrow directories.
+        let bits: BitVector<u64> = BitVector::with_fill(64 * 800, true);
+        let bytes = Rank9::estimate_size(64 * 800) as u64;
+        assert_eq!(if cfg!(feature = "index32") {808} else {1616}, bytes);
+        let mut monitor = Monitor::new().with_memory_budget(bytes);
+        let result: Result<Rank9<_>, _> = Rank9::with_monitor(bits.clone(), &mut monitor);
+        assert!(result.is_ok());
+        let mut monitor = Monitor::new().with_memory_budget(bytes - 1);
+        let result: Result<Rank9<_>, _> = Rank9::with_monitor(bits, &mut monitor);
+        assert_eq!(Some(Error::OverBudget(bytes)), result.err());
+
+        let mut builder = SuffixArrayBuilder::new(&[0u8; 100]);
+        let mut monitor = Monitor::new().with_memory_budget(2000);
+        assert!(match builder.run(&mut monitor) {
+            Err(Error::OverBudget(bytes)) => bytes >= 3 * 101 * 8,
+            _ => false,
+        });
+        assert!(builder.run(&mut Monitor::new().with_memory_budget(1 << 20)).is_ok());
+    }
+}
@@ -13,5 +13,52 @@ pub trait UniversalCode {
     /// `Ok(None)` indicates (benign) EOF.
     fn decode<R: BitRead>(&self, source: &mut R) -> Result<Option<u64>>;
 
+    /// Decodes values from `source` one at a time as it is iterated,
+    /// until it runs out or a read fails.
+    ///
+    /// ```
+    /// use succinct::coding::{GAMMA, UniversalCode};
+    /// use succinct::stream::BitBuffer;
+    ///
+    /// let mut buffer: BitBuffer = BitBuffer::new();
+    /// for value in 1 .. 6 {
+    ///     GAMMA.encode(&mut buffer, value).unwrap();
+    /// }
+    /// buffer.seek(0).unwrap();
+    ///
+    /// let values: Vec<u64> = GAMMA.decode_iter(&mut buffer).skip(2)
+    ///                             .map(Result::unwrap).collect();
+    /// assert_eq!(vec![3, 4, 5], values);
+    /// ```
+    fn decode_iter<'a, R: BitRead>(&'a self, source: &'a mut R) -> Decoded<'a, Self, R>
+        where Self: Sized {
+
+        Decoded { code: self, source, failed: false }
+    }
+
     // TODO: bigint support
 }
+
+/// The values of a stream, decoded as they are needed; see
+/// [`UniversalCode::decode_iter`](trait.UniversalCode.html#method.decode_iter).
+#[derive(Debug)]
+pub struct Decoded<'a, Code: 'a, R: 'a> {
+    code: &'a Code,
+    source: &'a mut R,
+    failed: bool,
+}
+
+impl<'a, Code: UniversalCode, R: BitRead> Iterator for Decoded<'a, Code, R> {
+    type Item = Result<u64>;
+
+    fn next(&mut self) -> Option<Result<u64>> {
+        if self.failed { return None; }
+        match self.code.decode(self.source) {
+            Ok(value) => value.map(Ok),
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
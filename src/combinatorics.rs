@@ -0,0 +1,173 @@
+//! Binomial coefficients, the combinatorial number system and Gray codes.
+//!
+//! A compressed bit vector in the style of Raman, Raman and Rao stores
+//! each block of *n* bits as its *class*, the number *k* of ones in it,
+//! and an *offset* identifying which of the C(*n*, *k*) blocks of that
+//! class it is. The offset is the block’s rank in the combinatorial
+//! number system: with its ones at positions *p*<sub>1</sub> < … <
+//! *p*<sub>*k*</sub>, it is the sum of C(*p*<sub>*i*</sub>, *i*). These
+//! routines encode and decode offsets for blocks of up to 64 bits from a
+//! precomputed table of binomial coefficients, for building such blocks
+//! by hand.
+//!
+//! ```
+//! use succinct::combinatorics::{decode_offset, encode_offset, offset_bits};
+//!
+//! let block = 0b1010_0110u64;
+//! let (class, offset) = encode_offset(block, 8);
+//! assert_eq!(4, class);
+//! assert!(offset_bits(8, 4) <= 7);
+//! assert_eq!(block, decode_offset(8, class, offset));
+//! ```
+
+/// The largest block length the tables cover.
+pub const MAX_BLOCK_BITS: usize = 64;
+
+const fn binomial_table() -> [[u64; MAX_BLOCK_BITS + 1]; MAX_BLOCK_BITS + 1] {
+    let mut table = [[0; MAX_BLOCK_BITS + 1]; MAX_BLOCK_BITS + 1];
+    let mut n = 0;
+    while n <= MAX_BLOCK_BITS {
+        table[n][0] = 1;
+        let mut k = 1;
+        while k <= n {
+            table[n][k] = table[n - 1][k - 1] + table[n - 1][k];
+            k += 1;
+        }
+        n += 1;
+    }
+    table
+}
+
+// C(n, k) for n and k up to 64; the largest, C(64, 32), fits in a u64.
+static BINOMIALS: [[u64; MAX_BLOCK_BITS + 1]; MAX_BLOCK_BITS + 1] = binomial_table();
+
+/// Returns C(`n`, `k`), the number of ways to choose `k` of `n` things,
+/// which is 0 if `k > n`.
+///
+/// # Panics
+///
+/// Panics if `n` exceeds 64.
+pub fn binomial(n: usize, k: usize) -> u64 {
+    assert!(n <= MAX_BLOCK_BITS, "binomial: n exceeds 64");
+    if k > n {0} else {BINOMIALS[n][k]}
+}
+
+/// Returns the bits needed for an offset among the `n`-bit blocks with
+/// `k` ones: ⌈lg C(`n`, `k`)⌉, which is 0 when there is only one such
+/// block.
+///
+/// # Panics
+///
+/// Panics if `n` exceeds 64 or `k` exceeds `n`.
+pub fn offset_bits(n: usize, k: usize) -> usize {
+    assert!(k <= n, "offset_bits: more ones than bits");
+    let count = binomial(n, k);
+    64 - (count - 1).leading_zeros() as usize
+}
+
+/// Returns the class and offset of the low `n` bits of `block`.
+///
+/// # Panics
+///
+/// Panics if `n` exceeds 64 or `block` has bits set at or above `n`.
+pub fn encode_offset(block: u64, n: usize) -> (usize, u64) {
+    assert!(n <= MAX_BLOCK_BITS, "encode_offset: n exceeds 64");
+    assert!(n == 64 || block >> n == 0, "encode_offset: block too long");
+
+    let mut offset = 0;
+    let mut rest = block;
+    let mut class = 0;
+    while rest != 0 {
+        class += 1;
+        offset += BINOMIALS[rest.trailing_zeros() as usize][class];
+        rest &= rest - 1;
+    }
+    (class, offset)
+}
+
+/// Returns the `n`-bit block with `class` ones at `offset`.
+///
+/// # Panics
+///
+/// Panics if `n` exceeds 64, `class` exceeds `n`, or `offset` is not
+/// less than C(`n`, `class`).
+pub fn decode_offset(n: usize, class: usize, offset: u64) -> u64 {
+    assert!(offset < binomial(n, class), "decode_offset: offset out of range");
+
+    // The highest one is at the largest position p with C(p, class) no
+    // more than the offset, and so on down.
+    let mut block = 0;
+    let mut rest = offset;
+    let mut position = n;
+    for i in (1 ..= class).rev() {
+        position -= 1;
+        while BINOMIALS[position][i] > rest {
+            position -= 1;
+        }
+        block |= 1 << position;
+        rest -= BINOMIALS[position][i];
+    }
+    block
+}
+
+/// Returns the reflected binary Gray code of `value`, in which
+/// consecutive values differ in one bit.
+pub fn gray_encode(value: u64) -> u64 {
+    value ^ (value >> 1)
+}
+
+/// Inverts [`gray_encode`](fn.gray_encode.html).
+pub fn gray_decode(mut code: u64) -> u64 {
+    let mut shift = 1;
+    while shift < 64 {
+        code ^= code >> shift;
+        shift <<= 1;
+    }
+    code
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn binomials() {
+        assert_eq!(1, binomial(0, 0));
+        assert_eq!(0, binomial(3, 4));
+        assert_eq!(252, binomial(10, 5));
+        assert_eq!(1_832_624_140_942_590_534, binomial(64, 32));
+        assert_eq!(0, offset_bits(64, 64));
+        assert_eq!(1, offset_bits(2, 1));
+        assert_eq!(8, offset_bits(10, 5));
+    }
+
+    #[test]
+    fn offsets_are_dense() {
+        // Every 8-bit block of class 3 gets a distinct offset below C(8, 3).
+        let mut seen = vec![false; binomial(8, 3) as usize];
+        for block in (0 .. 256u64).filter(|block| block.count_ones() == 3) {
+            let (class, offset) = encode_offset(block, 8);
+            assert_eq!(3, class);
+            assert!(!seen[offset as usize]);
+            seen[offset as usize] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+    }
+
+    #[test]
+    fn qc_round_trips() {
+        fn prop(block: u64, n: u8) -> bool {
+            let n = n as usize % 65;
+            let block = if n == 64 {block} else {block & ((1 << n) - 1)};
+            let (class, offset) = encode_offset(block, n);
+
+            class == block.count_ones() as usize
+                && decode_offset(n, class, offset) == block
+                && gray_decode(gray_encode(block)) == block
+                && (gray_encode(block) ^ gray_encode(block.wrapping_add(1))).count_ones() == 1
+        }
+
+        quickcheck(prop as fn(u64, u8) -> bool);
+    }
+}
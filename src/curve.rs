@@ -0,0 +1,288 @@
+//! Space-filling curves, for storing points in the plane as integers.
+//!
+//! A Morton (Z-order) or Hilbert key maps a point with 32-bit
+//! coordinates to a 64-bit integer such that every aligned square of
+//! side 2<sup>*k*</sup> is a run of 4<sup>*k*</sup> consecutive keys. A
+//! point set then fits any of the 1D structures, such as
+//! [`EliasFano`](../int_vec/struct.EliasFano.html), and a rectangle
+//! becomes a few key ranges, found by
+//! [`morton_ranges`](fn.morton_ranges.html) or
+//! [`hilbert_ranges`](fn.hilbert_ranges.html). Hilbert keys keep nearby
+//! points closer together, so a rectangle needs fewer ranges; Morton
+//! keys are cheaper to compute.
+//!
+//! ```
+//! use succinct::curve::{morton_encode, morton_ranges};
+//! use succinct::int_vec::EliasFano;
+//!
+//! let points = [(1, 1), (2, 5), (4, 4), (6, 2), (7, 7)];
+//! let mut keys: Vec<u64> = points.iter().map(|&(x, y)| morton_encode(x, y)).collect();
+//! keys.sort();
+//! let set = EliasFano::new(1 << 6, keys);
+//!
+//! // The points with 2 ≤ x ≤ 6 and 2 ≤ y ≤ 5.
+//! let count: u64 = morton_ranges(2 ..= 6, 2 ..= 5, 16).into_iter()
+//!     .map(|range| set.count_range(*range.start() .. range.end() + 1))
+//!     .sum();
+//! assert_eq!(3, count);
+//! ```
+
+use std::ops::RangeInclusive;
+
+/// Interleaves the bits of `x` and `y` into a Morton key, with each bit
+/// of `y` just above the same bit of `x`.
+///
+/// ```
+/// use succinct::curve::{morton_decode, morton_encode};
+///
+/// assert_eq!(0b1001, morton_encode(0b01, 0b10));
+/// assert_eq!((0b01, 0b10), morton_decode(0b1001));
+/// ```
+#[inline]
+pub fn morton_encode(x: u32, y: u32) -> u64 {
+    spread(x) | spread(y) << 1
+}
+
+/// The point whose Morton key is `key`.
+#[inline]
+pub fn morton_decode(key: u64) -> (u32, u32) {
+    (compact(key), compact(key >> 1))
+}
+
+// Moves bit i of `value` to bit 2i.
+fn spread(value: u32) -> u64 {
+    let mut x = value as u64;
+    x = (x | x << 16) & 0x0000_FFFF_0000_FFFF;
+    x = (x | x << 8) & 0x00FF_00FF_00FF_00FF;
+    x = (x | x << 4) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | x << 2) & 0x3333_3333_3333_3333;
+    (x | x << 1) & 0x5555_5555_5555_5555
+}
+
+// Moves bit 2i of `key` to bit i, dropping the odd bits.
+fn compact(key: u64) -> u32 {
+    let mut x = key & 0x5555_5555_5555_5555;
+    x = (x | x >> 1) & 0x3333_3333_3333_3333;
+    x = (x | x >> 2) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | x >> 4) & 0x00FF_00FF_00FF_00FF;
+    x = (x | x >> 8) & 0x0000_FFFF_0000_FFFF;
+    (x | x >> 16) as u32
+}
+
+/// The distance of `(x, y)` along the Hilbert curve through the
+/// 2<sup>32</sup> × 2<sup>32</sup> grid, starting at the origin and
+/// ending at (2<sup>32</sup> − 1, 0).
+///
+/// ```
+/// use succinct::curve::{hilbert_decode, hilbert_encode};
+///
+/// // The first four cells, going across, up and back.
+/// assert_eq!(vec![0, 1, 2, 3],
+///            vec![(0, 0), (1, 0), (1, 1), (0, 1)].into_iter()
+///                .map(|(x, y)| hilbert_encode(x, y))
+///                .collect::<Vec<_>>());
+/// assert_eq!((1 << 16, 1 << 20), hilbert_decode(hilbert_encode(1 << 16, 1 << 20)));
+/// ```
+pub fn hilbert_encode(x: u32, y: u32) -> u64 {
+    let (mut x, mut y) = (x as u64, y as u64);
+    let mut key = 0;
+    for level in (0 .. 32).rev() {
+        let rx = x >> level & 1;
+        let ry = y >> level & 1;
+        key |= ((3 * rx) ^ ry) << (2 * level);
+        rotate(1 << 32, &mut x, &mut y, rx, ry);
+    }
+    key
+}
+
+/// The point at distance `key` along the Hilbert curve of
+/// [`hilbert_encode`](fn.hilbert_encode.html).
+pub fn hilbert_decode(key: u64) -> (u32, u32) {
+    let (mut x, mut y) = (0, 0);
+    for level in 0 .. 32 {
+        let digit = key >> (2 * level);
+        let rx = digit >> 1 & 1;
+        let ry = (digit ^ rx) & 1;
+        rotate(1 << level, &mut x, &mut y, rx, ry);
+        x += rx << level;
+        y += ry << level;
+    }
+    (x as u32, y as u32)
+}
+
+// Reflects and transposes a quadrant of side `side` so that its curve
+// runs the standard way.
+fn rotate(side: u64, x: &mut u64, y: &mut u64, rx: u64, ry: u64) {
+    if ry == 0 {
+        if rx == 1 {
+            *x = side.wrapping_sub(1).wrapping_sub(*x);
+            *y = side.wrapping_sub(1).wrapping_sub(*y);
+        }
+        ::std::mem::swap(x, y);
+    }
+}
+
+/// Sorted, disjoint ranges of Morton keys covering the points with *x*
+/// in `x` and *y* in `y`, at most `max_ranges` of them.
+///
+/// The ranges are exact when the budget allows. Otherwise the squares
+/// along the rectangle’s edge are covered whole, so the ranges hold
+/// some keys outside it, which the caller can filter by decoding them.
+///
+/// # Panics
+///
+/// Panics if `max_ranges` is 0.
+pub fn morton_ranges(x: RangeInclusive<u32>, y: RangeInclusive<u32>, max_ranges: usize)
+                     -> Vec<RangeInclusive<u64>> {
+    decompose(x, y, max_ranges, morton_encode)
+}
+
+/// Sorted, disjoint ranges of Hilbert keys covering the points with *x*
+/// in `x` and *y* in `y`, at most `max_ranges` of them, as for
+/// [`morton_ranges`](fn.morton_ranges.html).
+///
+/// # Panics
+///
+/// Panics if `max_ranges` is 0.
+pub fn hilbert_ranges(x: RangeInclusive<u32>, y: RangeInclusive<u32>, max_ranges: usize)
+                      -> Vec<RangeInclusive<u64>> {
+    decompose(x, y, max_ranges, hilbert_encode)
+}
+
+// Splits the grid into aligned squares a level at a time, keeping those
+// inside the rectangle and splitting those crossing its edge, until the
+// cells are reached or the next level would go over budget.
+fn decompose<F>(x: RangeInclusive<u32>, y: RangeInclusive<u32>, max_ranges: usize, encode: F)
+                -> Vec<RangeInclusive<u64>>
+    where F: Fn(u32, u32) -> u64 {
+
+    assert!(max_ranges > 0, "curve: max_ranges must be positive");
+
+    let (x0, x1) = (*x.start() as u64, *x.end() as u64);
+    let (y0, y1) = (*y.start() as u64, *y.end() as u64);
+    let mut ranges = Vec::new();
+    if x0 > x1 || y0 > y1 { return ranges; }
+
+    // A square is its corner and the log of its side.
+    let range = |(sx, sy, level): (u64, u64, u32)| {
+        let low = ((1u128 << (2 * level)) - 1) as u64;
+        let start = encode(sx as u32, sy as u32) & !low;
+        start ..= start | low
+    };
+
+    let mut crossing = vec![(0, 0, 32)];
+    while let Some(&(_, _, level)) = crossing.first() {
+        if level == 0 { break; }
+
+        let half = 1 << (level - 1);
+        let mut inside = Vec::new();
+        let mut next = Vec::new();
+        for &(sx, sy, _) in &crossing {
+            for &(cx, cy) in &[(sx, sy), (sx + half, sy), (sx, sy + half), (sx + half, sy + half)] {
+                let (ex, ey) = (cx + half - 1, cy + half - 1);
+                if ex < x0 || cx > x1 || ey < y0 || cy > y1 { continue; }
+
+                let square = (cx, cy, level - 1);
+                if x0 <= cx && ex <= x1 && y0 <= cy && ey <= y1 {
+                    inside.push(square);
+                } else {
+                    next.push(square);
+                }
+            }
+        }
+
+        if ranges.len() + inside.len() + next.len() > max_ranges {
+            break;
+        }
+        ranges.extend(inside.into_iter().map(range));
+        crossing = next;
+    }
+    ranges.extend(crossing.into_iter().map(range));
+
+    // Merge the ranges that touch.
+    ranges.sort_by_key(|range| *range.start());
+    let mut merged: Vec<RangeInclusive<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        if let Some(last) = merged.last_mut() {
+            if *last.end() + 1 == *range.start() {
+                *last = *last.start() ..= *range.end();
+                continue;
+            }
+        }
+        merged.push(range);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn whole_grid() {
+        let all = vec![0 ..= u64::MAX];
+        assert_eq!(all, morton_ranges(0 ..= u32::MAX, 0 ..= u32::MAX, 1));
+        assert_eq!(all, hilbert_ranges(0 ..= u32::MAX, 0 ..= u32::MAX, 1));
+
+        let (start, end) = (3, 2);
+        assert!(morton_ranges(start ..= end, 0 ..= 9, 4).is_empty());
+    }
+
+    #[test]
+    fn hilbert_is_continuous() {
+        // Within each 2^4 square, consecutive keys are neighboring cells.
+        for key in 0 .. 1 << 10 {
+            let (x, y) = hilbert_decode(key);
+            let (nx, ny) = hilbert_decode(key + 1);
+            let distance = (x as i64 - nx as i64).abs() + (y as i64 - ny as i64).abs();
+            assert_eq!(1, distance, "key {}", key);
+        }
+    }
+
+    #[test]
+    fn qc_round_trip() {
+        fn prop(x: u32, y: u32) -> bool {
+            morton_decode(morton_encode(x, y)) == (x, y)
+                && hilbert_decode(hilbert_encode(x, y)) == (x, y)
+        }
+
+        quickcheck(prop as fn(u32, u32) -> bool);
+    }
+
+    #[test]
+    fn qc_ranges_cover() {
+        fn prop(x: (u8, u8), y: (u8, u8), budget: u8) -> bool {
+            let (x, y) = ((x.0 % 40) as u32 ..= (x.1 % 40) as u32,
+                          (y.0 % 40) as u32 ..= (y.1 % 40) as u32);
+            let budget = budget as usize % 20 + 1;
+
+            let check = |ranges: Vec<RangeInclusive<u64>>, encode: fn(u32, u32) -> u64| {
+                ranges.len() <= budget
+                    && ranges.windows(2).all(|pair| pair[0].end() + 1 < *pair[1].start())
+                    && (0 .. 48).all(|px| (0 .. 48).all(|py| {
+                        let inside = x.contains(&px) && y.contains(&py);
+                        !inside || ranges.iter().any(|range| range.contains(&encode(px, py)))
+                    }))
+            };
+            // With room enough, the ranges hold just the rectangle.
+            let exact = |ranges: Vec<RangeInclusive<u64>>, decode: fn(u64) -> (u32, u32)| {
+                let width = (*x.end() + 1).saturating_sub(*x.start()) as u64;
+                let height = (*y.end() + 1).saturating_sub(*y.start()) as u64;
+                ranges.iter().map(|range| range.end() - range.start() + 1).sum::<u64>()
+                    == width * height
+                    && ranges.into_iter().all(|range| range.into_iter().all(|key| {
+                        let (px, py) = decode(key);
+                        x.contains(&px) && y.contains(&py)
+                    }))
+            };
+
+            check(morton_ranges(x.clone(), y.clone(), budget), morton_encode)
+                && check(hilbert_ranges(x.clone(), y.clone(), budget), hilbert_encode)
+                && exact(morton_ranges(x.clone(), y.clone(), 10_000), morton_decode)
+                && exact(hilbert_ranges(x.clone(), y.clone(), 10_000), hilbert_decode)
+        }
+
+        quickcheck(prop as fn((u8, u8), (u8, u8), u8) -> bool);
+    }
+}
@@ -0,0 +1,278 @@
+//! Compact textual dumps and comparisons, for debugging and validation.
+//!
+//! A dump is a short header followed by the underlying blocks, in either
+//! hex or base64. The header records the logical size, so a dump
+//! restores to a structure equal to the original:
+//!
+//! ```
+//! use succinct::{IntVector, IntVecMut};
+//! use succinct::dump::Dump;
+//!
+//! let mut v = IntVector::<u16>::new(5);
+//! v.push(3);
+//! v.push(17);
+//!
+//! assert_eq!("5:2:h0223", v.dump());
+//! assert_eq!(v, IntVector::restore(&v.dump()).unwrap());
+//! assert_eq!(v, IntVector::restore(&v.dump_base64()).unwrap());
+//! ```
+//!
+//! [`Diff`](struct.Diff.html) summarizes where two structures disagree,
+//! which is handy when checking a port against another library’s output.
+//!
+//! A [`Snapshot`](trait.Snapshot.html) lists a structure’s logical
+//! content rather than its blocks, in a fixed order, so that merging or
+//! rebuilding can be written once for any structure:
+//!
+//! ```
+//! use succinct::dump::{Diff, Snapshot};
+//! use succinct::int_vec::{DeltaVector, EliasFano};
+//!
+//! // Everything that lists increasing values can feed an Elias–Fano
+//! // rebuild.
+//! fn rebuild<T: Snapshot<Item = u64>>(source: &T, universe: u64) -> EliasFano {
+//!     EliasFano::new(universe, source.dump_iter())
+//! }
+//!
+//! let deltas = DeltaVector::new(4, vec![2, 3, 5, 7, 11]);
+//! let ef = rebuild(&deltas, 12);
+//! assert!(Diff::of_snapshots(&deltas, &ef).is_empty());
+//! ```
+
+use std::fmt;
+use std::io::Result;
+
+use num_traits::{PrimInt, Zero};
+
+use access::AccessIter;
+use bit_vec::BitVec;
+use int_vec::IntVec;
+use storage::BlockType;
+
+/// Structures that can list their logical content in a canonical order.
+///
+/// The items are what a rebuild needs: the elements of a sequence in
+/// index order, the keys of a set in increasing order, or the symbols
+/// of an indexed text in text order. Each implementation says which.
+pub trait Snapshot {
+    /// One piece of the content.
+    type Item;
+
+    /// The iterator over the content.
+    type Iter<'a>: Iterator<Item = Self::Item> where Self: 'a;
+
+    /// Lists the content.
+    fn dump_iter(&self) -> Self::Iter<'_>;
+}
+
+/// The elements of an integer vector in order, read one `get` at a
+/// time, for structures without an iterator of their own.
+pub type Elements<'a, V> = AccessIter<'a, V, <V as IntVec>::Block>;
+
+/// Types that can be written to and restored from a compact textual form.
+pub trait Dump: Sized {
+    /// Dumps the receiver with its blocks in hex.
+    fn dump(&self) -> String;
+
+    /// Dumps the receiver with its blocks in base64.
+    ///
+    /// This is about two-thirds the length of [`dump`](#tymethod.dump).
+    fn dump_base64(&self) -> String;
+
+    /// Restores a value from the output of `dump` or `dump_base64`.
+    ///
+    /// Fails with `ErrorKind::InvalidData` if `text` is malformed or
+    /// inconsistent with its header.
+    fn restore(text: &str) -> Result<Self>;
+}
+
+/// Where and how much two sequences differ.
+///
+/// Positions past the end of the shorter sequence count as differences.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Diff {
+    /// The first position at which the sequences differ, if any.
+    pub first: Option<u64>,
+    /// The number of positions at which the sequences differ.
+    pub count: u64,
+}
+
+impl Diff {
+    /// Compares two bit vectors bit by bit.
+    ///
+    /// This works a block at a time, so it is cheap even for long vectors.
+    pub fn of_bits<A, B>(a: &A, b: &B) -> Self
+        where A: BitVec + ?Sized,
+              B: BitVec<Block = A::Block> + ?Sized {
+
+        let common = a.bit_len().min(b.bit_len());
+        let mut result = Diff::default();
+
+        for i in 0 .. A::Block::ceil_div_nbits(common) {
+            let mut x = a.get_block(i) ^ b.get_block(i);
+            if i + 1 == A::Block::ceil_div_nbits(common) {
+                x = x & A::Block::low_mask(A::Block::last_block_bits(common));
+            }
+
+            if x != A::Block::zero() {
+                if result.first.is_none() {
+                    result.first = Some(A::Block::mul_nbits(i)
+                                        + x.trailing_zeros() as u64);
+                }
+                result.count += x.count_ones() as u64;
+            }
+        }
+
+        result.add_length_difference(a.bit_len(), b.bit_len());
+        result
+    }
+
+    /// Compares two integer vectors element by element.
+    pub fn of_elements<A, B>(a: &A, b: &B) -> Self
+        where A: IntVec + ?Sized,
+              B: IntVec<Block = A::Block> + ?Sized {
+
+        let common = a.len().min(b.len());
+        let mut result = Diff::default();
+
+        for i in 0 .. common {
+            if a.get(i) != b.get(i) {
+                if result.first.is_none() {
+                    result.first = Some(i);
+                }
+                result.count += 1;
+            }
+        }
+
+        result.add_length_difference(a.len(), b.len());
+        result
+    }
+
+    /// Compares the snapshots of two structures item by item.
+    ///
+    /// The structures may be of different types, so this checks a
+    /// rebuild against its source.
+    pub fn of_snapshots<A, B>(a: &A, b: &B) -> Self
+        where A: Snapshot + ?Sized,
+              B: Snapshot<Item = A::Item> + ?Sized,
+              A::Item: PartialEq {
+
+        let mut result = Diff::default();
+        let mut a = a.dump_iter();
+        let mut b = b.dump_iter();
+        let mut i = 0;
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => return result,
+                (x, y) => if x != y {
+                    result.first = result.first.or(Some(i));
+                    result.count += 1;
+                },
+            }
+            i += 1;
+        }
+    }
+
+    fn add_length_difference(&mut self, len_a: u64, len_b: u64) {
+        let common = len_a.min(len_b);
+        let excess = len_a.max(len_b) - common;
+
+        if excess > 0 {
+            self.first = self.first.or(Some(common));
+            self.count += excess;
+        }
+    }
+
+    /// Are the compared sequences the same?
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.first {
+            None => write!(formatter, "no differences"),
+            Some(first) => write!(formatter,
+                                  "{} difference(s), the first at {}",
+                                  self.count, first),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::{BitVecMut, BitVecPush, BitVector};
+    use int_vec::{DeltaVector, EliasFano, IntVecMut, IntVector, PforVector};
+    use quickcheck::quickcheck;
+    use wavelet::WaveletMatrix;
+
+    #[test]
+    fn bits() {
+        let mut a: BitVector<u8> = BitVector::with_fill(20, false);
+        let mut b: BitVector<u8> = BitVector::with_fill(20, false);
+        assert!(Diff::of_bits(&a, &b).is_empty());
+
+        a.set_bit(13, true);
+        a.set_bit(19, true);
+        b.set_bit(19, true);
+        b.set_bit(2, true);
+        assert_eq!(Diff { first: Some(2), count: 2 }, Diff::of_bits(&a, &b));
+
+        b.push_bit(false);
+        b.push_bit(false);
+        assert_eq!(Diff { first: Some(2), count: 4 }, Diff::of_bits(&a, &b));
+        assert_eq!(Diff { first: Some(2), count: 4 }, Diff::of_bits(&b, &a));
+    }
+
+    #[test]
+    fn prefix() {
+        let a: BitVector<u32> = BitVector::with_fill(40, true);
+        let b: BitVector<u32> = BitVector::with_fill(35, true);
+        assert_eq!(Diff { first: Some(35), count: 5 }, Diff::of_bits(&a, &b));
+    }
+
+    #[test]
+    fn elements() {
+        let mut a = IntVector::<u32>::with_fill(7, 10, 5);
+        let b = IntVector::<u32>::with_fill(7, 10, 5);
+        assert_eq!("no differences", Diff::of_elements(&a, &b).to_string());
+
+        a.set(4, 100);
+        a.set(8, 0);
+        assert_eq!("2 difference(s), the first at 4",
+                   Diff::of_elements(&a, &b).to_string());
+    }
+
+    #[test]
+    fn qc_snapshots_agree() {
+        fn prop(values: Vec<u16>) -> bool {
+            let mut values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            let vector: IntVector<u64> = values.iter().cloned().collect();
+            let wm = WaveletMatrix::new(values.iter().cloned());
+            let pfor = PforVector::new(values.iter().cloned());
+
+            values.sort();
+            values.dedup();
+            let deltas = DeltaVector::new(3, values.iter().cloned());
+            let ef = EliasFano::new(1 << 16, values.iter().cloned());
+
+            vector.dump_iter().eq(Elements::new(&vector))
+                && Diff::of_snapshots(&vector, &wm).is_empty()
+                && Diff::of_snapshots(&vector, &pfor).is_empty()
+                && Diff::of_snapshots(&deltas, &ef).is_empty()
+                && ef.dump_iter().rev().eq(values.iter().cloned().rev())
+        }
+
+        quickcheck(prop as fn(Vec<u16>) -> bool);
+    }
+
+    #[test]
+    fn snapshots_differ() {
+        let a = IntVector::<u32>::with_fill(7, 10, 5);
+        let mut b = IntVector::<u32>::with_fill(7, 12, 5);
+        b.set(3, 6);
+        assert_eq!(Diff { first: Some(3), count: 3 }, Diff::of_snapshots(&a, &b));
+    }
+}
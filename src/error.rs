@@ -0,0 +1,81 @@
+//! The crate’s error type.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Errors from constructing succinct data structures.
+///
+/// The `try_` constructors return these instead of panicking, so that
+/// sizes coming from untrusted input can be rejected gracefully.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Error {
+    /// The element size was zero or larger than the block size.
+    ElementBits(usize),
+    /// The requested size doesn’t fit in the address space.
+    Overflow,
+    /// The requested storage could not be allocated.
+    Allocation,
+    /// A value was too large for the element size or the universe.
+    ValueTooLarge,
+    /// A value was less than the one before it, or equal to it in a
+    /// sequence that must be strictly increasing.
+    NotIncreasing,
+    /// More values were supplied than were declared.
+    TooManyValues,
+    /// The input didn’t describe a single tree.
+    InvalidTree,
+    /// The document wasn’t valid JSON; the byte offset of the problem.
+    InvalidJson(usize),
+    /// The construction was cancelled through its
+    /// [`Monitor`](build/struct.Monitor.html).
+    Cancelled,
+    /// The construction would need more memory than its budget; the
+    /// estimated peak in bytes.
+    OverBudget(u64),
+    /// A key appeared more than once.
+    DuplicateKey,
+    /// A name, such as a representation chosen in a configuration,
+    /// didn’t match any known one.
+    UnknownName,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ElementBits(bits) =>
+                write!(formatter, "invalid element size: {} bits", bits),
+            Error::Overflow =>
+                write!(formatter, "size overflow"),
+            Error::Allocation =>
+                write!(formatter, "allocation failed"),
+            Error::ValueTooLarge =>
+                write!(formatter, "value too large for element size"),
+            Error::NotIncreasing =>
+                write!(formatter, "values out of order"),
+            Error::TooManyValues =>
+                write!(formatter, "more values than declared"),
+            Error::InvalidTree =>
+                write!(formatter, "not a single tree"),
+            Error::InvalidJson(offset) =>
+                write!(formatter, "invalid JSON at byte {}", offset),
+            Error::Cancelled =>
+                write!(formatter, "construction cancelled"),
+            Error::OverBudget(bytes) =>
+                write!(formatter, "construction needs about {} bytes, over budget", bytes),
+            Error::DuplicateKey =>
+                write!(formatter, "duplicate key"),
+            Error::UnknownName =>
+                write!(formatter, "unknown name"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeSet;
+
+use error::Error;
+use space_usage::SpaceUsage;
+use super::K2Tree;
+
+/// The fewest pending changes that trigger a rebuild.
+const MIN_PENDING: u64 = 256;
+
+/// A [`K2Tree`](struct.K2Tree.html) that takes edge insertions and
+/// removals.
+///
+/// Changes collect in a small overlay beside the static tree, and
+/// queries combine the two. Once the overlay holds more than a sixteenth
+/// as many changes as the tree has edges, and at least 256, the next
+/// change rebuilds the tree with the overlay applied, so each change
+/// costs amortized *O*(log *n*) rebuilding per edge and the overlay
+/// stays small. [`merge`](#method.merge) rebuilds on demand.
+///
+/// # Example
+///
+/// ```
+/// use succinct::graph::DynamicK2Tree;
+///
+/// let mut graph = DynamicK2Tree::new(8);
+/// assert!(graph.insert(1, 2));
+/// assert!(graph.insert(1, 7));
+/// assert!(!graph.insert(1, 2));
+/// assert!(graph.remove(1, 2));
+///
+/// assert_eq!(vec![7], graph.neighbors(1));
+/// graph.merge();
+/// assert_eq!(0, graph.pending());
+/// assert!(graph.has_edge(1, 7));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DynamicK2Tree {
+    base: K2Tree,
+    // Edges absent from `base` and added since.
+    inserted: BTreeSet<(u64, u64)>,
+    // Edges of `base` removed since.
+    removed: BTreeSet<(u64, u64)>,
+}
+
+impl DynamicK2Tree {
+    /// Creates an empty graph on `vertices` vertices.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 2<sup>32</sup> vertices.
+    pub fn new(vertices: u64) -> Self {
+        Self::from_tree(K2Tree::new(vertices, vec![]))
+    }
+
+    /// Starts from an existing tree.
+    pub fn from_tree(base: K2Tree) -> Self {
+        DynamicK2Tree { base, inserted: BTreeSet::new(), removed: BTreeSet::new() }
+    }
+
+    /// The number of vertices.
+    pub fn vertex_count(&self) -> u64 {
+        self.base.vertex_count()
+    }
+
+    /// The number of edges.
+    pub fn edge_count(&self) -> u64 {
+        self.base.edge_count() + self.inserted.len() as u64 - self.removed.len() as u64
+    }
+
+    /// The number of changes not yet merged into the static tree.
+    pub fn pending(&self) -> u64 {
+        (self.inserted.len() + self.removed.len()) as u64
+    }
+
+    /// The static tree, without the pending changes.
+    pub fn base(&self) -> &K2Tree {
+        &self.base
+    }
+
+    /// Adds an edge, returning whether it was absent, or returns
+    /// `Error::ValueTooLarge` if an endpoint isn’t a vertex.
+    pub fn try_insert(&mut self, row: u64, column: u64) -> Result<bool, Error> {
+        self.check(row, column)?;
+        let added = if self.base.has_edge(row, column) {
+            self.removed.remove(&(row, column))
+        } else {
+            self.inserted.insert((row, column))
+        };
+        self.merge_if_due();
+        Ok(added)
+    }
+
+    /// Adds an edge, returning whether it was absent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an endpoint isn’t a vertex.
+    pub fn insert(&mut self, row: u64, column: u64) -> bool {
+        self.try_insert(row, column).expect("DynamicK2Tree::insert")
+    }
+
+    /// Removes an edge, returning whether it was present.
+    pub fn remove(&mut self, row: u64, column: u64) -> bool {
+        let removed = if self.base.has_edge(row, column) {
+            self.removed.insert((row, column))
+        } else {
+            self.inserted.remove(&(row, column))
+        };
+        self.merge_if_due();
+        removed
+    }
+
+    fn check(&self, row: u64, column: u64) -> Result<(), Error> {
+        let vertices = self.vertex_count();
+        if row < vertices && column < vertices {Ok(())} else {Err(Error::ValueTooLarge)}
+    }
+
+    /// Is there an edge from `row` to `column`?
+    pub fn has_edge(&self, row: u64, column: u64) -> bool {
+        if self.base.has_edge(row, column) {
+            !self.removed.contains(&(row, column))
+        } else {
+            self.inserted.contains(&(row, column))
+        }
+    }
+
+    /// The targets of the edges out of `row`, in increasing order.
+    pub fn neighbors(&self, row: u64) -> Vec<u64> {
+        let mut result: Vec<u64> = self.base.neighbors(row).into_iter()
+            .filter(|&column| !self.removed.contains(&(row, column)))
+            .collect();
+        result.extend(self.inserted.range((row, 0) .. (row + 1, 0)).map(|&(_, column)| column));
+        result.sort();
+        result
+    }
+
+    /// The sources of the edges into `column`, in increasing order.
+    pub fn reverse_neighbors(&self, column: u64) -> Vec<u64> {
+        let mut result: Vec<u64> = self.base.reverse_neighbors(column).into_iter()
+            .filter(|&row| !self.removed.contains(&(row, column)))
+            .collect();
+        result.extend(self.inserted.iter()
+                          .filter(|edge| edge.1 == column)
+                          .map(|&(row, _)| row));
+        result.sort();
+        result
+    }
+
+    fn merge_if_due(&mut self) {
+        if self.pending() > MIN_PENDING.max(self.base.edge_count() / 16) {
+            self.merge();
+        }
+    }
+
+    /// Rebuilds the static tree with the pending changes applied.
+    pub fn merge(&mut self) {
+        if self.pending() == 0 { return; }
+
+        let removed = &self.removed;
+        let edges: Vec<(u64, u64)> = self.base.edges().into_iter()
+            .filter(|edge| !removed.contains(edge))
+            .chain(self.inserted.iter().cloned())
+            .collect();
+        self.base = K2Tree::new(self.vertex_count(), edges);
+        self.inserted.clear();
+        self.removed.clear();
+    }
+}
+
+impl SpaceUsage for DynamicK2Tree {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        // A B-tree entry holds two words, plus overhead we don’t model.
+        self.base.heap_bytes()
+            + self.pending() as usize * 2 * ::std::mem::size_of::<u64>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn automatic_merge() {
+        let mut graph = DynamicK2Tree::new(100);
+        for i in 0 .. 600 {
+            graph.insert(i % 100, i / 100);
+        }
+        assert!(graph.pending() <= MIN_PENDING);
+        assert_eq!(600, graph.edge_count());
+        assert_eq!((0 .. 6).collect::<Vec<_>>(), graph.neighbors(42));
+        assert_eq!(Some(Error::ValueTooLarge), graph.try_insert(100, 0).err());
+    }
+
+    #[test]
+    fn qc_matches_btree() {
+        // Each step inserts an edge, or removes it if the flag is set.
+        fn prop(steps: Vec<(u8, u8, bool)>) -> bool {
+            let vertices = 12;
+            let mut graph = DynamicK2Tree::from_tree(K2Tree::new(vertices, vec![(0, 0), (3, 4)]));
+            let mut model: BTreeSet<(u64, u64)> = vec![(0, 0), (3, 4)].into_iter().collect();
+
+            for (i, (u, v, remove)) in steps.into_iter().enumerate() {
+                let edge = (u as u64 % vertices, v as u64 % vertices);
+                let changed = if remove {graph.remove(edge.0, edge.1)} else {graph.insert(edge.0, edge.1)};
+                let expected = if remove {model.remove(&edge)} else {model.insert(edge)};
+                if changed != expected { return false; }
+                if i % 7 == 0 { graph.merge(); }
+            }
+
+            graph.edge_count() == model.len() as u64
+                && (0 .. vertices).all(|u| {
+                    let out: Vec<u64> = model.iter().filter(|e| e.0 == u).map(|e| e.1).collect();
+                    let into: Vec<u64> = model.iter().filter(|e| e.1 == u).map(|e| e.0).collect();
+                    graph.neighbors(u) == out && graph.reverse_neighbors(u) == into
+                })
+        }
+
+        quickcheck(prop as fn(Vec<(u8, u8, bool)>) -> bool);
+    }
+}
@@ -0,0 +1,262 @@
+use bit_vec::{BitVec, BitVecPush, BitVector};
+use curve::morton_encode;
+use error::Error;
+use rank::{BitRankSupport, Rank9};
+use space_usage::SpaceUsage;
+
+/// An immutable directed graph as a k²-tree, with *k* = 2, over its
+/// adjacency matrix.
+///
+/// The matrix, padded to a power of two, is split into four quadrants,
+/// each of those into four, and so on down to single cells. Each level
+/// records four bits for every nonempty quadrant of the level above,
+/// saying which of its quadrants are nonempty, and the levels are
+/// concatenated. The children of the *r*th set bit are then the four
+/// bits at 4*r*, so a query descends with a rank per level. Empty
+/// regions cost nothing, which suits sparse graphs with clustered edges
+/// such as web graphs.
+///
+/// # Example
+///
+/// ```
+/// use succinct::graph::K2Tree;
+///
+/// let tree = K2Tree::new(6, vec![(0, 1), (0, 5), (4, 4), (5, 0)]);
+/// assert!(tree.has_edge(0, 5));
+/// assert!(!tree.has_edge(5, 5));
+/// assert_eq!(vec![1, 5], tree.neighbors(0));
+/// assert_eq!(vec![4], tree.reverse_neighbors(4));
+/// ```
+#[derive(Clone, Debug)]
+pub struct K2Tree {
+    vertices: u64,
+    edges: u64,
+    // The padded matrix is 1 << levels wide, with a level of blocks per
+    // halving.
+    levels: usize,
+    bits: Rank9<BitVector<u64>>,
+}
+
+impl K2Tree {
+    /// Creates a graph on `vertices` vertices with the given edges, in
+    /// any order, with repeats ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an endpoint isn’t a vertex, or there are more than
+    /// 2<sup>32</sup> vertices.
+    pub fn new<I>(vertices: u64, edges: I) -> Self
+        where I: IntoIterator<Item = (u64, u64)> {
+
+        Self::try_new(vertices, edges).expect("K2Tree::new")
+    }
+
+    /// Creates a graph on `vertices` vertices with the given edges, or
+    /// returns `Error::ValueTooLarge` if an endpoint isn’t a vertex, or
+    /// `Error::Overflow` if there are more than 2<sup>32</sup> vertices.
+    pub fn try_new<I>(vertices: u64, edges: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = (u64, u64)> {
+
+        if vertices > 1 << 32 { return Err(Error::Overflow); }
+        let levels = (64 - vertices.saturating_sub(1).leading_zeros() as usize).max(1);
+
+        // In Z order, each level’s quadrants come out in the order the
+        // level is laid out, children grouped under their parents.
+        let mut keys = Vec::new();
+        for (row, column) in edges {
+            if row >= vertices || column >= vertices { return Err(Error::ValueTooLarge); }
+            keys.push(interleave(row, column));
+        }
+        keys.sort();
+        keys.dedup();
+
+        let mut bits = BitVector::new();
+        for level in 0 .. levels {
+            let shift = 2 * (levels - 1 - level);
+            let mut block = 0;
+            let mut parent = None;
+            for &key in &keys {
+                let prefix = key >> shift;
+                if parent != Some(prefix >> 2) {
+                    if parent.is_some() { push_block(&mut bits, block); }
+                    parent = Some(prefix >> 2);
+                    block = 0;
+                }
+                block |= 1 << (prefix & 3);
+            }
+            if parent.is_some() { push_block(&mut bits, block); }
+        }
+
+        Ok(K2Tree {
+            vertices,
+            edges: keys.len() as u64,
+            levels,
+            bits: Rank9::new(bits),
+        })
+    }
+
+    /// The number of vertices.
+    pub fn vertex_count(&self) -> u64 {
+        self.vertices
+    }
+
+    /// The number of edges.
+    pub fn edge_count(&self) -> u64 {
+        self.edges
+    }
+
+    // The position of the first child of the set bit at `position`.
+    fn children(&self, position: u64) -> u64 {
+        4 * self.bits.rank1(position)
+    }
+
+    /// Is there an edge from `row` to `column`?
+    pub fn has_edge(&self, row: u64, column: u64) -> bool {
+        if self.edges == 0 || row >= self.vertices || column >= self.vertices {
+            return false;
+        }
+
+        let mut block = 0;
+        for level in 0 .. self.levels {
+            let shift = self.levels - 1 - level;
+            let position = block + quadrant(row >> shift & 1, column >> shift & 1);
+            if !self.bits.get_bit(position) { return false; }
+            block = self.children(position);
+        }
+        true
+    }
+
+    // The cells set in row `fixed`, or column `fixed` if `by_column`.
+    fn line(&self, fixed: u64, by_column: bool) -> Vec<u64> {
+        let mut result = Vec::new();
+        if self.edges == 0 || fixed >= self.vertices { return result; }
+
+        // Each entry is a block, its level, and the cells before it.
+        let mut stack = vec![(0, 0, 0)];
+        while let Some((block, level, base)) = stack.pop() {
+            let shift = self.levels - 1 - level;
+            let bit = fixed >> shift & 1;
+            for other in (0 .. 2).rev() {
+                let position = block + if by_column {quadrant(other, bit)} else {quadrant(bit, other)};
+                if !self.bits.get_bit(position) { continue; }
+
+                let cell = base | other << shift;
+                if level + 1 == self.levels {
+                    result.push(cell);
+                } else {
+                    stack.push((self.children(position), level + 1, cell));
+                }
+            }
+        }
+        result.sort();
+        result
+    }
+
+    /// The targets of the edges out of `row`, in increasing order.
+    pub fn neighbors(&self, row: u64) -> Vec<u64> {
+        self.line(row, false)
+    }
+
+    /// The sources of the edges into `column`, in increasing order.
+    pub fn reverse_neighbors(&self, column: u64) -> Vec<u64> {
+        self.line(column, true)
+    }
+
+    /// Every edge, in Z order.
+    pub fn edges(&self) -> Vec<(u64, u64)> {
+        let mut result = Vec::with_capacity(self.edges as usize);
+        if self.edges == 0 { return result; }
+
+        let mut stack = vec![(0, 0, 0, 0)];
+        while let Some((block, level, row, column)) = stack.pop() {
+            let shift = self.levels - 1 - level;
+            let leaf = level + 1 == self.levels;
+            for i in 0 .. 4 {
+                // Cells come out in order, and blocks go on the stack in
+                // reverse so that they come off in order.
+                let digit = if leaf {i} else {3 - i};
+                let position = block + digit;
+                if !self.bits.get_bit(position) { continue; }
+
+                let row = row | (digit >> 1) << shift;
+                let column = column | (digit & 1) << shift;
+                if leaf {
+                    result.push((row, column));
+                } else {
+                    stack.push((self.children(position), level + 1, row, column));
+                }
+            }
+        }
+        result
+    }
+}
+
+impl SpaceUsage for K2Tree {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.bits.heap_bytes()
+    }
+}
+
+// Which of a block’s four bits is the quadrant at `row` and `column`,
+// each 0 or 1.
+fn quadrant(row: u64, column: u64) -> u64 {
+    row << 1 | column
+}
+
+fn push_block(bits: &mut BitVector<u64>, block: u64) {
+    for digit in 0 .. 4 {
+        bits.push_bit(block >> digit & 1 == 1);
+    }
+}
+
+// The Morton key of a cell, with `row`’s bits above.
+fn interleave(row: u64, column: u64) -> u64 {
+    morton_encode(column as u32, row as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let tree = K2Tree::new(10, vec![]);
+        assert!(!tree.has_edge(0, 0));
+        assert!(tree.neighbors(3).is_empty());
+        assert!(tree.edges().is_empty());
+        assert_eq!(0, K2Tree::new(0, vec![]).edge_count());
+    }
+
+    #[test]
+    fn bad_edges() {
+        assert_eq!(Some(Error::ValueTooLarge), K2Tree::try_new(3, vec![(3, 0)]).err());
+        assert_eq!(Some(Error::Overflow), K2Tree::try_new((1 << 32) + 1, vec![]).err());
+    }
+
+    #[test]
+    fn qc_matches_btree() {
+        fn prop(edges: Vec<(u8, u8)>, vertices: u8) -> bool {
+            let vertices = vertices as u64 % 40 + 1;
+            let edges: BTreeSet<(u64, u64)> = edges.into_iter()
+                .map(|(u, v)| (u as u64 % vertices, v as u64 % vertices))
+                .collect();
+            let tree = K2Tree::new(vertices, edges.iter().cloned());
+
+            let mut z_order: Vec<(u64, u64)> = edges.iter().cloned().collect();
+            z_order.sort_by_key(|&(u, v)| interleave(u, v));
+            tree.edges() == z_order
+                && (0 .. vertices).all(|u| {
+                    let out: Vec<u64> = edges.iter().filter(|e| e.0 == u).map(|e| e.1).collect();
+                    let into: Vec<u64> = edges.iter().filter(|e| e.1 == u).map(|e| e.0).collect();
+                    tree.neighbors(u) == out && tree.reverse_neighbors(u) == into
+                        && (0 .. vertices).all(|v| tree.has_edge(u, v) == edges.contains(&(u, v)))
+                })
+        }
+
+        quickcheck(prop as fn(Vec<(u8, u8)>, u8) -> bool);
+    }
+}
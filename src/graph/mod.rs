@@ -0,0 +1,10 @@
+//! Succinct graphs.
+
+mod dynamic_k2_tree;
+pub use self::dynamic_k2_tree::*;
+
+mod k2_tree;
+pub use self::k2_tree::*;
+
+mod static_graph;
+pub use self::static_graph::*;
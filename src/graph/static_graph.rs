@@ -0,0 +1,230 @@
+use build::Monitor;
+use error::Error;
+use int_vec::{EliasFano, EliasFanoBuilder, EliasFanoCursor, IntVec};
+use space_usage::SpaceUsage;
+
+/// An immutable directed graph in compressed sparse row form, with both
+/// arrays in Elias–Fano coding.
+///
+/// Vertices are `0 .. vertex_count()`. The edges, sorted by source and
+/// then target, are encoded as the single increasing sequence of
+/// `source * vertex_count() + target`, so each adjacency list is a run
+/// of it, and the offsets of the runs are a second, non-decreasing,
+/// sequence. Iterating the neighbors of a vertex decodes just its run,
+/// and testing for an edge is one `next_geq`. That takes about
+/// 2 + log(*n*² / *m*) bits per edge plus 2 + log(*m* / *n*) per vertex.
+///
+/// # Example
+///
+/// ```
+/// use succinct::graph::StaticGraph;
+///
+/// let graph = StaticGraph::new(4, vec![(0, 1), (0, 2), (2, 3), (3, 0), (0, 1)]);
+/// assert_eq!(4, graph.edge_count());
+/// assert_eq!(2, graph.degree(0));
+/// assert_eq!(vec![1, 2], graph.neighbors(0).collect::<Vec<_>>());
+/// assert!(graph.has_edge(3, 0));
+/// assert!(!graph.has_edge(0, 3));
+/// assert_eq!(0, graph.degree(1));
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticGraph {
+    vertices: u64,
+    // Where each vertex’s run of edges starts, and a final end.
+    offsets: EliasFano,
+    edges: EliasFano,
+}
+
+impl StaticGraph {
+    /// Creates a graph on `vertices` vertices with the given edges, in
+    /// any order, with repeats ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an endpoint isn’t a vertex, or there are too many
+    /// vertices to number the edges in a `u64`.
+    pub fn new<I>(vertices: u64, edges: I) -> Self
+        where I: IntoIterator<Item = (u64, u64)> {
+
+        Self::try_new(vertices, edges).expect("StaticGraph::new")
+    }
+
+    /// Creates a graph on `vertices` vertices with the given edges, or
+    /// returns `Error::ValueTooLarge` if an endpoint isn’t a vertex, or
+    /// `Error::Overflow` if `vertices` squared exceeds a `u64`.
+    pub fn try_new<I>(vertices: u64, edges: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = (u64, u64)> {
+
+        Self::with_monitor(vertices, edges, &mut Monitor::new())
+    }
+
+    /// Creates a graph on `vertices` vertices with the given edges, or
+    /// returns `Error::ValueTooLarge` if an endpoint isn’t a vertex,
+    /// `Error::Overflow` if `vertices` squared exceeds a `u64`, or
+    /// `Error::OverBudget` if the encoding would need more memory than
+    /// `monitor` allows.
+    pub fn with_monitor<I>(vertices: u64, edges: I, monitor: &mut Monitor)
+                           -> Result<Self, Error>
+        where I: IntoIterator<Item = (u64, u64)> {
+
+        let universe = vertices.checked_mul(vertices).ok_or(Error::Overflow)?;
+
+        let mut keys = Vec::new();
+        for (source, target) in edges {
+            if source >= vertices || target >= vertices {
+                return Err(Error::ValueTooLarge);
+            }
+            keys.push(source * vertices + target);
+        }
+        keys.sort();
+        keys.dedup();
+
+        let len = keys.len() as u64;
+        monitor.reserve((EliasFano::estimate_size(universe, len)
+                         + EliasFano::estimate_size(len + 1, vertices + 1)) as u64)?;
+
+        // The offsets are read off the sorted keys, with no scratch space
+        // per vertex.
+        let mut offsets = EliasFanoBuilder::multiset(len + 1, vertices + 1);
+        let mut end = 0;
+        for vertex in 0 ..= vertices {
+            while end < keys.len() && keys[end] < vertex * vertices {
+                end += 1;
+            }
+            offsets.push(end as u64)?;
+        }
+
+        Ok(StaticGraph {
+            vertices,
+            offsets: offsets.build(),
+            edges: EliasFano::new(universe, keys),
+        })
+    }
+
+    /// The number of vertices.
+    pub fn vertex_count(&self) -> u64 {
+        self.vertices
+    }
+
+    /// The number of edges.
+    pub fn edge_count(&self) -> u64 {
+        self.edges.len()
+    }
+
+    /// The number of edges out of `vertex`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `vertex` isn’t a vertex.
+    pub fn degree(&self, vertex: u64) -> u64 {
+        assert!(vertex < self.vertices, "StaticGraph::degree: not a vertex");
+        self.offsets.get(vertex + 1) - self.offsets.get(vertex)
+    }
+
+    /// The targets of the edges out of `vertex`, in increasing order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `vertex` isn’t a vertex.
+    pub fn neighbors(&self, vertex: u64) -> Neighbors<'_> {
+        let remaining = self.degree(vertex);
+        let mut cursor = self.edges.cursor();
+        if remaining > 0 {
+            cursor.next_geq(vertex * self.vertices);
+        }
+        Neighbors { cursor, base: vertex * self.vertices, remaining }
+    }
+
+    /// Is there an edge from `source` to `target`?
+    pub fn has_edge(&self, source: u64, target: u64) -> bool {
+        source < self.vertices && target < self.vertices
+            && self.edges.contains(source * self.vertices + target)
+    }
+}
+
+impl SpaceUsage for StaticGraph {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.offsets.heap_bytes() + self.edges.heap_bytes()
+    }
+}
+
+/// The neighbors of a vertex of a [`StaticGraph`](struct.StaticGraph.html).
+#[derive(Clone, Debug)]
+pub struct Neighbors<'a> {
+    cursor: EliasFanoCursor<'a>,
+    base: u64,
+    remaining: u64,
+}
+
+impl<'a> Iterator for Neighbors<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 { return None; }
+        self.remaining -= 1;
+        self.cursor.next().map(|key| key - self.base)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl<'a> ExactSizeIterator for Neighbors<'a> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let graph = StaticGraph::new(0, vec![]);
+        assert_eq!(0, graph.edge_count());
+        assert!(!graph.has_edge(0, 0));
+
+        let graph = StaticGraph::new(3, vec![]);
+        assert_eq!(0, graph.neighbors(2).count());
+    }
+
+    #[test]
+    fn bad_edges() {
+        assert_eq!(Some(Error::ValueTooLarge), StaticGraph::try_new(2, vec![(0, 2)]).err());
+        assert_eq!(Some(Error::Overflow), StaticGraph::try_new(1 << 32, vec![]).err());
+
+        let mut monitor = Monitor::new().with_memory_budget(1 << 20);
+        match StaticGraph::with_monitor((1 << 32) - 1, vec![], &mut monitor) {
+            Err(Error::OverBudget(bytes)) => assert!(bytes > 1 << 28),
+            other => panic!("{:?}", other.map(|graph| graph.vertex_count())),
+        }
+    }
+
+    #[test]
+    fn qc_matches_btree() {
+        fn prop(edges: Vec<(u8, u8)>, probes: Vec<(u8, u8)>) -> bool {
+            let vertices = 20;
+            let edges: BTreeSet<(u64, u64)> = edges.into_iter()
+                .map(|(u, v)| (u as u64 % vertices, v as u64 % vertices))
+                .collect();
+            let graph = StaticGraph::new(vertices, edges.iter().cloned());
+
+            graph.edge_count() == edges.len() as u64
+                && (0 .. vertices).all(|u| {
+                    let expected: Vec<u64> = edges.range((u, 0) .. (u + 1, 0))
+                                                  .map(|&(_, v)| v)
+                                                  .collect();
+                    graph.neighbors(u).collect::<Vec<_>>() == expected
+                        && graph.degree(u) == expected.len() as u64
+                })
+                && probes.into_iter().all(|(u, v)| {
+                    let (u, v) = (u as u64 % 25, v as u64 % 25);
+                    graph.has_edge(u, v) == edges.contains(&(u, v))
+                })
+        }
+
+        quickcheck(prop as fn(Vec<(u8, u8)>, Vec<(u8, u8)>) -> bool);
+    }
+}
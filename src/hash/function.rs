@@ -0,0 +1,344 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use error::Error;
+use space_usage::SpaceUsage;
+use super::hasher::{mix, KeyHasher};
+
+/// Cells per key in each layer, over three: peeling a random 3-uniform
+/// hypergraph succeeds with high probability above 1.222.
+const CELLS_PER_KEY: f64 = 1.23;
+
+/// Extra cells per layer, so that small layers peel too.
+const EXTRA_CELLS: u64 = 32;
+
+/// A map from a fixed set of keys to `u64` values that doesn’t store the
+/// keys: looking up a key that wasn’t in the set returns an arbitrary
+/// value.
+///
+/// The values are Huffman coded, and bit *i* of each key’s codeword is
+/// the exclusive or of three bits in layer *i*, chosen by hashing the
+/// key. Solving for the layers by peeling—the construction of XOR filters
+/// and Bloomier filters—takes about 1.23 bits per codeword bit, so the
+/// whole takes about 1.23 times the Huffman-coded size of the values,
+/// which is less than *n*(*H* + 1) bits for *n* keys whose values have
+/// empirical entropy *H*. A function with only one value takes no space
+/// per key at all.
+///
+/// Paired with a filter or a check on the returned value, it can stand
+/// in for a hash map whose keys are never needed back.
+///
+/// # Example
+///
+/// ```
+/// use succinct::hash::StaticFunction;
+///
+/// let colors = vec![("apple", 0), ("banana", 1), ("cherry", 0),
+///                   ("lime", 2), ("strawberry", 0)];
+/// let function = StaticFunction::new(colors.iter().cloned());
+///
+/// assert_eq!(1, function.get("banana"));
+/// assert_eq!(0, function.get("strawberry"));
+/// assert_eq!(5, function.len());
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticFunction {
+    len: u64,
+    // The distinct values in canonical code order, and the number of
+    // codewords of each length.
+    symbols: Vec<u64>,
+    counts: Vec<u64>,
+    layers: Vec<Layer>,
+}
+
+#[derive(Clone, Debug)]
+struct Layer {
+    seed: u64,
+    // The cells in each third of `bits`.
+    segment: u64,
+    bits: BitVector<u64>,
+}
+
+// A key’s equation in a layer: its three cells and its bit.
+#[derive(Clone, Copy, Debug)]
+struct Equation {
+    cells: [u64; 3],
+    bit: bool,
+}
+
+impl Layer {
+    fn cells(&self, signature: (u64, u64)) -> [u64; 3] {
+        cells(self.seed, self.segment, signature)
+    }
+
+    fn get(&self, signature: (u64, u64)) -> bool {
+        self.cells(signature).iter().fold(false, |bit, &cell| bit ^ self.bits.get_bit(cell))
+    }
+}
+
+fn cells(seed: u64, segment: u64, (a, b): (u64, u64)) -> [u64; 3] {
+    let mut result = [0; 3];
+    for (j, cell) in result.iter_mut().enumerate() {
+        let hash = mix(a ^ mix(b.wrapping_add(seed).wrapping_add(j as u64)));
+        *cell = j as u64 * segment + reduce(hash, segment);
+    }
+    result
+}
+
+// Maps a hash onto `0 .. n` by multiplying rather than dividing.
+fn reduce(hash: u64, n: u64) -> u64 {
+    ((hash as u128 * n as u128) >> 64) as u64
+}
+
+impl StaticFunction {
+    /// Stores the value of each key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key appears more than once.
+    pub fn new<K, I>(pairs: I) -> Self
+        where K: Hash,
+              I: IntoIterator<Item = (K, u64)> {
+
+        Self::try_new(pairs).expect("StaticFunction::new")
+    }
+
+    /// Stores the value of each key, or returns `Error::DuplicateKey` if
+    /// a key appears more than once.
+    pub fn try_new<K, I>(pairs: I) -> Result<Self, Error>
+        where K: Hash,
+              I: IntoIterator<Item = (K, u64)> {
+
+        let mut keys: Vec<((u64, u64), u64)> = pairs.into_iter()
+            .map(|(key, value)| (signature(&key), value))
+            .collect();
+        keys.sort_unstable_by_key(|&(signature, _)| signature);
+        if keys.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(Error::DuplicateKey);
+        }
+
+        let (symbols, counts, codes) = canonical_code(keys.iter().map(|&(_, value)| value));
+        let mut layers = Vec::with_capacity(counts.len().saturating_sub(1));
+        for layer in 0 .. counts.len().saturating_sub(1) {
+            let equations: Vec<((u64, u64), bool)> = keys.iter().filter_map(|&(signature, value)| {
+                let (code, len) = codes[&value];
+                if layer < len {
+                    Some((signature, code >> (len - 1 - layer) & 1 == 1))
+                } else {
+                    None
+                }
+            }).collect();
+            layers.push(solve(&equations, layer as u64));
+        }
+
+        Ok(StaticFunction {
+            len: keys.len() as u64,
+            symbols,
+            counts,
+            layers,
+        })
+    }
+
+    /// The number of keys.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Are there no keys?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The value stored for `key`, or an arbitrary one if `key` wasn’t
+    /// given when building.
+    pub fn get<K: Hash + ?Sized>(&self, key: &K) -> u64 {
+        let signature = signature(key);
+
+        // Canonical decoding: codewords of each length are consecutive,
+        // starting at `first`.
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        if self.counts.first() == Some(&1) { return self.symbols[0]; }
+        for (layer, &count) in self.layers.iter().zip(&self.counts[1 ..]) {
+            code |= layer.get(signature) as u64;
+            if code < first + count {
+                return self.symbols[(index + code - first) as usize];
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        self.symbols.first().cloned().unwrap_or(0)
+    }
+}
+
+// Two independent hashes of `key`, which the layers’ cells are drawn from.
+fn signature<K: Hash + ?Sized>(key: &K) -> (u64, u64) {
+    (KeyHasher::hash(0, key), KeyHasher::hash(!0, key))
+}
+
+// Each value’s codeword and its length.
+type Codewords = HashMap<u64, (u64, usize)>;
+
+// Huffman codes the values, returning the symbols in canonical order,
+// the number of codewords of each length from 0 up, and each value’s
+// codeword and length.
+fn canonical_code<I>(values: I) -> (Vec<u64>, Vec<u64>, Codewords)
+    where I: IntoIterator<Item = u64> {
+
+    let mut frequencies: HashMap<u64, u64> = HashMap::new();
+    for value in values {
+        *frequencies.entry(value).or_insert(0) += 1;
+    }
+    let mut symbols: Vec<u64> = frequencies.keys().cloned().collect();
+    symbols.sort_unstable();
+
+    // Merge the two lightest trees until one is left; a symbol’s code is
+    // as long as its leaf is deep.
+    let mut parents = vec![0; symbols.len()];
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = symbols.iter().enumerate()
+        .map(|(i, symbol)| Reverse((frequencies[symbol], i)))
+        .collect();
+    while heap.len() > 1 {
+        let Reverse((left, i)) = heap.pop().unwrap();
+        let Reverse((right, j)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(0);
+        parents[i] = node;
+        parents[j] = node;
+        heap.push(Reverse((left + right, node)));
+    }
+
+    let mut depths = vec![0usize; parents.len()];
+    for node in (0 .. parents.len().saturating_sub(1)).rev() {
+        depths[node] = depths[parents[node]] + 1;
+    }
+    let mut lengths: Vec<(usize, u64)> = symbols.iter().enumerate()
+        .map(|(i, &symbol)| (depths[i], symbol))
+        .collect();
+    lengths.sort_unstable();
+
+    let max_len = lengths.last().map_or(0, |&(len, _)| len);
+    assert!(max_len <= 64, "StaticFunction: code too long");
+    let mut counts = vec![0; max_len + 1];
+    let mut codes = HashMap::new();
+    let (mut code, mut len) = (0u64, 0);
+    for &(symbol_len, symbol) in &lengths {
+        code <<= symbol_len - len;
+        len = symbol_len;
+        counts[len] += 1;
+        codes.insert(symbol, (code, len));
+        code = code.wrapping_add(1);
+    }
+
+    (lengths.into_iter().map(|(_, symbol)| symbol).collect(), counts, codes)
+}
+
+// Finds a layer in which each key’s three bits have its bit as their
+// exclusive or, retrying with new seeds until the hypergraph peels.
+fn solve(keys: &[((u64, u64), bool)], layer: u64) -> Layer {
+    let segment = ((keys.len() as f64 * CELLS_PER_KEY / 3.0).ceil() as u64) + EXTRA_CELLS / 3;
+    for attempt in 0 .. {
+        let seed = mix(layer << 32 | attempt);
+        let equations: Vec<Equation> = keys.iter().map(|&(signature, bit)| {
+            Equation { cells: cells(seed, segment, signature), bit }
+        }).collect();
+        if let Some(bits) = peel(&equations, 3 * segment) {
+            return Layer { seed, segment, bits };
+        }
+    }
+    unreachable!()
+}
+
+// Repeatedly removes an equation that is alone on one of its cells, then
+// assigns the cells in reverse order, or returns `None` if a core remains.
+fn peel(equations: &[Equation], cell_count: u64) -> Option<BitVector<u64>> {
+    let mut degrees = vec![0u32; cell_count as usize];
+    let mut incident = vec![0usize; cell_count as usize];
+    for (e, equation) in equations.iter().enumerate() {
+        for &cell in &equation.cells {
+            degrees[cell as usize] += 1;
+            incident[cell as usize] ^= e;
+        }
+    }
+
+    let mut queue: Vec<usize> = (0 .. degrees.len()).filter(|&c| degrees[c] == 1).collect();
+    let mut order = Vec::with_capacity(equations.len());
+    while let Some(cell) = queue.pop() {
+        if degrees[cell] != 1 { continue; }
+        let e = incident[cell];
+        order.push((e, cell));
+        for &other in &equations[e].cells {
+            let other = other as usize;
+            degrees[other] -= 1;
+            incident[other] ^= e;
+            if degrees[other] == 1 { queue.push(other); }
+        }
+    }
+    if order.len() < equations.len() { return None; }
+
+    let mut bits = BitVector::with_fill(cell_count, false);
+    for &(e, cell) in order.iter().rev() {
+        let equation = equations[e];
+        let others = equation.cells.iter()
+            .filter(|&&other| other != cell as u64)
+            .fold(false, |bit, &other| bit ^ bits.get_bit(other));
+        bits.set_bit(cell as u64, equation.bit ^ others);
+    }
+    Some(bits)
+}
+
+impl SpaceUsage for StaticFunction {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.symbols.heap_bytes() + self.counts.heap_bytes()
+            + self.layers.iter().map(|layer| layer.bits.heap_bytes()).sum::<usize>()
+            + self.layers.capacity() * ::std::mem::size_of::<Layer>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn small() {
+        let empty = StaticFunction::new(Vec::<(u64, u64)>::new());
+        assert!(empty.is_empty());
+        assert_eq!(0, empty.get(&5u64));
+
+        let constant = StaticFunction::new((0 .. 1000u64).map(|key| (key, 42)));
+        assert_eq!(42, constant.get(&999u64));
+        assert!(constant.layers.is_empty());
+
+        assert_eq!(Some(Error::DuplicateKey),
+                   StaticFunction::try_new(vec![("a", 1), ("b", 2), ("a", 1)]).err());
+    }
+
+    #[test]
+    fn near_entropy() {
+        // 90% zeros: entropy about 0.75 bits, Huffman about 1.3 bits.
+        let n = 50_000u64;
+        let value = |key: u64| if key % 10 == 0 {key % 7 + 1} else {0};
+        let function = StaticFunction::new((0 .. n).map(|key| (key, value(key))));
+        assert!((0 .. n).all(|key| function.get(&key) == value(key)));
+
+        let layer_bits: u64 = function.layers.iter().map(|layer| layer.bits.bit_len()).sum();
+        assert!(layer_bits < n * 7 / 4, "{} bits", layer_bits);
+    }
+
+    #[test]
+    fn qc_returns_values() {
+        fn prop(pairs: Vec<(u32, u8)>) -> bool {
+            let map: HashMap<u32, u64> = pairs.into_iter()
+                .map(|(key, value)| (key, value as u64 % 13)).collect();
+            let function = StaticFunction::new(map.iter().map(|(&key, &value)| (key, value)));
+            map.iter().all(|(key, &value)| function.get(key) == value)
+        }
+
+        quickcheck(prop as fn(Vec<(u32, u8)>) -> bool);
+    }
+}
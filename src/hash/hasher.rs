@@ -0,0 +1,79 @@
+use std::hash::{Hash, Hasher};
+
+/// A seeded 64-bit hasher whose output is the same on every platform and
+/// release, unlike `std`’s `DefaultHasher`.
+///
+/// It folds its input a word at a time with a multiply-rotate step and
+/// finishes with the SplitMix64 mixer, which is plenty for spreading keys
+/// over a table, though not for resisting an adversary who knows the
+/// seed.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyHasher {
+    state: u64,
+}
+
+const MULTIPLIER: u64 = 0x9E37_79B9_7F4A_7C15;
+
+impl KeyHasher {
+    /// Starts a hash with the given seed.
+    pub fn new(seed: u64) -> Self {
+        KeyHasher { state: mix(seed) }
+    }
+
+    /// Hashes `key` with the given seed.
+    pub fn hash<K: Hash + ?Sized>(seed: u64, key: &K) -> u64 {
+        let mut hasher = KeyHasher::new(seed);
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn fold(&mut self, word: u64) {
+        self.state = (self.state ^ word).wrapping_mul(MULTIPLIER).rotate_left(31);
+    }
+}
+
+impl Hasher for KeyHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.fold(u64::from_le_bytes(word));
+        }
+
+        let rest = chunks.remainder();
+        let mut word = [0; 8];
+        word[.. rest.len()].copy_from_slice(rest);
+        // Mark the length so that trailing zeros still count.
+        self.fold(u64::from_le_bytes(word) ^ (rest.len() as u64) << 59);
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.fold(value);
+    }
+
+    fn finish(&self) -> u64 {
+        mix(self.state)
+    }
+}
+
+/// The SplitMix64 finalizer, a bijection with good avalanche.
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(MULTIPLIER);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stable_and_seeded() {
+        assert_eq!(KeyHasher::hash(7, "key"), KeyHasher::hash(7, &"key".to_owned()));
+        assert_ne!(KeyHasher::hash(7, "key"), KeyHasher::hash(8, "key"));
+        assert_ne!(KeyHasher::hash(0, &[0u8; 3][..]), KeyHasher::hash(0, &[0u8; 4][..]));
+        assert_ne!(KeyHasher::hash(0, &1u64), KeyHasher::hash(0, &2u64));
+    }
+}
@@ -0,0 +1,7 @@
+//! Structures built on hashing the keys they store.
+
+mod function;
+pub use self::function::*;
+
+mod hasher;
+pub use self::hasher::*;
@@ -0,0 +1,328 @@
+//! Counting the queries a structure answers.
+//!
+//! Wrapping a structure in an [`Instrumented`](struct.Instrumented.html)
+//! counts the rank, select and access calls made on it, and the blocks
+//! read from it. Because rank and select directories read their bits
+//! through the `BitVec` interface, wrapping the bits underneath one shows
+//! how many blocks each query touches:
+//!
+//! ```
+//! use succinct::{BitVecPush, BitVector, Rank9, BitRankSupport};
+//! use succinct::instrument::Instrumented;
+//!
+//! let mut bits: BitVector<u64> = BitVector::new();
+//! for i in 0 .. 10_000 {
+//!     bits.push_bit(i % 3 == 0);
+//! }
+//!
+//! let rank = Instrumented::new(Rank9::new(Instrumented::new(bits)));
+//! rank.inner().inner().reset(); // forget the reads made building it
+//!
+//! rank.rank1(5000);
+//! rank.rank1(9000);
+//!
+//! assert_eq!(2, rank.stats().rank);
+//! assert_eq!(2, rank.inner().inner().stats().block_reads);
+//! ```
+//!
+//! The counters are atomic, so an instrumented structure can still be
+//! shared between threads. This module is only available with the
+//! `instrumentation` feature.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bit_vec::{BitVec, BitVecMut};
+use error::Error;
+use int_vec::IntVec;
+use rank::{BitRankSupport, RankSupport};
+use select::{Select0Support, Select1Support, SelectSupport};
+use space_usage::SpaceUsage;
+use storage::BlockStore;
+
+/// A snapshot of the counts kept by an `Instrumented`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct QueryStats {
+    /// Rank queries, of either bit or a general value.
+    pub rank: u64,
+    /// Select queries, of either bit or a general value.
+    pub select: u64,
+    /// Element and bit lookups: `IntVec::get` and `BitVec::get_bit`.
+    pub access: u64,
+    /// Blocks read through `BitVec::get_block`.
+    pub block_reads: u64,
+}
+
+/// A structure that counts the queries made on it.
+///
+/// `Instrumented<T>` implements whichever of the query traits `T` does,
+/// as well as `BlockStore`, so it can stand in for the bits, or the
+/// counts, of another structure.
+#[derive(Debug, Default)]
+pub struct Instrumented<T> {
+    inner: T,
+    rank: AtomicU64,
+    select: AtomicU64,
+    access: AtomicU64,
+    block_reads: AtomicU64,
+}
+
+fn bump(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+impl<T> Instrumented<T> {
+    /// Wraps `inner` with all counts zero.
+    pub fn new(inner: T) -> Self {
+        Instrumented {
+            inner,
+            rank: AtomicU64::new(0),
+            select: AtomicU64::new(0),
+            access: AtomicU64::new(0),
+            block_reads: AtomicU64::new(0),
+        }
+    }
+
+    /// The counts so far.
+    pub fn stats(&self) -> QueryStats {
+        QueryStats {
+            rank: self.rank.load(Ordering::Relaxed),
+            select: self.select.load(Ordering::Relaxed),
+            access: self.access.load(Ordering::Relaxed),
+            block_reads: self.block_reads.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the counts so far and sets them to zero.
+    pub fn reset(&self) -> QueryStats {
+        QueryStats {
+            rank: self.rank.swap(0, Ordering::Relaxed),
+            select: self.select.swap(0, Ordering::Relaxed),
+            access: self.access.swap(0, Ordering::Relaxed),
+            block_reads: self.block_reads.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    /// Borrows the wrapped structure.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the wrapped structure.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Clones the structure along with its counts.
+impl<T: Clone> Clone for Instrumented<T> {
+    fn clone(&self) -> Self {
+        let stats = self.stats();
+        Instrumented {
+            inner: self.inner.clone(),
+            rank: AtomicU64::new(stats.rank),
+            select: AtomicU64::new(stats.select),
+            access: AtomicU64::new(stats.access),
+            block_reads: AtomicU64::new(stats.block_reads),
+        }
+    }
+}
+
+impl<T: BitVec> BitVec for Instrumented<T> {
+    type Block = T::Block;
+
+    fn bit_len(&self) -> u64 {
+        self.inner.bit_len()
+    }
+
+    fn block_len(&self) -> usize {
+        self.inner.block_len()
+    }
+
+    fn get_bit(&self, position: u64) -> bool {
+        bump(&self.access);
+        self.inner.get_bit(position)
+    }
+
+    fn get_block(&self, position: usize) -> T::Block {
+        bump(&self.block_reads);
+        self.inner.get_block(position)
+    }
+}
+
+impl<T: BitVecMut> BitVecMut for Instrumented<T> {
+    fn set_bit(&mut self, position: u64, value: bool) {
+        self.inner.set_bit(position, value);
+    }
+
+    fn set_block(&mut self, position: usize, value: T::Block) {
+        self.inner.set_block(position, value);
+    }
+}
+
+impl<T: BlockStore> BlockStore for Instrumented<T> {
+    fn with_block_capacity(block_capacity: usize) -> Self {
+        Self::new(T::with_block_capacity(block_capacity))
+    }
+
+    fn try_with_block_capacity(block_capacity: usize) -> Result<Self, Error> {
+        T::try_with_block_capacity(block_capacity).map(Self::new)
+    }
+
+    fn block_capacity(&self) -> usize {
+        self.inner.block_capacity()
+    }
+
+    fn push_block(&mut self, block: T::Block) {
+        self.inner.push_block(block);
+    }
+
+    fn pop_block(&mut self) -> Option<T::Block> {
+        self.inner.pop_block()
+    }
+
+    fn resize_blocks(&mut self, block_len: usize, fill: T::Block) {
+        self.inner.resize_blocks(block_len, fill);
+    }
+
+    fn truncate_blocks(&mut self, block_len: usize) {
+        self.inner.truncate_blocks(block_len);
+    }
+
+    fn reserve_blocks(&mut self, additional: usize) {
+        self.inner.reserve_blocks(additional);
+    }
+
+    fn reserve_blocks_exact(&mut self, additional: usize) {
+        self.inner.reserve_blocks_exact(additional);
+    }
+
+    fn shrink_blocks_to_fit(&mut self) {
+        self.inner.shrink_blocks_to_fit();
+    }
+}
+
+impl<T: IntVec> IntVec for Instrumented<T> {
+    type Block = T::Block;
+
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn element_bits(&self) -> usize {
+        self.inner.element_bits()
+    }
+
+    fn get(&self, index: u64) -> T::Block {
+        bump(&self.access);
+        self.inner.get(index)
+    }
+}
+
+impl<T: RankSupport> RankSupport for Instrumented<T> {
+    type Over = T::Over;
+
+    fn rank(&self, position: u64, value: T::Over) -> u64 {
+        bump(&self.rank);
+        self.inner.rank(position, value)
+    }
+
+    fn limit(&self) -> u64 {
+        self.inner.limit()
+    }
+}
+
+impl<T: BitRankSupport> BitRankSupport for Instrumented<T> {
+    fn rank1(&self, position: u64) -> u64 {
+        bump(&self.rank);
+        self.inner.rank1(position)
+    }
+
+    fn rank0(&self, position: u64) -> u64 {
+        bump(&self.rank);
+        self.inner.rank0(position)
+    }
+}
+
+impl<T: Select1Support> Select1Support for Instrumented<T> {
+    fn select1(&self, index: u64) -> Option<u64> {
+        bump(&self.select);
+        self.inner.select1(index)
+    }
+}
+
+impl<T: Select0Support> Select0Support for Instrumented<T> {
+    fn select0(&self, index: u64) -> Option<u64> {
+        bump(&self.select);
+        self.inner.select0(index)
+    }
+}
+
+impl<T: SelectSupport> SelectSupport for Instrumented<T> {
+    type Over = T::Over;
+
+    fn select(&self, index: u64, value: T::Over) -> Option<u64> {
+        bump(&self.select);
+        self.inner.select(index, value)
+    }
+}
+
+impl<T: SpaceUsage> SpaceUsage for Instrumented<T> {
+    fn is_stack_only() -> bool { T::is_stack_only() }
+
+    fn heap_bytes(&self) -> usize {
+        self.inner.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::{BitVecPush, BitVector};
+    use int_vec::IntVector;
+    use rank::Rank9;
+    use select::Select9;
+
+    #[test]
+    fn counts() {
+        let mut bits: BitVector<u64> = BitVector::new();
+        for i in 0 .. 4096 {
+            bits.push_bit(i % 2 == 0);
+        }
+
+        let select = Instrumented::new(
+            Select9::new(Rank9::new(Instrumented::new(bits))));
+
+        assert_eq!(Some(200), select.select1(100));
+        assert_eq!(Some(2000), select.select1(1000));
+        assert_eq!(2, select.stats().select);
+        assert_eq!(0, select.stats().rank);
+
+        let bit_reads = select.inner().inner().inner().stats().block_reads;
+        assert!(bit_reads > 0);
+
+        assert_eq!(QueryStats { select: 2, .. QueryStats::default() },
+                   select.reset());
+        assert_eq!(QueryStats::default(), select.stats());
+    }
+
+    #[test]
+    fn directory_reads() {
+        let bits = BitVector::<u64>::with_fill(4096, true);
+        let rank: Rank9<_, Instrumented<Vec<u64>>> = Rank9::new_in(bits);
+        let counts = rank.counts().stats().block_reads;
+
+        assert_eq!(3000, rank.rank1(2999));
+        assert_eq!(counts + 2, rank.counts().stats().block_reads);
+    }
+
+    #[test]
+    fn int_vec_access() {
+        let v = Instrumented::new(IntVector::<u16>::with_fill(5, 100, 3));
+        assert_eq!(3, v.get(7));
+        assert_eq!(3, v.get(8));
+        assert_eq!(2, v.stats().access);
+
+        let copy = v.clone();
+        assert_eq!(2, copy.stats().access);
+    }
+}
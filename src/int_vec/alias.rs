@@ -0,0 +1,172 @@
+use num_traits::ToPrimitive;
+
+use error::Error;
+use int_vec::{IntVec, IntVecMut, IntVector};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+
+/// Walker’s alias method for drawing indices in proportion to integer
+/// weights in constant time.
+///
+/// Each of the *n* indices owns a bucket of size *W*, the total weight,
+/// split at a threshold between the index itself and one alias. A draw
+/// picks a bucket and a point in it, so it takes two random numbers and
+/// two lookups, and the probabilities are exactly `weight / W`: Vose’s
+/// construction is carried out in integers. The thresholds and aliases
+/// are packed as tightly as *W* and *n* allow.
+///
+/// Randomness comes from any `FnMut() -> u64` producing uniform words,
+/// such as `|| rng.gen()` with the `rand` crate. For weights that
+/// change, [`FenwickTree::sample`](struct.FenwickTree.html#method.sample)
+/// draws in logarithmic time instead.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVector;
+/// use succinct::int_vec::AliasTable;
+///
+/// let weights: IntVector<u32> = vec![0, 3, 1].into_iter().collect();
+/// let table = AliasTable::new(&weights);
+///
+/// // A xorshift generator, for the example.
+/// let mut state = 0x1234_5678_9ABC_DEF0u64;
+/// let mut rng = || { state ^= state << 13; state ^= state >> 7; state ^= state << 17; state };
+///
+/// let draws: Vec<u64> = (0 .. 1000).map(|_| table.sample(&mut rng).unwrap()).collect();
+/// assert!(draws.iter().all(|&i| i == 1 || i == 2));
+/// assert!(draws.iter().filter(|&&i| i == 1).count() > 600);
+/// ```
+#[derive(Clone, Debug)]
+pub struct AliasTable {
+    total: u64,
+    thresholds: IntVector<u64>,
+    aliases: IntVector<u64>,
+}
+
+impl AliasTable {
+    /// Builds a table over `weights`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total weight doesn’t fit in a `u64`.
+    pub fn new<V: IntVec + ?Sized>(weights: &V) -> Self {
+        Self::try_new(weights).expect("AliasTable::new")
+    }
+
+    /// Builds a table over `weights`, or returns `Error::Overflow` if
+    /// their total doesn’t fit in a `u64`.
+    pub fn try_new<V: IntVec + ?Sized>(weights: &V) -> Result<Self, Error> {
+        let n = weights.len();
+        let weight = |i: u64| weights.get(i).to_u64().expect("AliasTable: weight too large");
+        let total = (0 .. n).try_fold(0u64, |sum, i| sum.checked_add(weight(i)))
+                            .ok_or(Error::Overflow)?;
+
+        // Scaled by n, the buckets each hold `total`.
+        let mut scaled: Vec<u128> = (0 .. n).map(|i| weight(i) as u128 * n as u128).collect();
+        let (mut small, mut large): (Vec<u64>, Vec<u64>) =
+            (0 .. n).partition(|&i| scaled[i as usize] < total as u128);
+
+        let mut thresholds = IntVector::with_fill(bits_to_represent(total), n, total);
+        let mut aliases = IntVector::with_fill(bits_to_represent(n.saturating_sub(1)), n, 0);
+        while let (Some(&less), Some(&more)) = (small.last(), large.last()) {
+            small.pop();
+            thresholds.set(less, scaled[less as usize] as u64);
+            aliases.set(less, more);
+
+            scaled[more as usize] -= total as u128 - scaled[less as usize];
+            if scaled[more as usize] < total as u128 {
+                large.pop();
+                small.push(more);
+            }
+        }
+        // Whatever remains fills its own bucket, which leaves the
+        // default threshold of `total`.
+
+        Ok(AliasTable { total, thresholds, aliases })
+    }
+
+    /// The number of indices.
+    pub fn len(&self) -> u64 {
+        self.thresholds.len()
+    }
+
+    /// Are there no indices?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The total weight.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Draws an index with probability proportional to its weight, or
+    /// returns `None` if the weights are all zero.
+    pub fn sample<R: FnMut() -> u64>(&self, rng: &mut R) -> Option<u64> {
+        if self.total == 0 { return None; }
+        let bucket = uniform(rng, self.len());
+        if uniform(rng, self.total) < self.thresholds.get(bucket) {
+            Some(bucket)
+        } else {
+            Some(self.aliases.get(bucket))
+        }
+    }
+}
+
+impl SpaceUsage for AliasTable {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.thresholds.heap_bytes() + self.aliases.heap_bytes()
+    }
+}
+
+/// Draws uniformly from `0 .. n`, which must be positive, by Lemire’s
+/// multiply-and-reject method.
+pub(crate) fn uniform<R: FnMut() -> u64>(rng: &mut R, n: u64) -> u64 {
+    let threshold = n.wrapping_neg() % n;
+    loop {
+        let product = rng() as u128 * n as u128;
+        if product as u64 >= threshold { return (product >> 64) as u64; }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::iter::FromIterator;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn degenerate() {
+        let table = |weights: Vec<u64>| AliasTable::try_new(&IntVector::from_iter(weights));
+        let mut state = 0u64;
+        let mut rng = || { state = state.wrapping_add(0x9E37_79B9_7F4A_7C15); state };
+        assert_eq!(None, table(vec![]).unwrap().sample(&mut rng));
+        assert_eq!(None, table(vec![0; 4]).unwrap().sample(&mut rng));
+        assert_eq!(Some(2), table(vec![0, 0, 9]).unwrap().sample(&mut rng));
+        assert_eq!(Some(Error::Overflow), table(vec![!0, 1]).err());
+    }
+
+    #[test]
+    fn qc_exact_probabilities() {
+        fn prop(weights: Vec<u16>) -> bool {
+            let table = AliasTable::new(&IntVector::from_iter(weights.iter().cloned()));
+            let n = weights.len() as u64;
+
+            // Each index’s share of all the buckets, scaled by n.
+            let mut shares = vec![0u128; weights.len()];
+            for bucket in 0 .. n {
+                let threshold = table.thresholds.get(bucket);
+                shares[bucket as usize] += threshold as u128;
+                shares[table.aliases.get(bucket) as usize] += (table.total - threshold) as u128;
+            }
+            table.total == 0 || weights.iter().zip(&shares).all(|(&weight, &share)| {
+                share == weight as u128 * n as u128
+            })
+        }
+
+        quickcheck(prop as fn(Vec<u16>) -> bool);
+    }
+}
@@ -0,0 +1,158 @@
+use std::io::{self, Error, ErrorKind};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use bit_vec::{BitVec, ByteBitVector};
+use int_vec::{IntVec, IntVector};
+use space_usage::SpaceUsage;
+use storage::BlockType;
+
+/// A read-only integer vector queried in place in a byte buffer, in the
+/// layout [`IntVector`](struct.IntVector.html)’s
+/// [`Persist`](../persist/trait.Persist.html) implementation writes.
+///
+/// Like a [`ByteBitVector`](../bit_vec/struct.ByteBitVector.html), which
+/// holds its bits, it opens in constant time and reads each element from
+/// the buffer when asked, whatever the buffer’s alignment.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{IntVec, IntVecMut, IntVector};
+/// use succinct::int_vec::ByteIntVector;
+/// use succinct::persist::{Container, ContainerWriter, Verify};
+///
+/// let mut v = IntVector::<u64>::new(12);
+/// for i in 0 .. 100 {
+///     v.push(i * 37 % 4096);
+/// }
+///
+/// let mut writer = ContainerWriter::new(Vec::new()).unwrap();
+/// writer.add("values", &v).unwrap();
+/// let file = writer.finish().unwrap();
+///
+/// let container = Container::open(&file, Verify::Full).unwrap();
+/// let view = ByteIntVector::<_, u64>::open(container.bytes("values").unwrap()).unwrap();
+/// assert_eq!(100, view.len());
+/// assert_eq!(v.get(57), view.get(57));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ByteIntVector<B, Block = u64> {
+    bits: ByteBitVector<B, Block>,
+    element_bits: usize,
+    len: u64,
+}
+
+// The element size and the length before the blocks.
+const HEADER_BYTES: usize = 9;
+
+impl<B: AsRef<[u8]>, Block: BlockType> ByteIntVector<B, Block> {
+    /// Opens the vector that `IntVector::<Block>::write_to` wrote to
+    /// `bytes`.
+    ///
+    /// Fails with `ErrorKind::InvalidData` if the element size is out of
+    /// range or the bytes are the wrong length for the header, or
+    /// `ErrorKind::UnexpectedEof` if there is no header.
+    pub fn open(bytes: B) -> io::Result<Self> {
+        let who = "ByteIntVector::open";
+        let buffer = bytes.as_ref();
+        if buffer.len() < HEADER_BYTES {
+            return Err(Error::new(ErrorKind::UnexpectedEof,
+                                  format!("{}: could not load: no header", who)));
+        }
+
+        let element_bits = buffer[0] as usize;
+        let len = LittleEndian::read_u64(&buffer[1 ..]);
+        if element_bits == 0 || element_bits > Block::nbits() {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("{}: could not load: bad element size", who)));
+        }
+        let bit_len = match len.checked_mul(element_bits as u64) {
+            Some(bit_len) => bit_len,
+            None => return Err(Error::new(ErrorKind::InvalidData,
+                                          format!("{}: could not load: length overflow", who))),
+        };
+
+        let bits = ByteBitVector::from_parts(who, bytes, HEADER_BYTES, bit_len)?;
+        Ok(ByteIntVector { bits, element_bits, len })
+    }
+
+    /// The elements’ bits.
+    pub fn bits(&self) -> &ByteBitVector<B, Block> {
+        &self.bits
+    }
+
+    /// Returns the buffer.
+    pub fn into_bytes(self) -> B {
+        self.bits.into_bytes()
+    }
+
+    /// Copies the elements into an owned vector.
+    pub fn to_int_vector(&self) -> IntVector<Block> {
+        let mut result = IntVector::with_capacity(self.element_bits, self.len);
+        for i in 0 .. self.len {
+            result.push(self.get(i));
+        }
+        result
+    }
+}
+
+impl<B: AsRef<[u8]>, Block: BlockType> IntVec for ByteIntVector<B, Block> {
+    type Block = Block;
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn element_bits(&self) -> usize {
+        self.element_bits
+    }
+
+    fn get(&self, index: u64) -> Block {
+        assert!(index < self.len, "ByteIntVector::get: out of bounds");
+        self.bits.get_bits(index * self.element_bits as u64, self.element_bits)
+    }
+}
+
+/// Counts the whole buffer, for whatever owns it.
+impl<B: AsRef<[u8]>, Block> SpaceUsage for ByteIntVector<B, Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.bits.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use persist::Persist;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn qc_matches_owned() {
+        fn prop(values: Vec<u16>, element_bits: u8, offset: u8) -> bool {
+            let element_bits = element_bits as usize % 16 + 1;
+            let mask = (1u32 << element_bits) - 1;
+            let v: IntVector<u16> = {
+                let mut v = IntVector::new(element_bits);
+                for value in values {
+                    v.push((value as u32 & mask) as u16);
+                }
+                v
+            };
+            let offset = offset as usize % 2;
+            let mut buffer = vec![0; offset];
+            v.write_to(&mut buffer).unwrap();
+
+            let view = ByteIntVector::<_, u16>::open(&buffer[offset ..]).unwrap();
+            view.len() == v.len()
+                && view.element_bits() == element_bits
+                && (0 .. v.len()).all(|i| view.get(i) == v.get(i))
+                && view.to_int_vector() == v
+                && ByteIntVector::<_, u16>::open(&buffer[offset .. buffer.len() - 1]).is_err()
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+}
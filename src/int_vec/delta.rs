@@ -0,0 +1,303 @@
+use dump::Snapshot;
+use int_vec::{EscapedIntVector, IntVec, IntVector};
+use space_usage::SpaceUsage;
+use util::{bits_to_represent, zigzag_decode, zigzag_encode};
+
+/// An immutable sequence of `u64`s stored as differences between
+/// neighbors, with every `sample`th value stored outright.
+///
+/// The differences are zig-zag encoded, so the sequence needn’t be
+/// sorted, and kept in an [`EscapedIntVector`](struct.EscapedIntVector.html)
+/// so that a few large jumps don’t widen the rest. `get` starts from the
+/// nearest sampled value and adds up to `sample - 1` differences;
+/// iterating adds one difference per element.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::int_vec::DeltaVector;
+///
+/// let positions = vec![3, 17, 18, 40, 1000, 1003];
+/// let v = DeltaVector::new(4, positions.iter().cloned());
+///
+/// assert_eq!(1000, v.get(4));
+/// assert_eq!(positions, v.iter().collect::<Vec<_>>());
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeltaVector {
+    sample: u64,
+    anchors: IntVector<u64>,
+    deltas: EscapedIntVector<u64>,
+}
+
+impl DeltaVector {
+    /// Creates a delta vector from an iterator, storing every `sample`th
+    /// value outright.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample` is 0.
+    pub fn new<I>(sample: usize, elements: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        assert!(sample > 0, "DeltaVector::new: sample must be positive");
+        let sample = sample as u64;
+
+        let mut anchors = Vec::new();
+        let mut deltas = IntVector::<u64>::new(64);
+        let mut previous = 0u64;
+
+        for (i, element) in elements.into_iter().enumerate() {
+            if i as u64 % sample == 0 {
+                anchors.push(element);
+                deltas.push(0);
+            } else {
+                deltas.push(zigzag_encode(element.wrapping_sub(previous) as i64));
+            }
+            previous = element;
+        }
+
+        let anchor_bits = anchors.iter().cloned().max()
+                                 .map_or(1, bits_to_represent);
+        let mut packed_anchors =
+            IntVector::with_capacity(anchor_bits, anchors.len() as u64);
+        for anchor in anchors {
+            packed_anchors.push(anchor);
+        }
+
+        let low_bits = EscapedIntVector::best_low_bits(&deltas);
+
+        DeltaVector {
+            sample,
+            anchors: packed_anchors,
+            deltas: EscapedIntVector::from_int_vec(&deltas, low_bits),
+        }
+    }
+
+    /// Creates a delta-encoded copy of `source`.
+    pub fn from_int_vec<V>(sample: usize, source: &V) -> Self
+        where V: IntVec<Block = u64> + ?Sized {
+
+        Self::new(sample, (0 .. source.len()).map(|i| source.get(i)))
+    }
+
+    /// The distance between sampled values.
+    pub fn sample(&self) -> usize {
+        self.sample as usize
+    }
+
+    /// Iterates over the values in order.
+    pub fn iter(&self) -> DeltaIter<'_> {
+        DeltaIter {
+            data: self,
+            index: 0,
+            current: 0,
+            end: self.len(),
+        }
+    }
+
+    fn delta(&self, index: u64) -> u64 {
+        zigzag_decode(self.deltas.get(index)) as u64
+    }
+}
+
+impl IntVec for DeltaVector {
+    type Block = u64;
+
+    fn len(&self) -> u64 {
+        self.deltas.len()
+    }
+
+    /// The width of the widest sampled value.
+    fn element_bits(&self) -> usize {
+        self.anchors.element_bits()
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        assert!(index < self.len(), "DeltaVector::get: out of bounds");
+
+        let anchor = index / self.sample;
+        let mut result = self.anchors.get(anchor);
+        for i in anchor * self.sample + 1 .. index + 1 {
+            result = result.wrapping_add(self.delta(i));
+        }
+        result
+    }
+}
+
+/// Lists the elements in order.
+impl Snapshot for DeltaVector {
+    type Item = u64;
+    type Iter<'a> = DeltaIter<'a> where Self: 'a;
+
+    fn dump_iter(&self) -> DeltaIter<'_> {
+        self.iter()
+    }
+}
+
+impl SpaceUsage for DeltaVector {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.anchors.heap_bytes() + self.deltas.heap_bytes()
+    }
+}
+
+/// An iterator over the values of a `DeltaVector`.
+///
+/// Going forward adds one difference per element, and `nth` skips to the
+/// nearest sampled value. Going backward costs a `get` per element.
+#[derive(Clone, Debug)]
+pub struct DeltaIter<'a> {
+    data: &'a DeltaVector,
+    index: u64,
+    current: u64,
+    // The elements from `end` on have been taken from the back.
+    end: u64,
+}
+
+impl<'a> Iterator for DeltaIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.index >= self.end { return None; }
+
+        self.current = if self.index % self.data.sample == 0 {
+            self.data.anchors.get(self.index / self.data.sample)
+        } else {
+            self.current.wrapping_add(self.data.delta(self.index))
+        };
+        self.index += 1;
+
+        Some(self.current)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end.saturating_sub(self.index) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<u64> {
+        let target = self.index.saturating_add(n as u64);
+        if target >= self.end {
+            self.index = self.end;
+            return None;
+        }
+
+        let anchor = target / self.data.sample * self.data.sample;
+        if anchor > self.index {
+            self.index = anchor;
+        }
+        while self.index < target {
+            self.next();
+        }
+        self.next()
+    }
+
+    impl_exact_size_count_last!(u64);
+}
+
+impl<'a> ExactSizeIterator for DeltaIter<'a> {}
+
+impl<'a> DoubleEndedIterator for DeltaIter<'a> {
+    impl_index_range_back!(u64, index .. end, |iter, index| iter.data.get(index));
+}
+
+impl<'a> IntoIterator for &'a DeltaVector {
+    type Item = u64;
+    type IntoIter = DeltaIter<'a>;
+
+    fn into_iter(self) -> DeltaIter<'a> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::{quickcheck, TestResult};
+
+    #[test]
+    fn empty() {
+        let v = DeltaVector::new(8, vec![]);
+        assert_eq!(0, v.len());
+        assert_eq!(None, v.iter().next());
+    }
+
+    #[test]
+    fn sorted_positions() {
+        let positions: Vec<u64> = (0 .. 10_000).map(|i| i * 3 + i % 7).collect();
+        let v = DeltaVector::new(16, positions.iter().cloned());
+
+        for (i, &x) in positions.iter().enumerate() {
+            assert_eq!(x, v.get(i as u64));
+        }
+        assert_eq!(positions, v.iter().collect::<Vec<_>>());
+
+        // The deltas are 4 or -3, which zig-zag to 4 bits, so with the
+        // escape bitmap and the samples this is under a byte per value.
+        assert!(v.total_bytes() < positions.len());
+    }
+
+    #[test]
+    fn unsorted_extremes() {
+        let values = vec![0, !0, 5, 1 << 63, 0, 1];
+        let v = DeltaVector::new(4, values.iter().cloned());
+        assert_eq!(values, v.iter().collect::<Vec<_>>());
+        assert_eq!(1, v.get(5));
+    }
+
+    #[test]
+    fn qc_skip_and_reverse() {
+        // Alternates `nth(skip)` from the front with `next_back`.
+        fn prop(values: Vec<u64>, skips: Vec<u8>) -> bool {
+            let v = DeltaVector::new(5, values.iter().cloned());
+            let mut iter = v.iter();
+            let mut expected = values.iter().cloned();
+            skips.into_iter().all(|skip| {
+                let skip = skip as usize % 12;
+                iter.nth(skip) == expected.nth(skip)
+                    && iter.next_back() == expected.next_back()
+                    && iter.len() == expected.len()
+            })
+        }
+
+        quickcheck(prop as fn(Vec<u64>, Vec<u8>) -> bool);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_sample() {
+        DeltaVector::new(0, vec![1]);
+    }
+
+    #[test]
+    fn qc_round_trip() {
+        fn prop(values: Vec<u64>, sample: u8) -> TestResult {
+            if sample == 0 { return TestResult::discard(); }
+
+            let v = DeltaVector::new(sample as usize, values.iter().cloned());
+            TestResult::from_bool(
+                v.iter().collect::<Vec<_>>() == values
+                    && values.iter().enumerate()
+                             .all(|(i, &x)| v.get(i as u64) == x))
+        }
+
+        quickcheck(prop as fn(Vec<u64>, u8) -> TestResult);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u16>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            let v = DeltaVector::new(3, values.iter().cloned());
+            skips_agree(v.iter(), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+}
@@ -0,0 +1,1225 @@
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use broadword;
+use dump::Snapshot;
+use error::Error;
+use int_vec::{IntVec, IntVector};
+use internal::merge::Union;
+use persist::{self, Component, Persist};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use space_usage::SpaceUsage;
+use stats::BitStats;
+use util::bits_to_represent;
+
+// How many ones, and zeros, there are between the recorded positions.
+const HINT_RATE: u64 = 256;
+
+/// An immutable increasing sequence of integers below a `universe`, in
+/// Elias–Fano coding.
+///
+/// The values are strictly increasing, unless built as a multiset, by
+/// [`new_multiset`](#method.new_multiset) or
+/// [`EliasFanoBuilder::multiset`](struct.EliasFanoBuilder.html#method.multiset),
+/// when repeats are allowed and `count` says how many there are.
+///
+/// Each value is split into its low `low_bits()` bits, stored packed,
+/// and the rest, stored in unary: value *i*’s high part *h* sets bit
+/// *h + i* of a bit vector with one zero per high part. That takes
+/// about 2 + log(universe / len) bits per value. The position of every
+/// 256th one and every 256th zero is recorded, so `get` finds its one,
+/// and `next_geq` the start of its high part’s bucket, by scanning a
+/// few words from the nearest.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::int_vec::EliasFano;
+///
+/// let ef = EliasFano::new(1000, vec![3, 17, 18, 420, 999]);
+/// assert_eq!(420, ef.get(3));
+/// assert_eq!(Some((3, 420)), ef.next_geq(19));
+/// assert_eq!(None, ef.next_geq(1000));
+///
+/// // A cursor only moves forward, so intersections scan each list once.
+/// let other = EliasFano::new(1000, vec![17, 400, 420, 500]);
+/// let mut cursor = other.cursor();
+/// let both: Vec<u64> = ef.iter()
+///     .filter(|&x| cursor.next_geq(x).map(|(_, y)| y) == Some(x))
+///     .collect();
+/// assert_eq!(vec![17, 420], both);
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "rkyv", derive(::rkyv::Archive, ::rkyv::Serialize, ::rkyv::Deserialize))]
+pub struct EliasFano {
+    universe: u64,
+    len: u64,
+    low_bits: usize,
+    // Empty when `low_bits` is 0.
+    lows: IntVector<u64>,
+    highs: BitVector<u64>,
+    // The positions of ones, and zeros, 0, HINT_RATE, 2 * HINT_RATE, ….
+    one_hints: IntVector<u64>,
+    zero_hints: IntVector<u64>,
+}
+
+impl EliasFano {
+    /// Encodes `values`, which must be strictly increasing and less than
+    /// `universe`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values are out of order or out of range.
+    pub fn new<I>(universe: u64, values: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        Self::try_new(universe, values).expect("EliasFano::new")
+    }
+
+    /// Encodes `values`, or returns an error if they aren’t strictly
+    /// increasing and less than `universe`.
+    pub fn try_new<I>(universe: u64, values: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = u64> {
+
+        let values: Vec<u64> = values.into_iter().collect();
+        Self::try_from_builder(EliasFanoBuilder::new(universe, values.len() as u64),
+                               values)
+    }
+
+    /// Encodes `values`, which must be strictly increasing and less than
+    /// `universe`, or returns an error if they aren’t. When the iterator
+    /// knows its exact length, the values are encoded as they arrive, in
+    /// one pass without collecting them.
+    pub fn from_sorted_iter<I>(universe: u64, values: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = u64> {
+
+        let mut values = values.into_iter();
+        match values.size_hint() {
+            (lower, Some(upper)) if lower == upper => {
+                let mut builder = EliasFanoBuilder::new(universe, lower as u64);
+                values.try_for_each(|value| builder.push(value))?;
+                Ok(builder.build())
+            }
+            _ => Self::try_new(universe, values),
+        }
+    }
+
+    /// Encodes `values`, which must be non-decreasing and less than
+    /// `universe`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values are out of order or out of range.
+    pub fn new_multiset<I>(universe: u64, values: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        Self::try_new_multiset(universe, values).expect("EliasFano::new_multiset")
+    }
+
+    /// Encodes `values`, or returns an error if they aren’t
+    /// non-decreasing and less than `universe`.
+    pub fn try_new_multiset<I>(universe: u64, values: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = u64> {
+
+        let values: Vec<u64> = values.into_iter().collect();
+        Self::try_from_builder(EliasFanoBuilder::multiset(universe, values.len() as u64),
+                               values)
+    }
+
+    /// Encodes `values`, which must be strictly increasing and less than
+    /// `universe`, on rayon’s pool, or returns an error if they aren’t.
+    /// The result is the same as from `try_new`. (With the `rayon`
+    /// feature.)
+    ///
+    /// The values are cut into a few chunks per thread, at multiples of
+    /// 64 values so that each chunk’s low bits fill whole words. Each
+    /// task checks and encodes its chunk into words of its own; the
+    /// chunks’ high bits cover disjoint ranges, which share at most a word
+    /// where they meet, so the pieces are ORed into place. To build on a
+    /// pool of your own, call this inside its `install`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use succinct::int_vec::EliasFano;
+    ///
+    /// let values: Vec<u64> = (0 .. 10_000).map(|i| 3 * i + i % 2).collect();
+    /// let ef = EliasFano::par_from_sorted(30_000, &values).unwrap();
+    /// assert_eq!(Some((5000, 15_000)), ef.next_geq(14_999));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_from_sorted(universe: u64, values: &[u64]) -> Result<Self, Error> {
+        let len = values.len() as u64;
+        let EliasFanoBuilder { low_bits, mut highs, .. } = EliasFanoBuilder::new(universe, len);
+
+        let tasks = 4 * ::rayon::current_num_threads();
+        let chunk_len = values.len().div_ceil(tasks).div_ceil(64).max(1) * 64;
+        let pieces: Vec<_> = values.par_chunks(chunk_len).enumerate().map(|(i, chunk)| {
+            let start = i * chunk_len;
+            let previous = start.checked_sub(1).map(|i| values[i]);
+            encode_chunk(universe, low_bits, start as u64, previous, chunk)
+        }).collect();
+
+        let mut lows = IntVector::with_fill(low_bits.max(1),
+                                            if low_bits == 0 {0} else {len}, 0);
+        let mut low_block = 0;
+        for piece in pieces {
+            let piece = piece?;
+            for word in piece.lows {
+                lows.set_block(low_block, word);
+                low_block += 1;
+            }
+            for (i, word) in piece.highs.into_iter().enumerate() {
+                let block = piece.first_high_block + i;
+                let existing = highs.get_block(block);
+                highs.set_block(block, existing | word);
+            }
+        }
+
+        Ok(EliasFano::from_parts(universe, len, low_bits, lows, highs))
+    }
+
+    fn from_parts(universe: u64, len: u64, low_bits: usize,
+                  lows: IntVector<u64>, highs: BitVector<u64>) -> Self {
+        let position_bits = bits_to_represent(highs.bit_len());
+        let mut one_hints = IntVector::new(position_bits);
+        let mut zero_hints = IntVector::new(position_bits);
+        let (mut ones, mut zeros) = (0, 0);
+        for block in 0 .. highs.block_len() {
+            let width = (highs.bit_len() - block as u64 * 64).min(64);
+            let mask = if width == 64 {!0} else {(1 << width) - 1};
+            let word = highs.get_block(block);
+            let base = block as u64 * 64;
+            push_hints(word & mask, base, &mut ones, &mut one_hints);
+            push_hints(!word & mask, base, &mut zeros, &mut zero_hints);
+        }
+
+        EliasFano {
+            universe,
+            len,
+            low_bits,
+            lows,
+            highs,
+            one_hints,
+            zero_hints,
+        }
+    }
+
+    fn try_from_builder(mut builder: EliasFanoBuilder, values: Vec<u64>)
+                        -> Result<Self, Error> {
+        for value in values {
+            builder.push(value)?;
+        }
+        Ok(builder.build())
+    }
+
+    /// The heap bytes of `len` values below `universe`, without encoding
+    /// them: about 2 + lg(`universe` / `len`) bits each, plus the hints.
+    pub fn estimate_size(universe: u64, len: u64) -> usize {
+        let (low_bits, high_len) = EliasFanoBuilder::dimensions(universe, len);
+        let position_bits = bits_to_represent(high_len);
+        IntVector::<u64>::estimate_size(low_bits.max(1), if low_bits == 0 {0} else {len})
+            + BitVector::<u64>::estimate_size(high_len)
+            + IntVector::<u64>::estimate_size(position_bits, len.div_ceil(HINT_RATE))
+            + IntVector::<u64>::estimate_size(position_bits,
+                                              (high_len - len).div_ceil(HINT_RATE))
+    }
+
+    /// The bound the values are below.
+    pub fn universe(&self) -> u64 {
+        self.universe
+    }
+
+    /// The number of values.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Are there no values?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of low bits stored per value.
+    pub fn low_bits(&self) -> usize {
+        self.low_bits
+    }
+
+    /// The index and value of the first element no less than `value`.
+    pub fn next_geq(&self, value: u64) -> Option<(u64, u64)> {
+        self.cursor().next_geq(value)
+    }
+
+    /// Is `value` an element?
+    pub fn contains(&self, value: u64) -> bool {
+        self.next_geq(value).map(|(_, found)| found) == Some(value)
+    }
+
+    /// The number of elements less than `value`.
+    pub fn count_less(&self, value: u64) -> u64 {
+        self.next_geq(value).map_or(self.len, |(index, _)| index)
+    }
+
+    /// The number of elements equal to `value`.
+    pub fn count(&self, value: u64) -> u64 {
+        self.count_range(value .. value.saturating_add(1))
+    }
+
+    /// The number of elements in `range`.
+    pub fn count_range(&self, range: Range<u64>) -> u64 {
+        if range.start >= range.end { return 0; }
+        self.count_less(range.end) - self.count_less(range.start)
+    }
+
+    /// The values in either sequence, without repeats, below the larger
+    /// universe.
+    ///
+    /// Both are read twice, in order, to size and then fill the result,
+    /// so neither is decoded whole.
+    pub fn union(&self, other: &EliasFano) -> EliasFano {
+        let universe = self.universe.max(other.universe);
+        let len = Union::new(self.iter(), other.iter()).count() as u64;
+        let mut builder = EliasFanoBuilder::new(universe, len);
+        for value in Union::new(self.iter(), other.iter()) {
+            builder.push(value).expect("EliasFano::union: out of order");
+        }
+        builder.build()
+    }
+
+    /// The values in both sequences, without repeats, below the smaller
+    /// universe.
+    ///
+    /// Each value of the shorter sequence is looked for with a cursor
+    /// over the longer, so this costs about the shorter’s length in
+    /// `next_geq` steps, twice over.
+    pub fn intersection(&self, other: &EliasFano) -> EliasFano {
+        let universe = self.universe.min(other.universe);
+        let mut len = 0;
+        self.for_each_common(other, |_| len += 1);
+        let mut builder = EliasFanoBuilder::new(universe, len);
+        self.for_each_common(other, |value| {
+            builder.push(value).expect("EliasFano::intersection: out of order");
+        });
+        builder.build()
+    }
+
+    fn for_each_common<F: FnMut(u64)>(&self, other: &EliasFano, mut f: F) {
+        let (short, long) = if self.len <= other.len {(self, other)} else {(other, self)};
+        let mut cursor = long.cursor();
+        let mut last = None;
+        for value in short.iter() {
+            if last == Some(value) { continue; }
+            match cursor.next_geq(value) {
+                Some((_, found)) if found == value => {
+                    f(value);
+                    last = Some(value);
+                }
+                Some(_) => {}
+                None => return,
+            }
+        }
+    }
+
+    /// A cursor at the first element.
+    pub fn cursor(&self) -> EliasFanoCursor<'_> {
+        EliasFanoCursor { ef: self, index: 0, position: 0, end: self.len }
+    }
+
+    /// The elements in increasing order.
+    pub fn iter(&self) -> EliasFanoCursor<'_> {
+        self.cursor()
+    }
+}
+
+// What the queries read, so that they run the same on a sequence in
+// memory and on one archived with `rkyv`.
+trait Parts {
+    fn universe(&self) -> u64;
+
+    fn low_bits(&self) -> usize;
+
+    // Block `block` of the high bits.
+    fn high_block(&self, block: usize) -> u64;
+
+    // The `index`th hint for ones, or zeros.
+    fn hint(&self, index: u64, ones: bool) -> u64;
+
+    // The low bits of element `index`.
+    fn low(&self, index: u64) -> u64;
+
+    // The position of the `index`th one (or zero, if `ones` is false) of
+    // the high bits, scanning forward from the nearest hint.
+    fn select_high(&self, index: u64, ones: bool) -> u64 {
+        let start = self.hint(index / HINT_RATE, ones);
+        let mut remaining = index % HINT_RATE;
+
+        let mut block = start / 64;
+        let mut word = self.word(block, ones) & (!0 << (start % 64));
+        loop {
+            let count = word.count_ones() as u64;
+            if remaining < count {
+                let offset = broadword::select1_raw(remaining as usize, word);
+                return block * 64 + offset as u64;
+            }
+            remaining -= count;
+            block += 1;
+            word = self.word(block, ones);
+        }
+    }
+
+    // Block `block` of the high bits, complemented unless `ones`.
+    fn word(&self, block: u64, ones: bool) -> u64 {
+        let word = self.high_block(block as usize);
+        if ones {word} else {!word}
+    }
+
+    // The value of the element `index`, whose one is at `position`.
+    fn value_at(&self, index: u64, position: u64) -> u64 {
+        (position - index) << self.low_bits() | self.low(index)
+    }
+
+    // The position of the first one of the values with high part `high`,
+    // and the index of that value.
+    fn bucket_start(&self, high: u64) -> (u64, u64) {
+        if high == 0 { return (0, 0); }
+        let position = self.select_high(high - 1, false) + 1;
+        (position, position - high)
+    }
+
+    // The value of element `index`, unless it is `end` or past, moving
+    // `position`, at or before its one, to its one.
+    fn find_one(&self, index: u64, position: &mut u64, end: u64) -> Option<u64> {
+        if index >= end { return None; }
+
+        let mut block = *position / 64;
+        let mut word = self.word(block, true) & (!0 << (*position % 64));
+        while word == 0 {
+            block += 1;
+            word = self.word(block, true);
+        }
+
+        *position = block * 64 + word.trailing_zeros() as u64;
+        Some(self.value_at(index, *position))
+    }
+
+    // Moves `index` and `position` to the first element no less than
+    // `value` at or after them, and returns its index and value, as
+    // `EliasFanoCursor::next_geq` does.
+    fn seek(&self, index: &mut u64, position: &mut u64, end: u64, value: u64)
+            -> Option<(u64, u64)> {
+        if value >= self.universe() {
+            *index = end;
+            return None;
+        }
+
+        let (start, first) = self.bucket_start(value >> self.low_bits());
+        if first > *index {
+            *index = first;
+            *position = start;
+        }
+
+        while let Some(found) = self.find_one(*index, position, end) {
+            if found >= value { return Some((*index, found)); }
+            *index += 1;
+            *position += 1;
+        }
+        None
+    }
+}
+
+impl Parts for EliasFano {
+    fn universe(&self) -> u64 {
+        self.universe
+    }
+
+    fn low_bits(&self) -> usize {
+        self.low_bits
+    }
+
+    fn high_block(&self, block: usize) -> u64 {
+        self.highs.get_block(block)
+    }
+
+    fn hint(&self, index: u64, ones: bool) -> u64 {
+        if ones {self.one_hints.get(index)} else {self.zero_hints.get(index)}
+    }
+
+    fn low(&self, index: u64) -> u64 {
+        if self.low_bits == 0 {0} else {self.lows.get(index)}
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl Parts for ArchivedEliasFano {
+    fn universe(&self) -> u64 {
+        self.universe.to_native()
+    }
+
+    fn low_bits(&self) -> usize {
+        self.low_bits.to_native() as usize
+    }
+
+    fn high_block(&self, block: usize) -> u64 {
+        self.highs.get_block(block)
+    }
+
+    fn hint(&self, index: u64, ones: bool) -> u64 {
+        if ones {self.one_hints.get(index)} else {self.zero_hints.get(index)}
+    }
+
+    fn low(&self, index: u64) -> u64 {
+        if self.low_bits() == 0 {0} else {self.lows.get(index)}
+    }
+}
+
+/// Queries in place in a buffer an `EliasFano` sequence was archived to
+/// with `rkyv` (with the `rkyv` feature), the same way as the sequence.
+///
+/// # Example
+///
+/// ```
+/// # extern crate rkyv;
+/// # extern crate succinct;
+/// use succinct::IntVec;
+/// use succinct::int_vec::EliasFano;
+///
+/// # fn main() {
+/// let ef = EliasFano::new(1000, vec![3, 17, 18, 420, 999]);
+/// let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&ef).unwrap();
+///
+/// let archived = rkyv::access::<rkyv::Archived<EliasFano>, rkyv::rancor::Error>(&bytes)
+///     .unwrap();
+/// assert_eq!(420, archived.get(3));
+/// assert_eq!(Some((3, 420)), archived.next_geq(19));
+/// assert_eq!(None, archived.next_geq(1000));
+/// # }
+/// ```
+#[cfg(feature = "rkyv")]
+impl ArchivedEliasFano {
+    /// The bound the values are below.
+    pub fn universe(&self) -> u64 {
+        self.universe.to_native()
+    }
+
+    /// The number of values.
+    pub fn len(&self) -> u64 {
+        self.len.to_native()
+    }
+
+    /// Are there no values?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The index and value of the first element no less than `value`.
+    pub fn next_geq(&self, value: u64) -> Option<(u64, u64)> {
+        self.seek(&mut 0, &mut 0, self.len(), value)
+    }
+
+    /// Is `value` an element?
+    pub fn contains(&self, value: u64) -> bool {
+        self.next_geq(value).map(|(_, found)| found) == Some(value)
+    }
+
+    /// The number of elements less than `value`.
+    pub fn count_less(&self, value: u64) -> u64 {
+        self.next_geq(value).map_or(self.len(), |(index, _)| index)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl IntVec for ArchivedEliasFano {
+    type Block = u64;
+
+    fn len(&self) -> u64 {
+        self.len.to_native()
+    }
+
+    fn element_bits(&self) -> usize {
+        bits_to_represent(self.universe().saturating_sub(1))
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        assert!(index < IntVec::len(self), "ArchivedEliasFano::get: out of bounds");
+        self.value_at(index, self.select_high(index, true))
+    }
+}
+
+/// Builds an [`EliasFano`](struct.EliasFano.html) sequence one value at
+/// a time, given its universe and length up front.
+///
+/// Each value is checked as it is pushed, so that input from outside,
+/// like a file, can be rejected with an error rather than a panic.
+///
+/// # Example
+///
+/// ```
+/// use succinct::Error;
+/// use succinct::int_vec::EliasFanoBuilder;
+///
+/// let mut builder = EliasFanoBuilder::new(100, 3);
+/// builder.push(5).unwrap();
+/// assert_eq!(Err(Error::NotIncreasing), builder.push(5));
+/// assert_eq!(Err(Error::ValueTooLarge), builder.push(100));
+/// builder.push(50).unwrap();
+///
+/// let ef = builder.build();
+/// assert_eq!(vec![5, 50], ef.iter().collect::<Vec<_>>());
+/// ```
+#[derive(Clone, Debug)]
+pub struct EliasFanoBuilder {
+    universe: u64,
+    capacity: u64,
+    len: u64,
+    low_bits: usize,
+    lows: IntVector<u64>,
+    highs: BitVector<u64>,
+    last: Option<u64>,
+    strict: bool,
+}
+
+impl EliasFanoBuilder {
+    /// Starts a sequence of up to `len` values below `universe`.
+    ///
+    /// The encoding is sized for `len` values; pushing fewer wastes a bit
+    /// for each one missing.
+    pub fn new(universe: u64, len: u64) -> Self {
+        let (low_bits, high_len) = Self::dimensions(universe, len);
+
+        EliasFanoBuilder {
+            universe,
+            capacity: len,
+            len: 0,
+            low_bits,
+            lows: IntVector::with_capacity(low_bits.max(1),
+                                           if low_bits == 0 {0} else {len}),
+            highs: BitVector::with_fill(high_len, false),
+            last: None,
+            strict: true,
+        }
+    }
+
+    // The number of low bits and the length of the high bits for `len`
+    // values below `universe`.
+    fn dimensions(universe: u64, len: u64) -> (usize, u64) {
+        let low_bits = if len == 0 || universe <= len {0} else {
+            63 - (universe / len).leading_zeros() as usize
+        };
+        let buckets = if universe == 0 {0} else {((universe - 1) >> low_bits) + 1};
+        (low_bits, len + buckets)
+    }
+
+    /// Starts a sequence of up to `len` values below `universe`, which
+    /// may repeat.
+    pub fn multiset(universe: u64, len: u64) -> Self {
+        EliasFanoBuilder { strict: false, ..Self::new(universe, len) }
+    }
+
+    /// The number of values pushed so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Have no values been pushed?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `value`, or returns an error, changing nothing, if it is
+    /// less than the last value (or equal, unless this is a multiset),
+    /// not less than the universe, or one more than the declared length.
+    pub fn push(&mut self, value: u64) -> Result<(), Error> {
+        if self.len == self.capacity { return Err(Error::TooManyValues); }
+        if value >= self.universe { return Err(Error::ValueTooLarge); }
+        if self.last.is_some_and(|last| value < last || (self.strict && value == last)) {
+            return Err(Error::NotIncreasing);
+        }
+
+        if self.low_bits > 0 {
+            self.lows.push(value & ((1 << self.low_bits) - 1));
+        }
+        self.highs.set_bit((value >> self.low_bits) + self.len, true);
+        self.len += 1;
+        self.last = Some(value);
+        Ok(())
+    }
+
+    /// Finishes the sequence.
+    pub fn build(self) -> EliasFano {
+        EliasFano::from_parts(self.universe, self.len, self.low_bits, self.lows, self.highs)
+    }
+}
+
+/// The universe, capacity, length and last value (or `u64::MAX`) as
+/// `u64`s, the low bits and whether repeats are refused as bytes, then
+/// the low and high parts.
+///
+/// A long build can save its builder this way and resume from it.
+impl Persist for EliasFanoBuilder {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u64::<LittleEndian>(self.universe)?;
+        sink.write_u64::<LittleEndian>(self.capacity)?;
+        sink.write_u64::<LittleEndian>(self.len)?;
+        sink.write_u64::<LittleEndian>(self.last.unwrap_or(u64::MAX))?;
+        sink.write_u8(self.low_bits as u8)?;
+        sink.write_u8(self.strict as u8)?;
+        persist::write_part(&self.lows, sink)?;
+        persist::write_part(&self.highs, sink)
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let who = "EliasFanoBuilder::read_from";
+        let universe = bytes.read_u64::<LittleEndian>()?;
+        let capacity = bytes.read_u64::<LittleEndian>()?;
+        let len = bytes.read_u64::<LittleEndian>()?;
+        let last = bytes.read_u64::<LittleEndian>()?;
+        let low_bits = bytes.read_u8()? as usize;
+        let strict = bytes.read_u8()? != 0;
+        let lows: IntVector<u64> = persist::read_part(&mut bytes)?;
+        let highs: BitVector<u64> = persist::read_part(&mut bytes)?;
+        persist::expect_end(who, bytes)?;
+
+        // The parts must be the ones `new` would make for these sizes.
+        let (expected_low_bits, high_len) = Self::dimensions(universe, capacity);
+        let last = if len == 0 {None} else {Some(last)};
+        if expected_low_bits != low_bits || len > capacity || highs.bit_len() != high_len
+            || highs.count_ones() != len
+            || lows.len() != if low_bits == 0 {0} else {len}
+            || last.is_some_and(|last| last >= universe) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("{}: could not load: inconsistent state", who)));
+        }
+
+        Ok(EliasFanoBuilder { universe, capacity, len, low_bits, lows, highs, last, strict })
+    }
+}
+
+impl Component for EliasFanoBuilder {
+    fn tag() -> String {
+        "EliasFanoBuilder".to_owned()
+    }
+}
+
+/// A forward-only position in an [`EliasFano`](struct.EliasFano.html)
+/// sequence, for iterating and skipping ahead.
+///
+/// Each call to `next_geq` scans ahead from the cursor if the target is
+/// near, and otherwise jumps using the sequence’s hints, so stepping a
+/// cursor through a list costs time proportional to the distance
+/// covered. As an iterator it also skips with `nth`, using the hints,
+/// and runs backward from the end.
+#[derive(Clone, Debug)]
+pub struct EliasFanoCursor<'a> {
+    ef: &'a EliasFano,
+    // The index of the next element, and a position of the high bits at
+    // or before its one.
+    index: u64,
+    position: u64,
+    // The elements from `end` on have been taken from the back.
+    end: u64,
+}
+
+impl<'a> EliasFanoCursor<'a> {
+    /// The index of the element the cursor is at.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    // Moves to the next one at or after the current position.
+    fn find_one(&mut self) -> Option<u64> {
+        self.ef.find_one(self.index, &mut self.position, self.end)
+    }
+
+    /// Moves to the first element no less than `value`, at or after the
+    /// cursor, and returns its index and value. The cursor stays there.
+    pub fn next_geq(&mut self, value: u64) -> Option<(u64, u64)> {
+        self.ef.seek(&mut self.index, &mut self.position, self.end, value)
+    }
+}
+
+impl<'a> Iterator for EliasFanoCursor<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let found = self.find_one()?;
+        self.index += 1;
+        self.position += 1;
+        Some(found)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end.saturating_sub(self.index) as usize;
+        (remaining, Some(remaining))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<u64> {
+        let target = self.index.saturating_add(n as u64);
+        if target >= self.end {
+            self.index = self.end;
+            return None;
+        }
+        if n > 0 {
+            self.index = target;
+            self.position = self.ef.select_high(target, true);
+        }
+        self.next()
+    }
+
+    impl_exact_size_count_last!(u64);
+}
+
+impl<'a> ExactSizeIterator for EliasFanoCursor<'a> {}
+
+impl<'a> DoubleEndedIterator for EliasFanoCursor<'a> {
+    impl_index_range_back!(u64, index .. end,
+                           |iter, index| iter.ef.value_at(index, iter.ef.select_high(index, true)));
+}
+
+impl Default for EliasFano {
+    /// An empty sequence over an empty universe.
+    fn default() -> Self {
+        EliasFano::new(0, vec![])
+    }
+}
+
+/// Encodes strictly increasing values over the smallest universe that
+/// holds them, or returns `Error::NotIncreasing` if they aren’t.
+impl TryFrom<Vec<u64>> for EliasFano {
+    type Error = Error;
+
+    fn try_from(values: Vec<u64>) -> Result<Self, Error> {
+        let universe = match values.last() {
+            Some(&last) => last.checked_add(1).ok_or(Error::Overflow)?,
+            None => 0,
+        };
+        EliasFano::try_new(universe, values)
+    }
+}
+
+impl IntVec for EliasFano {
+    type Block = u64;
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn element_bits(&self) -> usize {
+        bits_to_represent(self.universe.saturating_sub(1))
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        assert!(index < self.len, "EliasFano::get: out of bounds");
+        self.value_at(index, self.select_high(index, true))
+    }
+}
+
+/// Lists the values in increasing order.
+impl Snapshot for EliasFano {
+    type Item = u64;
+    type Iter<'a> = EliasFanoCursor<'a> where Self: 'a;
+
+    fn dump_iter(&self) -> EliasFanoCursor<'_> {
+        self.iter()
+    }
+}
+
+impl SpaceUsage for EliasFano {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.lows.heap_bytes()
+            + self.highs.heap_bytes()
+            + self.one_hints.heap_bytes()
+            + self.zero_hints.heap_bytes()
+    }
+}
+
+// Records the position of every `HINT_RATE`th set bit of `word`, which
+// starts at bit `base`, given that `count` bits were set before it.
+fn push_hints(word: u64, base: u64, count: &mut u64, hints: &mut IntVector<u64>) {
+    let ones = word.count_ones() as u64;
+    let mut next = count.div_ceil(HINT_RATE) * HINT_RATE;
+    while next < *count + ones {
+        let offset = broadword::select1_raw((next - *count) as usize, word);
+        hints.push(base + offset as u64);
+        next += HINT_RATE;
+    }
+    *count += ones;
+}
+
+// The words of one chunk of a parallel build.
+#[cfg(feature = "rayon")]
+struct EncodedChunk {
+    lows: Vec<u64>,
+    first_high_block: usize,
+    highs: Vec<u64>,
+}
+
+// Checks and encodes `values`, the elements from index `start` on, after
+// `previous`.
+#[cfg(feature = "rayon")]
+fn encode_chunk(universe: u64, low_bits: usize, start: u64, previous: Option<u64>,
+                values: &[u64]) -> Result<EncodedChunk, Error> {
+    let mut last = previous;
+    for &value in values {
+        if value >= universe { return Err(Error::ValueTooLarge); }
+        if last.is_some_and(|last| value <= last) { return Err(Error::NotIncreasing); }
+        last = Some(value);
+    }
+
+    let mut lows = IntVector::<u64>::with_capacity(low_bits.max(1), values.len() as u64);
+    if low_bits > 0 {
+        for &value in values {
+            lows.push(value & ((1 << low_bits) - 1));
+        }
+    }
+
+    let position = |i: usize| (values[i] >> low_bits) + start + i as u64;
+    let (first, last) = match values.len() {
+        0 => (0, 0),
+        n => (position(0) / 64, position(n - 1) / 64),
+    };
+    let mut highs = vec![0; (last - first) as usize + 1];
+    for i in 0 .. values.len() {
+        let bit = position(i);
+        highs[(bit / 64 - first) as usize] |= 1 << (bit % 64);
+    }
+
+    let lows = if low_bits == 0 {vec![]} else {
+        (0 .. lows.block_len()).map(|i| lows.get_block(i)).collect()
+    };
+    Ok(EncodedChunk {
+        lows,
+        first_high_block: first as usize,
+        highs,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let ef = EliasFano::new(0, vec![]);
+        assert!(ef.is_empty());
+        assert_eq!(None, ef.next_geq(0));
+        assert_eq!(0, ef.iter().count());
+
+        let ef = EliasFano::new(100, vec![]);
+        assert_eq!(None, ef.next_geq(5));
+
+        assert!(EliasFano::default().is_empty());
+    }
+
+    #[test]
+    fn try_from_vec() {
+        use std::convert::TryFrom;
+
+        let ef = EliasFano::try_from(vec![3, 8, 20]).unwrap();
+        assert_eq!((21, 3), (ef.universe(), ef.len()));
+        assert_eq!(8, ef.get(1));
+        assert_eq!(Some(Error::NotIncreasing), EliasFano::try_from(vec![3, 3]).err());
+        assert_eq!(Some(Error::Overflow), EliasFano::try_from(vec![!0]).err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_errors() {
+        let mut values: Vec<u64> = (0 .. 1000).collect();
+        values[700] = 699;
+        assert_eq!(Some(Error::NotIncreasing), EliasFano::par_from_sorted(1000, &values).err());
+        values[700] = 1000;
+        assert_eq!(Some(Error::ValueTooLarge), EliasFano::par_from_sorted(1000, &values).err());
+        assert!(EliasFano::par_from_sorted(10, &[]).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn qc_parallel_matches_sequential() {
+        fn prop(gaps: Vec<(u8, u16)>, threads: u8) -> bool {
+            // Repeat each gap so the chunks are long enough to split.
+            let mut values = Vec::new();
+            let mut value = 0u64;
+            for (count, gap) in gaps {
+                for _ in 0 .. count % 16 {
+                    value += gap as u64 % 300 + 1;
+                    values.push(value);
+                }
+            }
+            let universe = value + 1 + value / 3;
+
+            let expected = EliasFano::new(universe, values.iter().cloned());
+            let pool = ::rayon::ThreadPoolBuilder::new()
+                .num_threads(threads as usize % 6 + 1)
+                .build()
+                .unwrap();
+            let ef = pool.install(|| EliasFano::par_from_sorted(universe, &values)).unwrap();
+            ef.iter().collect::<Vec<_>>() == values
+                && ef.one_hints == expected.one_hints
+                && ef.zero_hints == expected.zero_hints
+                && (0 .. values.len()).step_by(7).all(|i| ef.get(i as u64) == values[i])
+                && (0 .. universe).step_by(97).all(|v| ef.next_geq(v) == expected.next_geq(v))
+        }
+
+        quickcheck(prop as fn(Vec<(u8, u16)>, u8) -> bool);
+    }
+
+    #[test]
+    fn skipping_and_reversing() {
+        let values: Vec<u64> = (0 .. 3000).map(|i| i * 7 + i % 3).collect();
+        let ef = EliasFano::new(30_000, values.iter().cloned());
+
+        let mut iter = ef.iter();
+        assert_eq!(Some(values[1000]), iter.nth(1000));
+        assert_eq!(Some(values[1001]), iter.next());
+        assert_eq!(Some(values[2999]), iter.next_back());
+        assert_eq!(1997, iter.len());
+        assert_eq!(Some(values[2998]), iter.nth(1996));
+        assert_eq!(None, iter.next());
+        assert_eq!(values.iter().rev().cloned().collect::<Vec<_>>(),
+                   ef.iter().rev().collect::<Vec<_>>());
+
+        let mut iter = ef.iter();
+        iter.next_back();
+        assert_eq!(None, iter.next_geq(values[2999]));
+    }
+
+    #[test]
+    fn dense() {
+        let ef = EliasFano::new(2000, 0 .. 2000);
+        assert_eq!(0, ef.low_bits());
+        assert_eq!(1234, ef.get(1234));
+        assert_eq!(Some((1999, 1999)), ef.next_geq(1999));
+    }
+
+    #[test]
+    fn long_cursor() {
+        let values: Vec<u64> = (0 .. 5000).map(|i| i * i).collect();
+        let ef = EliasFano::new(25_000_000, values.iter().cloned());
+        assert_eq!(values, ef.iter().collect::<Vec<_>>());
+
+        let mut cursor = ef.cursor();
+        for i in (0 .. 5000).step_by(7) {
+            assert_eq!(Some((i, i * i)), cursor.next_geq(i * i - (i > 0) as u64));
+        }
+        assert_eq!(None, cursor.next_geq(24_995_002));
+    }
+
+    #[test]
+    fn estimate_size() {
+        for &(universe, len) in &[(0, 0), (1000, 10), (1 << 40, 100_000), (5000, 5000)] {
+            let ef = EliasFano::new(universe, (0 .. len).map(|i| i * (universe / len.max(1))));
+            let estimate = EliasFano::estimate_size(universe, len);
+            assert!(estimate <= ef.heap_bytes() && ef.heap_bytes() <= estimate + estimate / 8 + 64,
+                    "{} estimated as {}", ef.heap_bytes(), estimate);
+        }
+    }
+
+    #[test]
+    fn builder_checkpoint() {
+        let mut builder = EliasFanoBuilder::multiset(1000, 6);
+        for &value in &[3, 3, 70] {
+            builder.push(value).unwrap();
+        }
+        let mut checkpoint = Vec::new();
+        builder.write_to(&mut checkpoint).unwrap();
+
+        let mut resumed = EliasFanoBuilder::read_from(&checkpoint).unwrap();
+        assert_eq!(3, resumed.len());
+        assert_eq!(Err(Error::NotIncreasing), resumed.push(69));
+        resumed.push(70).unwrap();
+        resumed.push(999).unwrap();
+        assert_eq!(vec![3, 3, 70, 70, 999], resumed.build().iter().collect::<Vec<_>>());
+
+        // A universe of 5096 would need more low bits.
+        checkpoint[1] ^= 0x10;
+        assert!(EliasFanoBuilder::read_from(&checkpoint).is_err());
+
+        // With no low bits, only the high bits tell how many values there are.
+        let mut builder = EliasFanoBuilder::new(6, 6);
+        builder.push(1).unwrap();
+        builder.push(4).unwrap();
+        let mut checkpoint = Vec::new();
+        builder.write_to(&mut checkpoint).unwrap();
+        checkpoint[16] = 5;
+        assert!(EliasFanoBuilder::read_from(&checkpoint).is_err());
+    }
+
+    #[test]
+    fn builder_errors() {
+        let mut builder = EliasFanoBuilder::new(50, 2);
+        assert_eq!(Ok(()), builder.push(0));
+        assert_eq!(Err(Error::NotIncreasing), builder.push(0));
+        assert_eq!(Ok(()), builder.push(49));
+        assert_eq!(Err(Error::TooManyValues), builder.push(60));
+        assert_eq!(vec![0, 49], builder.build().iter().collect::<Vec<_>>());
+
+        assert_eq!(Some(Error::NotIncreasing),
+                   EliasFano::try_new(10, vec![4, 2]).err());
+        assert_eq!(Some(Error::ValueTooLarge),
+                   EliasFano::from_sorted_iter(10, vec![4, 10]).err());
+        assert_eq!(3, EliasFano::from_sorted_iter(1 << 40, (0 .. 3).map(|i| i << 30))
+                          .unwrap().len());
+        assert_eq!(2, EliasFano::from_sorted_iter(10, (0 .. 10).filter(|&i| i % 7 == 0))
+                          .unwrap().len());
+    }
+
+    #[test]
+    fn merging() {
+        let a = EliasFano::new_multiset(100, vec![1, 1, 5, 50, 99]);
+        let b = EliasFano::new(1000, vec![0, 5, 99, 500]);
+        let union = a.union(&b);
+        assert_eq!(1000, union.universe());
+        assert_eq!(vec![0, 1, 5, 50, 99, 500], union.iter().collect::<Vec<_>>());
+
+        let both = a.intersection(&b);
+        assert_eq!(100, both.universe());
+        assert_eq!(vec![5, 99], both.iter().collect::<Vec<_>>());
+        assert!(a.intersection(&EliasFano::new(10, vec![])).is_empty());
+    }
+
+    #[test]
+    fn short_builder() {
+        let mut builder = EliasFanoBuilder::new(1 << 20, 1000);
+        for value in &[7, 70_000, 700_000] {
+            builder.push(*value).unwrap();
+        }
+        let ef = builder.build();
+        assert_eq!(3, ef.len());
+        assert_eq!(Some((2, 700_000)), ef.next_geq(70_001));
+        assert_eq!(None, ef.next_geq(700_001));
+    }
+
+    #[test]
+    fn multiset() {
+        let ef = EliasFano::new_multiset(20, vec![2, 2, 2, 7, 7, 19]);
+        assert_eq!(3, ef.count(2));
+        assert_eq!(0, ef.count(3));
+        assert_eq!(5, ef.count_range(2 .. 19));
+        assert_eq!(Some((3, 7)), ef.next_geq(3));
+        assert_eq!(Some(Error::NotIncreasing),
+                   EliasFano::try_new_multiset(20, vec![3, 2]).err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_order() {
+        EliasFano::new(10, vec![1, 3, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_range() {
+        EliasFano::new(10, vec![1, 10]);
+    }
+
+    #[test]
+    fn qc_multiset_counts() {
+        fn prop(values: Vec<u8>, start: u8, end: u8) -> bool {
+            let mut values: Vec<u64> = values.into_iter().map(|x| x as u64 % 64).collect();
+            values.sort();
+            let ef = EliasFano::new_multiset(64, values.iter().cloned());
+            let (start, end) = (start as u64 % 70, end as u64 % 70);
+
+            ef.iter().collect::<Vec<_>>() == values
+                && ef.count(start) == values.iter().filter(|&&x| x == start).count() as u64
+                && ef.count_range(start .. end)
+                    == values.iter().filter(|&&x| start <= x && x < end).count() as u64
+        }
+
+        quickcheck(prop as fn(Vec<u8>, u8, u8) -> bool);
+    }
+
+    #[test]
+    fn qc_matches_btree() {
+        fn prop(values: Vec<u32>, universe: u32, probes: Vec<u32>) -> bool {
+            let universe = universe as u64 % 100_000 + 1;
+            let set: BTreeSet<u64> = values.into_iter()
+                .map(|x| x as u64 % universe)
+                .collect();
+            let ef = EliasFano::new(universe, set.iter().cloned());
+
+            let mut probes: Vec<u64> = probes.into_iter()
+                .map(|x| x as u64 % (universe + 10))
+                .collect();
+            probes.sort();
+            let mut cursor = ef.cursor();
+
+            ef.iter().collect::<Vec<_>>() == set.iter().cloned().collect::<Vec<_>>()
+                && set.iter().enumerate().all(|(i, &x)| ef.get(i as u64) == x)
+                && probes.into_iter().all(|probe| {
+                    let expected = set.range(probe ..).next().map(|&x| {
+                        (set.range(.. x).count() as u64, x)
+                    });
+                    ef.next_geq(probe) == expected && cursor.next_geq(probe) == expected
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u32>, u32, Vec<u32>) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u16>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let mut values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            values.sort();
+            let ef = EliasFano::new_multiset(1 << 16, values.iter().cloned());
+            skips_agree(ef.iter(), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn qc_archived() {
+        use access::Access;
+        use rkyv::rancor::Error;
+
+        fn prop(values: Vec<u32>, probes: Vec<u32>) -> bool {
+            let values: BTreeSet<u64> = values.into_iter().map(|v| v as u64 % 100_000).collect();
+            let ef = EliasFano::new(100_000, values.iter().cloned());
+            let bytes = rkyv::to_bytes::<Error>(&ef).unwrap();
+            let archived = rkyv::access::<rkyv::Archived<EliasFano>, Error>(&bytes).unwrap();
+            let back: EliasFano = rkyv::deserialize::<_, Error>(archived).unwrap();
+
+            archived.universe() == 100_000
+                && archived.len() == ef.len()
+                && Access::iter(archived).eq(values.iter().cloned())
+                && probes.iter().all(|&probe| {
+                    let probe = probe as u64 % 100_001;
+                    archived.next_geq(probe) == ef.next_geq(probe)
+                        && archived.contains(probe) == ef.contains(probe)
+                        && archived.count_less(probe) == ef.count_less(probe)
+                })
+                && back.iter().eq(ef.iter())
+        }
+
+        quickcheck(prop as fn(Vec<u32>, Vec<u32>) -> bool);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived_rejects_truncation() {
+        use rkyv::rancor::Error;
+        use rkyv::util::AlignedVec;
+
+        let ef = EliasFano::new(1000, vec![3, 17, 18, 420, 999]);
+        let bytes = rkyv::to_bytes::<Error>(&ef).unwrap();
+        let mut truncated = AlignedVec::<16>::new();
+        truncated.extend_from_slice(&bytes[.. bytes.len() - 8]);
+        assert!(rkyv::access::<rkyv::Archived<EliasFano>, Error>(&truncated).is_err());
+    }
+}
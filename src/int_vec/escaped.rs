@@ -0,0 +1,301 @@
+use bit_vec::{BitVecPush, BitVector};
+use dump::Snapshot;
+use int_vec::{IntVec, IntVector};
+use int_vec::exceptions::{ExceptionIter, Exceptions};
+use rank::{BitRankSupport, Rank9};
+use space_usage::SpaceUsage;
+use storage::BlockType;
+use util::bits_to_represent;
+
+/// An immutable integer vector that stores most elements in a few bits
+/// and escapes the outliers to a wider array.
+///
+/// Each element gets `low_bits` bits. Values too large for that store
+/// the escape marker, all ones, instead, and go in order to an overflow
+/// vector just wide enough for the largest of them. A bitmap with
+/// `Rank9` finds an escaped element’s place in the overflow vector.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::int_vec::EscapedIntVector;
+///
+/// let v = EscapedIntVector::new(3, vec![1u32, 5, 100_000, 2, 7]);
+/// assert_eq!(2, v.escape_count()); // 7 is the marker, so it escapes too
+/// assert_eq!(100_000, v.get(2));
+/// assert_eq!(7, v.get(4));
+/// ```
+#[derive(Clone, Debug)]
+pub struct EscapedIntVector<Block: BlockType = usize> {
+    low: IntVector<Block>,
+    escaped: Rank9<BitVector<u64>>,
+    overflow: IntVector<Block>,
+}
+
+impl<Block: BlockType> EscapedIntVector<Block> {
+    /// Creates a vector from an iterator, storing each element in
+    /// `low_bits` bits unless it needs to escape.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low_bits` is 0 or larger than the block size.
+    pub fn new<I>(low_bits: usize, elements: I) -> Self
+        where I: IntoIterator<Item = Block> {
+
+        let mut low = IntVector::new(low_bits);
+        let mut escaped = BitVector::new();
+        let mut outliers = Vec::new();
+        let marker = Block::low_mask(low_bits);
+
+        for element in elements {
+            if element >= marker {
+                low.push(marker);
+                escaped.push_bit(true);
+                outliers.push(element);
+            } else {
+                low.push(element);
+                escaped.push_bit(false);
+            }
+        }
+
+        low.shrink_to_fit();
+        escaped.shrink_to_fit();
+
+        let wide_bits = outliers.iter().cloned().max()
+                                .map_or(1, bits_to_represent);
+        let mut overflow = IntVector::with_capacity(wide_bits,
+                                                    outliers.len() as u64);
+        for outlier in outliers {
+            overflow.push(outlier);
+        }
+
+        EscapedIntVector {
+            low,
+            escaped: Rank9::new(escaped),
+            overflow,
+        }
+    }
+
+    /// Creates a copy of `source`, storing each element in `low_bits`
+    /// bits unless it needs to escape.
+    pub fn from_int_vec<V>(source: &V, low_bits: usize) -> Self
+        where V: IntVec<Block = Block> + ?Sized {
+
+        Self::new(low_bits, (0 .. source.len()).map(|i| source.get(i)))
+    }
+
+    /// Chooses the `low_bits` that makes the copy of `source` smallest.
+    ///
+    /// The escape bitmap costs the same whatever the choice, so this
+    /// weighs the primary array against the overflow vector.
+    pub fn best_low_bits<V>(source: &V) -> usize
+        where V: IntVec<Block = Block> + ?Sized {
+
+        // widths[b] counts the elements that need exactly b bits, and
+        // markers[b] those that are also all ones, so would escape at b.
+        let mut widths = vec![0u64; Block::nbits() + 1];
+        let mut markers = vec![0u64; Block::nbits() + 1];
+        for i in 0 .. source.len() {
+            let value = source.get(i);
+            let bits = bits_to_represent(value);
+            widths[bits] += 1;
+            if value == Block::low_mask(bits) {
+                markers[bits] += 1;
+            }
+        }
+
+        let widest = widths.iter().rposition(|&count| count > 0).unwrap_or(1);
+        let mut best = (u64::MAX, widest);
+
+        for low_bits in 1 .. (widest + 1).min(Block::nbits()) + 1 {
+            let escapes = markers[low_bits]
+                + widths[low_bits + 1 ..].iter().sum::<u64>();
+            let cost = source.len() * low_bits as u64 + escapes * widest as u64;
+            if cost < best.0 {
+                best = (cost, low_bits);
+            }
+        }
+
+        best.1
+    }
+
+    /// The number of bits per element in the primary array.
+    pub fn low_bits(&self) -> usize {
+        self.low.element_bits()
+    }
+
+    /// The number of elements stored in the overflow vector.
+    pub fn escape_count(&self) -> u64 {
+        self.overflow.len()
+    }
+
+    /// Iterates over the elements in order.
+    pub fn iter(&self) -> EscapedIter<'_, Block> {
+        EscapedIter(ExceptionIter::new(self))
+    }
+}
+
+impl<Block: BlockType> IntVec for EscapedIntVector<Block> {
+    type Block = Block;
+
+    fn len(&self) -> u64 {
+        self.low.len()
+    }
+
+    /// The width of the widest element, which may be more than
+    /// `low_bits`.
+    fn element_bits(&self) -> usize {
+        self.low.element_bits().max(self.overflow.element_bits())
+    }
+
+    fn get(&self, index: u64) -> Block {
+        let value = self.low.get(index);
+        if value == Block::low_mask(self.low.element_bits()) {
+            self.overflow.get(self.escaped.rank1(index) - 1)
+        } else {
+            value
+        }
+    }
+}
+
+/// Lists the elements in order.
+impl<Block: BlockType> Snapshot for EscapedIntVector<Block> {
+    type Item = Block;
+    type Iter<'a> = EscapedIter<'a, Block> where Self: 'a;
+
+    fn dump_iter(&self) -> EscapedIter<'_, Block> {
+        self.iter()
+    }
+}
+
+impl<Block: BlockType> SpaceUsage for EscapedIntVector<Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.low.heap_bytes()
+            + self.escaped.heap_bytes()
+            + self.overflow.heap_bytes()
+    }
+}
+
+impl<Block: BlockType> Exceptions for EscapedIntVector<Block> {
+    type Block = Block;
+
+    fn element_count(&self) -> u64 {
+        self.len()
+    }
+
+    fn exception_count(&self) -> u64 {
+        self.overflow.len()
+    }
+
+    fn inline(&self, index: u64) -> Option<Block> {
+        let value = self.low.get(index);
+        if value == Block::low_mask(self.low.element_bits()) {None} else {Some(value)}
+    }
+
+    fn exception(&self, place: u64) -> Block {
+        self.overflow.get(place)
+    }
+
+    fn exceptions_through(&self, index: u64) -> u64 {
+        self.escaped.rank1(index)
+    }
+}
+
+/// An iterator over the elements of an
+/// [`EscapedIntVector`](struct.EscapedIntVector.html).
+///
+/// It counts the escapes it passes instead of ranking each one, and
+/// ranks only to skip with `nth`.
+#[derive(Clone, Debug)]
+pub struct EscapedIter<'a, Block: BlockType + 'a = usize>(
+    ExceptionIter<'a, EscapedIntVector<Block>>);
+
+impl_iterator_newtype!(['a, Block: BlockType] EscapedIter<'a, Block> => Block);
+
+impl<'a, Block: BlockType> IntoIterator for &'a EscapedIntVector<Block> {
+    type Item = Block;
+    type IntoIter = EscapedIter<'a, Block>;
+
+    fn into_iter(self) -> EscapedIter<'a, Block> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use int_vec::IntVecMut;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let v = EscapedIntVector::<u16>::new(4, vec![]);
+        assert_eq!(0, v.len());
+        assert_eq!(0, v.escape_count());
+        assert_eq!(4, v.element_bits());
+    }
+
+    #[test]
+    fn outliers() {
+        let mut elements = vec![3u64; 10_000];
+        elements[5] = 1 << 40;
+        elements[9000] = 15;
+        elements[9999] = 16;
+
+        let v = EscapedIntVector::new(4, elements.iter().cloned());
+        assert_eq!(3, v.escape_count());
+        assert_eq!(41, v.element_bits());
+        for (i, &x) in elements.iter().enumerate() {
+            assert_eq!(x, v.get(i as u64));
+        }
+
+        let dense = {
+            let mut dense = IntVector::<u64>::new(41);
+            for &x in &elements { dense.push(x); }
+            dense
+        };
+        assert!(v.total_bytes() < dense.total_bytes() / 4);
+    }
+
+    #[test]
+    fn best_low_bits() {
+        let mut source = IntVector::<u32>::with_fill(20, 1000, 5);
+        assert_eq!(3, EscapedIntVector::best_low_bits(&source));
+
+        // Once many elements need 20 bits, escaping stops paying.
+        for i in 0 .. 900 {
+            source.set(i, 1 << 19);
+        }
+        assert_eq!(20, EscapedIntVector::best_low_bits(&source));
+    }
+
+    #[test]
+    fn qc_round_trip() {
+        fn prop(elements: Vec<u32>, low_bits: u8) -> bool {
+            let low_bits = low_bits as usize % 32 + 1;
+            let v = EscapedIntVector::new(low_bits, elements.iter().cloned());
+            v.len() == elements.len() as u64
+                && elements.iter().enumerate()
+                           .all(|(i, &x)| v.get(i as u64) == x)
+        }
+
+        quickcheck(prop as fn(Vec<u32>, u8) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u16>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            let v = EscapedIntVector::<u64>::new(2, values.iter().cloned());
+            skips_agree(v.iter(), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+}
@@ -0,0 +1,113 @@
+use storage::BlockType;
+
+// An integer vector that stores most elements inline and the rest, its
+// exceptions, in order in a separate vector.
+pub trait Exceptions {
+    type Block: BlockType;
+
+    // The number of elements.
+    fn element_count(&self) -> u64;
+
+    // The number of exceptions.
+    fn exception_count(&self) -> u64;
+
+    // The `index`th element, or `None` if it is an exception.
+    fn inline(&self, index: u64) -> Option<Self::Block>;
+
+    // The `place`th exception.
+    fn exception(&self, place: u64) -> Self::Block;
+
+    // The number of exceptions at or before `index`.
+    fn exceptions_through(&self, index: u64) -> u64;
+}
+
+// An iterator over the elements of an `Exceptions` vector, shared by
+// `SparseIter` and `EscapedIter`.
+#[derive(Debug)]
+pub struct ExceptionIter<'a, V: 'a> {
+    data: &'a V,
+    index: u64,
+    end: u64,
+    // The places of the next exception from each end.
+    front: u64,
+    back: u64,
+}
+
+impl<'a, V: Exceptions> ExceptionIter<'a, V> {
+    pub fn new(data: &'a V) -> Self {
+        ExceptionIter {
+            data,
+            index: 0,
+            end: data.element_count(),
+            front: 0,
+            back: data.exception_count(),
+        }
+    }
+}
+
+impl<'a, V> Clone for ExceptionIter<'a, V> {
+    fn clone(&self) -> Self {
+        ExceptionIter { ..*self }
+    }
+}
+
+impl<'a, V: Exceptions> Iterator for ExceptionIter<'a, V> {
+    type Item = V::Block;
+
+    fn next(&mut self) -> Option<V::Block> {
+        if self.index >= self.end { return None; }
+
+        let value = self.data.inline(self.index);
+        self.index += 1;
+        value.or_else(|| {
+            self.front += 1;
+            Some(self.data.exception(self.front - 1))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.index) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<V::Block> {
+        let target = self.index.saturating_add(n as u64);
+        if target >= self.end {
+            self.index = self.end;
+            return None;
+        }
+        if n > 0 {
+            self.index = target;
+            self.front = self.data.exceptions_through(target - 1);
+        }
+        self.next()
+    }
+
+    impl_exact_size_count_last!(V::Block);
+}
+
+impl<'a, V: Exceptions> ExactSizeIterator for ExceptionIter<'a, V> {}
+
+impl<'a, V: Exceptions> DoubleEndedIterator for ExceptionIter<'a, V> {
+    fn next_back(&mut self) -> Option<V::Block> {
+        if self.index >= self.end { return None; }
+
+        self.end -= 1;
+        self.data.inline(self.end).or_else(|| {
+            self.back -= 1;
+            Some(self.data.exception(self.back))
+        })
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<V::Block> {
+        if n as u64 >= self.end - self.index {
+            self.end = self.index;
+            return None;
+        }
+        if n > 0 {
+            self.end -= n as u64;
+            self.back = self.data.exceptions_through(self.end - 1);
+        }
+        self.next_back()
+    }
+}
@@ -0,0 +1,199 @@
+use int_vec::{IntVec, IntVecMut, IntVector};
+use space_usage::SpaceUsage;
+use storage::BlockType;
+
+/// An immutable sorted set of integers laid out for fast predecessor
+/// and successor search.
+///
+/// The keys are stored in Eytzinger order, the breadth-first order of a
+/// complete binary search tree, in an
+/// [`IntVector`](struct.IntVector.html). A search walks down from the
+/// root, picking each child arithmetically rather than by a branch, and
+/// the first levels, which every search visits, share a few cache
+/// lines; a binary search over the sorted array instead touches a new
+/// line at nearly every step.
+///
+/// # Example
+///
+/// ```
+/// use succinct::int_vec::EytzingerVector;
+///
+/// let keys = EytzingerVector::<u32>::new(10, vec![3, 17, 18, 40, 1000]);
+///
+/// assert_eq!(Some(18), keys.predecessor(39));
+/// assert_eq!(Some(40), keys.successor(19));
+/// assert_eq!(None, keys.predecessor(2));
+/// assert!(keys.contains(1000));
+/// ```
+#[derive(Clone, Debug)]
+pub struct EytzingerVector<Block: BlockType = usize> {
+    // Slot 0 is unused, so the children of node k are 2k and 2k + 1.
+    keys: IntVector<Block>,
+}
+
+impl<Block: BlockType> EytzingerVector<Block> {
+    /// Creates a search structure with `element_bits`-bit keys from an
+    /// iterator in non-decreasing order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element_bits` is invalid, a key doesn’t fit, or the
+    /// keys are out of order.
+    pub fn new<I>(element_bits: usize, sorted: I) -> Self
+        where I: IntoIterator<Item = Block> {
+
+        let sorted: Vec<Block> = sorted.into_iter().collect();
+        assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]),
+                "EytzingerVector::new: keys out of order");
+
+        let len = sorted.len() as u64;
+        let mut keys = IntVector::with_fill(element_bits, len + 1, Block::zero());
+
+        for (node, key) in in_order(len).into_iter().zip(sorted) {
+            keys.set(node, key);
+        }
+
+        EytzingerVector { keys }
+    }
+
+    /// Creates a search structure over the elements of `source`, which
+    /// must be sorted.
+    pub fn from_int_vec<V>(source: &V) -> Self
+        where V: IntVec<Block = Block> + ?Sized {
+
+        Self::new(source.element_bits(),
+                  (0 .. source.len()).map(|i| source.get(i)))
+    }
+
+    /// The number of keys.
+    pub fn len(&self) -> u64 {
+        self.keys.len() - 1
+    }
+
+    /// Are there no keys?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of bits per key.
+    pub fn element_bits(&self) -> usize {
+        self.keys.element_bits()
+    }
+
+    // The node of the result, or 0, after walking down while `right`
+    // says to and then backing up past the turns of the wrong kind.
+    fn search<F>(&self, right: F, last_turn_right: bool) -> u64
+        where F: Fn(Block) -> bool {
+
+        let len = self.len();
+        let mut node = 1;
+        while node <= len {
+            node = 2 * node + right(self.keys.get(node)) as u64;
+        }
+
+        if last_turn_right {
+            node >> (node.trailing_zeros() + 1)
+        } else {
+            node >> (node.trailing_ones() + 1)
+        }
+    }
+
+    fn key_at(&self, node: u64) -> Option<Block> {
+        if node == 0 {None} else {Some(self.keys.get(node))}
+    }
+
+    /// The largest key no greater than `value`.
+    pub fn predecessor(&self, value: Block) -> Option<Block> {
+        self.key_at(self.search(|key| key <= value, true))
+    }
+
+    /// The smallest key no less than `value`.
+    pub fn successor(&self, value: Block) -> Option<Block> {
+        self.key_at(self.search(|key| key < value, false))
+    }
+
+    /// Is `value` a key?
+    pub fn contains(&self, value: Block) -> bool {
+        self.successor(value) == Some(value)
+    }
+
+    /// The keys in increasing order.
+    pub fn to_sorted_vec(&self) -> Vec<Block> {
+        in_order(self.len()).into_iter().map(|node| self.keys.get(node)).collect()
+    }
+}
+
+// The nodes of a tree of `len` nodes in Eytzinger order, visited in
+// order, which is the sorted order of their keys.
+fn in_order(len: u64) -> Vec<u64> {
+    let mut result = Vec::with_capacity(len as usize);
+    let mut node = 1;
+    let mut stack = Vec::new();
+    loop {
+        while node <= len {
+            stack.push(node);
+            node *= 2;
+        }
+        match stack.pop() {
+            Some(top) => {
+                result.push(top);
+                node = 2 * top + 1;
+            }
+            None => return result,
+        }
+    }
+}
+
+impl<Block: BlockType> SpaceUsage for EytzingerVector<Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.keys.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let keys = EytzingerVector::<u8>::new(4, vec![]);
+        assert!(keys.is_empty());
+        assert_eq!(None, keys.predecessor(15));
+        assert_eq!(None, keys.successor(0));
+    }
+
+    #[test]
+    fn duplicates() {
+        let keys = EytzingerVector::<u16>::new(8, vec![5, 5, 5, 9]);
+        assert_eq!(Some(5), keys.successor(1));
+        assert_eq!(Some(5), keys.predecessor(8));
+        assert_eq!(Some(9), keys.successor(6));
+        assert_eq!(vec![5, 5, 5, 9], keys.to_sorted_vec());
+    }
+
+    #[test]
+    #[should_panic]
+    fn unsorted() {
+        EytzingerVector::<u8>::new(8, vec![2, 1]);
+    }
+
+    #[test]
+    fn qc_matches_btree() {
+        fn prop(keys: Vec<u16>, probes: Vec<u16>) -> bool {
+            let set: BTreeSet<u16> = keys.into_iter().collect();
+            let keys = EytzingerVector::<u16>::new(16, set.iter().cloned());
+
+            keys.to_sorted_vec() == set.iter().cloned().collect::<Vec<_>>()
+                && probes.into_iter().all(|probe| {
+                    keys.predecessor(probe) == set.range(..= probe).next_back().cloned()
+                        && keys.successor(probe) == set.range(probe ..).next().cloned()
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u16>, Vec<u16>) -> bool);
+    }
+}
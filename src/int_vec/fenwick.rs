@@ -0,0 +1,401 @@
+use std::ops::Range;
+
+use error::Error;
+use int_vec::{IntVec, IntVecMut, IntVector};
+use int_vec::alias::uniform;
+use space_usage::SpaceUsage;
+use storage::BlockType;
+
+/// A sequence of `element_bits`-bit counters with logarithmic-time
+/// updates and prefix sums: a Fenwick tree, or binary indexed tree.
+///
+/// Node *i*, counting from 1, holds the sum of the elements in
+/// `i - lowbit(i) .. i`, packed in an
+/// [`IntVector`](struct.IntVector.html) as tightly as the element width
+/// allows. Updates come in three flavors, as on the primitive integers:
+/// `add` panics if the total would no longer fit in `element_bits`,
+/// `try_add` returns an error and leaves the tree unchanged, and
+/// `wrapping_add` works modulo 2<sup>`element_bits`</sup>, as do the sums
+/// afterward.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::int_vec::FenwickTree;
+///
+/// let mut counts = FenwickTree::<u32>::new(12, 10);
+/// counts.add(2, 5);
+/// counts.add(7, 1);
+/// counts.add(2, 3);
+///
+/// assert_eq!(8, counts.get(2));
+/// assert_eq!(8, counts.prefix_sum(7));
+/// assert_eq!(9, counts.prefix_sum(8));
+/// assert_eq!(1, counts.range_sum(3 .. 10));
+/// assert_eq!(7, counts.search(8));
+/// assert!(counts.try_add(0, 4095).is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct FenwickTree<Block: BlockType = usize> {
+    // Node i is at index i - 1.
+    nodes: IntVector<Block>,
+    total: Block,
+}
+
+fn lowbit(index: u64) -> u64 {
+    index & index.wrapping_neg()
+}
+
+impl<Block: BlockType> FenwickTree<Block> {
+    /// Creates a tree of `len` zeros.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element_bits` is invalid.
+    pub fn new(element_bits: usize, len: u64) -> Self {
+        FenwickTree {
+            nodes: IntVector::with_fill(element_bits, len, Block::zero()),
+            total: Block::zero(),
+        }
+    }
+
+    /// Creates a tree holding the given values, in linear time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element_bits` is invalid or the values’ total doesn’t
+    /// fit.
+    pub fn from_values<I>(element_bits: usize, values: I) -> Self
+        where I: IntoIterator<Item = Block> {
+
+        let mut nodes: Vec<Block> = values.into_iter().collect();
+        let mask = Block::low_mask(element_bits);
+        let total = nodes.iter().try_fold(Block::zero(), |sum, &value| {
+            sum.checked_add(&value).filter(|&sum| sum <= mask)
+        }).expect("FenwickTree::from_values: total too large");
+
+        // Each node passes its sum up to its parent; none exceeds the
+        // total.
+        let len = nodes.len();
+        for i in 1 .. len + 1 {
+            let parent = i + lowbit(i as u64) as usize;
+            if parent <= len {
+                nodes[parent - 1] = nodes[parent - 1] + nodes[i - 1];
+            }
+        }
+
+        let mut result = Self::new(element_bits, len as u64);
+        for (i, &node) in nodes.iter().enumerate() {
+            result.nodes.set(i as u64, node);
+        }
+        result.total = total;
+        result
+    }
+
+    // Adds modulo 2^element_bits.
+    fn wrap_add(&self, x: Block, y: Block) -> Block {
+        let mask = Block::low_mask(self.nodes.element_bits());
+        let y = y & mask;
+        match x.checked_add(&y) {
+            Some(sum) => sum & mask,
+            // Only possible when the elements are as wide as the block.
+            None => x - (mask - y) - Block::one(),
+        }
+    }
+
+    // Subtracts modulo 2^element_bits.
+    fn wrap_sub(&self, x: Block, y: Block) -> Block {
+        let mask = Block::low_mask(self.nodes.element_bits());
+        let y = y & mask;
+        if x >= y { x - y } else { mask - (y - x) + Block::one() }
+    }
+
+    // Applies `step` to every node covering element `index`.
+    fn update<F>(&mut self, index: u64, step: F)
+        where F: Fn(&Self, Block) -> Block {
+
+        assert!(index < self.len(), "FenwickTree: index out of bounds");
+        let mut node = index + 1;
+        while node <= self.len() {
+            let value = step(self, self.nodes.get(node - 1));
+            self.nodes.set(node - 1, value);
+            node += lowbit(node);
+        }
+    }
+
+    /// Adds `delta` to element `index`, or returns an error, changing
+    /// nothing, if the total would not fit in `element_bits`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn try_add(&mut self, index: u64, delta: Block) -> Result<(), Error> {
+        assert!(index < self.len(), "FenwickTree::try_add: out of bounds");
+        let mask = Block::low_mask(self.nodes.element_bits());
+        let total = match self.total.checked_add(&delta) {
+            Some(total) if total <= mask => total,
+            _ => return Err(Error::ValueTooLarge),
+        };
+
+        // Every node is part of the total, so none overflows unless an
+        // earlier update wrapped.
+        self.update(index, |tree, value| tree.wrap_add(value, delta));
+        self.total = total;
+        Ok(())
+    }
+
+    /// Adds `delta` to element `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the total would not fit in
+    /// `element_bits`.
+    pub fn add(&mut self, index: u64, delta: Block) {
+        self.try_add(index, delta).expect("FenwickTree::add: overflow");
+    }
+
+    /// Adds `delta` to element `index` modulo 2<sup>`element_bits`</sup>.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn wrapping_add(&mut self, index: u64, delta: Block) {
+        self.update(index, |tree, value| tree.wrap_add(value, delta));
+        self.total = self.wrap_add(self.total, delta);
+    }
+
+    /// Subtracts `delta` from element `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the element is less than
+    /// `delta`.
+    pub fn sub(&mut self, index: u64, delta: Block) {
+        assert!(self.get(index) >= delta, "FenwickTree::sub: underflow");
+        self.update(index, |tree, value| tree.wrap_sub(value, delta));
+        self.total = self.wrap_sub(self.total, delta);
+    }
+
+    /// Subtracts `delta` from element `index` modulo
+    /// 2<sup>`element_bits`</sup>.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn wrapping_sub(&mut self, index: u64, delta: Block) {
+        self.update(index, |tree, value| tree.wrap_sub(value, delta));
+        self.total = self.wrap_sub(self.total, delta);
+    }
+
+    /// The sum of the elements before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn prefix_sum(&self, end: u64) -> Block {
+        assert!(end <= self.len(), "FenwickTree::prefix_sum: out of bounds");
+        let mut result = Block::zero();
+        let mut node = end;
+        while node > 0 {
+            result = self.wrap_add(result, self.nodes.get(node - 1));
+            node -= lowbit(node);
+        }
+        result
+    }
+
+    /// The sum of the elements in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn range_sum(&self, range: Range<u64>) -> Block {
+        assert!(range.start <= range.end,
+                "FenwickTree::range_sum: bad range");
+        self.wrap_sub(self.prefix_sum(range.end), self.prefix_sum(range.start))
+    }
+
+    /// The sum of all the elements.
+    pub fn total(&self) -> Block {
+        self.total
+    }
+
+    /// The largest `end` such that `prefix_sum(end) <= value`, in one
+    /// descent of the tree.
+    ///
+    /// The answer is only meaningful if no sum has wrapped.
+    pub fn search(&self, value: Block) -> u64 {
+        let len = self.len();
+        let mut step = if len == 0 {0} else {1 << (63 - len.leading_zeros())};
+        let mut end = 0;
+        let mut remaining = value;
+
+        while step > 0 {
+            let next = end + step;
+            if next <= len && self.nodes.get(next - 1) <= remaining {
+                remaining = remaining - self.nodes.get(next - 1);
+                end = next;
+            }
+            step >>= 1;
+        }
+
+        end
+    }
+
+    /// Draws an index with probability proportional to its element, or
+    /// returns `None` if the elements are all zero, in one descent of the
+    /// tree.
+    ///
+    /// Randomness comes from any `FnMut() -> u64` producing uniform
+    /// words; see [`AliasTable`](struct.AliasTable.html) for constant-time
+    /// draws from weights that don’t change. The answer is only
+    /// meaningful if no sum has wrapped.
+    pub fn sample<R: FnMut() -> u64>(&self, rng: &mut R) -> Option<u64> {
+        let total = self.total.to_u64().expect("FenwickTree::sample: total too large");
+        if total == 0 { return None; }
+        let point = uniform(rng, total);
+        Some(self.search(Block::from(point).expect("FenwickTree::sample: bad point")))
+    }
+}
+
+impl<Block: BlockType> IntVec for FenwickTree<Block> {
+    type Block = Block;
+
+    fn len(&self) -> u64 {
+        self.nodes.len()
+    }
+
+    fn element_bits(&self) -> usize {
+        self.nodes.element_bits()
+    }
+
+    fn get(&self, index: u64) -> Block {
+        assert!(index < self.len(), "FenwickTree::get: out of bounds");
+
+        // Node index + 1 covers the element and some before it; take
+        // away the nodes that make up the sum of those.
+        let node = index + 1;
+        let stop = node - lowbit(node);
+        let mut result = self.nodes.get(node - 1);
+        let mut other = node - 1;
+        while other > stop {
+            result = self.wrap_sub(result, self.nodes.get(other - 1));
+            other -= lowbit(other);
+        }
+        result
+    }
+}
+
+impl<Block: BlockType> SpaceUsage for FenwickTree<Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.nodes.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::{quickcheck, TestResult};
+
+    #[test]
+    fn empty() {
+        let tree = FenwickTree::<u8>::new(4, 0);
+        assert_eq!(0, tree.prefix_sum(0));
+        assert_eq!(0, tree.search(5));
+    }
+
+    #[test]
+    fn overflow() {
+        let mut tree = FenwickTree::<u8>::new(4, 5);
+        tree.add(1, 10);
+        assert_eq!(Err(Error::ValueTooLarge), tree.try_add(3, 6));
+        assert_eq!(0, tree.get(3));
+        assert_eq!(10, tree.total());
+
+        tree.wrapping_add(3, 6);
+        assert_eq!(0, tree.total());
+        assert_eq!(6, tree.get(3));
+        assert_eq!(10, tree.prefix_sum(3));
+        tree.wrapping_sub(1, 12);
+        assert_eq!(14, tree.get(1));
+    }
+
+    #[test]
+    fn full_width() {
+        let mut tree = FenwickTree::<u8>::new(8, 3);
+        tree.add(0, 200);
+        tree.wrapping_add(2, 100);
+        assert_eq!(44, tree.total());
+        assert_eq!(100, tree.range_sum(1 .. 3));
+        tree.wrapping_sub(0, 250);
+        assert_eq!(206, tree.get(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn sub_underflow() {
+        let mut tree = FenwickTree::<u32>::new(8, 4);
+        tree.add(1, 3);
+        tree.sub(1, 4);
+    }
+
+    #[test]
+    fn sample() {
+        let mut tree = FenwickTree::<u32>::from_values(16, vec![0, 3, 0, 1]);
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut rng = || { state ^= state << 13; state ^= state >> 7; state ^= state << 17; state };
+
+        let draws: Vec<u64> = (0 .. 4000).map(|_| tree.sample(&mut rng).unwrap()).collect();
+        assert!(draws.iter().all(|&i| i == 1 || i == 3));
+        let ones = draws.iter().filter(|&&i| i == 1).count();
+        assert!(2800 < ones && ones < 3200, "{} of 4000", ones);
+
+        tree.sub(1, 3);
+        assert_eq!(Some(3), tree.sample(&mut rng));
+        tree.sub(3, 1);
+        assert_eq!(None, tree.sample(&mut rng));
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(values: Vec<u8>, updates: Vec<(usize, u8, bool)>) -> TestResult {
+            if values.is_empty() { return TestResult::discard(); }
+
+            let mut naive: Vec<u32> = values.into_iter().map(u32::from).collect();
+            let mut tree = FenwickTree::<u32>::from_values(20, naive.iter().cloned());
+
+            for (index, delta, subtract) in updates {
+                let index = index % naive.len();
+                let delta = u32::from(delta);
+                if subtract {
+                    let delta = delta.min(naive[index]);
+                    naive[index] -= delta;
+                    tree.sub(index as u64, delta);
+                } else {
+                    naive[index] += delta;
+                    tree.add(index as u64, delta);
+                }
+            }
+
+            let mut sum = 0;
+            for (i, &value) in naive.iter().enumerate() {
+                if tree.prefix_sum(i as u64) != sum || tree.get(i as u64) != value {
+                    return TestResult::failed();
+                }
+                sum += value;
+
+                let end = tree.search(sum);
+                if tree.prefix_sum(end) != sum
+                    || (end < tree.len() && tree.prefix_sum(end + 1) == sum) {
+                    return TestResult::failed();
+                }
+            }
+
+            TestResult::from_bool(tree.total() == sum)
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<(usize, u8, bool)>) -> TestResult);
+    }
+}
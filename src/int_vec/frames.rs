@@ -0,0 +1,271 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use error::Error;
+use int_vec::{IntVec, IntVector};
+use space_usage::SpaceUsage;
+
+/// The frame layouts of the `bitpacking` crate, for `u32`s packed at a
+/// fixed width.
+///
+/// A frame holds 32 integers per lane. In each lane the integers are
+/// packed low bits first into consecutive little-endian `u32` words, and
+/// the lanes’ words alternate, so the *k*th word of lane *j* is word
+/// *k* · lanes + *j* of the frame, and integer *i* of the frame is in
+/// lane *i* mod lanes. With one lane that is the plain little-endian bit
+/// stream that Parquet’s bit-packed runs use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FrameLayout {
+    /// `BitPacker1x`: 32-integer frames, one lane.
+    #[default]
+    Scalar,
+    /// `BitPacker4x`: 128-integer frames, four lanes.
+    Lanes4,
+    /// `BitPacker8x`: 256-integer frames, eight lanes.
+    Lanes8,
+}
+
+impl FrameLayout {
+    /// The number of interleaved lanes.
+    pub fn lanes(self) -> usize {
+        match self {
+            FrameLayout::Scalar => 1,
+            FrameLayout::Lanes4 => 4,
+            FrameLayout::Lanes8 => 8,
+        }
+    }
+
+    /// The number of integers in a frame.
+    pub fn frame_len(self) -> usize {
+        32 * self.lanes()
+    }
+
+    /// The number of bytes a frame of `bits`-bit integers takes.
+    pub fn frame_bytes(self, bits: usize) -> usize {
+        4 * bits * self.lanes()
+    }
+
+    /// Packs `values` at `bits` bits each into whole frames, the last
+    /// padded with zeros.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` exceeds 32 or a value doesn’t fit.
+    pub fn pack<I>(self, bits: usize, values: I) -> Vec<u8>
+        where I: IntoIterator<Item = u32> {
+
+        assert!(bits <= 32, "FrameLayout::pack: more than 32 bits");
+        let mask = low_mask(bits);
+        let lanes = self.lanes();
+
+        let mut bytes = Vec::new();
+        let mut frame = vec![0u32; bits * lanes];
+        let mut position = 0;
+        for value in values {
+            assert!(value <= mask, "FrameLayout::pack: value too large");
+            if bits > 0 {
+                let (word, offset) = locate(lanes, bits, position);
+                frame[word] |= value << offset;
+                if offset + bits > 32 {
+                    frame[word + lanes] |= value >> (32 - offset);
+                }
+            }
+
+            position += 1;
+            if position == self.frame_len() {
+                flush(&mut bytes, &mut frame);
+                position = 0;
+            }
+        }
+        if position > 0 { flush(&mut bytes, &mut frame); }
+        bytes
+    }
+
+    /// Packs the elements of `values` at their own width.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the elements are wider than 32 bits.
+    pub fn pack_int_vec<V>(self, values: &V) -> Vec<u8>
+        where V: IntVec<Block = u32> + ?Sized {
+
+        self.pack(values.element_bits(), (0 .. values.len()).map(|i| values.get(i)))
+    }
+}
+
+/// A read-only `IntVec` over `u32`s packed in a
+/// [`FrameLayout`](enum.FrameLayout.html), such as the output of the
+/// `bitpacking` crate.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::int_vec::{FrameLayout, FrameView};
+///
+/// let values: Vec<u32> = (0 .. 200).map(|i| i * 3 % 64).collect();
+/// let bytes = FrameLayout::Lanes4.pack(6, values.iter().cloned());
+/// assert_eq!(2 * 6 * 16, bytes.len());
+///
+/// let view = FrameView::new(FrameLayout::Lanes4, 6, 200, &bytes);
+/// assert_eq!(200, view.len());
+/// assert_eq!(values[137], view.get(137));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct FrameView<'a> {
+    bytes: &'a [u8],
+    layout: FrameLayout,
+    bits: usize,
+    len: u64,
+}
+
+impl<'a> FrameView<'a> {
+    /// Views `bytes` as `len` integers of `bits` bits in `layout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` exceeds 32 or `bytes` is too short.
+    pub fn new(layout: FrameLayout, bits: usize, len: u64, bytes: &'a [u8]) -> Self {
+        Self::try_new(layout, bits, len, bytes).expect("FrameView::new")
+    }
+
+    /// Views `bytes` as `len` integers of `bits` bits in `layout`, or
+    /// returns `Error::ElementBits` if `bits` exceeds 32, or
+    /// `Error::TooManyValues` if `bytes` is too short to hold them.
+    pub fn try_new(layout: FrameLayout, bits: usize, len: u64, bytes: &'a [u8])
+                   -> Result<Self, Error> {
+        if bits > 32 { return Err(Error::ElementBits(bits)); }
+        let frames = len.div_ceil(layout.frame_len() as u64);
+        let needed = frames.checked_mul(layout.frame_bytes(bits) as u64)
+                           .ok_or(Error::Overflow)?;
+        if needed > bytes.len() as u64 { return Err(Error::TooManyValues); }
+        Ok(FrameView { bytes, layout, bits, len })
+    }
+
+    /// The layout of the frames.
+    pub fn layout(&self) -> FrameLayout {
+        self.layout
+    }
+
+    /// The packed bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Copies the integers to an `IntVector` of the same width, or one
+    /// bit if they are all zero-width.
+    pub fn to_int_vector(&self) -> IntVector<u32> {
+        let values: Vec<u32> = (0 .. self.len).map(|i| self.get(i)).collect();
+        IntVector::pack_from(self.bits.max(1), &values)
+    }
+
+    fn word(&self, index: usize) -> u32 {
+        LittleEndian::read_u32(&self.bytes[4 * index ..])
+    }
+}
+
+impl<'a> IntVec for FrameView<'a> {
+    type Block = u32;
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn get(&self, index: u64) -> u32 {
+        assert!(index < self.len, "FrameView::get: out of bounds");
+        if self.bits == 0 { return 0; }
+
+        let frame_len = self.layout.frame_len() as u64;
+        let lanes = self.layout.lanes();
+        let base = (index / frame_len) as usize * self.bits * lanes;
+        let (word, offset) = locate(lanes, self.bits, (index % frame_len) as usize);
+
+        let mut value = self.word(base + word) >> offset;
+        if offset + self.bits > 32 {
+            value |= self.word(base + word + lanes) << (32 - offset);
+        }
+        value & low_mask(self.bits)
+    }
+
+    fn element_bits(&self) -> usize {
+        self.bits
+    }
+}
+
+impl<'a> SpaceUsage for FrameView<'a> {
+    fn is_stack_only() -> bool { true }
+
+    fn heap_bytes(&self) -> usize { 0 }
+}
+
+// The word of a frame holding the low bits of integer `position`, and
+// their offset in it.
+fn locate(lanes: usize, bits: usize, position: usize) -> (usize, usize) {
+    let bit = position / lanes * bits;
+    (bit / 32 * lanes + position % lanes, bit % 32)
+}
+
+fn low_mask(bits: usize) -> u32 {
+    if bits == 0 {0} else {!0 >> (32 - bits)}
+}
+
+fn flush(bytes: &mut Vec<u8>, frame: &mut [u32]) {
+    for word in frame.iter_mut() {
+        let mut buffer = [0; 4];
+        LittleEndian::write_u32(&mut buffer, *word);
+        bytes.extend_from_slice(&buffer);
+        *word = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn lane_order() {
+        // One bit each: integer 5 is the second bit of lane 1, which is
+        // word 1 of the frame.
+        let values = (0 .. 128).map(|i| (i == 5) as u32);
+        let bytes = FrameLayout::Lanes4.pack(1, values);
+        assert_eq!(16, bytes.len());
+        assert_eq!(vec![0, 0, 0, 0, 2, 0, 0, 0], bytes[.. 8].to_vec());
+
+        // With one lane the frame is a plain bit stream.
+        let bytes = FrameLayout::Scalar.pack(3, vec![1, 2, 7]);
+        assert_eq!(12, bytes.len());
+        assert_eq!(0b111_010_001, LittleEndian::read_u32(&bytes));
+    }
+
+    #[test]
+    fn zero_width_and_errors() {
+        let view = FrameView::new(FrameLayout::Lanes8, 0, 300, &[]);
+        assert_eq!(0, view.get(299));
+        assert_eq!(1, view.to_int_vector().element_bits());
+
+        assert_eq!(Some(Error::ElementBits(33)),
+                   FrameView::try_new(FrameLayout::Scalar, 33, 0, &[]).err());
+        assert_eq!(Some(Error::TooManyValues),
+                   FrameView::try_new(FrameLayout::Scalar, 4, 33, &[0; 16]).err());
+    }
+
+    #[test]
+    fn qc_round_trip() {
+        fn prop(values: Vec<u32>, bits: u8, layout: u8) -> bool {
+            let bits = bits as usize % 33;
+            let layout = [FrameLayout::Scalar, FrameLayout::Lanes4,
+                          FrameLayout::Lanes8][layout as usize % 3];
+            let values: Vec<u32> = values.into_iter().map(|v| v & low_mask(bits)).collect();
+
+            let bytes = layout.pack(bits, values.iter().cloned());
+            let view = FrameView::new(layout, bits, values.len() as u64, &bytes);
+            let copy = view.to_int_vector();
+
+            (0 .. values.len()).all(|i| view.get(i as u64) == values[i]
+                                        && copy.get(i as u64) == values[i])
+                && (bits == 0 || layout.pack_int_vec(&copy) == bytes)
+        }
+
+        quickcheck(prop as fn(Vec<u32>, u8, u8) -> bool);
+    }
+}
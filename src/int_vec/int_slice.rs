@@ -0,0 +1,353 @@
+use std::cmp::Ordering;
+
+use bit_vec::{BitVec, IntoRange};
+use int_vec::{IntVec, IntVector};
+use space_usage::SpaceUsage;
+use storage::{BlockStore, BlockType};
+
+/// A borrowed slice of an integer vector.
+///
+/// Slices come from [`IntSlice::new`](#method.new), or from the
+/// [`chunks`](trait.IntVec.html#method.chunks) and
+/// [`windows`](trait.IntVec.html#method.windows) of any `IntVec`, and
+/// are themselves `IntVec`s indexed from 0.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{IntVec, IntVector};
+///
+/// let mut v = IntVector::<u32>::new(10);
+/// for i in 0 .. 10 {
+///     v.push(i * i);
+/// }
+///
+/// // The maximum of each block of four, the last one short.
+/// let maxima: Vec<u32> = v.chunks(4)
+///     .map(|chunk| (0 .. chunk.len()).map(|i| chunk.get(i)).max().unwrap())
+///     .collect();
+/// assert_eq!(vec![9, 49, 81], maxima);
+///
+/// let sums: Vec<u32> = v.windows(2).map(|w| w.get(0) + w.get(1)).collect();
+/// assert_eq!(1, sums[0]);
+/// assert_eq!(145, sums[8]);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct IntSlice<'a, Base: 'a + IntVec + ?Sized> {
+    data: &'a Base,
+    start: u64,
+    len: u64,
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> IntSlice<'a, Base> {
+    /// Slices base to the specified range.
+    pub fn new<R: IntoRange<u64>>(base: &'a Base, range: R) -> Self {
+        let range = range.into_range(0, base.len());
+        assert!(range.end <= base.len(), "IntSlice::new: out of bounds");
+        IntSlice {
+            data: base,
+            start: range.start,
+            len: range.end.saturating_sub(range.start),
+        }
+    }
+
+    /// Slices this slice into a subslice.
+    ///
+    /// Unlike `IntSlice::new`, does not create an additional layer of
+    /// indirection.
+    pub fn slice<R: IntoRange<u64>>(&self, range: R) -> Self {
+        let range = range.into_range(0, self.len);
+        assert!(range.end <= self.len, "IntSlice::slice: out of bounds");
+        IntSlice {
+            data: self.data,
+            start: self.start + range.start,
+            len: range.end.saturating_sub(range.start),
+        }
+    }
+
+    /// Where the slice starts in the underlying vector.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+}
+
+impl<'a, Block, Store> IntSlice<'a, IntVector<Block, Store>>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    /// Compares the elements of two slices lexicographically, as
+    /// [`cmp_elements`](trait.IntVec.html#method.cmp_elements) does.
+    ///
+    /// When the widths match, this reads as many whole elements as fit
+    /// in a `Block` from each side at once, wherever the slices start,
+    /// and only picks out single elements where a pair of reads differs.
+    /// For byte-sized elements in `u64` blocks, that’s eight at a time,
+    /// like `memcmp`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    /// use succinct::{IntVector, IntVec};
+    /// use succinct::int_vec::IntSlice;
+    ///
+    /// let keys: IntVector<u64> = vec![3, 1, 4, 1, 5, 3, 1, 4, 2].into_iter().collect();
+    /// let first = IntSlice::new(&keys, 0 .. 4);
+    /// let second = IntSlice::new(&keys, 5 ..);
+    /// assert_eq!(Ordering::Greater, second.cmp_packed(&first));
+    /// assert_eq!(second.cmp_elements(&first), second.cmp_packed(&first));
+    /// ```
+    pub fn cmp_packed<Store2>(&self, other: &IntSlice<IntVector<Block, Store2>>) -> Ordering
+        where Store2: BlockStore<Block = Block> {
+
+        let width = self.element_bits();
+        if width != other.element_bits() {
+            return self.cmp_elements(other);
+        }
+
+        let group = (Block::nbits() / width) as u64;
+        let common = self.len.min(other.len);
+        let mut index = 0;
+        while index < common {
+            let count = group.min(common - index);
+            let mine = self.data.get_bits((self.start + index) * width as u64,
+                                          count as usize * width);
+            let theirs = other.data.get_bits((other.start + index) * width as u64,
+                                             count as usize * width);
+            if mine != theirs {
+                // The lowest differing bit is in the first differing element.
+                let offset = (mine ^ theirs).trailing_zeros() as usize / width * width;
+                return BlockType::get_bits(mine, offset, width)
+                    .cmp(&BlockType::get_bits(theirs, offset, width));
+            }
+            index += count;
+        }
+
+        self.len.cmp(&other.len)
+    }
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> IntVec for IntSlice<'a, Base> {
+    type Block = Base::Block;
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn element_bits(&self) -> usize {
+        self.data.element_bits()
+    }
+
+    fn get(&self, index: u64) -> Base::Block {
+        assert!(index < self.len, "IntSlice::get: out of bounds");
+        self.data.get(self.start + index)
+    }
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> SpaceUsage for IntSlice<'a, Base> {
+    fn is_stack_only() -> bool { true }
+    fn heap_bytes(&self) -> usize { 0 }
+}
+
+/// The non-overlapping slices of an `IntVec`; see
+/// [`IntVec::chunks`](trait.IntVec.html#method.chunks).
+#[derive(Clone, Debug)]
+pub struct Chunks<'a, Base: 'a + IntVec + ?Sized> {
+    data: &'a Base,
+    chunk_len: u64,
+    // The chunks starting in `start .. end` remain.
+    start: u64,
+    end: u64,
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> Chunks<'a, Base> {
+    pub(crate) fn new(data: &'a Base, chunk_len: u64) -> Self {
+        assert!(chunk_len > 0, "IntVec::chunks: chunk length must be positive");
+        Chunks { data, chunk_len, start: 0, end: data.len() }
+    }
+
+    fn chunk(&self, start: u64) -> IntSlice<'a, Base> {
+        let end = (start + self.chunk_len).min(self.data.len());
+        IntSlice::new(self.data, start .. end)
+    }
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> Iterator for Chunks<'a, Base> {
+    type Item = IntSlice<'a, Base>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start >= self.end { return None; }
+        let chunk = self.chunk(self.start);
+        self.start += self.chunk_len;
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end.saturating_sub(self.start).div_ceil(self.chunk_len) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.start = self.start.saturating_add((n as u64).saturating_mul(self.chunk_len));
+        self.next()
+    }
+
+    impl_exact_size_count_last!(Self::Item);
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> ExactSizeIterator for Chunks<'a, Base> {}
+
+impl<'a, Base: 'a + IntVec + ?Sized> DoubleEndedIterator for Chunks<'a, Base> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.start >= self.end { return None; }
+        // The last chunk starts at a multiple of the length.
+        let last = (self.end - 1) / self.chunk_len * self.chunk_len;
+        self.end = last;
+        Some(self.chunk(last))
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        if n >= self.len() {
+            self.end = self.start;
+            return None;
+        }
+        // Leave the end just past the start of the chunk wanted.
+        let last = (self.end - 1) / self.chunk_len * self.chunk_len;
+        self.end = last - n as u64 * self.chunk_len + 1;
+        self.next_back()
+    }
+}
+
+/// The overlapping slices of an `IntVec`; see
+/// [`IntVec::windows`](trait.IntVec.html#method.windows).
+#[derive(Clone, Debug)]
+pub struct Windows<'a, Base: 'a + IntVec + ?Sized> {
+    data: &'a Base,
+    window_len: u64,
+    // The windows starting in `start .. end` remain.
+    start: u64,
+    end: u64,
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> Windows<'a, Base> {
+    pub(crate) fn new(data: &'a Base, window_len: u64) -> Self {
+        assert!(window_len > 0, "IntVec::windows: window length must be positive");
+        let end = (data.len() + 1).saturating_sub(window_len);
+        Windows { data, window_len, start: 0, end }
+    }
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> Iterator for Windows<'a, Base> {
+    type Item = IntSlice<'a, Base>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start >= self.end { return None; }
+        self.start += 1;
+        Some(IntSlice::new(self.data, self.start - 1 .. self.start - 1 + self.window_len))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end.saturating_sub(self.start) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.start = self.start.saturating_add(n as u64);
+        self.next()
+    }
+
+    impl_exact_size_count_last!(Self::Item);
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> ExactSizeIterator for Windows<'a, Base> {}
+
+impl<'a, Base: 'a + IntVec + ?Sized> DoubleEndedIterator for Windows<'a, Base> {
+    impl_index_range_back!(Self::Item, start .. end,
+                           |iter, index| IntSlice::new(iter.data, index .. index + iter.window_len));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use int_vec::IntVector;
+
+    fn squares(len: u64) -> IntVector<u64> {
+        let mut v = IntVector::new(16);
+        for i in 0 .. len {
+            v.push(i * i);
+        }
+        v
+    }
+
+    fn values<V: IntVec>(v: &V) -> Vec<V::Block> {
+        (0 .. v.len()).map(|i| v.get(i)).collect()
+    }
+
+    #[test]
+    fn qc_cmp_packed() {
+        fn prop(a: Vec<u8>, b: Vec<u8>, width: u8, skip: u8) -> bool {
+            let width = width as usize % 8 + 1;
+            let mask = ((1u16 << width) - 1) as u8;
+            let a: Vec<u8> = a.into_iter().map(|x| x & mask).collect();
+            let b: Vec<u8> = b.into_iter().map(|x| x & mask).collect();
+            let skip = (skip as usize).min(a.len());
+
+            let mut va = IntVector::<u16>::new(width);
+            let mut vb = IntVector::<u16>::new(width);
+            for &x in &a { va.push(x as u16); }
+            for &x in &b { vb.push(x as u16); }
+            let sa = IntSlice::new(&va, skip as u64 ..);
+            let sb = IntSlice::new(&vb, ..);
+
+            let expected = a[skip ..].cmp(&b[..]);
+            sa.cmp_packed(&sb) == expected && sa.cmp_elements(&sb) == expected
+                && sb.cmp_packed(&sb) == Ordering::Equal
+        }
+
+        ::quickcheck::quickcheck(prop as fn(Vec<u8>, Vec<u8>, u8, u8) -> bool);
+    }
+
+    #[test]
+    fn slices() {
+        let v = squares(10);
+        let slice = IntSlice::new(&v, 2 ..);
+        assert_eq!(8, slice.len());
+        assert_eq!(9, slice.get(1));
+        assert_eq!(vec![16, 25], values(&slice.slice(2 .. 4)));
+        assert_eq!(4, slice.slice(2 ..).start());
+    }
+
+    #[test]
+    fn chunks() {
+        let v = squares(10);
+        let chunks: Vec<Vec<u64>> = v.chunks(4).map(|chunk| values(&chunk)).collect();
+        assert_eq!(vec![vec![0, 1, 4, 9], vec![16, 25, 36, 49], vec![64, 81]], chunks);
+        assert_eq!(3, v.chunks(4).len());
+        assert_eq!(vec![vec![64, 81], vec![16, 25, 36, 49]],
+                   v.chunks(4).rev().take(2).map(|chunk| values(&chunk)).collect::<Vec<_>>());
+        assert_eq!(Some(vec![64, 81]), v.chunks(4).nth(2).map(|chunk| values(&chunk)));
+        assert_eq!(Some(vec![0, 1, 4, 9]), v.chunks(4).nth_back(2).map(|chunk| values(&chunk)));
+        assert_eq!(None, v.chunks(4).nth_back(3).map(|chunk| values(&chunk)));
+        assert_eq!(vec![vec![64, 81], vec![0, 1, 4, 9]],
+                   v.chunks(4).rev().step_by(2).map(|chunk| values(&chunk)).collect::<Vec<_>>());
+        assert_eq!(Some(vec![36, 49]), v.windows(2).nth_back(2).map(|w| values(&w)));
+        assert_eq!(0, squares(0).chunks(3).count());
+    }
+
+    #[test]
+    fn windows() {
+        let v = squares(5);
+        let windows: Vec<Vec<u64>> = v.windows(3).map(|window| values(&window)).collect();
+        assert_eq!(vec![vec![0, 1, 4], vec![1, 4, 9], vec![4, 9, 16]], windows);
+        assert_eq!(Some(vec![4, 9, 16]), v.windows(3).next_back().map(|w| values(&w)));
+        assert_eq!(3, v.windows(3).len());
+        assert_eq!(0, v.windows(6).count());
+        assert_eq!(None, v.windows(2).nth(4).map(|w| values(&w)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_chunks() {
+        squares(3).chunks(0);
+    }
+}
@@ -1,10 +1,21 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt;
+use std::io::{self, Write};
+use std::iter::FromIterator;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use super::*;
 use bit_vec::{BitVec, BitVecMut};
+use broadword;
+use dump::{Diff, Dump, Snapshot};
+use error::Error;
+use internal::encoding;
 use internal::vector_base::{VectorBase, self};
+use persist::{self, Component, Persist};
 use space_usage::SpaceUsage;
-use storage::BlockType;
+use storage::{BlockStore, BlockType, SharedStore};
 
 /// Uncompressed vector of *k*-bit unsigned integers.
 ///
@@ -12,13 +23,22 @@ use storage::BlockType;
 ///
 /// `Block` gives the representation type. The element width *k* can
 /// never exceed the number of bits in `Block`.
+///
+/// `Store` holds the blocks. By default it’s a `Vec<Block>`, but any
+/// [`BlockStore`](../storage/trait.BlockStore.html) will do, such as a
+/// [`PagedStore`](../storage/struct.PagedStore.html) for very large
+/// vectors.
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub struct IntVector<Block: BlockType = usize> {
+#[cfg_attr(feature = "rkyv", derive(::rkyv::Archive, ::rkyv::Serialize, ::rkyv::Deserialize))]
+pub struct IntVector<Block: BlockType = usize, Store = Vec<Block>> {
     element_bits: usize,
-    base: VectorBase<Block>,
+    base: VectorBase<Block, Store>,
 }
 
-impl<Block: BlockType> IntVector<Block> {
+impl<Block, Store> IntVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     /// Asserts that `element_bits` is valid.
     fn check_element_bits(element_bits: usize) {
         assert!(element_bits != 0,
@@ -27,6 +47,15 @@ impl<Block: BlockType> IntVector<Block> {
                 "IntVector: element size cannot exceed block size");
     }
 
+    /// Checks `element_bits` without panicking.
+    fn try_check_element_bits(element_bits: usize) -> Result<(), Error> {
+        if element_bits == 0 || element_bits > Block::nbits() {
+            Err(Error::ElementBits(element_bits))
+        } else {
+            Ok(())
+        }
+    }
+
     fn check_value_random(element_bits: usize, element_value: Block) {
         assert!(element_value <= Block::low_mask(element_bits),
                 "IntVector: value to large for element size");
@@ -37,7 +66,7 @@ impl<Block: BlockType> IntVector<Block> {
     }
 
     /// Checks `element_bits` before assembling an `IntVector`.
-    fn create(element_bits: usize, base: VectorBase<Block>) -> Self {
+    fn create(element_bits: usize, base: VectorBase<Block, Store>) -> Self {
         Self::check_element_bits(element_bits);
         IntVector {
             element_bits: element_bits,
@@ -61,6 +90,18 @@ impl<Block: BlockType> IntVector<Block> {
         .expect("IntVector: index overflow")
     }
 
+    /// Creates an integer vector with `element_bits`-bit elements
+    /// that adopts the blocks already in `store`.
+    ///
+    /// The length will be the number of elements that fit in those
+    /// blocks.
+    pub fn with_store(element_bits: usize, store: Store) -> Self {
+        Self::create(element_bits, VectorBase::with_store(element_bits, store))
+    }
+
+}
+
+impl<Block: BlockType> IntVector<Block> {
     /// Creates a new integer vector.
     ///
     /// # Arguments
@@ -75,6 +116,13 @@ impl<Block: BlockType> IntVector<Block> {
         Self::create(element_bits, VectorBase::new())
     }
 
+    /// Creates a new integer vector, or returns an error if
+    /// `element_bits` is zero or exceeds the block size.
+    pub fn try_new(element_bits: usize) -> Result<Self, Error> {
+        Self::try_check_element_bits(element_bits)?;
+        Ok(Self::create(element_bits, VectorBase::new()))
+    }
+
     /// Creates a new, empty integer vector, allocating sufficient storage
     /// for `capacity` elements.
     pub fn with_capacity(element_bits: usize, capacity: u64) -> Self {
@@ -82,6 +130,16 @@ impl<Block: BlockType> IntVector<Block> {
                      VectorBase::with_capacity(element_bits, capacity))
     }
 
+    /// Creates a new, empty integer vector with storage for `capacity`
+    /// elements, or returns an error if `element_bits` is invalid, the
+    /// size overflows, or the storage can’t be allocated.
+    pub fn try_with_capacity(element_bits: usize, capacity: u64)
+                             -> Result<Self, Error> {
+        Self::try_check_element_bits(element_bits)?;
+        let base = VectorBase::try_with_capacity(element_bits, capacity)?;
+        Ok(Self::create(element_bits, base))
+    }
+
     /// Creates a new, empty integer vector, allocating `block_capacity`
     /// blocks of storage.
     pub fn block_with_capacity(element_bits: usize, block_capacity: usize)
@@ -96,6 +154,20 @@ impl<Block: BlockType> IntVector<Block> {
                      VectorBase::with_fill(element_bits, len, value))
     }
 
+    /// Creates a new integer vector containing `len` copies of `value`,
+    /// or returns an error if `element_bits` is invalid, `value` doesn’t
+    /// fit, the size overflows, or the storage can’t be allocated.
+    pub fn try_with_fill(element_bits: usize, len: u64, value: Block)
+                         -> Result<Self, Error> {
+        Self::try_check_element_bits(element_bits)?;
+        if value > Block::low_mask(element_bits) {
+            return Err(Error::ValueTooLarge);
+        }
+
+        let base = VectorBase::try_with_fill(element_bits, len, value)?;
+        Ok(Self::create(element_bits, base))
+    }
+
     /// Creates a new integer vector containing `block_len` copies of the
     /// block `value`.
     ///
@@ -109,6 +181,75 @@ impl<Block: BlockType> IntVector<Block> {
                                                  value))
     }
 
+
+    /// Creates an integer vector holding `values`, packed a block at a
+    /// time rather than an element at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element_bits` is invalid or a value doesn’t fit.
+    pub fn pack_from(element_bits: usize, values: &[Block]) -> Self {
+        Self::try_pack_from(element_bits, values).expect("IntVector::pack_from")
+    }
+
+    /// Creates an integer vector holding `values`, or returns an error if
+    /// `element_bits` is invalid or a value doesn’t fit.
+    pub fn try_pack_from(element_bits: usize, values: &[Block])
+                         -> Result<Self, Error> {
+        Self::try_check_element_bits(element_bits)?;
+        let mask = Block::low_mask(element_bits);
+        let nbits = Block::nbits();
+        let total_bits = (values.len() as u64)
+            .checked_mul(element_bits as u64)
+            .ok_or(Error::Overflow)?;
+
+        // Each value is ORed into the block being filled; one that
+        // straddles the boundary leaves its high bits to start the next.
+        let mut blocks = Vec::with_capacity(total_bits.div_ceil(nbits as u64) as usize);
+        let mut current = Block::zero();
+        let mut filled = 0;
+        for &value in values {
+            if value > mask { return Err(Error::ValueTooLarge); }
+            current = current | value << filled;
+            filled += element_bits;
+            if filled >= nbits {
+                blocks.push(current);
+                filled -= nbits;
+                current = if filled == 0 {
+                    Block::zero()
+                } else {
+                    value >> (element_bits - filled)
+                };
+            }
+        }
+        if filled > 0 { blocks.push(current); }
+
+        let mut result = Self::with_store(element_bits, blocks);
+        result.truncate(values.len() as u64);
+        Ok(result)
+    }
+
+    /// The heap bytes an integer vector of `len` elements of
+    /// `element_bits` bits takes, without building it.
+    pub fn estimate_size(element_bits: usize, len: u64) -> usize {
+        Block::ceil_div_nbits(len.saturating_mul(element_bits as u64))
+            * ::std::mem::size_of::<Block>()
+    }
+
+    /// Moves the elements to a [`SharedStore`](../storage/struct.SharedStore.html),
+    /// after which the vector clones in constant time.
+    pub fn into_shared(self) -> IntVector<Block, SharedStore<Block>> {
+        IntVector {
+            element_bits: self.element_bits,
+            base: self.base.map_store(SharedStore::from),
+        }
+    }
+}
+
+impl<Block, Store> IntVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     /// Returns the element at a given index, also given an arbitrary
     /// element size and bit offset.
     ///
@@ -183,6 +324,9 @@ impl<Block: BlockType> IntVector<Block> {
     }
 
     /// Resizes to the given number of elements, filling if necessary.
+    ///
+    /// The new elements are written a block at a time, as by
+    /// [`fill`](trait.IntVecMut.html#method.fill).
     pub fn resize(&mut self, n_elements: u64, fill: Block) {
         self.base.resize(self.element_bits, n_elements, fill);
     }
@@ -277,8 +421,83 @@ impl<Block: BlockType> IntVector<Block> {
         self.base.clear();
     }
 
+    /// Copies the elements into `out`, a block at a time rather than an
+    /// element at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` isn’t the same length as the vector.
+    pub fn unpack_into(&self, out: &mut [Block]) {
+        assert_eq!(out.len() as u64, self.len(),
+                   "IntVector::unpack_into: length mismatch");
+
+        let element_bits = self.element_bits;
+        let mask = Block::low_mask(element_bits);
+        let nbits = Block::nbits();
+        let block_len = self.base.block_len();
+
+        let mut index = 0;
+        let mut offset = 0;
+        let mut current = if block_len > 0 {self.base.get_block(0)} else {Block::zero()};
+        for slot in out {
+            let mut value = current >> offset;
+            offset += element_bits;
+            if offset >= nbits {
+                index += 1;
+                offset -= nbits;
+                if index < block_len { current = self.base.get_block(index); }
+                if offset > 0 { value = value | current << (element_bits - offset); }
+            }
+            *slot = value & mask;
+        }
+    }
+
+    /// Reads the elements a `u64` of lanes at a time, for the scans,
+    /// which only do so for narrow elements. Each item is the index of
+    /// the first element in the word, the word, and the high bits of the
+    /// lanes holding elements.
+    fn lane_words(&self) -> impl Iterator<Item = (u64, u64, u64)> + '_ {
+        let width = self.element_bits;
+        let lanes = (64 / width) as u64;
+        let len = self.len();
+        let highs = broadword::lane_highs(width);
+
+        (0 .. len.div_ceil(lanes)).map(move |group| {
+            let first = group * lanes;
+            let bits = lanes.min(len - first) as usize * width;
+            let start = first * width as u64;
+
+            let mut word = 0;
+            let mut filled = 0;
+            while filled < bits {
+                let count = Block::nbits().min(bits - filled);
+                let piece = self.get_bits(start + filled as u64, count).to_u64()
+                    .expect("IntVector::lane_words: block too wide");
+                word |= piece << filled;
+                filled += count;
+            }
+            (first, word, highs & u64::low_mask(bits))
+        })
+    }
+
+    // The value to scan for, which must fit in an element, repeated in
+    // each lane, or `None` if the elements are too wide to be worth
+    // comparing in lanes.
+    fn lane_value(&self, value: Block) -> Option<u64> {
+        if self.element_bits > 32 { return None; }
+        Some(broadword::splat(value.to_u64()?, self.element_bits))
+    }
+
+    /// Compares the elements of two vectors lexicographically; see
+    /// [`IntSlice::cmp_packed`](struct.IntSlice.html#method.cmp_packed).
+    pub fn cmp_packed<Store2>(&self, other: &IntVector<Block, Store2>) -> Ordering
+        where Store2: BlockStore<Block = Block> {
+
+        IntSlice::new(self, ..).cmp_packed(&IntSlice::new(other, ..))
+    }
+
     /// Gets an iterator over the elements of the vector.
-    pub fn iter(&self) -> Iter<Block> {
+    pub fn iter(&self) -> Iter<'_, Block, Store> {
         Iter(vector_base::Iter::new(self.element_bits, &self.base))
     }
 
@@ -293,9 +512,76 @@ impl<Block: BlockType> IntVector<Block> {
     pub fn is_aligned(&self) -> bool {
         Block::nbits() % self.element_bits() == 0
     }
+
+    /// Compares the elements of two vectors.
+    ///
+    /// Vectors with different element sizes compare by value.
+    pub fn diff(&self, other: &Self) -> Diff {
+        Diff::of_elements(self, other)
+    }
+
+    /// Copies the elements to a vector with a different block type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the elements are wider than `NewBlock`.
+    pub fn convert<NewBlock: BlockType>(&self) -> IntVector<NewBlock> {
+        self.try_convert().expect("IntVector::convert")
+    }
+
+    /// Copies the elements to a vector with a different block type, or
+    /// returns `Error::ElementBits` if they are wider than `NewBlock`.
+    ///
+    /// The elements are laid out low bits first whatever the block size,
+    /// so this copies the bits a block at a time rather than an element
+    /// at a time.
+    ///
+    /// ```
+    /// use succinct::{IntVec, IntVecMut, IntVector};
+    ///
+    /// let mut narrow = IntVector::<u8>::new(5);
+    /// for i in 0 .. 20 { narrow.push(i); }
+    /// let wide = narrow.convert::<u64>();
+    /// assert_eq!(17, wide.get(17));
+    /// assert_eq!(narrow, wide.convert::<u8>());
+    /// assert!(IntVector::<u64>::new(40).try_convert::<u32>().is_err());
+    /// ```
+    pub fn try_convert<NewBlock: BlockType>(&self) -> Result<IntVector<NewBlock>, Error> {
+        let element_bits = self.element_bits;
+        if element_bits > NewBlock::nbits() { return Err(Error::ElementBits(element_bits)); }
+
+        let bit_len = self.bit_len();
+        let block_len = NewBlock::checked_ceil_div_nbits(bit_len).ok_or(Error::Overflow)?;
+        let mut blocks: Vec<NewBlock> = Vec::new();
+        blocks.try_reserve_exact(block_len).map_err(|_| Error::Allocation)?;
+
+        // Each new block is gathered in pieces no wider than either type.
+        let piece = Block::nbits().min(NewBlock::nbits());
+        let mut start = 0;
+        for _ in 0 .. block_len {
+            let mut block = NewBlock::zero();
+            let mut filled = 0;
+            while filled < NewBlock::nbits() && start < bit_len {
+                let count = (piece as u64).min(bit_len - start) as usize;
+                let bits = NewBlock::from(self.get_bits(start, count))
+                    .expect("IntVector::try_convert: piece too wide");
+                block = block | bits << filled;
+                filled += count;
+                start += count as u64;
+            }
+            blocks.push(block);
+        }
+
+        let base = VectorBase::from_blocks(element_bits, self.len(), blocks)
+            .expect("IntVector::try_convert: bad block count");
+        Ok(IntVector::create(element_bits, base))
+    }
 }
 
-impl<Block: BlockType> IntVec for IntVector<Block> {
+impl<Block, Store> IntVec for IntVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     type Block = Block;
 
     fn len(&self) -> u64 {
@@ -314,9 +600,52 @@ impl<Block: BlockType> IntVec for IntVector<Block> {
     fn element_bits(&self) -> usize {
         self.element_bits
     }
+
+    /// Compares a word of elements at once when they are 32 bits or
+    /// narrower.
+    fn count_eq(&self, value: Block) -> u64 {
+        if value > Block::low_mask(self.element_bits) { return 0; }
+        let width = self.element_bits;
+        match self.lane_value(value) {
+            Some(splat) => self.lane_words().map(|(_, word, highs)| {
+                (broadword::zero_lanes(word ^ splat, width) & highs).count_ones() as u64
+            }).sum(),
+            None => self.iter().filter(|&x| x == value).count() as u64,
+        }
+    }
+
+    /// Compares a word of elements at once when they are 32 bits or
+    /// narrower.
+    fn count_lt(&self, value: Block) -> u64 {
+        if value > Block::low_mask(self.element_bits) { return self.len(); }
+        let width = self.element_bits;
+        match self.lane_value(value) {
+            Some(splat) => self.lane_words().map(|(_, word, highs)| {
+                (broadword::u_lt_lanes(word, splat, width) & highs).count_ones() as u64
+            }).sum(),
+            None => self.iter().filter(|&x| x < value).count() as u64,
+        }
+    }
+
+    /// Compares a word of elements at once when they are 32 bits or
+    /// narrower.
+    fn find_first_ge(&self, value: Block) -> Option<u64> {
+        if value > Block::low_mask(self.element_bits) { return None; }
+        let width = self.element_bits;
+        match self.lane_value(value) {
+            Some(splat) => self.lane_words().filter_map(|(first, word, highs)| {
+                let ge = !broadword::u_lt_lanes(word, splat, width) & highs;
+                broadword::lsb(ge).map(|bit| first + (bit / width) as u64)
+            }).next(),
+            None => self.iter().position(|x| x >= value).map(|i| i as u64),
+        }
+    }
 }
 
-impl<Block: BlockType> IntVecMut for IntVector<Block> {
+impl<Block, Store> IntVecMut for IntVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn set(&mut self, element_index: u64, element_value: Block) {
         if self.is_block_sized() {
             self.base.set_block(self.element_bits,
@@ -331,9 +660,19 @@ impl<Block: BlockType> IntVecMut for IntVector<Block> {
         self.base.set_bits(self.element_bits, address,
                            self.element_bits, element_value);
     }
+
+    /// Writes whole blocks of the repeating pattern, setting only the
+    /// elements that straddle the ends singly.
+    fn fill(&mut self, value: Block) {
+        self.check_value(value);
+        self.base.fill(self.element_bits, value);
+    }
 }
 
-impl<Block: BlockType> BitVec for IntVector<Block> {
+impl<Block, Store> BitVec for IntVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     type Block = Block;
 
     fn block_len(&self) -> usize {
@@ -349,7 +688,10 @@ impl<Block: BlockType> BitVec for IntVector<Block> {
     }
 }
 
-impl<Block: BlockType> BitVecMut for IntVector<Block> {
+impl<Block, Store> BitVecMut for IntVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn set_block(&mut self, position: usize, value: Block) {
         self.base.set_block(self.element_bits, position, value);
     }
@@ -357,10 +699,13 @@ impl<Block: BlockType> BitVecMut for IntVector<Block> {
 
 /// An iterator over the elements of an [`IntVector`](struct.IntVector.html).
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Iter<'a, Block: BlockType + 'a = usize>
-    (vector_base::Iter<'a, Block>);
+pub struct Iter<'a, Block: BlockType + 'a = usize, Store: 'a = Vec<Block>>
+    (vector_base::Iter<'a, Block, Store>);
+
+impl<'a, Block, Store> Iterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
 
-impl<'a, Block: BlockType> Iterator for Iter<'a, Block> {
     type Item = Block;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -385,29 +730,164 @@ impl<'a, Block: BlockType> Iterator for Iter<'a, Block> {
 }
 
 #[cfg(target_pointer_width = "64")]
-impl<'a, Block: BlockType> ExactSizeIterator for Iter<'a, Block> {
+impl<'a, Block, Store> ExactSizeIterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn len(&self) -> usize {
         self.0.len()
     }
 }
 
-impl<'a, Block: BlockType> DoubleEndedIterator for Iter<'a, Block> {
+impl<'a, Block, Store> DoubleEndedIterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     fn next_back(&mut self) -> Option<Self::Item> {
         self.0.next_back()
     }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.0.nth_back(n)
+    }
 }
 
-impl<'a, Block: BlockType + 'a> IntoIterator for &'a IntVector<Block> {
+impl<'a, Block, Store> IntoIterator for &'a IntVector<Block, Store>
+    where Block: BlockType + 'a,
+          Store: BlockStore<Block = Block> + 'a {
+
     type Item = Block;
-    type IntoIter = Iter<'a, Block>;
+    type IntoIter = Iter<'a, Block, Store>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-impl<Block> fmt::Debug for IntVector<Block>
-        where Block: BlockType + fmt::Debug {
+/// An iterator that moves out of an [`IntVector`](struct.IntVector.html).
+///
+/// Unlike [`Iter`](struct.Iter.html), it owns the vector, so it can
+/// outlive the binding it came from.
+#[derive(Clone)]
+pub struct IntoIter<Block: BlockType = usize, Store = Vec<Block>> {
+    vector: IntVector<Block, Store>,
+    start: u64,
+    limit: u64,
+}
+
+impl<Block, Store> Iterator for IntoIter<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    type Item = Block;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start < self.limit {
+            self.start += 1;
+            Some(self.vector.get(self.start - 1))
+        } else { None }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.limit - self.start;
+        if len > usize::MAX as u64 {
+            (usize::MAX, None)
+        } else {
+            (len as usize, Some(len as usize))
+        }
+    }
+
+    fn count(self) -> usize {
+        self.size_hint().0
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.start = self.start.saturating_add(n as u64).min(self.limit);
+        self.next()
+    }
+}
+
+#[cfg(target_pointer_width = "64")]
+impl<Block, Store> ExactSizeIterator for IntoIter<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    fn len(&self) -> usize {
+        (self.limit - self.start) as usize
+    }
+}
+
+impl<Block, Store> fmt::Debug for IntoIter<Block, Store>
+        where Block: BlockType + fmt::Debug,
+              Store: BlockStore<Block = Block> {
+
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.debug_struct("IntoIter")
+                 .field("vector", &self.vector)
+                 .field("start", &self.start)
+                 .field("limit", &self.limit)
+                 .finish()
+    }
+}
+
+impl<Block, Store> DoubleEndedIterator for IntoIter<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    impl_index_range_back!(Self::Item, start .. limit, |iter, index| iter.vector.get(index));
+}
+
+impl<Block, Store> IntoIterator for IntVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    type Item = Block;
+    type IntoIter = IntoIter<Block, Store>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let limit = self.len();
+        IntoIter { vector: self, start: 0, limit }
+    }
+}
+
+/// Collects into a vector just wide enough for the largest value.
+impl<Block: BlockType> Default for IntVector<Block> {
+    /// An empty vector of block-sized elements.
+    fn default() -> Self {
+        IntVector::new(Block::nbits())
+    }
+}
+
+/// Packs the values at the narrowest width that holds them all, as
+/// `collect` does, or returns `Error::ValueTooLarge` if one doesn’t fit
+/// in a `Block`.
+impl<Block: BlockType> TryFrom<Vec<u64>> for IntVector<Block> {
+    type Error = Error;
+
+    fn try_from(values: Vec<u64>) -> Result<Self, Error> {
+        let blocks: Vec<Block> = values.into_iter()
+            .map(|value| Block::from(value).ok_or(Error::ValueTooLarge))
+            .collect::<Result<_, _>>()?;
+        Ok(blocks.into_iter().collect())
+    }
+}
+
+impl<Block: BlockType> FromIterator<Block> for IntVector<Block> {
+    fn from_iter<I: IntoIterator<Item = Block>>(iter: I) -> Self {
+        let values: Vec<Block> = iter.into_iter().collect();
+        let widest = values.iter().cloned().fold(Block::zero(), |a, b| a | b);
+        let element_bits = Block::nbits() - widest.leading_zeros() as usize;
+        Self::pack_from(element_bits.max(1), &values)
+    }
+}
+
+impl<Block, Store> fmt::Debug for IntVector<Block, Store>
+        where Block: BlockType + fmt::Debug,
+              Store: BlockStore<Block = Block> {
 
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         try!(write!(formatter, "IntVector {{ element_bits: {}, elements: {{ ",
@@ -421,7 +901,143 @@ impl<Block> fmt::Debug for IntVector<Block>
     }
 }
 
-impl<A: BlockType> SpaceUsage for IntVector<A> {
+/// Lists the elements in order.
+impl<Block, Store> Snapshot for IntVector<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    type Item = Block;
+    type Iter<'a> = Iter<'a, Block, Store> where Self: 'a;
+
+    fn dump_iter(&self) -> Iter<'_, Block, Store> {
+        self.iter()
+    }
+}
+
+impl<Block: BlockType> Dump for IntVector<Block> {
+    fn dump(&self) -> String {
+        format!("{}:{}:h{}", self.element_bits, self.len(),
+                encoding::encode_hex(self))
+    }
+
+    fn dump_base64(&self) -> String {
+        format!("{}:{}:b{}", self.element_bits, self.len(),
+                encoding::encode_base64(self))
+    }
+
+    fn restore(text: &str) -> io::Result<Self> {
+        let who = "IntVector::restore";
+        let mut fields = text.splitn(3, ':');
+
+        let element_bits: usize = encoding::parse_field(who, &mut fields)?;
+        let len: u64 = encoding::parse_field(who, &mut fields)?;
+        let payload = fields.next().unwrap_or("");
+
+        if element_bits == 0 || element_bits > Block::nbits() {
+            return encoding::bad_dump(who, "bad element size");
+        }
+
+        let block_len = match len.checked_mul(element_bits as u64)
+                                 .and_then(Block::checked_ceil_div_nbits) {
+            Some(block_len) => block_len,
+            None => return encoding::bad_dump(who, "length overflow"),
+        };
+
+        let blocks = encoding::decode_payload(who, payload, block_len)?;
+
+        match VectorBase::from_blocks(element_bits, len, blocks) {
+            Some(base) => Ok(Self::create(element_bits, base)),
+            None => encoding::bad_dump(who, "bits set past the end"),
+        }
+    }
+}
+
+/// Reads the elements in place in a buffer an `IntVector` was archived
+/// to with `rkyv` (with the `rkyv` feature).
+///
+/// # Example
+///
+/// ```
+/// # extern crate rkyv;
+/// # extern crate succinct;
+/// use succinct::{IntVec, IntVector};
+///
+/// # fn main() {
+/// let v: IntVector<u64> = (0 .. 1000).map(|i| i * 7 % 1000).collect();
+/// let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&v).unwrap();
+///
+/// let archived = rkyv::access::<rkyv::Archived<IntVector<u64>>, rkyv::rancor::Error>(&bytes)
+///     .unwrap();
+/// assert_eq!(10, archived.element_bits());
+/// assert_eq!(693, archived.get(99));
+/// # }
+/// ```
+#[cfg(feature = "rkyv")]
+impl<Block> IntVec for ArchivedIntVector<Block, Vec<Block>>
+    where Block: BlockType + ::rkyv::Archive,
+          ::rkyv::vec::ArchivedVec<::rkyv::Archived<Block>>: BitVec<Block = Block> {
+
+    type Block = Block;
+
+    fn len(&self) -> u64 {
+        self.base.len()
+    }
+
+    fn element_bits(&self) -> usize {
+        self.element_bits.to_native() as usize
+    }
+
+    fn get(&self, element_index: u64) -> Block {
+        assert!(element_index < self.len(), "ArchivedIntVector::get: out of bounds");
+        let element_bits = self.element_bits();
+        self.base.blocks().get_bits(element_index * element_bits as u64, element_bits)
+    }
+}
+
+/// The element size as a `u8` and the length as a `u64`, then the
+/// blocks.
+impl<Block: BlockType> Persist for IntVector<Block> {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u8(self.element_bits as u8)?;
+        sink.write_u64::<LittleEndian>(self.len())?;
+        encoding::write_blocks(self, sink)
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let who = "IntVector::read_from";
+        let element_bits = bytes.read_u8()? as usize;
+        let len = bytes.read_u64::<LittleEndian>()?;
+
+        if element_bits == 0 || element_bits > Block::nbits() {
+            return encoding::bad_dump(who, "bad element size");
+        }
+
+        let block_len = match len.checked_mul(element_bits as u64)
+                                 .and_then(Block::checked_ceil_div_nbits) {
+            Some(block_len) => block_len,
+            None => return encoding::bad_dump(who, "length overflow"),
+        };
+
+        let blocks = encoding::read_blocks(who, &mut bytes, block_len)?;
+        persist::expect_end(who, bytes)?;
+
+        match VectorBase::from_blocks(element_bits, len, blocks) {
+            Some(base) => Ok(Self::create(element_bits, base)),
+            None => encoding::bad_dump(who, "bits set past the end"),
+        }
+    }
+}
+
+impl<Block: BlockType> Component for IntVector<Block> {
+    fn tag() -> String {
+        format!("IntVector<u{}>", Block::nbits())
+    }
+}
+
+impl<A, Store> SpaceUsage for IntVector<A, Store>
+    where A: BlockType,
+          Store: BlockStore<Block = A> {
+
     #[inline]
     fn is_stack_only() -> bool { false }
 
@@ -564,6 +1180,21 @@ mod test {
         assert_eq!(vec![1, 1, 2, 3, 5], v.iter().collect::<Vec<_>>());
     }
 
+    #[test]
+    fn into_iter() {
+        let v: IntVector<u16> = vec![1, 1, 2, 3, 5, 8].into_iter().collect();
+
+        let mut iter = v.clone().into_iter();
+        assert_eq!(6, iter.len());
+        assert_eq!(Some(8), iter.next_back());
+        assert_eq!(Some(2), iter.nth(2));
+        assert_eq!(vec![3, 5], iter.collect::<Vec<_>>());
+
+        let mut sum = 0;
+        for x in v { sum += x; }
+        assert_eq!(20, sum);
+    }
+
     #[test]
     fn debug() {
         let mut v = IntVector::<u16>::new(13);
@@ -623,6 +1254,91 @@ mod test {
         assert!( v == u );
     }
 
+    #[test]
+    fn dump_restore() {
+        use dump::Dump;
+
+        let mut v = IntVector::<u32>::new(11);
+        for i in 0 .. 40 {
+            v.push(i * 37);
+        }
+
+        assert_eq!(v, IntVector::restore(&v.dump()).unwrap());
+        assert_eq!(v, IntVector::restore(&v.dump_base64()).unwrap());
+
+        let empty = IntVector::<u8>::new(3);
+        assert_eq!("3:0:h", empty.dump());
+        assert_eq!(empty, IntVector::restore("3:0:b").unwrap());
+
+        assert!(IntVector::<u8>::restore("3:2:h3f").is_ok());
+        assert!(IntVector::<u8>::restore("3:2:h7f").is_err());
+        assert!(IntVector::<u8>::restore("3:3:h3f").is_err());
+        assert!(IntVector::<u8>::restore("9:1:h3f").is_err());
+        assert!(IntVector::<u8>::restore("3:2").is_err());
+        assert!(IntVector::<u8>::restore("3:2:x3f").is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let mut v = IntVector::<u32>::with_fill(5, 10, 3);
+        let mut u = IntVector::<u32>::with_fill(6, 10, 3);
+        assert!(v.diff(&u).is_empty());
+
+        v.set(7, 9);
+        u.push(60);
+        assert_eq!(Some(7), v.diff(&u).first);
+        assert_eq!(2, v.diff(&u).count);
+    }
+
+    #[test]
+    fn try_constructors() {
+        use error::Error;
+
+        assert_eq!(Some(Error::ElementBits(0)),
+                   IntVector::<u32>::try_new(0).err());
+        assert_eq!(Some(Error::ElementBits(33)),
+                   IntVector::<u32>::try_with_capacity(33, 5).err());
+        assert_eq!(Some(Error::Overflow),
+                   IntVector::<u32>::try_with_capacity(5, !0).err());
+        assert_eq!(Some(Error::Allocation),
+                   IntVector::<u64>::try_with_fill(64, 1 << 56, 0).err());
+        assert_eq!(Some(Error::ValueTooLarge),
+                   IntVector::<u8>::try_with_fill(3, 5, 8).err());
+
+        let v = IntVector::<u8>::try_with_fill(3, 5, 7).unwrap();
+        assert_eq!(IntVector::with_fill(3, 5, 7), v);
+        assert!(IntVector::<u8>::try_with_capacity(3, 100).unwrap()
+                    .capacity() >= 100);
+    }
+
+    #[test]
+    fn paged() {
+        use storage::{BlockStore, PagedStore};
+
+        let mut v: IntVector<u16, PagedStore<u16>> =
+            IntVector::with_store(5, PagedStore::with_page_bytes(8));
+        let mut u = IntVector::<u16>::new(5);
+        for i in 0 .. 100 {
+            v.push(i % 32);
+            u.push(i % 32);
+        }
+
+        assert_eq!(u.iter().collect::<Vec<_>>(), v.iter().collect::<Vec<_>>());
+        v.set(50, 7);
+        assert_eq!(7, v.get(50));
+
+        v.truncate(30);
+        assert_eq!(30, v.len());
+        assert_eq!(Some(29), v.pop());
+        assert_eq!(10, v.block_len());
+
+        let mut v: IntVector<u64, PagedStore<u64>> =
+            IntVector::with_store(64, PagedStore::with_block_capacity(1000));
+        v.push(3);
+        v.reserve(100);
+        assert_eq!(Some(3), v.pop());
+    }
+
     #[test]
     fn block_size_elements_u16() {
         let mut v = IntVector::<u16>::new(16);
@@ -642,4 +1358,163 @@ mod test {
         assert_eq!(Some(0), v.pop());
         assert_eq!(None, v.pop());
     }
+
+    #[test]
+    fn convert_blocks() {
+        use quickcheck::quickcheck;
+
+        fn prop(values: Vec<u32>, bits: u8) -> bool {
+            let bits = bits as usize % 32 + 1;
+            let values: Vec<u32> = values.into_iter().map(|v| v & (!0 >> (32 - bits))).collect();
+            let vector = IntVector::<u32>::pack_from(bits, &values);
+
+            let wide: IntVector<u64> = vector.convert();
+            let bytes: Result<IntVector<u8>, _> = vector.try_convert();
+            wide.len() == vector.len()
+                && (0 .. vector.len()).all(|i| wide.get(i) == vector.get(i) as u64)
+                && wide.convert::<u32>() == vector
+                && vector.try_convert::<u16>().map_or(bits > 16, |v| v.convert::<u32>() == vector)
+                && bytes.map_or(bits > 8, |narrow| narrow.convert::<u32>() == vector)
+        }
+
+        quickcheck(prop as fn(Vec<u32>, u8) -> bool);
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        use quickcheck::quickcheck;
+
+        fn prop(values: Vec<u64>, bits: u8) -> bool {
+            let bits = bits as usize % 64 + 1;
+            let mask = !0 >> (64 - bits);
+            let values: Vec<u64> = values.into_iter().map(|v| v & mask).collect();
+
+            let packed = IntVector::<u64>::pack_from(bits, &values);
+            let mut pushed = IntVector::<u64>::new(bits);
+            for &value in &values {
+                pushed.push(value);
+            }
+            let mut out = vec![0; values.len()];
+            packed.unpack_into(&mut out);
+
+            let small_bits = bits.min(8);
+            let narrow: Vec<u8> = values.iter().map(|&v| v as u8 & (!0 >> (8 - small_bits))).collect();
+            let mut narrow_out = vec![0; narrow.len()];
+            IntVector::<u8>::pack_from(small_bits, &narrow).unpack_into(&mut narrow_out);
+
+            packed == pushed && out == values && narrow_out == narrow
+        }
+
+        quickcheck(prop as fn(Vec<u64>, u8) -> bool);
+    }
+
+    #[test]
+    fn pack_errors() {
+        assert!(IntVector::<u32>::try_pack_from(0, &[]).is_err());
+        assert!(IntVector::<u32>::try_pack_from(3, &[1, 8]).is_err());
+        assert_eq!(0, IntVector::<u32>::pack_from(3, &[]).len());
+    }
+
+    #[test]
+    fn from_iter() {
+        let v: IntVector<u32> = vec![3, 0, 9, 4].into_iter().collect();
+        assert_eq!(4, v.element_bits());
+        assert_eq!(vec![3, 0, 9, 4], v.iter().collect::<Vec<_>>());
+
+        let v: IntVector<u32> = None.into_iter().collect();
+        assert_eq!(1, v.element_bits());
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn std_conversions() {
+        use std::convert::TryFrom;
+
+        let v = IntVector::<u16>::try_from(vec![5, 300, 2]).unwrap();
+        assert_eq!(9, v.element_bits());
+        assert_eq!(300, v.get(1));
+        assert_eq!(Some(::Error::ValueTooLarge), IntVector::<u8>::try_from(vec![1, 256]).err());
+
+        let v = IntVector::<u32>::default();
+        assert_eq!((0, 32), (v.len(), v.element_bits()));
+    }
+
+    #[test]
+    fn qc_scans_match_elementwise() {
+        use quickcheck::quickcheck;
+        use storage::BlockType;
+
+        fn run<Block: BlockType>(values: &[u64], element_bits: usize, probe: u64) -> bool {
+            let element_bits = element_bits % Block::nbits() + 1;
+            let mask = u64::low_mask(element_bits);
+            let mut v = IntVector::<Block>::new(element_bits);
+            for &value in values {
+                v.push(Block::from(value & mask).unwrap());
+            }
+
+            // Probe present values, absent ones, and ones too wide to fit.
+            let probes = [probe & mask, values.first().map_or(0, |&x| x & mask),
+                          mask, mask.saturating_add(1), probe];
+            probes.iter().filter_map(|&p| Block::from(p)).all(|p| {
+                let elements: Vec<Block> = v.iter().collect();
+                v.count_eq(p) == elements.iter().filter(|&&x| x == p).count() as u64
+                    && v.count_lt(p) == elements.iter().filter(|&&x| x < p).count() as u64
+                    && v.find_first_ge(p) == elements.iter().position(|&x| x >= p).map(|i| i as u64)
+            })
+        }
+
+        fn prop(values: Vec<u64>, element_bits: u8, probe: u64) -> bool {
+            let element_bits = element_bits as usize;
+            run::<u8>(&values, element_bits, probe)
+                && run::<u32>(&values, element_bits, probe)
+                && run::<u64>(&values, element_bits, probe)
+        }
+
+        quickcheck(prop as fn(Vec<u64>, u8, u64) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+        use quickcheck::quickcheck;
+
+        fn prop(values: Vec<u16>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            let packed: IntVector<u64> = values.iter().cloned().collect();
+            skips_agree(packed.iter(), &values, n, step)
+                && skips_agree(packed.into_iter(), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn qc_archived() {
+        use quickcheck::quickcheck;
+        use rkyv::rancor::Error;
+        use storage::BlockType;
+
+        fn prop(values: Vec<u64>, element_bits: u8) -> bool {
+            let element_bits = element_bits as usize % 64 + 1;
+            let values: Vec<u64> = values.into_iter().map(|v| v & u64::low_mask(element_bits))
+                                         .collect();
+            let mut v = IntVector::<u64>::new(element_bits);
+            for &value in &values {
+                v.push(value);
+            }
+
+            let bytes = rkyv::to_bytes::<Error>(&v).unwrap();
+            let archived = rkyv::access::<rkyv::Archived<IntVector<u64>>, Error>(&bytes).unwrap();
+            let back: IntVector<u64> = rkyv::deserialize::<_, Error>(archived).unwrap();
+
+            archived.element_bits() == element_bits
+                && archived.len() == values.len() as u64
+                && (0 .. values.len()).all(|i| archived.get(i as u64) == values[i])
+                && back == v
+        }
+
+        quickcheck(prop as fn(Vec<u64>, u8) -> bool);
+    }
 }
@@ -1,7 +1,54 @@
 //! Vectors of *k*-bit unsigned integers.
 
+mod alias;
+pub use self::alias::*;
+
+mod byte_backed;
+pub use self::byte_backed::*;
+
+mod delta;
+pub use self::delta::*;
+
+mod elias_fano;
+pub use self::elias_fano::*;
+
+mod escaped;
+pub use self::escaped::*;
+
+mod exceptions;
+
+mod eytzinger;
+pub use self::eytzinger::*;
+
+mod fenwick;
+pub use self::fenwick::*;
+
+mod frames;
+pub use self::frames::*;
+
+mod int_slice;
+pub use self::int_slice::*;
+
 mod int_vector;
 pub use self::int_vector::*;
 
+mod packed_records;
+pub use self::packed_records::*;
+
+mod pfor;
+pub use self::pfor::*;
+
+mod quotient;
+pub use self::quotient::*;
+
+mod signed;
+pub use self::signed::*;
+
+mod sparse;
+pub use self::sparse::*;
+
+mod stream_vbyte;
+pub use self::stream_vbyte::*;
+
 mod traits;
 pub use self::traits::*;
@@ -0,0 +1,313 @@
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use error::Error;
+use space_usage::SpaceUsage;
+
+/// A growable array of records, each a fixed sequence of fixed-width
+/// unsigned fields.
+///
+/// The schema, the width of each field, is given at construction, and
+/// records are packed one after another with no padding, so a record
+/// of a 3-bit, a 1-bit and a 12-bit field takes 16 bits. Fields may be
+/// up to 64 bits wide, and records any width. Where the record layout
+/// is known at compile time, [`bit_record!`](../macro.bit_record.html)
+/// gives the fields names.
+///
+/// # Example
+///
+/// ```
+/// use succinct::int_vec::PackedRecords;
+///
+/// let mut records = PackedRecords::new(&[3, 1, 12]);
+/// records.push(&[5, 1, 4000]);
+/// records.push(&[2, 0, 17]);
+/// records.set(1, 2, 18);
+///
+/// assert_eq!(16, records.record_bits());
+/// assert_eq!(4000, records.get(0, 2));
+/// assert_eq!(vec![2, 0, 18], records.record(1));
+/// assert_eq!(vec![5, 2], records.column(0).collect::<Vec<_>>());
+/// ```
+#[derive(Clone, Debug)]
+pub struct PackedRecords {
+    widths: Vec<usize>,
+    // Where each field starts within its record.
+    offsets: Vec<u64>,
+    record_bits: u64,
+    len: u64,
+    bits: BitVector<u64>,
+}
+
+impl PackedRecords {
+    /// Creates an empty array of records with fields of the given
+    /// widths.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no fields, or a width is 0 or more than 64.
+    pub fn new(widths: &[usize]) -> Self {
+        Self::try_new(widths).expect("PackedRecords::new")
+    }
+
+    /// Creates an empty array of records with fields of the given
+    /// widths, or returns `Error::ElementBits` if there are no fields,
+    /// or a width is 0 or more than 64.
+    pub fn try_new(widths: &[usize]) -> Result<Self, Error> {
+        if widths.is_empty() { return Err(Error::ElementBits(0)); }
+        if let Some(&bad) = widths.iter().find(|&&width| width == 0 || width > 64) {
+            return Err(Error::ElementBits(bad));
+        }
+
+        let mut offsets = Vec::with_capacity(widths.len());
+        let mut record_bits = 0;
+        for &width in widths {
+            offsets.push(record_bits);
+            record_bits += width as u64;
+        }
+
+        Ok(PackedRecords {
+            widths: widths.to_vec(),
+            offsets,
+            record_bits,
+            len: 0,
+            bits: BitVector::new(),
+        })
+    }
+
+    /// Creates an array of `len` records with fields of the given widths,
+    /// all zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schema is invalid, as for `new`.
+    pub fn with_len(widths: &[usize], len: u64) -> Self {
+        let mut result = Self::new(widths);
+        result.resize(len);
+        result
+    }
+
+    /// The number of fields in a record.
+    pub fn field_count(&self) -> usize {
+        self.widths.len()
+    }
+
+    /// The width of `field`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no such field.
+    pub fn field_bits(&self, field: usize) -> usize {
+        self.widths[field]
+    }
+
+    /// The width of a record.
+    pub fn record_bits(&self) -> u64 {
+        self.record_bits
+    }
+
+    /// The number of records.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Are there no records?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Where `field` of record `index` starts.
+    fn position(&self, index: u64, field: usize) -> u64 {
+        assert!(index < self.len, "PackedRecords: index out of bounds");
+        index * self.record_bits + self.offsets[field]
+    }
+
+    /// Fetches `field` of record `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` or `field` is out of bounds.
+    pub fn get(&self, index: u64, field: usize) -> u64 {
+        self.bits.get_bits(self.position(index, field), self.widths[field])
+    }
+
+    /// Updates `field` of record `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` or `field` is out of bounds, or `value` doesn’t
+    /// fit in the field.
+    pub fn set(&mut self, index: u64, field: usize, value: u64) {
+        let width = self.widths[field];
+        assert!(width == 64 || value >> width == 0, "PackedRecords::set: value too wide");
+        let position = self.position(index, field);
+        self.bits.set_bits(position, width, value);
+    }
+
+    /// The fields of record `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn record(&self, index: u64) -> Vec<u64> {
+        (0 .. self.field_count()).map(|field| self.get(index, field)).collect()
+    }
+
+    /// Appends a record of the given fields.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of fields is wrong or one doesn’t fit, in
+    /// which case nothing is appended.
+    pub fn push(&mut self, fields: &[u64]) {
+        assert_eq!(self.field_count(), fields.len(), "PackedRecords::push: wrong field count");
+        assert!(fields.iter().zip(&self.widths)
+                      .all(|(&value, &width)| width == 64 || value >> width == 0),
+                "PackedRecords::push: value too wide");
+
+        self.resize(self.len + 1);
+        let index = self.len - 1;
+        for (field, &value) in fields.iter().enumerate() {
+            self.set(index, field, value);
+        }
+    }
+
+    /// Grows or shrinks the array to `len` records, adding all-zero
+    /// records.
+    pub fn resize(&mut self, len: u64) {
+        self.bits.resize(len * self.record_bits, false);
+        self.len = len;
+    }
+
+    /// Iterates over `field` of each record, in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no such field.
+    pub fn column(&self, field: usize) -> Column<'_> {
+        assert!(field < self.field_count(), "PackedRecords::column: no such field");
+        Column { records: self, field, index: 0, end: self.len }
+    }
+}
+
+impl SpaceUsage for PackedRecords {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.widths.heap_bytes() + self.offsets.heap_bytes() + self.bits.heap_bytes()
+    }
+}
+
+/// One field of every record of a
+/// [`PackedRecords`](struct.PackedRecords.html), in order.
+#[derive(Clone, Debug)]
+pub struct Column<'a> {
+    records: &'a PackedRecords,
+    field: usize,
+    index: u64,
+    end: u64,
+}
+
+impl<'a> Iterator for Column<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.index >= self.end { return None; }
+        self.index += 1;
+        Some(self.records.get(self.index - 1, self.field))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.index) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<u64> {
+        self.index = self.index.saturating_add(n as u64).min(self.end);
+        self.next()
+    }
+
+    impl_exact_size_count_last!(u64);
+}
+
+impl<'a> ExactSizeIterator for Column<'a> {}
+
+impl<'a> DoubleEndedIterator for Column<'a> {
+    impl_index_range_back!(u64, index .. end,
+                           |iter, index| iter.records.get(index, iter.field));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn bad_schemas() {
+        assert_eq!(Some(Error::ElementBits(0)), PackedRecords::try_new(&[]).err());
+        assert_eq!(Some(Error::ElementBits(65)), PackedRecords::try_new(&[3, 65]).err());
+        assert_eq!(Some(Error::ElementBits(0)), PackedRecords::try_new(&[0]).err());
+    }
+
+    #[test]
+    fn wide_fields() {
+        let mut records = PackedRecords::with_len(&[64, 1, 64], 3);
+        records.set(1, 0, !0);
+        records.set(1, 2, 1 << 63);
+        records.push(&[7, 1, 9]);
+        assert_eq!(vec![!0, 0, 1 << 63], records.record(1));
+        assert_eq!(vec![0, 0, 0, 1], records.column(1).collect::<Vec<_>>());
+        assert_eq!(vec![9, 0, 1 << 63, 0], records.column(2).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_wide() {
+        PackedRecords::new(&[4, 4]).push(&[1, 16]);
+    }
+
+    #[test]
+    fn qc_matches_vecs() {
+        fn prop(widths: Vec<u8>, values: Vec<u64>) -> bool {
+            let widths: Vec<usize> = widths.into_iter().take(5).map(|w| w as usize % 64 + 1).collect();
+            if widths.is_empty() { return true; }
+
+            let mut records = PackedRecords::new(&widths);
+            let mut expected = Vec::new();
+            for chunk in values.chunks(widths.len()) {
+                if chunk.len() < widths.len() { break; }
+                let record: Vec<u64> = chunk.iter().zip(&widths)
+                    .map(|(&value, &width)| if width == 64 {value} else {value & ((1 << width) - 1)})
+                    .collect();
+                records.push(&record);
+                expected.push(record);
+            }
+
+            records.len() == expected.len() as u64
+                && expected.iter().enumerate().all(|(i, record)| records.record(i as u64) == *record)
+                && (0 .. widths.len()).all(|field| {
+                    records.column(field).collect::<Vec<_>>()
+                        == expected.iter().map(|record| record[field]).collect::<Vec<_>>()
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u64>) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u16>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            let mut records = PackedRecords::new(&[16, 3]);
+            for &value in &values {
+                records.push(&[value, value % 8]);
+            }
+            let low: Vec<u64> = values.iter().map(|&v| v % 8).collect();
+            skips_agree(records.column(0), &values, n, step)
+                && skips_agree(records.column(1), &low, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+}
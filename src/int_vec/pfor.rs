@@ -0,0 +1,434 @@
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use dump::Snapshot;
+use int_vec::{EliasFano, IntVec, IntVector};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+
+/// The number of values in a full frame of a
+/// [`PforVector`](struct.PforVector.html).
+pub const PFOR_FRAME_LEN: usize = 128;
+
+// The bits of a frame’s header: its exception count, up to 128, and
+// their width.
+const COUNT_BITS: usize = 8;
+const WIDTH_BITS: usize = 7;
+// The bits of an exception’s position in its frame.
+const POSITION_BITS: usize = 7;
+
+/// An immutable column of `u64`s in patched frame-of-reference frames,
+/// as in FastPFor.
+///
+/// The values go in frames of 128. Each frame subtracts its minimum,
+/// the reference, and packs the rest at a width chosen to make the frame
+/// smallest; values too wide for it keep their low bits in place and
+/// store their positions and high bits after the frame as exceptions. A
+/// few outliers then cost a few patches rather than widening the whole
+/// frame.
+///
+/// The frames’ bit offsets are kept in an
+/// [`EliasFano`](struct.EliasFano.html), so any frame can be found, and
+/// [decoded](#method.decode_frame) whole, in constant time, and `get`
+/// reads one value and scans the frame’s exceptions.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::int_vec::PforVector;
+///
+/// // Timestamps a few ticks apart, with one glitch.
+/// let mut column: Vec<u64> = (0 .. 1000).map(|i| 1_600_000_000 + 3 * i).collect();
+/// column[500] = 0;
+/// let v = PforVector::new(column.iter().cloned());
+///
+/// assert_eq!(8, v.frame_count());
+/// assert_eq!(0, v.get(500));
+///
+/// let mut frame = Vec::new();
+/// v.decode_frame(1, &mut frame);
+/// assert_eq!(&column[128 .. 256], &frame[..]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct PforVector {
+    len: u64,
+    references: IntVector<u64>,
+    widths: IntVector<u8>,
+    // The bit offset of each frame in `frames`.
+    offsets: EliasFano,
+    frames: BitVector<u64>,
+}
+
+impl PforVector {
+    /// Encodes the values from an iterator.
+    pub fn new<I>(values: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        let mut len = 0;
+        let mut references = Vec::new();
+        let mut widths = IntVector::new(WIDTH_BITS);
+        let mut offsets = Vec::new();
+        let mut frames = BitVector::new();
+
+        let mut values = values.into_iter().peekable();
+        let mut frame = Vec::with_capacity(PFOR_FRAME_LEN);
+        while values.peek().is_some() {
+            frame.clear();
+            frame.extend(values.by_ref().take(PFOR_FRAME_LEN));
+            len += frame.len() as u64;
+
+            let reference = frame.iter().cloned().min().unwrap_or(0);
+            for value in &mut frame {
+                *value -= reference;
+            }
+            let width = best_width(&frame);
+
+            references.push(reference);
+            widths.push(width as u8);
+            offsets.push(frames.bit_len());
+            encode_frame(&mut frames, &frame, width);
+        }
+
+        let reference_bits = references.iter().cloned().max().map_or(1, bits_to_represent);
+        let mut packed = IntVector::with_capacity(reference_bits, references.len() as u64);
+        for reference in references {
+            packed.push(reference);
+        }
+        frames.shrink_to_fit();
+
+        PforVector {
+            len,
+            references: packed,
+            widths,
+            offsets: EliasFano::new(frames.bit_len() + 1, offsets),
+            frames,
+        }
+    }
+
+    /// Encodes a copy of `source`.
+    pub fn from_int_vec<V>(source: &V) -> Self
+        where V: IntVec<Block = u64> + ?Sized {
+
+        Self::new((0 .. source.len()).map(|i| source.get(i)))
+    }
+
+    /// The number of frames, all full but perhaps the last.
+    pub fn frame_count(&self) -> u64 {
+        self.references.len()
+    }
+
+    /// The number of exceptions in the `frame`th frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame` is out of bounds.
+    pub fn exception_count(&self, frame: u64) -> usize {
+        self.header(frame).2 as usize
+    }
+
+    /// Appends the values of the `frame`th frame to `out`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame` is out of bounds.
+    pub fn decode_frame(&self, frame: u64, out: &mut Vec<u64>) {
+        let (offset, width, count, high_width) = self.header(frame);
+        let reference = self.references.get(frame);
+        let start = out.len();
+        let frame_len = self.frame_len(frame);
+
+        out.extend((0 .. frame_len).map(|i| {
+            reference + self.read(offset + i * width as u64, width)
+        }));
+
+        let patches = self.patches(offset + frame_len * width as u64);
+        for i in 0 .. count {
+            let position = self.read(patches + i * POSITION_BITS as u64, POSITION_BITS);
+            let high = self.read(patches + count * POSITION_BITS as u64
+                                     + i * high_width as u64,
+                                 high_width);
+            out[start + position as usize] += high << width;
+        }
+    }
+
+    /// Iterates over the values in order.
+    pub fn iter(&self) -> PforIter<'_> {
+        PforIter {
+            data: self,
+            frame: None,
+            buffer: Vec::with_capacity(PFOR_FRAME_LEN),
+            index: 0,
+            end: self.len,
+        }
+    }
+
+    fn frame_len(&self, frame: u64) -> u64 {
+        (self.len - frame * PFOR_FRAME_LEN as u64).min(PFOR_FRAME_LEN as u64)
+    }
+
+    // The frame’s offset, width, exception count and exception width.
+    fn header(&self, frame: u64) -> (u64, usize, u64, usize) {
+        assert!(frame < self.frame_count(), "PforVector: frame out of bounds");
+        let offset = self.offsets.get(frame);
+        let width = self.widths.get(frame) as usize;
+        let header = offset + self.frame_len(frame) * width as u64;
+        let count = self.read(header, COUNT_BITS);
+        let high_width = self.read(header + COUNT_BITS as u64, WIDTH_BITS) as usize;
+        (offset, width, count, high_width)
+    }
+
+    // Where a frame’s exception positions start, given where its header is.
+    fn patches(&self, header: u64) -> u64 {
+        header + (COUNT_BITS + WIDTH_BITS) as u64
+    }
+
+    fn read(&self, position: u64, bits: usize) -> u64 {
+        if bits == 0 {0} else {self.frames.get_bits(position, bits)}
+    }
+}
+
+impl IntVec for PforVector {
+    type Block = u64;
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// The width of the widest frame, before patching.
+    fn element_bits(&self) -> usize {
+        Iterator::max(self.widths.iter()).unwrap_or(0) as usize
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        assert!(index < self.len, "PforVector::get: out of bounds");
+
+        let frame = index / PFOR_FRAME_LEN as u64;
+        let slot = index % PFOR_FRAME_LEN as u64;
+        let (offset, width, count, high_width) = self.header(frame);
+        let low = self.read(offset + slot * width as u64, width);
+
+        let patches = self.patches(offset + self.frame_len(frame) * width as u64);
+        // Only a frame narrower than 64 bits has exceptions.
+        let patched = (0 .. count)
+            .find(|&i| self.read(patches + i * POSITION_BITS as u64, POSITION_BITS) == slot)
+            .map_or(low, |i| {
+                low | self.read(patches + count * POSITION_BITS as u64 + i * high_width as u64,
+                                high_width) << width
+            });
+
+        self.references.get(frame) + patched
+    }
+}
+
+/// Lists the values in order.
+impl Snapshot for PforVector {
+    type Item = u64;
+    type Iter<'a> = PforIter<'a> where Self: 'a;
+
+    fn dump_iter(&self) -> PforIter<'_> {
+        self.iter()
+    }
+}
+
+impl SpaceUsage for PforVector {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.references.heap_bytes() + self.widths.heap_bytes()
+            + self.offsets.heap_bytes() + self.frames.heap_bytes()
+    }
+}
+
+/// An iterator over the values of a `PforVector`.
+///
+/// Going forward decodes a frame at a time, and `nth` skips straight to
+/// the frame wanted. Going backward costs a `get` per value.
+#[derive(Clone, Debug)]
+pub struct PforIter<'a> {
+    data: &'a PforVector,
+    // The values of the `frame`th frame, if one has been decoded.
+    frame: Option<u64>,
+    buffer: Vec<u64>,
+    index: u64,
+    // The elements from `end` on have been taken from the back.
+    end: u64,
+}
+
+impl<'a> Iterator for PforIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.index >= self.end { return None; }
+
+        let frame = self.index / PFOR_FRAME_LEN as u64;
+        if self.frame != Some(frame) {
+            self.buffer.clear();
+            self.data.decode_frame(frame, &mut self.buffer);
+            self.frame = Some(frame);
+        }
+        self.index += 1;
+
+        Some(self.buffer[((self.index - 1) % PFOR_FRAME_LEN as u64) as usize])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end.saturating_sub(self.index) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<u64> {
+        let target = self.index.saturating_add(n as u64);
+        if target >= self.end {
+            self.index = self.end;
+            return None;
+        }
+
+        self.index = target;
+        self.next()
+    }
+
+    impl_exact_size_count_last!(u64);
+}
+
+impl<'a> ExactSizeIterator for PforIter<'a> {}
+
+impl<'a> DoubleEndedIterator for PforIter<'a> {
+    impl_index_range_back!(u64, index .. end, |iter, index| iter.data.get(index));
+}
+
+impl<'a> IntoIterator for &'a PforVector {
+    type Item = u64;
+    type IntoIter = PforIter<'a>;
+
+    fn into_iter(self) -> PforIter<'a> {
+        self.iter()
+    }
+}
+
+// The significant bits of `value`, which are none for 0.
+fn significant_bits(value: u64) -> usize {
+    64 - value.leading_zeros() as usize
+}
+
+// The width that makes a frame of `deltas` smallest, exceptions included.
+fn best_width(deltas: &[u64]) -> usize {
+    let mut counts = [0u64; 65];
+    for &delta in deltas {
+        counts[significant_bits(delta)] += 1;
+    }
+    let widest = counts.iter().rposition(|&count| count > 0).unwrap_or(0);
+
+    let mut best = (u64::MAX, widest);
+    let mut exceptions = 0;
+    for width in (0 ..= widest).rev() {
+        let cost = deltas.len() as u64 * width as u64
+            + exceptions * (POSITION_BITS + widest - width) as u64;
+        if cost < best.0 {
+            best = (cost, width);
+        }
+        exceptions += counts[width];
+    }
+    best.1
+}
+
+// Appends the low bits, exception count and width, exception positions
+// and exception high bits of a frame.
+fn encode_frame(frames: &mut BitVector<u64>, deltas: &[u64], width: usize) {
+    let fits = |delta: u64| significant_bits(delta) <= width;
+    let high_width = deltas.iter().map(|&delta| significant_bits(delta))
+                           .max().unwrap_or(0).saturating_sub(width);
+
+    for &delta in deltas {
+        let low = if width == 64 {delta} else {delta & ((1 << width) - 1)};
+        push_bits(frames, low, width);
+    }
+
+    let exceptions: Vec<usize> = (0 .. deltas.len()).filter(|&i| !fits(deltas[i])).collect();
+    push_bits(frames, exceptions.len() as u64, COUNT_BITS);
+    push_bits(frames, high_width as u64, WIDTH_BITS);
+    for &i in &exceptions {
+        push_bits(frames, i as u64, POSITION_BITS);
+    }
+    for &i in &exceptions {
+        push_bits(frames, deltas[i] >> width, high_width);
+    }
+}
+
+fn push_bits(bits: &mut BitVector<u64>, value: u64, count: usize) {
+    if count == 0 { return; }
+    let start = bits.bit_len();
+    bits.resize(start + count as u64, false);
+    bits.set_bits(start, count, value);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let v = PforVector::new(vec![]);
+        assert_eq!(0, v.len());
+        assert_eq!(0, v.frame_count());
+        assert_eq!(None, v.iter().next());
+    }
+
+    #[test]
+    fn outliers_are_patched() {
+        let mut values: Vec<u64> = (0 .. 3000).map(|i| 1000 + i % 16).collect();
+        values[7] = 1 << 40;
+        values[200] = !0;
+        let v = PforVector::new(values.iter().cloned());
+
+        assert_eq!(4, v.element_bits());
+        assert_eq!(vec![1, 1, 0], (0 .. 3).map(|f| v.exception_count(f)).collect::<Vec<_>>());
+        assert_eq!(values, v.iter().collect::<Vec<_>>());
+        assert_eq!(!0, v.get(200));
+        // Under a byte per value, where the outliers would force eight.
+        assert!(v.total_bytes() < values.len());
+    }
+
+    #[test]
+    fn qc_round_trip() {
+        fn prop(values: Vec<u64>, shifts: Vec<u8>, skips: Vec<u8>) -> bool {
+            // Mostly narrow values, with some wide ones.
+            let values: Vec<u64> = values.iter().enumerate().map(|(i, &value)| {
+                value >> shifts.get(i).map_or(60, |&shift| shift % 64)
+            }).collect();
+            let values: Vec<u64> = values.iter().cycle().take(values.len() * 3).cloned().collect();
+            let v = PforVector::new(values.iter().cloned());
+
+            let mut decoded = vec![];
+            for frame in 0 .. v.frame_count() {
+                v.decode_frame(frame, &mut decoded);
+            }
+
+            let mut iter = v.iter();
+            let mut expected = values.iter().cloned();
+            decoded == values
+                && (0 .. v.len()).all(|i| v.get(i) == values[i as usize])
+                && skips.into_iter().all(|skip| {
+                    let skip = skip as usize % 200;
+                    iter.nth(skip) == expected.nth(skip)
+                        && iter.next_back() == expected.next_back()
+                        && iter.len() == expected.len()
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u64>, Vec<u8>, Vec<u8>) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u16>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let values: Vec<u64> = values.into_iter().map(|v| v as u64).collect();
+            let v = PforVector::new(values.iter().cloned());
+            skips_agree(v.iter(), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8, u8) -> bool);
+    }
+}
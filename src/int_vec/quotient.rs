@@ -0,0 +1,228 @@
+use bit_vec::{BitVec, BitVecMut, BitVecPush, BitVector};
+use error::Error;
+use int_vec::{IntVec, IntVector};
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+
+/// An immutable set of integers, split by quotienting into buckets for
+/// fast membership tests.
+///
+/// Each key’s high `bucket_bits()` bits pick a bucket, and its other
+/// `remainder_bits()` bits are stored, sorted within each bucket, in an
+/// [`IntVector`](struct.IntVector.html). There are about as many buckets
+/// as keys. One bitmap marks the buckets that hold anything, so most
+/// misses take one bit read; a second marks the last remainder of each
+/// bucket, so a hit finds its bucket’s remainders with a rank and two
+/// selects, and then searches just those. That takes about
+/// 3 + log(universe / len) bits per key.
+///
+/// Unlike [`EliasFano`](struct.EliasFano.html), this has no access by
+/// index; it trades that for the quick misses.
+///
+/// # Example
+///
+/// ```
+/// use succinct::int_vec::QuotientSet;
+///
+/// let set = QuotientSet::new(vec![1_000_000, 17, 3, 17, 40_000]);
+/// assert_eq!(4, set.len());
+/// assert!(set.contains(40_000));
+/// assert!(!set.contains(40_001));
+/// assert_eq!(vec![3, 17, 40_000, 1_000_000], set.to_sorted_vec());
+/// ```
+#[derive(Clone, Debug)]
+pub struct QuotientSet {
+    key_bits: usize,
+    remainder_bits: usize,
+    // Bit b is set if bucket b holds a key.
+    occupied: Rank9<BitVector<u64>>,
+    // Marks the last remainder of each occupied bucket.
+    ends: Select9<BitVector<u64>>,
+    remainders: IntVector<u64>,
+}
+
+impl QuotientSet {
+    /// Creates a set of the given keys, in any order, with repeats
+    /// ignored.
+    pub fn new<I>(keys: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        let mut keys: Vec<u64> = keys.into_iter().collect();
+        keys.sort();
+        keys.dedup();
+        Self::from_sorted_keys(keys)
+    }
+
+    /// Creates a set of keys given in strictly increasing order, without
+    /// sorting them, or returns `Error::NotIncreasing` if they are out
+    /// of order.
+    pub fn from_sorted_iter<I>(keys: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = u64> {
+
+        let mut sorted = Vec::new();
+        for key in keys {
+            if sorted.last().is_some_and(|&last| last >= key) {
+                return Err(Error::NotIncreasing);
+            }
+            sorted.push(key);
+        }
+        Ok(Self::from_sorted_keys(sorted))
+    }
+
+    fn from_sorted_keys(keys: Vec<u64>) -> Self {
+        let key_bits = bits_to_represent(keys.last().cloned().unwrap_or(0));
+        let bucket_bits = bits_to_represent(keys.len() as u64).min(key_bits);
+        let remainder_bits = key_bits - bucket_bits;
+        let mask = if remainder_bits == 0 {0} else {!0 >> (64 - remainder_bits)};
+
+        let mut occupied = BitVector::with_fill(1 << bucket_bits, false);
+        let mut ends = BitVector::with_capacity(keys.len() as u64);
+        let mut remainders = IntVector::with_capacity(remainder_bits.max(1),
+                                                      keys.len() as u64);
+
+        for (i, &key) in keys.iter().enumerate() {
+            let bucket = key >> remainder_bits;
+            occupied.set_bit(bucket, true);
+            ends.push_bit(keys.get(i + 1)
+                              .map_or(true, |&next| next >> remainder_bits != bucket));
+            remainders.push(key & mask);
+        }
+
+        QuotientSet {
+            key_bits,
+            remainder_bits,
+            occupied: Rank9::new(occupied),
+            ends: Select9::new(Rank9::new(ends)),
+            remainders,
+        }
+    }
+
+    /// The number of keys.
+    pub fn len(&self) -> u64 {
+        self.remainders.len()
+    }
+
+    /// Are there no keys?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of bits that pick a key’s bucket.
+    pub fn bucket_bits(&self) -> usize {
+        self.key_bits - self.remainder_bits
+    }
+
+    /// The number of bits stored per key.
+    pub fn remainder_bits(&self) -> usize {
+        self.remainder_bits
+    }
+
+    // The remainders of the `index`th occupied bucket.
+    fn bucket_range(&self, index: u64) -> (u64, u64) {
+        let start = match index {
+            0 => 0,
+            _ => self.ends.select1(index - 1).expect("QuotientSet: bad ends") + 1,
+        };
+        let end = self.ends.select1(index).expect("QuotientSet: bad ends") + 1;
+        (start, end)
+    }
+
+    fn remainder_of(&self, key: u64) -> u64 {
+        if self.remainder_bits == 0 {0} else {key & (!0 >> (64 - self.remainder_bits))}
+    }
+
+    /// Is `key` in the set?
+    pub fn contains(&self, key: u64) -> bool {
+        if self.is_empty() || (self.key_bits < 64 && key >> self.key_bits != 0) {
+            return false;
+        }
+
+        let bucket = key >> self.remainder_bits;
+        if !self.occupied.get_bit(bucket) { return false; }
+
+        let (mut start, mut end) = self.bucket_range(self.occupied.rank1(bucket) - 1);
+        let wanted = self.remainder_of(key);
+        while start < end {
+            let mid = start + (end - start) / 2;
+            let found = self.remainders.get(mid);
+            if found == wanted { return true; }
+            if found < wanted { start = mid + 1; } else { end = mid; }
+        }
+        false
+    }
+
+    /// The keys in increasing order.
+    pub fn to_sorted_vec(&self) -> Vec<u64> {
+        let mut result = Vec::with_capacity(self.len() as usize);
+        let mut index = 0;
+        for bucket in 0 .. self.occupied.bit_len() {
+            if !self.occupied.get_bit(bucket) { continue; }
+
+            let (start, end) = self.bucket_range(index);
+            for i in start .. end {
+                result.push(bucket << self.remainder_bits | self.remainders.get(i));
+            }
+            index += 1;
+        }
+        result
+    }
+}
+
+impl SpaceUsage for QuotientSet {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.occupied.heap_bytes() + self.ends.heap_bytes() + self.remainders.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let set = QuotientSet::new(vec![]);
+        assert!(set.is_empty());
+        assert!(!set.contains(0));
+        assert!(!set.contains(!0));
+    }
+
+    #[test]
+    fn sorted_input() {
+        let set = QuotientSet::from_sorted_iter(vec![2, 30, 400]).unwrap();
+        assert!(set.contains(30));
+        assert_eq!(Some(Error::NotIncreasing),
+                   QuotientSet::from_sorted_iter(vec![2, 2]).err());
+    }
+
+    #[test]
+    fn wide_keys() {
+        let keys = [0, 1, !0, 1 << 63, 12_345_678_901];
+        let set = QuotientSet::new(keys.iter().cloned());
+        assert!(keys.iter().all(|&key| set.contains(key)));
+        assert!(!set.contains(2));
+        assert!(!set.contains(!0 - 1));
+        assert_eq!(vec![0, 1, 12_345_678_901, 1 << 63, !0], set.to_sorted_vec());
+    }
+
+    #[test]
+    fn qc_matches_btree() {
+        fn prop(keys: Vec<u32>, probes: Vec<u32>) -> bool {
+            let set: BTreeSet<u64> = keys.iter().map(|&x| x as u64 % 5000).collect();
+            let quotient = QuotientSet::new(keys.into_iter().map(|x| x as u64 % 5000));
+
+            quotient.to_sorted_vec() == set.iter().cloned().collect::<Vec<_>>()
+                && probes.into_iter().all(|probe| {
+                    let probe = probe as u64 % 6000;
+                    quotient.contains(probe) == set.contains(&probe)
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u32>, Vec<u32>) -> bool);
+    }
+}
@@ -0,0 +1,156 @@
+use num_traits::{NumCast, ToPrimitive};
+
+use int_vec::{IntVec, IntVecMut};
+use util::{zigzag_decode, zigzag_encode};
+
+/// A borrowed view of an unsigned integer vector as signed integers,
+/// stored zig-zag encoded.
+///
+/// Zig-zag encoding keeps small values of either sign small, so deltas
+/// fit in a narrow `IntVector`. See
+/// [`zigzag_encode`](../util/fn.zigzag_encode.html).
+///
+/// ```
+/// use succinct::IntVector;
+/// use succinct::int_vec::{SignedView, SignedViewMut};
+///
+/// let mut v = IntVector::<u8>::with_fill(4, 3, 0);
+/// SignedViewMut::new(&mut v).set(1, -5);
+///
+/// let signed = SignedView::new(&v);
+/// assert_eq!(-5, signed.get(1));
+/// assert_eq!(0, signed.get(2));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SignedView<'a, Base: 'a + IntVec + ?Sized> {
+    data: &'a Base,
+}
+
+/// A borrowed, mutable view of an unsigned integer vector as signed
+/// integers, stored zig-zag encoded.
+#[derive(Debug)]
+pub struct SignedViewMut<'a, Base: 'a + IntVecMut + ?Sized> {
+    data: &'a mut Base,
+}
+
+fn decode<Block: ToPrimitive>(value: Block) -> i64 {
+    zigzag_decode(value.to_u64().expect("SignedView: block too wide"))
+}
+
+impl<'a, Base: 'a + IntVec + ?Sized> SignedView<'a, Base> {
+    /// Views `base` as signed.
+    pub fn new(base: &'a Base) -> Self {
+        SignedView { data: base }
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> u64 {
+        self.data.len()
+    }
+
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Fetches the value of the `index`th element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn get(&self, index: u64) -> i64 {
+        decode(self.data.get(index))
+    }
+}
+
+impl<'a, Base: 'a + IntVecMut + ?Sized> SignedViewMut<'a, Base> {
+    /// Views `base` as signed.
+    pub fn new(base: &'a mut Base) -> Self {
+        SignedViewMut { data: base }
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> u64 {
+        self.data.len()
+    }
+
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Fetches the value of the `index`th element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn get(&self, index: u64) -> i64 {
+        decode(self.data.get(index))
+    }
+
+    /// Updates the value of the `index`th element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the encoded `value` doesn’t
+    /// fit in the element size.
+    pub fn set(&mut self, index: u64, value: i64) {
+        let encoded = zigzag_encode(value);
+        assert!(encoded >> (self.data.element_bits() - 1) >> 1 == 0,
+                "SignedViewMut::set: value too large for element size");
+        let block = <Base::Block as NumCast>::from(encoded)
+            .expect("SignedViewMut::set: value too large for block");
+        self.data.set(index, block);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use int_vec::IntVector;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn round_trip() {
+        let mut v = IntVector::<u32>::with_fill(5, 10, 0);
+        {
+            let mut signed = SignedViewMut::new(&mut v);
+            for i in 0 .. 10 {
+                signed.set(i, i as i64 - 5);
+            }
+            assert_eq!(-5, signed.get(0));
+        }
+
+        assert_eq!(9, v.get(0));
+        let signed = SignedView::new(&v);
+        assert_eq!(10, signed.len());
+        for i in 0 .. 10 {
+            assert_eq!(i as i64 - 5, signed.get(i));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_large() {
+        let mut v = IntVector::<u32>::with_fill(3, 1, 0);
+        SignedViewMut::new(&mut v).set(0, -5);
+    }
+
+    #[test]
+    fn qc_full_width() {
+        fn prop(values: Vec<i64>) -> bool {
+            let mut v = IntVector::<u64>::with_fill(64, values.len() as u64, 0);
+            {
+                let mut signed = SignedViewMut::new(&mut v);
+                for (i, &x) in values.iter().enumerate() {
+                    signed.set(i as u64, x);
+                }
+            }
+
+            let signed = SignedView::new(&v);
+            values.iter().enumerate().all(|(i, &x)| signed.get(i as u64) == x)
+        }
+
+        quickcheck(prop as fn(Vec<i64>) -> bool);
+    }
+}
@@ -0,0 +1,254 @@
+use bit_vec::{BitVec, BitVecPush, BitVector};
+use dump::Snapshot;
+use int_vec::{IntVec, IntVector};
+use int_vec::exceptions::{ExceptionIter, Exceptions};
+use rank::{BitRankSupport, Rank9};
+use space_usage::SpaceUsage;
+use storage::BlockType;
+
+/// An immutable integer vector for when most elements equal a default.
+///
+/// A bitmap marks the elements that differ from the default, and those
+/// are stored densely in order. A `Rank9` over the bitmap finds an
+/// element’s place among them, so `get` is constant time. The space is
+/// about 1.25 bits per element plus `element_bits` per exception.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{IntVec, IntVecMut, IntVector};
+/// use succinct::int_vec::SparseIntVector;
+///
+/// let mut dense = IntVector::<u32>::with_fill(8, 1000, 0);
+/// dense.set(17, 200);
+/// dense.set(600, 3);
+///
+/// let sparse = SparseIntVector::from_int_vec(&dense, 0);
+/// assert_eq!(2, sparse.exception_count());
+/// assert_eq!(200, sparse.get(17));
+/// assert_eq!(0, sparse.get(18));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SparseIntVector<Block: BlockType = usize> {
+    default: Block,
+    present: Rank9<BitVector<u64>>,
+    exceptions: IntVector<Block>,
+}
+
+impl<Block: BlockType> SparseIntVector<Block> {
+    /// Creates a sparse vector with `element_bits`-bit elements from an
+    /// iterator, storing only the elements that differ from `default`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element_bits` is invalid or any element, including
+    /// `default`, doesn’t fit.
+    pub fn new<I>(element_bits: usize, default: Block, elements: I) -> Self
+        where I: IntoIterator<Item = Block> {
+
+        let mut present = BitVector::new();
+        let mut exceptions = IntVector::new(element_bits);
+
+        assert!(default <= Block::low_mask(element_bits),
+                "SparseIntVector::new: default too large");
+
+        for element in elements {
+            present.push_bit(element != default);
+            if element != default {
+                exceptions.push(element);
+            }
+        }
+
+        present.shrink_to_fit();
+        exceptions.shrink_to_fit();
+
+        SparseIntVector {
+            default,
+            present: Rank9::new(present),
+            exceptions,
+        }
+    }
+
+    /// Creates a sparse copy of `source`, storing only the elements that
+    /// differ from `default`.
+    pub fn from_int_vec<V>(source: &V, default: Block) -> Self
+        where V: IntVec<Block = Block> + ?Sized {
+
+        Self::new(source.element_bits(), default,
+                  (0 .. source.len()).map(|i| source.get(i)))
+    }
+
+    /// The value of the elements that aren’t stored.
+    pub fn default_value(&self) -> Block {
+        self.default
+    }
+
+    /// The number of elements that differ from the default.
+    pub fn exception_count(&self) -> u64 {
+        self.exceptions.len()
+    }
+
+    /// Is the `index`th element different from the default?
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn is_exception(&self, index: u64) -> bool {
+        self.present.get_bit(index)
+    }
+
+    /// Iterates over the elements in order.
+    pub fn iter(&self) -> SparseIter<'_, Block> {
+        SparseIter(ExceptionIter::new(self))
+    }
+}
+
+impl<Block: BlockType> IntVec for SparseIntVector<Block> {
+    type Block = Block;
+
+    fn len(&self) -> u64 {
+        self.present.bit_len()
+    }
+
+    fn element_bits(&self) -> usize {
+        self.exceptions.element_bits()
+    }
+
+    fn get(&self, index: u64) -> Block {
+        if self.present.get_bit(index) {
+            self.exceptions.get(self.present.rank1(index) - 1)
+        } else {
+            self.default
+        }
+    }
+}
+
+/// Lists the elements in order, defaults included.
+impl<Block: BlockType> Snapshot for SparseIntVector<Block> {
+    type Item = Block;
+    type Iter<'a> = SparseIter<'a, Block> where Self: 'a;
+
+    fn dump_iter(&self) -> SparseIter<'_, Block> {
+        self.iter()
+    }
+}
+
+impl<Block: BlockType> SpaceUsage for SparseIntVector<Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.present.heap_bytes() + self.exceptions.heap_bytes()
+    }
+}
+
+impl<Block: BlockType> Exceptions for SparseIntVector<Block> {
+    type Block = Block;
+
+    fn element_count(&self) -> u64 {
+        self.len()
+    }
+
+    fn exception_count(&self) -> u64 {
+        self.exceptions.len()
+    }
+
+    fn inline(&self, index: u64) -> Option<Block> {
+        if self.present.get_bit(index) {None} else {Some(self.default)}
+    }
+
+    fn exception(&self, place: u64) -> Block {
+        self.exceptions.get(place)
+    }
+
+    fn exceptions_through(&self, index: u64) -> u64 {
+        self.present.rank1(index)
+    }
+}
+
+/// An iterator over the elements of a
+/// [`SparseIntVector`](struct.SparseIntVector.html).
+///
+/// It counts the exceptions it passes instead of ranking each one, and
+/// ranks only to skip with `nth`.
+#[derive(Clone, Debug)]
+pub struct SparseIter<'a, Block: BlockType + 'a = usize>(
+    ExceptionIter<'a, SparseIntVector<Block>>);
+
+impl_iterator_newtype!(['a, Block: BlockType] SparseIter<'a, Block> => Block);
+
+impl<'a, Block: BlockType> IntoIterator for &'a SparseIntVector<Block> {
+    type Item = Block;
+    type IntoIter = SparseIter<'a, Block>;
+
+    fn into_iter(self) -> SparseIter<'a, Block> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use int_vec::IntVecMut;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let v = SparseIntVector::<u8>::new(3, 5, vec![]);
+        assert_eq!(0, v.len());
+        assert_eq!(0, v.exception_count());
+    }
+
+    #[test]
+    fn mostly_default() {
+        let mut dense = IntVector::<u16>::with_fill(11, 5000, 7);
+        for i in 0 .. 50 {
+            dense.set(i * 97, i as u16);
+        }
+
+        let sparse = SparseIntVector::from_int_vec(&dense, 7);
+        assert_eq!(dense.len(), sparse.len());
+        assert_eq!(11, sparse.element_bits());
+        assert_eq!(49, sparse.exception_count());
+        assert!(sparse.is_exception(97));
+        assert!(!sparse.is_exception(98));
+
+        for i in 0 .. dense.len() {
+            assert_eq!(dense.get(i), sparse.get(i));
+        }
+
+        assert!(sparse.total_bytes() < dense.total_bytes() / 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn default_too_large() {
+        SparseIntVector::<u8>::new(3, 8, vec![]);
+    }
+
+    #[test]
+    fn qc_matches_dense() {
+        fn prop(elements: Vec<u8>) -> bool {
+            let elements: Vec<u8> = elements.into_iter().map(|x| x % 4).collect();
+            let sparse = SparseIntVector::new(2, 0, elements.iter().cloned());
+            sparse.len() == elements.len() as u64
+                && elements.iter().enumerate()
+                           .all(|(i, &x)| sparse.get(i as u64) == x)
+        }
+
+        quickcheck(prop as fn(Vec<u8>) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u8>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let values: Vec<u16> = values.into_iter().map(|v| v as u16 % 4 * 100).collect();
+            let v = SparseIntVector::new(9, 0u16, values.iter().cloned());
+            skips_agree(v.iter(), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u8>, u8, u8) -> bool);
+    }
+}
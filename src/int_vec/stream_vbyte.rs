@@ -0,0 +1,484 @@
+use std::convert::TryFrom;
+
+use dump::Snapshot;
+use int_vec::{IntVec, IntVector};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+
+/// An immutable sequence of `u32`s in Lemire’s Stream VByte format, for
+/// decoding as fast as possible in order.
+///
+/// Values go in groups of four. Each group has a control byte giving the
+/// byte length, 1 to 4, of each of its values, and the values’ bytes are
+/// kept apart from the controls, so a decoder reads one control, looks
+/// up a shuffle and moves all four values into place at once. On
+/// `x86_64` with SSSE3 and on little-endian `aarch64` with NEON, detected
+/// once when the sequence is built, that’s a single byte shuffle;
+/// elsewhere the same table drives a plain loop.
+///
+/// Random access starts from a sampled byte offset, kept for every
+/// `sample`th group, and skips the groups until the one wanted by
+/// adding up their lengths from the controls. For sequences that are
+/// only ever read from the front, [`decode_into`](#method.decode_into)
+/// and the iterator are the point; for sorted values, an
+/// [`EliasFano`](struct.EliasFano.html) is smaller, and gets faster.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::int_vec::StreamVByte;
+///
+/// let values = vec![7, 300, 70_000, 1 << 30, 0, 12];
+/// let v = StreamVByte::new(8, values.iter().cloned());
+///
+/// assert_eq!(70_000, v.get(2));
+/// assert_eq!(values, v.iter().collect::<Vec<_>>());
+///
+/// let mut decoded = Vec::new();
+/// v.decode_into(&mut decoded);
+/// assert_eq!(values, decoded);
+/// ```
+#[derive(Clone, Debug)]
+pub struct StreamVByte {
+    len: u64,
+    sample: u64,
+    controls: Vec<u8>,
+    // Followed by `PADDING` zeros, so that every group can be loaded whole.
+    data: Vec<u8>,
+    // The offset in `data` of every `sample`th group.
+    offsets: IntVector<u64>,
+    kernel: Kernel,
+}
+
+// The most bytes one group’s values can take.
+const PADDING: usize = 16;
+
+impl StreamVByte {
+    /// Encodes the values from an iterator, sampling the offset of every
+    /// `sample`th group of four.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample` is 0.
+    pub fn new<I>(sample: usize, values: I) -> Self
+        where I: IntoIterator<Item = u32> {
+
+        assert!(sample > 0, "StreamVByte::new: sample must be positive");
+        let sample = sample as u64;
+
+        let mut len = 0u64;
+        let mut controls = Vec::new();
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        let mut values = values.into_iter().peekable();
+
+        while values.peek().is_some() {
+            if controls.len() as u64 % sample == 0 {
+                offsets.push(data.len() as u64);
+            }
+
+            let mut control = 0;
+            for lane in 0 .. 4 {
+                // The last group is filled out with zeros.
+                let value = values.next().map_or(0, |value| { len += 1; value });
+                let bytes = bits_to_represent(value).div_ceil(8);
+                data.extend_from_slice(&value.to_le_bytes()[.. bytes]);
+                control |= ((bytes - 1) as u8) << (2 * lane);
+            }
+            controls.push(control);
+        }
+        data.resize(data.len() + PADDING, 0);
+
+        let offset_bits = bits_to_represent(data.len() as u64);
+        let mut packed = IntVector::with_capacity(offset_bits, offsets.len() as u64);
+        for offset in offsets {
+            packed.push(offset);
+        }
+
+        StreamVByte {
+            len,
+            sample,
+            controls,
+            data,
+            offsets: packed,
+            kernel: Kernel::detect(),
+        }
+    }
+
+    /// Encodes a copy of `source`.
+    pub fn from_int_vec<V>(sample: usize, source: &V) -> Self
+        where V: IntVec<Block = u32> + ?Sized {
+
+        Self::new(sample, (0 .. source.len()).map(|i| source.get(i)))
+    }
+
+    /// The number of groups between sampled offsets.
+    pub fn sample(&self) -> usize {
+        self.sample as usize
+    }
+
+    /// Appends every value to `out`, decoding a group at a time.
+    pub fn decode_into(&self, out: &mut Vec<u32>) {
+        let start = out.len();
+        out.reserve(self.controls.len() * 4);
+
+        let mut offset = 0;
+        for &control in &self.controls {
+            out.extend_from_slice(&self.kernel.decode(control, self.group_bytes(offset)));
+            offset += group_len(control);
+        }
+        out.truncate(start + self.len as usize);
+    }
+
+    /// Iterates over the values in order.
+    pub fn iter(&self) -> StreamVByteIter<'_> {
+        StreamVByteIter {
+            data: self,
+            group: 0,
+            offset: 0,
+            buffer: [0; 4],
+            index: 0,
+            end: self.len,
+        }
+    }
+
+    fn group_bytes(&self, offset: usize) -> &[u8; PADDING] {
+        <&[u8; PADDING]>::try_from(&self.data[offset .. offset + PADDING])
+            .expect("StreamVByte: short padding")
+    }
+
+    // The offset in `data` of the `group`th group.
+    fn group_offset(&self, group: usize) -> usize {
+        let sampled = group as u64 / self.sample;
+        let first = (sampled * self.sample) as usize;
+        let skipped: usize = self.controls[first .. group].iter()
+                                 .map(|&control| group_len(control))
+                                 .sum();
+        self.offsets.get(sampled) as usize + skipped
+    }
+}
+
+impl IntVec for StreamVByte {
+    type Block = u32;
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Always 32, the width of the widest value that fits.
+    fn element_bits(&self) -> usize {
+        32
+    }
+
+    fn get(&self, index: u64) -> u32 {
+        assert!(index < self.len, "StreamVByte::get: out of bounds");
+
+        let group = (index / 4) as usize;
+        let lane = (index % 4) as usize;
+        let control = self.controls[group];
+        let start = self.group_offset(group)
+            + (0 .. lane).map(|lane| lane_len(control, lane)).sum::<usize>();
+
+        let mut bytes = [0; 4];
+        let len = lane_len(control, lane);
+        bytes[.. len].copy_from_slice(&self.data[start .. start + len]);
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Lists the values in order.
+impl Snapshot for StreamVByte {
+    type Item = u32;
+    type Iter<'a> = StreamVByteIter<'a> where Self: 'a;
+
+    fn dump_iter(&self) -> StreamVByteIter<'_> {
+        self.iter()
+    }
+}
+
+impl SpaceUsage for StreamVByte {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.controls.heap_bytes() + self.data.heap_bytes() + self.offsets.heap_bytes()
+    }
+}
+
+/// An iterator over the values of a `StreamVByte`.
+///
+/// Going forward decodes a group at a time into a buffer, and `nth`
+/// skips to the group wanted. Going backward costs a `get` per value.
+#[derive(Clone, Debug)]
+pub struct StreamVByteIter<'a> {
+    data: &'a StreamVByte,
+    // The next group to decode and where its bytes start.
+    group: usize,
+    offset: usize,
+    // The values of the group before `group`.
+    buffer: [u32; 4],
+    index: u64,
+    // The elements from `end` on have been taken from the back.
+    end: u64,
+}
+
+impl<'a> Iterator for StreamVByteIter<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.index >= self.end { return None; }
+
+        if self.index % 4 == 0 {
+            let control = self.data.controls[self.group];
+            self.buffer = self.data.kernel.decode(control, self.data.group_bytes(self.offset));
+            self.offset += group_len(control);
+            self.group += 1;
+        }
+        self.index += 1;
+
+        Some(self.buffer[((self.index - 1) % 4) as usize])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end.saturating_sub(self.index) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<u32> {
+        let target = self.index.saturating_add(n as u64);
+        if target >= self.end {
+            self.index = self.end;
+            return None;
+        }
+
+        // Land at the start of the target’s group, unless already in it.
+        let group = (target / 4) as usize;
+        if group >= self.group {
+            self.group = group;
+            self.offset = self.data.group_offset(group);
+            self.index = group as u64 * 4;
+        }
+        while self.index < target {
+            self.next();
+        }
+        self.next()
+    }
+
+    impl_exact_size_count_last!(u32);
+}
+
+impl<'a> ExactSizeIterator for StreamVByteIter<'a> {}
+
+impl<'a> DoubleEndedIterator for StreamVByteIter<'a> {
+    impl_index_range_back!(u32, index .. end, |iter, index| iter.data.get(index));
+}
+
+impl<'a> IntoIterator for &'a StreamVByte {
+    type Item = u32;
+    type IntoIter = StreamVByteIter<'a>;
+
+    fn into_iter(self) -> StreamVByteIter<'a> {
+        self.iter()
+    }
+}
+
+fn lane_len(control: u8, lane: usize) -> usize {
+    ((control >> (2 * lane)) & 3) as usize + 1
+}
+
+fn group_len(control: u8) -> usize {
+    GROUP_LENS[control as usize] as usize
+}
+
+const fn group_len_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut control = 0;
+    while control < 256 {
+        let mut lane = 0;
+        while lane < 4 {
+            table[control] += ((control >> (2 * lane)) & 3) as u8 + 1;
+            lane += 1;
+        }
+        control += 1;
+    }
+    table
+}
+
+// For each control byte, where each output byte of a group comes from
+// in its input, or 0x80 for a zero.
+const fn shuffle_table() -> [[u8; 16]; 256] {
+    let mut table = [[0x80; 16]; 256];
+    let mut control = 0;
+    while control < 256 {
+        let mut source = 0;
+        let mut lane = 0;
+        while lane < 4 {
+            let len = ((control >> (2 * lane)) & 3) + 1;
+            let mut byte = 0;
+            while byte < len {
+                table[control][4 * lane + byte] = source;
+                source += 1;
+                byte += 1;
+            }
+            lane += 1;
+        }
+        control += 1;
+    }
+    table
+}
+
+static GROUP_LENS: [u8; 256] = group_len_table();
+static SHUFFLES: [[u8; 16]; 256] = shuffle_table();
+
+// How to decode a group, chosen once from the features the CPU has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kernel {
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Ssse3,
+    #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+    Neon,
+}
+
+impl Kernel {
+    fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("ssse3") { return Kernel::Ssse3; }
+        }
+
+        #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") { return Kernel::Neon; }
+        }
+
+        Kernel::Scalar
+    }
+
+    #[inline]
+    fn decode(self, control: u8, bytes: &[u8; PADDING]) -> [u32; 4] {
+        match self {
+            Kernel::Scalar => decode_group_scalar(control, bytes),
+            // Safe because `detect` found the feature.
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Ssse3 => unsafe { decode_group_ssse3(control, bytes) },
+            #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+            Kernel::Neon => unsafe { decode_group_neon(control, bytes) },
+        }
+    }
+}
+
+fn decode_group_scalar(control: u8, bytes: &[u8; PADDING]) -> [u32; 4] {
+    let shuffle = &SHUFFLES[control as usize];
+    let mut result = [0; 4];
+    for (lane, value) in result.iter_mut().enumerate() {
+        let mut word = [0; 4];
+        for (out, &source) in word.iter_mut().zip(&shuffle[4 * lane .. 4 * lane + 4]) {
+            if source < 0x80 { *out = bytes[source as usize]; }
+        }
+        *value = u32::from_le_bytes(word);
+    }
+    result
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn decode_group_ssse3(control: u8, bytes: &[u8; PADDING]) -> [u32; 4] {
+    use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_shuffle_epi8, _mm_storeu_si128};
+
+    let input = _mm_loadu_si128(bytes.as_ptr() as *const __m128i);
+    let shuffle = _mm_loadu_si128(SHUFFLES[control as usize].as_ptr() as *const __m128i);
+    let mut result = [0u32; 4];
+    _mm_storeu_si128(result.as_mut_ptr() as *mut __m128i, _mm_shuffle_epi8(input, shuffle));
+    result
+}
+
+// `vqtbl1q_u8` gives 0 for an index past the 16th byte, as `0x80` is.
+#[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+#[target_feature(enable = "neon")]
+unsafe fn decode_group_neon(control: u8, bytes: &[u8; PADDING]) -> [u32; 4] {
+    use std::arch::aarch64::{vld1q_u8, vqtbl1q_u8, vst1q_u8};
+
+    let input = vld1q_u8(bytes.as_ptr());
+    let shuffle = vld1q_u8(SHUFFLES[control as usize].as_ptr());
+    let mut result = [0u32; 4];
+    vst1q_u8(result.as_mut_ptr() as *mut u8, vqtbl1q_u8(input, shuffle));
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let v = StreamVByte::new(4, vec![]);
+        assert_eq!(0, v.len());
+        assert_eq!(None, v.iter().next());
+        let mut decoded = vec![];
+        v.decode_into(&mut decoded);
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    fn byte_lengths() {
+        let v = StreamVByte::new(1, vec![0, 0xFF, 0x100, 0xFFFF_FFFF, 0x1_0000]);
+        assert_eq!(vec![0b11_01_00_00, 0b00_00_00_10], v.controls);
+        assert_eq!(1 + 1 + 2 + 4 + 3 + 3 + PADDING, v.data.len());
+        assert_eq!(0xFFFF_FFFF, v.get(3));
+        assert_eq!(0x1_0000, v.get(4));
+    }
+
+    #[test]
+    fn qc_scalar_matches_dispatch() {
+        fn prop(control: u8, bytes: Vec<u8>) -> bool {
+            let mut group = [0; PADDING];
+            for (slot, byte) in group.iter_mut().zip(bytes) { *slot = byte; }
+            Kernel::detect().decode(control, &group) == decode_group_scalar(control, &group)
+        }
+
+        quickcheck(prop as fn(u8, Vec<u8>) -> bool);
+    }
+
+    #[test]
+    fn qc_round_trip() {
+        fn prop(values: Vec<u32>, sample: u8, skips: Vec<u8>) -> bool {
+            // Spread the widths out over all four byte lengths.
+            let values: Vec<u32> = values.iter().enumerate()
+                .map(|(i, &value)| value >> (i * 7 % 32))
+                .collect();
+            let v = StreamVByte::new(sample as usize % 5 + 1, values.iter().cloned());
+
+            let mut decoded = vec![];
+            v.decode_into(&mut decoded);
+
+            let mut iter = v.iter();
+            let mut expected = values.iter().cloned();
+            decoded == values
+                && v.iter().collect::<Vec<_>>() == values
+                && (0 .. v.len()).all(|i| v.get(i) == values[i as usize])
+                && skips.into_iter().all(|skip| {
+                    let skip = skip as usize % 9;
+                    iter.nth(skip) == expected.nth(skip)
+                        && iter.next_back() == expected.next_back()
+                        && iter.len() == expected.len()
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u32>, u8, Vec<u8>) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u32>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let v = StreamVByte::new(3, values.iter().cloned());
+            skips_agree(v.iter(), &values, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u32>, u8, u8) -> bool);
+    }
+}
@@ -1,4 +1,7 @@
+use std::cmp::Ordering;
+
 use storage::BlockType;
+use super::{Chunks, Windows};
 
 /// An immutable array of integers of limited width.
 pub trait IntVec {
@@ -23,6 +26,67 @@ pub trait IntVec {
     ///
     /// Panics if `index` is out of bounds.
     fn get(&self, index: u64) -> Self::Block;
+
+    /// Iterates over [slices](struct.IntSlice.html) of `chunk_len`
+    /// elements, with the last shorter if the length isn’t a multiple.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_len` is 0.
+    fn chunks(&self, chunk_len: u64) -> Chunks<'_, Self>
+        where Self: Sized {
+
+        Chunks::new(self, chunk_len)
+    }
+
+    /// Iterates over the overlapping [slices](struct.IntSlice.html) of
+    /// `len` elements, none if the vector is shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is 0.
+    fn windows(&self, len: u64) -> Windows<'_, Self>
+        where Self: Sized {
+
+        Windows::new(self, len)
+    }
+
+    /// Compares the elements of `self` and `other` lexicographically,
+    /// as slices of `Block` would be.
+    ///
+    /// This reads one element at a time; for two
+    /// [`IntVector`](struct.IntVector.html)s of the same width,
+    /// [`cmp_packed`](struct.IntSlice.html#method.cmp_packed) compares a
+    /// block’s worth at once.
+    fn cmp_elements<Other>(&self, other: &Other) -> Ordering
+        where Self: Sized,
+              Other: IntVec<Block = Self::Block> + ?Sized {
+
+        let common = self.len().min(other.len());
+        (0 .. common).map(|i| self.get(i).cmp(&other.get(i)))
+                     .find(|&ordering| ordering != Ordering::Equal)
+                     .unwrap_or_else(|| self.len().cmp(&other.len()))
+    }
+
+    /// The number of elements equal to `value`.
+    ///
+    /// The default reads one element at a time;
+    /// [`IntVector`](struct.IntVector.html) compares a word of narrow
+    /// elements at once.
+    fn count_eq(&self, value: Self::Block) -> u64 {
+        (0 .. self.len()).filter(|&i| self.get(i) == value).count() as u64
+    }
+
+    /// The number of elements less than `value`.
+    fn count_lt(&self, value: Self::Block) -> u64 {
+        (0 .. self.len()).filter(|&i| self.get(i) < value).count() as u64
+    }
+
+    /// The index of the first element at least `value`, or `None` if
+    /// every element is less.
+    fn find_first_ge(&self, value: Self::Block) -> Option<u64> {
+        (0 .. self.len()).find(|&i| self.get(i) >= value)
+    }
 }
 
 /// A mutable array of integers of limited width.
@@ -36,4 +100,14 @@ pub trait IntVecMut: IntVec {
     ///   - May panic (?) if `element_value` is too large to
     ///     fit in the element size. (TODO: What’s the right thing here?)
     fn set(&mut self, index: u64, value: Self::Block);
+
+    /// Sets every element to `value`.
+    ///
+    /// The default sets one element at a time; packed vectors write
+    /// whole blocks.
+    fn fill(&mut self, value: Self::Block) {
+        for i in 0 .. self.len() {
+            self.set(i, value);
+        }
+    }
 }
@@ -0,0 +1,254 @@
+//! Textual encodings of block sequences, used by `Dump`.
+//!
+//! Hex renders each block as `Block::nbits() / 4` digits, most
+//! significant digit first, so that a block reads the way it would be
+//! written as a literal. Base64 (standard alphabet, no padding) encodes
+//! the blocks as little-endian bytes, matching the usual on-disk layout.
+
+use std::io::{self, Error, ErrorKind, Result};
+
+use byteorder::LittleEndian;
+use num_traits::NumCast;
+
+use bit_vec::BitVec;
+use internal::unaligned::{self, block_bytes};
+use storage::BlockType;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn bad_dump<A>(who: &str, what: &str) -> Result<A> {
+    Err(Error::new(ErrorKind::InvalidData,
+                   format!("{}: could not restore: {}", who, what)))
+}
+
+fn block_to_u64<Block: BlockType>(block: Block) -> u64 {
+    block.to_u64().expect("encoding: block wider than 64 bits")
+}
+
+/// Encodes the blocks of `bits` in hex.
+pub fn encode_hex<V: BitVec + ?Sized>(bits: &V) -> String {
+    let digits = V::Block::nbits() / 4;
+    let mut result = String::with_capacity(bits.block_len() * digits);
+
+    for i in 0 .. bits.block_len() {
+        let block = block_to_u64(bits.get_block(i));
+        for j in (0 .. digits).rev() {
+            let digit = (block >> (4 * j)) & 0xF;
+            result.push(HEX_DIGITS[digit as usize] as char);
+        }
+    }
+
+    result
+}
+
+/// Encodes the blocks of `bits` in base64.
+pub fn encode_base64<V: BitVec + ?Sized>(bits: &V) -> String {
+    let mut bytes = Vec::with_capacity(bits.block_len()
+                                           * block_bytes::<V::Block>());
+    for i in 0 .. bits.block_len() {
+        let block = block_to_u64(bits.get_block(i));
+        for j in 0 .. block_bytes::<V::Block>() {
+            bytes.push((block >> (8 * j)) as u8);
+        }
+    }
+
+    let mut result = String::with_capacity((bytes.len() * 4).ceil_div(3));
+
+    for chunk in bytes.chunks(3) {
+        let mut group = 0u32;
+        for (j, &byte) in chunk.iter().enumerate() {
+            group |= (byte as u32) << (16 - 8 * j);
+        }
+
+        for j in 0 .. chunk.len() + 1 {
+            let digit = (group >> (18 - 6 * j)) & 0x3F;
+            result.push(BASE64_DIGITS[digit as usize] as char);
+        }
+    }
+
+    result
+}
+
+fn hex_value(c: u8) -> Option<u64> {
+    (c as char).to_digit(16).map(|d| d as u64)
+}
+
+fn base64_value(c: u8) -> Option<u32> {
+    BASE64_DIGITS.iter().position(|&d| d == c).map(|d| d as u32)
+}
+
+/// Decodes `block_len` blocks from hex.
+pub fn decode_hex<Block: BlockType>(who: &str, text: &str, block_len: usize)
+                                    -> Result<Vec<Block>> {
+    let digits = Block::nbits() / 4;
+    let text = text.as_bytes();
+
+    if Some(text.len()) != block_len.checked_mul(digits) {
+        return bad_dump(who, "wrong number of hex digits");
+    }
+
+    let mut result = Vec::with_capacity(block_len);
+
+    for chunk in text.chunks(digits) {
+        let mut block = 0u64;
+        for &c in chunk {
+            match hex_value(c) {
+                Some(digit) => block = block << 4 | digit,
+                None => return bad_dump(who, "invalid hex digit"),
+            }
+        }
+        result.push(<Block as NumCast>::from(block)
+                        .expect("decode_hex: block overflow"));
+    }
+
+    Ok(result)
+}
+
+/// Decodes `block_len` blocks from base64.
+pub fn decode_base64<Block: BlockType>(who: &str, text: &str,
+                                       block_len: usize)
+                                       -> Result<Vec<Block>> {
+    let nbytes = match block_len.checked_mul(block_bytes::<Block>()) {
+        Some(nbytes) => nbytes,
+        None => return bad_dump(who, "too many blocks"),
+    };
+
+    let text = text.as_bytes();
+    if text.len() != (nbytes * 4).ceil_div(3) {
+        return bad_dump(who, "wrong number of base64 digits");
+    }
+
+    let mut bytes = Vec::with_capacity(nbytes);
+
+    for chunk in text.chunks(4) {
+        let mut group = 0u32;
+        for (j, &c) in chunk.iter().enumerate() {
+            match base64_value(c) {
+                Some(digit) => group |= digit << (18 - 6 * j),
+                None => return bad_dump(who, "invalid base64 digit"),
+            }
+        }
+
+        for j in 0 .. chunk.len() - 1 {
+            bytes.push((group >> (16 - 8 * j)) as u8);
+        }
+
+        // Any bits left over past the last byte must be zero, or the
+        // dump wasn’t produced by `encode_base64`.
+        if group & ((1 << (24 - 8 * (chunk.len() - 1))) - 1) != 0 {
+            return bad_dump(who, "non-canonical base64");
+        }
+    }
+
+    let result = bytes.chunks(block_bytes::<Block>()).map(|chunk| {
+        let mut block = 0u64;
+        for (j, &byte) in chunk.iter().enumerate() {
+            block |= (byte as u64) << (8 * j);
+        }
+        <Block as NumCast>::from(block).expect("decode_base64: block overflow")
+    }).collect();
+
+    Ok(result)
+}
+
+/// Decodes `block_len` blocks from a dump payload, which is `h`
+/// followed by hex or `b` followed by base64.
+pub fn decode_payload<Block: BlockType>(who: &str, text: &str,
+                                        block_len: usize)
+                                        -> Result<Vec<Block>> {
+    if let Some(hex) = text.strip_prefix('h') {
+        decode_hex(who, hex, block_len)
+    } else if let Some(base64) = text.strip_prefix('b') {
+        decode_base64(who, base64, block_len)
+    } else {
+        bad_dump(who, "unknown payload format")
+    }
+}
+
+/// Parses one `:`-terminated header field of a dump.
+pub fn parse_field<'a, T, I>(who: &str, fields: &mut I) -> Result<T>
+    where T: ::std::str::FromStr,
+          I: Iterator<Item = &'a str> {
+
+    match fields.next().map(str::parse) {
+        Some(Ok(value)) => Ok(value),
+        _ => bad_dump(who, "malformed header"),
+    }
+}
+
+/// Writes the blocks of `bits` in little-endian binary, for `Persist`.
+pub fn write_blocks<V, W>(bits: &V, sink: &mut W) -> Result<()>
+    where V: BitVec + ?Sized,
+          W: io::Write {
+
+    for i in 0 .. bits.block_len() {
+        bits.get_block(i).write_block::<W, LittleEndian>(sink)?;
+    }
+    Ok(())
+}
+
+/// Reads `block_len` little-endian blocks from the front of `bytes`,
+/// for `Persist`.
+pub fn read_blocks<Block: BlockType>(who: &str, bytes: &mut &[u8],
+                                     block_len: usize)
+                                     -> Result<Vec<Block>> {
+    if block_len.checked_mul(block_bytes::<Block>())
+                .map_or(true, |nbytes| nbytes > bytes.len()) {
+        return Err(Error::new(ErrorKind::InvalidData,
+                              format!("{}: could not load: too few bytes", who)));
+    }
+
+    let (blocks, rest) = bytes.split_at(block_len * block_bytes::<Block>());
+    *bytes = rest;
+    Ok((0 .. block_len).map(|i| unaligned::read_block(blocks, i)).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn hex_u16() {
+        let blocks = vec![0x0123u16, 0xBEEF];
+        assert_eq!("0123beef", encode_hex(&blocks));
+        assert_eq!(blocks, decode_hex::<u16>("test", "0123beef", 2).unwrap());
+        assert!(decode_hex::<u16>("test", "0123beef", 3).is_err());
+        assert!(decode_hex::<u16>("test", "0123beeg", 2).is_err());
+    }
+
+    #[test]
+    fn base64_u8() {
+        let blocks = b"Man".to_vec();
+        assert_eq!("TWFu", encode_base64(&blocks));
+
+        let blocks = b"Ma".to_vec();
+        assert_eq!("TWE", encode_base64(&blocks));
+        assert_eq!(blocks, decode_base64::<u8>("test", "TWE", 2).unwrap());
+        assert!(decode_base64::<u8>("test", "TWF", 2).is_err());
+    }
+
+    #[test]
+    fn qc_hex_u32() {
+        fn prop(blocks: Vec<u32>) -> bool {
+            let text = encode_hex(&blocks);
+            decode_hex::<u32>("test", &text, blocks.len()).unwrap() == blocks
+        }
+
+        quickcheck(prop as fn(Vec<u32>) -> bool);
+    }
+
+    #[test]
+    fn qc_base64_u64() {
+        fn prop(blocks: Vec<u64>) -> bool {
+            let text = encode_base64(&blocks);
+            decode_base64::<u64>("test", &text, blocks.len()).unwrap()
+                == blocks
+        }
+
+        quickcheck(prop as fn(Vec<u64>) -> bool);
+    }
+}
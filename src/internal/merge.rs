@@ -0,0 +1,112 @@
+//! Set operations over sorted iterators, for merging static structures
+//! without decoding either side in full.
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+
+/// The distinct items of two sorted iterators, in order.
+pub struct Union<A: Iterator, B: Iterator<Item = A::Item>> {
+    a: Peekable<A>,
+    b: Peekable<B>,
+    last: Option<A::Item>,
+}
+
+impl<A, B> Union<A, B>
+    where A: Iterator,
+          B: Iterator<Item = A::Item> {
+
+    pub fn new(a: A, b: B) -> Self {
+        Union { a: a.peekable(), b: b.peekable(), last: None }
+    }
+}
+
+impl<A, B> Iterator for Union<A, B>
+    where A: Iterator,
+          B: Iterator<Item = A::Item>,
+          A::Item: Ord + Clone {
+
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<A::Item> {
+        loop {
+            let order = match (self.a.peek(), self.b.peek()) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => return None,
+            };
+
+            let item = match order {
+                Ordering::Less => self.a.next(),
+                Ordering::Greater => self.b.next(),
+                Ordering::Equal => {
+                    self.b.next();
+                    self.a.next()
+                }
+            };
+
+            if item != self.last {
+                self.last = item.clone();
+                return item;
+            }
+        }
+    }
+}
+
+/// The items of two strictly increasing iterators that both hold, in
+/// order.
+pub struct Intersection<A: Iterator, B: Iterator<Item = A::Item>> {
+    a: Peekable<A>,
+    b: Peekable<B>,
+}
+
+impl<A, B> Intersection<A, B>
+    where A: Iterator,
+          B: Iterator<Item = A::Item> {
+
+    pub fn new(a: A, b: B) -> Self {
+        Intersection { a: a.peekable(), b: b.peekable() }
+    }
+}
+
+impl<A, B> Iterator for Intersection<A, B>
+    where A: Iterator,
+          B: Iterator<Item = A::Item>,
+          A::Item: Ord {
+
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<A::Item> {
+        loop {
+            let order = match (self.a.peek(), self.b.peek()) {
+                (Some(a), Some(b)) => a.cmp(b),
+                _ => return None,
+            };
+
+            match order {
+                Ordering::Less => { self.a.next(); }
+                Ordering::Greater => { self.b.next(); }
+                Ordering::Equal => {
+                    self.b.next();
+                    return self.a.next();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn small() {
+        let a = [1, 1, 3, 5, 8];
+        let b = [2, 3, 8, 9];
+        assert_eq!(vec![1, 2, 3, 5, 8, 9],
+                   Union::new(a.iter(), b.iter()).cloned().collect::<Vec<_>>());
+        assert_eq!(vec![3, 8],
+                   Intersection::new(a[1..].iter(), b.iter()).cloned().collect::<Vec<_>>());
+        assert_eq!(0, Intersection::new(a.iter(), None.into_iter()).count());
+    }
+}
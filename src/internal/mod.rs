@@ -1,3 +1,28 @@
+pub mod encoding;
 pub mod errors;
+pub mod merge;
+pub mod parallel;
 pub mod search;
+pub mod unaligned;
 pub mod vector_base;
+
+#[cfg(test)]
+pub mod properties {
+    // Skipping from either end must land where the plain slice does.
+    pub fn skips_agree<I>(iter: I, expected: &[I::Item], n: usize, step: usize) -> bool
+        where I: DoubleEndedIterator + ExactSizeIterator + Clone,
+              I::Item: Clone + PartialEq {
+
+        let mut from_both = iter.clone();
+        let mut naive = expected.iter().cloned();
+        let both_agree = from_both.nth(n) == naive.nth(n)
+            && from_both.nth_back(n) == naive.nth_back(n)
+            && from_both.eq(naive);
+
+        both_agree
+            && iter.clone().rev().step_by(step).eq(expected.iter().rev().step_by(step).cloned())
+            && iter.clone().rev().skip(n).take(3).eq(expected.iter().rev().skip(n).take(3).cloned())
+            && iter.clone().last() == expected.last().cloned()
+            && iter.count() == expected.len()
+    }
+}
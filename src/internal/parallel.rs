@@ -0,0 +1,70 @@
+//! Splitting an index range across threads, for building and querying
+//! structures that any thread can read.
+//!
+//! These run on scoped standard threads.
+
+use std::ops::Range;
+use std::thread;
+
+/// Cuts `0 .. len` into up to `threads` contiguous ranges whose lengths
+/// are multiples of `grain`, except perhaps the last, runs `work` on
+/// each on a thread of its own, and returns the results in order.
+///
+/// # Panics
+///
+/// Panics if `threads` or `grain` is 0, or if a thread panics.
+pub fn map<T, W>(len: u64, threads: usize, grain: u64, work: W) -> Vec<T>
+    where T: Send,
+          W: Fn(Range<u64>) -> T + Sync {
+
+    assert!(threads > 0, "parallel::map: no threads");
+    assert!(grain > 0, "parallel::map: zero grain");
+
+    let chunk = len.div_ceil(threads as u64).div_ceil(grain).max(1) * grain;
+    let work = &work;
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0 .. len.div_ceil(chunk)).map(|i| {
+            let range = i * chunk .. ((i + 1) * chunk).min(len);
+            scope.spawn(move || work(range))
+        }).collect();
+        handles.into_iter()
+               .map(|handle| handle.join().expect("parallel::map: thread panicked"))
+               .collect()
+    })
+}
+
+/// Like [`map`](fn.map.html), but combines the results in order with
+/// `reduce`. A range may fold to `None`, as may the whole.
+///
+/// # Panics
+///
+/// Panics if `threads` or `grain` is 0, or if a thread panics.
+pub fn map_reduce<T, W, R>(len: u64, threads: usize, grain: u64, work: W, reduce: R)
+                           -> Option<T>
+    where T: Send,
+          W: Fn(Range<u64>) -> Option<T> + Sync,
+          R: Fn(T, T) -> T {
+
+    map(len, threads, grain, work).into_iter().flatten().reduce(reduce)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn covers_in_order() {
+        assert_eq!(vec![0 .. 64, 64 .. 100], map(100, 8, 64, |range| range));
+        assert!(map(0, 2, 1, |range| range).is_empty());
+
+        let ranges = map_reduce(1000, 3, 64, |range| Some(vec![range]),
+                                |mut left, right| { left.extend(right); left });
+        assert_eq!(Some(vec![0 .. 384, 384 .. 768, 768 .. 1000]), ranges);
+
+        let count = |range: Range<u64>| Some(range.count());
+        assert_eq!(None, map_reduce(0, 4, 1, count, |a, b| a + b));
+        assert_eq!(Some(10), map_reduce(10, 64, 1, count, |a, b| a + b));
+        assert_eq!(Some(5), map_reduce(10, 2, 1, |range| Some(range.start).filter(|&s| s > 0),
+                                       |a, b| a + b));
+    }
+}
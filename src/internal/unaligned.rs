@@ -0,0 +1,72 @@
+//! Reading blocks from byte buffers that needn’t be aligned for them.
+//!
+//! A section of a file, a memory map at an odd offset, or a buffer from
+//! the network can put a block at any address, and some targets fault
+//! on a misaligned load. Each block here is assembled from its bytes
+//! with `from_le_bytes`, which is safe everywhere and which compilers
+//! turn into a single load where the target allows one.
+
+use std::convert::TryFrom;
+
+use storage::BlockType;
+
+/// The number of bytes in a `Block`.
+pub fn block_bytes<Block: BlockType>() -> usize {
+    Block::nbits() / 8
+}
+
+/// Reads the `index`th little-endian `Block` of `bytes`.
+///
+/// # Panics
+///
+/// Panics if the block runs past the end of `bytes`.
+pub fn read_block<Block: BlockType>(bytes: &[u8], index: usize) -> Block {
+    let width = block_bytes::<Block>();
+    let start = index.checked_mul(width).expect("read_block: index overflow");
+    let bytes = &bytes[start .. start + width];
+
+    let value = match width {
+        1 => bytes[0] as u64,
+        2 => u16::from_le_bytes(<[u8; 2]>::try_from(bytes).unwrap()) as u64,
+        4 => u32::from_le_bytes(<[u8; 4]>::try_from(bytes).unwrap()) as u64,
+        8 => u64::from_le_bytes(<[u8; 8]>::try_from(bytes).unwrap()),
+        _ => panic!("read_block: no blocks of {} bytes", width),
+    };
+    Block::from(value).expect("read_block: block too narrow")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use byteorder::{ByteOrder, LittleEndian};
+    use quickcheck::quickcheck;
+
+    #[test]
+    #[should_panic]
+    fn past_the_end() {
+        read_block::<u32>(&[1, 2, 3, 4, 5, 6, 7], 1);
+    }
+
+    #[test]
+    fn qc_matches_byteorder() {
+        fn prop(bytes: Vec<u8>, offset: u8) -> bool {
+            let offset = (offset as usize).min(bytes.len());
+            let bytes = &bytes[offset ..];
+
+            (0 .. bytes.len()).all(|i| read_block::<u8>(bytes, i) == bytes[i])
+                && (0 .. bytes.len() / 2).all(|i| {
+                    read_block::<u16>(bytes, i) == LittleEndian::read_u16(&bytes[2 * i ..])
+                })
+                && (0 .. bytes.len() / 4).all(|i| {
+                    read_block::<u32>(bytes, i) == LittleEndian::read_u32(&bytes[4 * i ..])
+                })
+                && (0 .. bytes.len() / 8).all(|i| {
+                    let expected = LittleEndian::read_u64(&bytes[8 * i ..]);
+                    read_block::<u64>(bytes, i) == expected
+                        && (usize::BITS != 64 || read_block::<usize>(bytes, i) as u64 == expected)
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, u8) -> bool);
+    }
+}
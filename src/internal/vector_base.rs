@@ -1,11 +1,14 @@
 #![allow(dead_code)]
 
+use std::marker::PhantomData;
+use std::ops::Range;
+
 #[cfg(target_pointer_width = "32")]
 use num::ToPrimitive;
 
-use bit_vec::{BitVec, BitVecMut};
+use error::Error;
 use space_usage::SpaceUsage;
-use storage::BlockType;
+use storage::{BlockStore, BlockType};
 
 /// VectorBase provides basic functionality for IntVector and BitVector. It
 /// doesn’t know its element size, but it does know (once provided its
@@ -22,9 +25,29 @@ use storage::BlockType;
 /// it is assumed to have already been checked, so the client must ensure that it
 /// doesn’t pass bogus `element_bits` values.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct VectorBase<Block> {
+#[cfg_attr(feature = "rkyv", derive(::rkyv::Archive, ::rkyv::Serialize, ::rkyv::Deserialize))]
+pub struct VectorBase<Block, Store = Vec<Block>> {
     len: u64,
-    vec: Vec<Block>,
+    vec: Store,
+    marker: PhantomData<Block>,
+}
+
+// The blocks of `value` repeated end to end in `element_bits`-bit
+// fields, through one period: block `k` of any vector filled with
+// `value` is the `k % len`th of them.
+fn periodic_blocks<Block: BlockType>(element_bits: usize, value: Block) -> Vec<Block> {
+    let nbits = Block::nbits();
+    let len = element_bits / gcd(element_bits, nbits);
+    (0 .. len).map(|k| {
+        (0 .. nbits).fold(Block::zero(), |block, bit| {
+            let phase = (k * nbits + bit) % element_bits;
+            block.with_bit(bit, value.get_bit(phase))
+        })
+    }).collect()
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {a} else {gcd(b, a % b)}
 }
 
 #[inline]
@@ -33,21 +56,50 @@ fn len_to_block_len<Block: BlockType>(element_bits: usize, len: u64) -> Option<u
        .and_then(Block::checked_ceil_div_nbits)
 }
 
+// Allocates an empty `Vec` with room for `capacity` elements, failing
+// rather than aborting if the allocation is refused.
+pub fn try_alloc<T>(capacity: usize) -> Result<Vec<T>, Error> {
+    let mut vec = Vec::new();
+    vec.try_reserve_exact(capacity).map_err(|_| Error::Allocation)?;
+    Ok(vec)
+}
+
 impl<Block: BlockType> VectorBase<Block> {
+    // Adopts `vec` as the blocks of a vector of `len` elements, or returns
+    // `None` if that would violate the invariants.
+    pub fn from_blocks(element_bits: usize, len: u64, vec: Vec<Block>)
+                       -> Option<Self> {
+        if len_to_block_len::<Block>(element_bits, len) != Some(vec.len()) {
+            return None;
+        }
+
+        let result = VectorBase { len, vec, marker: PhantomData };
+
+        let mut cleared = result.clone();
+        cleared.clear_extra_bits(element_bits);
+        if cleared == result {Some(result)} else {None}
+    }
+}
+
+impl<Block, Store> VectorBase<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     // Maintains the second invariant: extra bits are zero.
     #[inline]
     fn clear_extra_bits(&mut self, element_bits: usize) {
         let bit_len = self.len * element_bits as u64;
-        self.vec.last_mut().map(|block| {
+        if let Some(last) = self.vec.block_len().checked_sub(1) {
             let mask = Block::low_mask(Block::last_block_bits(bit_len));
-            *block = *block & mask;
-        });
+            let block = self.vec.get_block(last);
+            self.vec.set_block(last, block & mask);
+        }
     }
 
-    // Sets the length based on the number of blocks in the underlying Vec.
+    // Sets the length based on the number of blocks in the underlying store.
     #[inline]
     fn set_len_from_blocks(&mut self, element_bits: usize) {
-        self.len = Block::mul_nbits(self.vec.len()) / element_bits as u64;
+        self.len = Block::mul_nbits(self.vec.block_len()) / element_bits as u64;
         self.clear_extra_bits(element_bits);
     }
 
@@ -58,9 +110,35 @@ impl<Block: BlockType> VectorBase<Block> {
 
     #[inline]
     pub fn block_with_capacity(block_capacity: usize) -> Self {
+        Self::from_store(Store::with_block_capacity(block_capacity))
+    }
+
+    #[inline]
+    fn from_store(store: Store) -> Self {
         VectorBase {
             len: 0,
-            vec: Vec::with_capacity(block_capacity)
+            vec: store,
+            marker: PhantomData,
+        }
+    }
+
+    // Adopts the blocks of `store`, making the length as many elements as
+    // they hold.
+    pub fn with_store(element_bits: usize, store: Store) -> Self {
+        let mut result = Self::from_store(store);
+        result.set_len_from_blocks(element_bits);
+        result
+    }
+
+    // Moves the blocks to another kind of store. `convert` must keep
+    // them as they are.
+    pub fn map_store<F, Other>(self, convert: F) -> VectorBase<Block, Other>
+        where F: FnOnce(Store) -> Other {
+
+        VectorBase {
+            len: self.len,
+            vec: convert(self.vec),
+            marker: PhantomData,
         }
     }
 
@@ -71,45 +149,100 @@ impl<Block: BlockType> VectorBase<Block> {
                 .expect("VectorBase::with_capacity: overflow"))
     }
 
+    pub fn try_with_capacity(element_bits: usize, capacity: u64)
+                             -> Result<Self, Error> {
+        let block_capacity = len_to_block_len::<Block>(element_bits, capacity)
+                                 .ok_or(Error::Overflow)?;
+        Ok(Self::from_store(Store::try_with_block_capacity(block_capacity)?))
+    }
+
     #[inline]
     pub fn block_with_fill(element_bits: usize, block_len: usize, fill: Block)
                            -> Self {
-        let mut result = VectorBase {
-            len: 0,
-            vec: vec![ fill; block_len ],
-        };
-
+        let mut result = Self::block_with_capacity(block_len);
+        result.vec.resize_blocks(block_len, fill);
         result.set_len_from_blocks(element_bits);
         result
     }
 
+    pub fn try_block_with_fill(element_bits: usize, block_len: usize,
+                               fill: Block) -> Result<Self, Error> {
+        let mut result =
+            Self::from_store(Store::try_with_block_capacity(block_len)?);
+        result.vec.resize_blocks(block_len, fill);
+        result.set_len_from_blocks(element_bits);
+        Ok(result)
+    }
+
     #[inline]
     pub fn with_fill(element_bits: usize, len: u64, value: Block) -> Self {
         let block_len = len_to_block_len::<Block>(element_bits, len)
                             .expect("VectorBase::with_fill: overflow");
-        let mut result = VectorBase {
-            len: len,
-            vec: vec![ Block::zero(); block_len ],
-        };
-
-        for i in 0 .. len {
-            result.set_bits(element_bits, i * element_bits as u64,
-                            element_bits, value);
+        let mut result = Self::block_with_capacity(block_len);
+        result.vec.resize_blocks(block_len, Block::zero());
+        result.len = len;
+        result.fill_from(element_bits, 0, value);
+        result
+    }
+
+    pub fn try_with_fill(element_bits: usize, len: u64, value: Block)
+                         -> Result<Self, Error> {
+        let block_len = len_to_block_len::<Block>(element_bits, len)
+                            .ok_or(Error::Overflow)?;
+        let mut result =
+            Self::from_store(Store::try_with_block_capacity(block_len)?);
+        result.vec.resize_blocks(block_len, Block::zero());
+        result.len = len;
+        result.fill_from(element_bits, 0, value);
+        Ok(result)
+    }
+
+    // Sets every element from `start` on to `value`. The elements that
+    // cover whole blocks repeat with a period of lcm(element_bits, nbits)
+    // bits, so those blocks are copied from a precomputed period; only
+    // the elements that share the first and last blocks are set singly.
+    fn fill_from(&mut self, element_bits: usize, start: u64, value: Block) {
+        let width = element_bits as u64;
+        let nbits = Block::nbits() as u64;
+        let first_block = (start * width).div_ceil(nbits);
+        let end_block = self.len * width / nbits;
+
+        if first_block >= end_block {
+            self.fill_elements(element_bits, start .. self.len, value);
+            return;
         }
 
-        result
+        let period = periodic_blocks(element_bits, value);
+        self.fill_elements(element_bits, start .. (first_block * nbits).div_ceil(width), value);
+        for block in first_block .. end_block {
+            let pattern = period[(block % period.len() as u64) as usize];
+            self.vec.set_block(block as usize, pattern);
+        }
+        self.fill_elements(element_bits, end_block * nbits / width .. self.len, value);
+    }
+
+    fn fill_elements(&mut self, element_bits: usize, range: Range<u64>, value: Block) {
+        for i in range {
+            self.set_bits(element_bits, i * element_bits as u64,
+                          element_bits, value);
+        }
+    }
+
+    // Sets every element to `value`.
+    pub fn fill(&mut self, element_bits: usize, value: Block) {
+        self.fill_from(element_bits, 0, value);
     }
 
     #[inline]
     pub fn get_block(&self, block_index: usize) -> Block {
-        self.vec[block_index]
+        self.vec.get_block(block_index)
     }
 
     #[inline]
     pub fn set_block(&mut self, element_bits: usize,
                      block_index: usize, value: Block) {
-        self.vec[block_index] = value;
-        if block_index + 1 == self.vec.len() {
+        self.vec.set_block(block_index, value);
+        if block_index + 1 == self.vec.block_len() {
             self.clear_extra_bits(element_bits);
         }
     }
@@ -148,21 +281,22 @@ impl<Block: BlockType> VectorBase<Block> {
 
     #[inline]
     pub fn push_block(&mut self, element_bits: usize, value: Block) {
-        self.vec.push(value);
+        self.vec.push_block(value);
         self.set_len_from_blocks(element_bits);
     }
 
     #[inline]
     pub fn pop_block(&mut self, element_bits: usize) -> Option<Block> {
-        let result = self.vec.pop();
+        let result = self.vec.pop_block();
         self.set_len_from_blocks(element_bits);
         result
     }
 
     #[inline]
     pub fn push_bits(&mut self, element_bits: usize, value: Block) {
-        if element_bits as u64 * (self.len + 1) > Block::mul_nbits(self.vec.len()) {
-            self.vec.push(Block::zero());
+        if element_bits as u64 * (self.len + 1)
+                > Block::mul_nbits(self.vec.block_len()) {
+            self.vec.push_block(Block::zero());
         }
 
         let pos = self.len;
@@ -182,7 +316,7 @@ impl<Block: BlockType> VectorBase<Block> {
         self.set_bits(element_bits, bit_len, element_bits, Block::zero());
         self.len -= 1;
 
-        if self.vec.len() > block_len { self.vec.pop(); }
+        if self.vec.block_len() > block_len { self.vec.pop_block(); }
 
         Some(result)
     }
@@ -190,8 +324,8 @@ impl<Block: BlockType> VectorBase<Block> {
     // PRECONDITION: element_bits == 1
     #[inline]
     pub fn push_bit(&mut self, value: bool) {
-        if self.len + 1 > Block::mul_nbits(self.vec.len()) {
-            self.vec.push(Block::zero());
+        if self.len + 1 > Block::mul_nbits(self.vec.block_len()) {
+            self.vec.push_block(Block::zero());
         }
 
         let pos = self.len;
@@ -209,14 +343,14 @@ impl<Block: BlockType> VectorBase<Block> {
         self.len = new_len;
 
         let block_len = Block::ceil_div_nbits(new_len);
-        if self.vec.len() > block_len { self.vec.pop(); }
+        if self.vec.block_len() > block_len { self.vec.pop_block(); }
 
         Some(result)
     }
 
     #[inline]
     pub fn block_len(&self) -> usize {
-        self.vec.len()
+        self.vec.block_len()
     }
 
     #[inline]
@@ -226,12 +360,12 @@ impl<Block: BlockType> VectorBase<Block> {
 
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.vec.is_empty()
+        self.vec.block_len() == 0
     }
 
     #[inline]
     pub fn block_capacity(&self) -> usize {
-        self.vec.capacity()
+        self.vec.block_capacity()
     }
 
     #[inline]
@@ -241,8 +375,8 @@ impl<Block: BlockType> VectorBase<Block> {
 
     #[inline]
     pub fn block_truncate(&mut self, element_bits: usize, block_len: usize) {
-        if block_len < self.vec.len() {
-            self.vec.truncate(block_len);
+        if block_len < self.vec.block_len() {
+            self.vec.truncate_blocks(block_len);
             self.set_len_from_blocks(element_bits);
         }
     }
@@ -251,7 +385,7 @@ impl<Block: BlockType> VectorBase<Block> {
     pub fn truncate(&mut self, element_bits: usize, len: u64) {
         if len < self.len {
             let block_len = Block::ceil_div_nbits(len * element_bits as u64);
-            self.vec.truncate(block_len);
+            self.vec.truncate_blocks(block_len);
             self.len = len;
             self.clear_extra_bits(element_bits);
         }
@@ -259,23 +393,23 @@ impl<Block: BlockType> VectorBase<Block> {
 
     #[inline]
     pub fn clear(&mut self) {
-        self.vec.clear();
+        self.vec.clear_blocks();
         self.len = 0;
     }
 
     #[inline]
     pub fn shrink_to_fit(&mut self) {
-        self.vec.shrink_to_fit()
+        self.vec.shrink_blocks_to_fit()
     }
 
     #[inline]
     pub fn block_reserve(&mut self, additional: usize) {
-        self.vec.reserve(additional);
+        self.vec.reserve_blocks(additional);
     }
 
     #[inline]
     pub fn block_reserve_exact(&mut self, additional: usize) {
-        self.vec.reserve_exact(additional);
+        self.vec.reserve_blocks_exact(additional);
     }
 
     fn additional_blocks(&self, element_bits: usize, additional: u64)
@@ -284,7 +418,7 @@ impl<Block: BlockType> VectorBase<Block> {
                 .and_then(|e| e.checked_mul(element_bits as u64))
                 .and_then(Block::checked_ceil_div_nbits)
                 .expect("VectorBase::reserve_(exact): overflow")
-                .saturating_sub(self.vec.capacity())
+                .saturating_sub(self.vec.block_capacity())
     }
 
     #[inline]
@@ -302,7 +436,7 @@ impl<Block: BlockType> VectorBase<Block> {
     #[inline]
     pub fn block_resize(&mut self, element_bits: usize,
                          block_len: usize, fill: Block) {
-        self.vec.resize(block_len, fill);
+        self.vec.resize_blocks(block_len, fill);
         self.set_len_from_blocks(element_bits);
     }
 
@@ -311,32 +445,33 @@ impl<Block: BlockType> VectorBase<Block> {
         let block_len = len_to_block_len::<Block>(element_bits, len)
                             .expect("VectorBase::resize: overflow");
 
-        self.vec.resize(block_len, Block::zero());
+        self.vec.resize_blocks(block_len, Block::zero());
         let old_len = self.len;
         self.len = len;
 
         if len <= old_len {
             self.clear_extra_bits(element_bits);
         } else {
-            for i in old_len .. len {
-                self.set_bits(element_bits, i * element_bits as u64,
-                              element_bits, fill);
-            }
+            self.fill_from(element_bits, old_len, fill);
         }
     }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Iter<'a, Block: BlockType + 'a> {
+pub struct Iter<'a, Block: BlockType + 'a, Store: 'a = Vec<Block>> {
     start: u64,
     limit: u64,
     element_bits: usize,
-    data:  &'a VectorBase<Block>,
+    data:  &'a VectorBase<Block, Store>,
 }
 
-impl<'a, Block: BlockType> Iter<'a, Block> {
+impl<'a, Block, Store> Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     #[inline]
-    pub fn new(element_bits: usize, data: &'a VectorBase<Block>) -> Self {
+    pub fn new(element_bits: usize, data: &'a VectorBase<Block, Store>)
+               -> Self {
         Iter {
             start: 0,
             limit: data.len(),
@@ -346,7 +481,10 @@ impl<'a, Block: BlockType> Iter<'a, Block> {
     }
 }
 
-impl<'a, Block: BlockType> Iterator for Iter<'a, Block> {
+impl<'a, Block, Store> Iterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     type Item = Block;
 
     #[inline]
@@ -378,15 +516,7 @@ impl<'a, Block: BlockType> Iterator for Iter<'a, Block> {
         (len, Some(len))
     }
 
-    #[inline]
-    fn count(self) -> usize {
-        self.len()
-    }
-
-    #[inline]
-    fn last(mut self) -> Option<Self::Item> {
-        self.next_back()
-    }
+    impl_exact_size_count_last!(Self::Item);
 
     #[inline]
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
@@ -396,27 +526,29 @@ impl<'a, Block: BlockType> Iterator for Iter<'a, Block> {
 }
 
 #[cfg(target_pointer_width = "64")]
-impl<'a, Block: BlockType> ExactSizeIterator for Iter<'a, Block> {
+impl<'a, Block, Store> ExactSizeIterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     #[inline]
     fn len(&self) -> usize {
         (self.limit - self.start) as usize
     }
 }
 
-impl<'a, Block: BlockType> DoubleEndedIterator for Iter<'a, Block> {
-    #[inline]
-    fn next_back(&mut self) -> Option<Self::Item> {
-        if self.start < self.limit {
-            self.limit -= 1;
-            Some(self.data.get_bits(
-                self.element_bits,
-                self.element_bits as u64 * self.limit,
-                self.element_bits))
-        } else { None }
-    }
+impl<'a, Block, Store> DoubleEndedIterator for Iter<'a, Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
+    impl_index_range_back!(Self::Item, start .. limit, |iter, index| {
+        iter.data.get_bits(iter.element_bits, iter.element_bits as u64 * index, iter.element_bits)
+    });
 }
 
-impl<Block: BlockType> SpaceUsage for VectorBase<Block> {
+impl<Block, Store> SpaceUsage for VectorBase<Block, Store>
+    where Block: BlockType,
+          Store: BlockStore<Block = Block> {
+
     #[inline]
     fn is_stack_only() -> bool { false }
 
@@ -426,6 +558,19 @@ impl<Block: BlockType> SpaceUsage for VectorBase<Block> {
     }
 }
 
+// Reads an archived vector base in place; the vector built on it
+// knows what its length counts.
+#[cfg(feature = "rkyv")]
+impl<Block: BlockType + ::rkyv::Archive> ArchivedVectorBase<Block, Vec<Block>> {
+    pub fn len(&self) -> u64 {
+        self.len.to_native()
+    }
+
+    pub fn blocks(&self) -> &::rkyv::vec::ArchivedVec<::rkyv::Archived<Block>> {
+        &self.vec
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -760,6 +905,33 @@ mod test {
         assert_eq!(0b00011010, v.get_block(0));
     }
 
+    #[test]
+    fn qc_fill_matches_elementwise() {
+        fn prop(before: u8, after: u16, width: u8, old: u32, value: u32) -> bool {
+            let width = width as usize % 32 + 1;
+            let mask = if width == 32 {!0} else {(1u32 << width) - 1};
+            let (old, value) = (old & mask, value & mask);
+            let (before, after) = (before as u64, before as u64 + after as u64 % 300);
+
+            let mut v = VectorBase::<u32>::with_fill(width, before, old);
+            v.resize(width, after, value);
+            let mut filled = v.clone();
+            filled.fill(width, old);
+
+            (0 .. after).all(|i| {
+                let expected = if i < before {old} else {value};
+                v.get_bits(width, i * width as u64, width) == expected
+                    && filled.get_bits(width, i * width as u64, width) == old
+            }) && {
+                // Nothing is written past the end.
+                let used = (after * width as u64 % 32) as u32;
+                used == 0 || v.get_block(v.block_len() - 1) >> used == 0
+            }
+        }
+
+        ::quickcheck::quickcheck(prop as fn(u8, u16, u8, u32, u32) -> bool);
+    }
+
     #[test] #[should_panic]
     fn with_capacity_overflow() {
         VB::with_capacity(5, !0);
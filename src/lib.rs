@@ -7,9 +7,28 @@
 //!   - [integer vectors](int_vector/struct.IntVec.html) with arbitrary-sized
 //!     (1- to 64-bit) elements;
 //!   - a variety of [universal codes](coding/index.html;
-//!   - constant-time [rank](struct.JacobsonRank.html) queries; and
+//!   - constant-time [rank](struct.JacobsonRank.html) queries;
 //!   - *O*(lg lg *n*)-time [select](struct.BinSearchSelect.html) queries
-//!     based on binary search over ranks.
+//!     based on binary search over ranks;
+//!   - constant-time [select](struct.Select9.html) queries over
+//!     [`Rank9`](struct.Rank9.html);
+//!   - [wavelet matrices](wavelet/struct.WaveletMatrix.html) for rank and
+//!     select over integer sequences, and [point
+//!     grids](wavelet/struct.PointGrid.html) on them for 2D range queries;
+//!   - [FM-indexes](text/struct.FmIndex.html) over byte, `u16` or `u32`
+//!     text;
+//!   - [LOUDS](tree/struct.Louds.html) and [balanced
+//!     parentheses](tree/struct.BalancedParens.html) trees, navigated by
+//!     [range min-max trees](tree/struct.RangeMinMaxTree.html);
+//!   - [static graphs](graph/struct.StaticGraph.html) with Elias–Fano
+//!     adjacency;
+//!   - [space-filling curves](curve/index.html) for storing 2D points in
+//!     any of the above;
+//!   - [static functions](hash/struct.StaticFunction.html) mapping keys
+//!     to values in about the values’ entropy; and
+//!   - a [checksummed file format](persist/index.html) for saving bit and
+//!     integer vectors, `Rank9`, `Select9`, wavelet matrices and
+//!     FM-indexes.
 //!
 //! # Usage
 //!
@@ -21,25 +40,70 @@
 //! ```
 //!
 //! to your `Cargo.toml`.
+//!
+//! # Positions and sizes
+//!
+//! Every position, index, rank and length in the API is a `u64`,
+//! whatever the target, so a structure over more than 2<sup>32</sup> bits
+//! works the same on 32-bit and wasm targets as on 64-bit ones. `usize`
+//! appears only for quantities bounded by memory: indices of blocks in a
+//! store, element widths, and offsets into slices the caller passes in.
+//!
+//! # Thread safety
+//!
+//! Queries take `&self` and nothing caches behind the caller’s back, so
+//! every structure here is `Send` and `Sync` whenever its store is. A
+//! built index can go in an `Arc`, or on a
+//! [`SharedStore`](storage/struct.SharedStore.html), and be queried from
+//! any number of threads at once.
 
 #![doc(html_root_url = "https://docs.rs/succinct/0.5.2")]
 #![warn(missing_docs)]
 
 extern crate byteorder;
+#[cfg(feature = "memmap2")]
+extern crate memmap2;
 extern crate num_traits;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rkyv")]
+extern crate rkyv;
 
 #[cfg(test)]
 extern crate quickcheck;
 
 #[macro_use]
 mod macros;
+#[doc(hidden)]
+pub use macros::{__bit_record_get, __bit_record_set};
 
 mod internal;
 
+pub mod access;
 pub mod broadword;
+pub mod build;
 pub mod coding;
+pub mod combinatorics;
+pub mod curve;
+pub mod dump;
+pub mod graph;
+pub mod hash;
+#[cfg(feature = "instrumentation")]
+pub mod instrument;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod persist;
+pub mod shard;
+pub mod stats;
 pub mod storage;
 pub mod stream;
+pub mod text;
+pub mod tree;
+pub mod util;
+pub mod wavelet;
+
+mod error;
+pub use error::Error;
 
 mod space_usage;
 pub use space_usage::SpaceUsage;
@@ -54,5 +118,58 @@ pub mod rank;
 pub use rank::{BitRankSupport, JacobsonRank, Rank9};
 
 pub mod select;
-pub use select::{Select1Support, BinSearchSelect};
+pub use select::{Select1Support, BinSearchSelect, Select9};
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use int_vec::{DeltaVector, EscapedIntVector, SparseIntVector};
+    use storage::{PagedStore, SharedStore};
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<BitVector<u64>>();
+        assert_send_sync::<BitVector<u64, PagedStore<u64>>>();
+        assert_send_sync::<BitVector<u64, SharedStore<u64>>>();
+        assert_send_sync::<IntVector<u32>>();
+        assert_send_sync::<IntVector<u32, SharedStore<u32>>>();
+        assert_send_sync::<SparseIntVector<u16>>();
+        assert_send_sync::<EscapedIntVector<u16>>();
+        assert_send_sync::<DeltaVector>();
+        assert_send_sync::<JacobsonRank<BitVector<u64>>>();
+        assert_send_sync::<Rank9<BitVector<u64>>>();
+        assert_send_sync::<BinSearchSelect<Rank9<BitVector<u64>>>>();
+        assert_send_sync::<Select9<BitVector<u64>>>();
+        assert_send_sync::<wavelet::WaveletMatrix>();
+        assert_send_sync::<wavelet::PointGrid>();
+        assert_send_sync::<text::FmIndex<u32>>();
+    }
+
+    #[test]
+    fn concurrent_queries() {
+        let mut bits: BitVector<u64> = BitVector::new();
+        for i in 0 .. 100_000u64 {
+            bits.push_bit(i % 5 == 0);
+        }
+        let select = Arc::new(Select9::new(Rank9::new(bits)));
+
+        let handles: Vec<_> = (0 .. 4u64).map(|t| {
+            let select = select.clone();
+            thread::spawn(move || {
+                for k in (t .. 20_000).step_by(4) {
+                    assert_eq!(Some(5 * k), select.select1(k));
+                    assert_eq!(k + 1, select.inner().rank1(5 * k));
+                }
+            })
+        }).collect();
 
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -1,5 +1,9 @@
 //! Macros for export.
 
+use num_traits::{NumCast, ToPrimitive};
+
+use bit_vec::{BitVec, BitVecMut};
+
 /// Implements `SpaceUsage` for a stack-only (`Copy`) type.
 ///
 /// # Example
@@ -131,3 +135,249 @@ macro_rules! impl_select_support_adapter {
         }
     }
 }
+
+/// Defines a record of bit fields, with accessors that read and write
+/// records packed one after another in a bit vector.
+///
+/// Each field is given as `getter / setter: width`, in bits, up to the
+/// block size of the vectors it will be used with, and the fields are
+/// packed in order from the least significant bit. The macro defines a
+/// struct of the named fields, as `u64`s, with these associated items:
+///
+///   - `BITS`, the width of a record;
+///   - `get(&bits, index)` and `set(&mut bits, index, record)`, which
+///     read and write record `index` whole;
+///   - for each field, `getter(&bits, index)` and
+///     `setter(&mut bits, index, value)`, which touch just that field.
+///
+/// The vector can be any `BitVec`, or `BitVecMut` for the setters,
+/// including an `IntVector` whose elements are `BITS` wide. Setting a
+/// field to a value wider than it panics.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use] extern crate succinct;
+/// use succinct::BitVector;
+///
+/// bit_record! {
+///     /// A header: a 3-bit type, a flag, and a 12-bit length.
+///     pub struct Header {
+///         kind / set_kind: 3,
+///         flag / set_flag: 1,
+///         length / set_length: 12,
+///     }
+/// }
+///
+/// fn main() {
+///     assert_eq!(16, Header::BITS);
+///
+///     let mut bits: BitVector<u64> = BitVector::with_fill(10 * Header::BITS as u64, false);
+///     Header::set(&mut bits, 3, Header { kind: 5, flag: 1, length: 4000 });
+///     Header::set_length(&mut bits, 4, 17);
+///
+///     assert_eq!(4000, Header::length(&bits, 3));
+///     assert_eq!(Header { kind: 0, flag: 0, length: 17 }, Header::get(&bits, 4));
+///     assert_eq!(5, Header::get(&bits, 3).kind);
+/// }
+/// ```
+#[macro_export]
+macro_rules! bit_record {
+    ( $(#[$attr:meta])*
+      $vis:vis struct $name:ident {
+          $( $(#[$field_attr:meta])* $field:ident / $setter:ident : $width:expr ),* $(,)*
+      } ) =>
+    {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+        $vis struct $name {
+            $( $(#[$field_attr])* pub $field: u64, )*
+        }
+
+        impl $name {
+            /// The number of bits in a record.
+            pub const BITS: usize = 0 $( + $width )*;
+
+            /// Reads record `index` of `bits`.
+            #[allow(dead_code)]
+            pub fn get<V: $crate::BitVec + ?Sized>(bits: &V, index: u64) -> Self {
+                $name { $( $field: $name::$field(bits, index), )* }
+            }
+
+            /// Writes `record` as record `index` of `bits`.
+            #[allow(dead_code)]
+            pub fn set<V: $crate::BitVecMut + ?Sized>(bits: &mut V, index: u64, record: Self) {
+                $( $name::$setter(bits, index, record.$field); )*
+            }
+        }
+
+        $crate::bit_record!(@fields $name, 0; $( $field / $setter : $width ),*);
+    };
+
+    ( @fields $name:ident, $offset:expr; ) => {};
+
+    ( @fields $name:ident, $offset:expr;
+      $field:ident / $setter:ident : $width:expr
+      $(, $rest:ident / $rest_setter:ident : $rest_width:expr )* ) =>
+    {
+        impl $name {
+            /// Reads this field of record `index` of `bits`.
+            #[allow(dead_code)]
+            pub fn $field<V: $crate::BitVec + ?Sized>(bits: &V, index: u64) -> u64 {
+                $crate::__bit_record_get(bits, index * $name::BITS as u64 + ($offset) as u64,
+                                         $width)
+            }
+
+            /// Writes this field of record `index` of `bits`.
+            #[allow(dead_code)]
+            pub fn $setter<V: $crate::BitVecMut + ?Sized>(bits: &mut V, index: u64, value: u64) {
+                $crate::__bit_record_set(bits, index * $name::BITS as u64 + ($offset) as u64,
+                                         $width, value)
+            }
+        }
+
+        $crate::bit_record!(@fields $name, $offset + $width;
+                            $( $rest / $rest_setter : $rest_width ),*);
+    };
+}
+
+// Implements `count` and `last` for an `ExactSizeIterator` that is also
+// a `DoubleEndedIterator`, inside its `Iterator` impl.
+macro_rules! impl_exact_size_count_last {
+    ( $item:ty )
+        =>
+    {
+        fn count(self) -> usize {
+            self.len()
+        }
+
+        fn last(mut self) -> Option<$item> {
+            self.next_back()
+        }
+    }
+}
+
+// Implements `DoubleEndedIterator` for an iterator whose remaining
+// elements are those at indices `self.$front .. self.$back`, reading the
+// element at index `$index` with `$get`, where `$iter` borrows the
+// iterator.
+macro_rules! impl_index_range_back {
+    ( $item:ty, $front:ident .. $back:ident, |$iter:ident, $index:ident| $get:expr )
+        =>
+    {
+        fn next_back(&mut self) -> Option<$item> {
+            if self.$front >= self.$back { return None; }
+            self.$back -= 1;
+            let ($iter, $index) = (&*self, self.$back);
+            Some($get)
+        }
+
+        fn nth_back(&mut self, n: usize) -> Option<$item> {
+            self.$back = self.$back.saturating_sub(n as u64).max(self.$front);
+            self.next_back()
+        }
+    }
+}
+
+// Implements `Iterator`, `ExactSizeIterator` and `DoubleEndedIterator`
+// for a newtype over another iterator that has them, by forwarding to
+// it.
+macro_rules! impl_iterator_newtype {
+    ( [ $($param:tt)* ] $iter:ty => $item:ty )
+        =>
+    {
+        impl<$($param)*> Iterator for $iter {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<$item> {
+                self.0.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.0.size_hint()
+            }
+
+            fn nth(&mut self, n: usize) -> Option<$item> {
+                self.0.nth(n)
+            }
+
+            impl_exact_size_count_last!($item);
+        }
+
+        impl<$($param)*> ExactSizeIterator for $iter {}
+
+        impl<$($param)*> DoubleEndedIterator for $iter {
+            fn next_back(&mut self) -> Option<$item> {
+                self.0.next_back()
+            }
+
+            fn nth_back(&mut self, n: usize) -> Option<$item> {
+                self.0.nth_back(n)
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn __bit_record_get<V: BitVec + ?Sized>(bits: &V, start: u64, width: usize) -> u64 {
+    bits.get_bits(start, width).to_u64().expect("bit_record: field too wide")
+}
+
+#[doc(hidden)]
+pub fn __bit_record_set<V: BitVecMut + ?Sized>(bits: &mut V, start: u64, width: usize,
+                                               value: u64) {
+    assert!(width >= 64 || value >> width == 0, "bit_record: value too wide for its field");
+    let value = NumCast::from(value).expect("bit_record: field too wide");
+    bits.set_bits(start, width, value);
+}
+
+#[cfg(test)]
+mod test {
+    use bit_vec::BitVector;
+    use int_vec::{IntVec, IntVector};
+
+    bit_record! {
+        struct Span {
+            start / set_start: 20,
+            wide / set_wide: 64,
+            end / set_end: 9,
+        }
+    }
+
+    bit_record! {
+        struct Pair {
+            left / set_left: 5,
+            right / set_right: 11
+        }
+    }
+
+    #[test]
+    fn wide_fields() {
+        let mut records: BitVector<u64> = BitVector::with_fill(5 * Span::BITS as u64, false);
+        let span = Span { start: 1 << 19, wide: !0, end: 300 };
+        Span::set(&mut records, 2, span);
+        Span::set_end(&mut records, 3, 511);
+
+        assert_eq!(93, Span::BITS);
+        assert_eq!(span, Span::get(&records, 2));
+        assert_eq!(Span { start: 0, wide: 0, end: 511 }, Span::get(&records, 3));
+        assert_eq!(Span::default(), Span::get(&records, 4));
+    }
+
+    #[test]
+    fn over_int_vector() {
+        let mut records = IntVector::<u32>::with_fill(Pair::BITS, 4, 0);
+        Pair::set(&mut records, 1, Pair { left: 31, right: 1000 });
+        Pair::set_left(&mut records, 2, 7);
+        assert_eq!(1000, Pair::right(&records, 1));
+        assert_eq!(31 | 1000 << 5, records.get(1));
+        assert_eq!(7, records.get(2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn value_too_wide() {
+        let mut records = IntVector::<u32>::with_fill(Pair::BITS, 1, 0);
+        Pair::set_left(&mut records, 0, 32);
+    }
+}
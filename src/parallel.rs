@@ -0,0 +1,261 @@
+//! `rayon` parallel iterators over containers (with the `rayon` feature).
+//!
+//! A shared reference to an integer vector, an Elias–Fano or other
+//! compressed sequence, or a wavelet matrix is `IntoParallelIterator`,
+//! so `par_iter` maps and reduces over its elements across rayon’s pool.
+//! The iterator, [`Elements`](struct.Elements.html), is indexed: it
+//! splits its index range in two until the pieces are small enough and
+//! reads each with `get`, so it works as well for compressed sequences
+//! as for packed ones. [`ones`](fn.ones.html) iterates in parallel over
+//! the positions of the ones of any `BitVec`, splitting at block
+//! boundaries.
+//!
+//! ```
+//! extern crate rayon;
+//! extern crate succinct;
+//!
+//! use rayon::prelude::*;
+//! use succinct::{BitVector, IntVector};
+//! use succinct::int_vec::EliasFano;
+//! use succinct::parallel;
+//!
+//! # fn main() {
+//! let v: IntVector<u32> = (0 .. 10_000).collect();
+//! assert_eq!(49_995_000, v.par_iter().map(|x| x as u64).sum::<u64>());
+//!
+//! let ef = EliasFano::new(30_000, (0 .. 10_000).map(|i| 3 * i));
+//! assert_eq!(Some(29_997), ef.par_iter().max());
+//!
+//! let bools: Vec<bool> = (0 .. 1000).map(|i| i % 3 == 0).collect();
+//! let bits: BitVector<u64> = BitVector::from(&bools[..]);
+//! assert_eq!(Some(999), parallel::ones(&bits).max());
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use num_traits::{One, PrimInt, Zero};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use rayon::iter::plumbing::{bridge, bridge_unindexed, Consumer, Folder, Producer,
+                            ProducerCallback, UnindexedConsumer, UnindexedProducer};
+
+use access::{Access, AccessIter};
+use bit_vec::BitVec;
+use int_vec::{DeltaVector, EliasFano, EscapedIntVector, IntVector, PforVector,
+              SparseIntVector, StreamVByte};
+use storage::{BlockStore, BlockType};
+use wavelet::{LevelBits, WaveletMatrix};
+
+/// A parallel iterator over the items of an
+/// [`Access`](../access/trait.Access.html) sequence, in order.
+#[derive(Debug)]
+pub struct Elements<'a, A: ?Sized + 'a, Item> {
+    data: &'a A,
+    range: Range<u64>,
+    marker: PhantomData<fn() -> Item>,
+}
+
+impl<'a, A: Access<Item> + Sync + ?Sized, Item: Send> Elements<'a, A, Item> {
+    /// Iterates in parallel over the items of `data`.
+    pub fn new(data: &'a A) -> Self {
+        Elements { data, range: 0 .. data.len(), marker: PhantomData }
+    }
+}
+
+impl<'a, A: ?Sized, Item> Clone for Elements<'a, A, Item> {
+    fn clone(&self) -> Self {
+        Elements { data: self.data, range: self.range.clone(), marker: self.marker }
+    }
+}
+
+impl<'a, A, Item> ParallelIterator for Elements<'a, A, Item>
+    where A: Access<Item> + Sync + ?Sized,
+          Item: Send {
+
+    type Item = Item;
+
+    fn drive_unindexed<C: UnindexedConsumer<Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(IndexedParallelIterator::len(self))
+    }
+}
+
+impl<'a, A, Item> IndexedParallelIterator for Elements<'a, A, Item>
+    where A: Access<Item> + Sync + ?Sized,
+          Item: Send {
+
+    fn len(&self) -> usize {
+        (self.range.end - self.range.start) as usize
+    }
+
+    fn drive<C: Consumer<Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(self)
+    }
+}
+
+impl<'a, A, Item> Producer for Elements<'a, A, Item>
+    where A: Access<Item> + Sync + ?Sized,
+          Item: Send {
+
+    type Item = Item;
+    type IntoIter = AccessIter<'a, A, Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        AccessIter::with_range(self.data, self.range)
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let mid = self.range.start + index as u64;
+        (Elements { data: self.data, range: self.range.start .. mid, marker: PhantomData },
+         Elements { data: self.data, range: mid .. self.range.end, marker: PhantomData })
+    }
+}
+
+macro_rules! impl_into_parallel_iterator {
+    ( $( [ $($param:tt)* ] $container:ty => $item:ty { $($bounds:tt)* } )* )
+        =>
+    {
+        $(
+            impl<'a, $($param)*> IntoParallelIterator for &'a $container
+                where $($bounds)* {
+
+                type Item = $item;
+                type Iter = Elements<'a, $container, $item>;
+
+                fn into_par_iter(self) -> Self::Iter {
+                    Elements::new(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_into_parallel_iterator! {
+    [Block, Store] IntVector<Block, Store> => Block {
+        Block: BlockType + Send + Sync,
+        Store: BlockStore<Block = Block> + Sync
+    }
+    [] EliasFano => u64 {}
+    [] DeltaVector => u64 {}
+    [] PforVector => u64 {}
+    [] StreamVByte => u32 {}
+    [Block] SparseIntVector<Block> => Block { Block: BlockType + Send + Sync }
+    [Block] EscapedIntVector<Block> => Block { Block: BlockType + Send + Sync }
+    [Bits] WaveletMatrix<Bits> => u64 { Bits: LevelBits + Sync }
+}
+
+/// Iterates in parallel over the positions of the ones of `bits`.
+pub fn ones<V: BitVec + Sync + ?Sized>(bits: &V) -> Ones<'_, V> {
+    Ones { bits, blocks: 0 .. bits.block_len() }
+}
+
+/// A parallel iterator over the positions of the ones of a
+/// [`BitVec`](../bit_vec/trait.BitVec.html).
+///
+/// It isn’t indexed, since how many ones a run of blocks holds isn’t
+/// known before it is scanned, but the positions come out in order
+/// wherever order is kept, as by `collect`.
+#[derive(Debug)]
+pub struct Ones<'a, V: ?Sized + 'a> {
+    bits: &'a V,
+    blocks: Range<usize>,
+}
+
+impl<'a, V: ?Sized> Clone for Ones<'a, V> {
+    fn clone(&self) -> Self {
+        Ones { bits: self.bits, blocks: self.blocks.clone() }
+    }
+}
+
+impl<'a, V: BitVec + Sync + ?Sized> ParallelIterator for Ones<'a, V> {
+    type Item = u64;
+
+    fn drive_unindexed<C: UnindexedConsumer<u64>>(self, consumer: C) -> C::Result {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+impl<'a, V: BitVec + Sync + ?Sized> UnindexedProducer for Ones<'a, V> {
+    type Item = u64;
+
+    fn split(self) -> (Self, Option<Self>) {
+        let Range { start, end } = self.blocks;
+        if end - start < 2 {
+            return (self, None);
+        }
+        let mid = start + (end - start) / 2;
+        (Ones { bits: self.bits, blocks: start .. mid },
+         Some(Ones { bits: self.bits, blocks: mid .. end }))
+    }
+
+    fn fold_with<F: Folder<u64>>(self, mut folder: F) -> F {
+        let nbits = V::Block::nbits() as u64;
+        let bit_len = self.bits.bit_len();
+        for block in self.blocks {
+            let mut word = self.bits.get_block(block);
+            while word != V::Block::zero() {
+                let position = block as u64 * nbits + word.trailing_zeros() as u64;
+                if position >= bit_len || folder.full() { return folder; }
+                folder = folder.consume(position);
+                word = word & (word - V::Block::one());
+            }
+        }
+        folder
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::BitVector;
+    use quickcheck::quickcheck;
+    use rayon::prelude::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(0, IntVector::<u8>::new(3).par_iter().count());
+        assert_eq!(0, ones(&BitVector::<u8>::with_fill(100, false)).count());
+    }
+
+    #[test]
+    fn qc_elements() {
+        fn prop(values: Vec<u16>, skip: u8) -> bool {
+            let mut sorted = values.clone();
+            sorted.sort();
+            sorted.dedup();
+            let packed: IntVector<u16> = values.iter().cloned().collect();
+            let coded = EliasFano::new(1 << 16, sorted.iter().map(|&x| x as u64));
+            let skip = skip as usize;
+
+            packed.par_iter().collect::<Vec<_>>() == values
+                && packed.par_iter().rev().skip(skip).collect::<Vec<_>>()
+                    == values.iter().cloned().rev().skip(skip).collect::<Vec<_>>()
+                && coded.par_iter().with_min_len(3).sum::<u64>()
+                    == sorted.iter().map(|&x| x as u64).sum::<u64>()
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u8) -> bool);
+    }
+
+    #[test]
+    fn qc_ones() {
+        fn prop(bits: Vec<bool>) -> bool {
+            let bit_vector: BitVector<u8> = BitVector::from(&bits[..]);
+            let expected: Vec<u64> =
+                (0 .. bits.len() as u64).filter(|&i| bits[i as usize]).collect();
+            ones(&bit_vector).collect::<Vec<_>>() == expected
+                && ones(&bit_vector).find_first(|&i| i % 5 == 0)
+                    == expected.iter().cloned().find(|&i| i % 5 == 0)
+        }
+
+        quickcheck(prop as fn(Vec<bool>) -> bool);
+    }
+}
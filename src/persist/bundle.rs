@@ -0,0 +1,384 @@
+use std::io::{self, Error, ErrorKind, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use super::{Container, ContainerWriter, Persist, Verify};
+
+/// The section holding a bundle’s table of contents.
+const TOC: &str = "::toc";
+
+/// Types that can be stored in an [`IndexBundle`](struct.IndexBundle.html).
+///
+/// The tag and version are recorded with each component, so that loading
+/// it as the wrong type is an error, and a type whose encoding changes can
+/// bump its version and still read what older versions wrote.
+pub trait Component: Persist {
+    /// The version of the encoding `write_to` produces.
+    const VERSION: u32 = 1;
+
+    /// Names the type, such as `IntVector<u32>`.
+    fn tag() -> String;
+
+    /// Loads a value written by version `version` of the encoding.
+    ///
+    /// The default reads the current version and rejects any other with
+    /// `ErrorKind::InvalidData`.
+    fn read_version(bytes: &[u8], version: u32) -> io::Result<Self> {
+        if version == Self::VERSION {
+            Self::read_from(bytes)
+        } else {
+            Err(Error::new(ErrorKind::InvalidData,
+                           format!("{}: unsupported version {}", Self::tag(), version)))
+        }
+    }
+}
+
+/// A component’s entry in the table of contents.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ComponentInfo {
+    name: String,
+    tag: String,
+    version: u32,
+}
+
+impl ComponentInfo {
+    /// The component’s name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The tag of its type.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// The version of its type’s encoding.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// Writes several named structures to one file.
+#[derive(Debug)]
+pub struct BundleWriter<W: Write> {
+    container: ContainerWriter<W>,
+    components: Vec<ComponentInfo>,
+}
+
+impl<W: Write> BundleWriter<W> {
+    /// Starts a bundle on `sink`.
+    pub fn new(sink: W) -> io::Result<Self> {
+        Ok(BundleWriter {
+            container: ContainerWriter::new(sink)?,
+            components: Vec::new(),
+        })
+    }
+
+    /// Adds `value` under `name`.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if the name is taken.
+    pub fn add<T: Component>(&mut self, name: &str, value: &T) -> io::Result<()> {
+        if name == TOC {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "BundleWriter: reserved component name"));
+        }
+        self.container.add(name, value)?;
+        self.components.push(ComponentInfo {
+            name: name.to_owned(),
+            tag: T::tag(),
+            version: T::VERSION,
+        });
+        Ok(())
+    }
+
+    /// Writes the table of contents, returning the sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut toc = Vec::new();
+        toc.write_u32::<LittleEndian>(self.components.len() as u32)?;
+        for component in &self.components {
+            write_string(&mut toc, &component.name)?;
+            write_string(&mut toc, &component.tag)?;
+            toc.write_u32::<LittleEndian>(component.version)?;
+        }
+        self.container.add_bytes(TOC, &toc)?;
+        self.container.finish()
+    }
+}
+
+/// Several named structures stored together, loaded one at a time.
+///
+/// Opening a bundle reads just its table of contents; each component is
+/// checksummed and decoded when it is loaded, so a program pays only for
+/// the parts it uses.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{BitVector, IntVector, IntVecMut};
+/// use succinct::persist::{BundleWriter, IndexBundle};
+///
+/// let mut lengths = IntVector::<u32>::new(12);
+/// lengths.push(1000);
+/// let marks: BitVector<u64> = BitVector::with_fill(10, true);
+///
+/// let mut writer = BundleWriter::new(Vec::new()).unwrap();
+/// writer.add("lengths", &lengths).unwrap();
+/// writer.add("marks", &marks).unwrap();
+/// let bytes = writer.finish().unwrap();
+///
+/// let bundle = IndexBundle::open(&bytes).unwrap();
+/// assert_eq!("IntVector<u32>", bundle.info("lengths").unwrap().tag());
+/// assert_eq!(lengths, bundle.load::<IntVector<u32>>("lengths").unwrap());
+/// assert!(bundle.load::<IntVector<u32>>("marks").is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct IndexBundle<'a> {
+    container: Container<'a>,
+    components: Vec<ComponentInfo>,
+}
+
+impl<'a> IndexBundle<'a> {
+    /// Opens the bundle in `bytes`, checking its table of contents.
+    ///
+    /// Fails with `ErrorKind::InvalidData` if the file is corrupt or has
+    /// no table of contents.
+    pub fn open(bytes: &'a [u8]) -> io::Result<Self> {
+        let container = Container::open(bytes, Verify::Skip)?;
+        if container.verify(TOC).is_err() {
+            return corrupt("bad table of contents");
+        }
+
+        let toc = container.bytes(TOC).unwrap_or(&[]);
+        let components = read_toc(toc).or_else(|_| corrupt("malformed table of contents"))?;
+        if components.iter().any(|component| container.find(&component.name).is_none()) {
+            return corrupt("component missing");
+        }
+        Ok(IndexBundle { container, components })
+    }
+
+    /// The components, in the order they were added.
+    pub fn components(&self) -> &[ComponentInfo] {
+        &self.components
+    }
+
+    /// The entry for the component named `name`, if any.
+    pub fn info(&self, name: &str) -> Option<&ComponentInfo> {
+        self.components.iter().find(|component| component.name == name)
+    }
+
+    /// Is there a component named `name`?
+    pub fn contains(&self, name: &str) -> bool {
+        self.info(name).is_some()
+    }
+
+    /// Checksums and decodes the component named `name`.
+    ///
+    /// Fails with `ErrorKind::NotFound` if there is no such component, or
+    /// `ErrorKind::InvalidData` if it is corrupt, isn’t a `T`, or has a
+    /// version `T` can’t read.
+    pub fn load<T: Component>(&self, name: &str) -> io::Result<T> {
+        let info = match self.info(name) {
+            Some(info) => info,
+            None => return Err(Error::new(ErrorKind::NotFound,
+                                          format!("IndexBundle: no component `{}`", name))),
+        };
+        if info.tag != T::tag() {
+            return corrupt(&format!("component `{}` is a `{}`, not a `{}`",
+                                    name, info.tag, T::tag()));
+        }
+
+        self.container.verify(name)?;
+        T::read_version(self.container.bytes(name).unwrap_or(&[]), info.version)
+    }
+}
+
+fn write_string(sink: &mut Vec<u8>, string: &str) -> io::Result<()> {
+    sink.write_u16::<LittleEndian>(string.len() as u16)?;
+    sink.write_all(string.as_bytes())
+}
+
+fn read_string(source: &mut &[u8]) -> io::Result<String> {
+    let mut bytes = vec![0; source.read_u16::<LittleEndian>()? as usize];
+    source.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+}
+
+fn read_toc(mut toc: &[u8]) -> io::Result<Vec<ComponentInfo>> {
+    let count = toc.read_u32::<LittleEndian>()?;
+    let mut components = Vec::new();
+    for _ in 0 .. count {
+        let name = read_string(&mut toc)?;
+        let tag = read_string(&mut toc)?;
+        let version = toc.read_u32::<LittleEndian>()?;
+        components.push(ComponentInfo { name, tag, version });
+    }
+    super::expect_end("IndexBundle", toc)?;
+    Ok(components)
+}
+
+fn corrupt<A>(what: &str) -> io::Result<A> {
+    Err(Error::new(ErrorKind::InvalidData,
+                   format!("IndexBundle: could not open: {}", what)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::{BitVec, BitVector};
+    use int_vec::IntVector;
+
+    // A type whose encoding went from one byte to two.
+    #[derive(Debug, PartialEq)]
+    struct Counter(u16);
+
+    impl Persist for Counter {
+        fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+            sink.write_u16::<LittleEndian>(self.0)
+        }
+
+        fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+            bytes.read_u16::<LittleEndian>().map(Counter)
+        }
+    }
+
+    impl Component for Counter {
+        const VERSION: u32 = 2;
+
+        fn tag() -> String { "Counter".to_owned() }
+
+        fn read_version(bytes: &[u8], version: u32) -> io::Result<Self> {
+            match version {
+                1 => Ok(Counter(bytes[0] as u16)),
+                _ => Self::read_from(bytes),
+            }
+        }
+    }
+
+    #[test]
+    fn components() {
+        let mut writer = BundleWriter::new(Vec::new()).unwrap();
+        writer.add("count", &Counter(300)).unwrap();
+        writer.add("bits", &BitVector::<u8>::with_fill(9, true)).unwrap();
+        assert_eq!(ErrorKind::InvalidInput,
+                   writer.add(TOC, &Counter(0)).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidInput,
+                   writer.add("count", &Counter(0)).unwrap_err().kind());
+        let bytes = writer.finish().unwrap();
+
+        let bundle = IndexBundle::open(&bytes).unwrap();
+        let names: Vec<&str> = bundle.components().iter().map(ComponentInfo::name).collect();
+        assert_eq!(vec!["count", "bits"], names);
+        assert_eq!(2, bundle.info("count").unwrap().version());
+        assert!(!bundle.contains(TOC));
+        assert_eq!(Counter(300), bundle.load("count").unwrap());
+        assert_eq!(ErrorKind::NotFound, bundle.load::<Counter>("other").unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidData,
+                   bundle.load::<IntVector<u8>>("bits").unwrap_err().kind());
+    }
+
+    #[test]
+    fn old_versions() {
+        // What version 1 of `Counter` wrote.
+        let mut toc = Vec::new();
+        toc.write_u32::<LittleEndian>(1).unwrap();
+        write_string(&mut toc, "count").unwrap();
+        write_string(&mut toc, "Counter").unwrap();
+        toc.write_u32::<LittleEndian>(1).unwrap();
+
+        let mut writer = ContainerWriter::new(Vec::new()).unwrap();
+        writer.add_bytes("count", &[7]).unwrap();
+        writer.add_bytes(TOC, &toc).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let bundle = IndexBundle::open(&bytes).unwrap();
+        assert_eq!(Counter(7), bundle.load("count").unwrap());
+    }
+
+    #[test]
+    fn structures_round_trip() {
+        use dump::Snapshot;
+        use rank::{BitRankSupport, Rank9};
+        use select::{Select1Support, Select9};
+        use text::{FmIndex, Sampling};
+        use wavelet::{QuadWaveletMatrix, RunLengthWaveletMatrix, WaveletMatrix};
+
+        let text = b"she sells sea shells by the sea shore";
+        let values: Vec<u64> = (0 .. 3000).map(|i| i / 40 * 37 % 300).collect();
+        let bits = BitVector::<u64>::from_bits((0 .. 5000).map(|i| i % 7 == 0 || i > 4000));
+
+        let mut writer = BundleWriter::new(Vec::new()).unwrap();
+        writer.add("fm", &FmIndex::new(&text[..], 4)).unwrap();
+        writer.add("count", &FmIndex::count_only(&text[..])).unwrap();
+        writer.add("wm", &WaveletMatrix::new(values.iter().cloned())).unwrap();
+        writer.add("quad", &QuadWaveletMatrix::new(values.iter().cloned())).unwrap();
+        writer.add("runs", &RunLengthWaveletMatrix::new(values.iter().cloned())).unwrap();
+        writer.add("narrow", &Rank9::try_new_narrow(bits.clone()).unwrap()).unwrap();
+        writer.add("select", &Select9::with_sample_rate(Rank9::new(bits.clone()), 100)).unwrap();
+        let bytes = writer.finish().unwrap();
+        let bundle = IndexBundle::open(&bytes).unwrap();
+
+        let fm: FmIndex = bundle.load("fm").unwrap();
+        let mut found = fm.locate(b"sea");
+        found.sort();
+        assert_eq!(vec![10, 28], found);
+        assert_eq!(text.to_vec(), fm.extract(0 .. fm.len()));
+        assert_eq!(Some(Sampling::Text(4)), fm.sampling());
+        let count: FmIndex = bundle.load("count").unwrap();
+        assert!(count.is_count_only());
+        assert_eq!(3, count.count(b"sh"));
+        assert_eq!("FmIndex<u8>", bundle.info("fm").unwrap().tag());
+        assert!(bundle.load::<FmIndex<u16>>("fm").is_err());
+
+        let wm: WaveletMatrix = bundle.load("wm").unwrap();
+        let quad: QuadWaveletMatrix = bundle.load("quad").unwrap();
+        let runs: RunLengthWaveletMatrix = bundle.load("runs").unwrap();
+        assert_eq!(values, wm.dump_iter().collect::<Vec<_>>());
+        assert_eq!(values, quad.dump_iter().collect::<Vec<_>>());
+        assert_eq!(values, runs.dump_iter().collect::<Vec<_>>());
+        assert_eq!(75, runs.runs());
+        assert_eq!(wm.count_less(2000, 150), quad.count_less(2000, 150));
+
+        let narrow: Rank9<BitVector<u64>> = bundle.load("narrow").unwrap();
+        let select: Select9<BitVector<u64>> = bundle.load("select").unwrap();
+        assert!(narrow.is_narrow());
+        assert_eq!(100, select.sample_rate());
+        let mut ones = 0;
+        for i in 0 .. bits.bit_len() {
+            if bits.get_bit(i) {
+                assert_eq!(Some(i), select.select1(ones));
+                ones += 1;
+            }
+            assert_eq!(ones, narrow.rank1(i));
+        }
+        assert!(bundle.load::<Rank9<BitVector<u64>>>("select").is_err());
+    }
+
+    #[test]
+    fn truncated_structures() {
+        use text::FmIndex;
+
+        let mut bytes = Vec::new();
+        FmIndex::new(&b"abracadabra"[..], 3).write_to(&mut bytes).unwrap();
+        assert_eq!(11, FmIndex::<u8>::read_from(&bytes).unwrap().len());
+        for len in 0 .. bytes.len() {
+            assert!(FmIndex::<u8>::read_from(&bytes[.. len]).is_err());
+        }
+    }
+
+    #[test]
+    fn corruption_is_lazy() {
+        let mut writer = BundleWriter::new(Vec::new()).unwrap();
+        writer.add("a", &Counter(1)).unwrap();
+        writer.add("b", &Counter(2)).unwrap();
+        let mut bytes = writer.finish().unwrap();
+
+        let offset = Container::open(&bytes, Verify::Skip).unwrap().find("b").unwrap().offset();
+        bytes[offset as usize] ^= 4;
+        let bundle = IndexBundle::open(&bytes).unwrap();
+        assert_eq!(Counter(1), bundle.load("a").unwrap());
+        assert_eq!(ErrorKind::InvalidData, bundle.load::<Counter>("b").unwrap_err().kind());
+
+        let plain = ContainerWriter::new(Vec::new()).unwrap().finish().unwrap();
+        assert!(IndexBundle::open(&plain).is_err());
+    }
+}
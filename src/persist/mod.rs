@@ -0,0 +1,567 @@
+//! A checksummed binary format for saving structures to disk.
+//!
+//! A file is a header, a run of named sections, a manifest saying where
+//! each section is and what its CRC32C is, and a fixed-size footer
+//! locating the manifest, which has its own checksum. Opening a file
+//! always checks the header, footer and manifest; with
+//! [`Verify::Full`](enum.Verify.html) it checks every section too, and
+//! with `Verify::Skip` it leaves them to
+//! [`Container::verify`](struct.Container.html#method.verify), so that a
+//! large file opens in constant time. Either way a corrupted file is an
+//! `InvalidData` error rather than wrong answers later on.
+//!
+//! Sections hold anything that implements [`Persist`](trait.Persist.html):
+//!
+//! ```
+//! use succinct::{BitVector, BitVecMut, IntVector, IntVecMut};
+//! use succinct::persist::{Container, ContainerWriter, Verify};
+//!
+//! let mut v = IntVector::<u32>::new(5);
+//! v.push(3);
+//! v.push(17);
+//! let bits: BitVector<u64> = BitVector::with_fill(70, true);
+//!
+//! let mut writer = ContainerWriter::new(Vec::new()).unwrap();
+//! writer.add("values", &v).unwrap();
+//! writer.add("bits", &bits).unwrap();
+//! let bytes = writer.finish().unwrap();
+//!
+//! let container = Container::open(&bytes, Verify::Full).unwrap();
+//! assert_eq!(v, container.load::<IntVector<u32>>("values").unwrap());
+//! assert_eq!(bits, container.load::<BitVector<u64>>("bits").unwrap());
+//!
+//! let mut corrupt = bytes.clone();
+//! corrupt[20] ^= 1;
+//! assert!(Container::open(&corrupt, Verify::Full).is_err());
+//! ```
+//!
+//! An [`IndexBundle`](struct.IndexBundle.html) builds on this to store
+//! several typed [`Component`](trait.Component.html)s in one file.
+//!
+//! A `Container` borrows the file rather than copying it, so opening one
+//! costs nothing beyond the checks; `load` then decodes a section into
+//! an owned value. To skip that, open a section’s
+//! [`bytes`](struct.Container.html#method.bytes) as a
+//! [`ByteBitVector`](../bit_vec/struct.ByteBitVector.html) or
+//! [`ByteIntVector`](../int_vec/struct.ByteIntVector.html), which query
+//! it in place, at any alignment, and build rank and select support
+//! over those.
+//!
+//! With the `rkyv` feature, `BitVector`, `IntVector`, `EliasFano` and
+//! `Rank9` can be archived with [`rkyv`](https://docs.rs/rkyv) instead,
+//! and queried in place in an aligned buffer through their archived
+//! forms, including `Rank9`’s directory, which then isn’t built again.
+
+use std::io::{self, Error, ErrorKind, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+mod bundle;
+pub use self::bundle::*;
+
+/// The first bytes of every file.
+const MAGIC: &[u8; 8] = b"SUCCINCT";
+
+/// The last bytes of every file.
+const FOOTER_MAGIC: &[u8; 4] = b"SCFT";
+
+/// The format version written and understood.
+const VERSION: u32 = 1;
+
+const HEADER_BYTES: usize = 12;
+
+// The manifest’s offset and length, its checksum, and the magic.
+const FOOTER_BYTES: usize = 24;
+
+/// Types that can be written to and loaded from a section.
+pub trait Persist: Sized {
+    /// Writes the receiver in binary.
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()>;
+
+    /// Loads a value from exactly the bytes `write_to` wrote.
+    ///
+    /// Fails with `ErrorKind::InvalidData` if `bytes` is malformed, or
+    /// `ErrorKind::UnexpectedEof` if it is cut short.
+    fn read_from(bytes: &[u8]) -> io::Result<Self>;
+}
+
+/// How much of a file to check when opening it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Verify {
+    /// Check every section’s checksum.
+    #[default]
+    Full,
+    /// Check only the manifest, leaving sections to be checked on demand.
+    Skip,
+}
+
+/// Where a section is in a file, and its checksum.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Section {
+    name: String,
+    offset: u64,
+    len: u64,
+    checksum: u32,
+}
+
+impl Section {
+    /// The section’s name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The offset of the section from the start of the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The length of the section in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Is the section empty?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The CRC32C of the section’s bytes.
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+}
+
+/// Writes a file section by section.
+#[derive(Debug)]
+pub struct ContainerWriter<W: Write> {
+    sink: W,
+    offset: u64,
+    sections: Vec<Section>,
+}
+
+impl<W: Write> ContainerWriter<W> {
+    /// Starts a file on `sink`, writing the header.
+    pub fn new(mut sink: W) -> io::Result<Self> {
+        sink.write_all(MAGIC)?;
+        sink.write_u32::<LittleEndian>(VERSION)?;
+        Ok(ContainerWriter {
+            sink,
+            offset: HEADER_BYTES as u64,
+            sections: Vec::new(),
+        })
+    }
+
+    /// Appends a section of raw bytes.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if the name is already taken
+    /// or longer than 65,535 bytes.
+    pub fn add_bytes(&mut self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        if name.len() > u16::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "ContainerWriter: section name too long"));
+        }
+        if self.sections.iter().any(|section| section.name == name) {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("ContainerWriter: duplicate section `{}`",
+                                          name)));
+        }
+
+        self.sink.write_all(bytes)?;
+        self.sections.push(Section {
+            name: name.to_owned(),
+            offset: self.offset,
+            len: bytes.len() as u64,
+            checksum: crc32c(bytes),
+        });
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Appends a section holding `value`.
+    pub fn add<T: Persist>(&mut self, name: &str, value: &T) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        value.write_to(&mut bytes)?;
+        self.add_bytes(name, &bytes)
+    }
+
+    /// The sections written so far.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Writes the manifest and footer, returning the sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut manifest = Vec::new();
+        manifest.write_u32::<LittleEndian>(self.sections.len() as u32)?;
+        for section in &self.sections {
+            manifest.write_u16::<LittleEndian>(section.name.len() as u16)?;
+            manifest.write_all(section.name.as_bytes())?;
+            manifest.write_u64::<LittleEndian>(section.offset)?;
+            manifest.write_u64::<LittleEndian>(section.len)?;
+            manifest.write_u32::<LittleEndian>(section.checksum)?;
+        }
+
+        self.sink.write_all(&manifest)?;
+        self.sink.write_u64::<LittleEndian>(self.offset)?;
+        self.sink.write_u64::<LittleEndian>(manifest.len() as u64)?;
+        self.sink.write_u32::<LittleEndian>(crc32c(&manifest))?;
+        self.sink.write_all(FOOTER_MAGIC)?;
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+}
+
+/// A file opened for reading, borrowing its bytes.
+#[derive(Clone, Debug)]
+pub struct Container<'a> {
+    bytes: &'a [u8],
+    sections: Vec<Section>,
+}
+
+impl<'a> Container<'a> {
+    /// Opens the file in `bytes`, checking as much as `verify` asks.
+    ///
+    /// Fails with `ErrorKind::InvalidData` if the file is truncated, has
+    /// the wrong magic or version, or fails a checksum.
+    pub fn open(bytes: &'a [u8], verify: Verify) -> io::Result<Self> {
+        if bytes.len() < HEADER_BYTES + FOOTER_BYTES || &bytes[.. 8] != MAGIC {
+            return corrupt("not a succinct file");
+        }
+        if (&bytes[8 .. HEADER_BYTES]).read_u32::<LittleEndian>()? != VERSION {
+            return corrupt("unknown format version");
+        }
+
+        let mut footer = &bytes[bytes.len() - FOOTER_BYTES ..];
+        let manifest_offset = footer.read_u64::<LittleEndian>()?;
+        let manifest_len = footer.read_u64::<LittleEndian>()?;
+        let manifest_checksum = footer.read_u32::<LittleEndian>()?;
+        if footer != FOOTER_MAGIC {
+            return corrupt("missing footer");
+        }
+
+        let body_end = (bytes.len() - FOOTER_BYTES) as u64;
+        if manifest_offset < HEADER_BYTES as u64
+            || manifest_offset.checked_add(manifest_len) != Some(body_end) {
+            return corrupt("manifest out of bounds");
+        }
+        let manifest = &bytes[manifest_offset as usize .. body_end as usize];
+        if crc32c(manifest) != manifest_checksum {
+            return corrupt("manifest checksum mismatch");
+        }
+
+        let sections = read_manifest(manifest, manifest_offset)
+            .or_else(|_| corrupt("malformed manifest"))?;
+        let result = Container { bytes, sections };
+
+        if verify == Verify::Full {
+            for section in &result.sections {
+                result.verify_section(section)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// The sections, in the order they were written.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// The section named `name`, if any.
+    pub fn find(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// The bytes of the section named `name`, unchecked.
+    pub fn bytes(&self, name: &str) -> Option<&'a [u8]> {
+        self.find(name).map(|section| self.slice(section))
+    }
+
+    /// Checks the checksum of the section named `name`.
+    ///
+    /// Fails with `ErrorKind::NotFound` if there is no such section, or
+    /// `ErrorKind::InvalidData` if it is corrupt.
+    pub fn verify(&self, name: &str) -> io::Result<()> {
+        match self.find(name) {
+            Some(section) => self.verify_section(section),
+            None => not_found(name),
+        }
+    }
+
+    /// Loads the section named `name`.
+    ///
+    /// Fails with `ErrorKind::NotFound` if there is no such section, or
+    /// `ErrorKind::InvalidData` if it is malformed. It isn’t checksummed
+    /// again; call [`verify`](#method.verify) first if the file was
+    /// opened with `Verify::Skip`.
+    pub fn load<T: Persist>(&self, name: &str) -> io::Result<T> {
+        match self.bytes(name) {
+            Some(bytes) => T::read_from(bytes),
+            None => not_found(name),
+        }
+    }
+
+    fn slice(&self, section: &Section) -> &'a [u8] {
+        &self.bytes[section.offset as usize .. (section.offset + section.len) as usize]
+    }
+
+    fn verify_section(&self, section: &Section) -> io::Result<()> {
+        if crc32c(self.slice(section)) == section.checksum {
+            Ok(())
+        } else {
+            corrupt(&format!("checksum mismatch in section `{}`", section.name))
+        }
+    }
+}
+
+// Parses the manifest, which starts at `end` in the file; every section
+// must lie between the header and it.
+fn read_manifest(mut manifest: &[u8], end: u64) -> io::Result<Vec<Section>> {
+    let count = manifest.read_u32::<LittleEndian>()?;
+    let mut sections = Vec::new();
+    for _ in 0 .. count {
+        let name_len = manifest.read_u16::<LittleEndian>()? as usize;
+        let mut name = vec![0; name_len];
+        manifest.read_exact(&mut name)?;
+        let name = String::from_utf8(name)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+
+        let offset = manifest.read_u64::<LittleEndian>()?;
+        let len = manifest.read_u64::<LittleEndian>()?;
+        let checksum = manifest.read_u32::<LittleEndian>()?;
+        if offset < HEADER_BYTES as u64 || offset.checked_add(len).map_or(true, |e| e > end) {
+            return corrupt("section out of bounds");
+        }
+        sections.push(Section { name, offset, len, checksum });
+    }
+
+    if manifest.is_empty() { Ok(sections) } else { corrupt("trailing manifest bytes") }
+}
+
+fn corrupt<A>(what: &str) -> io::Result<A> {
+    Err(Error::new(ErrorKind::InvalidData,
+                   format!("Container: could not open: {}", what)))
+}
+
+fn not_found<A>(name: &str) -> io::Result<A> {
+    Err(Error::new(ErrorKind::NotFound,
+                   format!("Container: no section `{}`", name)))
+}
+
+/// Fails with `ErrorKind::InvalidData` unless `bytes` has been read to
+/// the end, for `Persist::read_from` implementations.
+pub fn expect_end(who: &str, bytes: &[u8]) -> io::Result<()> {
+    if bytes.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::InvalidData,
+                       format!("{}: could not load: trailing bytes", who)))
+    }
+}
+
+/// Writes `value` prefixed with its length, so that several can follow
+/// one another in a `Persist::write_to` implementation.
+pub fn write_part<T: Persist, W: Write>(value: &T, sink: &mut W) -> io::Result<()> {
+    let mut bytes = Vec::new();
+    value.write_to(&mut bytes)?;
+    sink.write_u64::<LittleEndian>(bytes.len() as u64)?;
+    sink.write_all(&bytes)
+}
+
+/// Reads a value written by [`write_part`](fn.write_part.html) from the
+/// front of `bytes`, advancing past it.
+pub fn read_part<T: Persist>(bytes: &mut &[u8]) -> io::Result<T> {
+    let len = bytes.read_u64::<LittleEndian>()?;
+    if len > bytes.len() as u64 {
+        return Err(Error::new(ErrorKind::UnexpectedEof,
+                              "read_part: could not load: part cut short"));
+    }
+    let (part, rest) = bytes.split_at(len as usize);
+    *bytes = rest;
+    T::read_from(part)
+}
+
+/// The length as a `u64`, then the values.
+impl Persist for Vec<u64> {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u64::<LittleEndian>(self.len() as u64)?;
+        for &value in self {
+            sink.write_u64::<LittleEndian>(value)?;
+        }
+        Ok(())
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let len = bytes.read_u64::<LittleEndian>()?;
+        if len.checked_mul(8) != Some(bytes.len() as u64) {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  "Vec<u64>::read_from: could not load: wrong length"));
+        }
+        (0 .. len).map(|_| bytes.read_u64::<LittleEndian>()).collect()
+    }
+}
+
+/// The CRC32C (Castagnoli) checksum of `bytes`, as used by iSCSI, ext4
+/// and most storage formats.
+///
+/// ```
+/// assert_eq!(0xE306_9283, succinct::persist::crc32c(b"123456789"));
+/// ```
+pub fn crc32c(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let low = crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        crc = CRC_TABLES[7][low as usize & 0xFF]
+            ^ CRC_TABLES[6][(low >> 8) as usize & 0xFF]
+            ^ CRC_TABLES[5][(low >> 16) as usize & 0xFF]
+            ^ CRC_TABLES[4][(low >> 24) as usize]
+            ^ CRC_TABLES[3][chunk[4] as usize]
+            ^ CRC_TABLES[2][chunk[5] as usize]
+            ^ CRC_TABLES[1][chunk[6] as usize]
+            ^ CRC_TABLES[0][chunk[7] as usize];
+    }
+    for &byte in chunks.remainder() {
+        crc = CRC_TABLES[0][(crc as u8 ^ byte) as usize] ^ crc >> 8;
+    }
+    !crc
+}
+
+// Slicing-by-8 tables for the reflected Castagnoli polynomial: table k
+// advances a byte followed by k zero bytes.
+const CRC_TABLES: [[u32; 256]; 8] = crc_tables();
+
+const fn crc_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {crc >> 1 ^ 0x82F6_3B78} else {crc >> 1};
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let previous = tables[k - 1][i];
+            tables[k][i] = previous >> 8 ^ tables[0][previous as usize & 0xFF];
+            i += 1;
+        }
+        k += 1;
+    }
+    tables
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::{BitVecPush, BitVector};
+    use int_vec::IntVector;
+    use quickcheck::quickcheck;
+
+    fn sample() -> Vec<u8> {
+        let mut v = IntVector::<u16>::new(11);
+        for i in 0 .. 100 {
+            v.push(i * 17 % 2048);
+        }
+        let mut writer = ContainerWriter::new(Vec::new()).unwrap();
+        writer.add("values", &v).unwrap();
+        writer.add_bytes("empty", &[]).unwrap();
+        writer.add_bytes("raw", b"hello").unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn manifest() {
+        let bytes = sample();
+        let container = Container::open(&bytes, Verify::Full).unwrap();
+        let names: Vec<&str> = container.sections().iter().map(Section::name).collect();
+        assert_eq!(vec!["values", "empty", "raw"], names);
+        assert_eq!(Some(&b"hello"[..]), container.bytes("raw"));
+        assert!(container.find("empty").unwrap().is_empty());
+        assert_eq!(ErrorKind::NotFound, container.verify("missing").unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound,
+                   container.load::<BitVector>("missing").unwrap_err().kind());
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let mut writer = ContainerWriter::new(Vec::new()).unwrap();
+        writer.add_bytes("a", b"x").unwrap();
+        assert_eq!(ErrorKind::InvalidInput,
+                   writer.add_bytes("a", b"y").unwrap_err().kind());
+    }
+
+    #[test]
+    fn skip_defers_section_checks() {
+        let mut bytes = sample();
+        let raw = Container::open(&bytes, Verify::Skip).unwrap().find("raw").unwrap().offset();
+        bytes[raw as usize] ^= 0x20;
+
+        assert!(Container::open(&bytes, Verify::Full).is_err());
+        let container = Container::open(&bytes, Verify::Skip).unwrap();
+        assert!(container.verify("values").is_ok());
+        assert_eq!(ErrorKind::InvalidData, container.verify("raw").unwrap_err().kind());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = sample();
+        for len in 0 .. bytes.len() {
+            assert!(Container::open(&bytes[.. len], Verify::Skip).is_err());
+        }
+    }
+
+    #[test]
+    fn qc_detects_flips() {
+        fn prop(position: usize, bit: u8) -> bool {
+            let mut bytes = sample();
+            let position = position % bytes.len();
+            bytes[position] ^= 1 << (bit % 8);
+            Container::open(&bytes, Verify::Full).is_err()
+        }
+
+        quickcheck(prop as fn(usize, u8) -> bool);
+    }
+
+    #[test]
+    fn crc_matches_bytewise() {
+        fn prop(bytes: Vec<u8>) -> bool {
+            let mut crc = !0u32;
+            for &byte in &bytes {
+                crc ^= byte as u32;
+                for _ in 0 .. 8 {
+                    crc = if crc & 1 == 1 {crc >> 1 ^ 0x82F6_3B78} else {crc >> 1};
+                }
+            }
+            crc32c(&bytes) == !crc
+        }
+
+        quickcheck(prop as fn(Vec<u8>) -> bool);
+        assert_eq!(0, crc32c(b""));
+    }
+
+    #[test]
+    fn vectors_round_trip() {
+        let mut bits = BitVector::<u8>::new();
+        for i in 0 .. 21 {
+            bits.push_bit(i % 3 == 0);
+        }
+        let mut bytes = Vec::new();
+        bits.write_to(&mut bytes).unwrap();
+        assert_eq!(bits, BitVector::read_from(&bytes).unwrap());
+        assert!(BitVector::<u8>::read_from(&bytes[.. bytes.len() - 1]).is_err());
+
+        let v = IntVector::<u64>::with_fill(33, 7, 5);
+        let mut bytes = Vec::new();
+        v.write_to(&mut bytes).unwrap();
+        assert_eq!(v, IntVector::read_from(&bytes).unwrap());
+        bytes.push(0);
+        assert!(IntVector::<u64>::read_from(&bytes).is_err());
+    }
+}
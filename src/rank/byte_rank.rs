@@ -0,0 +1,236 @@
+use rank::RankSupport;
+use space_usage::SpaceUsage;
+
+/// The bytes counted by each superblock entry.
+const SUPERBLOCK: u64 = 1 << 16;
+
+/// The bytes counted by each block entry.
+const BLOCK: u64 = 1 << 10;
+
+/// Rank queries over a byte sequence, for any of the 256 byte values.
+///
+/// For every byte value, a directory records how often it occurs before
+/// each 64 KiB superblock, in a `u64`, and before each 1 KiB block,
+/// relative to its superblock, in a `u16`. A query adds the two and
+/// counts the rest of its block eight bytes at a time, so it reads two
+/// counters and at most 128 words, with no dependence on the alphabet,
+/// where a wavelet matrix takes eight dependent rank steps. That is the
+/// hot path of a byte-alphabet FM-index. The directory costs about half
+/// a byte per byte, on top of the bytes themselves.
+///
+/// The counters are laid out as [`ByteRankLayout`](enum.ByteRankLayout.html)
+/// says, chosen with [`with_layout`](#method.with_layout).
+///
+/// # Example
+///
+/// ```
+/// use succinct::rank::{ByteRank, RankSupport};
+///
+/// let ranks = ByteRank::new(b"abracadabra".to_vec());
+/// assert_eq!(5, ranks.rank_before(11, b'a'));
+/// assert_eq!(1, ranks.rank_before(2, b'b'));
+/// assert_eq!(2, ranks.rank(8, b'b'));   // positions 0 through 8
+/// assert_eq!(0, ranks.rank_before(11, b'z'));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ByteRank {
+    bytes: Vec<u8>,
+    layout: ByteRankLayout,
+    // The count of each byte before each superblock.
+    superblocks: Vec<u64>,
+    // The count of each byte from its superblock’s start to each block.
+    blocks: Vec<u16>,
+}
+
+/// How a [`ByteRank`](struct.ByteRank.html) arranges its counters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ByteRankLayout {
+    /// Each byte value’s counters are contiguous, across all the blocks.
+    /// Queries for one value at nearby positions, as in a backward
+    /// search, share cache lines. This is the default.
+    #[default]
+    ByValue,
+    /// Each block’s 256 counters are contiguous. Queries for different
+    /// values at one position, such as computing every value’s rank at
+    /// a row, share cache lines, and a query costs one or two misses
+    /// however the values vary.
+    Interleaved,
+}
+
+// Where the counter of `byte` for `entry` goes, out of `entries` per
+// byte value.
+fn counter(layout: ByteRankLayout, entries: usize, byte: usize, entry: usize) -> usize {
+    match layout {
+        ByteRankLayout::ByValue => byte * entries + entry,
+        ByteRankLayout::Interleaved => entry * 256 + byte,
+    }
+}
+
+impl ByteRank {
+    /// Creates a rank structure over `bytes`, with the default
+    /// layout.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self::with_layout(bytes, ByteRankLayout::default())
+    }
+
+    /// Creates a rank structure over `bytes` with its counters laid out
+    /// as `layout` says.
+    pub fn with_layout(bytes: Vec<u8>, layout: ByteRankLayout) -> Self {
+        let len = bytes.len() as u64;
+        let superblock_count = (len / SUPERBLOCK + 1) as usize;
+        let block_count = (len / BLOCK + 1) as usize;
+
+        let mut superblocks = vec![0; 256 * superblock_count];
+        let mut blocks = vec![0; 256 * block_count];
+        let mut counts = [0u64; 256];
+        let mut base = [0u64; 256];
+
+        for (block, chunk) in bytes.chunks(BLOCK as usize).chain(Some(&[][..])).enumerate() {
+            if block as u64 * BLOCK > len { break; }
+
+            if block as u64 * BLOCK % SUPERBLOCK == 0 {
+                let superblock = (block as u64 * BLOCK / SUPERBLOCK) as usize;
+                base = counts;
+                for byte in 0 .. 256 {
+                    superblocks[counter(layout, superblock_count, byte, superblock)] = counts[byte];
+                }
+            }
+            for byte in 0 .. 256 {
+                blocks[counter(layout, block_count, byte, block)] = (counts[byte] - base[byte]) as u16;
+            }
+            for &byte in chunk {
+                counts[byte as usize] += 1;
+            }
+        }
+
+        ByteRank { bytes, layout, superblocks, blocks }
+    }
+
+    /// The number of bytes.
+    pub fn len(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    /// Are there no bytes?
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// How the counters are laid out.
+    pub fn layout(&self) -> ByteRankLayout {
+        self.layout
+    }
+
+    /// The underlying bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The number of occurrences of `byte` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn rank_before(&self, end: u64, byte: u8) -> u64 {
+        assert!(end <= self.len(), "ByteRank::rank_before: out of bounds");
+
+        let superblock_count = self.superblocks.len() / 256;
+        let block_count = self.blocks.len() / 256;
+        let byte_index = byte as usize;
+        let block = end / BLOCK;
+
+        let superblock = (end / SUPERBLOCK) as usize;
+        self.superblocks[counter(self.layout, superblock_count, byte_index, superblock)]
+            + self.blocks[counter(self.layout, block_count, byte_index, block as usize)] as u64
+            + count_byte(&self.bytes[(block * BLOCK) as usize .. end as usize], byte)
+    }
+}
+
+// The occurrences of `byte` in `bytes`, eight at a time.
+fn count_byte(bytes: &[u8], byte: u8) -> u64 {
+    const LOW7: u64 = 0x7F7F_7F7F_7F7F_7F7F;
+    let pattern = byte as u64 * 0x0101_0101_0101_0101;
+
+    let mut words = bytes.chunks_exact(8);
+    let mut count = 0;
+    for word in &mut words {
+        let mut buffer = [0; 8];
+        buffer.copy_from_slice(word);
+        // The high bit of each byte of `zeros` says it matched.
+        let x = u64::from_le_bytes(buffer) ^ pattern;
+        let zeros = !(((x & LOW7) + LOW7) | x | LOW7);
+        count += zeros.count_ones() as u64;
+    }
+    count + words.remainder().iter().filter(|&&other| other == byte).count() as u64
+}
+
+impl RankSupport for ByteRank {
+    type Over = u8;
+
+    fn rank(&self, position: u64, value: u8) -> u64 {
+        assert!(position < self.len(), "ByteRank::rank: out of bounds");
+        self.rank_before(position + 1, value)
+    }
+
+    fn limit(&self) -> u64 {
+        self.len()
+    }
+}
+
+impl SpaceUsage for ByteRank {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.bytes.heap_bytes() + self.superblocks.heap_bytes() + self.blocks.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let ranks = ByteRank::new(vec![]);
+        assert!(ranks.is_empty());
+        assert_eq!(0, ranks.rank_before(0, 0));
+    }
+
+    #[test]
+    fn across_superblocks() {
+        let bytes: Vec<u8> = (0 .. 3 * SUPERBLOCK + 517).map(|i| (i * i % 251) as u8).collect();
+        check_all(&bytes, &ByteRank::with_layout(bytes.clone(), ByteRankLayout::ByValue));
+        check_all(&bytes, &ByteRank::with_layout(bytes.clone(), ByteRankLayout::Interleaved));
+    }
+
+    fn check_all(bytes: &[u8], ranks: &ByteRank) {
+        let mut counts = [0u64; 256];
+        for (i, &byte) in bytes.iter().enumerate() {
+            if i % 333 == 0 || i as u64 % BLOCK == 0 {
+                for probe in [0u8, 1, 4, 9, 250, 255].iter() {
+                    assert_eq!(counts[*probe as usize], ranks.rank_before(i as u64, *probe));
+                }
+            }
+            counts[byte as usize] += 1;
+        }
+        assert_eq!(counts[4], ranks.rank_before(bytes.len() as u64, 4));
+        assert!(ranks.heap_bytes() < 2 * bytes.len());
+    }
+
+    #[test]
+    fn qc_matches_count() {
+        fn prop(bytes: Vec<u8>, probes: Vec<(u16, u8)>) -> bool {
+            let ranks = ByteRank::new(bytes.clone());
+            let interleaved = ByteRank::with_layout(bytes.clone(), ByteRankLayout::Interleaved);
+            probes.into_iter().all(|(end, byte)| {
+                let end = end as usize % (bytes.len() + 1);
+                let expected = bytes[.. end].iter().filter(|&&other| other == byte).count();
+                ranks.rank_before(end as u64, byte) == expected as u64
+                    && interleaved.rank_before(end as u64, byte) == expected as u64
+            })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<(u16, u8)>) -> bool);
+    }
+}
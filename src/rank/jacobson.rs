@@ -1,43 +1,75 @@
+use std::fmt;
+
 use num_traits::PrimInt;
 
-use bit_vec::BitVec;
+use bit_vec::{BitVec, BitVector};
+use error::Error;
 use int_vec::{IntVec, IntVector};
 use space_usage::SpaceUsage;
-use storage::{Address, BlockType};
+use storage::{Address, BlockStore, BlockType};
 
 use super::{RankSupport, BitRankSupport};
 
 /// Jacobson’s rank structure for fast rank queries over a `BitVec`.
 ///
-/// Construct with `JacobsonRank::new`.
-#[derive(Clone, Debug)]
-pub struct JacobsonRank<Store> {
+/// Construct with `JacobsonRank::new`. `Counts` holds the two
+/// directories of packed ranks, and like the bits can be any
+/// [`BlockStore`](../storage/trait.BlockStore.html); construct with
+/// `JacobsonRank::new_in` to pick one.
+#[derive(Clone)]
+pub struct JacobsonRank<Store, Counts = Vec<u64>> {
     bit_store: Store,
     large_block_size: usize,
-    large_block_ranks: IntVector<u64>,
-    small_block_ranks: IntVector<u64>,
+    large_block_ranks: IntVector<u64, Counts>,
+    small_block_ranks: IntVector<u64, Counts>,
 }
 
 impl<Store: BitVec> JacobsonRank<Store> {
     /// Creates a new rank support structure for the given bit vector.
     pub fn new(bits: Store) -> Self {
+        Self::new_in(bits)
+    }
+
+    /// Creates a new rank support structure for the given bit vector, or
+    /// returns an error if the directories can’t be allocated.
+    pub fn try_new(bits: Store) -> Result<Self, Error> {
+        Self::try_new_in(bits)
+    }
+}
+
+impl<Store, Counts> JacobsonRank<Store, Counts>
+    where Store: BitVec,
+          Counts: BlockStore<Block = u64> {
+
+    /// Creates a new rank support structure for the given bit vector,
+    /// keeping the directories in a `Counts`.
+    pub fn new_in(bits: Store) -> Self {
+        Self::try_new_in(bits).unwrap_or_else(|error|
+            panic!("JacobsonRank::new: {}", error))
+    }
+
+    /// Creates a new rank support structure for the given bit vector,
+    /// keeping the directories in a `Counts`, or returns an error if they
+    /// can’t be allocated.
+    pub fn try_new_in(bits: Store) -> Result<Self, Error> {
         let n = bits.bit_len();
         let lg_n = n.ceil_lg();
         let lg2_n = lg_n * lg_n;
 
         let small_block_size  = Store::Block::nbits();
-        let small_per_large   = lg2_n.ceil_div(small_block_size);
+        let small_per_large   = lg2_n.ceil_div(small_block_size).max(1);
         let large_block_size  = small_block_size * small_per_large;
         let large_block_count = n / large_block_size as u64 + 1;
         let small_block_count = n / small_block_size as u64 + 1;
 
-        let large_meta_size   = (n + 1).ceil_lg();
+        let large_meta_size   = n.checked_add(1).ok_or(Error::Overflow)?
+                                 .ceil_lg().max(1);
         let small_meta_size   = (large_block_size + 1).ceil_lg();
 
         let mut large_block_ranks =
-            IntVector::with_capacity(large_meta_size, large_block_count);
+            Self::directory(large_meta_size, large_block_count)?;
         let mut small_block_ranks =
-            IntVector::with_capacity(small_meta_size, small_block_count);
+            Self::directory(small_meta_size, small_block_count)?;
 
         let mut current_rank: u64 = 0;
         let mut last_large_rank: u64 = 0;
@@ -64,12 +96,23 @@ impl<Store: BitVec> JacobsonRank<Store> {
         let excess_rank = current_rank - last_large_rank;
         small_block_ranks.push(excess_rank);
 
-        JacobsonRank {
+        Ok(JacobsonRank {
             bit_store: bits,
             large_block_size: large_block_size,
             large_block_ranks: large_block_ranks,
             small_block_ranks: small_block_ranks,
-        }
+        })
+    }
+
+    // An empty directory of `element_bits`-bit ranks with room for
+    // `count` of them.
+    fn directory(element_bits: usize, count: u64)
+                 -> Result<IntVector<u64, Counts>, Error> {
+        let bits = count.checked_mul(element_bits as u64)
+                        .ok_or(Error::Overflow)?;
+        let blocks = u64::checked_ceil_div_nbits(bits).ok_or(Error::Overflow)?;
+        Ok(IntVector::with_store(element_bits,
+                                 Counts::try_with_block_capacity(blocks)?))
     }
 
     /// Borrows a reference to the underlying bit store.
@@ -83,7 +126,52 @@ impl<Store: BitVec> JacobsonRank<Store> {
     }
 }
 
-impl<Store: BitVec> RankSupport for JacobsonRank<Store> {
+impl<Block: BlockType> JacobsonRank<BitVector<Block>> {
+    /// Builds the directories over the bits `bits` yields, without the
+    /// caller collecting them first.
+    pub fn from_bits<I>(bits: I) -> Self
+        where I: IntoIterator<Item = bool> {
+
+        JacobsonRank::new(BitVector::from_bits(bits))
+    }
+
+    /// Builds the directories over `len` bits with ones at `positions`,
+    /// in any order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a position isn’t less than `len`.
+    pub fn from_ones<I>(len: u64, positions: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        JacobsonRank::new(BitVector::from_ones(len, positions))
+    }
+}
+
+impl<Store, Counts> fmt::Debug for JacobsonRank<Store, Counts>
+    where Store: fmt::Debug,
+          Counts: BlockStore<Block = u64> {
+
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.debug_struct("JacobsonRank")
+            .field("bit_store", &self.bit_store)
+            .field("large_block_size", &self.large_block_size)
+            .field("large_block_ranks", &self.large_block_ranks)
+            .field("small_block_ranks", &self.small_block_ranks)
+            .finish()
+    }
+}
+
+impl<Store: BitVec + Default> Default for JacobsonRank<Store> {
+    fn default() -> Self {
+        JacobsonRank::new(Store::default())
+    }
+}
+
+impl<Store, Counts> RankSupport for JacobsonRank<Store, Counts>
+    where Store: BitVec,
+          Counts: BlockStore<Block = u64> {
+
     type Over = bool;
 
     fn rank(&self, position: u64, value: bool) -> u64 {
@@ -95,7 +183,10 @@ impl<Store: BitVec> RankSupport for JacobsonRank<Store> {
     }
 }
 
-impl<Store: BitVec> BitRankSupport for JacobsonRank<Store> {
+impl<Store, Counts> BitRankSupport for JacobsonRank<Store, Counts>
+    where Store: BitVec,
+          Counts: BlockStore<Block = u64> {
+
     fn rank1(&self, position: u64) -> u64 {
         assert!(position < self.bit_len(),
                 "JacobsonRank::rank1: out of bounds");
@@ -112,11 +203,17 @@ impl<Store: BitVec> BitRankSupport for JacobsonRank<Store> {
     }
 }
 
-impl<Store: BitVec> BitVec for JacobsonRank<Store> {
+impl<Store, Counts> BitVec for JacobsonRank<Store, Counts>
+    where Store: BitVec,
+          Counts: BlockStore<Block = u64> {
+
     impl_bit_vec_adapter!(Store::Block, bit_store);
 }
 
-impl<Store: SpaceUsage> SpaceUsage for JacobsonRank<Store> {
+impl<Store, Counts> SpaceUsage for JacobsonRank<Store, Counts>
+    where Store: SpaceUsage,
+          Counts: BlockStore<Block = u64> {
+
     #[inline]
     fn is_stack_only() -> bool { false }
 
@@ -155,6 +252,16 @@ mod test {
         assert_eq!(4096, rank.rank1(1024 * 32 - 1));
     }
 
+    #[test]
+    fn tiny() {
+        let rank = JacobsonRank::try_new(Vec::<u8>::new()).unwrap();
+        assert_eq!(0, rank.limit());
+
+        let rank = JacobsonRank::new(vec![0b101u8]);
+        assert_eq!(2, rank.rank1(7));
+        assert_eq!(1, rank.rank0(1));
+    }
+
     // This test is a sanity check that we aren’t taking up too much
     // space with the metadata.
     #[test]
@@ -170,4 +277,19 @@ mod test {
                         < 1.5);
         }
     }
+
+    #[test]
+    fn paged_counts() {
+        use storage::PagedStore;
+
+        let vec = vec![ 0b00000000000001110000000000000001u32; 1024 ];
+        let rank = JacobsonRank::new(&*vec);
+        let paged = JacobsonRank::<_, PagedStore<u64>>::try_new_in(&*vec)
+            .unwrap();
+
+        for i in (0 .. 1024 * 32).step_by(37) {
+            assert_eq!(rank.rank1(i), paged.rank1(i));
+        }
+        assert_eq!(4096, paged.rank1(1024 * 32 - 1));
+    }
 }
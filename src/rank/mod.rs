@@ -1,5 +1,8 @@
 //! Support for fast rank queries.
 
+mod byte_rank;
+pub use self::byte_rank::*;
+
 mod jacobson;
 pub use self::jacobson::*;
 
@@ -1,23 +1,55 @@
-use num_traits::ToPrimitive;
+use std::io::{self, Write};
+#[cfg(feature = "rkyv")]
+use std::ptr;
 
-use bit_vec::BitVec;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use num_traits::ToPrimitive;
+#[cfg(feature = "rkyv")]
+use rkyv::{Archive, Deserialize, Place, Portable, Serialize};
+#[cfg(feature = "rkyv")]
+use rkyv::bytecheck::CheckBytes;
+#[cfg(feature = "rkyv")]
+use rkyv::munge::munge;
+#[cfg(feature = "rkyv")]
+use rkyv::rancor::{Fallible, Source};
+
+use bit_vec::{BitVec, BitVector};
+use build::Monitor;
+use error::Error;
+use persist::{Component, Persist};
 use rank::{RankSupport, BitRankSupport};
 use space_usage::SpaceUsage;
-use storage::BlockType;
+use storage::BlockStore;
+
+// How many words to count between progress reports.
+const REPORT_BLOCKS: usize = 1 << 16;
 
 /// Vigna’s rank structure for fast rank queries over a `BitVec`.
+///
+/// `Counts` holds the directory, two words per basic block: the rank
+/// before the block, then the packed ranks of its words. Like the bits,
+/// it can be any [`BlockStore`](../storage/trait.BlockStore.html).
+///
+/// Bit vectors shorter than 2<sup>32</sup> bits can use a narrow
+/// directory instead, built by [`try_new_narrow`](#method.try_new_narrow),
+/// with one word per basic block: a 32-bit rank before the block and
+/// 9-bit ranks of every other word. That halves the space over the bits
+/// to 12.5%, which matters for many medium bitmaps such as the levels of
+/// a wavelet matrix, at the cost of counting one more word in some
+/// queries. With the `index32` feature, the other constructors choose
+/// it whenever the bits fit.
 #[derive(Clone, Debug)]
-pub struct Rank9<Store> {
+// A fixed layout, as an archived `Rank9` is one over archived parts.
+#[repr(C)]
+pub struct Rank9<Store, Counts = Vec<u64>> {
     bit_store: Store,
-    counts: Vec<Rank9Cell>,
+    counts: Counts,
+    narrow: bool,
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug)]
-struct Rank9Cell {
-    level1: u64,
-    level2: Level2,
-}
+// The bits of a narrow directory entry holding the rank before the
+// basic block; the ranks before words 2, 4 and 6 follow, 9 bits each.
+const NARROW_LEVEL1_BITS: usize = 32;
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
@@ -50,8 +82,114 @@ impl Level2 {
 impl<Store: BitVec<Block = u64>> Rank9<Store> {
     /// Creates a new rank9 structure.
     pub fn new(bits: Store) -> Self {
-        let bb_count = bits.block_len().ceil_div(8);
-        let mut result = Vec::with_capacity(bb_count + 1);
+        Self::new_in(bits)
+    }
+
+    /// Creates a new rank support structure for the given bit vector, or
+    /// returns an error if the counts can’t be allocated.
+    pub fn try_new(bits: Store) -> Result<Self, Error> {
+        Self::try_new_in(bits)
+    }
+
+    /// Creates a new rank support structure with a narrow directory, or
+    /// returns `Error::Overflow` if the bit vector has 2<sup>32</sup>
+    /// bits or more, or an error if the counts can’t be allocated.
+    pub fn try_new_narrow(bits: Store) -> Result<Self, Error> {
+        Self::try_new_narrow_in(bits)
+    }
+}
+
+impl<Store: BitVec<Block = u64> + Default> Default for Rank9<Store> {
+    fn default() -> Self {
+        Rank9::new(Store::default())
+    }
+}
+
+impl Rank9<BitVector<u64>> {
+    /// Builds the directory over the bits `bits` yields, without the
+    /// caller collecting them first.
+    pub fn from_bits<I>(bits: I) -> Self
+        where I: IntoIterator<Item = bool> {
+
+        Rank9::new(BitVector::from_bits(bits))
+    }
+
+    /// Builds the directory over `len` bits with ones at `positions`,
+    /// in any order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a position isn’t less than `len`.
+    pub fn from_ones<I>(len: u64, positions: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        Rank9::new(BitVector::from_ones(len, positions))
+    }
+
+    /// The heap bytes of the directory over `bit_len` bits, not counting
+    /// the bits themselves, without building it: two words per 512 bits,
+    /// or 25% of the bits, or half that where `new` would choose the
+    /// narrow directory.
+    pub fn estimate_size(bit_len: u64) -> usize {
+        let narrow = default_narrow(bit_len);
+        directory_words(bit_len.div_ceil(64) as usize, narrow) * 8
+    }
+}
+
+// Whether the constructors that don’t say pick the narrow directory.
+fn default_narrow(bit_len: u64) -> bool {
+    cfg!(feature = "index32") && fits_narrow(bit_len)
+}
+
+fn fits_narrow(bit_len: u64) -> bool {
+    bit_len >> NARROW_LEVEL1_BITS == 0
+}
+
+// The words of the directory over `block_len` words: two for each basic
+// block, or one if narrow, plus a sentinel.
+fn directory_words(block_len: usize, narrow: bool) -> usize {
+    let per_block = if narrow {1} else {2};
+    per_block * (block_len / 8 + 1)
+}
+
+impl<Store, Counts> Rank9<Store, Counts>
+    where Store: BitVec<Block = u64>,
+          Counts: BlockStore<Block = u64> {
+
+    /// Creates a new rank9 structure, keeping the counts in a `Counts`.
+    pub fn new_in(bits: Store) -> Self {
+        Self::try_new_in(bits).unwrap_or_else(|error|
+            panic!("Rank9::new: {}", error))
+    }
+
+    /// Creates a new rank9 structure, keeping the counts in a `Counts`,
+    /// or returns an error if the counts can’t be allocated.
+    pub fn try_new_in(bits: Store) -> Result<Self, Error> {
+        Self::with_monitor(bits, &mut Monitor::new())
+    }
+
+    /// Creates a new rank9 structure, keeping the counts in a `Counts`
+    /// and reporting the words counted to `monitor`, or returns an error
+    /// if the counts can’t be allocated, exceed the budget, or `monitor`
+    /// cancels.
+    pub fn with_monitor(bits: Store, monitor: &mut Monitor) -> Result<Self, Error> {
+        let narrow = default_narrow(bits.bit_len());
+        Self::build(bits, narrow, monitor)
+    }
+
+    /// Creates a new rank9 structure with a narrow directory, keeping
+    /// the counts in a `Counts`, or returns `Error::Overflow` if the bit
+    /// vector has 2<sup>32</sup> bits or more, or an error if the counts
+    /// can’t be allocated.
+    pub fn try_new_narrow_in(bits: Store) -> Result<Self, Error> {
+        if !fits_narrow(bits.bit_len()) { return Err(Error::Overflow); }
+        Self::build(bits, true, &mut Monitor::new())
+    }
+
+    fn build(bits: Store, narrow: bool, monitor: &mut Monitor) -> Result<Self, Error> {
+        let words = directory_words(bits.block_len(), narrow);
+        monitor.reserve(words as u64 * 8)?;
+        let mut result = Counts::try_with_block_capacity(words)?;
 
         let mut level1_count = 0;
         let mut level2_count = 0;
@@ -64,19 +202,31 @@ impl<Store: BitVec<Block = u64>> Rank9<Store> {
                 let basic_block_index = i / 8;
                 let word_offset       = i % 8;
 
-                if word_offset == 0 {
-                    result.push(Rank9Cell {
-                        level1: *level1_count,
-                        level2: Level2::new(),
-                    });
+                if narrow {
+                    if word_offset == 0 {
+                        result.push_block(*level1_count);
+                        *level2_count = 0;
+                    } else if word_offset % 2 == 0 {
+                        let shift = NARROW_LEVEL1_BITS + 9 * (word_offset / 2 - 1);
+                        let entry = result.get_block(basic_block_index);
+                        result.set_block(basic_block_index, entry | *level2_count << shift);
+                    }
+                } else if word_offset == 0 {
+                    result.push_block(*level1_count);
+                    result.push_block(Level2::new().0);
                     *level2_count = 0;
                 } else {
-                    result[basic_block_index].level2
-                            .set(word_offset, *level2_count);
+                    let mut level2 =
+                        Level2(result.get_block(2 * basic_block_index + 1));
+                    level2.set(word_offset, *level2_count);
+                    result.set_block(2 * basic_block_index + 1, level2.0);
                 }
             };
 
             for i in 0..bits.block_len() {
+                if i % REPORT_BLOCKS == 0 {
+                    monitor.report("rank directory", i as u64, bits.block_len() as u64)?;
+                }
                 store_counts(i, &mut level1_count, &mut level2_count);
 
                 let word_count = bits.get_block(i).count_ones() as u64;
@@ -87,13 +237,70 @@ impl<Store: BitVec<Block = u64>> Rank9<Store> {
             store_counts(bits.block_len(),
                          &mut level1_count, &mut level2_count);
         }
+        monitor.report("rank directory", bits.block_len() as u64, bits.block_len() as u64)?;
 
-        Rank9 {
+        Ok(Rank9 {
             bit_store: bits,
             counts: result,
+            narrow,
+        })
+    }
+}
+
+impl<Store, Counts> Rank9<Store, Counts>
+    where Store: BitVec<Block = u64>,
+          Counts: BitVec<Block = u64> {
+
+    /// Does the directory take one word per basic block rather than two?
+    pub fn is_narrow(&self) -> bool {
+        self.narrow
+    }
+
+    /// The number of basic blocks, including the sentinel at the end.
+    pub(crate) fn basic_block_count(&self) -> usize {
+        if self.narrow {
+            self.counts.block_len()
+        } else {
+            self.counts.block_len() / 2
+        }
+    }
+
+    /// The number of ones before basic block `bb_index`.
+    pub(crate) fn basic_block_rank(&self, bb_index: usize) -> u64 {
+        if self.narrow {
+            self.counts.get_block(bb_index) & ((1 << NARROW_LEVEL1_BITS) - 1)
+        } else {
+            self.counts.get_block(2 * bb_index)
+        }
+    }
+
+    /// The number of ones between the start of basic block `bb_index`
+    /// and word `word_offset` within it.
+    pub(crate) fn word_rank(&self, bb_index: usize, word_offset: usize)
+                            -> u64 {
+        if !self.narrow {
+            return Level2(self.counts.get_block(2 * bb_index + 1)).get(word_offset);
+        }
+
+        // Only the even words’ ranks are stored; an odd word adds the
+        // word before it.
+        let pair = word_offset / 2;
+        let even = match pair {
+            0 => 0,
+            _ => self.counts.get_block(bb_index) >> (NARROW_LEVEL1_BITS + 9 * (pair - 1)) & 0x1FF,
+        };
+        if word_offset % 2 == 0 {
+            even
+        } else {
+            even + self.bit_store.get_block(8 * bb_index + word_offset - 1).count_ones() as u64
         }
     }
 
+    /// Borrows the counts directory.
+    pub fn counts(&self) -> &Counts {
+        &self.counts
+    }
+
     /// Borrows a reference to the underlying bit store.
     pub fn inner(&self) -> &Store {
         &self.bit_store
@@ -105,7 +312,10 @@ impl<Store: BitVec<Block = u64>> Rank9<Store> {
     }
 }
 
-impl<Store: BitVec<Block = u64>> BitRankSupport for Rank9<Store> {
+impl<Store, Counts> BitRankSupport for Rank9<Store, Counts>
+    where Store: BitVec<Block = u64>,
+          Counts: BitVec<Block = u64> {
+
     fn rank1(&self, position: u64) -> u64 {
         let bb_index = (position / 512).to_usize()
                                        .expect("Rank9::rank1: index overflow");
@@ -114,10 +324,8 @@ impl<Store: BitVec<Block = u64>> BitRankSupport for Rank9<Store> {
         let word_offset = word_index % 8;
         let bit_offset = position % 64;
 
-        let cell = self.counts[bb_index];
-
-        let bb_portion = cell.level1;
-        let word_portion = cell.level2.get(word_offset);
+        let bb_portion = self.basic_block_rank(bb_index);
+        let word_portion = self.word_rank(bb_index, word_offset);
         let bit_portion = self.bit_store.get_block(word_index)
                                         .rank1(bit_offset);
 
@@ -125,7 +333,10 @@ impl<Store: BitVec<Block = u64>> BitRankSupport for Rank9<Store> {
     }
 }
 
-impl<Store: BitVec<Block = u64>> RankSupport for Rank9<Store> {
+impl<Store, Counts> RankSupport for Rank9<Store, Counts>
+    where Store: BitVec<Block = u64>,
+          Counts: BitVec<Block = u64> {
+
     type Over = bool;
 
     fn rank(&self, position: u64, value: bool) -> u64 {
@@ -137,14 +348,146 @@ impl<Store: BitVec<Block = u64>> RankSupport for Rank9<Store> {
     }
 }
 
-impl<Store: BitVec<Block = u64>> BitVec for Rank9<Store> {
+impl<Store, Counts> BitVec for Rank9<Store, Counts>
+    where Store: BitVec<Block = u64>,
+          Counts: BitVec<Block = u64> {
+
     impl_bit_vec_adapter!(u64, bit_store);
 }
 
-impl_stack_only_space_usage!(Rank9Cell);
+/// Whether the directory is narrow as a byte, then the bits. Loading
+/// builds the directory again, in one pass over the words.
+impl Persist for Rank9<BitVector<u64>> {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u8(self.narrow as u8)?;
+        self.bit_store.write_to(sink)
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let narrow = match bytes.read_u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                           "Rank9::read_from: could not load: bad flag")),
+        };
+        let bits = BitVector::read_from(bytes)?;
+        let result = if narrow {
+            Self::try_new_narrow_in(bits)
+        } else {
+            Self::build(bits, false, &mut Monitor::new())
+        };
+        result.map_err(|error| io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("Rank9::read_from: could not load: {}",
+                                                      error)))
+    }
+}
+
+impl Component for Rank9<BitVector<u64>> {
+    fn tag() -> String {
+        "Rank9".to_owned()
+    }
+}
+
+/// Archives as a `Rank9` over the archived bits and counts (with the
+/// `rkyv` feature), so that the directory answers rank queries in place
+/// in the buffer, without being built again.
+///
+/// # Example
+///
+/// ```
+/// # extern crate rkyv;
+/// # extern crate succinct;
+/// use succinct::BitVector;
+/// use succinct::rank::{BitRankSupport, Rank9};
+///
+/// # fn main() {
+/// let bools: Vec<bool> = (0 .. 2000).map(|i| i % 3 == 0).collect();
+/// let rank = Rank9::new(BitVector::<u64>::from(&bools[..]));
+/// let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&rank).unwrap();
+///
+/// let archived =
+///     rkyv::access::<rkyv::Archived<Rank9<BitVector<u64>>>, rkyv::rancor::Error>(&bytes)
+///         .unwrap();
+/// assert_eq!(rank.rank1(1500), archived.rank1(1500));
+/// # }
+/// ```
+#[cfg(feature = "rkyv")]
+impl<Store: Archive, Counts: Archive> Archive for Rank9<Store, Counts> {
+    type Archived = Rank9<Store::Archived, Counts::Archived>;
+    type Resolver = (Store::Resolver, Counts::Resolver);
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let Rank9 { bit_store, counts, narrow } = out);
+        self.bit_store.resolve(resolver.0, bit_store);
+        self.counts.resolve(resolver.1, counts);
+        self.narrow.resolve((), narrow);
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<Store, Counts, S> Serialize<S> for Rank9<Store, Counts>
+    where Store: Serialize<S>,
+          Counts: Serialize<S>,
+          S: Fallible + ?Sized {
+
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok((self.bit_store.serialize(serializer)?, self.counts.serialize(serializer)?))
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<Store, Counts, D> Deserialize<Rank9<Store, Counts>, D>
+    for Rank9<Store::Archived, Counts::Archived>
+    where Store: Archive,
+          Counts: Archive,
+          Store::Archived: Deserialize<Store, D>,
+          Counts::Archived: Deserialize<Counts, D>,
+          D: Fallible + ?Sized {
+
+    fn deserialize(&self, deserializer: &mut D) -> Result<Rank9<Store, Counts>, D::Error> {
+        Ok(Rank9 {
+            bit_store: self.bit_store.deserialize(deserializer)?,
+            counts: self.counts.deserialize(deserializer)?,
+            narrow: self.narrow,
+        })
+    }
+}
+
+// `repr(C)` fixes the layout, and each part is portable.
+#[cfg(feature = "rkyv")]
+unsafe impl<Store: Portable, Counts: Portable> Portable for Rank9<Store, Counts> {}
+
+#[cfg(feature = "rkyv")]
+unsafe impl<Store, Counts, C> CheckBytes<C> for Rank9<Store, Counts>
+    where Store: CheckBytes<C> + BitVec<Block = u64>,
+          Counts: CheckBytes<C> + BitVec<Block = u64>,
+          C: Fallible + ?Sized,
+          C::Error: Source {
+
+    unsafe fn check_bytes(value: *const Self, context: &mut C) -> Result<(), C::Error> {
+        Store::check_bytes(ptr::addr_of!((*value).bit_store), context)?;
+        Counts::check_bytes(ptr::addr_of!((*value).counts), context)?;
+        bool::check_bytes(ptr::addr_of!((*value).narrow), context)?;
+
+        // Queries index the directory by the bits’ blocks, so it must
+        // be as long as they imply.
+        let value = &*value;
+        if value.counts.block_len()
+            != directory_words(value.bit_store.block_len(), value.narrow) {
+            return Err(C::Error::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Rank9: directory length doesn’t match the bits")));
+        }
+        Ok(())
+    }
+}
+
 impl_stack_only_space_usage!(Level2);
 
-impl<Store: SpaceUsage> SpaceUsage for Rank9<Store> {
+impl<Store, Counts> SpaceUsage for Rank9<Store, Counts>
+    where Store: SpaceUsage,
+          Counts: SpaceUsage {
+
     fn is_stack_only() -> bool { false }
 
     fn heap_bytes(&self) -> usize {
@@ -217,6 +560,23 @@ mod test {
         assert_eq!(4096, rank.rank1(1024 * 64 - 1));
     }
 
+    #[test]
+    fn paged() {
+        use bit_vec::{BitVecPush, BitVector};
+        use storage::PagedStore;
+
+        let mut bits: BitVector<u64, PagedStore<u64>> =
+            BitVector::with_store(PagedStore::with_page_bytes(64));
+        for i in 0 .. 5000u64 {
+            bits.push_bit(i % 3 == 0);
+        }
+
+        let paged: Rank9<_, PagedStore<u64>> = Rank9::new_in(bits);
+        for i in 0 .. 5000 {
+            assert_eq!(i / 3 + 1, paged.rank1(i));
+        }
+    }
+
     // This test is a sanity check that we aren’t taking up too much
     // space with the metadata.
     #[test]
@@ -231,4 +591,62 @@ mod test {
             assert!(rank.total_bytes() as f64 / vec_bytes < 1.3);
         }
     }
+
+    #[test]
+    fn narrow() {
+        use space_usage::SpaceUsage;
+
+        let bits: Vec<u64> = (0 .. 3000u64).map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15)).collect();
+        let narrow = Rank9::try_new_narrow(bits.clone()).unwrap();
+        assert!(narrow.is_narrow());
+        assert_eq!(3000 / 8 + 1, narrow.counts().len());
+        assert_eq!(3000 * 8 + 376 * 8, narrow.heap_bytes());
+
+        let mut expected = 0;
+        for i in 0 .. 3000 * 64 {
+            expected += bits.get_bit(i) as u64;
+            assert_eq!(expected, narrow.rank1(i));
+        }
+    }
+
+    #[test]
+    fn estimate_size() {
+        use bit_vec::BitVector;
+        use space_usage::SpaceUsage;
+
+        for &len in &[0, 1, 511, 512, 513, 100_000] {
+            let rank = Rank9::new(BitVector::<u64>::with_fill(len, true));
+            assert_eq!(rank.heap_bytes(),
+                       BitVector::<u64>::estimate_size(len) + Rank9::estimate_size(len));
+        }
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived() {
+        use rkyv::rancor::Error;
+
+        let bools: Vec<bool> = (0 .. 5000u64).map(|i| i.count_ones() % 3 == 0).collect();
+        let bits = BitVector::<u64>::from(&bools[..]);
+        for rank in vec![Rank9::new(bits.clone()), Rank9::try_new_narrow(bits).unwrap()] {
+            let bytes = rkyv::to_bytes::<Error>(&rank).unwrap();
+            let archived =
+                rkyv::access::<rkyv::Archived<Rank9<BitVector<u64>>>, Error>(&bytes).unwrap();
+            assert_eq!(rank.is_narrow(), archived.is_narrow());
+            assert_eq!(5000, archived.bit_len());
+            for position in (0 .. 5000).step_by(7) {
+                assert_eq!(rank.rank1(position), archived.rank1(position));
+            }
+
+            let back: Rank9<BitVector<u64>> = rkyv::deserialize::<_, Error>(archived).unwrap();
+            assert_eq!(rank.counts(), back.counts());
+        }
+
+        // A directory too short for its bits is refused.
+        let short = Rank9 { bit_store: BitVector::<u64>::with_fill(5000, true),
+                            counts: vec![0; 4],
+                            narrow: false };
+        let bytes = rkyv::to_bytes::<Error>(&short).unwrap();
+        assert!(rkyv::access::<rkyv::Archived<Rank9<BitVector<u64>>>, Error>(&bytes).is_err());
+    }
 }
@@ -39,3 +39,25 @@ pub trait BitRankSupport: RankSupport<Over = bool> {
         position + 1 - self.rank1(position)
     }
 }
+
+impl<T: RankSupport + ?Sized> RankSupport for Box<T> {
+    type Over = T::Over;
+
+    fn rank(&self, position: u64, value: T::Over) -> u64 {
+        (**self).rank(position, value)
+    }
+
+    fn limit(&self) -> u64 {
+        (**self).limit()
+    }
+}
+
+impl<T: BitRankSupport + ?Sized> BitRankSupport for Box<T> {
+    fn rank1(&self, position: u64) -> u64 {
+        (**self).rank1(position)
+    }
+
+    fn rank0(&self, position: u64) -> u64 {
+        (**self).rank0(position)
+    }
+}
@@ -3,5 +3,14 @@
 mod bin_search;
 pub use self::bin_search::*;
 
+mod rank_select;
+pub use self::rank_select::*;
+
+mod sampled;
+pub use self::sampled::*;
+
+mod select9;
+pub use self::select9::*;
+
 mod traits;
 pub use self::traits::*;
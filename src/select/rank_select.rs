@@ -0,0 +1,181 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bit_vec::{BitVec, BitVecPush, BitVector, DynamicBitVector, HybridBitmap};
+use error::Error;
+use rank::{BitRankSupport, Rank9};
+use super::{BinSearchSelect, Select0Support, Select1Support};
+
+/// A bitmap with rank and select for both bit values, usable as a trait
+/// object.
+///
+/// This is implemented for everything with the three query traits, all
+/// of which take and return `u64`s and have no generic methods, so a
+/// `Box<dyn RankSelect>` holds any of them. The box has the query traits
+/// too, so it can be passed where a generic bitmap is wanted. That lets
+/// an application choose a representation at run time, say with a
+/// [`BitmapKind`](enum.BitmapKind.html) named in its configuration,
+/// without being generic over it.
+///
+/// # Example
+///
+/// ```
+/// use succinct::BitVector;
+/// use succinct::rank::BitRankSupport;
+/// use succinct::select::{BitmapKind, RankSelect, Select1Support};
+///
+/// let bits: Vec<bool> = (0 .. 1000).map(|i| i % 10 == 3).collect();
+/// let bits = BitVector::<u64>::from(&bits[..]);
+///
+/// let kind: BitmapKind = "hybrid".parse().unwrap();
+/// let bitmap: Box<dyn RankSelect + Send + Sync> = kind.build(&bits);
+///
+/// assert_eq!(100, bitmap.ones());
+/// assert_eq!(5, bitmap.rank1(49));
+/// assert_eq!(Some(53), bitmap.select1(5));
+/// assert!(bitmap.bit_at(993));
+/// ```
+pub trait RankSelect: BitRankSupport + Select1Support + Select0Support {
+    /// The bit at `position`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position >= self.limit()`.
+    fn bit_at(&self, position: u64) -> bool {
+        let before = if position == 0 {0} else {self.rank1(position - 1)};
+        self.rank1(position) > before
+    }
+
+    /// The number of one bits.
+    fn ones(&self) -> u64 {
+        match self.limit() {
+            0 => 0,
+            limit => self.rank1(limit - 1),
+        }
+    }
+}
+
+impl<T: BitRankSupport + Select1Support + Select0Support + ?Sized> RankSelect for T {}
+
+/// The bitmap representations a [`RankSelect`](trait.RankSelect.html)
+/// object can be built with, by name.
+///
+/// The names, for `FromStr` and `Display`, are `rank9`, `hybrid` and
+/// `dynamic`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BitmapKind {
+    /// A plain bit vector with a [`Rank9`](../rank/struct.Rank9.html)
+    /// directory, selecting by binary search: fast rank for dense bits.
+    Rank9,
+    /// A [`HybridBitmap`](../bit_vec/struct.HybridBitmap.html), which
+    /// compresses sparse, dense and run-heavy stretches.
+    Hybrid,
+    /// A [`DynamicBitVector`](../bit_vec/struct.DynamicBitVector.html),
+    /// which can be changed later.
+    Dynamic,
+}
+
+impl BitmapKind {
+    /// Every kind, in the order above.
+    pub const ALL: [BitmapKind; 3] = [BitmapKind::Rank9, BitmapKind::Hybrid, BitmapKind::Dynamic];
+
+    /// The kind’s name.
+    pub fn name(self) -> &'static str {
+        match self {
+            BitmapKind::Rank9 => "rank9",
+            BitmapKind::Hybrid => "hybrid",
+            BitmapKind::Dynamic => "dynamic",
+        }
+    }
+
+    /// Builds a bitmap of this kind holding `bits`.
+    pub fn build<B: BitVec + ?Sized>(self, bits: &B) -> Box<dyn RankSelect + Send + Sync> {
+        let len = bits.bit_len();
+        match self {
+            BitmapKind::Rank9 => {
+                let mut copy = BitVector::<u64>::with_capacity(len);
+                for i in 0 .. len {
+                    copy.push_bit(bits.get_bit(i));
+                }
+                Box::new(BinSearchSelect::new(Rank9::new(copy)))
+            }
+            BitmapKind::Hybrid => {
+                let ones = (0 .. len).filter(|&i| bits.get_bit(i));
+                Box::new(HybridBitmap::new(len, ones))
+            }
+            BitmapKind::Dynamic => {
+                Box::new(DynamicBitVector::from_bits((0 .. len).map(|i| bits.get_bit(i))))
+            }
+        }
+    }
+}
+
+impl fmt::Display for BitmapKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.name())
+    }
+}
+
+impl FromStr for BitmapKind {
+    type Err = Error;
+
+    /// Parses a kind’s name, or returns `Error::UnknownName`.
+    fn from_str(name: &str) -> Result<Self, Error> {
+        BitmapKind::ALL.iter().cloned()
+            .find(|kind| kind.name() == name)
+            .ok_or(Error::UnknownName)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use int_vec::IntVec;
+    use access::Access;
+    use rank::RankSupport;
+    use select::SelectSupport;
+    use quickcheck::quickcheck;
+
+    // Each query trait must stay usable as a trait object.
+    #[test]
+    fn object_safe() {
+        let _: Option<&dyn RankSupport<Over = u64>> = None;
+        let _: Option<&dyn BitRankSupport> = None;
+        let _: Option<&dyn Select1Support> = None;
+        let _: Option<&dyn Select0Support> = None;
+        let _: Option<&dyn SelectSupport<Over = u64>> = None;
+        let _: Option<&dyn IntVec<Block = u64>> = None;
+        let _: Option<&dyn BitVec<Block = u64>> = None;
+        let _: Option<&dyn Access<u64>> = None;
+        let _: Option<&dyn RankSelect> = None;
+    }
+
+    #[test]
+    fn names() {
+        for &kind in &BitmapKind::ALL {
+            assert_eq!(Ok(kind), kind.to_string().parse());
+        }
+        assert_eq!(Err(Error::UnknownName), "rank10".parse::<BitmapKind>());
+    }
+
+    #[test]
+    fn qc_kinds_agree() {
+        fn prop(bits: Vec<bool>) -> bool {
+            let len = bits.len() as u64;
+            let source = BitVector::<u8>::from(&bits[..]);
+            let ones = bits.iter().filter(|&&b| b).count() as u64;
+            let positions: Vec<u64> = (0 .. len).filter(|&i| bits[i as usize]).collect();
+
+            BitmapKind::ALL.iter().all(|kind| {
+                let bitmap = kind.build(&source);
+                bitmap.ones() == ones
+                    && bitmap.limit() == len
+                    && (0 .. len).all(|i| bitmap.bit_at(i) == bits[i as usize])
+                    && (0 .. ones + 1).all(|k| bitmap.select1(k) == positions.get(k as usize).cloned())
+                    && bitmap.select0(len - ones).is_none()
+            })
+        }
+
+        quickcheck(prop as fn(Vec<bool>) -> bool);
+    }
+}
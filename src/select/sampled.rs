@@ -0,0 +1,161 @@
+use num_traits::ToPrimitive;
+
+use int_vec::IntVec;
+use space_usage::SpaceUsage;
+use super::SelectSupport;
+
+/// Select over the values of an integer vector, by scanning from
+/// samples of each value’s positions.
+///
+/// For each value up to the largest present, this records the position
+/// of every `rate`th occurrence, and how many there are. A query jumps
+/// to the sample at or before the occurrence it wants and scans the
+/// vector from there, reading at most the elements up to the next
+/// sample of that value. That costs one `u64` per `rate` elements, plus
+/// a count and a vector header per value, so it suits small alphabets,
+/// where a [`WaveletMatrix`](../wavelet/struct.WaveletMatrix.html) would
+/// spend its levels on few distinct values. Rare values scan further,
+/// since their samples are further apart.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{IntVec, IntVecMut, IntVector};
+/// use succinct::select::{SampledSelect, SelectSupport};
+///
+/// let mut v = IntVector::<u8>::new(2);
+/// for i in 0 .. 100 {
+///     v.push(i % 3);
+/// }
+///
+/// let select = SampledSelect::new(v, 8);
+/// assert_eq!(Some(31), select.select(10, 1));
+/// assert_eq!(34, select.count(0));
+/// assert_eq!(None, select.select(33, 2));
+/// assert_eq!(None, select.select(0, 3));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SampledSelect<V> {
+    values: V,
+    rate: u64,
+    // The position of occurrences 0, rate, 2 * rate, … of each value.
+    samples: Vec<Vec<u64>>,
+    counts: Vec<u64>,
+}
+
+impl<V: IntVec> SampledSelect<V> {
+    /// Samples the position of every `rate`th occurrence of each value
+    /// of `values`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is 0 or a value doesn’t fit in a `usize`.
+    pub fn new(values: V, rate: u64) -> Self {
+        assert!(rate > 0, "SampledSelect::new: rate must be positive");
+
+        let mut samples: Vec<Vec<u64>> = Vec::new();
+        let mut counts: Vec<u64> = Vec::new();
+        for i in 0 .. values.len() {
+            let value = values.get(i).to_usize()
+                .expect("SampledSelect::new: value too large");
+            if value >= counts.len() {
+                counts.resize(value + 1, 0);
+                samples.resize(value + 1, Vec::new());
+            }
+            if counts[value] % rate == 0 {
+                samples[value].push(i);
+            }
+            counts[value] += 1;
+        }
+
+        SampledSelect { values, rate, samples, counts }
+    }
+
+    /// The sampling rate.
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// The number of occurrences of `value`.
+    pub fn count(&self, value: V::Block) -> u64 {
+        value.to_usize()
+            .and_then(|value| self.counts.get(value).cloned())
+            .unwrap_or(0)
+    }
+
+    /// Borrows the underlying vector.
+    pub fn inner(&self) -> &V {
+        &self.values
+    }
+
+    /// Returns the underlying vector.
+    pub fn into_inner(self) -> V {
+        self.values
+    }
+}
+
+impl<V: IntVec> SelectSupport for SampledSelect<V> {
+    type Over = V::Block;
+
+    fn select(&self, index: u64, value: V::Block) -> Option<u64> {
+        if index >= self.count(value) { return None; }
+        let samples = &self.samples[value.to_usize()?];
+
+        let sample = samples[(index / self.rate) as usize];
+        (sample .. self.values.len())
+            .filter(|&i| self.values.get(i) == value)
+            .nth((index % self.rate) as usize)
+    }
+}
+
+impl<V: SpaceUsage> SpaceUsage for SampledSelect<V> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.values.heap_bytes() + self.samples.heap_bytes() + self.counts.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use int_vec::IntVector;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let select = SampledSelect::new(IntVector::<u8>::new(3), 4);
+        assert_eq!(0, select.count(0));
+        assert_eq!(None, select.select(0, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_rate() {
+        SampledSelect::new(IntVector::<u8>::new(3), 0);
+    }
+
+    #[test]
+    fn qc_matches_scan() {
+        fn prop(values: Vec<u8>, rate: u8) -> bool {
+            let rate = rate as u64 % 9 + 1;
+            let mut v = IntVector::<u8>::new(3);
+            for &value in &values {
+                v.push(value % 8);
+            }
+            let select = SampledSelect::new(v, rate);
+
+            (0 .. 9).all(|symbol| {
+                let positions: Vec<u64> = (0 .. values.len() as u64)
+                    .filter(|&i| values[i as usize] % 8 == symbol)
+                    .collect();
+                select.count(symbol) == positions.len() as u64
+                    && (0 .. positions.len() as u64 + 2).all(|k| {
+                        select.select(k, symbol) == positions.get(k as usize).cloned()
+                    })
+            })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, u8) -> bool);
+    }
+}
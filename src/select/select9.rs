@@ -0,0 +1,566 @@
+use std::io::{self, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use num_traits::ToPrimitive;
+
+use bit_vec::{BitVec, BitVector};
+use broadword;
+use persist::{Component, Persist};
+use rank::{BitRankSupport, Rank9, RankSupport};
+use space_usage::SpaceUsage;
+use util::div_ceil;
+use super::Select1Support;
+
+/// The bits an inventory sample should span when its ones are spread
+/// evenly: 16 basic blocks, so the search between samples is short.
+const TARGET_SPAN: u64 = 16 * 512;
+
+/// The least and most ones per inventory sample that
+/// [`Select9::new`](struct.Select9.html#method.new) chooses.
+const MIN_SAMPLE: u64 = 64;
+const MAX_SAMPLE: u64 = 8192;
+
+/// Samples whose ones span more basic blocks than this store their
+/// positions outright.
+const SPARSE_SPAN: usize = 1024;
+
+/// Samples whose ones span no more basic blocks than this need no
+/// sub-inventory, since `select1` can scan them.
+const NEAR_SPAN: usize = 32;
+
+/// The ones per sub-inventory entry, or fewer if the samples are.
+const SUBSAMPLE: u64 = 32;
+
+/// Sub-inventory groups whose ones span more basic blocks than this
+/// store their positions outright, which bounds the scan in `select1`.
+const SUB_SPAN: usize = 8;
+
+/// Marks a sub-inventory entry that indexes explicit positions.
+const EXPLICIT: u16 = 1 << 15;
+
+/// Vigna’s select structure, built on the counts of a `Rank9`.
+///
+/// An inventory records the basic block of every *k*th one. Where *k*
+/// ones are spread over more than 1024 basic blocks, their positions are
+/// stored explicitly. Where they are spread over more than 32, a
+/// sub-inventory records, for every 32nd one of the sample, its basic
+/// block as an offset from the sample’s, and where those 32 ones spread
+/// over more than 8 basic blocks, their positions as offsets too. A
+/// select query looks up the inventory and any sub-inventory, scans the
+/// `Rank9` counts of at most 32 basic blocks, and finishes within a word
+/// using [`select1_raw`](../broadword/fn.select1_raw.html), so it
+/// takes constant time. The sub-inventory and the explicit positions
+/// each cost at most a quarter of a bit per bit, and only where the ones
+/// are spread unevenly.
+///
+/// By default *k* follows the density of the ones, so that a sample
+/// spans about 16 basic blocks: a half-full vector samples every 4096th
+/// one, and one with a one in a thousand bits every 64th, which keeps
+/// its samples from needing explicit positions. The extra space is then
+/// a small fraction of the bit vector at any density.
+/// [`with_sample_rate`](#method.with_sample_rate) fixes *k* instead.
+#[derive(Clone, Debug)]
+pub struct Select9<Store> {
+    rank_support: Rank9<Store>,
+    ones: u64,
+    sample_rate: u64,
+    inventory: Vec<Sample>,
+    sparse: Vec<u64>,
+    // Each entry is a basic block offset from the sample’s, or, marked
+    // `EXPLICIT`, where the group’s positions start within the sample’s
+    // part of `sub_sparse`, counted in groups.
+    subinventory: Vec<u16>,
+    sub_sparse: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    // The basic block containing the sampled one.
+    basic_block: usize,
+    ones: SampleOnes,
+}
+
+// Where a sample finds its ones.
+#[derive(Clone, Copy, Debug)]
+enum SampleOnes {
+    // Their positions start here in `sparse`.
+    Sparse(usize),
+    // They span at most `NEAR_SPAN` basic blocks.
+    Near,
+    // Their sub-inventory entries start at `entries` in `subinventory`,
+    // and the explicit positions of their spread-out groups at
+    // `positions` in `sub_sparse`, as offsets from the sample’s basic
+    // block.
+    Dense { entries: usize, positions: usize },
+}
+
+impl<Store: BitVec<Block = u64>> Select9<Store> {
+    /// Creates a new select structure over the given rank structure,
+    /// sampling at a rate suited to the density of its ones.
+    pub fn new(rank_support: Rank9<Store>) -> Self {
+        let ones = count_ones(&rank_support);
+        let sample_rate = auto_sample_rate(ones, rank_support.bit_len());
+        Self::build(rank_support, ones, sample_rate)
+    }
+
+    /// Creates a new select structure over the given rank structure,
+    /// sampling every `sample_rate`th one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is zero.
+    pub fn with_sample_rate(rank_support: Rank9<Store>, sample_rate: u64) -> Self {
+        assert!(sample_rate > 0, "Select9::with_sample_rate: zero rate");
+        let ones = count_ones(&rank_support);
+        Self::build(rank_support, ones, sample_rate)
+    }
+
+    fn build(rank_support: Rank9<Store>, ones: u64, sample_rate: u64) -> Self {
+        let sample_count = div_ceil(ones, sample_rate).to_usize()
+                               .expect("Select9::new: index overflow");
+
+        let mut positions = Vec::with_capacity(sample_count);
+        for_each_one(&rank_support, 0, |index, position| {
+            if index % sample_rate == 0 {
+                positions.push(position);
+            }
+            positions.len() < sample_count
+        });
+
+        Self::from_samples(rank_support, ones, sample_rate, positions)
+    }
+
+    // Builds the inventory for the sampled ones at `positions`.
+    fn from_samples(rank_support: Rank9<Store>, ones: u64, sample_rate: u64,
+                    positions: Vec<u64>) -> Self {
+        let sample_count = positions.len();
+        let last_block = rank_support.basic_block_count() - 1;
+        let sub_rate = SUBSAMPLE.min(sample_rate) as usize;
+        let mut inventory = Vec::with_capacity(sample_count);
+        let mut sparse = Vec::new();
+        let mut subinventory = Vec::new();
+        let mut sub_sparse = Vec::new();
+        let mut sample_ones = Vec::new();
+
+        for (i, &position) in positions.iter().enumerate() {
+            let basic_block = basic_block_of(position);
+            let next_block = positions.get(i + 1)
+                                      .map_or(last_block,
+                                              |&next| basic_block_of(next));
+
+            let span = next_block - basic_block;
+            if span <= NEAR_SPAN {
+                inventory.push(Sample { basic_block, ones: SampleOnes::Near });
+                continue;
+            }
+
+            let count = (ones - i as u64 * sample_rate).min(sample_rate);
+            sample_ones.clear();
+            for_each_one(&rank_support, position, |index, position| {
+                sample_ones.push(position);
+                index + 1 < count
+            });
+
+            let sample_ones_at = if span > SPARSE_SPAN {
+                let start = sparse.len();
+                sparse.extend_from_slice(&sample_ones);
+                SampleOnes::Sparse(start)
+            } else {
+                // Offsets from the sample’s basic block fit in 20 bits,
+                // and in basic blocks in 15, since its ones span at most
+                // `SPARSE_SPAN` blocks. For the same reason it has fewer
+                // than 2^15 groups, so explicit groups record where their
+                // positions start in groups.
+                let base = basic_block as u64 * 512;
+                let ones_at = SampleOnes::Dense {
+                    entries: subinventory.len(),
+                    positions: sub_sparse.len(),
+                };
+                let positions_start = sub_sparse.len();
+                for group in sample_ones.chunks(sub_rate) {
+                    let first = basic_block_of(group[0]);
+                    let last = basic_block_of(group[group.len() - 1]);
+                    if last - first > SUB_SPAN {
+                        let group_index = (sub_sparse.len() - positions_start) / sub_rate;
+                        subinventory.push(EXPLICIT | group_index as u16);
+                        sub_sparse.extend(group.iter().map(|&one| (one - base) as u32));
+                    } else {
+                        subinventory.push((first - basic_block) as u16);
+                    }
+                }
+                ones_at
+            };
+
+            inventory.push(Sample {
+                basic_block,
+                ones: sample_ones_at,
+            });
+        }
+
+        Select9 {
+            rank_support,
+            ones,
+            sample_rate,
+            inventory,
+            sparse,
+            subinventory,
+            sub_sparse,
+        }
+    }
+
+    /// The number of ones per inventory sample.
+    pub fn sample_rate(&self) -> u64 {
+        self.sample_rate
+    }
+
+    /// Changes the number of ones per inventory sample, keeping the
+    /// rank structure.
+    ///
+    /// The new samples are found by selecting with the old ones rather
+    /// than by scanning the bits, so this takes time in proportion to
+    /// the new inventory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is zero.
+    pub fn resample(self, sample_rate: u64) -> Self {
+        assert!(sample_rate > 0, "Select9::resample: zero rate");
+        let sample_count = div_ceil(self.ones, sample_rate);
+        let positions = (0 .. sample_count)
+            .map(|i| self.select1(i * sample_rate).expect("Select9::resample: missing one"))
+            .collect();
+        let ones = self.ones;
+        Self::from_samples(self.rank_support, ones, sample_rate, positions)
+    }
+
+    /// Borrows a reference to the underlying rank structure.
+    pub fn inner(&self) -> &Rank9<Store> {
+        &self.rank_support
+    }
+
+    /// Returns the underlying rank structure.
+    pub fn into_inner(self) -> Rank9<Store> {
+        self.rank_support
+    }
+}
+
+fn count_ones<Store: BitVec<Block = u64>>(bits: &Rank9<Store>) -> u64 {
+    match bits.bit_len() {
+        0 => 0,
+        len => bits.rank1(len - 1),
+    }
+}
+
+// The ones expected in `TARGET_SPAN` bits, within bounds and rounded up
+// to a power of two.
+fn auto_sample_rate(ones: u64, bit_len: u64) -> u64 {
+    if bit_len == 0 { return MIN_SAMPLE; }
+    let per_span = (ones as u128 * TARGET_SPAN as u128 / bit_len as u128) as u64;
+    per_span.clamp(MIN_SAMPLE, MAX_SAMPLE).next_power_of_two()
+}
+
+fn basic_block_of(position: u64) -> usize {
+    (position / 512).to_usize().expect("Select9: index overflow")
+}
+
+// Calls `visit` with the index (counting from the first one visited) and
+// position of each one at or after `start`, until `visit` returns false.
+fn for_each_one<Store, F>(bits: &Rank9<Store>, start: u64, mut visit: F)
+    where Store: BitVec<Block = u64>,
+          F: FnMut(u64, u64) -> bool {
+
+    let mut index = 0;
+    let first_word = (start / 64).to_usize()
+                                 .expect("Select9: index overflow");
+
+    for i in first_word .. bits.block_len() {
+        let mut word = bits.get_block(i);
+        if i == first_word {
+            word &= !0 << (start % 64);
+        }
+
+        while word != 0 {
+            let position = i as u64 * 64 + word.trailing_zeros() as u64;
+            if !visit(index, position) { return; }
+            index += 1;
+            word &= word - 1;
+        }
+    }
+}
+
+impl Select9<BitVector<u64>> {
+    /// Builds the structure over the bits `bits` yields, without the
+    /// caller collecting them first.
+    pub fn from_bits<I>(bits: I) -> Self
+        where I: IntoIterator<Item = bool> {
+
+        Select9::new(Rank9::from_bits(bits))
+    }
+
+    /// Builds the structure over `len` bits with ones at `positions`,
+    /// in any order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a position isn’t less than `len`.
+    pub fn from_ones<I>(len: u64, positions: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        Select9::new(Rank9::from_ones(len, positions))
+    }
+}
+
+impl<Store: BitVec<Block = u64> + Default> Default for Select9<Store> {
+    fn default() -> Self {
+        Select9::new(Rank9::default())
+    }
+}
+
+impl<Store: BitVec<Block = u64>> Select1Support for Select9<Store> {
+    fn select1(&self, index: u64) -> Option<u64> {
+        if index >= self.ones { return None; }
+
+        let sample = self.inventory[(index / self.sample_rate) as usize];
+        let within = index % self.sample_rate;
+
+        let (first, span) = match sample.ones {
+            SampleOnes::Sparse(start) => return Some(self.sparse[start + within as usize]),
+            SampleOnes::Near => (sample.basic_block, NEAR_SPAN),
+            SampleOnes::Dense { entries, positions } => {
+                let sub_rate = SUBSAMPLE.min(self.sample_rate);
+                let entry = self.subinventory[entries + (within / sub_rate) as usize];
+                if entry & EXPLICIT != 0 {
+                    let at = positions + (entry & !EXPLICIT) as usize * sub_rate as usize
+                        + (within % sub_rate) as usize;
+                    return Some(sample.basic_block as u64 * 512 + self.sub_sparse[at] as u64);
+                }
+                (sample.basic_block + entry as usize, SUB_SPAN)
+            }
+        };
+
+        // Find the last basic block, among the few the ones span, that
+        // starts with no more than `index` ones before it.
+        let rank = &self.rank_support;
+        let last = (first + span).min(rank.basic_block_count() - 1);
+        let mut bb_index = first;
+        while bb_index < last && rank.basic_block_rank(bb_index + 1) <= index {
+            bb_index += 1;
+        }
+
+        let remaining = index - rank.basic_block_rank(bb_index);
+        let words = (rank.block_len() - 8 * bb_index).min(8);
+
+        let mut word_offset = 0;
+        while word_offset + 1 < words
+                && rank.word_rank(bb_index, word_offset + 1) <= remaining {
+            word_offset += 1;
+        }
+
+        let word_index = 8 * bb_index + word_offset;
+        let in_word = remaining - rank.word_rank(bb_index, word_offset);
+        let bit_offset = broadword::select1_raw(in_word as usize,
+                                                rank.get_block(word_index));
+        debug_assert!(bit_offset < 64,
+                      "Select9::select1: counts disagree with bits");
+
+        Some(word_index as u64 * 64 + bit_offset as u64)
+    }
+}
+
+impl<Store: BitVec<Block = u64>> BitVec for Select9<Store> {
+    impl_bit_vec_adapter!(u64, rank_support);
+}
+
+impl<Store: BitVec<Block = u64>> RankSupport for Select9<Store> {
+    impl_rank_support_adapter!(bool, rank_support);
+}
+
+impl<Store: BitVec<Block = u64>> BitRankSupport for Select9<Store> {
+    impl_bit_rank_support_adapter!(rank_support);
+}
+
+/// The sample rate as a `u64`, then the `Rank9`. Loading builds the
+/// inventory again.
+impl Persist for Select9<BitVector<u64>> {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u64::<LittleEndian>(self.sample_rate)?;
+        self.rank_support.write_to(sink)
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let sample_rate = bytes.read_u64::<LittleEndian>()?;
+        if sample_rate == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "Select9::read_from: could not load: zero rate"));
+        }
+        Ok(Select9::with_sample_rate(Rank9::read_from(bytes)?, sample_rate))
+    }
+}
+
+impl Component for Select9<BitVector<u64>> {
+    fn tag() -> String {
+        "Select9".to_owned()
+    }
+}
+
+impl_stack_only_space_usage!(Sample);
+
+impl<Store: SpaceUsage> SpaceUsage for Select9<Store> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.rank_support.heap_bytes()
+            + self.inventory.heap_bytes()
+            + self.sparse.heap_bytes()
+            + self.subinventory.heap_bytes()
+            + self.sub_sparse.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::{BitVecMut, BitVector};
+    use quickcheck::quickcheck;
+    use rank::JacobsonRank;
+    use select::{BinSearchSelect, Select1Support};
+
+    fn check(bits: Vec<u64>) {
+        let expected: Vec<u64> = (0 .. bits.bit_len())
+            .filter(|&i| bits.get_bit(i))
+            .collect();
+
+        let select = Select9::new(Rank9::new(bits));
+        for (index, &position) in expected.iter().enumerate() {
+            assert_eq!(Some(position), select.select1(index as u64));
+        }
+        assert_eq!(None, select.select1(expected.len() as u64));
+    }
+
+    #[test]
+    fn empty() {
+        check(vec![]);
+        check(vec![0; 100]);
+    }
+
+    #[test]
+    fn dense() {
+        check(vec![!0; 100]);
+        check(vec![0x0007_0000_0000_0001; 1024]);
+        check(vec![0xAAAA_AAAA_AAAA_AAAA; 333]);
+    }
+
+    #[test]
+    fn sparse() {
+        // One one per 16 basic blocks, so each sample spans 8192 blocks.
+        let mut bits = BitVector::<u64>::with_fill(1 << 23, false);
+        for i in 0 .. 1024 {
+            bits.set_bit(i * 8192 + i % 100, true);
+        }
+        let expected: Vec<u64> = (0 .. 1024).map(|i| i * 8192 + i % 100)
+                                            .collect();
+
+        let select = Select9::with_sample_rate(Rank9::new(bits), 512);
+        assert!(!select.sparse.is_empty());
+        for (index, &position) in expected.iter().enumerate() {
+            assert_eq!(Some(position), select.select1(index as u64));
+        }
+        assert_eq!(None, select.select1(1024));
+    }
+
+    #[test]
+    fn clustered() {
+        // Sixteen ones at the start of one basic block and sixteen twelve
+        // blocks later, over and over: each group of 32 ones spreads too
+        // far to scan, so stores its positions.
+        let mut bits = BitVector::<u64>::with_fill(1 << 20, false);
+        let mut expected = Vec::new();
+        for region in 0 .. (1 << 20) / (16 * 512) {
+            for &block in &[0, 12] {
+                for i in 0 .. 16 {
+                    let position = (region * 16 + block) * 512 + i;
+                    bits.set_bit(position, true);
+                    expected.push(position);
+                }
+            }
+        }
+
+        let select = Select9::with_sample_rate(Rank9::new(bits), 512);
+        assert!(select.sparse.is_empty());
+        assert!(!select.sub_sparse.is_empty());
+        for (index, &position) in expected.iter().enumerate() {
+            assert_eq!(Some(position), select.select1(index as u64));
+        }
+        assert_eq!(None, select.select1(expected.len() as u64));
+    }
+
+    #[test]
+    fn sample_rates() {
+        let half = Select9::new(Rank9::new(vec![0xAAAA_AAAA_AAAA_AAAAu64; 1000]));
+        assert_eq!(4096, half.sample_rate());
+        assert!(half.subinventory.is_empty());
+
+        // A one per 1024 bits samples densely enough to need no
+        // explicit positions.
+        let mut bits = BitVector::<u64>::with_fill(1 << 24, false);
+        for i in 0 .. 1 << 14 { bits.set_bit(i << 10, true); }
+        let sparse = Select9::new(Rank9::new(bits.clone()));
+        assert_eq!(64, sparse.sample_rate());
+        assert!(sparse.sparse.is_empty());
+        assert_eq!(Some(5 << 10), sparse.select1(5));
+
+        let fixed = Select9::with_sample_rate(Rank9::new(bits), 4096);
+        assert!(!fixed.sparse.is_empty());
+        assert!(fixed.heap_bytes() > sparse.heap_bytes());
+        assert_eq!(Some(5 << 10), fixed.select1(5));
+
+        let resampled = sparse.resample(4096);
+        assert_eq!(fixed.heap_bytes(), resampled.heap_bytes());
+        assert_eq!(Some(5 << 10), resampled.select1(5));
+    }
+
+    #[test]
+    fn mixed() {
+        let mut bits = vec![0u64; 40_000];
+        for word in &mut bits[.. 2000] { *word = !0; }
+        for i in (2000 .. 40_000).step_by(700) { bits[i] = 1 << (i % 64); }
+        check(bits);
+    }
+
+    #[test]
+    fn qc_matches_bin_search() {
+        fn prop(bits: Vec<u64>, index: u64) -> bool {
+            let bin_search = BinSearchSelect::new(Rank9::new(bits.clone()));
+            let fixed = Select9::with_sample_rate(Rank9::new(bits.clone()), 3);
+            let select = Select9::new(Rank9::new(bits));
+            let index = index % (select.bit_len() + 1);
+            let resampled = fixed.clone().resample(index % 7 + 1);
+            select.select1(index) == bin_search.select1(index)
+                && fixed.select1(index) == bin_search.select1(index)
+                && resampled.select1(index) == bin_search.select1(index)
+                && resampled.sample_rate() == index % 7 + 1
+        }
+
+        quickcheck(prop as fn(Vec<u64>, u64) -> bool);
+    }
+
+    #[test]
+    fn qc_from_iterators() {
+        fn prop(bools: Vec<bool>) -> bool {
+            let len = bools.len() as u64;
+            let ones: Vec<u64> = (0 .. len).filter(|&i| bools[i as usize]).collect();
+            let by_bits = Select9::from_bits(bools.iter().cloned());
+            let by_ones = Select9::from_ones(len, ones.iter().rev().cloned());
+            let jacobson = JacobsonRank::<BitVector<u32>>::from_ones(len, ones.iter().cloned());
+
+            by_bits.bit_len() == len && by_ones.bit_len() == len
+                && (0 .. ones.len() as u64 + 1).all(|k| {
+                    let expected = ones.get(k as usize).cloned();
+                    by_bits.select1(k) == expected && by_ones.select1(k) == expected
+                })
+                && (0 .. len).all(|i| by_ones.rank1(i) == jacobson.rank1(i))
+        }
+
+        quickcheck(prop as fn(Vec<bool>) -> bool);
+    }
+}
@@ -18,3 +18,23 @@ pub trait SelectSupport {
     /// Returns the position of the `index`th occurrence of `value`.
     fn select(&self, index: u64, value: Self::Over) -> Option<u64>;
 }
+
+impl<T: Select1Support + ?Sized> Select1Support for Box<T> {
+    fn select1(&self, index: u64) -> Option<u64> {
+        (**self).select1(index)
+    }
+}
+
+impl<T: Select0Support + ?Sized> Select0Support for Box<T> {
+    fn select0(&self, index: u64) -> Option<u64> {
+        (**self).select0(index)
+    }
+}
+
+impl<T: SelectSupport + ?Sized> SelectSupport for Box<T> {
+    type Over = T::Over;
+
+    fn select(&self, index: u64, value: T::Over) -> Option<u64> {
+        (**self).select(index, value)
+    }
+}
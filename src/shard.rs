@@ -0,0 +1,406 @@
+//! Splitting an index into static shards.
+//!
+//! The structures in this crate are built once and then only read, so
+//! adding to one means rebuilding it. A [`ShardedIndex`](struct.ShardedIndex.html)
+//! holds several of them instead: new data goes into a new shard, built
+//! on its own, while queries fan out over every shard and their results
+//! are merged, with each shard’s local offsets moved past the shards
+//! before it. Now and then a run of small shards is
+//! [compacted](struct.ShardedIndex.html#method.compact_in_background)
+//! into one on a background thread, then swapped in.
+//!
+//! ```
+//! use succinct::shard::ShardedIndex;
+//! use succinct::text::FmIndex;
+//!
+//! let mut index = ShardedIndex::new();
+//! index.push(FmIndex::new(b"first batch", 4));
+//! index.push(FmIndex::new(b"second batch", 4));
+//!
+//! assert_eq!(23, index.len());
+//! assert_eq!(2, index.count(&b"batch"[..]));
+//! assert_eq!(vec![6, 18], index.locate(&b"batch"[..]));
+//! assert_eq!(Some((1, 7)), index.shard_of(18));
+//!
+//! // Fold both shards into one, off the querying thread.
+//! let job = index.compact_in_background(0 .. 2, |shards| {
+//!     let text: Vec<u8> = shards.iter().flat_map(|shard| shard.extract(0 .. shard.len())).collect();
+//!     FmIndex::new(&text, 4)
+//! });
+//! assert!(index.finish_compaction(job));
+//! assert_eq!(1, index.shard_count());
+//! assert_eq!(vec![6, 18], index.locate(&b"batch"[..]));
+//! ```
+
+use std::ops::Range;
+use std::sync::Arc;
+use std::thread;
+
+use internal::parallel;
+use space_usage::SpaceUsage;
+use text::{DeletableFmIndex, FmIndex, Symbol};
+use tree::{DeletableTrie, LoudsTrie};
+
+/// A static index that can be one shard of a
+/// [`ShardedIndex`](struct.ShardedIndex.html).
+///
+/// A shard covers a range of offsets, such as text positions or key
+/// ids, and reports its matches as offsets within that range.
+pub trait Shard {
+    /// What queries look for.
+    type Query: ?Sized;
+
+    /// The number of offsets the shard covers.
+    fn extent(&self) -> u64;
+
+    /// The offsets where `query` matches, in no particular order.
+    fn locate(&self, query: &Self::Query) -> Vec<u64>;
+
+    /// The number of matches of `query`.
+    fn count(&self, query: &Self::Query) -> u64 {
+        self.locate(query).len() as u64
+    }
+}
+
+/// Shards over text positions, matching patterns.
+impl<S: Symbol> Shard for FmIndex<S> {
+    type Query = [S];
+
+    fn extent(&self) -> u64 {
+        self.len()
+    }
+
+    fn locate(&self, query: &[S]) -> Vec<u64> {
+        FmIndex::locate(self, query)
+    }
+
+    fn count(&self, query: &[S]) -> u64 {
+        FmIndex::count(self, query)
+    }
+}
+
+/// Shards over the indexed text’s positions, deleted records included,
+/// matching patterns in live records.
+impl<S: Symbol> Shard for DeletableFmIndex<S> {
+    type Query = [S];
+
+    fn extent(&self) -> u64 {
+        self.index().len()
+    }
+
+    fn locate(&self, query: &[S]) -> Vec<u64> {
+        DeletableFmIndex::locate(self, query)
+    }
+
+    fn count(&self, query: &[S]) -> u64 {
+        DeletableFmIndex::count(self, query)
+    }
+}
+
+/// Shards over key ids, matching whole keys.
+impl Shard for LoudsTrie {
+    type Query = [u8];
+
+    fn extent(&self) -> u64 {
+        self.len()
+    }
+
+    fn locate(&self, query: &[u8]) -> Vec<u64> {
+        self.lookup(query).into_iter().collect()
+    }
+}
+
+/// Shards over the trie’s key ids, deleted keys included, matching
+/// whole live keys.
+impl Shard for DeletableTrie {
+    type Query = [u8];
+
+    fn extent(&self) -> u64 {
+        self.trie().len()
+    }
+
+    fn locate(&self, query: &[u8]) -> Vec<u64> {
+        self.lookup(query).into_iter().collect()
+    }
+}
+
+/// A sequence of static shards queried as one index.
+///
+/// The shards’ offsets are laid end to end, so an offset in the `i`th
+/// shard is moved past the extents of the shards before it. Shards are
+/// held behind `Arc`s, so a compaction can read them on another thread
+/// while queries go on.
+#[derive(Clone, Debug)]
+pub struct ShardedIndex<T> {
+    shards: Vec<Arc<T>>,
+    // starts[i] is the first global offset of shard i; the last entry
+    // is the total extent.
+    starts: Vec<u64>,
+}
+
+impl<T: Shard> Default for ShardedIndex<T> {
+    fn default() -> Self {
+        ShardedIndex::new()
+    }
+}
+
+impl<T: Shard> ShardedIndex<T> {
+    /// An index with no shards.
+    pub fn new() -> Self {
+        ShardedIndex { shards: Vec::new(), starts: vec![0] }
+    }
+
+    fn reindex(&mut self) {
+        self.starts.truncate(1);
+        let mut start = 0;
+        for shard in &self.shards {
+            start += shard.extent();
+            self.starts.push(start);
+        }
+    }
+
+    /// Adds a shard after the others.
+    pub fn push(&mut self, shard: T) {
+        let start = self.len() + shard.extent();
+        self.shards.push(Arc::new(shard));
+        self.starts.push(start);
+    }
+
+    /// The number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The `index`th shard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.shard_count()`.
+    pub fn shard(&self, index: usize) -> &T {
+        &self.shards[index]
+    }
+
+    /// The global offsets of the `index`th shard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.shard_count()`.
+    pub fn shard_range(&self, index: usize) -> Range<u64> {
+        assert!(index < self.shard_count(), "ShardedIndex::shard_range: out of bounds");
+        self.starts[index] .. self.starts[index + 1]
+    }
+
+    /// The total extent of the shards.
+    pub fn len(&self) -> u64 {
+        *self.starts.last().expect("ShardedIndex: no starts")
+    }
+
+    /// Do the shards cover no offsets?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The shard covering global `offset` and the offset within it, if
+    /// it is in bounds.
+    pub fn shard_of(&self, offset: u64) -> Option<(usize, u64)> {
+        if offset >= self.len() { return None; }
+        let shard = match self.starts.binary_search(&offset) {
+            Ok(mut i) => {
+                // Skip over empty shards starting at the same offset.
+                while self.starts[i + 1] == offset { i += 1; }
+                i
+            }
+            Err(i) => i - 1,
+        };
+        Some((shard, offset - self.starts[shard]))
+    }
+
+    /// The number of matches of `query` over all the shards.
+    pub fn count(&self, query: &T::Query) -> u64 {
+        self.shards.iter().map(|shard| shard.count(query)).sum()
+    }
+
+    /// The global offsets where `query` matches, in increasing order.
+    pub fn locate(&self, query: &T::Query) -> Vec<u64> {
+        let mut result = Vec::new();
+        for (shard, &start) in self.shards.iter().zip(&self.starts) {
+            let first = result.len();
+            result.extend(shard.locate(query).into_iter().map(|offset| start + offset));
+            result[first ..].sort_unstable();
+        }
+        result
+    }
+
+    /// The first global offset where `query` matches, if any.
+    pub fn lookup(&self, query: &T::Query) -> Option<u64> {
+        self.shards.iter().zip(&self.starts).find_map(|(shard, &start)| {
+            shard.locate(query).into_iter().min().map(|offset| start + offset)
+        })
+    }
+
+    /// Counts the matches of `query` with the shards spread over up to
+    /// `threads` threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is 0.
+    pub fn par_count(&self, threads: usize, query: &T::Query) -> u64
+        where T: Send + Sync,
+              T::Query: Sync {
+
+        let shards = self.shard_count() as u64;
+        parallel::map_reduce(shards, threads, 1, |range| {
+            Some(range.map(|i| self.shards[i as usize].count(query)).sum::<u64>())
+        }, |a, b| a + b).unwrap_or(0)
+    }
+
+    /// Starts building one shard to replace those in `shards`, calling
+    /// `merge` with them on a new thread.
+    ///
+    /// The index stays usable, and may gain shards, while the job runs;
+    /// [`finish_compaction`](#method.finish_compaction) swaps the result
+    /// in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is out of bounds.
+    pub fn compact_in_background<F>(&self, shards: Range<usize>, merge: F) -> Compaction<T>
+        where T: Send + Sync + 'static,
+              F: FnOnce(&[Arc<T>]) -> T + Send + 'static {
+
+        let inputs = self.shards[shards.clone()].to_vec();
+        let thread_inputs = inputs.clone();
+        Compaction {
+            start: shards.start,
+            inputs,
+            handle: thread::spawn(move || merge(&thread_inputs)),
+        }
+    }
+
+    /// Waits for `job` and swaps its shard in for the ones it merged,
+    /// returning whether it could: if those shards were replaced in the
+    /// meantime, the result is dropped and the index is left alone.
+    ///
+    /// The merged shard may cover fewer offsets than its inputs, say by
+    /// dropping deleted entries, in which case the global offsets of
+    /// the shards after it move down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the merge panicked.
+    pub fn finish_compaction(&mut self, job: Compaction<T>) -> bool {
+        let merged = job.handle.join().expect("ShardedIndex: compaction panicked");
+        let range = job.start .. job.start + job.inputs.len();
+        let unchanged = range.end <= self.shards.len()
+            && self.shards[range.clone()].iter().zip(&job.inputs)
+                   .all(|(shard, input)| Arc::ptr_eq(shard, input));
+        if !unchanged { return false; }
+
+        self.shards.splice(range, Some(Arc::new(merged)));
+        self.reindex();
+        true
+    }
+}
+
+impl<T: SpaceUsage> SpaceUsage for ShardedIndex<T> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.shards.iter().map(|shard| shard.total_bytes()).sum::<usize>()
+            + self.starts.heap_bytes()
+            + self.shards.capacity() * ::std::mem::size_of::<Arc<T>>()
+    }
+}
+
+/// A shard being merged on a background thread, from
+/// [`ShardedIndex::compact_in_background`](struct.ShardedIndex.html#method.compact_in_background).
+#[derive(Debug)]
+pub struct Compaction<T> {
+    start: usize,
+    inputs: Vec<Arc<T>>,
+    handle: thread::JoinHandle<T>,
+}
+
+impl<T> Compaction<T> {
+    /// The shards being merged.
+    pub fn shards(&self) -> Range<usize> {
+        self.start .. self.start + self.inputs.len()
+    }
+
+    /// Has the merge finished?
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty_shards() {
+        let mut index = ShardedIndex::new();
+        index.push(LoudsTrie::new(vec!["a", "b"]));
+        index.push(LoudsTrie::new(Vec::<&str>::new()));
+        index.push(LoudsTrie::new(vec!["b", "c"]));
+
+        assert_eq!(4, index.len());
+        assert_eq!(Some((2, 0)), index.shard_of(2));
+        assert_eq!(None, index.shard_of(4));
+        assert_eq!(2 .. 2, index.shard_range(1));
+        assert_eq!(Some(index.shard(0).lookup(b"b").unwrap()), index.lookup(b"b"));
+        assert_eq!(2, index.par_count(2, b"b"));
+    }
+
+    #[test]
+    fn stale_compaction() {
+        let mut index = ShardedIndex::new();
+        index.push(LoudsTrie::new(vec!["a"]));
+        index.push(LoudsTrie::new(vec!["b"]));
+
+        let first = index.compact_in_background(0 .. 2, |shards| shards[0].union(&shards[1]));
+        let second = index.compact_in_background(0 .. 2, |shards| shards[0].union(&shards[1]));
+        assert_eq!(0 .. 2, first.shards());
+        assert!(index.finish_compaction(first));
+        assert!(!index.finish_compaction(second));
+        assert_eq!(1, index.shard_count());
+        assert_eq!(2, index.len());
+    }
+
+    #[test]
+    fn qc_matches_per_shard() {
+        fn prop(texts: Vec<Vec<u8>>, pattern: Vec<u8>, threads: u8) -> bool {
+            let texts: Vec<Vec<u8>> = texts.into_iter()
+                .map(|text| text.into_iter().map(|b| b % 3).collect())
+                .collect();
+            let pattern: Vec<u8> = pattern.into_iter().take(3).map(|b| b % 3).collect();
+
+            let mut index = ShardedIndex::new();
+            let mut expected = Vec::new();
+            let mut start = 0;
+            for text in &texts {
+                index.push(FmIndex::new(text, 3));
+                for i in 0 .. (text.len() + 1).saturating_sub(pattern.len()) {
+                    if text[i ..].starts_with(&pattern) {
+                        expected.push(start + i as u64);
+                    }
+                }
+                start += text.len() as u64;
+            }
+
+            index.len() == start
+                && index.locate(&pattern) == expected
+                && index.count(&pattern) == expected.len() as u64
+                && index.par_count(threads as usize % 4 + 1, &pattern) == expected.len() as u64
+                && index.lookup(&pattern) == expected.first().cloned()
+                && (0 .. start).all(|offset| {
+                    let (shard, local) = index.shard_of(offset).unwrap();
+                    index.shard_range(shard).start + local == offset
+                        && local < index.shard(shard).len()
+                })
+        }
+
+        quickcheck(prop as fn(Vec<Vec<u8>>, Vec<u8>, u8) -> bool);
+    }
+}
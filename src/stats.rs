@@ -0,0 +1,192 @@
+//! Statistics for choosing a representation.
+//!
+//! These measure how compressible a bit vector or integer vector is,
+//! which is what decides between, say, a plain bit vector and a sparse
+//! encoding. Bit vector statistics scan a block at a time.
+//!
+//! ```
+//! use succinct::{BitVecPush, BitVector};
+//! use succinct::stats::BitStats;
+//!
+//! let mut bits: BitVector = BitVector::new();
+//! for i in 0 .. 100 {
+//!     bits.push_bit(i < 25);
+//! }
+//!
+//! assert_eq!(0.25, bits.density());
+//! assert_eq!(2, bits.runs());
+//! ```
+
+use std::collections::BTreeMap;
+
+use num_traits::{One, PrimInt};
+
+use bit_vec::BitVec;
+use int_vec::IntVec;
+use storage::BlockType;
+
+/// Statistics over the bits of a `BitVec`.
+///
+/// This is implemented for every `BitVec`.
+pub trait BitStats: BitVec {
+    /// Counts the one bits.
+    fn count_ones(&self) -> u64 {
+        let mut result = 0;
+        for i in 0 .. self.block_len() {
+            result += valid_block(self, i).count_ones() as u64;
+        }
+        result
+    }
+
+    /// The fraction of bits that are ones, or 0 if there are no bits.
+    fn density(&self) -> f64 {
+        if self.bit_len() == 0 {
+            0.0
+        } else {
+            self.count_ones() as f64 / self.bit_len() as f64
+        }
+    }
+
+    /// Counts the maximal runs of equal bits.
+    ///
+    /// This is 0 for an empty vector and 1 for a constant one.
+    fn runs(&self) -> u64 {
+        if self.bit_len() == 0 { return 0; }
+
+        let top = Self::Block::nbits() - 1;
+        let mut changes = 0;
+        let mut carry = self.get_block(0) & Self::Block::one();
+
+        for i in 0 .. self.block_len() {
+            let block = self.get_block(i);
+            // Bit j of `changed` is set when bits j - 1 and j differ.
+            let changed = (block ^ (block << 1 | carry))
+                & Self::Block::low_mask(valid_bits(self, i));
+            changes += changed.count_ones() as u64;
+            carry = block >> top;
+        }
+
+        changes + 1
+    }
+
+    /// The empirical zero-order entropy, in bits per bit.
+    ///
+    /// Multiply by `bit_len()` for a lower bound on the size of any
+    /// encoding that treats the bits independently.
+    fn zero_order_entropy(&self) -> f64 {
+        let ones = self.count_ones();
+        entropy_of_counts(&[ones, self.bit_len() - ones], self.bit_len())
+    }
+}
+
+impl<T: BitVec + ?Sized> BitStats for T {}
+
+// The number of bits of block `block_index` that are in the vector.
+fn valid_bits<V: BitVec + ?Sized>(bits: &V, block_index: usize) -> usize {
+    if block_index + 1 == bits.block_len() {
+        V::Block::last_block_bits(bits.bit_len())
+    } else {
+        V::Block::nbits()
+    }
+}
+
+// Block `block_index` with any bits past the end cleared.
+fn valid_block<V: BitVec + ?Sized>(bits: &V, block_index: usize) -> V::Block {
+    bits.get_block(block_index)
+        & V::Block::low_mask(valid_bits(bits, block_index))
+}
+
+/// Statistics over the elements of an `IntVec`.
+///
+/// This is implemented for every `IntVec`.
+pub trait IntStats: IntVec {
+    /// Counts the occurrences of each distinct element.
+    fn histogram(&self) -> BTreeMap<Self::Block, u64> {
+        let mut result = BTreeMap::new();
+        for i in 0 .. self.len() {
+            *result.entry(self.get(i)).or_insert(0) += 1;
+        }
+        result
+    }
+
+    /// The empirical zero-order entropy, in bits per element.
+    fn entropy(&self) -> f64 {
+        let counts: Vec<u64> = self.histogram().values().cloned().collect();
+        entropy_of_counts(&counts, self.len())
+    }
+}
+
+impl<T: IntVec + ?Sized> IntStats for T {}
+
+fn entropy_of_counts(counts: &[u64], total: u64) -> f64 {
+    let total = total as f64;
+    counts.iter()
+          .filter(|&&count| count > 0)
+          .map(|&count| {
+              let p = count as f64 / total;
+              -p * p.log2()
+          })
+          .sum::<f64>()
+          .max(0.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::{BitVecPush, BitVector};
+    use int_vec::IntVector;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn bit_stats() {
+        let empty: BitVector<u8> = BitVector::new();
+        assert_eq!(0, empty.count_ones());
+        assert_eq!(0.0, empty.density());
+        assert_eq!(0, empty.runs());
+        assert_eq!(0.0, empty.zero_order_entropy());
+
+        let ones: BitVector<u8> = BitVector::with_fill(13, true);
+        assert_eq!(13, ones.count_ones());
+        assert_eq!(1.0, ones.density());
+        assert_eq!(1, ones.runs());
+        assert_eq!(0.0, ones.zero_order_entropy());
+
+        let alternating: Vec<u8> = vec![0b0101_0101; 4];
+        assert_eq!(0.5, alternating.density());
+        assert_eq!(32, alternating.runs());
+        assert_eq!(1.0, alternating.zero_order_entropy());
+    }
+
+    #[test]
+    fn qc_bit_stats() {
+        fn prop(bits: Vec<bool>) -> bool {
+            let mut packed: BitVector<u16> = BitVector::new();
+            for &bit in &bits {
+                packed.push_bit(bit);
+            }
+
+            let ones = bits.iter().filter(|&&b| b).count() as u64;
+            let runs = if bits.is_empty() {0} else {
+                1 + bits.windows(2).filter(|w| w[0] != w[1]).count() as u64
+            };
+
+            packed.count_ones() == ones && packed.runs() == runs
+        }
+
+        quickcheck(prop as fn(Vec<bool>) -> bool);
+    }
+
+    #[test]
+    fn int_stats() {
+        let mut v = IntVector::<u32>::new(4);
+        assert_eq!(0.0, v.entropy());
+
+        for &x in &[3, 1, 3, 3, 7, 1, 3, 0] {
+            v.push(x);
+        }
+
+        let histogram: Vec<_> = v.histogram().into_iter().collect();
+        assert_eq!(vec![(0, 1), (1, 2), (3, 4), (7, 1)], histogram);
+        assert_eq!(1.75, v.entropy());
+    }
+}
@@ -1,13 +1,25 @@
 //! Traits describing how bits and arrays of bits are stored.
 
+#[cfg(feature = "memmap2")]
+use std::convert::TryFrom;
 use std::fmt;
+#[cfg(feature = "memmap2")]
+use std::fs::File;
 use std::io;
+#[cfg(feature = "memmap2")]
+use std::marker::PhantomData;
 use std::mem;
+#[cfg(feature = "memmap2")]
+use std::slice;
+use std::sync::Arc;
 
 use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "memmap2")]
+use memmap2::MmapMut;
 use num_traits::{One, PrimInt, ToPrimitive};
 
 use bit_vec::{BitVec, BitVecMut};
+use error::Error;
 use rank::{BitRankSupport, RankSupport};
 use space_usage::SpaceUsage;
 
@@ -64,12 +76,11 @@ pub trait BlockType: PrimInt + BitVec + BitVecMut + BitRankSupport +
     ///
     /// This is intended for converting a bit size into a block
     /// size, which is why it takes `u64` and returns `usize`.
-    /// There is no check that the result actually fits in a `usize`,
-    /// so this should only be used when `index` is already known to
-    /// be small enough.
+    /// Returns `None` if the result doesn’t fit in a `usize`.
     #[inline]
     fn checked_ceil_div_nbits(index: u64) -> Option<usize> {
-        Self::checked_div_nbits(index + (Self::nbits() as u64 - 1))
+        let carry = (Self::mod_nbits(index) != 0) as u64;
+        ((index >> Self::lg_nbits()) + carry).to_usize()
     }
 
     /// Returns `index % Self::nbits()`, computed by masking.
@@ -223,7 +234,7 @@ pub trait BlockType: PrimInt + BitVec + BitVecMut + BitRankSupport +
     /// Returns the smallest number `n` such that `n * divisor >= self`.
     #[inline]
     fn ceil_div(self, divisor: Self) -> Self {
-        (self + divisor - Self::one()) / divisor
+        ::util::div_ceil(self, divisor)
     }
 
     // I/O methods
@@ -367,6 +378,806 @@ impl Address {
     }
 }
 
+/// A growable sequence of blocks that a vector can keep its bits in.
+///
+/// `IntVector`, `BitVector` and the counts of `Rank9` are generic over
+/// their stores, defaulting to `Vec<Block>`. The methods mirror `Vec`’s,
+/// but count blocks. With the `memmap2` feature,
+/// [`MmapStore`](struct.MmapStore.html) keeps them in a mapped file. To
+/// keep a structure somewhere else, such as in pages read from disk on
+/// demand, implement this trait alongside `BitVec` and `BitVecMut`.
+pub trait BlockStore: BitVecMut + SpaceUsage + Default {
+    /// Creates an empty store with room for `block_capacity` blocks.
+    fn with_block_capacity(block_capacity: usize) -> Self;
+
+    /// Creates an empty store with room for `block_capacity` blocks, or
+    /// returns an error if the storage can’t be allocated.
+    fn try_with_block_capacity(block_capacity: usize) -> Result<Self, Error>;
+
+    /// The number of blocks the store can hold without allocating.
+    fn block_capacity(&self) -> usize;
+
+    /// Appends a block.
+    fn push_block(&mut self, block: Self::Block);
+
+    /// Removes and returns the last block, if any.
+    fn pop_block(&mut self) -> Option<Self::Block>;
+
+    /// Grows or shrinks to `block_len` blocks, filling with `fill`.
+    fn resize_blocks(&mut self, block_len: usize, fill: Self::Block);
+
+    /// Shrinks to `block_len` blocks, if longer.
+    fn truncate_blocks(&mut self, block_len: usize);
+
+    /// Removes every block, retaining the allocated storage.
+    fn clear_blocks(&mut self) {
+        self.truncate_blocks(0);
+    }
+
+    /// Reserves room for at least `additional` more blocks.
+    fn reserve_blocks(&mut self, additional: usize);
+
+    /// Reserves room for at least `additional` more blocks, without
+    /// deliberately over-allocating.
+    fn reserve_blocks_exact(&mut self, additional: usize);
+
+    /// Releases any storage not needed for the current blocks.
+    fn shrink_blocks_to_fit(&mut self);
+}
+
+impl<Block: BlockType> BlockStore for Vec<Block> {
+    fn with_block_capacity(block_capacity: usize) -> Self {
+        Vec::with_capacity(block_capacity)
+    }
+
+    fn try_with_block_capacity(block_capacity: usize) -> Result<Self, Error> {
+        let mut result = Vec::new();
+        result.try_reserve_exact(block_capacity)
+              .map_err(|_| Error::Allocation)?;
+        Ok(result)
+    }
+
+    fn block_capacity(&self) -> usize {
+        self.capacity()
+    }
+
+    fn push_block(&mut self, block: Block) {
+        self.push(block);
+    }
+
+    fn pop_block(&mut self) -> Option<Block> {
+        self.pop()
+    }
+
+    fn resize_blocks(&mut self, block_len: usize, fill: Block) {
+        self.resize(block_len, fill);
+    }
+
+    fn truncate_blocks(&mut self, block_len: usize) {
+        self.truncate(block_len);
+    }
+
+    fn reserve_blocks(&mut self, additional: usize) {
+        self.reserve(additional);
+    }
+
+    fn reserve_blocks_exact(&mut self, additional: usize) {
+        self.reserve_exact(additional);
+    }
+
+    fn shrink_blocks_to_fit(&mut self) {
+        self.shrink_to_fit();
+    }
+}
+
+/// A block store that allocates fixed-size pages rather than one
+/// contiguous buffer.
+///
+/// Growing a `Vec` past its capacity copies every block into a new
+/// buffer, which for a vector of several gigabytes means a long pause
+/// and, briefly, twice the memory. A `PagedStore` only ever allocates
+/// one more page. Indexing costs a shift and a mask more than a `Vec`.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{IntVec, IntVector};
+/// use succinct::storage::PagedStore;
+///
+/// let mut v: IntVector<u64, PagedStore<u64>> =
+///     IntVector::with_store(40, PagedStore::with_page_bytes(4096));
+///
+/// for i in 0 .. 10_000 {
+///     v.push(i * 1000);
+/// }
+///
+/// assert_eq!(9_999_000, v.get(9_999));
+/// ```
+#[derive(Clone, Debug)]
+pub struct PagedStore<Block> {
+    lg_page_blocks: usize,
+    block_len: usize,
+    // The pages holding blocks, then any allocated ahead, which are
+    // empty.
+    pages: Vec<Vec<Block>>,
+}
+
+/// The page size of `PagedStore::new`.
+pub const DEFAULT_PAGE_BYTES: usize = 64 * 1024;
+
+impl<Block: BlockType> PagedStore<Block> {
+    /// Creates an empty store with 64 KiB pages.
+    pub fn new() -> Self {
+        Self::with_page_bytes(DEFAULT_PAGE_BYTES)
+    }
+
+    /// Creates an empty store whose pages hold `page_bytes` bytes,
+    /// rounded up to a power of two and to at least one block.
+    pub fn with_page_bytes(page_bytes: usize) -> Self {
+        let page_blocks = page_bytes.div_ceil(mem::size_of::<Block>())
+                                    .next_power_of_two();
+        PagedStore {
+            lg_page_blocks: page_blocks.floor_lg(),
+            block_len: 0,
+            pages: Vec::new(),
+        }
+    }
+
+    /// The number of blocks in each page.
+    pub fn page_blocks(&self) -> usize {
+        1 << self.lg_page_blocks
+    }
+
+    fn locate(&self, block_index: usize) -> (usize, usize) {
+        assert!(block_index < self.block_len,
+                "PagedStore: index out of bounds");
+        (block_index >> self.lg_page_blocks,
+         block_index & (self.page_blocks() - 1))
+    }
+
+    fn page_count(&self, block_len: usize) -> usize {
+        block_len.div_ceil(self.page_blocks())
+    }
+
+    // The pages holding blocks, leaving out those allocated ahead, which
+    // is what comparisons look at.
+    fn used_pages(&self) -> &[Vec<Block>] {
+        &self.pages[.. self.page_count(self.block_len)]
+    }
+}
+
+impl<Block: BlockType> PartialEq for PagedStore<Block> {
+    fn eq(&self, other: &Self) -> bool {
+        self.lg_page_blocks == other.lg_page_blocks
+            && self.block_len == other.block_len
+            && self.used_pages() == other.used_pages()
+    }
+}
+
+impl<Block: BlockType> Eq for PagedStore<Block> {}
+
+impl<Block: BlockType> PartialOrd for PagedStore<Block> {
+    fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Block: BlockType> Ord for PagedStore<Block> {
+    fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+        (self.lg_page_blocks, self.block_len, self.used_pages())
+            .cmp(&(other.lg_page_blocks, other.block_len, other.used_pages()))
+    }
+}
+
+impl<Block: BlockType + ::std::hash::Hash> ::std::hash::Hash for PagedStore<Block> {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        self.lg_page_blocks.hash(state);
+        self.block_len.hash(state);
+        self.used_pages().hash(state);
+    }
+}
+
+impl<Block: BlockType> Default for PagedStore<Block> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Block: BlockType> BitVec for PagedStore<Block> {
+    type Block = Block;
+
+    #[inline]
+    fn bit_len(&self) -> u64 {
+        Block::mul_nbits(self.block_len)
+    }
+
+    #[inline]
+    fn block_len(&self) -> usize {
+        self.block_len
+    }
+
+    #[inline]
+    fn get_block(&self, position: usize) -> Block {
+        let (page, offset) = self.locate(position);
+        self.pages[page][offset]
+    }
+}
+
+impl<Block: BlockType> BitVecMut for PagedStore<Block> {
+    #[inline]
+    fn set_block(&mut self, position: usize, value: Block) {
+        let (page, offset) = self.locate(position);
+        self.pages[page][offset] = value;
+    }
+}
+
+impl<Block: BlockType> BlockStore for PagedStore<Block> {
+    /// Allocates the pages up front.
+    fn with_block_capacity(block_capacity: usize) -> Self {
+        let mut result = Self::new();
+        let page_blocks = result.page_blocks();
+        result.pages = (0 .. result.page_count(block_capacity))
+            .map(|_| Vec::with_capacity(page_blocks))
+            .collect();
+        result
+    }
+
+    /// Allocates the pages up front, so that running out of memory is
+    /// reported here rather than when a block is pushed.
+    fn try_with_block_capacity(block_capacity: usize) -> Result<Self, Error> {
+        let mut result = Self::new();
+        let pages = result.page_count(block_capacity);
+        result.pages.try_reserve_exact(pages)
+                    .map_err(|_| Error::Allocation)?;
+        for _ in 0 .. pages {
+            let mut page = Vec::new();
+            page.try_reserve_exact(result.page_blocks())
+                .map_err(|_| Error::Allocation)?;
+            result.pages.push(page);
+        }
+        Ok(result)
+    }
+
+    /// Counts only the allocated pages, since each further page is
+    /// allocated when it is needed.
+    fn block_capacity(&self) -> usize {
+        self.pages.len() << self.lg_page_blocks
+    }
+
+    fn push_block(&mut self, block: Block) {
+        let page = self.block_len >> self.lg_page_blocks;
+        if page == self.pages.len() {
+            self.pages.push(Vec::with_capacity(self.page_blocks()));
+        }
+
+        self.pages[page].push(block);
+        self.block_len += 1;
+    }
+
+    /// Frees the last page when it empties, unless it was allocated
+    /// ahead.
+    fn pop_block(&mut self) -> Option<Block> {
+        if self.block_len == 0 { return None; }
+
+        let page = (self.block_len - 1) >> self.lg_page_blocks;
+        let result = self.pages[page].pop();
+        self.block_len -= 1;
+        if page + 1 == self.pages.len() && self.pages[page].is_empty() {
+            self.pages.pop();
+        }
+
+        result
+    }
+
+    fn resize_blocks(&mut self, block_len: usize, fill: Block) {
+        if block_len <= self.block_len {
+            self.truncate_blocks(block_len);
+        } else {
+            self.reserve_blocks(block_len - self.block_len);
+            while self.block_len < block_len {
+                self.push_block(fill);
+            }
+        }
+    }
+
+    fn truncate_blocks(&mut self, block_len: usize) {
+        if block_len >= self.block_len { return; }
+
+        let pages = self.page_count(block_len);
+        self.pages.truncate(pages);
+        if let Some(last) = self.pages.last_mut() {
+            last.truncate(block_len - ((pages - 1) << self.lg_page_blocks));
+        }
+        self.block_len = block_len;
+    }
+
+    /// Reserves room to track the pages, but allocates no pages.
+    fn reserve_blocks(&mut self, additional: usize) {
+        let block_len = self.block_len.checked_add(additional)
+            .expect("PagedStore: capacity overflow");
+        let pages = self.page_count(block_len);
+        self.pages.reserve(pages.saturating_sub(self.pages.len()));
+    }
+
+    /// Reserves room to track the pages, but allocates no pages.
+    fn reserve_blocks_exact(&mut self, additional: usize) {
+        let block_len = self.block_len.checked_add(additional)
+            .expect("PagedStore: capacity overflow");
+        let pages = self.page_count(block_len);
+        self.pages.reserve_exact(pages.saturating_sub(self.pages.len()));
+    }
+
+    fn shrink_blocks_to_fit(&mut self) {
+        let pages = self.page_count(self.block_len);
+        self.pages.truncate(pages);
+        self.pages.shrink_to_fit();
+        if let Some(last) = self.pages.last_mut() {
+            last.shrink_to_fit();
+        }
+    }
+}
+
+impl<Block: BlockType> SpaceUsage for PagedStore<Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.pages.heap_bytes()
+    }
+}
+
+/// A block store that clones in constant time by sharing its blocks.
+///
+/// The blocks live in a `Vec` behind an `Arc`, so every clone of a
+/// structure built on a `SharedStore` reads the same memory, and threads
+/// can each hold one without borrowing. Modifying a store whose blocks
+/// are shared first copies them, so clones never see each other’s
+/// changes.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{IntVec, IntVecMut, IntVector};
+///
+/// let mut v = IntVector::<u32>::with_fill(10, 1000, 7).into_shared();
+/// let snapshot = v.clone();
+///
+/// v.set(3, 500);
+/// assert_eq!(500, v.get(3));
+/// assert_eq!(7, snapshot.get(3));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SharedStore<Block> {
+    blocks: Arc<Vec<Block>>,
+}
+
+impl<Block: BlockType> SharedStore<Block> {
+    /// Is the storage shared with a clone?
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.blocks) > 1
+    }
+
+    // Copies the blocks first if they are shared.
+    fn blocks_mut(&mut self) -> &mut Vec<Block> {
+        Arc::make_mut(&mut self.blocks)
+    }
+}
+
+impl<Block: BlockType> From<Vec<Block>> for SharedStore<Block> {
+    fn from(blocks: Vec<Block>) -> Self {
+        SharedStore { blocks: Arc::new(blocks) }
+    }
+}
+
+impl<Block: BlockType> Default for SharedStore<Block> {
+    fn default() -> Self {
+        SharedStore::from(Vec::new())
+    }
+}
+
+impl<Block: BlockType> BitVec for SharedStore<Block> {
+    type Block = Block;
+
+    #[inline]
+    fn bit_len(&self) -> u64 {
+        Block::mul_nbits(self.blocks.len())
+    }
+
+    #[inline]
+    fn block_len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    #[inline]
+    fn get_block(&self, position: usize) -> Block {
+        self.blocks[position]
+    }
+}
+
+impl<Block: BlockType> BitVecMut for SharedStore<Block> {
+    #[inline]
+    fn set_block(&mut self, position: usize, value: Block) {
+        self.blocks_mut()[position] = value;
+    }
+}
+
+impl<Block: BlockType> BlockStore for SharedStore<Block> {
+    fn with_block_capacity(block_capacity: usize) -> Self {
+        SharedStore::from(Vec::with_capacity(block_capacity))
+    }
+
+    fn try_with_block_capacity(block_capacity: usize) -> Result<Self, Error> {
+        Vec::try_with_block_capacity(block_capacity).map(SharedStore::from)
+    }
+
+    fn block_capacity(&self) -> usize {
+        self.blocks.capacity()
+    }
+
+    fn push_block(&mut self, block: Block) {
+        self.blocks_mut().push(block);
+    }
+
+    fn pop_block(&mut self) -> Option<Block> {
+        if self.blocks.is_empty() { return None; }
+        self.blocks_mut().pop()
+    }
+
+    fn resize_blocks(&mut self, block_len: usize, fill: Block) {
+        if block_len != self.blocks.len() {
+            self.blocks_mut().resize(block_len, fill);
+        }
+    }
+
+    fn truncate_blocks(&mut self, block_len: usize) {
+        if block_len < self.blocks.len() {
+            self.blocks_mut().truncate(block_len);
+        }
+    }
+
+    fn reserve_blocks(&mut self, additional: usize) {
+        if self.blocks.capacity() - self.blocks.len() < additional {
+            self.blocks_mut().reserve(additional);
+        }
+    }
+
+    fn reserve_blocks_exact(&mut self, additional: usize) {
+        if self.blocks.capacity() - self.blocks.len() < additional {
+            self.blocks_mut().reserve_exact(additional);
+        }
+    }
+
+    fn shrink_blocks_to_fit(&mut self) {
+        if self.blocks.capacity() > self.blocks.len() {
+            self.blocks_mut().shrink_to_fit();
+        }
+    }
+}
+
+/// Counts the blocks in full, even when a clone shares them.
+impl<Block: BlockType> SpaceUsage for SharedStore<Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.blocks.total_bytes()
+    }
+}
+
+/// A block store in memory-mapped pages, anonymous or backed by a file
+/// (with the `memmap2` feature).
+///
+/// The blocks are kept little-endian, so a file written on one machine
+/// reads the same on another. The mapping grows by doubling, like a
+/// `Vec`, by remapping; a file-backed store grows the file to match and
+/// cuts it back to the blocks when dropped, so the file then holds
+/// exactly the blocks. Its pages belong to the operating system’s page
+/// cache, so a vector larger than memory can be built and read, with the
+/// pages it isn’t using written back and evicted.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{IntVec, IntVecMut, IntVector};
+/// use succinct::storage::MmapStore;
+///
+/// let path = std::env::temp_dir().join(format!("succinct-doc-{}", std::process::id()));
+///
+/// {
+///     let file = std::fs::OpenOptions::new()
+///         .read(true).write(true).create(true).truncate(true)
+///         .open(&path).unwrap();
+///     let store = unsafe { MmapStore::<u64>::from_file(file) }.unwrap();
+///     let mut v = IntVector::with_store(64, store);
+///     for i in 0 .. 1000 {
+///         v.push(i * i);
+///     }
+/// }
+///
+/// let file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+/// let store = unsafe { MmapStore::<u64>::from_file(file) }.unwrap();
+/// let v = IntVector::with_store(64, store);
+/// assert_eq!(1000, v.len());
+/// assert_eq!(998_001, v.get(999));
+/// # drop(v);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[cfg(feature = "memmap2")]
+pub struct MmapStore<Block> {
+    // `None` while nothing is mapped, as a mapping can’t be empty.
+    map: Option<MmapMut>,
+    file: Option<File>,
+    block_len: usize,
+    marker: PhantomData<Block>,
+}
+
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> MmapStore<Block> {
+    /// Creates an empty store in anonymous memory.
+    pub fn new() -> Self {
+        MmapStore {
+            map: None,
+            file: None,
+            block_len: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Maps `file`, whose contents become the blocks; an empty file
+    /// makes an empty store. The file must be open for reading and
+    /// writing.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can’t be mapped, or if its length isn’t a
+    /// multiple of the block size.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may modify or truncate the file while the store
+    /// maps it, as the store’s blocks would change under it.
+    pub unsafe fn from_file(file: File) -> io::Result<Self> {
+        let bytes = file.metadata()?.len();
+        let block_bytes = mem::size_of::<Block>() as u64;
+        if bytes % block_bytes != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "MmapStore: file length isn’t a whole number of blocks"));
+        }
+        let block_len = usize::try_from(bytes / block_bytes)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData,
+                                        "MmapStore: file too large to map"))?;
+        let map = if bytes == 0 { None } else { Some(MmapMut::map_mut(&file)?) };
+        Ok(MmapStore {
+            map,
+            file: Some(file),
+            block_len,
+            marker: PhantomData,
+        })
+    }
+
+    /// Is the store backed by a file?
+    pub fn is_file_backed(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Writes the blocks back to the file, if there is one.
+    pub fn flush(&self) -> io::Result<()> {
+        match self.map {
+            Some(ref map) if self.file.is_some() => map.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    fn blocks(&self) -> &[Block] {
+        match self.map {
+            // The mapping is page-aligned, and blocks are plain integers.
+            Some(ref map) => unsafe {
+                slice::from_raw_parts(map.as_ptr() as *const Block,
+                                      map.len() / mem::size_of::<Block>())
+            },
+            None => &[],
+        }
+    }
+
+    fn blocks_mut(&mut self) -> &mut [Block] {
+        match self.map {
+            Some(ref mut map) => unsafe {
+                slice::from_raw_parts_mut(map.as_mut_ptr() as *mut Block,
+                                          map.len() / mem::size_of::<Block>())
+            },
+            None => &mut [],
+        }
+    }
+
+    // Remaps to hold `block_capacity` blocks, which must be at least
+    // `block_len`.
+    fn try_remap(&mut self, block_capacity: usize) -> io::Result<()> {
+        let bytes = block_capacity.checked_mul(mem::size_of::<Block>())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                                          "MmapStore: capacity overflow"))?;
+        if let Some(ref file) = self.file {
+            // The mapping is shared, so unmapping it loses nothing.
+            self.map = None;
+            file.set_len(bytes as u64)?;
+            if bytes > 0 {
+                self.map = Some(unsafe { MmapMut::map_mut(file)? });
+            }
+        } else {
+            let mut map = if bytes == 0 { None } else { Some(MmapMut::map_anon(bytes)?) };
+            if let Some(ref mut map) = map {
+                let used = self.block_len * mem::size_of::<Block>();
+                if let Some(ref old) = self.map {
+                    map[.. used].copy_from_slice(&old[.. used]);
+                }
+            }
+            self.map = map;
+        }
+        Ok(())
+    }
+
+    fn remap(&mut self, block_capacity: usize) {
+        self.try_remap(block_capacity).expect("MmapStore: could not remap");
+    }
+
+    fn reserve_for(&mut self, block_len: usize, exact: bool) {
+        let capacity = self.block_capacity();
+        if block_len > capacity {
+            self.remap(if exact { block_len } else { block_len.max(2 * capacity) });
+        }
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<Block> Drop for MmapStore<Block> {
+    /// Cuts a backing file back to the blocks. Errors are ignored;
+    /// call [`flush`](#method.flush) first to see them.
+    fn drop(&mut self) {
+        if let Some(ref file) = self.file {
+            if let Some(map) = self.map.take() {
+                let _ = map.flush();
+            }
+            let _ = file.set_len(self.block_len as u64 * mem::size_of::<Block>() as u64);
+        }
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> fmt::Debug for MmapStore<Block> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MmapStore")
+         .field("block_len", &self.block_len)
+         .field("block_capacity", &self.block_capacity())
+         .field("file_backed", &self.is_file_backed())
+         .finish()
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> PartialEq for MmapStore<Block> {
+    fn eq(&self, other: &Self) -> bool {
+        self.blocks()[.. self.block_len] == other.blocks()[.. other.block_len]
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> Eq for MmapStore<Block> {}
+
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> Default for MmapStore<Block> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> BitVec for MmapStore<Block> {
+    type Block = Block;
+
+    #[inline]
+    fn bit_len(&self) -> u64 {
+        Block::mul_nbits(self.block_len)
+    }
+
+    #[inline]
+    fn block_len(&self) -> usize {
+        self.block_len
+    }
+
+    #[inline]
+    fn get_block(&self, position: usize) -> Block {
+        assert!(position < self.block_len, "MmapStore: index out of bounds");
+        Block::from_le(self.blocks()[position])
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> BitVecMut for MmapStore<Block> {
+    #[inline]
+    fn set_block(&mut self, position: usize, value: Block) {
+        assert!(position < self.block_len, "MmapStore: index out of bounds");
+        self.blocks_mut()[position] = value.to_le();
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> BlockStore for MmapStore<Block> {
+    /// Maps anonymous memory for the blocks up front.
+    fn with_block_capacity(block_capacity: usize) -> Self {
+        let mut result = Self::new();
+        result.remap(block_capacity);
+        result
+    }
+
+    fn try_with_block_capacity(block_capacity: usize) -> Result<Self, Error> {
+        let mut result = Self::new();
+        result.try_remap(block_capacity).map_err(|_| Error::Allocation)?;
+        Ok(result)
+    }
+
+    fn block_capacity(&self) -> usize {
+        self.blocks().len()
+    }
+
+    fn push_block(&mut self, block: Block) {
+        let block_len = self.block_len.checked_add(1)
+            .expect("MmapStore: capacity overflow");
+        self.reserve_for(block_len, false);
+        self.block_len = block_len;
+        self.set_block(block_len - 1, block);
+    }
+
+    fn pop_block(&mut self) -> Option<Block> {
+        if self.block_len == 0 { return None; }
+        let result = self.get_block(self.block_len - 1);
+        self.block_len -= 1;
+        Some(result)
+    }
+
+    fn resize_blocks(&mut self, block_len: usize, fill: Block) {
+        if block_len <= self.block_len {
+            self.truncate_blocks(block_len);
+        } else {
+            self.reserve_for(block_len, false);
+            let old_len = mem::replace(&mut self.block_len, block_len);
+            for block in &mut self.blocks_mut()[old_len .. block_len] {
+                *block = fill.to_le();
+            }
+        }
+    }
+
+    fn truncate_blocks(&mut self, block_len: usize) {
+        self.block_len = self.block_len.min(block_len);
+    }
+
+    fn reserve_blocks(&mut self, additional: usize) {
+        let block_len = self.block_len.checked_add(additional)
+            .expect("MmapStore: capacity overflow");
+        self.reserve_for(block_len, false);
+    }
+
+    fn reserve_blocks_exact(&mut self, additional: usize) {
+        let block_len = self.block_len.checked_add(additional)
+            .expect("MmapStore: capacity overflow");
+        self.reserve_for(block_len, true);
+    }
+
+    fn shrink_blocks_to_fit(&mut self) {
+        if self.block_capacity() > self.block_len {
+            let block_len = self.block_len;
+            self.remap(block_len);
+        }
+    }
+}
+
+/// Counts an anonymous mapping, but not the pages of a file, which the
+/// operating system can evict and read back.
+#[cfg(feature = "memmap2")]
+impl<Block: BlockType> SpaceUsage for MmapStore<Block> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        if self.is_file_backed() { 0 } else { self.block_capacity() * mem::size_of::<Block>() }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -504,5 +1315,187 @@ mod test {
 
         quickcheck(prop as fn(u64, u64) -> TestResult);
     }
+
+    #[test]
+    fn paged_store() {
+        let mut store = PagedStore::<u32>::with_page_bytes(16);
+        assert_eq!(4, store.page_blocks());
+
+        for i in 0 .. 10 {
+            store.push_block(i);
+        }
+        assert_eq!(10, store.block_len());
+        assert_eq!(12, store.block_capacity());
+        assert_eq!(7, store.get_block(7));
+
+        store.set_block(4, 40);
+        assert_eq!(40, store.get_block(4));
+
+        assert_eq!(Some(9), store.pop_block());
+        assert_eq!(Some(8), store.pop_block());
+        assert_eq!(8, store.block_capacity());
+
+        store.truncate_blocks(5);
+        assert_eq!(5, store.block_len());
+        assert_eq!(40, store.get_block(4));
+
+        store.resize_blocks(9, 1);
+        assert_eq!(vec![0, 1, 2, 3, 40, 1, 1, 1, 1],
+                   (0 .. 9).map(|i| store.get_block(i)).collect::<Vec<_>>());
+
+        let mut other = PagedStore::with_page_bytes(16);
+        for i in 0 .. 9 {
+            other.push_block(store.get_block(i));
+        }
+        assert_eq!(store, other);
+
+        store.clear_blocks();
+        assert_eq!(0, store.block_len());
+        assert_eq!(None, store.pop_block());
+    }
+
+    #[test]
+    fn paged_store_capacity() {
+        let mut store = PagedStore::<u64>::try_with_block_capacity(20_000).unwrap();
+        let page_blocks = store.page_blocks();
+        assert_eq!(3 * page_blocks, store.block_capacity());
+        assert!(store.heap_bytes() >= 3 * page_blocks * 8);
+
+        // Pushing fills the pages allocated ahead, in order.
+        let mut other = PagedStore::new();
+        for i in 0 .. page_blocks as u64 + 5 {
+            store.push_block(i);
+            other.push_block(i);
+        }
+        assert_eq!(3 * page_blocks, store.block_capacity());
+        assert_eq!(page_blocks as u64 + 4, store.get_block(page_blocks + 4));
+        assert_eq!(other, store);
+
+        assert_eq!(Some(page_blocks as u64 + 4), store.pop_block());
+        assert_eq!(3 * page_blocks, store.block_capacity());
+        store.shrink_blocks_to_fit();
+        assert_eq!(2 * page_blocks, store.block_capacity());
+        assert_eq!(page_blocks as u64 + 3, store.get_block(page_blocks + 3));
+
+        // Reserving within the pages allocated ahead adds nothing.
+        let mut store = PagedStore::<u64>::with_block_capacity(20_000);
+        store.push_block(1);
+        store.reserve_blocks(10);
+        store.reserve_blocks_exact(10);
+        assert_eq!(3 * page_blocks, store.block_capacity());
+
+        assert_eq!(Err(Error::Allocation), PagedStore::<u64>::try_with_block_capacity(usize::MAX));
+        assert_eq!(page_blocks, PagedStore::<u64>::with_block_capacity(1).block_capacity());
+    }
+
+    #[test]
+    fn shared_store() {
+        let mut store = SharedStore::from(vec![1u8, 2, 3]);
+        assert!(!store.is_shared());
+
+        let snapshot = store.clone();
+        assert!(store.is_shared());
+        store.reserve_blocks(0);
+        assert!(store.is_shared());
+
+        store.set_block(0, 10);
+        store.push_block(4);
+        assert!(!store.is_shared());
+        assert_eq!(4, store.block_len());
+        assert_eq!(10, store.get_block(0));
+        assert_eq!(3, snapshot.block_len());
+        assert_eq!(1, snapshot.get_block(0));
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn mmap_store() {
+        let mut store = MmapStore::<u32>::new();
+        assert_eq!(0, store.block_capacity());
+
+        for i in 0 .. 10 {
+            store.push_block(i);
+        }
+        assert_eq!(10, store.block_len());
+        assert!(store.block_capacity() >= 10);
+        assert_eq!(7, store.get_block(7));
+
+        store.set_block(4, 40);
+        assert_eq!(Some(9), store.pop_block());
+        store.truncate_blocks(5);
+        store.resize_blocks(9, 1);
+        assert_eq!(vec![0, 1, 2, 3, 40, 1, 1, 1, 1],
+                   (0 .. 9).map(|i| store.get_block(i)).collect::<Vec<_>>());
+
+        store.shrink_blocks_to_fit();
+        assert_eq!(9, store.block_capacity());
+        assert_eq!(36, store.heap_bytes());
+        store.reserve_blocks_exact(3);
+        assert_eq!(12, store.block_capacity());
+        assert_eq!(40, store.get_block(4));
+
+        let mut other = MmapStore::with_block_capacity(100);
+        for i in 0 .. 9 {
+            other.push_block(store.get_block(i));
+        }
+        assert_eq!(store, other);
+
+        store.clear_blocks();
+        assert_eq!(None, store.pop_block());
+        assert_eq!(Err(Error::Allocation), MmapStore::<u64>::try_with_block_capacity(usize::MAX));
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn mmap_store_file() {
+        use std::fs::{self, OpenOptions};
+        use int_vec::{IntVec, IntVector};
+
+        let path = ::std::env::temp_dir()
+            .join(format!("succinct-mmap-store-{}", ::std::process::id()));
+        let open = |truncate| OpenOptions::new().read(true).write(true).create(true)
+                                                .truncate(truncate).open(&path).unwrap();
+
+        {
+            let store = unsafe { MmapStore::<u16>::from_file(open(true)) }.unwrap();
+            assert!(store.is_file_backed());
+            let mut v = IntVector::with_store(16, store);
+            for i in 0 .. 5000 {
+                v.push(i * 3);
+            }
+        }
+        assert_eq!(10_000, fs::metadata(&path).unwrap().len());
+
+        {
+            let store = unsafe { MmapStore::<u16>::from_file(open(false)) }.unwrap();
+            assert_eq!(0, store.heap_bytes());
+            let mut v = IntVector::with_store(16, store);
+            assert_eq!(5000, v.len());
+            assert_eq!(14_997, v.get(4999));
+            v.pop();
+        }
+        assert_eq!(9998, fs::metadata(&path).unwrap().len());
+
+        open(true).set_len(3).unwrap();
+        assert_eq!(io::ErrorKind::InvalidData,
+                   unsafe { MmapStore::<u16>::from_file(open(false)) }.unwrap_err().kind());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn paged_store_reserve_overflow() {
+        let mut store = PagedStore::<u8>::new();
+        store.push_block(0);
+        store.reserve_blocks(usize::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn paged_store_oob() {
+        let mut store = PagedStore::<u8>::new();
+        store.push_block(0);
+        store.get_block(1);
+    }
 }
 
@@ -0,0 +1,217 @@
+use std::marker::PhantomData;
+
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use int_vec::{IntVecMut, IntVector};
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use super::Symbol;
+
+/// The symbols that occur in some text, numbered densely from 0.
+///
+/// Structures over integer symbols, such as a
+/// [`WaveletMatrix`](../wavelet/struct.WaveletMatrix.html) or an
+/// [`FmIndex`](struct.FmIndex.html), cost space and time that grow with
+/// the largest symbol, so remapping a sparse alphabet first makes them
+/// smaller and faster. The mapping is a bitmap over the symbols up to
+/// the largest, with rank to map and select to map back, so it costs
+/// about one bit per possible symbol rather than a table.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::{Alphabet, FmIndex};
+///
+/// let text = [1000u32, 7, 1000, 50000, 7];
+/// let alphabet = Alphabet::new(&text);
+/// assert_eq!(3, alphabet.len());
+/// assert_eq!(Some(2), alphabet.rank(50000));
+/// assert_eq!(1000, alphabet.select(1));
+///
+/// let dense: Vec<u8> = alphabet.encode_symbols(&text);
+/// assert_eq!(vec![1, 0, 1, 2, 0], dense);
+///
+/// let index = FmIndex::new(&dense, 4);
+/// let pattern: Vec<u8> = alphabet.encode_symbols(&[7, 1000]);
+/// assert_eq!(vec![1], index.locate(&pattern));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Alphabet<S: Symbol = u8> {
+    present: Select9<BitVector<u64>>,
+    len: u64,
+    marker: PhantomData<S>,
+}
+
+impl<S: Symbol> Alphabet<S> {
+    /// The alphabet of the symbols in `text`.
+    pub fn new(text: &[S]) -> Self {
+        Self::from_symbols(text.iter().cloned())
+    }
+
+    /// The alphabet of some symbols, in any order and with repeats.
+    pub fn from_symbols<I>(symbols: I) -> Self
+        where I: IntoIterator<Item = S> {
+
+        let symbols: Vec<u64> = symbols.into_iter().map(S::to_u64).collect();
+        let universe = symbols.iter().cloned().max().map_or(0, |max| max + 1);
+
+        let mut bits: BitVector<u64> = BitVector::with_fill(universe, false);
+        for &symbol in &symbols {
+            bits.set_bit(symbol, true);
+        }
+
+        let present = Select9::new(Rank9::new(bits));
+        let len = if universe == 0 {0} else {present.rank1(universe - 1)};
+        Alphabet { present, len, marker: PhantomData }
+    }
+
+    /// The number of distinct symbols, σ.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Are there no symbols?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bits a dense symbol needs.
+    pub fn symbol_bits(&self) -> usize {
+        bits_to_represent(self.len.saturating_sub(1))
+    }
+
+    /// Does `symbol` occur?
+    pub fn contains(&self, symbol: S) -> bool {
+        let symbol = symbol.to_u64();
+        symbol < self.present.bit_len() && self.present.get_bit(symbol)
+    }
+
+    /// The dense number of `symbol`, which is how many smaller symbols
+    /// occur, or `None` if it doesn’t occur.
+    pub fn rank(&self, symbol: S) -> Option<u64> {
+        if !self.contains(symbol) { return None; }
+        Some(self.present.rank1(symbol.to_u64()) - 1)
+    }
+
+    /// The symbol with dense number `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    pub fn select(&self, index: u64) -> S {
+        let symbol = self.present.select1(index)
+                         .expect("Alphabet::select: out of bounds");
+        S::from_u64(symbol)
+    }
+
+    /// Maps `text` to dense symbols, stored as tightly as σ allows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a symbol isn’t in the alphabet.
+    pub fn encode(&self, text: &[S]) -> IntVector<u64> {
+        let mut result = IntVector::with_fill(self.symbol_bits(),
+                                              text.len() as u64, 0);
+        for (i, &symbol) in text.iter().enumerate() {
+            let dense = self.rank(symbol)
+                            .expect("Alphabet::encode: unknown symbol");
+            result.set(i as u64, dense);
+        }
+        result
+    }
+
+    /// Maps `text` to dense symbols of another type, for building a
+    /// text index with a smaller alphabet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a symbol isn’t in the alphabet, or σ doesn’t fit in `T`.
+    pub fn encode_symbols<T: Symbol>(&self, text: &[S]) -> Vec<T> {
+        if let Some(top) = self.len.checked_sub(1) {
+            assert!(T::from_u64(top).to_u64() == top,
+                    "Alphabet::encode_symbols: alphabet too large");
+        }
+
+        text.iter().map(|&symbol| {
+            let dense = self.rank(symbol)
+                            .expect("Alphabet::encode_symbols: unknown symbol");
+            T::from_u64(dense)
+        }).collect()
+    }
+
+    /// Maps dense symbols back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value is not less than σ.
+    pub fn decode<T: Symbol>(&self, dense: &[T]) -> Vec<S> {
+        dense.iter().map(|&value| self.select(value.to_u64())).collect()
+    }
+}
+
+impl<S: Symbol> SpaceUsage for Alphabet<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.present.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use int_vec::IntVec;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let alphabet = Alphabet::<u8>::new(&[]);
+        assert!(alphabet.is_empty());
+        assert_eq!(None, alphabet.rank(0));
+        assert_eq!(0, alphabet.encode(&[]).len());
+    }
+
+    #[test]
+    fn bytes() {
+        let alphabet = Alphabet::new(b"hello world");
+        assert_eq!(8, alphabet.len());
+        assert_eq!(3, alphabet.symbol_bits());
+        assert_eq!(Some(0), alphabet.rank(b' '));
+        assert_eq!(None, alphabet.rank(b'z'));
+
+        let encoded = alphabet.encode(b"low");
+        assert_eq!(3, encoded.element_bits());
+        let values: Vec<u8> = encoded.iter().map(|x| x as u8).collect();
+        assert_eq!(b"low".to_vec(), alphabet.decode(&values));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_large() {
+        let text: Vec<u16> = (0 .. 300).collect();
+        let _: Vec<u8> = Alphabet::new(&text).encode_symbols(&text);
+    }
+
+    #[test]
+    fn qc_round_trip() {
+        fn prop(text: Vec<u32>) -> bool {
+            let text: Vec<u32> = text.into_iter().map(|x| x % 5000).collect();
+            let alphabet = Alphabet::new(&text);
+            let dense: Vec<u32> = alphabet.encode_symbols(&text);
+
+            let mut distinct = text.clone();
+            distinct.sort();
+            distinct.dedup();
+
+            alphabet.decode(&dense) == text
+                && alphabet.len() == distinct.len() as u64
+                && distinct.iter().enumerate().all(|(i, &symbol)| {
+                    alphabet.rank(symbol) == Some(i as u64)
+                        && alphabet.select(i as u64) == symbol
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u32>) -> bool);
+    }
+}
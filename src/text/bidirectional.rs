@@ -0,0 +1,238 @@
+use std::ops::Range;
+
+use space_usage::SpaceUsage;
+use super::{FmIndex, Symbol};
+
+/// The occurrences of a pattern in a
+/// [`BidirectionalFmIndex`](struct.BidirectionalFmIndex.html): its rows
+/// among the suffixes of the text and among those of the reversed text.
+///
+/// Both ranges have the same length, the number of occurrences.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BiInterval {
+    forward: u64,
+    reverse: u64,
+    len: u64,
+}
+
+impl BiInterval {
+    /// The number of occurrences.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Does the pattern not occur?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The rows of the suffixes of the text that start with the pattern.
+    pub fn forward(&self) -> Range<u64> {
+        self.forward .. self.forward + self.len
+    }
+
+    /// The rows of the suffixes of the reversed text that start with the
+    /// reversed pattern.
+    pub fn reverse(&self) -> Range<u64> {
+        self.reverse .. self.reverse + self.len
+    }
+}
+
+/// An FM-index of a text and of its reverse, kept in step so that a
+/// pattern can be extended at either end.
+///
+/// Extending by a symbol takes a rank and a `count_less` over one of the
+/// two transforms, so searches can grow a match outward from a seed,
+/// as maximal exact match finding needs. Only the forward index keeps
+/// position samples, so the reverse one costs little more than its
+/// transform.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::BidirectionalFmIndex;
+///
+/// let index = BidirectionalFmIndex::new(b"abracadabra", 4);
+///
+/// let bra = index.search(b"bra");
+/// let abra = index.extend_left(bra, b'a');
+/// let abrac = index.extend_right(abra, b'c');
+///
+/// assert_eq!(2, abra.len());
+/// assert_eq!(vec![0], index.locate(abrac));
+/// ```
+#[derive(Clone, Debug)]
+pub struct BidirectionalFmIndex<S: Symbol = u8> {
+    forward: FmIndex<S>,
+    reverse: FmIndex<S>,
+}
+
+impl<S: Symbol> BidirectionalFmIndex<S> {
+    /// Indexes `text`, sampling every `sample_rate`th position for
+    /// `locate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is 0.
+    pub fn new(text: &[S], sample_rate: usize) -> Self {
+        let reversed: Vec<S> = text.iter().rev().cloned().collect();
+        BidirectionalFmIndex {
+            forward: FmIndex::new(text, sample_rate),
+            reverse: FmIndex::count_only(&reversed),
+        }
+    }
+
+    /// The index of the text.
+    pub fn forward(&self) -> &FmIndex<S> {
+        &self.forward
+    }
+
+    /// The index of the reversed text, which is count-only.
+    pub fn reverse(&self) -> &FmIndex<S> {
+        &self.reverse
+    }
+
+    /// The length of the text.
+    pub fn len(&self) -> u64 {
+        self.forward.len()
+    }
+
+    /// Is the text empty?
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    /// The interval of the empty pattern, which occurs at every position.
+    pub fn full(&self) -> BiInterval {
+        BiInterval {
+            forward: 0,
+            reverse: 0,
+            len: self.len() + 1,
+        }
+    }
+
+    /// The interval of `pattern`.
+    pub fn search(&self, pattern: &[S]) -> BiInterval {
+        let mut result = self.full();
+        for &symbol in pattern.iter().rev() {
+            if result.is_empty() { break; }
+            result = self.extend_left(result, symbol);
+        }
+        result
+    }
+
+    /// Prepends `symbol` to the pattern of `interval`.
+    pub fn extend_left(&self, interval: BiInterval, symbol: S) -> BiInterval {
+        let (forward, reverse) =
+            extend(&self.forward, interval.forward(), interval.reverse, symbol);
+        BiInterval {
+            forward: forward.start,
+            reverse,
+            len: forward.end - forward.start,
+        }
+    }
+
+    /// Appends `symbol` to the pattern of `interval`.
+    pub fn extend_right(&self, interval: BiInterval, symbol: S) -> BiInterval {
+        let (reverse, forward) =
+            extend(&self.reverse, interval.reverse(), interval.forward, symbol);
+        BiInterval {
+            forward,
+            reverse: reverse.start,
+            len: reverse.end - reverse.start,
+        }
+    }
+
+    /// The positions of the occurrences of `interval`’s pattern, in no
+    /// particular order.
+    pub fn locate(&self, interval: BiInterval) -> Vec<u64> {
+        interval.forward().map(|row| self.forward.suffix_at(row)).collect()
+    }
+}
+
+// Extends the pattern whose rows in `index` are `rows` by `symbol` on
+// the left, returning its new rows there and the new start of its rows
+// in the other index, which was `other_start`. Suffixes in `rows`
+// preceded by smaller symbols, or the marker, sort first in the other.
+fn extend<S: Symbol>(index: &FmIndex<S>, rows: Range<u64>, other_start: u64,
+                     symbol: S) -> (Range<u64>, u64) {
+    let shifted = symbol.to_u64() + 1;
+    let smaller = index.bwt().count_less_range(rows.start, rows.end, shifted);
+    (index.extend_left(rows, symbol), other_start + smaller)
+}
+
+impl<S: Symbol> SpaceUsage for BidirectionalFmIndex<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.forward.heap_bytes() + self.reverse.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::{quickcheck, TestResult};
+
+    #[test]
+    fn empty_text() {
+        let index = BidirectionalFmIndex::<u8>::new(&[], 2);
+        assert_eq!(1, index.full().len());
+        assert!(index.extend_right(index.full(), 7).is_empty());
+    }
+
+    #[test]
+    fn grow_both_ways() {
+        let index = BidirectionalFmIndex::new(b"mississippi", 3);
+
+        let s = index.search(b"s");
+        assert_eq!(4, s.len());
+        let ss = index.extend_right(s, b's');
+        assert_eq!(2, ss.len());
+        let iss = index.extend_left(ss, b'i');
+        let issi = index.extend_right(iss, b'i');
+        assert_eq!(index.search(b"issi"), issi);
+
+        let mut found = index.locate(issi);
+        found.sort();
+        assert_eq!(vec![1, 4], found);
+
+        assert!(index.extend_left(issi, b'p').is_empty());
+    }
+
+    #[test]
+    fn qc_extensions_match_search() {
+        // Each step extends left if the bit is set, right otherwise.
+        fn prop(text: Vec<u8>, steps: Vec<(bool, u8)>) -> TestResult {
+            let text: Vec<u32> = text.into_iter().map(|b| b as u32 % 3).collect();
+            let index = BidirectionalFmIndex::new(&text, 4);
+
+            let mut pattern = Vec::new();
+            let mut interval = index.full();
+            for (left, symbol) in steps.into_iter().take(6) {
+                let symbol = symbol as u32 % 3;
+                if left {
+                    pattern.insert(0, symbol);
+                    interval = index.extend_left(interval, symbol);
+                } else {
+                    pattern.push(symbol);
+                    interval = index.extend_right(interval, symbol);
+                }
+
+                let reversed: Vec<u32> = pattern.iter().rev().cloned().collect();
+                let forward = index.forward().backward_search(&pattern);
+                let reverse = index.reverse().backward_search(&reversed);
+                if interval.is_empty() {
+                    return TestResult::from_bool(forward.start == forward.end);
+                }
+                if interval.forward() != forward || interval.reverse() != reverse {
+                    return TestResult::failed();
+                }
+            }
+
+            TestResult::passed()
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<(bool, u8)>) -> TestResult);
+    }
+}
@@ -0,0 +1,208 @@
+use std::ops::Range;
+
+use bit_vec::Tombstones;
+use space_usage::SpaceUsage;
+use super::{FmIndex, RecordIndex, Symbol};
+
+/// An [`FmIndex`](struct.FmIndex.html) over separator-delimited records
+/// with a deletion overlay, so records can be removed without
+/// reindexing the text each time.
+///
+/// Deleting a record marks it in a
+/// [`Tombstones`](../bit_vec/struct.Tombstones.html) bitmap, and `locate`
+/// and `count` drop the occurrences that touch a deleted record. The
+/// index itself is untouched, so the backward search still finds those
+/// occurrences and each is located before being dropped;
+/// [`compact`](#method.compact) reindexes the live records once enough
+/// have gone.
+///
+/// Compacting joins the live records with the separator, so a pattern
+/// without the separator is counted the same before and after.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::DeletableFmIndex;
+///
+/// let mut index = DeletableFmIndex::new(b"cat\ndog\ncattle\nbat", b'\n', 4);
+/// assert_eq!(3, index.count(b"at"));
+///
+/// assert!(index.delete_record(2));
+/// assert_eq!(2, index.count(b"at"));
+/// assert_eq!(vec![5], index.locate(b"og"));
+/// assert_eq!(1, index.count(b"cat"));
+///
+/// let compacted = index.compact(4);
+/// assert_eq!(3, compacted.record_count());
+/// assert_eq!(b"bat".to_vec(), compacted.extract_record(2));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeletableFmIndex<S: Symbol = u8> {
+    index: FmIndex<S>,
+    records: RecordIndex,
+    separator: S,
+    deleted: Tombstones,
+}
+
+impl<S: Symbol> DeletableFmIndex<S> {
+    /// Indexes `text`, cut into records at each `separator`, sampling
+    /// every `sample_rate`th position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is 0.
+    pub fn new(text: &[S], separator: S, sample_rate: usize) -> Self {
+        let records = RecordIndex::from_text(text, separator);
+        DeletableFmIndex {
+            index: FmIndex::new(text, sample_rate),
+            deleted: Tombstones::new(records.record_count()),
+            records,
+            separator,
+        }
+    }
+
+    /// The index underneath, deleted records and all.
+    pub fn index(&self) -> &FmIndex<S> {
+        &self.index
+    }
+
+    /// The records of the indexed text, deleted ones included.
+    pub fn records(&self) -> &RecordIndex {
+        &self.records
+    }
+
+    /// The deletion overlay, indexed by record number.
+    pub fn tombstones(&self) -> &Tombstones {
+        &self.deleted
+    }
+
+    /// The number of live records.
+    pub fn record_count(&self) -> u64 {
+        self.deleted.live_count()
+    }
+
+    /// Deletes the `record`th record of the indexed text, returning
+    /// whether it was live.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `record >= self.records().record_count()`.
+    pub fn delete_record(&mut self, record: u64) -> bool {
+        self.deleted.delete(record)
+    }
+
+    /// Is the `record`th record of the indexed text deleted?
+    ///
+    /// # Panics
+    ///
+    /// Panics if `record >= self.records().record_count()`.
+    pub fn is_deleted(&self, record: u64) -> bool {
+        self.deleted.is_deleted(record)
+    }
+
+    // Do the positions in `range` all fall in live records?
+    fn is_live(&self, range: Range<u64>) -> bool {
+        let first = self.records.record_of(range.start);
+        let last = self.records.record_of(range.end.saturating_sub(1).max(range.start));
+        (first ..= last).all(|record| !self.is_deleted(record))
+    }
+
+    /// The positions in the indexed text where `pattern` occurs within
+    /// live records, in no particular order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is count-only.
+    pub fn locate(&self, pattern: &[S]) -> Vec<u64> {
+        let len = pattern.len() as u64;
+        let mut found = self.index.locate(pattern);
+        if self.deleted.deleted_count() > 0 {
+            found.retain(|&position| self.is_live(position .. position + len));
+        }
+        found
+    }
+
+    /// The number of occurrences of `pattern` within live records.
+    ///
+    /// With no deletions this is a plain count; otherwise each
+    /// occurrence is located.
+    ///
+    /// # Panics
+    ///
+    /// Panics if records have been deleted and the index is count-only.
+    pub fn count(&self, pattern: &[S]) -> u64 {
+        if self.deleted.deleted_count() == 0 {
+            self.index.count(pattern)
+        } else {
+            self.locate(pattern).len() as u64
+        }
+    }
+
+    /// Recovers the `index`th live record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.record_count()` or the index is
+    /// count-only.
+    pub fn extract_record(&self, index: u64) -> Vec<S> {
+        let record = self.deleted.live_id(index)
+            .expect("DeletableFmIndex::extract_record: out of bounds");
+        self.index.extract(self.records.record(record))
+    }
+
+    /// Reindexes the live records, joined with the separator, sampling
+    /// every `sample_rate`th position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is 0 or the index is count-only.
+    pub fn compact(&self, sample_rate: usize) -> Self {
+        let mut text = Vec::with_capacity(self.index.len() as usize);
+        for (i, record) in self.deleted.live_ids().enumerate() {
+            if i > 0 { text.push(self.separator); }
+            text.extend(self.index.extract(self.records.record(record)));
+        }
+
+        Self::new(&text, self.separator, sample_rate)
+    }
+}
+
+impl<S: Symbol> SpaceUsage for DeletableFmIndex<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.index.heap_bytes() + self.records.heap_bytes() + self.deleted.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn qc_matches_compacted() {
+        fn prop(text: Vec<u8>, deletes: Vec<u8>, pattern: Vec<u8>) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 4).collect();
+            let pattern: Vec<u8> = pattern.into_iter().take(3).map(|b| b % 3 + 1).collect();
+            let mut index = DeletableFmIndex::new(&text, 0, 3);
+            let records = index.records().record_count();
+            for &record in &deletes {
+                index.delete_record(record as u64 % records);
+            }
+            let compacted = index.compact(3);
+
+            let live: Vec<&[u8]> = text.split(|&b| b == 0).enumerate()
+                .filter(|&(i, _)| !index.is_deleted(i as u64))
+                .map(|(_, record)| record)
+                .collect();
+
+            index.record_count() == live.len() as u64
+                && (0 .. live.len()).all(|i| index.extract_record(i as u64) == live[i])
+                && (pattern.is_empty() || index.count(&pattern) == compacted.count(&pattern))
+                && index.locate(&pattern).len() as u64 == index.count(&pattern)
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>, Vec<u8>) -> bool);
+    }
+}
@@ -0,0 +1,852 @@
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Range;
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use dump::Snapshot;
+use int_vec::{IntVec, IntVector};
+use persist::{self, Component, Persist};
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9, SelectSupport};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use wavelet::WaveletMatrix;
+use super::{suffix_array, Sampling, SamplingPolicy, Symbol, TextSampling};
+
+/// An FM-index: a compressed full-text index that counts and locates
+/// the occurrences of a pattern, and recovers any part of the text.
+///
+/// The Burrows–Wheeler transform of the text is kept in a
+/// [`WaveletMatrix`](../wavelet/struct.WaveletMatrix.html), so symbols
+/// may be bytes, `u16`s or `u32`s and the alphabet can be large. Some
+/// suffixes are sampled, recording their text positions; `locate` and
+/// `extract` walk the transform to the nearest of those. By default
+/// every `sample_rate`th text position is sampled, so walks take fewer
+/// than `sample_rate` steps, and a
+/// [`SamplingPolicy`](trait.SamplingPolicy.html) chooses otherwise.
+/// Fewer samples save space at the cost of those queries; `count` is
+/// unaffected.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::FmIndex;
+///
+/// let index = FmIndex::new(b"abracadabra", 4);
+/// assert_eq!(2, index.count(b"abra"));
+///
+/// let mut found = index.locate(b"abra");
+/// found.sort();
+/// assert_eq!(vec![0, 7], found);
+///
+/// assert_eq!(b"cad".to_vec(), index.extract(4 .. 7));
+/// ```
+#[derive(Clone, Debug)]
+pub struct FmIndex<S: Symbol = u8> {
+    bwt: WaveletMatrix,
+    // Marks the rows of the sampled suffixes.
+    sampled_rows: Rank9<BitVector<u64>>,
+    // The suffix positions of the marked rows, in row order.
+    positions: IntVector<u64>,
+    // Marks the sampled text positions, including the end of the text.
+    sampled_positions: Select9<BitVector<u64>>,
+    // The rows of the marked positions, in text order.
+    rows: IntVector<u64>,
+    sampling: Option<Sampling>,
+    marker: PhantomData<S>,
+}
+
+impl<S: Symbol> FmIndex<S> {
+    /// Indexes `text`, sampling every `sample_rate`th position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is 0.
+    pub fn new(text: &[S], sample_rate: usize) -> Self {
+        Self::with_sampling(text, &TextSampling::new(sample_rate))
+    }
+
+    /// Indexes `text`, sampling the suffixes `policy` chooses.
+    pub fn with_sampling<P>(text: &[S], policy: &P) -> Self
+        where P: SamplingPolicy + ?Sized {
+
+        let suffixes = suffix_array(text);
+        let bwt = transform(text, &suffixes);
+
+        let mut samples = Vec::new();
+        for (row, &suffix) in suffixes.iter().enumerate() {
+            let at_run_boundary = (row > 0 && bwt[row - 1] != bwt[row])
+                || (row + 1 < bwt.len() && bwt[row + 1] != bwt[row]);
+            // Row 0 is the empty suffix at the end of the text.
+            if row == 0 || suffix == 0 || policy.is_sampled(row as u64, suffix, at_run_boundary) {
+                samples.push((row as u64, suffix));
+            }
+        }
+
+        Self::from_samples(WaveletMatrix::new(bwt), samples, policy.parameters())
+    }
+
+    /// Builds the index over a transform from its samples, as pairs of
+    /// a row and its suffix’s position in row order.
+    fn from_samples(bwt: WaveletMatrix, mut samples: Vec<(u64, u64)>,
+                    sampling: Option<Sampling>) -> Self {
+        let len = bwt.len();
+        let position_bits = bits_to_represent(len);
+
+        let mut sampled_rows = BitVector::with_fill(len, false);
+        let mut positions = IntVector::with_capacity(position_bits, samples.len() as u64);
+        for &(row, position) in &samples {
+            sampled_rows.set_bit(row, true);
+            positions.push(position);
+        }
+
+        samples.sort_unstable_by_key(|&(_, position)| position);
+        let mut sampled_positions = BitVector::with_fill(len, false);
+        let mut rows = IntVector::with_capacity(position_bits, samples.len() as u64);
+        for &(row, position) in &samples {
+            sampled_positions.set_bit(position, true);
+            rows.push(row);
+        }
+
+        FmIndex {
+            bwt,
+            sampled_rows: Rank9::new(sampled_rows),
+            positions,
+            sampled_positions: Select9::new(Rank9::new(sampled_positions)),
+            rows,
+            sampling,
+            marker: PhantomData,
+        }
+    }
+
+    /// Samples the index again as `policy` chooses, without sorting the
+    /// suffixes again.
+    ///
+    /// One walk of the transform back from the end of the text recovers
+    /// the position of every suffix, taking as long as extracting the
+    /// whole text, so this is the way to change an index’s rate after
+    /// it was built or loaded. It works on a count-only index too,
+    /// giving it samples.
+    ///
+    /// ```
+    /// use succinct::text::{FmIndex, Sampling, TextSampling};
+    ///
+    /// let sparse = FmIndex::new(b"abracadabra", 8);
+    /// let dense = sparse.resample(&TextSampling::new(2));
+    /// assert_eq!(Some(Sampling::Text(2)), dense.sampling());
+    /// assert!(dense.samples() > sparse.samples());
+    /// assert_eq!(b"cad".to_vec(), dense.extract(4 .. 7));
+    /// ```
+    pub fn resample<P>(&self, policy: &P) -> Self
+        where P: SamplingPolicy + ?Sized {
+
+        let len = self.bwt.len();
+        let at_run_boundary = |row: u64| {
+            let symbol = self.bwt.get(row);
+            (row > 0 && self.bwt.get(row - 1) != symbol)
+                || (row + 1 < len && self.bwt.get(row + 1) != symbol)
+        };
+
+        // Row 0 holds the empty suffix, and each step back from the
+        // suffix at a position reaches the one before it.
+        let mut samples = vec![(0, self.len())];
+        let mut row = 0;
+        for position in (0 .. self.len()).rev() {
+            row = self.lf(row);
+            if position == 0 || policy.is_sampled(row, position, at_run_boundary(row)) {
+                samples.push((row, position));
+            }
+        }
+        samples.sort_unstable();
+
+        Self::from_samples(self.bwt.clone(), samples, policy.parameters())
+    }
+
+    /// Indexes `text` for `count` alone.
+    ///
+    /// This keeps just the transform, without the samples that `locate`,
+    /// `suffix_at` and `extract` need; those panic on the result. It
+    /// suits callers that only ask how often patterns occur.
+    ///
+    /// ```
+    /// use succinct::text::FmIndex;
+    ///
+    /// let index = FmIndex::count_only(b"abracadabra");
+    /// assert!(index.is_count_only());
+    /// assert_eq!(2, index.count(b"abra"));
+    /// ```
+    pub fn count_only(text: &[S]) -> Self {
+        let bwt = transform(text, &suffix_array(text));
+        FmIndex {
+            bwt: WaveletMatrix::new(bwt),
+            sampled_rows: Rank9::new(BitVector::new()),
+            positions: IntVector::new(1),
+            sampled_positions: Select9::new(Rank9::new(BitVector::new())),
+            rows: IntVector::new(1),
+            sampling: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// Was the index built by [`count_only`](#method.count_only)?
+    pub fn is_count_only(&self) -> bool {
+        // A full index samples at least the end of the text.
+        self.sampled_rows.bit_len() == 0
+    }
+
+    /// The length of the text.
+    pub fn len(&self) -> u64 {
+        self.bwt.len() - 1
+    }
+
+    /// Is the text empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of sampled suffixes.
+    pub fn samples(&self) -> u64 {
+        self.positions.len()
+    }
+
+    /// The parameters of the policy that chose the samples, if it was
+    /// one of the crate’s; `None` for a policy of the caller’s own or a
+    /// count-only index.
+    pub fn sampling(&self) -> Option<Sampling> {
+        self.sampling
+    }
+
+    /// The Burrows–Wheeler transform, with the marker as 0 and symbols
+    /// shifted up by one.
+    pub(crate) fn bwt(&self) -> &WaveletMatrix {
+        &self.bwt
+    }
+
+    /// The number of rows whose suffixes start with a symbol less than
+    /// `symbol`, where the marker is 0 and symbols are shifted up by one.
+    pub(crate) fn rows_before(&self, symbol: u64) -> u64 {
+        self.bwt.count_less(self.bwt.len(), symbol)
+    }
+
+    /// Maps the row range of the suffixes starting with some string to
+    /// that of the suffixes starting with `symbol` followed by it.
+    pub fn extend_left(&self, rows: Range<u64>, symbol: S) -> Range<u64> {
+        let symbol = symbol.to_u64() + 1;
+        let before = self.rows_before(symbol);
+        let start = before + self.bwt.rank_before(rows.start, symbol);
+        let end = before + self.bwt.rank_before(rows.end, symbol);
+        start .. end
+    }
+
+    /// The range of rows, in suffix order, of the suffixes that start
+    /// with `pattern`.
+    ///
+    /// The range is empty if the pattern doesn’t occur. The empty
+    /// pattern matches every suffix, including the empty one at the end.
+    pub fn backward_search(&self, pattern: &[S]) -> Range<u64> {
+        let mut rows = 0 .. self.bwt.len();
+        for &symbol in pattern.iter().rev() {
+            rows = self.extend_left(rows, symbol);
+            if rows.start >= rows.end { return rows.start .. rows.start; }
+        }
+        rows
+    }
+
+    /// The number of occurrences of `pattern` in the text.
+    pub fn count(&self, pattern: &[S]) -> u64 {
+        let rows = self.backward_search(pattern);
+        rows.end - rows.start
+    }
+
+    /// The positions where `pattern` occurs, in no particular order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is count-only.
+    pub fn locate(&self, pattern: &[S]) -> Vec<u64> {
+        assert!(!self.is_count_only(), "FmIndex::locate: index is count-only");
+        self.backward_search(pattern).map(|row| self.suffix_at(row)).collect()
+    }
+
+    /// The row ranges of the strings that differ from `pattern` in at
+    /// most `mismatches` positions and occur in the text.
+    ///
+    /// Different strings of the same length start disjoint sets of
+    /// suffixes, so the ranges don’t overlap. The search backtracks over
+    /// the symbols the transform holds in each range; it can visit a
+    /// number of ranges exponential in `mismatches`, so it suits small
+    /// error bounds. Insertions and deletions aren’t considered.
+    pub fn approx_search(&self, pattern: &[S], mismatches: usize)
+                         -> Vec<Range<u64>> {
+        let mut result = Vec::new();
+        self.approx_search_from(pattern, mismatches, 0 .. self.bwt.len(),
+                                &mut result);
+        result
+    }
+
+    // Matches `pattern` leftward from the suffixes in `rows`.
+    fn approx_search_from(&self, pattern: &[S], mismatches: usize,
+                          rows: Range<u64>, result: &mut Vec<Range<u64>>) {
+        let (&wanted, rest) = match pattern.split_last() {
+            Some(split) => split,
+            None => {
+                result.push(rows);
+                return;
+            }
+        };
+
+        if mismatches == 0 {
+            let mut rows = rows;
+            for &symbol in pattern.iter().rev() {
+                rows = self.extend_left(rows, symbol);
+                if rows.start >= rows.end { return; }
+            }
+            result.push(rows);
+            return;
+        }
+
+        for (shifted, _) in self.bwt.distinct_values(rows.start, rows.end) {
+            // Skip the marker, which no pattern symbol matches.
+            if shifted == 0 { continue; }
+
+            let symbol = S::from_u64(shifted - 1);
+            let cost = (symbol != wanted) as usize;
+            let next = self.extend_left(rows.clone(), symbol);
+            self.approx_search_from(rest, mismatches - cost, next, result);
+        }
+    }
+
+    /// The number of positions where a string occurs that differs from
+    /// `pattern` in at most `mismatches` positions.
+    ///
+    /// ```
+    /// use succinct::text::FmIndex;
+    ///
+    /// let index = FmIndex::new(b"the cat sat on the mat", 4);
+    /// assert_eq!(0, index.count_approx(b"bat", 0));
+    /// assert_eq!(3, index.count_approx(b"bat", 1));
+    /// ```
+    pub fn count_approx(&self, pattern: &[S], mismatches: usize) -> u64 {
+        self.approx_search(pattern, mismatches).into_iter()
+            .map(|rows| rows.end - rows.start)
+            .sum()
+    }
+
+    /// The positions where a string occurs that differs from `pattern`
+    /// in at most `mismatches` positions, in no particular order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is count-only.
+    pub fn locate_approx(&self, pattern: &[S], mismatches: usize) -> Vec<u64> {
+        assert!(!self.is_count_only(),
+                "FmIndex::locate_approx: index is count-only");
+        self.approx_search(pattern, mismatches).into_iter()
+            .flatten()
+            .map(|row| self.suffix_at(row))
+            .collect()
+    }
+
+    // Steps from a row to the row of the suffix one position earlier.
+    fn lf(&self, row: u64) -> u64 {
+        let symbol = self.bwt.get(row);
+        self.rows_before(symbol) + self.bwt.rank_before(row, symbol)
+    }
+
+    /// Steps from a row to the row of the suffix one position later,
+    /// undoing `lf`: the row is that of the occurrence in the transform
+    /// of the symbol the suffix starts with.
+    pub(crate) fn psi(&self, row: u64) -> u64 {
+        use internal::search::binary_search_function;
+
+        // The last symbol whose rows start at or before `row`.
+        let limit = 1 << self.bwt.levels();
+        let symbol = binary_search_function(0, limit, row + 1, |s| self.rows_before(s + 1))
+            .expect("FmIndex::psi: out of bounds");
+        self.bwt.select(row - self.rows_before(symbol), symbol)
+            .expect("FmIndex::psi: inconsistent transform")
+    }
+
+    /// The text position of the suffix at `row` in suffix order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row > self.len()` or the index is count-only.
+    pub fn suffix_at(&self, row: u64) -> u64 {
+        assert!(!self.is_count_only(), "FmIndex::suffix_at: index is count-only");
+        let mut row = row;
+        let mut steps = 0;
+        while !self.sampled_rows.get_bit(row) {
+            row = self.lf(row);
+            steps += 1;
+        }
+
+        self.positions.get(self.sampled_rows.rank1(row) - 1) + steps
+    }
+
+    /// Recovers the text in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or the index is count-only.
+    pub fn extract(&self, range: Range<u64>) -> Vec<S> {
+        assert!(range.start <= range.end && range.end <= self.len(),
+                "FmIndex::extract: out of bounds");
+        assert!(!self.is_count_only(), "FmIndex::extract: index is count-only");
+
+        // Start from the first sample at or after the end and walk back;
+        // the end of the text is always sampled.
+        let sample = match range.end {
+            0 => 0,
+            end => self.sampled_positions.rank1(end - 1),
+        };
+        let mut position = self.sampled_positions.select1(sample)
+            .expect("FmIndex::extract: end of text not sampled");
+        let mut row = self.rows.get(sample);
+
+        let mut result = Vec::with_capacity((range.end - range.start) as usize);
+        while position > range.start {
+            let symbol = self.bwt.get(row);
+            position -= 1;
+            if position < range.end {
+                result.push(S::from_u64(symbol - 1));
+            }
+            row = self.lf(row);
+        }
+
+        result.reverse();
+        result
+    }
+
+    /// Recovers up to `before` symbols before `position` and `after`
+    /// from it on, clipped to the text, returning where they start.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.len()` or the index is count-only.
+    pub fn extract_around(&self, position: u64, before: u64, after: u64) -> (u64, Vec<S>) {
+        assert!(position <= self.len(), "FmIndex::extract_around: out of bounds");
+        let start = position.saturating_sub(before);
+        let end = position.saturating_add(after).min(self.len());
+        (start, self.extract(start .. end))
+    }
+
+    /// Recovers the record holding `position` with `before` records
+    /// before it and `after` after, like `grep -B` and `-A` for lines,
+    /// returning the range they cover.
+    ///
+    /// `separators` marks the positions of the separators between
+    /// records, such as newlines, so it must be as long as the text.
+    /// The range runs from just after a separator, or the start of the
+    /// text, up to but not including one, or the end of the text. A
+    /// separator at `position` ends its record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.len()`, `separators` is too short, or
+    /// the index is count-only.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use succinct::{BitVector, Rank9, Select9};
+    /// use succinct::text::FmIndex;
+    ///
+    /// let text = b"one\ntwo\nthree\nfour";
+    /// let newlines: Vec<bool> = text.iter().map(|&b| b == b'\n').collect();
+    /// let separators = Select9::new(Rank9::new(BitVector::<u64>::from(&newlines[..])));
+    /// let index = FmIndex::new(text, 4);
+    ///
+    /// let position = index.locate(b"hr")[0];
+    /// assert_eq!((8 .. 13, b"three".to_vec()), index.extract_records(position, 0, 0, &separators));
+    /// assert_eq!((4 .. 18, b"two\nthree\nfour".to_vec()),
+    ///            index.extract_records(position, 1, 5, &separators));
+    /// ```
+    pub fn extract_records<B>(&self, position: u64, before: u64, after: u64, separators: &B)
+                              -> (Range<u64>, Vec<S>)
+        where B: BitRankSupport + Select1Support {
+
+        assert!(position <= self.len(), "FmIndex::extract_records: out of bounds");
+
+        // The record holding `position` follows this many separators.
+        let record = if position == 0 {0} else {separators.rank1(position - 1)};
+        let start = match record.saturating_sub(before) {
+            0 => 0,
+            first => separators.select1(first - 1).expect("FmIndex: bad separators") + 1,
+        };
+        let end = record.checked_add(after)
+            .and_then(|last| separators.select1(last))
+            .map_or(self.len(), |end| end.min(self.len()));
+
+        (start .. end, self.extract(start .. end))
+    }
+}
+
+// The symbol before each suffix, or the marker, shifted up.
+fn transform<S: Symbol>(text: &[S], suffixes: &[u64]) -> Vec<u64> {
+    suffixes.iter().map(|&suffix| match suffix {
+        0 => 0,
+        _ => text[suffix as usize - 1].to_u64() + 1,
+    }).collect()
+}
+
+/// The transform, as a `WaveletMatrix` writes it, then the sampled rows,
+/// their positions, the sampled positions and their rows, then a byte
+/// saying whether the sampling parameters follow, and them.
+impl<S: Symbol> Persist for FmIndex<S> {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        persist::write_part(&self.bwt, sink)?;
+        persist::write_part(&self.sampled_rows, sink)?;
+        persist::write_part(&self.positions, sink)?;
+        persist::write_part(&self.sampled_positions, sink)?;
+        persist::write_part(&self.rows, sink)?;
+        match self.sampling {
+            Some(sampling) => {
+                sink.write_u8(1)?;
+                sampling.write_to(sink)
+            }
+            None => sink.write_u8(0),
+        }
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let who = "FmIndex::read_from";
+        let bad = |what: &str| io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("{}: could not load: {}", who, what));
+
+        let bwt: WaveletMatrix = persist::read_part(&mut bytes)?;
+        let sampled_rows: Rank9<BitVector<u64>> = persist::read_part(&mut bytes)?;
+        let positions: IntVector<u64> = persist::read_part(&mut bytes)?;
+        let sampled_positions: Select9<BitVector<u64>> = persist::read_part(&mut bytes)?;
+        let rows: IntVector<u64> = persist::read_part(&mut bytes)?;
+        let sampling = match bytes.read_u8()? {
+            0 => { persist::expect_end(who, bytes)?; None }
+            1 => Some(Sampling::read_from(bytes)?),
+            _ => return Err(bad("bad flag")),
+        };
+
+        // The transform holds the marker and the symbols shifted up by
+        // one, and a full index samples the end of the text.
+        let len = bwt.len();
+        if len == 0 || bwt.levels() > 8 * mem::size_of::<S>() + 1 {
+            return Err(bad("malformed transform"));
+        }
+        let samples = positions.len();
+        let count_only = sampled_rows.bit_len() == 0;
+        let sizes_agree = if count_only {
+            samples == 0 && sampled_positions.bit_len() == 0 && rows.is_empty()
+        } else {
+            sampled_rows.bit_len() == len
+                && sampled_positions.bit_len() == len
+                && samples > 0
+                && ones(&sampled_rows) == samples
+                && ones(&sampled_positions) == samples
+                && rows.len() == samples
+        };
+        if !sizes_agree || positions.iter().chain(rows.iter()).any(|value| value >= len) {
+            return Err(bad("inconsistent samples"));
+        }
+
+        Ok(FmIndex {
+            bwt,
+            sampled_rows,
+            positions,
+            sampled_positions,
+            rows,
+            sampling,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<S: Symbol> Component for FmIndex<S> {
+    fn tag() -> String {
+        format!("FmIndex<u{}>", 8 * mem::size_of::<S>())
+    }
+}
+
+fn ones<B: BitRankSupport>(bits: &B) -> u64 {
+    match bits.limit() {
+        0 => 0,
+        len => bits.rank1(len - 1),
+    }
+}
+
+impl<S: Symbol> SpaceUsage for FmIndex<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.bwt.heap_bytes()
+            + self.sampled_rows.heap_bytes()
+            + self.positions.heap_bytes()
+            + self.sampled_positions.heap_bytes()
+            + self.rows.heap_bytes()
+    }
+}
+
+/// Lists the symbols of the text in order.
+///
+/// # Panics
+///
+/// The iterator panics if the index is count-only.
+impl<S: Symbol> Snapshot for FmIndex<S> {
+    type Item = S;
+    type Iter<'a> = FmIndexText<'a, S> where Self: 'a;
+
+    fn dump_iter(&self) -> FmIndexText<'_, S> {
+        FmIndexText { index: self, next: 0, buffer: Vec::new().into_iter() }
+    }
+}
+
+/// The symbols of the text an [`FmIndex`](struct.FmIndex.html) holds,
+/// in order.
+///
+/// Each walk of the transform runs backwards, so the text is recovered
+/// a chunk at a time and handed out from the front of each.
+#[derive(Clone, Debug)]
+pub struct FmIndexText<'a, S: Symbol + 'a> {
+    index: &'a FmIndex<S>,
+    // The first position past the buffer.
+    next: u64,
+    buffer: ::std::vec::IntoIter<S>,
+}
+
+const TEXT_CHUNK: u64 = 4096;
+
+impl<'a, S: Symbol> Iterator for FmIndexText<'a, S> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<S> {
+        if let Some(symbol) = self.buffer.next() { return Some(symbol); }
+        if self.next >= self.index.len() { return None; }
+
+        let end = self.next.saturating_add(TEXT_CHUNK).min(self.index.len());
+        self.buffer = self.index.extract(self.next .. end).into_iter();
+        self.next = end;
+        self.buffer.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.buffer.len() + (self.index.len() - self.next) as usize;
+        (len, Some(len))
+    }
+}
+
+impl<'a, S: Symbol> ExactSizeIterator for FmIndexText<'a, S> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use text::{RowSampling, RunSampling};
+    use quickcheck::{quickcheck, TestResult};
+
+    fn naive_locate<S: Symbol>(text: &[S], pattern: &[S]) -> Vec<u64> {
+        (0 .. text.len() + 1)
+            .filter(|&i| text[i ..].starts_with(pattern))
+            .map(|i| i as u64)
+            .collect()
+    }
+
+    #[test]
+    fn text_snapshot() {
+        let text: Vec<u16> = (0 .. 10_000u32).map(|i| (i * i % 251) as u16).collect();
+        let index = FmIndex::new(&text, 16);
+        assert_eq!(text.len(), index.dump_iter().len());
+        assert!(index.dump_iter().eq(text.iter().cloned()));
+        assert_eq!(0, FmIndex::new(b"", 4).dump_iter().count());
+    }
+
+    #[test]
+    fn empty_text() {
+        let index = FmIndex::<u8>::new(&[], 3);
+        assert!(index.is_empty());
+        assert_eq!(0, index.count(b"a"));
+        assert_eq!(1, index.count(b""));
+        assert_eq!(Vec::<u8>::new(), index.extract(0 .. 0));
+    }
+
+    #[test]
+    fn banana() {
+        let index = FmIndex::new(b"banana", 2);
+        assert_eq!(3, index.count(b"a"));
+        assert_eq!(2, index.count(b"ana"));
+        assert_eq!(0, index.count(b"nab"));
+
+        let mut found = index.locate(b"an");
+        found.sort();
+        assert_eq!(vec![1, 3], found);
+
+        assert_eq!(b"banana".to_vec(), index.extract(0 .. 6));
+        assert_eq!(b"nan".to_vec(), index.extract(2 .. 5));
+    }
+
+    #[test]
+    fn large_alphabet() {
+        let text: Vec<u32> = (0 .. 2000).map(|i| (i * 7919) % 100_003).collect();
+        let index = FmIndex::new(&text, 16);
+
+        assert_eq!(1, index.count(&text[500 .. 503]));
+        assert_eq!(vec![500], index.locate(&text[500 .. 503]));
+        assert_eq!(0, index.count(&[100_002, 1]));
+        assert_eq!(&text[1234 .. 1300], &index.extract(1234 .. 1300)[..]);
+    }
+
+    #[test]
+    fn approx() {
+        let index = FmIndex::new(b"banana", 2);
+        assert_eq!(0, index.count_approx(b"nab", 0));
+        assert_eq!(1, index.count_approx(b"nab", 1));
+        assert_eq!(4, index.count_approx(b"xyz", 3));
+        assert_eq!(7, index.count_approx(b"", 1));
+
+        let mut found = index.locate_approx(b"nna", 1);
+        found.sort();
+        assert_eq!(vec![1, 3], found);
+    }
+
+    #[test]
+    fn count_only() {
+        let text = b"mississippi";
+        let full = FmIndex::new(text, 2);
+        let counter = FmIndex::count_only(text);
+
+        for pattern in &[&b"ss"[..], b"i", b"ippi", b"", b"sis", b"x"] {
+            assert_eq!(full.count(pattern), counter.count(pattern));
+        }
+        assert!(!full.is_count_only());
+        assert!(counter.heap_bytes() < full.heap_bytes());
+    }
+
+    #[test]
+    #[should_panic]
+    fn count_only_locate() {
+        FmIndex::count_only(b"abc").locate(b"b");
+    }
+
+    #[test]
+    fn context() {
+        let text = b"\nab\n\ncd";
+        let index = FmIndex::new(text, 3);
+        let newlines: Vec<bool> = text.iter().map(|&b| b == b'\n').collect();
+        let separators = Select9::new(Rank9::new(BitVector::<u64>::from(&newlines[..])));
+
+        assert_eq!((0, b"\nab".to_vec()), index.extract_around(1, 5, 2));
+        assert_eq!((5, b"cd".to_vec()), index.extract_around(7, 2, 9));
+
+        assert_eq!((0 .. 0, vec![]), index.extract_records(0, 0, 0, &separators));
+        assert_eq!((1 .. 3, b"ab".to_vec()), index.extract_records(3, 0, 0, &separators));
+        assert_eq!((4 .. 4, vec![]), index.extract_records(4, 0, 0, &separators));
+        assert_eq!((5 .. 7, b"cd".to_vec()), index.extract_records(7, 0, 0, &separators));
+        assert_eq!((0 .. 7, text.to_vec()), index.extract_records(2, 1, 2, &separators));
+        assert_eq!((4 .. 7, b"\ncd".to_vec()), index.extract_records(4, 0, !0, &separators));
+    }
+
+    #[test]
+    #[should_panic]
+    fn extract_oob() {
+        FmIndex::new(b"abc", 2).extract(1 .. 4);
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(text: Vec<u8>, pattern: Vec<u8>, rate: u8) -> TestResult {
+            if rate == 0 { return TestResult::discard(); }
+
+            let text: Vec<u16> = text.into_iter().map(|b| b as u16 % 3).collect();
+            let pattern: Vec<u16> = pattern.into_iter().take(4)
+                                           .map(|b| b as u16 % 3).collect();
+            let index = FmIndex::new(&text, rate as usize % 8 + 1);
+
+            let mut found = index.locate(&pattern);
+            found.sort();
+
+            TestResult::from_bool(
+                found == naive_locate(&text, &pattern)
+                    && index.count(&pattern) == found.len() as u64
+                    && index.extract(0 .. text.len() as u64) == text)
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>, u8) -> TestResult);
+    }
+
+    #[test]
+    fn qc_sampling_policies() {
+        fn prop(text: Vec<u8>, pattern: Vec<u8>, rate: u8) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 3).collect();
+            let pattern: Vec<u8> = pattern.into_iter().take(3).map(|b| b % 3).collect();
+            let rate = rate as usize % 8 + 1;
+            let expected = naive_locate(&text, &pattern);
+
+            let policies: [&dyn SamplingPolicy; 3] = [
+                &TextSampling::new(rate),
+                &RowSampling::new(rate),
+                &RunSampling::new(rate),
+            ];
+            policies.iter().all(|&policy| {
+                let index = FmIndex::with_sampling(&text, policy);
+                let mut found = index.locate(&pattern);
+                found.sort();
+                found == expected && index.extract(0 .. text.len() as u64) == text
+            })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>, u8) -> bool);
+    }
+
+    #[test]
+    fn qc_resample_matches_rebuild() {
+        fn prop(text: Vec<u8>, rate: u8, policy: u8) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 3).collect();
+            let rate = rate as usize % 8 + 1;
+            let sampling = match policy % 3 {
+                0 => Sampling::Text(rate),
+                1 => Sampling::Row(rate),
+                _ => Sampling::Run(rate),
+            };
+            let rebuilt = FmIndex::with_sampling(&text, &sampling);
+
+            [FmIndex::count_only(&text), FmIndex::new(&text, 3)].iter().all(|index| {
+                let resampled = index.resample(&sampling);
+                resampled.sampling() == Some(sampling)
+                    && resampled.positions == rebuilt.positions
+                    && resampled.rows == rebuilt.rows
+                    && (0 .. text.len() as u64 + 1).all(|row| {
+                        resampled.suffix_at(row) == rebuilt.suffix_at(row)
+                    })
+                    && resampled.extract(0 .. text.len() as u64) == text
+            })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, u8, u8) -> bool);
+    }
+
+    #[test]
+    fn qc_approx_matches_naive() {
+        fn prop(text: Vec<u8>, pattern: Vec<u8>, mismatches: u8) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 4).collect();
+            let pattern: Vec<u8> = pattern.into_iter().take(5)
+                                          .map(|b| b % 4).collect();
+            let mismatches = mismatches as usize % 3;
+            let index = FmIndex::new(&text, 3);
+
+            let naive: Vec<u64> = (0 .. text.len() + 1)
+                .filter(|&i| {
+                    text.len() - i >= pattern.len()
+                        && pattern.iter().zip(&text[i ..])
+                                  .filter(|&(a, b)| a != b).count() <= mismatches
+                })
+                .map(|i| i as u64)
+                .collect();
+
+            let mut found = index.locate_approx(&pattern, mismatches);
+            found.sort();
+            found == naive
+                && index.count_approx(&pattern, mismatches) == naive.len() as u64
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>, u8) -> bool);
+    }
+}
@@ -0,0 +1,297 @@
+use int_vec::{IntVec, IntVector};
+use internal::search::binary_search_function;
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+
+/// Packs a DNA k-mer two bits per base, the first base most significant.
+///
+/// Bases are `A`, `C`, `G` and `T`, in either case. Returns `None` if
+/// the k-mer has another symbol, such as `N`, or more than 32 bases.
+///
+/// ```
+/// use succinct::text::encode_kmer;
+///
+/// assert_eq!(Some(0b00_01_10_11), encode_kmer(b"ACgt"));
+/// assert_eq!(None, encode_kmer(b"ACNT"));
+/// ```
+pub fn encode_kmer(kmer: &[u8]) -> Option<u64> {
+    if kmer.len() > 32 { return None; }
+
+    let mut result = 0;
+    for &base in kmer {
+        result = result << 2 | base_code(base)?;
+    }
+    Some(result)
+}
+
+/// Unpacks a k-mer packed by [`encode_kmer`](fn.encode_kmer.html).
+pub fn decode_kmer(code: u64, k: usize) -> Vec<u8> {
+    (0 .. k).rev().map(|i| b"ACGT"[(code >> (2 * i) & 3) as usize]).collect()
+}
+
+fn base_code(base: u8) -> Option<u64> {
+    match base {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' => Some(3),
+        _ => None,
+    }
+}
+
+/// The distinct k-mers of some DNA sequences, with how often each occurs.
+///
+/// The k-mers are packed two bits per base and kept sorted in an
+/// [`IntVector`](../int_vec/struct.IntVector.html); a directory on their
+/// high bits, as in Elias–Fano coding, narrows each lookup to a bucket of
+/// a few entries, which is binary searched. K-mers with bases other than
+/// `ACGT` are skipped.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::KmerIndex;
+///
+/// let index = KmerIndex::new(3, b"ACGTACGTNACG");
+/// assert_eq!(4, index.len());
+/// assert_eq!(3, index.count(b"ACG"));
+/// assert!(index.contains(b"GTA"));
+/// assert!(!index.contains(b"TAA"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct KmerIndex {
+    k: usize,
+    kmers: IntVector<u64>,
+    counts: IntVector<u64>,
+    // The high bits of each k-mer select a bucket; bucket b holds
+    // `buckets[b] .. buckets[b + 1]`.
+    high_bits: usize,
+    buckets: IntVector<u64>,
+}
+
+impl KmerIndex {
+    /// Indexes the k-mers of one sequence.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is 0 or more than 32.
+    pub fn new(k: usize, sequence: &[u8]) -> Self {
+        Self::from_sequences(k, Some(sequence))
+    }
+
+    /// Indexes the k-mers of several sequences; none spans two.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is 0 or more than 32.
+    pub fn from_sequences<'a, I>(k: usize, sequences: I) -> Self
+        where I: IntoIterator<Item = &'a [u8]> {
+
+        assert!(k > 0 && k <= 32, "KmerIndex: k must be from 1 to 32");
+        let mask = if k == 32 {!0} else {(1 << (2 * k)) - 1};
+
+        let mut codes = Vec::new();
+        for sequence in sequences {
+            // The current k-mer, and how many valid bases end it.
+            let mut code = 0u64;
+            let mut valid = 0;
+            for &base in sequence {
+                match base_code(base) {
+                    Some(bits) => {
+                        code = (code << 2 | bits) & mask;
+                        valid += 1;
+                    }
+                    None => valid = 0,
+                }
+                if valid >= k { codes.push(code); }
+            }
+        }
+        codes.sort_unstable();
+
+        let kmer_bits = 2 * k;
+        let mut kmers = IntVector::new(kmer_bits);
+        let mut counts: Vec<u64> = Vec::new();
+        let mut previous = None;
+        for code in codes {
+            if previous == Some(code) {
+                *counts.last_mut().unwrap() += 1;
+            } else {
+                kmers.push(code);
+                counts.push(1);
+                previous = Some(code);
+            }
+        }
+        kmers.shrink_to_fit();
+
+        let count_bits = counts.iter().cloned().max().map_or(1, bits_to_represent);
+        let mut packed_counts = IntVector::with_capacity(count_bits,
+                                                         counts.len() as u64);
+        for count in counts {
+            packed_counts.push(count);
+        }
+
+        let high_bits = (bits_to_represent(kmers.len()) - 1).min(kmer_bits);
+        let position_bits = bits_to_represent(kmers.len());
+        let mut buckets = IntVector::with_capacity(position_bits,
+                                                   (1 << high_bits) + 1);
+        let mut entry = 0;
+        for bucket in 0 .. 1u64 << high_bits {
+            buckets.push(entry);
+            while entry < kmers.len()
+                && kmers.get(entry) >> (kmer_bits - high_bits) == bucket {
+                entry += 1;
+            }
+        }
+        buckets.push(entry);
+
+        KmerIndex {
+            k,
+            kmers,
+            counts: packed_counts,
+            high_bits,
+            buckets,
+        }
+    }
+
+    /// The length of the k-mers.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// The number of distinct k-mers.
+    pub fn len(&self) -> u64 {
+        self.kmers.len()
+    }
+
+    /// Are there no k-mers?
+    pub fn is_empty(&self) -> bool {
+        self.kmers.is_empty()
+    }
+
+    // The entry holding packed k-mer `code`, if any.
+    fn find(&self, code: u64) -> Option<u64> {
+        let bucket = code >> (2 * self.k - self.high_bits);
+        let start = self.buckets.get(bucket);
+        let limit = self.buckets.get(bucket + 1);
+
+        let entry = binary_search_function(start, limit, code,
+                                           |i| self.kmers.get(i))?;
+        if self.kmers.get(entry) == code {Some(entry)} else {None}
+    }
+
+    /// How often packed k-mer `code` occurs.
+    pub fn count_code(&self, code: u64) -> u64 {
+        self.find(code).map_or(0, |entry| self.counts.get(entry))
+    }
+
+    /// How often `kmer` occurs; 0 if it isn’t `k` valid bases.
+    pub fn count(&self, kmer: &[u8]) -> u64 {
+        if kmer.len() != self.k { return 0; }
+        encode_kmer(kmer).map_or(0, |code| self.count_code(code))
+    }
+
+    /// Does `kmer` occur?
+    pub fn contains(&self, kmer: &[u8]) -> bool {
+        self.count(kmer) > 0
+    }
+
+    /// The packed k-mers in increasing order, each with its count.
+    pub fn iter(&self) -> KmerIter<'_> {
+        KmerIter { index: self, entry: 0 }
+    }
+}
+
+/// Iterator over the entries of a [`KmerIndex`](struct.KmerIndex.html).
+pub struct KmerIter<'a> {
+    index: &'a KmerIndex,
+    entry: u64,
+}
+
+impl<'a> Iterator for KmerIter<'a> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        if self.entry == self.index.len() { return None; }
+
+        let entry = self.entry;
+        self.entry += 1;
+        Some((self.index.kmers.get(entry), self.index.counts.get(entry)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.index.len() - self.entry) as usize;
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for KmerIter<'a> {}
+
+impl SpaceUsage for KmerIndex {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.kmers.heap_bytes()
+            + self.counts.heap_bytes()
+            + self.buckets.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let index = KmerIndex::new(4, b"ACG");
+        assert!(index.is_empty());
+        assert_eq!(0, index.count(b"ACGT"));
+        assert_eq!(0, index.iter().count());
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(b"GATTACA".to_vec(),
+                   decode_kmer(encode_kmer(b"GATTACA").unwrap(), 7));
+    }
+
+    #[test]
+    fn long_kmers() {
+        let sequence: Vec<u8> = b"ACGTTGCA".iter().cycle().take(100).cloned().collect();
+        let index = KmerIndex::from_sequences(32, vec![&sequence[..], &sequence[..10]]);
+        assert_eq!(8, index.len());
+        assert_eq!(9, index.count(&sequence[8 .. 40]));
+        assert_eq!(0, index.count(&sequence[.. 31]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_k() {
+        KmerIndex::new(0, b"ACGT");
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(sequence: Vec<u8>, k: u8) -> bool {
+            let sequence: Vec<u8> = sequence.into_iter()
+                .map(|b| b"ACGTN"[b as usize % 5]).collect();
+            let k = k as usize % 6 + 1;
+            let index = KmerIndex::new(k, &sequence);
+
+            let mut naive = BTreeMap::new();
+            for window in sequence.windows(k) {
+                if let Some(code) = encode_kmer(window) {
+                    *naive.entry(code).or_insert(0) += 1;
+                }
+            }
+
+            index.iter().collect::<BTreeMap<_, _>>() == naive
+                && (0 .. 1u64 << (2 * k)).all(|code| {
+                    index.count_code(code) == naive.get(&code).cloned().unwrap_or(0)
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, u8) -> bool);
+    }
+}
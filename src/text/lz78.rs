@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use int_vec::{IntVec, IntVector};
+use internal::search::binary_search_function;
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use super::Symbol;
+
+/// An LZ78-compressed text, for workloads that mostly extract.
+///
+/// The text is parsed into phrases, each the longest earlier phrase
+/// followed by one more symbol, and stored as the trie of phrases plus
+/// where each starts. Any range can be extracted by walking up the trie
+/// from the phrases that cover it, which takes time proportional to the
+/// range plus one phrase at each end. Compressible texts have few,
+/// long phrases.
+///
+/// Pattern matching streams through the whole text, so `locate` takes
+/// linear time. Use an [`FmIndex`](struct.FmIndex.html) when searching
+/// dominates.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::Lz78Index;
+///
+/// let index = Lz78Index::new(b"abababababababab");
+/// assert!(index.phrases() < 8);
+/// assert_eq!(b"babab".to_vec(), index.extract(3 .. 8));
+/// assert_eq!(7, index.count(b"bab"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Lz78Index<S: Symbol = u8> {
+    len: u64,
+    // The parent of each trie node but the root, 0, in creation order.
+    parents: IntVector<u64>,
+    // The symbol each node adds to its parent’s phrase.
+    symbols: IntVector<u64>,
+    // The text position of each phrase. Every phrase creates the next
+    // node, except perhaps the last, which may repeat one.
+    starts: IntVector<u64>,
+    last_node: u64,
+    marker: PhantomData<S>,
+}
+
+impl<S: Symbol> Lz78Index<S> {
+    /// Parses and stores `text`.
+    pub fn new(text: &[S]) -> Self {
+        let position_bits = bits_to_represent(text.len() as u64);
+        let symbol_bits = text.iter().map(|symbol| symbol.to_u64()).max()
+                              .map_or(1, bits_to_represent);
+
+        let mut children = HashMap::new();
+        let mut parents = IntVector::new(position_bits);
+        let mut symbols = IntVector::new(symbol_bits);
+        let mut starts = IntVector::new(position_bits);
+        let mut node = 0;
+
+        for (position, symbol) in text.iter().enumerate() {
+            if node == 0 { starts.push(position as u64); }
+
+            let symbol = symbol.to_u64();
+            node = match children.get(&(node, symbol)) {
+                Some(&child) => child,
+                None => {
+                    let child = parents.len() + 1;
+                    children.insert((node, symbol), child);
+                    parents.push(node);
+                    symbols.push(symbol);
+                    0
+                }
+            };
+        }
+
+        let last_node = if node == 0 {parents.len()} else {node};
+        parents.shrink_to_fit();
+        symbols.shrink_to_fit();
+        starts.shrink_to_fit();
+
+        Lz78Index {
+            len: text.len() as u64,
+            parents,
+            symbols,
+            starts,
+            last_node,
+            marker: PhantomData,
+        }
+    }
+
+    /// The length of the text.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Is the text empty?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of phrases in the parse.
+    pub fn phrases(&self) -> u64 {
+        self.starts.len()
+    }
+
+    // The text of phrase `phrase`, appended to `buffer`.
+    fn push_phrase(&self, phrase: u64, buffer: &mut Vec<S>) {
+        let mut node = if phrase + 1 == self.phrases() {
+            self.last_node
+        } else {
+            phrase + 1
+        };
+
+        let mark = buffer.len();
+        while node != 0 {
+            buffer.push(S::from_u64(self.symbols.get(node - 1)));
+            node = self.parents.get(node - 1);
+        }
+        buffer[mark ..].reverse();
+    }
+
+    fn phrase_start(&self, phrase: u64) -> u64 {
+        if phrase == self.phrases() {self.len} else {self.starts.get(phrase)}
+    }
+
+    /// Recovers the text in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn extract(&self, range: Range<u64>) -> Vec<S> {
+        assert!(range.start <= range.end && range.end <= self.len,
+                "Lz78Index::extract: out of bounds");
+        if range.start == range.end { return Vec::new(); }
+
+        // The last phrase starting at or before the range.
+        let phrases = self.phrases();
+        let first = binary_search_function(0, phrases, range.start + 1,
+                                           |phrase| self.starts.get(phrase))
+            .unwrap_or(phrases) - 1;
+
+        let mut result = Vec::with_capacity((range.end - range.start) as usize);
+        let mut phrase = first;
+        while self.phrase_start(phrase) < range.end {
+            self.push_phrase(phrase, &mut result);
+            phrase += 1;
+        }
+
+        let skip = (range.start - self.starts.get(first)) as usize;
+        result.truncate(skip + (range.end - range.start) as usize);
+        result.drain(.. skip);
+        result
+    }
+
+    /// The positions where `pattern` occurs, in increasing order.
+    ///
+    /// This decompresses the text as it goes, matching with
+    /// Knuth–Morris–Pratt in linear time.
+    pub fn locate(&self, pattern: &[S]) -> Vec<u64> {
+        if pattern.is_empty() { return (0 .. self.len + 1).collect(); }
+
+        // failure[i] is the length of the longest proper border of the
+        // first i + 1 pattern symbols.
+        let mut failure = vec![0; pattern.len()];
+        let mut border = 0;
+        for i in 1 .. pattern.len() {
+            while border > 0 && pattern[i] != pattern[border] {
+                border = failure[border - 1];
+            }
+            if pattern[i] == pattern[border] { border += 1; }
+            failure[i] = border;
+        }
+
+        let mut result = Vec::new();
+        let mut buffer = Vec::new();
+        let mut matched = 0;
+        let mut position = 0;
+        for phrase in 0 .. self.phrases() {
+            buffer.clear();
+            self.push_phrase(phrase, &mut buffer);
+
+            for &symbol in &buffer {
+                while matched > 0 && symbol != pattern[matched] {
+                    matched = failure[matched - 1];
+                }
+                if symbol == pattern[matched] { matched += 1; }
+                position += 1;
+
+                if matched == pattern.len() {
+                    result.push(position - matched as u64);
+                    matched = failure[matched - 1];
+                }
+            }
+        }
+
+        result
+    }
+
+    /// The number of occurrences of `pattern` in the text.
+    pub fn count(&self, pattern: &[S]) -> u64 {
+        self.locate(pattern).len() as u64
+    }
+}
+
+impl<S: Symbol> SpaceUsage for Lz78Index<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.parents.heap_bytes()
+            + self.symbols.heap_bytes()
+            + self.starts.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::{quickcheck, TestResult};
+
+    #[test]
+    fn empty_text() {
+        let index = Lz78Index::<u8>::new(&[]);
+        assert!(index.is_empty());
+        assert_eq!(0, index.phrases());
+        assert_eq!(Vec::<u8>::new(), index.extract(0 .. 0));
+        assert_eq!(vec![0], index.locate(b""));
+        assert_eq!(0, index.count(b"a"));
+    }
+
+    #[test]
+    fn repeated_last_phrase() {
+        // Parses as a|b|ab|aba|b, the last repeating the second.
+        let index = Lz78Index::new(b"abababab");
+        assert_eq!(5, index.phrases());
+        assert_eq!(b"abababab".to_vec(), index.extract(0 .. 8));
+        assert_eq!(b"ab".to_vec(), index.extract(6 .. 8));
+        assert_eq!(vec![0, 2, 4, 6], index.locate(b"ab"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn extract_oob() {
+        Lz78Index::new(b"abc").extract(2 .. 4);
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(text: Vec<u8>, pattern: Vec<u8>, start: usize, len: usize)
+                -> TestResult {
+            if text.is_empty() { return TestResult::discard(); }
+
+            let text: Vec<u16> = text.into_iter().map(|b| b as u16 % 3).collect();
+            let pattern: Vec<u16> = pattern.into_iter().take(4)
+                                           .map(|b| b as u16 % 3).collect();
+            let start = start % text.len();
+            let end = start + len % (text.len() - start + 1);
+            let index = Lz78Index::new(&text);
+
+            let naive: Vec<u64> = (0 .. text.len() + 1)
+                .filter(|&i| text[i ..].starts_with(&pattern))
+                .map(|i| i as u64)
+                .collect();
+
+            TestResult::from_bool(
+                index.extract(start as u64 .. end as u64) == text[start .. end]
+                    && index.locate(&pattern) == naive)
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>, usize, usize) -> TestResult);
+    }
+}
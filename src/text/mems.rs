@@ -0,0 +1,212 @@
+use int_vec::IntVec;
+use super::{BiInterval, BidirectionalFmIndex, Symbol};
+
+/// A maximal exact match: a stretch of the query that occurs at a text
+/// position and can’t be extended there in either direction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Mem {
+    /// Where the match starts in the query.
+    pub query_start: usize,
+    /// Where the match starts in the text.
+    pub text_start: u64,
+    /// The length of the match.
+    pub len: usize,
+}
+
+/// A super-maximal exact match: a stretch of the query that occurs in
+/// the text and isn’t part of any longer stretch that does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Smem {
+    /// Where the match starts in the query.
+    pub query_start: usize,
+    /// The length of the match.
+    pub len: usize,
+    /// The match’s occurrences, for
+    /// [`locate`](struct.BidirectionalFmIndex.html#method.locate).
+    pub interval: BiInterval,
+}
+
+impl<S: Symbol> BidirectionalFmIndex<S> {
+    /// Finds every maximal exact match of at least `min_len` symbols
+    /// between `query` and the text, sorted by query then text position.
+    ///
+    /// Matches are maximal per occurrence: each pair of positions is
+    /// reported once, with the longest match there. This takes time
+    /// proportional to the query length times the longest match, plus
+    /// the candidate occurrences examined.
+    ///
+    /// ```
+    /// use succinct::text::{BidirectionalFmIndex, Mem};
+    ///
+    /// let index = BidirectionalFmIndex::new(b"xabcyabd", 4);
+    /// let mems = index.find_mems(b"abcd", 2);
+    ///
+    /// assert_eq!(vec![Mem { query_start: 0, text_start: 1, len: 3 },
+    ///                 Mem { query_start: 0, text_start: 5, len: 2 }],
+    ///            mems);
+    /// ```
+    pub fn find_mems(&self, query: &[S], min_len: usize) -> Vec<Mem> {
+        let min_len = min_len.max(1);
+        let bwt = self.forward().bwt();
+        let mut result = Vec::new();
+
+        for start in 0 .. query.len() {
+            // The symbol a left-extendable occurrence would be preceded
+            // by, shifted as in the transform; none matches the marker.
+            let before = if start == 0 {None} else {
+                Some(query[start - 1].to_u64() + 1)
+            };
+
+            let mut interval = self.full();
+            let mut len = 0;
+            loop {
+                let longer = query.get(start + len)
+                                  .map(|&symbol| self.extend_right(interval, symbol));
+
+                // Occurrences of the match so far that don’t continue
+                // are right-maximal at this length.
+                if len >= min_len {
+                    let continuing = longer.map_or(0 .. 0, |next| next.forward());
+                    for row in interval.forward() {
+                        let continues = continuing.start <= row
+                            && row < continuing.end;
+                        if !continues && Some(bwt.get(row)) != before {
+                            result.push(Mem {
+                                query_start: start,
+                                text_start: self.forward().suffix_at(row),
+                                len,
+                            });
+                        }
+                    }
+                }
+
+                match longer {
+                    Some(next) if !next.is_empty() => interval = next,
+                    _ => break,
+                }
+                len += 1;
+            }
+        }
+
+        result.sort();
+        result
+    }
+
+    /// Finds the super-maximal exact matches of at least `min_len`
+    /// symbols between `query` and the text, in query order.
+    ///
+    /// ```
+    /// use succinct::text::BidirectionalFmIndex;
+    ///
+    /// let index = BidirectionalFmIndex::new(b"xabcyabd", 4);
+    /// let smems = index.find_smems(b"zabcd", 1);
+    ///
+    /// assert_eq!(2, smems.len());
+    /// assert_eq!((1, 3), (smems[0].query_start, smems[0].len));
+    /// assert_eq!(vec![1], index.locate(smems[0].interval));
+    /// assert_eq!((4, 1), (smems[1].query_start, smems[1].len));
+    /// ```
+    pub fn find_smems(&self, query: &[S], min_len: usize) -> Vec<Smem> {
+        let min_len = min_len.max(1);
+        let mut result = Vec::new();
+        let mut previous_end = 0;
+
+        // The longest match at each start ends no earlier than the one
+        // before, so it is super-maximal exactly when it ends later.
+        for start in 0 .. query.len() {
+            let mut interval = self.full();
+            let mut len = 0;
+            while let Some(&symbol) = query.get(start + len) {
+                let longer = self.extend_right(interval, symbol);
+                if longer.is_empty() { break; }
+                interval = longer;
+                len += 1;
+            }
+
+            if start + len > previous_end && len >= min_len {
+                result.push(Smem { query_start: start, len, interval });
+            }
+            previous_end = previous_end.max(start + len);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    fn naive_mems(text: &[u8], query: &[u8], min_len: usize) -> Vec<Mem> {
+        let mut result = Vec::new();
+        for i in 0 .. query.len() {
+            for p in 0 .. text.len() {
+                let left_maximal = i == 0 || p == 0 || query[i - 1] != text[p - 1];
+                let len = query[i ..].iter().zip(&text[p ..])
+                                     .take_while(|&(a, b)| a == b).count();
+                if left_maximal && len >= min_len.max(1) {
+                    result.push(Mem { query_start: i, text_start: p as u64, len });
+                }
+            }
+        }
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn no_match() {
+        let index = BidirectionalFmIndex::new(b"aaaa", 2);
+        assert!(index.find_mems(b"bbb", 1).is_empty());
+        assert!(index.find_smems(b"bbb", 1).is_empty());
+        assert!(index.find_smems(b"", 1).is_empty());
+    }
+
+    #[test]
+    fn smems_skip_contained() {
+        let index = BidirectionalFmIndex::new(b"abcdxbcdex", 3);
+        let smems = index.find_smems(b"abcdex", 2);
+
+        let spans: Vec<_> = smems.iter().map(|m| (m.query_start, m.len)).collect();
+        assert_eq!(vec![(0, 4), (1, 5)], spans);
+        assert_eq!(vec![5], index.locate(smems[1].interval));
+    }
+
+    #[test]
+    fn qc_mems_match_naive() {
+        fn prop(text: Vec<u8>, query: Vec<u8>, min_len: u8) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 3).collect();
+            let query: Vec<u8> = query.into_iter().take(12).map(|b| b % 3).collect();
+            let min_len = min_len as usize % 4;
+
+            let index = BidirectionalFmIndex::new(&text, 3);
+            index.find_mems(&query, min_len) == naive_mems(&text, &query, min_len)
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>, u8) -> bool);
+    }
+
+    #[test]
+    fn qc_smems_are_maximal() {
+        fn prop(text: Vec<u8>, query: Vec<u8>) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 3).collect();
+            let query: Vec<u8> = query.into_iter().take(12).map(|b| b % 3).collect();
+            let index = BidirectionalFmIndex::new(&text, 3);
+
+            let occurs = |piece: &[u8]| {
+                text.windows(piece.len()).any(|window| window == piece)
+            };
+
+            index.find_smems(&query, 1).iter().all(|m| {
+                let end = m.query_start + m.len;
+                occurs(&query[m.query_start .. end])
+                    && (end == query.len()
+                        || !occurs(&query[m.query_start .. end + 1]))
+                    && (m.query_start == 0
+                        || !occurs(&query[m.query_start - 1 .. end]))
+            })
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>) -> bool);
+    }
+}
@@ -0,0 +1,46 @@
+//! Full-text indexes.
+
+mod alphabet;
+pub use self::alphabet::*;
+
+mod bidirectional;
+pub use self::bidirectional::*;
+
+mod deletable;
+pub use self::deletable::*;
+
+mod fm_index;
+pub use self::fm_index::*;
+
+mod kmer;
+pub use self::kmer::*;
+
+mod lz78;
+pub use self::lz78::*;
+
+mod mems;
+pub use self::mems::*;
+
+mod online;
+pub use self::online::*;
+
+mod records;
+pub use self::records::*;
+
+mod restricted;
+pub use self::restricted::*;
+
+mod run_length;
+pub use self::run_length::*;
+
+mod sampling;
+pub use self::sampling::*;
+
+mod suffix_array;
+pub use self::suffix_array::*;
+
+mod suffix_tree;
+pub use self::suffix_tree::*;
+
+mod symbol;
+pub use self::symbol::*;
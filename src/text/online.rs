@@ -0,0 +1,224 @@
+use std::marker::PhantomData;
+
+use int_vec::IntVec;
+use space_usage::SpaceUsage;
+use wavelet::DynamicWaveletMatrix;
+use super::{FmIndex, Symbol};
+
+/// An FM-index that grows as text arrives, one symbol at a time.
+///
+/// This keeps the Burrows–Wheeler transform of the text *reversed*, in a
+/// [`DynamicWaveletMatrix`](../wavelet/struct.DynamicWaveletMatrix.html):
+/// appending a symbol to the text prepends it to the reversal, which
+/// changes the transform in just two places, where the end-of-text
+/// marker was and where it goes. Searching the reversal for a reversed
+/// pattern means a backward search reads the pattern forward.
+///
+/// Nothing is sampled, so `locate` walks from each match to the end of
+/// the text, taking time proportional to how far back the match is;
+/// recent text is found quickly. Once the text is complete,
+/// [`to_fm_index`](#method.to_fm_index) builds a static index.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::OnlineFmIndex;
+///
+/// let mut index = OnlineFmIndex::new(8);
+/// index.extend(b"abracad");
+/// assert_eq!(1, index.count(b"abra"));
+///
+/// index.extend(b"abra");
+/// assert_eq!(2, index.count(b"abra"));
+/// assert_eq!(vec![0, 7], {
+///     let mut found = index.locate(b"abra");
+///     found.sort();
+///     found
+/// });
+/// assert_eq!(b"abracadabra".to_vec(), index.text());
+/// ```
+#[derive(Clone, Debug)]
+pub struct OnlineFmIndex<S: Symbol = u8> {
+    // The marker is 0 and symbols are shifted up by one.
+    bwt: DynamicWaveletMatrix,
+    // The row whose suffix is the whole reversed text.
+    marker_row: u64,
+    marker: PhantomData<S>,
+}
+
+impl<S: Symbol> OnlineFmIndex<S> {
+    /// Creates an index of the empty text, for symbols of up to
+    /// `symbol_bits` bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol_bits` is 0 or more than 63.
+    pub fn new(symbol_bits: usize) -> Self {
+        assert!(symbol_bits > 0 && symbol_bits < 64,
+                "OnlineFmIndex::new: bad symbol size");
+        let mut bwt = DynamicWaveletMatrix::new(symbol_bits + 1);
+        bwt.push(0);
+        OnlineFmIndex { bwt, marker_row: 0, marker: PhantomData }
+    }
+
+    /// The length of the text.
+    pub fn len(&self) -> u64 {
+        self.bwt.len() - 1
+    }
+
+    /// Is the text empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Where a range bound `row` goes when the pattern is extended by a
+    // shifted symbol, as in `FmIndex::extend_left`.
+    fn map(&self, row: u64, symbol: u64) -> u64 {
+        self.bwt.count_less(self.bwt.len(), symbol) + self.bwt.rank_before(row, symbol)
+    }
+
+    /// Appends `symbol` to the text.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol` needs more bits than the index was made for.
+    pub fn push(&mut self, symbol: S) {
+        let symbol = symbol.to_u64();
+        assert!(symbol >> (self.bwt.levels() - 1) == 0,
+                "OnlineFmIndex::push: symbol too wide");
+        let symbol = symbol + 1;
+        let row = self.map(self.marker_row, symbol);
+
+        // The marker’s place now holds the new symbol, and the marker
+        // moves to the row of the new, longest suffix.
+        self.bwt.remove(self.marker_row);
+        self.bwt.insert(self.marker_row, symbol);
+        self.bwt.insert(row, 0);
+        self.marker_row = row;
+    }
+
+    /// Appends every symbol in `text`.
+    pub fn extend(&mut self, text: &[S]) {
+        for &symbol in text {
+            self.push(symbol);
+        }
+    }
+
+    // The rows of the reversed text’s suffixes that start with the
+    // reversed pattern.
+    fn search(&self, pattern: &[S]) -> (u64, u64) {
+        let (mut start, mut end) = (0, self.bwt.len());
+        for &symbol in pattern {
+            let symbol = symbol.to_u64() + 1;
+            if start >= end { break; }
+            start = self.map(start, symbol);
+            end = self.map(end, symbol);
+        }
+        (start, end.max(start))
+    }
+
+    /// The number of occurrences of `pattern` in the text.
+    pub fn count(&self, pattern: &[S]) -> u64 {
+        let (start, end) = self.search(pattern);
+        end - start
+    }
+
+    /// The positions where `pattern` occurs, in no particular order.
+    pub fn locate(&self, pattern: &[S]) -> Vec<u64> {
+        let (start, end) = self.search(pattern);
+        let len = self.len();
+        let pattern_len = pattern.len() as u64;
+
+        (start .. end).map(|row| {
+            // Step back through the reversed text to its start.
+            let mut row = row;
+            let mut steps = 0;
+            while row != self.marker_row {
+                let symbol = self.bwt.get(row);
+                row = self.map(row, symbol);
+                steps += 1;
+            }
+            len - steps - pattern_len
+        }).collect()
+    }
+
+    /// Recovers the whole text.
+    pub fn text(&self) -> Vec<S> {
+        let mut result = Vec::with_capacity(self.len() as usize);
+        let mut row = 0;
+        for _ in 0 .. self.len() {
+            let symbol = self.bwt.get(row);
+            result.push(S::from_u64(symbol - 1));
+            row = self.map(row, symbol);
+        }
+        result
+    }
+
+    /// Builds a static index of the text, sampling every `sample_rate`th
+    /// position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is 0.
+    pub fn to_fm_index(&self, sample_rate: usize) -> FmIndex<S> {
+        FmIndex::new(&self.text(), sample_rate)
+    }
+}
+
+impl<S: Symbol> SpaceUsage for OnlineFmIndex<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.bwt.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let index = OnlineFmIndex::<u8>::new(8);
+        assert!(index.is_empty());
+        assert_eq!(1, index.count(b""));
+        assert_eq!(0, index.count(b"a"));
+        assert!(index.text().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn symbol_too_wide() {
+        OnlineFmIndex::<u16>::new(4).push(16);
+    }
+
+    #[test]
+    fn qc_matches_fm_index() {
+        fn prop(chunks: Vec<Vec<u8>>, pattern: Vec<u8>) -> bool {
+            let pattern: Vec<u8> = pattern.into_iter().take(3).map(|b| b % 3).collect();
+            let mut index = OnlineFmIndex::new(2);
+            let mut text = Vec::new();
+
+            for chunk in chunks.into_iter().take(4) {
+                let chunk: Vec<u8> = chunk.into_iter().map(|b| b % 3).collect();
+                index.extend(&chunk);
+                text.extend(chunk);
+
+                let reference = FmIndex::new(&text, 2);
+                let mut found = index.locate(&pattern);
+                found.sort();
+                let mut expected = reference.locate(&pattern);
+                expected.sort();
+
+                if found != expected || index.text() != text {
+                    return false;
+                }
+            }
+
+            true
+        }
+
+        quickcheck(prop as fn(Vec<Vec<u8>>, Vec<u8>) -> bool);
+    }
+}
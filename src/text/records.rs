@@ -0,0 +1,308 @@
+use std::ops::Range;
+
+use num_traits::{One, PrimInt, Zero};
+
+use bit_vec::BitVec;
+use dump::Snapshot;
+use int_vec::{EliasFano, EliasFanoCursor, IntVec};
+use rank::{BitRankSupport, RankSupport};
+use select::Select1Support;
+use space_usage::SpaceUsage;
+use storage::BlockType;
+use super::Symbol;
+
+/// The records of a text cut at separators, such as the lines of a file,
+/// mapping offsets to record numbers and back.
+///
+/// The separators’ positions are kept in an
+/// [`EliasFano`](../int_vec/struct.EliasFano.html), so the index takes
+/// about 2 + lg(*n* / *r*) bits per record for a text of *n* symbols in
+/// *r* records, and finding a record or its bounds is a rank or a
+/// select. Each record runs up to, but not including, its separator,
+/// and the last runs to the end of the text, so there is always one
+/// more record than separators, and a text that ends with a separator
+/// ends with an empty record.
+///
+/// It is also a bitmap with rank and select over the separators, so
+/// it can be passed to
+/// [`FmIndex::extract_records`](struct.FmIndex.html#method.extract_records).
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::RecordIndex;
+///
+/// let text = b"alpha\nbeta\n\ngamma";
+/// let records = RecordIndex::from_text(text, b'\n');
+///
+/// assert_eq!(4, records.record_count());
+/// assert_eq!(1, records.record_of(8));
+/// assert_eq!(6 .. 10, records.record(1));
+/// assert_eq!(vec![&b"alpha"[..], b"beta", b"", b"gamma"],
+///            records.iter().map(|r| &text[r.start as usize .. r.end as usize])
+///                   .collect::<Vec<_>>());
+/// ```
+#[derive(Clone, Debug)]
+pub struct RecordIndex {
+    separators: EliasFano,
+}
+
+impl RecordIndex {
+    /// Builds the index for the separators marked in `bits`, whose
+    /// length is the text’s.
+    pub fn new<B: BitVec + ?Sized>(bits: &B) -> Self {
+        let nbits = B::Block::nbits() as u64;
+        let len = bits.bit_len();
+        let mut positions = Vec::new();
+        for block in 0 .. bits.block_len() {
+            let mut word = bits.get_block(block);
+            while word != B::Block::zero() {
+                let position = block as u64 * nbits + word.trailing_zeros() as u64;
+                if position >= len { break; }
+                positions.push(position);
+                word = word & (word - B::Block::one());
+            }
+        }
+
+        Self::from_separators(len, positions)
+    }
+
+    /// Builds the index for a text of `len` symbols with separators at
+    /// `positions`, which must be strictly increasing and less than
+    /// `len`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the positions are out of order or out of range.
+    pub fn from_separators<I>(len: u64, positions: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        RecordIndex {
+            separators: EliasFano::new(len, positions),
+        }
+    }
+
+    /// Builds the index for `text` cut at each `separator`.
+    pub fn from_text<S: Symbol>(text: &[S], separator: S) -> Self {
+        let positions = (0 .. text.len()).filter(|&i| text[i] == separator);
+        Self::from_separators(text.len() as u64, positions.map(|i| i as u64))
+    }
+
+    /// The length of the text.
+    pub fn len(&self) -> u64 {
+        self.separators.universe()
+    }
+
+    /// Is the text empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of records, one more than the number of separators.
+    pub fn record_count(&self) -> u64 {
+        self.separators.len() + 1
+    }
+
+    /// The number of the record holding `position`; a separator belongs
+    /// to the record it ends.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.len()`.
+    pub fn record_of(&self, position: u64) -> u64 {
+        assert!(position <= self.len(), "RecordIndex::record_of: out of bounds");
+        self.separators.count_less(position)
+    }
+
+    /// The positions of the `record`th record, without its separator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `record >= self.record_count()`.
+    pub fn record(&self, record: u64) -> Range<u64> {
+        assert!(record < self.record_count(), "RecordIndex::record: out of bounds");
+        let start = if record == 0 {0} else {self.separators.get(record - 1) + 1};
+        let end = if record == self.separators.len() {
+            self.len()
+        } else {
+            self.separators.get(record)
+        };
+        start .. end
+    }
+
+    /// The positions of the record holding `position`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.len()`.
+    pub fn record_around(&self, position: u64) -> Range<u64> {
+        self.record(self.record_of(position))
+    }
+
+    /// Iterates over the records’ positions in order.
+    pub fn iter(&self) -> Records<'_> {
+        Records {
+            data: self,
+            front: 0,
+            back: self.record_count(),
+        }
+    }
+}
+
+impl RankSupport for RecordIndex {
+    type Over = bool;
+
+    fn rank(&self, position: u64, value: bool) -> u64 {
+        assert!(position < self.len(), "RecordIndex::rank: out of bounds");
+        let ones = self.separators.count_less(position + 1);
+        if value {ones} else {position + 1 - ones}
+    }
+
+    fn limit(&self) -> u64 {
+        self.len()
+    }
+}
+
+impl BitRankSupport for RecordIndex {}
+
+impl Select1Support for RecordIndex {
+    fn select1(&self, index: u64) -> Option<u64> {
+        if index < self.separators.len() {
+            Some(self.separators.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+/// Lists the positions of the separators, in increasing order.
+impl Snapshot for RecordIndex {
+    type Item = u64;
+    type Iter<'a> = EliasFanoCursor<'a> where Self: 'a;
+
+    fn dump_iter(&self) -> EliasFanoCursor<'_> {
+        self.separators.iter()
+    }
+}
+
+impl SpaceUsage for RecordIndex {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.separators.heap_bytes()
+    }
+}
+
+/// An iterator over the records of a
+/// [`RecordIndex`](struct.RecordIndex.html), as ranges of positions.
+#[derive(Clone, Debug)]
+pub struct Records<'a> {
+    data: &'a RecordIndex,
+    // The records in `front .. back` remain.
+    front: u64,
+    back: u64,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Range<u64>;
+
+    fn next(&mut self) -> Option<Range<u64>> {
+        if self.front >= self.back { return None; }
+        self.front += 1;
+        Some(self.data.record(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.back - self.front) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Range<u64>> {
+        self.front = self.front.saturating_add(n as u64).min(self.back);
+        self.next()
+    }
+
+    impl_exact_size_count_last!(Range<u64>);
+}
+
+impl<'a> ExactSizeIterator for Records<'a> {}
+
+impl<'a> DoubleEndedIterator for Records<'a> {
+    impl_index_range_back!(Range<u64>, front .. back,
+                           |iter, index| iter.data.record(index));
+}
+
+impl<'a> IntoIterator for &'a RecordIndex {
+    type Item = Range<u64>;
+    type IntoIter = Records<'a>;
+
+    fn into_iter(self) -> Records<'a> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::BitVector;
+    use text::FmIndex;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty_text() {
+        let records = RecordIndex::from_text(b"", b'\n');
+        assert!(records.is_empty());
+        assert_eq!(1, records.record_count());
+        assert_eq!(0 .. 0, records.record(0));
+        assert_eq!(0, records.record_of(0));
+    }
+
+    #[test]
+    fn with_fm_index() {
+        let text = b"one\ntwo\nthree";
+        let records = RecordIndex::from_text(text, b'\n');
+        let index = FmIndex::new(text, 4);
+        assert_eq!((4 .. 7, b"two".to_vec()), index.extract_records(5, 0, 0, &records));
+        assert_eq!((0 .. 13, text.to_vec()), index.extract_records(5, 1, 1, &records));
+    }
+
+    #[test]
+    fn qc_matches_split() {
+        fn prop(text: Vec<u8>) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 4).collect();
+            let bits: Vec<bool> = text.iter().map(|&b| b == 0).collect();
+            let records = RecordIndex::new(&BitVector::<u8>::from(&bits[..]));
+
+            let expected: Vec<&[u8]> = text.split(|&b| b == 0).collect();
+            let found: Vec<&[u8]> = records.iter()
+                .map(|r| &text[r.start as usize .. r.end as usize])
+                .collect();
+            let reversed: Vec<Range<u64>> = records.iter().rev().collect();
+
+            found == expected
+                && reversed.len() == expected.len()
+                && (0 .. text.len() as u64 + 1).all(|i| {
+                    let record = records.record_around(i);
+                    record.start <= i && i <= record.end
+                })
+        }
+
+        quickcheck(prop as fn(Vec<u8>) -> bool);
+    }
+
+    #[test]
+    fn qc_skips_from_both_ends() {
+        use internal::properties::skips_agree;
+
+        fn prop(values: Vec<u8>, n: u8, step: u8) -> bool {
+            let (n, step) = (n as usize % 8, step as usize % 5 + 1);
+            let text: Vec<u8> = values.into_iter().map(|b| b % 4).collect();
+            let records = RecordIndex::from_text(&text, 0);
+            let expected: Vec<Range<u64>> =
+                (0 .. records.record_count()).map(|i| records.record(i)).collect();
+            skips_agree(records.iter(), &expected, n, step)
+        }
+
+        quickcheck(prop as fn(Vec<u8>, u8, u8) -> bool);
+    }
+}
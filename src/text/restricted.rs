@@ -0,0 +1,172 @@
+use std::ops::Range;
+
+use int_vec::IntVec;
+use space_usage::SpaceUsage;
+use wavelet::WaveletMatrix;
+use super::{suffix_array, FmIndex, Symbol};
+
+/// A full-text index that counts and locates the occurrences of a
+/// pattern within a range of text positions.
+///
+/// Alongside a count-only [`FmIndex`](struct.FmIndex.html), this keeps
+/// the suffix array in a
+/// [`WaveletMatrix`](../wavelet/struct.WaveletMatrix.html). The
+/// occurrences of a pattern are a range of rows; the matrix counts the
+/// rows whose positions fall in the text range in time proportional to
+/// the number of bits per position, and reports them in increasing order
+/// in that time apiece. This takes more space than sampling, but suits
+/// searching only a document or a genomic region.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::RangeFmIndex;
+///
+/// let index = RangeFmIndex::new(b"abracadabra abracadabra");
+/// assert_eq!(4, index.count(b"abra"));
+/// assert_eq!(2, index.count_in_range(b"abra", 5 .. 16));
+/// assert_eq!(vec![7, 12], index.locate_in_range(b"abra", 5 .. 16));
+/// assert_eq!(0, index.count_in_range(b"abra", 8 .. 14));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RangeFmIndex<S: Symbol = u8> {
+    index: FmIndex<S>,
+    // The text position of each row.
+    suffixes: WaveletMatrix,
+}
+
+impl<S: Symbol> RangeFmIndex<S> {
+    /// Indexes `text`.
+    pub fn new(text: &[S]) -> Self {
+        RangeFmIndex {
+            index: FmIndex::count_only(text),
+            suffixes: WaveletMatrix::new(suffix_array(text)),
+        }
+    }
+
+    /// The count-only index of the text.
+    pub fn index(&self) -> &FmIndex<S> {
+        &self.index
+    }
+
+    /// The length of the text.
+    pub fn len(&self) -> u64 {
+        self.index.len()
+    }
+
+    /// Is the text empty?
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// The number of occurrences of `pattern` in the text.
+    pub fn count(&self, pattern: &[S]) -> u64 {
+        self.index.count(pattern)
+    }
+
+    /// The positions where `pattern` occurs, in increasing order.
+    pub fn locate(&self, pattern: &[S]) -> Vec<u64> {
+        self.locate_in_range(pattern, 0 .. self.len())
+    }
+
+    // The rows of the occurrences, and the starts they may have to lie
+    // within `range`.
+    fn restrict(&self, pattern: &[S], range: Range<u64>)
+                -> (Range<u64>, Range<u64>) {
+        assert!(range.start <= range.end && range.end <= self.len(),
+                "RangeFmIndex: out of bounds");
+        let pattern_len = pattern.len() as u64;
+        if range.end - range.start < pattern_len {
+            return (0 .. 0, 0 .. 0);
+        }
+
+        let starts = range.start .. range.end - pattern_len + 1;
+        (self.index.backward_search(pattern), starts)
+    }
+
+    /// The number of occurrences of `pattern` that lie within `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn count_in_range(&self, pattern: &[S], range: Range<u64>) -> u64 {
+        let (rows, starts) = self.restrict(pattern, range);
+        self.suffixes.count_less_range(rows.start, rows.end, starts.end)
+            - self.suffixes.count_less_range(rows.start, rows.end, starts.start)
+    }
+
+    /// The positions of the occurrences of `pattern` that lie within
+    /// `range`, in increasing order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn locate_in_range(&self, pattern: &[S], range: Range<u64>) -> Vec<u64> {
+        let (rows, starts) = self.restrict(pattern, range);
+        self.suffixes.distinct_values_in(rows.start, rows.end, starts)
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    /// The text position of the suffix at `row` in suffix order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row > self.len()`.
+    pub fn suffix_at(&self, row: u64) -> u64 {
+        self.suffixes.get(row)
+    }
+}
+
+impl<S: Symbol> SpaceUsage for RangeFmIndex<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.index.heap_bytes() + self.suffixes.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty_pattern() {
+        let index = RangeFmIndex::new(b"abc");
+        assert_eq!(2, index.count_in_range(b"", 1 .. 2));
+        assert_eq!(vec![0, 1, 2, 3], index.locate(b""));
+        assert_eq!(0, index.count_in_range(b"abc", 0 .. 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_bounds() {
+        RangeFmIndex::new(b"abc").count_in_range(b"a", 0 .. 4);
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(text: Vec<u8>, pattern: Vec<u8>, a: usize, b: usize) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|x| x % 3).collect();
+            let pattern: Vec<u8> = pattern.into_iter().take(3).map(|x| x % 3).collect();
+            let a = a % (text.len() + 1);
+            let b = b % (text.len() + 1);
+            let (start, end) = (a.min(b), a.max(b));
+            let index = RangeFmIndex::new(&text);
+
+            let naive: Vec<u64> = (start .. end + 1)
+                .filter(|&i| i + pattern.len() <= end
+                        && text[i ..].starts_with(&pattern))
+                .map(|i| i as u64)
+                .collect();
+
+            let range = start as u64 .. end as u64;
+            index.locate_in_range(&pattern, range.clone()) == naive
+                && index.count_in_range(&pattern, range) == naive.len() as u64
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>, usize, usize) -> bool);
+    }
+}
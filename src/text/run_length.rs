@@ -0,0 +1,297 @@
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use int_vec::{IntVec, IntVector};
+use internal::search::binary_search_function;
+use select::SelectSupport;
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use wavelet::WaveletMatrix;
+use super::{inverse_permutation, suffix_array, Symbol};
+
+/// A run-length compressed FM-index, in the style of the r-index, for
+/// highly repetitive texts.
+///
+/// The Burrows–Wheeler transform of a repetitive text has few runs of
+/// equal symbols, *r* say, so this stores only the run heads, in a
+/// [`WaveletMatrix`](../wavelet/struct.WaveletMatrix.html), and where the
+/// runs start. Instead of sampling text positions at a fixed rate it
+/// keeps the suffix array entries at run boundaries, from which `locate`
+/// reports each occurrence in a predecessor search. Everything is
+/// *O*(*r*) words, however long the text.
+///
+/// Unlike [`FmIndex`](struct.FmIndex.html), this can’t extract the text.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::RunLengthFmIndex;
+///
+/// let index = RunLengthFmIndex::new(b"abcabcabcabcabcabc");
+/// assert_eq!(5, index.count(b"ca"));
+/// assert!(index.runs() < 8);
+///
+/// let mut found = index.locate(b"bca");
+/// found.sort();
+/// assert_eq!(vec![1, 4, 7, 10, 13], found);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RunLengthFmIndex<S: Symbol = u8> {
+    rows: u64,
+    // The symbol of each run, with the marker as 0 and symbols shifted.
+    heads: WaveletMatrix,
+    // The first row of each run.
+    starts: IntVector<u64>,
+    // With the runs ordered by symbol, then position, the number of rows
+    // in the runs before each; the last entry is the number of rows.
+    cumulative: IntVector<u64>,
+    // The suffix at the last row of each run.
+    ends: IntVector<u64>,
+    // Text positions p, sorted, whose next position’s row starts a run,
+    // and the suffix in the row before p’s.
+    phi_keys: IntVector<u64>,
+    phi_values: IntVector<u64>,
+    // The suffix at the last row.
+    last: u64,
+    marker: PhantomData<S>,
+}
+
+impl<S: Symbol> RunLengthFmIndex<S> {
+    /// Indexes `text`.
+    pub fn new(text: &[S]) -> Self {
+        let suffixes = suffix_array(text);
+        let rows = suffixes.len() as u64;
+        let position_bits = bits_to_represent(rows);
+
+        let shifted = |suffix: u64| match suffix {
+            0 => 0,
+            _ => text[suffix as usize - 1].to_u64() + 1,
+        };
+
+        let mut heads = Vec::new();
+        let mut starts = IntVector::new(position_bits);
+        let mut lengths = Vec::new();
+        let mut ends = IntVector::new(position_bits);
+        for (row, &suffix) in suffixes.iter().enumerate() {
+            let symbol = shifted(suffix);
+            if heads.last() == Some(&symbol) {
+                *lengths.last_mut().unwrap() += 1;
+            } else {
+                heads.push(symbol);
+                starts.push(row as u64);
+                lengths.push(1);
+                if row > 0 { ends.push(suffixes[row - 1]); }
+            }
+        }
+        ends.push(suffixes[suffixes.len() - 1]);
+
+        let mut order: Vec<usize> = (0 .. heads.len()).collect();
+        order.sort_by_key(|&run| heads[run]);
+        let mut cumulative = IntVector::with_capacity(position_bits,
+                                                      order.len() as u64 + 1);
+        let mut total = 0;
+        cumulative.push(0);
+        for run in order {
+            total += lengths[run];
+            cumulative.push(total);
+        }
+
+        let inverse = inverse_permutation(&suffixes);
+        let mut phi: Vec<(u64, u64)> = (0 .. starts.len())
+            .map(|run| suffixes[starts.get(run) as usize])
+            .filter(|&suffix| suffix > 0)
+            .map(|suffix| {
+                let row = inverse[suffix as usize - 1];
+                (suffix - 1, suffixes[row as usize - 1])
+            })
+            .collect();
+        phi.sort();
+
+        let mut phi_keys = IntVector::with_capacity(position_bits,
+                                                    phi.len() as u64);
+        let mut phi_values = IntVector::with_capacity(position_bits,
+                                                      phi.len() as u64);
+        for (key, value) in phi {
+            phi_keys.push(key);
+            phi_values.push(value);
+        }
+
+        RunLengthFmIndex {
+            rows,
+            heads: WaveletMatrix::new(heads),
+            starts,
+            cumulative,
+            ends,
+            phi_keys,
+            phi_values,
+            last: suffixes[suffixes.len() - 1],
+            marker: PhantomData,
+        }
+    }
+
+    /// The length of the text.
+    pub fn len(&self) -> u64 {
+        self.rows - 1
+    }
+
+    /// Is the text empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of runs in the Burrows–Wheeler transform, counting the
+    /// end-of-text marker as one.
+    pub fn runs(&self) -> u64 {
+        self.heads.len()
+    }
+
+    // The run containing `row`.
+    fn run_of(&self, row: u64) -> u64 {
+        let runs = self.runs();
+        binary_search_function(0, runs, row + 1, |run| self.starts.get(run))
+            .unwrap_or(runs) - 1
+    }
+
+    // Where a range bound `row` goes when the pattern is extended left by
+    // `symbol` (shifted), as in `FmIndex::extend_left`.
+    fn map(&self, row: u64, symbol: u64) -> u64 {
+        let runs = self.runs();
+        let run = if row == self.rows {runs} else {self.run_of(row)};
+        let first = self.heads.count_less(runs, symbol)
+            + self.heads.rank_before(run, symbol);
+        let within = if run < runs && self.heads.get(run) == symbol {
+            row - self.starts.get(run)
+        } else {0};
+
+        self.cumulative.get(first) + within
+    }
+
+    // Searches for `pattern`, returning its rows and the suffix at the
+    // last of them, unless it doesn’t occur.
+    fn search(&self, pattern: &[S]) -> Option<(Range<u64>, u64)> {
+        let mut rows = 0 .. self.rows;
+        let mut last = self.last;
+
+        for &symbol in pattern.iter().rev() {
+            let symbol = symbol.to_u64() + 1;
+            let next = self.map(rows.start, symbol) .. self.map(rows.end, symbol);
+            if next.start >= next.end { return None; }
+
+            // The last row maps to the last row unless it holds another
+            // symbol, when the last occurrence before it, which ends a
+            // run, does.
+            let run = self.run_of(rows.end - 1);
+            last = if self.heads.get(run) == symbol {
+                last - 1
+            } else {
+                let before = self.heads.rank_before(run, symbol);
+                let run = self.heads.select(before - 1, symbol)
+                              .expect("RunLengthFmIndex: inconsistent runs");
+                self.ends.get(run) - 1
+            };
+            rows = next;
+        }
+
+        Some((rows, last))
+    }
+
+    // The suffix in the row before that of the suffix at `position`.
+    fn phi(&self, position: u64) -> u64 {
+        let key = binary_search_function(0, self.phi_keys.len(), position,
+                                         |i| self.phi_keys.get(i))
+            .expect("RunLengthFmIndex: inconsistent samples");
+        self.phi_values.get(key) - (self.phi_keys.get(key) - position)
+    }
+
+    /// The range of rows, in suffix order, of the suffixes that start
+    /// with `pattern`.
+    pub fn backward_search(&self, pattern: &[S]) -> Range<u64> {
+        self.search(pattern).map_or(0 .. 0, |(rows, _)| rows)
+    }
+
+    /// The number of occurrences of `pattern` in the text.
+    pub fn count(&self, pattern: &[S]) -> u64 {
+        let rows = self.backward_search(pattern);
+        rows.end - rows.start
+    }
+
+    /// The positions where `pattern` occurs, in no particular order.
+    pub fn locate(&self, pattern: &[S]) -> Vec<u64> {
+        let (rows, mut position) = match self.search(pattern) {
+            Some(found) => found,
+            None => return Vec::new(),
+        };
+
+        let mut result = Vec::with_capacity((rows.end - rows.start) as usize);
+        result.push(position);
+        for _ in rows.start + 1 .. rows.end {
+            position = self.phi(position);
+            result.push(position);
+        }
+
+        result
+    }
+}
+
+impl<S: Symbol> SpaceUsage for RunLengthFmIndex<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.heads.heap_bytes()
+            + self.starts.heap_bytes()
+            + self.cumulative.heap_bytes()
+            + self.ends.heap_bytes()
+            + self.phi_keys.heap_bytes()
+            + self.phi_values.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use text::FmIndex;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty_text() {
+        let index = RunLengthFmIndex::<u8>::new(&[]);
+        assert!(index.is_empty());
+        assert_eq!(1, index.runs());
+        assert_eq!(0, index.count(b"a"));
+        assert_eq!(vec![0], index.locate(b""));
+    }
+
+    #[test]
+    fn repetitive() {
+        let text: Vec<u8> = b"to be or not to be ".iter().cycle().take(19 * 50)
+                                                   .cloned().collect();
+        let index = RunLengthFmIndex::new(&text);
+        assert!(index.runs() < 40);
+        assert_eq!(100, index.count(b"be"));
+
+        let mut found = index.locate(b"not");
+        found.sort();
+        let expected: Vec<u64> = (0 .. 50).map(|i| i * 19 + 9).collect();
+        assert_eq!(expected, found);
+    }
+
+    #[test]
+    fn qc_matches_fm_index() {
+        fn prop(text: Vec<u8>, pattern: Vec<u8>) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 3).collect();
+            let pattern: Vec<u8> = pattern.into_iter().take(4)
+                                          .map(|b| b % 3).collect();
+            let index = RunLengthFmIndex::new(&text);
+            let reference = FmIndex::new(&text, 1);
+
+            let mut found = index.locate(&pattern);
+            found.sort();
+            let mut expected = reference.locate(&pattern);
+            expected.sort();
+
+            index.count(&pattern) == reference.count(&pattern) && found == expected
+        }
+
+        quickcheck(prop as fn(Vec<u8>, Vec<u8>) -> bool);
+    }
+}
@@ -0,0 +1,260 @@
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use persist::{self, Persist};
+
+/// Decides which suffixes an [`FmIndex`](struct.FmIndex.html) samples.
+///
+/// A sampled suffix has its text position recorded. `locate` walks the
+/// transform from each match to the nearest sampled suffix, and
+/// `extract` starts from the first sampled position after the range it
+/// recovers, so sampling more makes both faster at the cost of space.
+/// Whatever the policy, the index also samples the whole text and the
+/// empty suffix at its end, so every walk stops.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::{FmIndex, RowSampling, TextSampling};
+///
+/// let by_text = FmIndex::with_sampling(b"mississippi", &TextSampling::new(4));
+/// let by_row = FmIndex::with_sampling(b"mississippi", &RowSampling::new(4));
+/// assert_eq!(by_text.locate(b"ssi").len(), by_row.locate(b"ssi").len());
+/// ```
+pub trait SamplingPolicy {
+    /// Whether to sample the suffix at `row` in suffix order, which
+    /// starts at text position `position`. `at_run_boundary` says
+    /// whether the symbol the transform holds at `row` differs from the
+    /// one before it or the one after.
+    fn is_sampled(&self, row: u64, position: u64, at_run_boundary: bool) -> bool;
+
+    /// The policy as [`Sampling`](enum.Sampling.html) parameters, which
+    /// an index records, or `None` for a policy of the caller’s own.
+    fn parameters(&self) -> Option<Sampling> {
+        None
+    }
+}
+
+/// The parameters of one of the crate’s sampling policies, in a form
+/// that can be saved with the index it built and used to build another.
+///
+/// [`FmIndex::sampling`](struct.FmIndex.html#method.sampling) gives an
+/// index’s, and it is itself a policy, so it chooses the same samples
+/// again. Saved with [`Persist`](../persist/trait.Persist.html), it is a
+/// tag byte and the rate as a `u64`.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::{FmIndex, RowSampling, Sampling};
+///
+/// let index = FmIndex::with_sampling(b"mississippi", &RowSampling::new(4));
+/// assert_eq!(Some(Sampling::Row(4)), index.sampling());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sampling {
+    /// [`TextSampling`](struct.TextSampling.html) at the rate.
+    Text(usize),
+    /// [`RowSampling`](struct.RowSampling.html) at the rate.
+    Row(usize),
+    /// [`RunSampling`](struct.RunSampling.html) at the rate.
+    Run(usize),
+}
+
+impl Sampling {
+    /// The rate, whichever the policy.
+    pub fn rate(self) -> usize {
+        match self {
+            Sampling::Text(rate) | Sampling::Row(rate) | Sampling::Run(rate) => rate,
+        }
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            Sampling::Text(_) => 0,
+            Sampling::Row(_) => 1,
+            Sampling::Run(_) => 2,
+        }
+    }
+}
+
+impl SamplingPolicy for Sampling {
+    fn is_sampled(&self, row: u64, position: u64, at_run_boundary: bool) -> bool {
+        let rate = self.rate() as u64;
+        match *self {
+            Sampling::Text(_) => position % rate == 0,
+            Sampling::Row(_) => row % rate == 0,
+            Sampling::Run(_) => at_run_boundary || position % rate == 0,
+        }
+    }
+
+    fn parameters(&self) -> Option<Sampling> {
+        Some(*self)
+    }
+}
+
+impl Persist for Sampling {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u8(self.tag())?;
+        sink.write_u64::<LittleEndian>(self.rate() as u64)
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let who = "Sampling::read_from";
+        let tag = bytes.read_u8()?;
+        let rate = bytes.read_u64::<LittleEndian>()?;
+        persist::expect_end(who, bytes)?;
+
+        let rate = match usize::try_from(rate) {
+            Ok(rate) if rate > 0 => rate,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                           format!("{}: could not load: bad rate", who))),
+        };
+        match tag {
+            0 => Ok(Sampling::Text(rate)),
+            1 => Ok(Sampling::Row(rate)),
+            2 => Ok(Sampling::Run(rate)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                    format!("{}: could not load: unknown policy", who))),
+        }
+    }
+}
+
+/// Samples every `rate`th text position.
+///
+/// A walk takes fewer than `rate` steps, and `extract` of any range
+/// visits fewer than `rate` positions outside it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextSampling {
+    rate: u64,
+}
+
+impl TextSampling {
+    /// Samples every `rate`th text position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is 0.
+    pub fn new(rate: usize) -> Self {
+        assert!(rate > 0, "TextSampling::new: rate must be positive");
+        TextSampling { rate: rate as u64 }
+    }
+
+    /// The distance between sampled text positions.
+    pub fn rate(&self) -> usize {
+        self.rate as usize
+    }
+}
+
+impl SamplingPolicy for TextSampling {
+    fn is_sampled(&self, _row: u64, position: u64, _at_run_boundary: bool) -> bool {
+        position % self.rate == 0
+    }
+
+    fn parameters(&self) -> Option<Sampling> {
+        Some(Sampling::Text(self.rate()))
+    }
+}
+
+/// Samples every `rate`th row of the suffix order.
+///
+/// This takes the same space as [`TextSampling`](struct.TextSampling.html)
+/// at the same rate, and the samples of a range of rows are spread
+/// evenly through it, but a walk is bounded only on average.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RowSampling {
+    rate: u64,
+}
+
+impl RowSampling {
+    /// Samples every `rate`th row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is 0.
+    pub fn new(rate: usize) -> Self {
+        assert!(rate > 0, "RowSampling::new: rate must be positive");
+        RowSampling { rate: rate as u64 }
+    }
+
+    /// The distance between sampled rows.
+    pub fn rate(&self) -> usize {
+        self.rate as usize
+    }
+}
+
+impl SamplingPolicy for RowSampling {
+    fn is_sampled(&self, row: u64, _position: u64, _at_run_boundary: bool) -> bool {
+        row % self.rate == 0
+    }
+
+    fn parameters(&self) -> Option<Sampling> {
+        Some(Sampling::Row(self.rate()))
+    }
+}
+
+/// Samples the first and last row of every run of equal symbols in the
+/// transform, and every `rate`th text position besides.
+///
+/// On repetitive text the transform has few runs, so the boundaries are
+/// cheap to keep, and the rate bounds the walks between them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunSampling {
+    text: TextSampling,
+}
+
+impl RunSampling {
+    /// Samples the run boundaries and every `rate`th text position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is 0.
+    pub fn new(rate: usize) -> Self {
+        RunSampling { text: TextSampling::new(rate) }
+    }
+
+    /// The distance between the text positions sampled besides the run
+    /// boundaries.
+    pub fn rate(&self) -> usize {
+        self.text.rate()
+    }
+}
+
+impl SamplingPolicy for RunSampling {
+    fn is_sampled(&self, row: u64, position: u64, at_run_boundary: bool) -> bool {
+        at_run_boundary || self.text.is_sampled(row, position, at_run_boundary)
+    }
+
+    fn parameters(&self) -> Option<Sampling> {
+        Some(Sampling::Run(self.rate()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parameters_round_trip() {
+        let policies: [&dyn SamplingPolicy; 3] = [
+            &TextSampling::new(3),
+            &RowSampling::new(5),
+            &RunSampling::new(7),
+        ];
+        for policy in &policies {
+            let sampling = policy.parameters().unwrap();
+            let mut bytes = Vec::new();
+            sampling.write_to(&mut bytes).unwrap();
+            assert_eq!(sampling, Sampling::read_from(&bytes).unwrap());
+            assert!((0 .. 40).all(|i| {
+                policy.is_sampled(i, 39 - i, i % 4 == 0) == sampling.is_sampled(i, 39 - i, i % 4 == 0)
+            }));
+        }
+
+        assert!(Sampling::read_from(&[3, 1, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(Sampling::read_from(&[0; 9]).is_err());
+        assert!(Sampling::read_from(&[0, 1]).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::io::{self, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use build::Monitor;
+use error::Error;
+use persist::{self, Component, Persist};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use super::Symbol;
+
+/// Computes the suffix array of `text` followed by an end-of-text
+/// marker that sorts before every symbol.
+///
+/// The result has `text.len() + 1` entries, the first always being
+/// `text.len()`, the marker’s own suffix. It is built by prefix
+/// doubling, in *O*(*n* lg² *n*) time.
+///
+/// ```
+/// use succinct::text::suffix_array;
+///
+/// assert_eq!(vec![6, 5, 3, 1, 0, 4, 2], suffix_array(b"banana"));
+/// ```
+pub fn suffix_array<S: Symbol>(text: &[S]) -> Vec<u64> {
+    SuffixArrayBuilder::new(text).finish()
+}
+
+/// A suffix array construction that can be stopped between rounds,
+/// saved, and resumed.
+///
+/// Each [`step`](#method.step) is one round of prefix doubling, after
+/// which the suffixes are sorted by twice as many symbols as before.
+/// The state between rounds doesn’t refer to the text, so a builder
+/// saved with [`Persist`](../persist/trait.Persist.html) resumes
+/// without it.
+///
+/// # Example
+///
+/// ```
+/// use succinct::persist::Persist;
+/// use succinct::text::{suffix_array, SuffixArrayBuilder};
+///
+/// let text = b"mississippi";
+/// let mut builder = SuffixArrayBuilder::new(text);
+/// builder.step();
+///
+/// let mut checkpoint = Vec::new();
+/// builder.write_to(&mut checkpoint).unwrap();
+/// let resumed = SuffixArrayBuilder::read_from(&checkpoint).unwrap();
+/// assert_eq!(suffix_array(text), resumed.finish());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuffixArrayBuilder {
+    // rank[i] orders suffix i by the symbols sorted so far, and order
+    // lists the suffixes in that order.
+    rank: Vec<u64>,
+    order: Vec<u64>,
+    width: u64,
+    done: bool,
+}
+
+impl SuffixArrayBuilder {
+    /// Starts the suffix array of `text` and an end-of-text marker.
+    pub fn new<S: Symbol>(text: &[S]) -> Self {
+        // The marker gets rank 0 and the symbols their value plus one.
+        SuffixArrayBuilder {
+            rank: text.iter().map(|&symbol| symbol.to_u64() + 1).chain(Some(0)).collect(),
+            order: (0 .. text.len() as u64 + 1).collect(),
+            width: 1,
+            done: false,
+        }
+    }
+
+    /// Runs one round, returning whether the array is finished.
+    pub fn step(&mut self) -> bool {
+        if self.done { return true; }
+
+        let len = self.order.len();
+        let (rank, width) = (&self.rank, self.width as usize);
+        let key = |i: u64| {
+            let i = i as usize;
+            (rank[i], if i + width < len {rank[i + width] + 1} else {0})
+        };
+
+        self.order.sort_unstable_by_key(|&i| key(i));
+
+        let mut next_rank = vec![0; len];
+        for j in 1 .. len {
+            let step = (key(self.order[j - 1]) != key(self.order[j])) as u64;
+            next_rank[self.order[j] as usize] = next_rank[self.order[j - 1] as usize] + step;
+        }
+
+        self.rank = next_rank;
+        if self.rank[self.order[len - 1] as usize] as usize == len - 1 {
+            self.done = true;
+        } else {
+            self.width *= 2;
+        }
+        self.done
+    }
+
+    /// Is the array finished?
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Runs the remaining rounds, reporting each to `monitor`, or returns
+    /// `Error::OverBudget` before starting if they would need more memory
+    /// than `monitor` allows, or `Error::Cancelled` between rounds if
+    /// `monitor` cancels, leaving the builder to be resumed or saved.
+    ///
+    /// There are at most about lg *n* rounds, which is the total
+    /// reported; text without long repeats takes fewer.
+    pub fn run(&mut self, monitor: &mut Monitor) -> Result<(), Error> {
+        // Each round makes a new rank array beside the state.
+        monitor.reserve((self.heap_bytes() + 8 * self.order.len()) as u64)?;
+        let total = bits_to_represent(self.order.len() as u64) as u64 + 1;
+        let mut done = self.width.trailing_zeros() as u64;
+        while !self.done {
+            monitor.report("suffix array", done, total)?;
+            self.step();
+            done += 1;
+        }
+        monitor.report("suffix array", total, total)
+    }
+
+    /// Runs the remaining rounds and returns the suffix array.
+    pub fn finish(mut self) -> Vec<u64> {
+        self.run(&mut Monitor::new()).expect("SuffixArrayBuilder::finish");
+        self.order
+    }
+}
+
+/// Whether it is done and the width as `u64`s, then the ranks and the
+/// order.
+impl Persist for SuffixArrayBuilder {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u64::<LittleEndian>(self.done as u64)?;
+        sink.write_u64::<LittleEndian>(self.width)?;
+        persist::write_part(&self.rank, sink)?;
+        persist::write_part(&self.order, sink)
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let who = "SuffixArrayBuilder::read_from";
+        let done = bytes.read_u64::<LittleEndian>()? != 0;
+        let width = bytes.read_u64::<LittleEndian>()?;
+        let rank: Vec<u64> = persist::read_part(&mut bytes)?;
+        let order: Vec<u64> = persist::read_part(&mut bytes)?;
+        persist::expect_end(who, bytes)?;
+
+        let len = order.len() as u64;
+        if len == 0 || rank.len() as u64 != len || !width.is_power_of_two()
+            || order.iter().any(|&i| i >= len) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("{}: could not load: inconsistent state", who)));
+        }
+        Ok(SuffixArrayBuilder { rank, order, width, done })
+    }
+}
+
+impl SpaceUsage for SuffixArrayBuilder {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.rank.heap_bytes() + self.order.heap_bytes()
+    }
+}
+
+impl Component for SuffixArrayBuilder {
+    fn tag() -> String {
+        "SuffixArrayBuilder".to_owned()
+    }
+}
+
+/// Computes the inverse of a permutation such as a suffix array.
+pub fn inverse_permutation(permutation: &[u64]) -> Vec<u64> {
+    let mut result = vec![0; permutation.len()];
+    for (i, &j) in permutation.iter().enumerate() {
+        result[j as usize] = i as u64;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        assert_eq!(vec![0], suffix_array::<u8>(&[]));
+    }
+
+    #[test]
+    fn repetitive() {
+        assert_eq!(vec![5, 4, 3, 2, 1, 0], suffix_array(&[7u32; 5]));
+        assert_eq!(vec![6, 4, 2, 0, 5, 3, 1], suffix_array(b"ababab"));
+    }
+
+    #[test]
+    fn qc_sorted() {
+        fn prop(text: Vec<u8>) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 4).collect();
+            let sa = suffix_array(&text);
+
+            let mut naive: Vec<u64> = (0 .. text.len() as u64 + 1).collect();
+            naive.sort_by_key(|&i| &text[i as usize ..]);
+
+            sa == naive && inverse_permutation(&inverse_permutation(&sa)) == sa
+        }
+
+        quickcheck(prop as fn(Vec<u8>) -> bool);
+    }
+
+    #[test]
+    fn qc_resumes() {
+        fn prop(text: Vec<u8>, rounds: u8) -> bool {
+            let text: Vec<u8> = text.into_iter().map(|b| b % 3).collect();
+            let mut builder = SuffixArrayBuilder::new(&text);
+            for _ in 0 .. rounds % 4 {
+                builder.step();
+            }
+
+            let mut checkpoint = Vec::new();
+            builder.write_to(&mut checkpoint).unwrap();
+            let resumed = SuffixArrayBuilder::read_from(&checkpoint).unwrap();
+            resumed == builder && resumed.finish() == suffix_array(&text)
+        }
+
+        quickcheck(prop as fn(Vec<u8>, u8) -> bool);
+    }
+}
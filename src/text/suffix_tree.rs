@@ -0,0 +1,494 @@
+use std::ops::Range;
+
+use bit_vec::{BitVecMut, BitVecPush, BitVector};
+use internal::search::binary_search_function;
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9};
+use space_usage::SpaceUsage;
+use tree::RangeMinMaxTree;
+use super::{suffix_array, FmIndex, Symbol};
+
+/// A node of a
+/// [`CompressedSuffixTree`](struct.CompressedSuffixTree.html): the rows
+/// of the suffixes under it, and the length of the string on the path
+/// to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CstNode {
+    start: u64,
+    end: u64,
+    depth: u64,
+}
+
+impl CstNode {
+    /// The rows of the node’s leaves in the underlying index, which are
+    /// those of the suffixes starting with its string.
+    pub fn rows(&self) -> Range<u64> {
+        self.start .. self.end
+    }
+
+    /// The length of the string on the path to the node, not counting
+    /// the end-of-text marker that leaves implicitly end with.
+    pub fn depth(&self) -> u64 {
+        self.depth
+    }
+
+    /// The number of leaves under the node, which is the number of
+    /// occurrences of its string.
+    pub fn leaves(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Is this a leaf, the end of a single suffix?
+    pub fn is_leaf(&self) -> bool {
+        self.leaves() == 1
+    }
+}
+
+/// A compressed suffix tree: an [`FmIndex`](struct.FmIndex.html), the
+/// permuted LCP array, and the tree topology of the LCP array as
+/// balanced parentheses.
+///
+/// The tree is that of the text followed by an end-of-text marker, so
+/// there is one leaf per suffix, including the empty one. A node is the
+/// range of its leaves’ rows plus its string depth. The LCP array gives
+/// the length of the prefix each suffix shares with the one before, and
+/// the children of a node split its rows where that equals its depth.
+/// The array is kept in text order, as PLCP, in 2*n* bits, so reading an
+/// entry takes a `locate`.
+///
+/// The topology is two parentheses sequences of 2*n* bits over the LCP
+/// array, each searched with a
+/// [`RangeMinMaxTree`](../tree/struct.RangeMinMaxTree.html): a walk of
+/// the entries pushes each on a stack after popping the greater ones, or
+/// in the second also the equal ones, writing an open for every push
+/// and a close for every pop. Range minima and the nearest smaller
+/// entries on either side are then excess searches, so a node’s first
+/// child boundary, the next, and its parent and suffix link are found in
+/// a few of those, without reading the text. Reading a string depth
+/// costs a `locate`, under `sample_rate` steps of the transform, as does
+/// each symbol `child` compares in its binary search over the children.
+///
+/// # Example
+///
+/// ```
+/// use succinct::text::CompressedSuffixTree;
+///
+/// let tree = CompressedSuffixTree::new(b"banana", 2);
+/// let root = tree.root();
+///
+/// let a = tree.child(root, b'a').unwrap();
+/// assert_eq!(1, a.depth());
+/// assert_eq!(3, a.leaves());
+///
+/// let n = tree.child(root, b'n').unwrap();
+/// assert_eq!(b"na".to_vec(), tree.label(n));
+///
+/// let ana = tree.child(a, b'n').unwrap();
+/// assert_eq!(b"ana".to_vec(), tree.label(ana));
+/// assert_eq!(Some(n), tree.suffix_link(ana));
+/// assert_eq!(Some(a), tree.parent(ana));
+/// assert_eq!(3, tree.lcp(3));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompressedSuffixTree<S: Symbol = u8> {
+    index: FmIndex<S>,
+    // A 1 at 2p + PLCP[p] for each suffix position p.
+    plcp: Select9<BitVector<u64>>,
+    // The LCP entries as parentheses where equal entries nest, giving
+    // range minima and next smaller entries.
+    ties_nest: RangeMinMaxTree,
+    // Where equal entries pop each other, giving previous smaller and
+    // next smaller or equal entries.
+    ties_pop: RangeMinMaxTree,
+}
+
+impl<S: Symbol> CompressedSuffixTree<S> {
+    /// Builds the tree of `text`, sampling every `sample_rate`th position
+    /// for `locate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is 0.
+    pub fn new(text: &[S], sample_rate: usize) -> Self {
+        let index = FmIndex::new(text, sample_rate);
+        let suffixes = suffix_array(text);
+        let lcp = lcp_array(text, &suffixes);
+
+        let mut plcp = BitVector::with_fill(2 * lcp.len() as u64, false);
+        for (row, &position) in suffixes.iter().enumerate() {
+            plcp.set_bit(2 * position + lcp[row], true);
+        }
+
+        CompressedSuffixTree {
+            index,
+            plcp: Select9::new(Rank9::new(plcp)),
+            ties_nest: parens(&lcp, false),
+            ties_pop: parens(&lcp, true),
+        }
+    }
+
+    /// The underlying index.
+    pub fn index(&self) -> &FmIndex<S> {
+        &self.index
+    }
+
+    /// The length of the prefix that the suffix at `row` shares with the
+    /// one at `row - 1`, or 0 for row 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `row > self.index().len()`.
+    pub fn lcp(&self, row: u64) -> u64 {
+        let position = self.index.suffix_at(row);
+        let bit = self.plcp.select1(position).expect("CompressedSuffixTree: PLCP too short");
+        bit - 2 * position
+    }
+
+    /// The root, whose string is empty.
+    pub fn root(&self) -> CstNode {
+        CstNode { start: 0, end: self.index.len() + 1, depth: 0 }
+    }
+
+    // The node whose leaves are the rows `start .. end`.
+    fn node(&self, start: u64, end: u64) -> CstNode {
+        let depth = if end - start == 1 {
+            self.index.len() - self.index.suffix_at(start)
+        } else {
+            self.lcp(range_min(&self.ties_nest, start + 1, end - 1))
+        };
+        CstNode { start, end, depth }
+    }
+
+    // The rows of the children of `node`, in order, split at the entries
+    // of the LCP array equal to its depth.
+    fn child_rows(&self, node: CstNode) -> Vec<Range<u64>> {
+        if node.is_leaf() { return Vec::new(); }
+
+        let mut result = Vec::new();
+        let mut start = node.start;
+        let mut boundary = range_min(&self.ties_nest, node.start + 1, node.end - 1);
+        while boundary < node.end {
+            result.push(start .. boundary);
+            start = boundary;
+            boundary = next_smaller(&self.ties_pop, boundary);
+        }
+        result.push(start .. node.end);
+        result
+    }
+
+    /// The children of `node`, in the order of the symbols leading to
+    /// them; the leaf of a suffix ending at the node comes first.
+    pub fn children(&self, node: CstNode) -> Vec<CstNode> {
+        self.child_rows(node).into_iter()
+            .map(|rows| self.node(rows.start, rows.end))
+            .collect()
+    }
+
+    /// The child of `node` whose edge starts with `symbol`, if any.
+    pub fn child(&self, node: CstNode, symbol: S) -> Option<CstNode> {
+        // The symbol after the node’s string, or `None` for the marker.
+        let next = |rows: &Range<u64>| {
+            let position = self.index.suffix_at(rows.start) + node.depth;
+            if position == self.index.len() {
+                None
+            } else {
+                Some(self.index.extract(position .. position + 1)[0])
+            }
+        };
+
+        let rows = self.child_rows(node);
+        let found = rows.binary_search_by(|rows| next(rows).cmp(&Some(symbol))).ok()?;
+        Some(self.node(rows[found].start, rows[found].end))
+    }
+
+    /// The parent of `node`, unless it is the root.
+    pub fn parent(&self, node: CstNode) -> Option<CstNode> {
+        // The deeper of the LCP entries just outside the node’s rows
+        // bounds its parent.
+        let before = if node.start > 0 {Some(self.lcp(node.start))} else {None};
+        let after = if node.end <= self.index.len() {Some(self.lcp(node.end))} else {None};
+        let (row, depth) = match (before, after) {
+            (None, None) => return None,
+            (Some(before), Some(after)) if after > before => (node.end, after),
+            (Some(before), _) => (node.start, before),
+            (None, Some(after)) => (node.end, after),
+        };
+
+        Some(CstNode {
+            start: previous_smaller(&self.ties_pop, row),
+            end: next_smaller(&self.ties_nest, row),
+            depth,
+        })
+    }
+
+    /// The node of `node`’s string minus its first symbol, for internal
+    /// nodes other than the root.
+    ///
+    /// It is the lowest common ancestor of the leaves one position on
+    /// from the node’s first and last, whose rows are those around the
+    /// least LCP entry between them.
+    pub fn suffix_link(&self, node: CstNode) -> Option<CstNode> {
+        if node.depth == 0 || node.is_leaf() { return None; }
+
+        let first = self.index.psi(node.start);
+        let last = self.index.psi(node.end - 1);
+        let row = range_min(&self.ties_nest, first + 1, last);
+        Some(CstNode {
+            start: previous_smaller(&self.ties_pop, row),
+            end: next_smaller(&self.ties_nest, row),
+            depth: node.depth - 1,
+        })
+    }
+
+    /// The string on the path to `node`.
+    pub fn label(&self, node: CstNode) -> Vec<S> {
+        let position = self.index.suffix_at(node.start);
+        self.index.extract(position .. position + node.depth)
+    }
+
+    /// The text positions of the leaves under `node`, in no particular
+    /// order.
+    pub fn locate(&self, node: CstNode) -> Vec<u64> {
+        node.rows().map(|row| self.index.suffix_at(row)).collect()
+    }
+}
+
+// The LCP array of the suffixes of `text` in the order of `suffixes`,
+// by Kasai et al.’s scan in text order.
+fn lcp_array<S: Symbol>(text: &[S], suffixes: &[u64]) -> Vec<u64> {
+    let mut rows = vec![0; suffixes.len()];
+    for (row, &position) in suffixes.iter().enumerate() {
+        rows[position as usize] = row;
+    }
+
+    let mut result = vec![0; suffixes.len()];
+    let mut shared = 0;
+    for position in 0 .. suffixes.len() {
+        let row = rows[position];
+        if row == 0 {
+            shared = 0;
+            continue;
+        }
+
+        let before = suffixes[row - 1] as usize;
+        while position + shared < text.len() && before + shared < text.len()
+            && text[position + shared] == text[before + shared] {
+            shared += 1;
+        }
+        result[row] = shared as u64;
+        shared = shared.saturating_sub(1);
+    }
+
+    result
+}
+
+// The parentheses of `lcp`: an open for each entry, after a close for
+// each one before it still on the stack that is greater, or with `ties`
+// greater or equal.
+fn parens(lcp: &[u64], ties: bool) -> RangeMinMaxTree {
+    let mut bits = BitVector::with_capacity(2 * lcp.len() as u64);
+    let mut stack: Vec<u64> = Vec::new();
+    for &entry in lcp {
+        while stack.last().is_some_and(|&top| top > entry || ties && top == entry) {
+            stack.pop();
+            bits.push_bit(false);
+        }
+        stack.push(entry);
+        bits.push_bit(true);
+    }
+    for _ in stack {
+        bits.push_bit(false);
+    }
+
+    RangeMinMaxTree::new(bits)
+}
+
+// The position of the open of entry `row`.
+fn open(parens: &RangeMinMaxTree, row: u64) -> u64 {
+    binary_search_function(0, parens.len(), row + 1, |position| parens.bits().rank1(position))
+        .expect("CompressedSuffixTree: no such entry")
+}
+
+// The leftmost least entry in `start ..= end`. The entry pushed last at
+// the lowest height between the two opens, where equal entries nest,
+// is less than those before and no greater than those after.
+fn range_min(parens: &RangeMinMaxTree, start: u64, end: u64) -> u64 {
+    let (first, last) = (open(parens, start), open(parens, end));
+    let delta = parens.min_excess(first, last) - parens.excess(last + 1);
+    let position = parens.bwd_search(last + 1, delta)
+        .expect("CompressedSuffixTree: minimum not reached");
+    parens.bits().rank1(position) - 1
+}
+
+// The first entry after `row` that pops it, or the number of entries if
+// none does: the next one that is smaller, or with ties popping, smaller
+// or equal.
+fn next_smaller(parens: &RangeMinMaxTree, row: u64) -> u64 {
+    let close = parens.fwd_search(open(parens, row), 0)
+        .expect("CompressedSuffixTree: unmatched open") - 1;
+    parens.bits().rank1(close)
+}
+
+// The entry `row` was pushed on, or 0 if it went on an empty stack.
+// Where ties pop, that is the last entry before it that is smaller.
+fn previous_smaller(parens: &RangeMinMaxTree, row: u64) -> u64 {
+    parens.bwd_search(open(parens, row), -1)
+        .map_or(0, |position| parens.bits().rank1(position) - 1)
+}
+
+impl<S: Symbol> SpaceUsage for CompressedSuffixTree<S> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.index.heap_bytes() + self.plcp.heap_bytes()
+            + self.ties_nest.heap_bytes() + self.ties_pop.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+    use quickcheck::quickcheck;
+
+    // Every node’s label and number of leaves, and whether it is a leaf.
+    fn walk(tree: &CompressedSuffixTree, node: CstNode,
+            result: &mut Vec<(Vec<u8>, u64, bool)>) {
+        result.push((tree.label(node), node.leaves(), node.is_leaf()));
+        for child in tree.children(node) {
+            walk(tree, child, result);
+        }
+    }
+
+    // The internal nodes of the suffix tree: the substrings followed by
+    // at least two distinct symbols, or the end, with their counts.
+    fn naive_internal(text: &[u8]) -> BTreeMap<Vec<u8>, u64> {
+        let mut followers: BTreeMap<Vec<u8>, (BTreeSet<Option<u8>>, u64)> =
+            BTreeMap::new();
+        for start in 0 .. text.len() + 1 {
+            for end in start .. text.len() + 1 {
+                let entry = followers.entry(text[start .. end].to_vec())
+                                     .or_insert((BTreeSet::new(), 0));
+                entry.0.insert(text.get(end).cloned());
+                entry.1 += 1;
+            }
+        }
+
+        followers.into_iter()
+                 .filter(|&(_, (ref next, _))| next.len() > 1)
+                 .map(|(string, (_, count))| (string, count))
+                 .collect()
+    }
+
+    #[test]
+    fn empty_text() {
+        let tree = CompressedSuffixTree::<u8>::new(&[], 1);
+        let root = tree.root();
+        assert!(root.is_leaf());
+        assert!(tree.children(root).is_empty());
+        assert_eq!(None, tree.suffix_link(root));
+    }
+
+    #[test]
+    fn mississippi() {
+        let tree = CompressedSuffixTree::new(b"mississippi", 3);
+        let mut nodes = Vec::new();
+        walk(&tree, tree.root(), &mut nodes);
+
+        let leaves = nodes.iter().filter(|node| node.2).count();
+        assert_eq!(12, leaves);
+
+        let issi = tree.child(tree.root(), b'i')
+                       .and_then(|i| tree.child(i, b's'))
+                       .unwrap();
+        assert_eq!(b"issi".to_vec(), tree.label(issi));
+        let mut found = tree.locate(issi);
+        found.sort();
+        assert_eq!(vec![1, 4], found);
+
+        let ssi = tree.suffix_link(issi).unwrap();
+        assert_eq!(b"ssi".to_vec(), tree.label(ssi));
+        assert_eq!(None, tree.child(ssi, b'x'));
+    }
+
+    #[test]
+    fn long_text() {
+        // Spans several blocks of the parentheses’ search trees.
+        let mut state = 7u32;
+        let text: Vec<u8> = (0 .. 3000).map(|i| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            if i % 500 < 200 {b"acgt"[i % 4]} else {b"acgt"[(state >> 16) as usize % 4]}
+        }).collect();
+        let tree = CompressedSuffixTree::new(&text, 16);
+
+        // Descend along the suffix at 1000, which is inside a repeat.
+        let mut node = tree.root();
+        while !node.is_leaf() {
+            let child = tree.child(node, text[1000 + node.depth() as usize]).unwrap();
+            assert_eq!(tree.label(child)[..], text[1000 .. 1000 + child.depth() as usize]);
+            assert_eq!(Some(node), tree.parent(child));
+            if let Some(link) = tree.suffix_link(child) {
+                assert_eq!(tree.label(link)[..], tree.label(child)[1 ..]);
+                let shifted = tree.locate(link);
+                assert!(tree.locate(child).iter().all(|&p| shifted.contains(&(p + 1))));
+            }
+            node = child;
+        }
+        assert_eq!(2000, node.depth());
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(text: Vec<u8>) -> bool {
+            let text: Vec<u8> = text.into_iter().take(30).map(|b| b % 3).collect();
+            let tree = CompressedSuffixTree::new(&text, 2);
+
+            let mut nodes = Vec::new();
+            walk(&tree, tree.root(), &mut nodes);
+
+            let internal: BTreeMap<Vec<u8>, u64> = nodes.iter()
+                .filter(|node| !node.2)
+                .map(|node| (node.0.clone(), node.1))
+                .collect();
+            let leaves: BTreeSet<Vec<u8>> = nodes.iter()
+                .filter(|node| node.2)
+                .map(|node| node.0.clone())
+                .collect();
+            let suffixes: BTreeSet<Vec<u8>> = (0 .. text.len() + 1)
+                .map(|i| text[i ..].to_vec())
+                .collect();
+
+            let mut links_ok = true;
+            let mut parents_ok = tree.parent(tree.root()).is_none();
+            let mut stack = vec![tree.root()];
+            while let Some(node) = stack.pop() {
+                if let Some(link) = tree.suffix_link(node) {
+                    links_ok &= tree.label(link)[..] == tree.label(node)[1 ..]
+                        && !link.is_leaf();
+                }
+                for child in tree.children(node) {
+                    let label = tree.label(child);
+                    parents_ok &= tree.parent(child) == Some(node)
+                        && (label.len() as u64 == node.depth()
+                            || tree.child(node, label[node.depth() as usize]) == Some(child));
+                    stack.push(child);
+                }
+            }
+
+            let rows = suffix_array(&text);
+            let lcps_ok = (1 .. rows.len()).all(|row| {
+                let (a, b) = (&text[rows[row - 1] as usize ..], &text[rows[row] as usize ..]);
+                tree.lcp(row as u64) == a.iter().zip(b).take_while(|&(x, y)| x == y).count() as u64
+            });
+
+            internal == naive_internal(&text)
+                && leaves == suffixes
+                && nodes.iter().filter(|node| node.2).count() == text.len() + 1
+                && links_ok
+                && parents_ok
+                && lcps_ok
+        }
+
+        quickcheck(prop as fn(Vec<u8>) -> bool);
+    }
+}
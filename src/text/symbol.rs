@@ -0,0 +1,29 @@
+use std::fmt;
+
+/// The unsigned integer types that text indexes accept as symbols.
+///
+/// Indexes keep symbols as `u64`s, shifted up by one to leave room for
+/// the end-of-text marker, so they need a lossless round trip.
+pub trait Symbol: Copy + Ord + fmt::Debug {
+    /// Widens the symbol.
+    fn to_u64(self) -> u64;
+
+    /// Narrows a value that came from `to_u64`.
+    fn from_u64(value: u64) -> Self;
+}
+
+macro_rules! impl_symbol {
+    ( $t:ty ) => {
+        impl Symbol for $t {
+            #[inline]
+            fn to_u64(self) -> u64 { self as u64 }
+
+            #[inline]
+            fn from_u64(value: u64) -> Self { value as $t }
+        }
+    }
+}
+
+impl_symbol!(u8);
+impl_symbol!(u16);
+impl_symbol!(u32);
@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+
+use bit_vec::{BitVecPush, BitVector};
+use error::Error;
+use super::{BalancedParens, Louds};
+
+/// A step of a depth-first walk: entering a node or leaving it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DfsEvent {
+    /// Enters a new child of the current node, or the root.
+    Open,
+    /// Leaves the current node.
+    Close,
+}
+
+/// A value whose children make it a tree, like a parsed document.
+pub trait Nested {
+    /// The children, in order.
+    fn children(&self) -> Vec<&Self>;
+}
+
+/// Gathers the shape of a tree from a common representation, and
+/// encodes it succinctly.
+///
+/// The input’s nodes are numbered as it gives them: by index for a
+/// parent array, and in preorder for the others. The encodings number
+/// nodes their own way, so [`louds_ids`](#method.louds_ids) and
+/// [`parens_ids`](#method.parens_ids) map the input’s numbers to theirs,
+/// for carrying per-node data across.
+///
+/// # Example
+///
+/// ```
+/// use succinct::tree::TreeBuilder;
+/// use succinct::tree::DfsEvent::*;
+///
+/// // A root with two children, the first with one of its own.
+/// let builder = TreeBuilder::from_dfs(vec![Open, Open, Open, Close, Close,
+///                                          Open, Close, Close]).unwrap();
+/// assert_eq!(4, builder.len());
+/// assert_eq!(vec![0, 1, 3, 2], builder.louds_ids());
+///
+/// let louds = builder.to_louds();
+/// assert_eq!(Some(3), louds.first_child(1));
+///
+/// assert!(TreeBuilder::from_dfs(vec![Open, Close, Close]).is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TreeBuilder {
+    // The children of each node, in order; node 0 is the root unless
+    // `root` says otherwise.
+    children: Vec<Vec<usize>>,
+    root: usize,
+}
+
+impl TreeBuilder {
+    /// Reads a tree from the parent of each node, `None` for the root.
+    /// Children are ordered by index.
+    ///
+    /// Returns an error unless there is exactly one root, every parent
+    /// is in range, and every node descends from the root.
+    pub fn from_parents(parents: &[Option<u64>]) -> Result<Self, Error> {
+        let mut children = vec![Vec::new(); parents.len()];
+        let mut root = None;
+
+        for (node, &parent) in parents.iter().enumerate() {
+            match parent {
+                None if root.is_none() => root = Some(node),
+                None => return Err(Error::InvalidTree),
+                Some(parent) if parent < parents.len() as u64 =>
+                    children[parent as usize].push(node),
+                Some(_) => return Err(Error::InvalidTree),
+            }
+        }
+
+        let builder = TreeBuilder { children, root: root.unwrap_or(0) };
+        if !parents.is_empty() && (root.is_none() || builder.preorder().len() != parents.len()) {
+            // A cycle cuts some nodes off from the root.
+            return Err(Error::InvalidTree);
+        }
+        Ok(builder)
+    }
+
+    /// Reads a tree from a depth-first walk, which must open the root
+    /// first, close every node it opens, and stop when it closes the
+    /// root; an empty walk is an empty tree.
+    pub fn from_dfs<I>(events: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = DfsEvent> {
+
+        let mut children: Vec<Vec<usize>> = Vec::new();
+        let mut path: Vec<usize> = Vec::new();
+
+        for event in events {
+            match event {
+                DfsEvent::Open => {
+                    if path.is_empty() && !children.is_empty() {
+                        return Err(Error::InvalidTree);
+                    }
+                    let node = children.len();
+                    children.push(Vec::new());
+                    if let Some(&parent) = path.last() {
+                        children[parent].push(node);
+                    }
+                    path.push(node);
+                }
+                DfsEvent::Close => {
+                    if path.pop().is_none() { return Err(Error::InvalidTree); }
+                }
+            }
+        }
+
+        if !path.is_empty() { return Err(Error::InvalidTree); }
+        Ok(TreeBuilder { children, root: 0 })
+    }
+
+    /// Reads the tree under `root`.
+    pub fn from_nested<T: Nested + ?Sized>(root: &T) -> Self {
+        let mut children: Vec<Vec<usize>> = Vec::new();
+        // Each entry is a value and its parent’s number.
+        let mut stack: Vec<(&T, Option<usize>)> = vec![(root, None)];
+
+        // Pushing children in reverse pops them in order, so nodes are
+        // numbered in preorder.
+        while let Some((value, parent)) = stack.pop() {
+            let node = children.len();
+            children.push(Vec::new());
+            if let Some(parent) = parent {
+                children[parent].push(node);
+            }
+            stack.extend(value.children().into_iter().rev().map(|child| (child, Some(node))));
+        }
+
+        TreeBuilder { children, root: 0 }
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> u64 {
+        self.children.len() as u64
+    }
+
+    /// Is the tree empty?
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    // The nodes in preorder.
+    fn preorder(&self) -> Vec<usize> {
+        let mut result = Vec::with_capacity(self.children.len());
+        if self.children.is_empty() { return result; }
+
+        let mut stack = vec![self.root];
+        while let Some(node) = stack.pop() {
+            result.push(node);
+            stack.extend(self.children[node].iter().rev());
+        }
+        result
+    }
+
+    // The nodes in breadth-first order.
+    fn level_order(&self) -> Vec<usize> {
+        let mut result = Vec::with_capacity(self.children.len());
+        if self.children.is_empty() { return result; }
+
+        let mut queue = VecDeque::new();
+        queue.push_back(self.root);
+        while let Some(node) = queue.pop_front() {
+            result.push(node);
+            queue.extend(self.children[node].iter().cloned());
+        }
+        result
+    }
+
+    fn invert(&self, order: Vec<usize>) -> Vec<u64> {
+        let mut result = vec![0; self.children.len()];
+        for (rank, node) in order.into_iter().enumerate() {
+            result[node] = rank as u64;
+        }
+        result
+    }
+
+    /// The LOUDS number of each input node.
+    pub fn louds_ids(&self) -> Vec<u64> {
+        self.invert(self.level_order())
+    }
+
+    /// The preorder number of each input node, which is its rank among
+    /// the opens of the parentheses.
+    pub fn parens_ids(&self) -> Vec<u64> {
+        self.invert(self.preorder())
+    }
+
+    /// Encodes the tree as balanced parentheses.
+    pub fn to_balanced_parens(&self) -> BalancedParens {
+        let mut bits = BitVector::with_capacity(2 * self.len());
+        if !self.is_empty() {
+            // Each entry is a node and whether it is being left.
+            let mut stack = vec![(self.root, false)];
+            while let Some((node, leaving)) = stack.pop() {
+                bits.push_bit(!leaving);
+                if !leaving {
+                    stack.push((node, true));
+                    stack.extend(self.children[node].iter().rev().map(|&kid| (kid, false)));
+                }
+            }
+        }
+        BalancedParens::new(bits)
+    }
+
+    /// Encodes the tree as a level-order unary degree sequence.
+    pub fn to_louds(&self) -> Louds {
+        let mut bits = BitVector::with_capacity(2 * self.len() + 1);
+        if !self.is_empty() {
+            bits.push_bit(true);
+            bits.push_bit(false);
+            for node in self.level_order() {
+                for _ in &self.children[node] {
+                    bits.push_bit(true);
+                }
+                bits.push_bit(false);
+            }
+        }
+        Louds::new(bits)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    struct Value(Vec<Value>);
+
+    impl Nested for Value {
+        fn children(&self) -> Vec<&Value> {
+            self.0.iter().collect()
+        }
+    }
+
+    #[test]
+    fn bad_parents() {
+        assert_eq!(Some(Error::InvalidTree),
+                   TreeBuilder::from_parents(&[None, None]).err());
+        assert_eq!(Some(Error::InvalidTree),
+                   TreeBuilder::from_parents(&[None, Some(5)]).err());
+        // 1 and 2 are each other’s parents.
+        assert_eq!(Some(Error::InvalidTree),
+                   TreeBuilder::from_parents(&[None, Some(2), Some(1)]).err());
+        assert!(TreeBuilder::from_parents(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_dfs() {
+        use self::DfsEvent::*;
+        assert!(TreeBuilder::from_dfs(vec![Open, Close, Open, Close]).is_err());
+        assert!(TreeBuilder::from_dfs(vec![Open, Open, Close]).is_err());
+        assert!(TreeBuilder::from_dfs(vec![Close]).is_err());
+    }
+
+    #[test]
+    fn nested() {
+        let value = Value(vec![Value(vec![]), Value(vec![Value(vec![]), Value(vec![])])]);
+        let builder = TreeBuilder::from_nested(&value);
+        assert_eq!(5, builder.len());
+
+        let parens = builder.to_balanced_parens();
+        let second = parens.next_sibling(1).unwrap();
+        assert_eq!(3, parens.subtree_size(second));
+        assert_eq!(vec![0, 1, 2, 3, 4], builder.parens_ids());
+    }
+
+    #[test]
+    fn qc_encodings_agree() {
+        // Node i > 0 hangs off an earlier node.
+        fn prop(links: Vec<usize>) -> bool {
+            let parents: Vec<Option<u64>> = (0 .. links.len() + 1)
+                .map(|i| if i == 0 {None} else {Some((links[i - 1] % i) as u64)})
+                .collect();
+            let builder = TreeBuilder::from_parents(&parents).unwrap();
+            let louds = builder.to_louds();
+            let parens = builder.to_balanced_parens();
+            let louds_ids = builder.louds_ids();
+            let parens_ids = builder.parens_ids();
+
+            // Find each node's open from its preorder number.
+            let mut opens = vec![0; parents.len()];
+            for position in 0 .. 2 * parents.len() as u64 {
+                if parens.is_open(position) {
+                    opens[parens.preorder(position) as usize] = position;
+                }
+            }
+
+            (0 .. parents.len()).all(|node| {
+                let degree = parents.iter().filter(|&&p| p == Some(node as u64)).count() as u64;
+                let open = opens[parens_ids[node] as usize];
+                let louds_parent = louds.parent(louds_ids[node]);
+                let parens_parent = parens.parent(open);
+
+                louds.degree(louds_ids[node]) == degree
+                    && louds_parent == parents[node].map(|p| louds_ids[p as usize])
+                    && parens_parent == parents[node].map(|p| opens[parens_ids[p as usize] as usize])
+            })
+        }
+
+        quickcheck(prop as fn(Vec<usize>) -> bool);
+    }
+}
@@ -0,0 +1,329 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use error::Error;
+use int_vec::{IntVec, IntVecMut, IntVector};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use super::LoudsTrie;
+
+/// The block size of the range-maximum directory.
+const BLOCK: u64 = 64;
+
+/// A [`LoudsTrie`](struct.LoudsTrie.html) whose keys carry weights, for
+/// suggesting the heaviest completions of a prefix.
+///
+/// Taken in sorted order, the keys under any trie node are consecutive,
+/// so the weights are stored in that order in an
+/// [`IntVector`](../int_vec/struct.IntVector.html) with a range-maximum
+/// directory over it. Each node records where its keys start; they end
+/// where its next sibling’s start, or its parent’s end. The top *k*
+/// completions of a prefix come from repeatedly taking the maximum of a
+/// range and splitting the range around it, which costs *O*(*k* log *k*)
+/// range queries however many keys share the prefix.
+///
+/// # Example
+///
+/// ```
+/// use succinct::tree::ScoredTrie;
+///
+/// let trie = ScoredTrie::new(vec![("rust", 90), ("ruby", 70), ("rune", 5),
+///                                 ("python", 80), ("rustc", 95)]);
+///
+/// assert_eq!(vec![(b"rustc".to_vec(), 95), (b"rust".to_vec(), 90)],
+///            trie.top_k_completions(b"ru", 2));
+/// assert_eq!(Some(70), trie.weight(b"ruby"));
+/// assert!(trie.top_k_completions(b"java", 3).is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ScoredTrie {
+    trie: LoudsTrie,
+    // The sorted rank of the first key under each node.
+    starts: IntVector<u64>,
+    // The trie node of each key, in sorted order.
+    nodes: IntVector<u64>,
+    weights: RangeMax,
+}
+
+impl ScoredTrie {
+    /// Creates a trie of the given keys and weights, in any order; a
+    /// repeated key keeps its largest weight.
+    pub fn new<I, K>(entries: I) -> Self
+        where I: IntoIterator<Item = (K, u64)>,
+              K: AsRef<[u8]> {
+
+        let mut entries: Vec<(K, u64)> = entries.into_iter().collect();
+        entries.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()).then(b.1.cmp(&a.1)));
+        entries.dedup_by(|a, b| a.0.as_ref() == b.0.as_ref());
+        Self::from_sorted_iter(entries).expect("ScoredTrie::new: sorted")
+    }
+
+    /// Creates a trie of keys and weights given in strictly increasing
+    /// order of key, or returns `Error::NotIncreasing` if they are out of
+    /// order. The keys are read in one pass without being held.
+    pub fn from_sorted_iter<I, K>(entries: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = (K, u64)>,
+              K: AsRef<[u8]> {
+
+        let mut weights = Vec::new();
+        let trie = LoudsTrie::from_sorted_iter(entries.into_iter().map(|(key, weight)| {
+            weights.push(weight);
+            key
+        }))?;
+        let louds = trie.louds();
+        let len = weights.len() as u64;
+
+        // A preorder walk meets the keys in sorted order.
+        let mut starts = IntVector::with_fill(bits_to_represent(len), trie.node_count(), 0);
+        let mut nodes = IntVector::with_fill(bits_to_represent(trie.node_count()), len, 0);
+        let mut seen = 0;
+        let mut stack = vec![trie.root()];
+        while let Some(node) = stack.pop() {
+            starts.set(node, seen);
+            if trie.is_terminal(node) {
+                nodes.set(seen, node);
+                seen += 1;
+            }
+            if let Some(first) = louds.first_child(node) {
+                stack.extend((first .. first + louds.degree(node)).rev());
+            }
+        }
+
+        let widest = weights.iter().cloned().max().unwrap_or(0);
+        let mut packed = IntVector::with_capacity(bits_to_represent(widest), len);
+        for weight in weights {
+            packed.push(weight);
+        }
+
+        Ok(ScoredTrie {
+            trie,
+            starts,
+            nodes,
+            weights: RangeMax::new(packed),
+        })
+    }
+
+    /// The underlying trie.
+    pub fn trie(&self) -> &LoudsTrie {
+        &self.trie
+    }
+
+    /// The number of keys.
+    pub fn len(&self) -> u64 {
+        self.nodes.len()
+    }
+
+    /// Are there no keys?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The sorted ranks of the keys under `node`.
+    fn range(&self, node: u64) -> (u64, u64) {
+        let mut end_node = node;
+        let end = loop {
+            if let Some(sibling) = self.trie.louds().next_sibling(end_node) {
+                break self.starts.get(sibling);
+            }
+            match self.trie.parent(end_node) {
+                Some(parent) => end_node = parent,
+                None => break self.len(),
+            }
+        };
+        (self.starts.get(node), end)
+    }
+
+    /// The weight of `key`, if it is in the set.
+    pub fn weight(&self, key: &[u8]) -> Option<u64> {
+        let node = self.trie.find(key)?;
+        if !self.trie.is_terminal(node) { return None; }
+        Some(self.weights.values.get(self.starts.get(node)))
+    }
+
+    /// The at most `k` keys starting with `prefix` that weigh most, with
+    /// their weights, heaviest first; equal weights come in key order.
+    pub fn top_k_completions(&self, prefix: &[u8], k: usize) -> Vec<(Vec<u8>, u64)> {
+        let mut result = Vec::with_capacity(k);
+        let (start, end) = match self.trie.find(prefix) {
+            Some(node) => self.range(node),
+            None => return result,
+        };
+
+        // Ranges to search, keyed by their heaviest key.
+        let mut heap = BinaryHeap::new();
+        let offer = |heap: &mut BinaryHeap<_>, start, end| {
+            if start < end {
+                let best = self.weights.argmax(start, end);
+                heap.push((self.weights.values.get(best), Reverse(best), start, end));
+            }
+        };
+
+        offer(&mut heap, start, end);
+        while result.len() < k {
+            let (weight, Reverse(best), start, end) = match heap.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            result.push((self.trie.spell(self.nodes.get(best)), weight));
+            offer(&mut heap, start, best);
+            offer(&mut heap, best + 1, end);
+        }
+        result
+    }
+}
+
+impl SpaceUsage for ScoredTrie {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.trie.heap_bytes() + self.starts.heap_bytes() + self.nodes.heap_bytes()
+            + self.weights.heap_bytes()
+    }
+}
+
+// Values with a sparse table of the maxima of runs of blocks.
+#[derive(Clone, Debug)]
+struct RangeMax {
+    values: IntVector<u64>,
+    // Row r holds, for each block, the position of the maximum of the
+    // 2^r blocks starting there.
+    table: Vec<IntVector<u64>>,
+}
+
+impl RangeMax {
+    fn new(values: IntVector<u64>) -> Self {
+        let blocks = values.len().div_ceil(BLOCK);
+        let bits = bits_to_represent(values.len());
+        let mut table = Vec::new();
+
+        let mut row = IntVector::with_capacity(bits, blocks);
+        for block in 0 .. blocks {
+            let end = (block * BLOCK + BLOCK).min(values.len());
+            row.push(scan(&values, block * BLOCK, end));
+        }
+
+        let mut width = 1;
+        while width < row.len() {
+            let mut next = IntVector::with_capacity(bits, row.len() - width);
+            for block in 0 .. row.len() - width {
+                next.push(better(&values, row.get(block), row.get(block + width)));
+            }
+            table.push(row);
+            row = next;
+            width *= 2;
+        }
+        table.push(row);
+
+        RangeMax { values, table }
+    }
+
+    // The position of the leftmost maximum of `start .. end`, which must
+    // not be empty.
+    fn argmax(&self, start: u64, end: u64) -> u64 {
+        let first = start / BLOCK;
+        let last = (end - 1) / BLOCK;
+        if last <= first + 1 {
+            return scan(&self.values, start, end);
+        }
+
+        let head = scan(&self.values, start, first * BLOCK + BLOCK);
+        let tail = scan(&self.values, last * BLOCK, end);
+
+        // Two overlapping runs of 2^row blocks cover the middle.
+        let count = last - first - 1;
+        let row = 63 - count.leading_zeros() as usize;
+        let middle = better(&self.values,
+                            self.table[row].get(first + 1),
+                            self.table[row].get(last - (1 << row)));
+        better(&self.values, better(&self.values, head, middle), tail)
+    }
+}
+
+impl SpaceUsage for RangeMax {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.values.heap_bytes() + self.table.heap_bytes()
+    }
+}
+
+// Of two positions, the one with the larger value, or the first if they
+// tie.
+fn better(values: &IntVector<u64>, a: u64, b: u64) -> u64 {
+    let (a, b) = (a.min(b), a.max(b));
+    if values.get(b) > values.get(a) {b} else {a}
+}
+
+fn scan(values: &IntVector<u64>, start: u64, end: u64) -> u64 {
+    (start + 1 .. end).fold(start, |best, i| better(values, best, i))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn range_max() {
+        for &len in &[1usize, 64, 65, 300, 1000] {
+            let values: Vec<u64> = (0 .. len as u64).map(|i| (i * 7919) % 1009).collect();
+            let mut vector = IntVector::new(10);
+            for &value in &values {
+                vector.push(value);
+            }
+            let rmq = RangeMax::new(vector);
+
+            for start in (0 .. len).step_by(13) {
+                for end in (start + 1 .. len + 1).step_by(29) {
+                    let expected = (start .. end).max_by_key(|&i| (values[i], Reverse(i)));
+                    assert_eq!(expected.unwrap() as u64, rmq.argmax(start as u64, end as u64));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn repeats_and_ties() {
+        let trie = ScoredTrie::new(vec![("b", 1), ("a", 3), ("b", 4), ("c", 3), ("", 2)]);
+        assert_eq!(4, trie.len());
+        assert_eq!(Some(4), trie.weight(b"b"));
+        assert_eq!(vec![(b"b".to_vec(), 4), (b"a".to_vec(), 3), (b"c".to_vec(), 3),
+                        (b"".to_vec(), 2)],
+                   trie.top_k_completions(b"", 10));
+    }
+
+    #[test]
+    fn sorted_input() {
+        let trie = ScoredTrie::from_sorted_iter(vec![("a", 1), ("b", 2)]).unwrap();
+        assert_eq!(Some(2), trie.weight(b"b"));
+        assert_eq!(Some(Error::NotIncreasing),
+                   ScoredTrie::from_sorted_iter(vec![("b", 1), ("a", 2)]).err());
+    }
+
+    #[test]
+    fn qc_matches_sort() {
+        fn prop(entries: Vec<(Vec<u8>, u16)>, prefix: Vec<u8>, k: u8) -> bool {
+            let entries: Vec<(Vec<u8>, u64)> = entries.into_iter()
+                .map(|(key, weight)| (key.into_iter().map(|byte| byte % 3).collect(),
+                                      weight as u64 % 50))
+                .collect();
+            let prefix: Vec<u8> = prefix.into_iter().take(2).map(|byte| byte % 3).collect();
+            let trie = ScoredTrie::new(entries.clone());
+
+            let mut best: Vec<(Vec<u8>, u64)> = Vec::new();
+            for (key, weight) in entries {
+                if !key.starts_with(&prefix) { continue; }
+                match best.iter_mut().find(|entry| entry.0 == key) {
+                    Some(entry) => entry.1 = entry.1.max(weight),
+                    None => best.push((key, weight)),
+                }
+            }
+            best.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            best.truncate(k as usize);
+
+            trie.top_k_completions(&prefix, k as usize) == best
+        }
+
+        quickcheck(prop as fn(Vec<(Vec<u8>, u16)>, Vec<u8>, u8) -> bool);
+    }
+}
@@ -0,0 +1,166 @@
+use bit_vec::Tombstones;
+use dump::Snapshot;
+use space_usage::SpaceUsage;
+use super::{LoudsTrie, LoudsTrieKeys};
+
+/// A [`LoudsTrie`](struct.LoudsTrie.html) with a deletion overlay, so
+/// keys can be removed without rebuilding it each time.
+///
+/// Deleting a key marks its id in a
+/// [`Tombstones`](../bit_vec/struct.Tombstones.html) bitmap, and every
+/// query skips the marked ids. The trie itself is untouched, so it still
+/// takes its full space and walks over the deleted keys;
+/// [`compact`](#method.compact) rebuilds it from the live keys once
+/// enough have gone.
+///
+/// # Example
+///
+/// ```
+/// use succinct::tree::{DeletableTrie, LoudsTrie};
+///
+/// let mut trie = DeletableTrie::new(LoudsTrie::new(vec!["tea", "ten", "to", "inn"]));
+/// assert!(trie.delete(b"ten"));
+/// assert!(!trie.delete(b"ten"));
+///
+/// assert_eq!(3, trie.len());
+/// assert!(!trie.contains(b"ten"));
+/// assert_eq!(vec![b"tea".to_vec()], trie.keys_with_prefix(b"te"));
+///
+/// let compacted = trie.compact();
+/// assert_eq!(3, compacted.len());
+/// assert_eq!(Some(2), compacted.lookup(b"tea"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeletableTrie {
+    trie: LoudsTrie,
+    deleted: Tombstones,
+}
+
+impl DeletableTrie {
+    /// Lays an empty overlay over `trie`.
+    pub fn new(trie: LoudsTrie) -> Self {
+        let deleted = Tombstones::new(trie.len());
+        DeletableTrie { trie, deleted }
+    }
+
+    /// The trie underneath, deleted keys and all.
+    pub fn trie(&self) -> &LoudsTrie {
+        &self.trie
+    }
+
+    /// The deletion overlay, indexed by the trie’s key ids.
+    pub fn tombstones(&self) -> &Tombstones {
+        &self.deleted
+    }
+
+    /// The number of live keys.
+    pub fn len(&self) -> u64 {
+        self.deleted.live_count()
+    }
+
+    /// Are all the keys gone?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Deletes `key`, returning whether it was there.
+    pub fn delete(&mut self, key: &[u8]) -> bool {
+        match self.trie.lookup(key) {
+            Some(id) => self.deleted.delete(id),
+            None => false,
+        }
+    }
+
+    /// The trie’s id for `key`, if it is live.
+    pub fn lookup(&self, key: &[u8]) -> Option<u64> {
+        self.trie.lookup(key).filter(|&id| !self.deleted.is_deleted(id))
+    }
+
+    /// Is `key` live?
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.lookup(key).is_some()
+    }
+
+    /// The live keys that start with `prefix`, in increasing order.
+    pub fn keys_with_prefix(&self, prefix: &[u8]) -> Vec<Vec<u8>> {
+        self.trie.keys_with_prefix(prefix).into_iter()
+            .filter(|key| self.contains(key))
+            .collect()
+    }
+
+    /// The live keys in increasing order.
+    pub fn iter(&self) -> DeletableTrieKeys<'_> {
+        DeletableTrieKeys { data: self, keys: self.trie.iter() }
+    }
+
+    /// Rebuilds the trie from the live keys.
+    pub fn compact(&self) -> LoudsTrie {
+        LoudsTrie::from_sorted_iter(self.iter())
+            .expect("DeletableTrie::compact: out of order")
+    }
+}
+
+/// Lists the live keys in increasing order.
+impl Snapshot for DeletableTrie {
+    type Item = Vec<u8>;
+    type Iter<'a> = DeletableTrieKeys<'a> where Self: 'a;
+
+    fn dump_iter(&self) -> DeletableTrieKeys<'_> {
+        self.iter()
+    }
+}
+
+impl SpaceUsage for DeletableTrie {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.trie.heap_bytes() + self.deleted.heap_bytes()
+    }
+}
+
+/// The live keys of a [`DeletableTrie`](struct.DeletableTrie.html) in
+/// increasing order.
+#[derive(Clone, Debug)]
+pub struct DeletableTrieKeys<'a> {
+    data: &'a DeletableTrie,
+    keys: LoudsTrieKeys<'a>,
+}
+
+impl<'a> Iterator for DeletableTrieKeys<'a> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let data = self.data;
+        self.keys.find(|key| data.contains(key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn qc_matches_rebuilt() {
+        fn prop(keys: Vec<Vec<u8>>, deletes: Vec<usize>) -> bool {
+            let keys: Vec<Vec<u8>> = keys.into_iter()
+                .map(|key| key.into_iter().take(4).map(|b| b % 3).collect())
+                .collect();
+            let mut trie = DeletableTrie::new(LoudsTrie::new(keys.iter()));
+            let mut live: Vec<Vec<u8>> = trie.trie().iter().collect();
+            for &i in &deletes {
+                if keys.is_empty() { break; }
+                let key = &keys[i % keys.len()];
+                trie.delete(key);
+                live.retain(|live| live != key);
+            }
+
+            trie.iter().collect::<Vec<_>>() == live
+                && trie.len() == live.len() as u64
+                && trie.compact().iter().collect::<Vec<_>>() == live
+                && keys.iter().all(|key| trie.contains(key) == live.contains(key))
+        }
+
+        quickcheck(prop as fn(Vec<Vec<u8>>, Vec<usize>) -> bool);
+    }
+}
@@ -0,0 +1,577 @@
+use std::char;
+
+use num_traits::ToPrimitive;
+
+use bit_vec::{BitVec, BitVecMut, BitVecPush, BitVector};
+use error::Error;
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9};
+use space_usage::SpaceUsage;
+use super::BalancedParens;
+
+/// How deeply arrays and objects may nest.
+const MAX_DEPTH: usize = 1024;
+
+/// A JSON document indexed for navigation without building a tree of
+/// values.
+///
+/// Every value in the document, containers and scalars alike, is a node
+/// of a [`BalancedParens`](struct.BalancedParens.html) tree, in document
+/// order. Beside it, one bitmap over the text marks where each value
+/// starts and ends, so the *i*th parenthesis is at the *i*th mark; a
+/// second marks which values have keys, and a third where those keys
+/// start. Values are read straight from the text when asked for.
+///
+/// # Example
+///
+/// ```
+/// use succinct::tree::{JsonIndex, JsonKind};
+///
+/// let text = r#"{"user": {"name": "ada", "tags": ["x", "y\nz"]}, "n": 3}"#;
+/// let index = JsonIndex::new(text.to_owned()).unwrap();
+///
+/// let tags = index.pointer("/user/tags").unwrap();
+/// assert_eq!(JsonKind::Array, tags.kind());
+/// assert_eq!(2, tags.len());
+/// assert_eq!(Some("y\nz".to_owned()), tags.at(1).unwrap().as_str());
+///
+/// let n = index.root().get("n").unwrap();
+/// assert_eq!("3", n.raw());
+/// assert_eq!(Some("n".to_owned()), n.key());
+/// ```
+#[derive(Clone, Debug)]
+pub struct JsonIndex {
+    text: String,
+    tree: BalancedParens,
+    // Bit p is set where a value starts, and just past where it ends.
+    marks: Select9<BitVector<u64>>,
+    // Bit i is set if the ith value in preorder is an object member.
+    keyed: Rank9<BitVector<u64>>,
+    // Marks the opening quote of each key.
+    keys: Select9<BitVector<u64>>,
+}
+
+/// The type of a JSON value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JsonKind {
+    /// `null`.
+    Null,
+    /// `true` or `false`.
+    Bool,
+    /// A number.
+    Number,
+    /// A string.
+    String,
+    /// An array.
+    Array,
+    /// An object.
+    Object,
+}
+
+impl JsonIndex {
+    /// Parses and indexes a document.
+    ///
+    /// Returns `Error::InvalidJson` with the offending byte offset if
+    /// the text isn’t a single JSON value, or nests deeper than 1024
+    /// levels.
+    pub fn new(text: String) -> Result<Self, Error> {
+        let (parens, marks, keyed, keys) = {
+            let mut parser = Parser {
+                bytes: text.as_bytes(),
+                position: 0,
+                parens: BitVector::new(),
+                marks: Vec::new(),
+                keyed: BitVector::new(),
+                keys: Vec::new(),
+            };
+            parser.value(None, 0)?;
+            parser.skip_space();
+            if parser.position != text.len() { return parser.error(); }
+            (parser.parens, parser.marks, parser.keyed, parser.keys)
+        };
+
+        let len = text.len() as u64 + 1;
+        Ok(JsonIndex {
+            tree: BalancedParens::new(parens),
+            marks: Select9::new(Rank9::new(bitmap(len, &marks))),
+            keyed: Rank9::new(keyed),
+            keys: Select9::new(Rank9::new(bitmap(len, &keys))),
+            text,
+        })
+    }
+
+    /// The document.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The shape of the document, a node per value.
+    pub fn tree(&self) -> &BalancedParens {
+        &self.tree
+    }
+
+    /// The number of values, at every level.
+    pub fn len(&self) -> u64 {
+        self.tree.len()
+    }
+
+    /// Is the document empty? Never, since it holds at least one value.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// The outermost value.
+    pub fn root(&self) -> JsonValue<'_> {
+        self.value(0)
+    }
+
+    /// The value at tree node `node`, the position of its open.
+    pub fn value(&self, node: u64) -> JsonValue<'_> {
+        assert!(self.tree.is_open(node), "JsonIndex::value: not a node");
+        JsonValue { index: self, node }
+    }
+
+    /// Follows a JSON Pointer (RFC 6901), such as `/users/0/name`, from
+    /// the root.
+    pub fn pointer(&self, pointer: &str) -> Option<JsonValue<'_>> {
+        if pointer.is_empty() { return Some(self.root()); }
+        if !pointer.starts_with('/') { return None; }
+
+        let mut value = self.root();
+        for token in pointer[1..].split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            value = match value.kind() {
+                JsonKind::Object => value.get(&token)?,
+                JsonKind::Array => value.at(token.parse().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+}
+
+impl SpaceUsage for JsonIndex {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.text.capacity() + self.tree.heap_bytes() + self.marks.heap_bytes()
+            + self.keyed.heap_bytes() + self.keys.heap_bytes()
+    }
+}
+
+/// A value in a [`JsonIndex`](struct.JsonIndex.html).
+#[derive(Clone, Copy, Debug)]
+pub struct JsonValue<'a> {
+    index: &'a JsonIndex,
+    node: u64,
+}
+
+impl<'a> JsonValue<'a> {
+    /// The tree node, the position of the value’s open.
+    pub fn node(&self) -> u64 {
+        self.node
+    }
+
+    /// The value’s text, without surrounding space.
+    pub fn raw(&self) -> &'a str {
+        let marks = &self.index.marks;
+        let start = marks.select1(self.node).expect("JsonValue: bad marks");
+        let end = marks.select1(self.index.tree.find_close(self.node))
+                       .expect("JsonValue: bad marks");
+        &self.index.text[start as usize .. end as usize]
+    }
+
+    /// The type of the value.
+    pub fn kind(&self) -> JsonKind {
+        match self.raw().as_bytes()[0] {
+            b'n' => JsonKind::Null,
+            b't' | b'f' => JsonKind::Bool,
+            b'"' => JsonKind::String,
+            b'[' => JsonKind::Array,
+            b'{' => JsonKind::Object,
+            _ => JsonKind::Number,
+        }
+    }
+
+    /// The key naming the value within its object, if it is in one.
+    pub fn key(&self) -> Option<String> {
+        let preorder = self.index.tree.preorder(self.node);
+        if !self.index.keyed.get_bit(preorder) { return None; }
+
+        let quote = self.index.keys.select1(self.index.keyed.rank1(preorder) - 1)
+                                   .expect("JsonValue: bad keys") as usize;
+        let end = string_end(self.index.text.as_bytes(), quote);
+        Some(decode(&self.index.text[quote + 1 .. end - 1]))
+    }
+
+    /// The contents of a string, with escapes decoded.
+    pub fn as_str(&self) -> Option<String> {
+        let raw = self.raw();
+        if self.kind() == JsonKind::String {Some(decode(&raw[1 .. raw.len() - 1]))} else {None}
+    }
+
+    /// The value of a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.raw() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The value of a number, to the nearest `f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        if self.kind() == JsonKind::Number {self.raw().parse().ok()} else {None}
+    }
+
+    /// Is the value `null`?
+    pub fn is_null(&self) -> bool {
+        self.kind() == JsonKind::Null
+    }
+
+    /// The array or object containing the value, unless it is the root.
+    pub fn parent(&self) -> Option<JsonValue<'a>> {
+        self.index.tree.parent(self.node).map(|node| self.index.value(node))
+    }
+
+    /// The elements of an array or members of an object, in order; none
+    /// for a scalar.
+    pub fn children(&self) -> JsonChildren<'a> {
+        JsonChildren {
+            index: self.index,
+            next: self.index.tree.first_child(self.node),
+        }
+    }
+
+    /// The number of children.
+    pub fn len(&self) -> u64 {
+        self.children().count() as u64
+    }
+
+    /// Does the value have no children?
+    pub fn is_empty(&self) -> bool {
+        self.index.tree.is_leaf(self.node)
+    }
+
+    /// The member of an object with key `key`, the first if there are
+    /// several.
+    pub fn get(&self, key: &str) -> Option<JsonValue<'a>> {
+        if self.kind() != JsonKind::Object { return None; }
+        self.children().find(|child| child.key().is_some_and(|found| found == key))
+    }
+
+    /// The `index`th element of an array.
+    pub fn at(&self, index: u64) -> Option<JsonValue<'a>> {
+        if self.kind() != JsonKind::Array { return None; }
+        self.children().nth(index.to_usize()?)
+    }
+}
+
+/// The children of a [`JsonValue`](struct.JsonValue.html).
+#[derive(Clone, Debug)]
+pub struct JsonChildren<'a> {
+    index: &'a JsonIndex,
+    next: Option<u64>,
+}
+
+impl<'a> Iterator for JsonChildren<'a> {
+    type Item = JsonValue<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = self.index.tree.next_sibling(node);
+        Some(self.index.value(node))
+    }
+}
+
+fn bitmap(len: u64, positions: &[usize]) -> BitVector<u64> {
+    let mut bits = BitVector::with_fill(len, false);
+    for &position in positions {
+        bits.set_bit(position as u64, true);
+    }
+    bits
+}
+
+// Just past the closing quote of the valid string at `quote`.
+fn string_end(bytes: &[u8], quote: usize) -> usize {
+    let mut position = quote + 1;
+    loop {
+        match bytes[position] {
+            b'"' => return position + 1,
+            b'\\' => position += 2,
+            _ => position += 1,
+        }
+    }
+}
+
+// Decodes the inside of a valid string.
+fn decode(raw: &str) -> String {
+    fn flush(units: &mut Vec<u16>, result: &mut String) {
+        result.extend(char::decode_utf16(units.drain(..))
+                          .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+    }
+
+    let mut result = String::with_capacity(raw.len());
+    // Consecutive `\u` escapes, which may pair into one character.
+    let mut units = Vec::new();
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            flush(&mut units, &mut result);
+            result.push(c);
+            continue;
+        }
+
+        let escaped = chars.next().expect("decode: validated");
+        if escaped == 'u' {
+            let hex: String = chars.by_ref().take(4).collect();
+            units.push(u16::from_str_radix(&hex, 16).expect("decode: validated"));
+            continue;
+        }
+
+        flush(&mut units, &mut result);
+        result.push(match escaped {
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            other => other,
+        });
+    }
+
+    flush(&mut units, &mut result);
+    result
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    parens: BitVector<u64>,
+    marks: Vec<usize>,
+    keyed: BitVector<u64>,
+    keys: Vec<usize>,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self) -> Result<T, Error> {
+        Err(Error::InvalidJson(self.position))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).cloned()
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), Error> {
+        if self.peek() != Some(byte) { return self.error(); }
+        self.position += 1;
+        Ok(())
+    }
+
+    // Parses a value, after any space, recording the key that named it.
+    fn value(&mut self, key: Option<usize>, depth: usize) -> Result<(), Error> {
+        self.skip_space();
+        self.parens.push_bit(true);
+        self.marks.push(self.position);
+        self.keyed.push_bit(key.is_some());
+        self.keys.extend(key);
+
+        match self.peek() {
+            Some(b'{') => self.container(b'}', true, depth)?,
+            Some(b'[') => self.container(b']', false, depth)?,
+            Some(b'"') => self.string()?,
+            Some(b't') => self.literal(b"true")?,
+            Some(b'f') => self.literal(b"false")?,
+            Some(b'n') => self.literal(b"null")?,
+            Some(b'-') | Some(b'0' ..= b'9') => self.number()?,
+            _ => return self.error(),
+        }
+
+        self.parens.push_bit(false);
+        self.marks.push(self.position);
+        Ok(())
+    }
+
+    fn container(&mut self, close: u8, keyed: bool, depth: usize) -> Result<(), Error> {
+        if depth == MAX_DEPTH { return self.error(); }
+        self.position += 1;
+        self.skip_space();
+        if self.peek() == Some(close) {
+            self.position += 1;
+            return Ok(());
+        }
+
+        loop {
+            let key = if keyed {
+                self.skip_space();
+                let quote = self.position;
+                if self.peek() != Some(b'"') { return self.error(); }
+                self.string()?;
+                self.skip_space();
+                self.expect(b':')?;
+                Some(quote)
+            } else {
+                None
+            };
+
+            self.value(key, depth + 1)?;
+            self.skip_space();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(byte) if byte == close => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                _ => return self.error(),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<(), Error> {
+        self.position += 1;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    match self.peek() {
+                        Some(b'"') | Some(b'\\') | Some(b'/') | Some(b'b') | Some(b'f')
+                            | Some(b'n') | Some(b'r') | Some(b't') => self.position += 1,
+                        Some(b'u') => {
+                            self.position += 1;
+                            for _ in 0 .. 4 {
+                                if !self.peek().is_some_and(|byte| byte.is_ascii_hexdigit()) {
+                                    return self.error();
+                                }
+                                self.position += 1;
+                            }
+                        }
+                        _ => return self.error(),
+                    }
+                }
+                Some(byte) if byte >= 0x20 => self.position += 1,
+                _ => return self.error(),
+            }
+        }
+    }
+
+    fn literal(&mut self, word: &[u8]) -> Result<(), Error> {
+        if !self.bytes[self.position ..].starts_with(word) { return self.error(); }
+        self.position += word.len();
+        Ok(())
+    }
+
+    // Skips digits, returning how many.
+    fn digits(&mut self) -> usize {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn number(&mut self) -> Result<(), Error> {
+        if self.peek() == Some(b'-') { self.position += 1; }
+        match self.peek() {
+            Some(b'0') => self.position += 1,
+            Some(b'1' ..= b'9') => { self.digits(); }
+            _ => return self.error(),
+        }
+
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            if self.digits() == 0 { return self.error(); }
+        }
+
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.position += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() { self.position += 1; }
+            if self.digits() == 0 { return self.error(); }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn index(text: &str) -> JsonIndex {
+        JsonIndex::new(text.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn navigation() {
+        let doc = index(r#" [1, {"a": null, "b": [true, -2.5e3]}, "", {}] "#);
+        assert_eq!(9, doc.len());
+
+        let root = doc.root();
+        assert_eq!(JsonKind::Array, root.kind());
+        assert_eq!(4, root.len());
+        assert_eq!(None, root.key());
+
+        let object = root.at(1).unwrap();
+        assert_eq!(r#"{"a": null, "b": [true, -2.5e3]}"#, object.raw());
+        assert!(object.get("a").unwrap().is_null());
+        assert!(object.get("c").is_none());
+        assert!(object.at(0).is_none());
+        assert_eq!(vec![Some("a".to_owned()), Some("b".to_owned())],
+                   object.children().map(|child| child.key()).collect::<Vec<_>>());
+
+        let b = doc.pointer("/1/b/1").unwrap();
+        assert_eq!(Some(-2500.0), b.as_f64());
+        assert_eq!(Some(true), doc.pointer("/1/b/0").unwrap().as_bool());
+        assert_eq!(object.node(), b.parent().unwrap().parent().unwrap().node());
+        assert_eq!(Some(String::new()), root.at(2).unwrap().as_str());
+        assert!(root.at(3).unwrap().is_empty());
+        assert!(doc.pointer("/4").is_none());
+        assert!(doc.pointer("/0/x").is_none());
+        assert_eq!(root.node(), doc.pointer("").unwrap().node());
+    }
+
+    #[test]
+    fn escapes() {
+        let doc = index(r#"{"a/b~c": "\"\\\u00e9\ud83d\ude00\ud800x", "\t": 0}"#);
+        let value = doc.pointer("/a~1b~0c").unwrap();
+        assert_eq!(Some("\"\\é😀\u{fffd}x".to_owned()), value.as_str());
+        assert_eq!(Some("\t".to_owned()), doc.pointer("/\t").unwrap().key());
+    }
+
+    #[test]
+    fn scalar_root() {
+        let doc = index("  42\n");
+        assert_eq!(1, doc.len());
+        assert_eq!("42", doc.root().raw());
+        assert_eq!(0, doc.root().len());
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [("", 0), ("[1,]", 3), ("{\"a\" 1}", 5), ("[1 2]", 3),
+                     ("01", 1), ("1.", 2), ("\"\\x\"", 2), ("tru", 0),
+                     ("[] []", 3), ("{1: 2}", 1), ("\"a\nb\"", 2)];
+        for &(text, offset) in &cases {
+            assert_eq!(Some(Error::InvalidJson(offset)),
+                       JsonIndex::new(text.to_owned()).err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn too_deep() {
+        let text = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+        assert_eq!(Some(Error::InvalidJson(MAX_DEPTH)), JsonIndex::new(text).err());
+        let text = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert_eq!(MAX_DEPTH as u64, index(&text).len());
+    }
+}
@@ -0,0 +1,175 @@
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9};
+use space_usage::SpaceUsage;
+
+/// An ordinal tree in level-order unary degree sequence (LOUDS) form.
+///
+/// The nodes are numbered breadth first, from the root at 0. After a
+/// leading `10` for a notional parent of the root, each node in turn
+/// writes a 1 per child and then a 0, so a tree of *n* nodes takes
+/// 2*n* + 1 bits. Node *j* is the target of the *j*th 1, and its
+/// children follow the *j*+1st 0, so a child takes one select over the
+/// zeros and a parent one over the ones.
+///
+/// # Example
+///
+/// ```
+/// use succinct::tree::TreeBuilder;
+///
+/// // A root with children 1 and 2; 1 has child 3.
+/// let builder = TreeBuilder::from_parents(&[None, Some(0), Some(0), Some(1)]).unwrap();
+/// let tree = builder.to_louds();
+///
+/// assert_eq!(2, tree.degree(0));
+/// assert_eq!(Some(2), tree.child(0, 1));
+/// assert_eq!(Some(3), tree.first_child(1));
+/// assert_eq!(Some(1), tree.parent(3));
+/// assert!(tree.is_leaf(2));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Louds {
+    ones: Select9<BitVector<u64>>,
+    // The complement, for selecting zeros.
+    zeros: Select9<BitVector<u64>>,
+}
+
+impl Louds {
+    /// Wraps a LOUDS bit sequence, including the leading `10`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence doesn’t describe a tree.
+    pub fn new(bits: BitVector<u64>) -> Self {
+        let len = bits.bit_len();
+        let mut complement = BitVector::with_fill(len, false);
+        for i in 0 .. len {
+            complement.set_bit(i, !bits.get_bit(i));
+        }
+
+        let ones = Select9::new(Rank9::new(bits));
+        let zeros = Select9::new(Rank9::new(complement));
+        let one_count = if len == 0 {0} else {ones.rank1(len - 1)};
+        assert!(len == 2 * one_count + 1 || (len == 0),
+                "Louds::new: not a tree");
+        assert!(len == 0 || (ones.get_bit(0) && !ones.get_bit(1)),
+                "Louds::new: missing the leading 10");
+
+        Louds { ones, zeros }
+    }
+
+    /// The bits, with 1 for each child.
+    pub fn bits(&self) -> &Select9<BitVector<u64>> {
+        &self.ones
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> u64 {
+        self.ones.bit_len() / 2
+    }
+
+    /// Is the tree empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The root, unless the tree is empty.
+    pub fn root(&self) -> Option<u64> {
+        if self.is_empty() {None} else {Some(0)}
+    }
+
+    // The range of positions of the ones for the children of `node`.
+    fn children_bits(&self, node: u64) -> (u64, u64) {
+        assert!(node < self.len(), "Louds: node out of bounds");
+        let start = self.zeros.select1(node).expect("Louds: bad bits") + 1;
+        let end = self.zeros.select1(node + 1).expect("Louds: bad bits");
+        (start, end)
+    }
+
+    /// The number of children of `node`.
+    pub fn degree(&self, node: u64) -> u64 {
+        let (start, end) = self.children_bits(node);
+        end - start
+    }
+
+    /// Does `node` have no children?
+    pub fn is_leaf(&self, node: u64) -> bool {
+        self.degree(node) == 0
+    }
+
+    /// The `index`th child of `node`, if it has that many.
+    pub fn child(&self, node: u64, index: u64) -> Option<u64> {
+        let (start, end) = self.children_bits(node);
+        // There are `node + 1` zeros before `start`.
+        if index < end - start {Some(start + index - node - 1)} else {None}
+    }
+
+    /// The first child of `node`, unless it is a leaf.
+    pub fn first_child(&self, node: u64) -> Option<u64> {
+        self.child(node, 0)
+    }
+
+    /// The next sibling of `node`, unless it is the last.
+    pub fn next_sibling(&self, node: u64) -> Option<u64> {
+        if node == 0 { return None; }
+        let position = self.ones.select1(node).expect("Louds: node out of bounds");
+        if self.ones.get_bit(position + 1) {Some(node + 1)} else {None}
+    }
+
+    /// The parent of `node`, unless it is the root.
+    pub fn parent(&self, node: u64) -> Option<u64> {
+        if node == 0 { return None; }
+        let position = self.ones.select1(node).expect("Louds: node out of bounds");
+        // The zeros before the node’s 1 end its parent and those before.
+        Some(position - node - 1)
+    }
+}
+
+impl SpaceUsage for Louds {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.ones.heap_bytes() + self.zeros.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::BitVecPush;
+
+    fn parse(text: &str) -> Louds {
+        let mut bits = BitVector::new();
+        for c in text.chars() {
+            bits.push_bit(c == '1');
+        }
+        Louds::new(bits)
+    }
+
+    #[test]
+    fn navigation() {
+        // 0 has children 1, 2, 3; 2 has 4 and 5.
+        let tree = parse(concat!("10", "1110", "0", "110", "0", "0", "0"));
+        assert_eq!(6, tree.len());
+        assert_eq!(3, tree.degree(0));
+        assert_eq!(Some(4), tree.first_child(2));
+        assert_eq!(Some(5), tree.child(2, 1));
+        assert_eq!(None, tree.child(2, 2));
+        assert_eq!(Some(2), tree.parent(5));
+        assert_eq!(Some(0), tree.parent(3));
+        assert_eq!(Some(5), tree.next_sibling(4));
+        assert_eq!(None, tree.next_sibling(3));
+        assert!(tree.is_leaf(1));
+    }
+
+    #[test]
+    fn empty() {
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_a_tree() {
+        parse("101");
+    }
+}
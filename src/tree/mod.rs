@@ -0,0 +1,28 @@
+//! Succinct trees.
+
+mod builder;
+pub use self::builder::*;
+
+mod completion;
+pub use self::completion::*;
+
+mod deletable;
+pub use self::deletable::*;
+
+mod json;
+pub use self::json::*;
+
+mod louds;
+pub use self::louds::*;
+
+mod nested_trie;
+pub use self::nested_trie::*;
+
+mod parens;
+pub use self::parens::*;
+
+mod rmm;
+pub use self::rmm::*;
+
+mod trie;
+pub use self::trie::*;
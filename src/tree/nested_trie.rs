@@ -0,0 +1,439 @@
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use error::Error;
+use int_vec::{IntVec, IntVecMut, IntVector};
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9};
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use wavelet::WaveletMatrix;
+use super::trie::{child_by_label, PointerTrie};
+use super::{Louds, TreeBuilder};
+
+/// The number of tries in a chain unless asked otherwise.
+const DEFAULT_LEVELS: usize = 3;
+
+/// An immutable set of byte strings as a path-compressed trie whose
+/// long labels are kept in another such trie, after the design of
+/// marisa-trie.
+///
+/// Chains of nodes with one child and no key fold into a single edge,
+/// so each edge has a label of one or more bytes. As in
+/// [`LoudsTrie`](struct.LoudsTrie.html), the shape is a LOUDS tree and
+/// the first byte of each label sits in a wavelet matrix, for child
+/// lookup by rank and select. The rest of each longer label, its tail,
+/// is reversed and made a key of the next trie in the chain, so tails
+/// ending alike share their nodes there; the edge records that key’s
+/// id. The last trie stores its tails flat. Keys are numbered breadth
+/// first, as in `LoudsTrie`.
+///
+/// # Example
+///
+/// ```
+/// use succinct::tree::NestedTrie;
+///
+/// let trie = NestedTrie::new(vec!["application", "apply", "applied",
+///                                 "banana", "bandana"]);
+/// assert_eq!(5, trie.len());
+/// assert!(trie.contains(b"applied"));
+/// assert!(!trie.contains(b"appl"));
+///
+/// let id = trie.lookup(b"bandana").unwrap();
+/// assert_eq!(b"bandana".to_vec(), trie.key(id));
+///
+/// assert_eq!(vec![b"banana".to_vec(), b"bandana".to_vec()],
+///            trie.keys_with_prefix(b"ban"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct NestedTrie {
+    louds: Louds,
+    // The first byte of the label into node i + 1.
+    labels: WaveletMatrix,
+    // Marks the nodes whose labels have tails.
+    linked: Rank9<BitVector<u64>>,
+    tails: Tails,
+    terminals: Select9<BitVector<u64>>,
+}
+
+#[derive(Clone, Debug)]
+enum Tails {
+    // The tails back to back, with each one’s start and a final end.
+    Flat { bytes: Vec<u8>, offsets: IntVector<u64> },
+    // Each tail’s id in the next trie, which holds them reversed.
+    Nested { trie: Box<NestedTrie>, ids: IntVector<u64> },
+}
+
+impl NestedTrie {
+    /// Creates a trie of the given keys, in any order, with repeats
+    /// ignored, nesting tails three tries deep.
+    pub fn new<I>(keys: I) -> Self
+        where I: IntoIterator,
+              I::Item: AsRef<[u8]> {
+
+        Self::with_levels(keys, DEFAULT_LEVELS)
+    }
+
+    /// Creates a trie of the given keys in a chain of at most `levels`
+    /// tries; with 1, tails are stored flat.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` is 0.
+    pub fn with_levels<I>(keys: I, levels: usize) -> Self
+        where I: IntoIterator,
+              I::Item: AsRef<[u8]> {
+
+        let mut keys: Vec<I::Item> = keys.into_iter().collect();
+        keys.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        keys.dedup_by(|a, b| a.as_ref() == b.as_ref());
+        Self::from_sorted_iter(keys, levels).expect("NestedTrie::with_levels: sorted")
+    }
+
+    /// Creates a trie of keys given in strictly increasing order, in a
+    /// chain of at most `levels` tries, or returns
+    /// `Error::NotIncreasing` if they are out of order. The keys are
+    /// read in one pass without being held.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` is 0.
+    pub fn from_sorted_iter<I>(keys: I, levels: usize) -> Result<Self, Error>
+        where I: IntoIterator,
+              I::Item: AsRef<[u8]> {
+
+        assert!(levels > 0, "NestedTrie: no levels");
+
+        // First a trie of single bytes.
+        let PointerTrie { labels: bytes, children, terminal, .. } =
+            PointerTrie::from_sorted_iter(keys)?;
+
+        // Then fold each chain into its top node, keeping the children
+        // and mark of its bottom one.
+        let mut parents = vec![None];
+        let mut labels = vec![Vec::new()];
+        let mut terminals = vec![terminal[0]];
+        let mut stack: Vec<(usize, usize)> = children[0].iter().rev().map(|&kid| (kid, 0)).collect();
+        while let Some((mut node, parent)) = stack.pop() {
+            let mut label = vec![bytes[node]];
+            while !terminal[node] && children[node].len() == 1 {
+                node = children[node][0];
+                label.push(bytes[node]);
+            }
+
+            let folded = parents.len();
+            parents.push(Some(parent as u64));
+            labels.push(label);
+            terminals.push(terminal[node]);
+            stack.extend(children[node].iter().rev().map(|&kid| (kid, folded)));
+        }
+
+        // Siblings were numbered in label order, as `from_parents` wants.
+        let builder = TreeBuilder::from_parents(&parents).expect("NestedTrie: not a tree");
+        let mut order = vec![0; parents.len()];
+        for (node, &id) in builder.louds_ids().iter().enumerate() {
+            order[id as usize] = node;
+        }
+
+        let len = parents.len() as u64;
+        let mut first_bytes = Vec::with_capacity(order.len() - 1);
+        let mut linked = BitVector::with_fill(len, false);
+        let mut marks = BitVector::with_fill(len, false);
+        let mut tails = Vec::new();
+        for (id, &node) in order.iter().enumerate() {
+            let label = &labels[node];
+            if id > 0 { first_bytes.push(label[0] as u64); }
+            if label.len() > 1 {
+                linked.set_bit(id as u64, true);
+                tails.push(&label[1..]);
+            }
+            marks.set_bit(id as u64, terminals[node]);
+        }
+
+        Ok(NestedTrie {
+            louds: builder.to_louds(),
+            labels: WaveletMatrix::with_levels(8, first_bytes),
+            linked: Rank9::new(linked),
+            tails: Tails::new(&tails, levels),
+            terminals: Select9::new(Rank9::new(marks)),
+        })
+    }
+
+    /// The number of keys.
+    pub fn len(&self) -> u64 {
+        let nodes = self.terminals.bit_len();
+        self.terminals.rank1(nodes - 1)
+    }
+
+    /// Are there no keys?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of nodes in this trie, including the root but not the
+    /// nodes of the tries below.
+    pub fn node_count(&self) -> u64 {
+        self.louds.len()
+    }
+
+    /// The number of tries in the chain, this one included.
+    pub fn levels(&self) -> usize {
+        match self.tails {
+            Tails::Flat { .. } => 1,
+            Tails::Nested { ref trie, .. } => 1 + trie.levels(),
+        }
+    }
+
+    // The whole label into `node`.
+    fn label(&self, node: u64) -> Vec<u8> {
+        let mut result = vec![self.labels.get(node - 1) as u8];
+        if self.linked.get_bit(node) {
+            let index = self.linked.rank1(node) - 1;
+            match self.tails {
+                Tails::Flat { ref bytes, ref offsets } => {
+                    let start = offsets.get(index) as usize;
+                    let end = offsets.get(index + 1) as usize;
+                    result.extend_from_slice(&bytes[start .. end]);
+                }
+                Tails::Nested { ref trie, ref ids } => {
+                    result.extend(trie.key(ids.get(index)).into_iter().rev());
+                }
+            }
+        }
+        result
+    }
+
+    // Follows `key` from the root as far as whole labels match, giving
+    // the node reached and the number of bytes used.
+    fn descend(&self, key: &[u8]) -> (u64, usize) {
+        let mut node = 0;
+        let mut used = 0;
+        while used < key.len() {
+            let child = match child_by_label(&self.louds, &self.labels, node, key[used]) {
+                Some(child) => child,
+                None => break,
+            };
+            let label = self.label(child);
+            if !key[used ..].starts_with(&label) { break; }
+            node = child;
+            used += label.len();
+        }
+        (node, used)
+    }
+
+    /// The id of `key`, if it is in the set.
+    pub fn lookup(&self, key: &[u8]) -> Option<u64> {
+        let (node, used) = self.descend(key);
+        if used == key.len() && self.terminals.get_bit(node) {
+            Some(self.terminals.rank1(node) - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Is `key` in the set?
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.lookup(key).is_some()
+    }
+
+    /// The key with id `id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id >= self.len()`.
+    pub fn key(&self, id: u64) -> Vec<u8> {
+        let mut node = self.terminals.select1(id).expect("NestedTrie::key: id out of bounds");
+        let mut result = Vec::new();
+        while let Some(parent) = self.louds.parent(node) {
+            result.extend(self.label(node).into_iter().rev());
+            node = parent;
+        }
+        result.reverse();
+        result
+    }
+
+    /// The ids of the keys that are prefixes of `query`, shortest first.
+    pub fn prefixes_of(&self, query: &[u8]) -> Vec<u64> {
+        let mut result = Vec::new();
+        let mut node = 0;
+        let mut used = 0;
+        loop {
+            if self.terminals.get_bit(node) {
+                result.push(self.terminals.rank1(node) - 1);
+            }
+            if used == query.len() { return result; }
+
+            node = match child_by_label(&self.louds, &self.labels, node, query[used]) {
+                Some(child) => child,
+                None => return result,
+            };
+            let label = self.label(node);
+            if !query[used ..].starts_with(&label) { return result; }
+            used += label.len();
+        }
+    }
+
+    /// The id and length of the longest key that is a prefix of
+    /// `query`, if any is.
+    pub fn longest_prefix_of(&self, query: &[u8]) -> Option<(u64, usize)> {
+        // Keys on the path are met shortest first.
+        self.prefixes_of(query).pop().map(|id| {
+            let len = self.key(id).len();
+            (id, len)
+        })
+    }
+
+    /// The keys that start with `prefix`, in increasing order.
+    pub fn keys_with_prefix(&self, prefix: &[u8]) -> Vec<Vec<u8>> {
+        let mut result = Vec::new();
+
+        // The prefix may end within a label.
+        let (mut node, used) = self.descend(prefix);
+        let mut key = prefix[.. used].to_vec();
+        if used < prefix.len() {
+            node = match child_by_label(&self.louds, &self.labels, node, prefix[used]) {
+                Some(child) => child,
+                None => return result,
+            };
+            let label = self.label(node);
+            if !label.starts_with(&prefix[used ..]) { return result; }
+            key.extend(label);
+        }
+
+        // Each entry is a node and the length of the key its label
+        // extends.
+        let mut stack = vec![(node, None)];
+        while let Some((node, depth)) = stack.pop() {
+            if let Some(depth) = depth {
+                key.truncate(depth);
+                key.extend(self.label(node));
+            }
+            if self.terminals.get_bit(node) {
+                result.push(key.clone());
+            }
+            if let Some(first) = self.louds.first_child(node) {
+                let kids = first .. first + self.louds.degree(node);
+                stack.extend(kids.rev().map(|kid| (kid, Some(key.len()))));
+            }
+        }
+        result
+    }
+}
+
+impl Tails {
+    fn new(tails: &[&[u8]], levels: usize) -> Self {
+        if levels == 1 || tails.is_empty() {
+            let mut bytes = Vec::new();
+            let total = tails.iter().map(|tail| tail.len()).sum::<usize>() as u64;
+            let mut offsets = IntVector::with_capacity(bits_to_represent(total),
+                                                       tails.len() as u64 + 1);
+            offsets.push(0);
+            for tail in tails {
+                bytes.extend_from_slice(tail);
+                offsets.push(bytes.len() as u64);
+            }
+            return Tails::Flat { bytes, offsets };
+        }
+
+        let reversed: Vec<Vec<u8>> = tails.iter()
+            .map(|tail| tail.iter().rev().cloned().collect())
+            .collect();
+        let trie = NestedTrie::with_levels(&reversed, levels - 1);
+
+        let mut ids = IntVector::with_fill(bits_to_represent(trie.len()), reversed.len() as u64, 0);
+        for (i, tail) in reversed.iter().enumerate() {
+            ids.set(i as u64, trie.lookup(tail).expect("NestedTrie: lost a tail"));
+        }
+        Tails::Nested { trie: Box::new(trie), ids }
+    }
+}
+
+impl SpaceUsage for NestedTrie {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        let tails = match self.tails {
+            Tails::Flat { ref bytes, ref offsets } =>
+                bytes.capacity() + offsets.heap_bytes(),
+            Tails::Nested { ref trie, ref ids } =>
+                trie.total_bytes() + ids.heap_bytes(),
+        };
+        self.louds.heap_bytes() + self.labels.heap_bytes() + self.linked.heap_bytes()
+            + tails + self.terminals.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn folding() {
+        let trie = NestedTrie::with_levels(vec!["abcdef", "abcxyz", "q"], 1);
+        // The root, "abc", "def", "xyz" and "q".
+        assert_eq!(5, trie.node_count());
+        assert_eq!(1, trie.levels());
+        assert!(trie.contains(b"abcxyz"));
+        assert!(!trie.contains(b"abc"));
+        assert!(!trie.contains(b"abcxy"));
+        assert_eq!(vec![b"abcdef".to_vec(), b"abcxyz".to_vec()], trie.keys_with_prefix(b"ab"));
+        assert!(trie.keys_with_prefix(b"abd").is_empty());
+    }
+
+    #[test]
+    fn sorted_input() {
+        let trie = NestedTrie::from_sorted_iter(vec!["ab", "abc", "b"], 2).unwrap();
+        assert!(trie.contains(b"abc"));
+        assert_eq!(Some(Error::NotIncreasing),
+                   NestedTrie::from_sorted_iter(vec!["b", "ab"], 2).err());
+    }
+
+    #[test]
+    fn prefixes() {
+        let trie = NestedTrie::new(vec!["", "a", "ab", "abcd", "b"]);
+        let lengths: Vec<usize> = trie.prefixes_of(b"abcde").into_iter()
+            .map(|id| trie.key(id).len())
+            .collect();
+        assert_eq!(vec![0, 1, 2, 4], lengths);
+    }
+
+    #[test]
+    fn shared_tails() {
+        let keys: Vec<String> = (0 .. 100).map(|i| format!("{}-suffix-in-common", i)).collect();
+        let trie = NestedTrie::new(&keys);
+        assert_eq!(3, trie.levels());
+        for key in &keys {
+            let id = trie.lookup(key.as_bytes()).unwrap();
+            assert_eq!(key.as_bytes(), &trie.key(id)[..]);
+        }
+    }
+
+    #[test]
+    fn qc_matches_btree() {
+        fn prop(keys: Vec<Vec<u8>>, probes: Vec<Vec<u8>>, levels: u8) -> bool {
+            let set: BTreeSet<Vec<u8>> = keys.into_iter()
+                .map(|key| key.into_iter().map(|byte| byte % 4).collect())
+                .collect();
+            let trie = NestedTrie::with_levels(&set, levels as usize % 4 + 1);
+
+            trie.len() == set.len() as u64
+                && (0 .. trie.len()).all(|id| set.contains(&trie.key(id)))
+                && probes.into_iter().all(|probe| {
+                    let probe: Vec<u8> = probe.into_iter().map(|byte| byte % 4).collect();
+                    let expected: Vec<Vec<u8>> = set.iter()
+                        .filter(|key| key.starts_with(&probe))
+                        .cloned()
+                        .collect();
+                    let prefixes = set.iter().filter(|key| probe.starts_with(key)).count();
+                    trie.contains(&probe) == set.contains(&probe)
+                        && trie.keys_with_prefix(&probe) == expected
+                        && trie.prefixes_of(&probe).len() == prefixes
+                        && trie.longest_prefix_of(&probe).map(|(_, len)| len)
+                               == set.iter().filter(|key| probe.starts_with(key))
+                                     .map(|key| key.len()).max()
+                })
+        }
+
+        quickcheck(prop as fn(Vec<Vec<u8>>, Vec<Vec<u8>>, u8) -> bool);
+    }
+}
@@ -0,0 +1,179 @@
+use bit_vec::{BitVec, BitVector};
+use rank::BitRankSupport;
+use space_usage::SpaceUsage;
+use super::RangeMinMaxTree;
+
+/// An ordinal tree as a balanced parentheses sequence.
+///
+/// A depth-first walk writes an open, a 1, on entering each node and a
+/// close, a 0, on leaving it, so a tree of *n* nodes takes 2*n* bits.
+/// A node is named by the position of its open. Navigation reduces to
+/// searches of the excess, the number of opens less closes, which a
+/// [`RangeMinMaxTree`](struct.RangeMinMaxTree.html) answers.
+///
+/// # Example
+///
+/// ```
+/// use succinct::tree::TreeBuilder;
+///
+/// // A root with children 1 and 2; 1 has child 3.
+/// let builder = TreeBuilder::from_parents(&[None, Some(0), Some(0), Some(1)]).unwrap();
+/// let tree = builder.to_balanced_parens();
+///
+/// let root = tree.root().unwrap();
+/// let first = tree.first_child(root).unwrap();
+/// let second = tree.next_sibling(first).unwrap();
+/// assert_eq!(2, tree.subtree_size(first));
+/// assert_eq!(Some(root), tree.parent(second));
+/// assert_eq!(2, tree.depth(tree.first_child(first).unwrap()));
+/// assert_eq!(3, tree.preorder(second));
+/// ```
+#[derive(Clone, Debug)]
+pub struct BalancedParens {
+    excess: RangeMinMaxTree,
+}
+
+impl BalancedParens {
+    /// Wraps a parentheses sequence, with 1 for an open.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence isn’t balanced, or holds more than one
+    /// tree.
+    pub fn new(bits: BitVector<u64>) -> Self {
+        let excess = RangeMinMaxTree::new(bits);
+        let len = excess.len();
+        assert!(excess.excess(len) == 0 && (len == 0 || excess.min_excess(1, len) == 0)
+                    && (len < 2 || excess.min_excess(1, len - 1) > 0),
+                "BalancedParens::new: not a single balanced tree");
+        BalancedParens { excess }
+    }
+
+    /// The excess search structure over the parentheses.
+    pub fn excess(&self) -> &RangeMinMaxTree {
+        &self.excess
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> u64 {
+        self.excess.len() / 2
+    }
+
+    /// Is the tree empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The root, unless the tree is empty.
+    pub fn root(&self) -> Option<u64> {
+        if self.is_empty() {None} else {Some(0)}
+    }
+
+    /// Is `position` an open?
+    pub fn is_open(&self, position: u64) -> bool {
+        self.excess.bits().get_bit(position)
+    }
+
+    /// The close matching the open at `node`.
+    pub fn find_close(&self, node: u64) -> u64 {
+        self.excess.fwd_search(node, 0).expect("BalancedParens::find_close: unmatched") - 1
+    }
+
+    /// The open matching the close at `position`.
+    pub fn find_open(&self, position: u64) -> u64 {
+        self.excess.bwd_search(position + 1, 0).expect("BalancedParens::find_open: unmatched")
+    }
+
+    /// The parent of `node`, unless it is the root.
+    pub fn parent(&self, node: u64) -> Option<u64> {
+        self.excess.bwd_search(node, -1)
+    }
+
+    /// The first child of `node`, unless it is a leaf.
+    pub fn first_child(&self, node: u64) -> Option<u64> {
+        if self.is_open(node + 1) {Some(node + 1)} else {None}
+    }
+
+    /// The next sibling of `node`, unless it is the last.
+    pub fn next_sibling(&self, node: u64) -> Option<u64> {
+        let next = self.find_close(node) + 1;
+        if next < self.excess.len() && self.is_open(next) {Some(next)} else {None}
+    }
+
+    /// Does `node` have no children?
+    pub fn is_leaf(&self, node: u64) -> bool {
+        !self.is_open(node + 1)
+    }
+
+    /// The number of nodes in the subtree of `node`, itself included.
+    pub fn subtree_size(&self, node: u64) -> u64 {
+        (self.find_close(node) - node).div_ceil(2)
+    }
+
+    /// The number of ancestors of `node`.
+    pub fn depth(&self, node: u64) -> u64 {
+        self.excess.excess(node) as u64
+    }
+
+    /// The number of nodes before `node` in preorder.
+    pub fn preorder(&self, node: u64) -> u64 {
+        if node == 0 {0} else {self.excess.bits().rank1(node - 1)}
+    }
+}
+
+impl SpaceUsage for BalancedParens {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.excess.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::BitVecPush;
+
+    fn parse(text: &str) -> BalancedParens {
+        let mut bits = BitVector::new();
+        for c in text.chars() {
+            bits.push_bit(c == '(');
+        }
+        BalancedParens::new(bits)
+    }
+
+    #[test]
+    fn navigation() {
+        //          0123456789
+        let tree = parse("(()(()()))");
+        assert_eq!(5, tree.len());
+        assert_eq!(9, tree.find_close(0));
+        assert_eq!(3, tree.find_open(8));
+        assert_eq!(Some(3), tree.next_sibling(1));
+        assert_eq!(None, tree.next_sibling(3));
+        assert_eq!(Some(3), tree.parent(6));
+        assert_eq!(None, tree.parent(0));
+        assert_eq!(3, tree.subtree_size(3));
+        assert!(tree.is_leaf(4));
+        assert_eq!(4, tree.preorder(6));
+    }
+
+    #[test]
+    fn empty() {
+        let tree = parse("");
+        assert!(tree.is_empty());
+        assert_eq!(None, tree.root());
+    }
+
+    #[test]
+    #[should_panic]
+    fn forest() {
+        parse("()()");
+    }
+
+    #[test]
+    #[should_panic]
+    fn unbalanced() {
+        parse("(()");
+    }
+}
@@ -0,0 +1,489 @@
+use bit_vec::{BitVec, BitVector};
+use rank::{BitRankSupport, Rank9};
+use space_usage::SpaceUsage;
+
+// The number of bits per leaf.
+const LEAF_BITS: u64 = 1024;
+
+// The change in excess over each byte, and the least and greatest
+// excess reached over its prefixes, including the empty one. Bits are
+// read from least significant up, as a `BitVector` stores them.
+struct ByteTable {
+    total: [i8; 256],
+    min: [i8; 256],
+    max: [i8; 256],
+}
+
+const fn byte_table() -> ByteTable {
+    let mut table = ByteTable { total: [0; 256], min: [0; 256], max: [0; 256] };
+    let mut byte = 0;
+    while byte < 256 {
+        let (mut excess, mut min, mut max) = (0i8, 0i8, 0i8);
+        let mut bit = 0;
+        while bit < 8 {
+            excess += if byte >> bit & 1 == 1 {1} else {-1};
+            if excess < min { min = excess; }
+            if excess > max { max = excess; }
+            bit += 1;
+        }
+        table.total[byte] = excess;
+        table.min[byte] = min;
+        table.max[byte] = max;
+        byte += 1;
+    }
+    table
+}
+
+static BYTES: ByteTable = byte_table();
+
+// The change in excess over a range, and the least and greatest excess
+// reached, relative to its start, over its prefixes including the empty
+// one and the whole.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    total: i64,
+    min: i64,
+    max: i64,
+}
+
+impl Node {
+    const EMPTY: Node = Node { total: 0, min: 0, max: 0 };
+
+    fn then(self, other: Node) -> Node {
+        Node {
+            total: self.total + other.total,
+            min: self.min.min(self.total + other.min),
+            max: self.max.max(self.total + other.max),
+        }
+    }
+
+    fn reaches(&self, start: i64, target: i64) -> bool {
+        start + self.min <= target && target <= start + self.max
+    }
+}
+
+/// A range min-max tree over an excess sequence, for searching it by
+/// value.
+///
+/// Each bit is a step, up for a 1 and down for a 0, and `excess(j)` is
+/// the height after the first `j` steps, from `excess(0) = 0` to
+/// `excess(len())`. The tree records the change in height over each
+/// 1024-bit block and over each node above, with the least and greatest
+/// heights reached, so `fwd_search` and `bwd_search` go up and down it
+/// to the block holding their answer, and scan that a byte at a time.
+/// Balanced parentheses, where an open is a 1, reduce their navigation
+/// to these searches; other encodings of trees can use them directly.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{BitVecPush, BitVector};
+/// use succinct::tree::RangeMinMaxTree;
+///
+/// // (()(()))
+/// let mut bits = BitVector::new();
+/// for &bit in &[true, true, false, true, true, false, false, false] {
+///     bits.push_bit(bit);
+/// }
+/// let tree = RangeMinMaxTree::new(bits);
+///
+/// assert_eq!(2, tree.excess(4));
+/// // The close matching the open at 0 is at 7: the first return below it.
+/// assert_eq!(Some(8), tree.fwd_search(1, -1));
+/// assert_eq!(Some(0), tree.bwd_search(8, 0));
+/// assert_eq!(1, tree.rmq(1, 7));
+/// assert_eq!(5, tree.rmaxq(1, 7));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RangeMinMaxTree {
+    bits: Rank9<BitVector<u64>>,
+    // A complete binary tree in heap order, with node 1 the root and the
+    // leaves, one per block and then empty ones, from `leaves` on.
+    nodes: Vec<Node>,
+    leaves: usize,
+}
+
+impl RangeMinMaxTree {
+    /// Builds the tree over the steps in `bits`.
+    pub fn new(bits: BitVector<u64>) -> Self {
+        let len = bits.bit_len();
+        let blocks = len.div_ceil(LEAF_BITS) as usize;
+        let leaves = blocks.max(1).next_power_of_two();
+        let mut nodes = vec![Node::EMPTY; 2 * leaves];
+
+        let bits = Rank9::new(bits);
+        for block in 0 .. blocks {
+            let start = block as u64 * LEAF_BITS;
+            let end = (start + LEAF_BITS).min(len);
+            nodes[leaves + block] = scan_node(&bits, start, end);
+        }
+        for node in (1 .. leaves).rev() {
+            nodes[node] = nodes[2 * node].then(nodes[2 * node + 1]);
+        }
+
+        RangeMinMaxTree { bits, nodes, leaves }
+    }
+
+    /// The steps.
+    pub fn bits(&self) -> &Rank9<BitVector<u64>> {
+        &self.bits
+    }
+
+    /// The number of steps.
+    pub fn len(&self) -> u64 {
+        self.bits.bit_len()
+    }
+
+    /// Are there no steps?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The height after the first `position` steps.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.len()`.
+    pub fn excess(&self, position: u64) -> i64 {
+        assert!(position <= self.len(), "RangeMinMaxTree::excess: out of bounds");
+        let ones = if position == 0 {0} else {self.bits.rank1(position - 1)};
+        2 * ones as i64 - position as i64
+    }
+
+    fn step(&self, position: u64) -> i64 {
+        if self.bits.get_bit(position) {1} else {-1}
+    }
+
+    // Scans forward from `position`, at height `excess`, up to `end`, for
+    // the first position after it at height `target`.
+    fn scan_forward(&self, mut position: u64, mut excess: i64, end: u64, target: i64)
+                    -> Result<u64, i64> {
+        while position < end {
+            if position % 8 == 0 && position + 8 <= end {
+                let byte = self.byte(position);
+                if !reaches(byte, excess, target) {
+                    excess += BYTES.total[byte] as i64;
+                    position += 8;
+                    continue;
+                }
+            }
+
+            excess += self.step(position);
+            position += 1;
+            if excess == target { return Ok(position); }
+        }
+        Err(excess)
+    }
+
+    // Scans back from `position`, at height `excess`, down to `start`,
+    // for the last position before it at height `target`.
+    fn scan_backward(&self, mut position: u64, mut excess: i64, start: u64, target: i64)
+                     -> Result<u64, i64> {
+        while position > start {
+            if position % 8 == 0 && position - 8 >= start {
+                let byte = self.byte(position - 8);
+                let before = excess - BYTES.total[byte] as i64;
+                if !reaches(byte, before, target) {
+                    excess = before;
+                    position -= 8;
+                    continue;
+                }
+            }
+
+            position -= 1;
+            excess -= self.step(position);
+            if excess == target { return Ok(position); }
+        }
+        Err(excess)
+    }
+
+    fn byte(&self, position: u64) -> usize {
+        (self.bits.get_block((position / 64) as usize) >> (position % 64) & 0xFF) as usize
+    }
+
+    fn block_end(&self, block: usize) -> u64 {
+        ((block as u64 + 1) * LEAF_BITS).min(self.len())
+    }
+
+    /// The first position `j > position` with
+    /// `excess(j) == excess(position) + delta`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.len()`.
+    pub fn fwd_search(&self, position: u64, delta: i64) -> Option<u64> {
+        let target = self.excess(position) + delta;
+        if position == self.len() { return None; }
+
+        let block = (position / LEAF_BITS) as usize;
+        let mut excess = match self.scan_forward(position, self.excess(position),
+                                                 self.block_end(block), target) {
+            Ok(found) => return Some(found),
+            Err(excess) => excess,
+        };
+
+        // Climb until a right sibling reaches the target.
+        let mut node = self.leaves + block;
+        loop {
+            if node == 1 { return None; }
+            if node % 2 == 0 {
+                let sibling = self.nodes[node + 1];
+                if sibling.reaches(excess, target) {
+                    node += 1;
+                    break;
+                }
+                excess += sibling.total;
+            }
+            node /= 2;
+        }
+
+        // Descend to the leftmost leaf that does.
+        while node < self.leaves {
+            let left = self.nodes[2 * node];
+            if left.reaches(excess, target) {
+                node *= 2;
+            } else {
+                excess += left.total;
+                node = 2 * node + 1;
+            }
+        }
+
+        let block = node - self.leaves;
+        let start = block as u64 * LEAF_BITS;
+        self.scan_forward(start, excess, self.block_end(block), target).ok()
+    }
+
+    /// The last position `j < position` with
+    /// `excess(j) == excess(position) + delta`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.len()`.
+    pub fn bwd_search(&self, position: u64, delta: i64) -> Option<u64> {
+        let target = self.excess(position) + delta;
+        if position == 0 { return None; }
+
+        // The block holding the step just before `position`.
+        let block = ((position - 1) / LEAF_BITS) as usize;
+        let start = block as u64 * LEAF_BITS;
+        let mut excess = match self.scan_backward(position, self.excess(position),
+                                                  start, target) {
+            Ok(found) => return Some(found),
+            Err(excess) => excess,
+        };
+
+        // Climb until a left sibling reaches the target from its start.
+        let mut node = self.leaves + block;
+        loop {
+            if node == 1 { return None; }
+            if node % 2 != 0 {
+                let sibling = self.nodes[node - 1];
+                if sibling.reaches(excess - sibling.total, target) {
+                    node -= 1;
+                    break;
+                }
+                excess -= sibling.total;
+            }
+            node /= 2;
+        }
+
+        // Descend to the rightmost leaf that does.
+        while node < self.leaves {
+            let right = self.nodes[2 * node + 1];
+            if right.reaches(excess - right.total, target) {
+                node = 2 * node + 1;
+            } else {
+                excess -= right.total;
+                node *= 2;
+            }
+        }
+
+        let block = node - self.leaves;
+        let start = block as u64 * LEAF_BITS;
+        self.scan_backward(self.block_end(block), excess, start, target).ok()
+    }
+
+    // The summary of the steps `start .. end`.
+    fn summarize(&self, start: u64, end: u64) -> Node {
+        let first = (start / LEAF_BITS) as usize;
+        let last = (end / LEAF_BITS) as usize;
+        if first == last {
+            return scan_node(&self.bits, start, end);
+        }
+
+        let first_end = (first as u64 + 1) * LEAF_BITS;
+        let last_start = last as u64 * LEAF_BITS;
+        scan_node(&self.bits, start, first_end)
+            .then(self.summarize_blocks(1, 0, self.leaves, first + 1, last))
+            .then(scan_node(&self.bits, last_start, end))
+    }
+
+    // The summary of blocks `start .. end` under `node`, which covers
+    // blocks `low .. high`.
+    fn summarize_blocks(&self, node: usize, low: usize, high: usize,
+                        start: usize, end: usize) -> Node {
+        if end <= low || high <= start { return Node::EMPTY; }
+        if start <= low && high <= end { return self.nodes[node]; }
+
+        let mid = (low + high) / 2;
+        self.summarize_blocks(2 * node, low, mid, start, end)
+            .then(self.summarize_blocks(2 * node + 1, mid, high, start, end))
+    }
+
+    /// The least of `excess(start) ..= excess(end)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > end` or `end > self.len()`.
+    pub fn min_excess(&self, start: u64, end: u64) -> i64 {
+        assert!(start <= end && end <= self.len(), "RangeMinMaxTree: bad range");
+        self.excess(start) + self.summarize(start, end).min
+    }
+
+    /// The greatest of `excess(start) ..= excess(end)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > end` or `end > self.len()`.
+    pub fn max_excess(&self, start: u64, end: u64) -> i64 {
+        assert!(start <= end && end <= self.len(), "RangeMinMaxTree: bad range");
+        self.excess(start) + self.summarize(start, end).max
+    }
+
+    /// The leftmost position in `start ..= end` of least excess.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > end` or `end > self.len()`.
+    pub fn rmq(&self, start: u64, end: u64) -> u64 {
+        let min = self.min_excess(start, end);
+        self.first_at(start, min)
+    }
+
+    /// The leftmost position in `start ..= end` of greatest excess.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > end` or `end > self.len()`.
+    pub fn rmaxq(&self, start: u64, end: u64) -> u64 {
+        let max = self.max_excess(start, end);
+        self.first_at(start, max)
+    }
+
+    // The first position from `start` at height `target`, which the
+    // steps reach one at a time.
+    fn first_at(&self, start: u64, target: i64) -> u64 {
+        let here = self.excess(start);
+        if here == target { return start; }
+        self.fwd_search(start, target - here)
+            .expect("RangeMinMaxTree: extremum not reached")
+    }
+}
+
+fn reaches(byte: usize, start: i64, target: i64) -> bool {
+    start + BYTES.min[byte] as i64 <= target && target <= start + BYTES.max[byte] as i64
+}
+
+// Summarizes the steps `start .. end`, one at a time.
+fn scan_node<B: BitVec<Block = u64>>(bits: &B, start: u64, end: u64) -> Node {
+    let mut node = Node::EMPTY;
+    for position in start .. end {
+        node.total += if bits.get_bit(position) {1} else {-1};
+        node.min = node.min.min(node.total);
+        node.max = node.max.max(node.total);
+    }
+    node
+}
+
+impl SpaceUsage for RangeMinMaxTree {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.bits.heap_bytes() + self.nodes.capacity() * ::std::mem::size_of::<Node>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bit_vec::BitVecPush;
+    use quickcheck::quickcheck;
+
+    fn bits_of<I: IntoIterator<Item = bool>>(bits: I) -> BitVector<u64> {
+        let mut result = BitVector::new();
+        for bit in bits {
+            result.push_bit(bit);
+        }
+        result
+    }
+
+    // The excess after each prefix.
+    fn naive_excesses(bits: &[bool]) -> Vec<i64> {
+        let mut result = vec![0];
+        for &bit in bits {
+            let last = *result.last().unwrap();
+            result.push(last + if bit {1} else {-1});
+        }
+        result
+    }
+
+    #[test]
+    fn empty() {
+        let tree = RangeMinMaxTree::new(BitVector::new());
+        assert_eq!(0, tree.excess(0));
+        assert_eq!(None, tree.fwd_search(0, 0));
+        assert_eq!(None, tree.bwd_search(0, 0));
+        assert_eq!(0, tree.rmq(0, 0));
+    }
+
+    #[test]
+    fn long_walk() {
+        // Up 3000 steps, then down 3000.
+        let tree = RangeMinMaxTree::new(bits_of((0 .. 6000).map(|i| i < 3000)));
+
+        assert_eq!(Some(5999), tree.fwd_search(1, 0));
+        assert_eq!(Some(100), tree.fwd_search(10, 90));
+        assert_eq!(Some(3100), tree.fwd_search(2950, -50));
+        assert_eq!(Some(1), tree.bwd_search(5999, 0));
+        assert_eq!(None, tree.fwd_search(0, -1));
+        assert_eq!(3000, tree.rmaxq(0, 6000));
+        assert_eq!(6000, tree.rmq(2000, 6000));
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(bits: Vec<bool>, position: usize, delta: i8, other: usize) -> bool {
+            // Stretch the input so that searches cross blocks.
+            let bits: Vec<bool> = bits.iter().cycle().take(bits.len() * 40).cloned().collect();
+            let tree = RangeMinMaxTree::new(bits_of(bits.iter().cloned()));
+            let len = bits.len();
+            let position = position % (len + 1);
+            let other = other % (len + 1);
+            let delta = delta as i64 % 8;
+
+            let excess = naive_excesses(&bits);
+            let target = excess[position] + delta;
+            let forward = (position + 1 .. len + 1)
+                .find(|&j| excess[j] == target)
+                .map(|j| j as u64);
+            let backward = (0 .. position).rev()
+                .find(|&j| excess[j] == target)
+                .map(|j| j as u64);
+
+            let (start, end) = (position.min(other), position.max(other));
+            let values = &excess[start .. end + 1];
+            let min = *values.iter().min().unwrap();
+            let max = *values.iter().max().unwrap();
+            let rmq = start + values.iter().position(|&v| v == min).unwrap();
+            let rmaxq = start + values.iter().position(|&v| v == max).unwrap();
+
+            tree.excess(position as u64) == excess[position]
+                && tree.fwd_search(position as u64, delta) == forward
+                && tree.bwd_search(position as u64, delta) == backward
+                && tree.rmq(start as u64, end as u64) == rmq as u64
+                && tree.rmaxq(start as u64, end as u64) == rmaxq as u64
+        }
+
+        quickcheck(prop as fn(Vec<bool>, usize, i8, usize) -> bool);
+    }
+}
@@ -0,0 +1,431 @@
+use bit_vec::{BitVec, BitVecMut, BitVector};
+use dump::Snapshot;
+use error::Error;
+use int_vec::IntVec;
+use internal::merge::{Intersection, Union};
+use rank::{BitRankSupport, Rank9};
+use select::{Select1Support, Select9, SelectSupport};
+use space_usage::SpaceUsage;
+use wavelet::WaveletMatrix;
+use super::{Louds, TreeBuilder};
+
+/// An immutable set of byte strings as a trie in LOUDS form.
+///
+/// The trie’s shape is a [`Louds`](struct.Louds.html) tree, so nodes
+/// are numbered breadth first. Every node but the root is entered by an
+/// edge with a byte label, and the labels are kept in node order in a
+/// [`WaveletMatrix`](../wavelet/struct.WaveletMatrix.html). A node’s
+/// children are consecutive, so the child with a given label is found
+/// by two ranks and a select over the labels, in time proportional to
+/// the byte width rather than the branching. A bitmap marks the nodes
+/// that end keys; a key’s rank among those is its id, and ids run in
+/// breadth-first, so shortest-first, order.
+///
+/// # Example
+///
+/// ```
+/// use succinct::tree::LoudsTrie;
+///
+/// let trie = LoudsTrie::new(vec!["tea", "ten", "to", "inn", "in", "tea"]);
+/// assert_eq!(5, trie.len());
+/// assert!(trie.contains(b"ten"));
+/// assert!(!trie.contains(b"te"));
+///
+/// let id = trie.lookup(b"inn").unwrap();
+/// assert_eq!(b"inn".to_vec(), trie.key(id));
+///
+/// assert_eq!(vec![b"tea".to_vec(), b"ten".to_vec()], trie.keys_with_prefix(b"te"));
+/// assert_eq!(Some(3), trie.longest_prefix_of(b"inner").map(|(_, len)| len));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LoudsTrie {
+    louds: Louds,
+    // The label of node i + 1.
+    labels: WaveletMatrix,
+    terminals: Select9<BitVector<u64>>,
+}
+
+impl LoudsTrie {
+    /// Creates a trie of the given keys, in any order, with repeats
+    /// ignored.
+    pub fn new<I>(keys: I) -> Self
+        where I: IntoIterator,
+              I::Item: AsRef<[u8]> {
+
+        let mut keys: Vec<I::Item> = keys.into_iter().collect();
+        keys.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        keys.dedup_by(|a, b| a.as_ref() == b.as_ref());
+        Self::from_sorted_iter(keys).expect("LoudsTrie::new: sorted")
+    }
+
+    /// Creates a trie of keys given in strictly increasing order, in one
+    /// pass without holding them, or returns `Error::NotIncreasing` if
+    /// they are out of order.
+    pub fn from_sorted_iter<I>(keys: I) -> Result<Self, Error>
+        where I: IntoIterator,
+              I::Item: AsRef<[u8]> {
+
+        let pointers = PointerTrie::from_sorted_iter(keys)?;
+        let builder = TreeBuilder::from_parents(&pointers.parents)
+            .expect("LoudsTrie: not a tree");
+        let ids = builder.louds_ids();
+
+        let nodes = pointers.parents.len();
+        let mut labels = vec![0; nodes - 1];
+        let mut terminals = BitVector::with_fill(nodes as u64, false);
+        for (node, &id) in ids.iter().enumerate() {
+            if id > 0 { labels[id as usize - 1] = pointers.labels[node] as u64; }
+            terminals.set_bit(id, pointers.terminal[node]);
+        }
+
+        Ok(LoudsTrie {
+            louds: builder.to_louds(),
+            labels: WaveletMatrix::with_levels(8, labels),
+            terminals: Select9::new(Rank9::new(terminals)),
+        })
+    }
+
+    /// The number of keys.
+    pub fn len(&self) -> u64 {
+        let nodes = self.terminals.bit_len();
+        self.terminals.rank1(nodes - 1)
+    }
+
+    /// Are there no keys?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The shape of the trie.
+    pub fn louds(&self) -> &Louds {
+        &self.louds
+    }
+
+    /// The number of nodes, including the root.
+    pub fn node_count(&self) -> u64 {
+        self.louds.len()
+    }
+
+    /// The root, which stands for the empty string.
+    pub fn root(&self) -> u64 {
+        0
+    }
+
+    /// The label of the edge into `node`, unless it is the root.
+    pub fn label(&self, node: u64) -> Option<u8> {
+        if node == 0 {None} else {Some(self.labels.get(node - 1) as u8)}
+    }
+
+    /// The parent of `node`, unless it is the root.
+    pub fn parent(&self, node: u64) -> Option<u64> {
+        self.louds.parent(node)
+    }
+
+    /// The child of `node` by the edge labeled `label`, if there is one.
+    pub fn child(&self, node: u64, label: u8) -> Option<u64> {
+        child_by_label(&self.louds, &self.labels, node, label)
+    }
+
+    /// Does `node` end a key?
+    pub fn is_terminal(&self, node: u64) -> bool {
+        self.terminals.get_bit(node)
+    }
+
+    /// The id of the key that `node` ends, if it ends one.
+    pub fn key_id(&self, node: u64) -> Option<u64> {
+        if self.is_terminal(node) {Some(self.terminals.rank1(node) - 1)} else {None}
+    }
+
+    /// The node spelled by `prefix`, if any key starts with it.
+    pub fn find(&self, prefix: &[u8]) -> Option<u64> {
+        prefix.iter().try_fold(self.root(), |node, &byte| self.child(node, byte))
+    }
+
+    /// The id of `key`, if it is in the set.
+    pub fn lookup(&self, key: &[u8]) -> Option<u64> {
+        self.find(key).and_then(|node| self.key_id(node))
+    }
+
+    /// Is `key` in the set?
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.lookup(key).is_some()
+    }
+
+    /// The id and length of the longest key that is a prefix of
+    /// `query`, if any is.
+    pub fn longest_prefix_of(&self, query: &[u8]) -> Option<(u64, usize)> {
+        let mut node = self.root();
+        let mut best = self.key_id(node).map(|id| (id, 0));
+        for (i, &byte) in query.iter().enumerate() {
+            node = match self.child(node, byte) {
+                Some(child) => child,
+                None => break,
+            };
+            if let Some(id) = self.key_id(node) {
+                best = Some((id, i + 1));
+            }
+        }
+        best
+    }
+
+    /// The string that `node` spells.
+    pub fn spell(&self, mut node: u64) -> Vec<u8> {
+        let mut result = Vec::new();
+        while let Some(label) = self.label(node) {
+            result.push(label);
+            node = self.parent(node).expect("LoudsTrie: labeled root");
+        }
+        result.reverse();
+        result
+    }
+
+    /// The key with id `id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id >= self.len()`.
+    pub fn key(&self, id: u64) -> Vec<u8> {
+        let node = self.terminals.select1(id).expect("LoudsTrie::key: id out of bounds");
+        self.spell(node)
+    }
+
+    /// The keys that start with `prefix`, in increasing order.
+    pub fn keys_with_prefix(&self, prefix: &[u8]) -> Vec<Vec<u8>> {
+        match self.find(prefix) {
+            Some(node) => self.keys_below(node, prefix.to_vec()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The keys in increasing order, spelled one at a time.
+    pub fn iter(&self) -> LoudsTrieKeys<'_> {
+        self.keys_below(self.root(), Vec::new())
+    }
+
+    fn keys_below(&self, node: u64, key: Vec<u8>) -> LoudsTrieKeys<'_> {
+        LoudsTrieKeys { trie: self, stack: vec![(node, None)], key }
+    }
+
+    /// The keys in either trie.
+    ///
+    /// Both are walked in order once, so neither is decoded whole.
+    pub fn union(&self, other: &LoudsTrie) -> LoudsTrie {
+        LoudsTrie::from_sorted_iter(Union::new(self.iter(), other.iter()))
+            .expect("LoudsTrie::union: out of order")
+    }
+
+    /// The keys in both tries.
+    pub fn intersection(&self, other: &LoudsTrie) -> LoudsTrie {
+        LoudsTrie::from_sorted_iter(Intersection::new(self.iter(), other.iter()))
+            .expect("LoudsTrie::intersection: out of order")
+    }
+}
+
+/// The keys of a [`LoudsTrie`](struct.LoudsTrie.html) in increasing
+/// order.
+#[derive(Clone, Debug)]
+pub struct LoudsTrieKeys<'a> {
+    trie: &'a LoudsTrie,
+    // Each entry is a node still to visit and the length of the key its
+    // label extends, or `None` if `key` already spells it.
+    stack: Vec<(u64, Option<usize>)>,
+    key: Vec<u8>,
+}
+
+impl<'a> Iterator for LoudsTrieKeys<'a> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let louds = &self.trie.louds;
+        while let Some((node, depth)) = self.stack.pop() {
+            if let Some(depth) = depth {
+                self.key.truncate(depth);
+                self.key.push(self.trie.label(node).expect("LoudsTrie: labeled root"));
+            }
+            if let Some(first) = louds.first_child(node) {
+                let kids = first .. first + louds.degree(node);
+                let depth = self.key.len();
+                self.stack.extend(kids.rev().map(|kid| (kid, Some(depth))));
+            }
+            if self.trie.is_terminal(node) {
+                return Some(self.key.clone());
+            }
+        }
+        None
+    }
+}
+
+// A trie of single bytes with a node per prefix, the root at 0.
+pub(crate) struct PointerTrie {
+    pub parents: Vec<Option<u64>>,
+    // The label into each node; the root’s is 0.
+    pub labels: Vec<u8>,
+    pub children: Vec<Vec<usize>>,
+    pub terminal: Vec<bool>,
+}
+
+impl PointerTrie {
+    // Inserting in sorted order means a key can only share a prefix with
+    // its parent’s newest child, and that children are created in label
+    // order.
+    pub fn from_sorted_iter<I>(keys: I) -> Result<Self, Error>
+        where I: IntoIterator,
+              I::Item: AsRef<[u8]> {
+
+        let mut trie = PointerTrie {
+            parents: vec![None],
+            labels: vec![0],
+            children: vec![Vec::new()],
+            terminal: vec![false],
+        };
+        let mut previous: Option<Vec<u8>> = None;
+
+        for key in keys {
+            let key = key.as_ref();
+            if previous.as_ref().is_some_and(|previous| &previous[..] >= key) {
+                return Err(Error::NotIncreasing);
+            }
+
+            let mut node = 0;
+            for &byte in key {
+                node = match trie.children[node].last() {
+                    Some(&child) if trie.labels[child] == byte => child,
+                    _ => {
+                        let child = trie.parents.len();
+                        trie.parents.push(Some(node as u64));
+                        trie.labels.push(byte);
+                        trie.children.push(Vec::new());
+                        trie.terminal.push(false);
+                        trie.children[node].push(child);
+                        child
+                    }
+                };
+            }
+            trie.terminal[node] = true;
+
+            let mut buffer = previous.take().unwrap_or_default();
+            buffer.clear();
+            buffer.extend_from_slice(key);
+            previous = Some(buffer);
+        }
+
+        Ok(trie)
+    }
+}
+
+// The child of `node` whose edge is labeled `label`, where `labels`
+// holds the label into each node but the root, in LOUDS order.
+pub(crate) fn child_by_label(louds: &Louds, labels: &WaveletMatrix,
+                             node: u64, label: u8) -> Option<u64> {
+    let first = louds.first_child(node)?;
+    let end = first + louds.degree(node);
+
+    // Edge i enters node i + 1.
+    let label = label as u64;
+    let before = labels.rank_before(first - 1, label);
+    if labels.rank_before(end - 1, label) == before { return None; }
+    labels.select(before, label).map(|edge| edge + 1)
+}
+
+/// Lists the keys in increasing order.
+impl Snapshot for LoudsTrie {
+    type Item = Vec<u8>;
+    type Iter<'a> = LoudsTrieKeys<'a> where Self: 'a;
+
+    fn dump_iter(&self) -> LoudsTrieKeys<'_> {
+        self.iter()
+    }
+}
+
+impl SpaceUsage for LoudsTrie {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.louds.heap_bytes() + self.labels.heap_bytes() + self.terminals.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let trie = LoudsTrie::new(Vec::<&[u8]>::new());
+        assert!(trie.is_empty());
+        assert_eq!(1, trie.node_count());
+        assert!(!trie.contains(b""));
+        assert!(trie.keys_with_prefix(b"").is_empty());
+    }
+
+    #[test]
+    fn sorted_input() {
+        let trie = LoudsTrie::from_sorted_iter(vec!["a", "ab", "b"]).unwrap();
+        assert_eq!(3, trie.len());
+        assert_eq!(Some(Error::NotIncreasing),
+                   LoudsTrie::from_sorted_iter(vec!["a", "c", "b"]).err());
+        assert_eq!(Some(Error::NotIncreasing),
+                   LoudsTrie::from_sorted_iter(vec!["a", "a"]).err());
+    }
+
+    #[test]
+    fn merging() {
+        let a = LoudsTrie::new(vec!["", "ant", "bee", "cat"]);
+        let b = LoudsTrie::new(vec!["ant", "ants", "cat", "dog"]);
+        let union: Vec<Vec<u8>> = a.union(&b).iter().collect();
+        assert_eq!(vec![b"".to_vec(), b"ant".to_vec(), b"ants".to_vec(), b"bee".to_vec(),
+                        b"cat".to_vec(), b"dog".to_vec()],
+                   union);
+        assert_eq!(vec![b"ant".to_vec(), b"cat".to_vec()],
+                   a.intersection(&b).iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn wide_branching() {
+        let keys: Vec<Vec<u8>> = (0 .. 256).map(|byte| vec![byte as u8, 7]).collect();
+        let trie = LoudsTrie::new(keys.iter().chain(Some(&Vec::new())));
+        assert_eq!(257, trie.len());
+        assert_eq!(256, trie.louds().degree(trie.root()));
+        assert_eq!(Some(0), trie.lookup(b""));
+
+        for key in &keys {
+            let node = trie.child(trie.root(), key[0]).unwrap();
+            assert_eq!(Some(key[0]), trie.label(node));
+            assert!(trie.child(node, 6).is_none());
+            assert!(trie.contains(key));
+        }
+    }
+
+    #[test]
+    fn qc_matches_btree() {
+        fn prop(keys: Vec<Vec<u8>>, probes: Vec<Vec<u8>>) -> bool {
+            // Small labels make shared prefixes likely.
+            let set: BTreeSet<Vec<u8>> = keys.into_iter()
+                .map(|key| key.into_iter().map(|byte| byte % 4).collect())
+                .collect();
+            let trie = LoudsTrie::new(&set);
+
+            trie.len() == set.len() as u64
+                && (0 .. trie.len()).all(|id| set.contains(&trie.key(id)))
+                && probes.into_iter().all(|probe| {
+                    let probe: Vec<u8> = probe.into_iter().map(|byte| byte % 4).collect();
+                    let expected: Vec<Vec<u8>> = set.iter()
+                        .filter(|key| key.starts_with(&probe))
+                        .cloned()
+                        .collect();
+                    let longest = set.iter().filter(|key| probe.starts_with(key))
+                                     .map(|key| key.len()).max();
+                    trie.contains(&probe) == set.contains(&probe)
+                        && trie.keys_with_prefix(&probe) == expected
+                        && trie.longest_prefix_of(&probe).map(|(id, len)| {
+                               assert_eq!(&probe[.. len], &trie.key(id)[..]);
+                               len
+                           }) == longest
+                })
+        }
+
+        quickcheck(prop as fn(Vec<Vec<u8>>, Vec<Vec<u8>>) -> bool);
+    }
+}
@@ -0,0 +1,271 @@
+//! Logarithms, bit widths, rounding division and zig-zag encoding.
+//!
+//! These are the small calculations that sizing a succinct structure
+//! comes down to. They are defined for every input, including zero and
+//! the largest value of each type, where the obvious formulas overflow.
+
+use num_traits::PrimInt;
+
+use storage::BlockType;
+
+/// Returns the smallest `k` such that `2.pow(k) >= n`.
+///
+/// Both `ceil_log2(0)` and `ceil_log2(1)` are 0.
+///
+/// ```
+/// use succinct::util::ceil_log2;
+///
+/// assert_eq!(0, ceil_log2(1u32));
+/// assert_eq!(3, ceil_log2(8u32));
+/// assert_eq!(4, ceil_log2(9u32));
+/// ```
+#[inline]
+pub fn ceil_log2<T: BlockType>(n: T) -> usize {
+    n.ceil_lg()
+}
+
+/// Returns the largest `k` such that `2.pow(k) <= n`, or `None` if `n`
+/// is 0.
+#[inline]
+pub fn floor_log2<T: BlockType>(n: T) -> Option<usize> {
+    if n == T::zero() {
+        None
+    } else {
+        Some(n.floor_lg())
+    }
+}
+
+/// Returns the number of bits needed to store `value`.
+///
+/// This is at least 1, even for 0, so the result is always a valid
+/// element size for an `IntVector`.
+///
+/// ```
+/// use succinct::util::bits_to_represent;
+///
+/// assert_eq!(1, bits_to_represent(0u8));
+/// assert_eq!(3, bits_to_represent(7u8));
+/// assert_eq!(4, bits_to_represent(8u8));
+/// ```
+#[inline]
+pub fn bits_to_represent<T: BlockType>(value: T) -> usize {
+    (T::nbits() - value.leading_zeros() as usize).max(1)
+}
+
+/// Returns the number of `Block`s needed to hold `bits` bits, or `None`
+/// if that doesn’t fit in a `usize`.
+#[inline]
+pub fn words_for_bits<Block: BlockType>(bits: u64) -> Option<usize> {
+    Block::checked_ceil_div_nbits(bits)
+}
+
+/// Returns `dividend / divisor` rounded up, towards positive infinity.
+///
+/// Unlike `(dividend + divisor - 1) / divisor`, this doesn’t overflow,
+/// and it rounds up for negative quotients too.
+///
+/// ```
+/// use succinct::util::div_ceil;
+///
+/// assert_eq!(4, div_ceil(7u32, 2));
+/// assert_eq!(-3, div_ceil(-7i32, 2));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `divisor` is 0, or if the quotient overflows, as
+/// `i32::MIN / -1` does.
+#[inline]
+pub fn div_ceil<T: PrimInt>(dividend: T, divisor: T) -> T {
+    let quotient = dividend / divisor;
+    let remainder = dividend % divisor;
+    // Division truncates towards zero, which is already up when the
+    // remainder and divisor differ in sign.
+    if remainder != T::zero() && (remainder > T::zero()) == (divisor > T::zero()) {
+        quotient + T::one()
+    } else {
+        quotient
+    }
+}
+
+/// Returns `dividend / divisor` rounded to the nearest integer, with
+/// halves rounded up, towards positive infinity.
+///
+/// ```
+/// use succinct::util::div_round;
+///
+/// assert_eq!(4, div_round(7u32, 2));
+/// assert_eq!(-3, div_round(-7i32, 2));
+/// assert_eq!(-2, div_round(-7i32, 3));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `divisor` is 0, or if the quotient overflows, as
+/// `i32::MIN / -1` does.
+#[inline]
+pub fn div_round<T: PrimInt>(dividend: T, divisor: T) -> T {
+    let zero = T::zero();
+    let quotient = dividend / divisor;
+    let remainder = dividend % divisor;
+    if remainder == zero { return quotient; }
+
+    // The remainder and the distance to the next multiple of `divisor`
+    // are compared with their signs rather than as magnitudes, which
+    // could overflow.
+    let positive = divisor > zero;
+    if (remainder > zero) == positive {
+        // Truncated down, so go up from half way.
+        let rest = divisor - remainder;
+        let up = if positive { remainder >= rest } else { remainder <= rest };
+        if up { quotient + T::one() } else { quotient }
+    } else {
+        // Truncated up, so go down from past half way.
+        let rest = divisor + remainder;
+        let down = if positive { zero - remainder > rest } else { zero - remainder < rest };
+        if down { quotient - T::one() } else { quotient }
+    }
+}
+
+/// Maps a signed integer to an unsigned one so that values near zero,
+/// of either sign, become small: 0, −1, 1, −2, 2, … become 0, 1, 2, 3,
+/// 4, ….
+///
+/// ```
+/// use succinct::util::{zigzag_decode, zigzag_encode};
+///
+/// assert_eq!(3, zigzag_encode(-2));
+/// assert_eq!(-2, zigzag_decode(3));
+/// ```
+#[inline]
+pub fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Inverts [`zigzag_encode`](fn.zigzag_encode.html).
+#[inline]
+pub fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::{quickcheck, TestResult};
+
+    #[test]
+    fn logs_at_the_edges() {
+        assert_eq!(0, ceil_log2(0u8));
+        assert_eq!(0, ceil_log2(1u8));
+        assert_eq!(1, ceil_log2(2u8));
+        assert_eq!(8, ceil_log2(255u8));
+        assert_eq!(64, ceil_log2(!0u64));
+        assert_eq!(63, ceil_log2(1u64 << 63));
+
+        assert_eq!(None, floor_log2(0u32));
+        assert_eq!(Some(0), floor_log2(1u32));
+        assert_eq!(Some(1), floor_log2(3u32));
+        assert_eq!(Some(31), floor_log2(!0u32));
+    }
+
+    #[test]
+    fn widths() {
+        assert_eq!(1, bits_to_represent(0u64));
+        assert_eq!(1, bits_to_represent(1u64));
+        assert_eq!(2, bits_to_represent(2u64));
+        assert_eq!(64, bits_to_represent(!0u64));
+
+        assert_eq!(Some(0), words_for_bits::<u64>(0));
+        assert_eq!(Some(1), words_for_bits::<u64>(1));
+        assert_eq!(Some(1), words_for_bits::<u64>(64));
+        assert_eq!(Some(2), words_for_bits::<u64>(65));
+        assert_eq!(Some(1 << 61), words_for_bits::<u8>(!0));
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(0, div_ceil(0u32, 5));
+        assert_eq!(3, div_ceil(12u32, 5));
+        assert_eq!(255, div_ceil(255u8, 1));
+        assert_eq!(128, div_ceil(255u8, 2));
+
+        assert_eq!(2, div_round(12u32, 5));
+        assert_eq!(3, div_round(13u32, 5));
+        assert_eq!(1, div_round(1u32, 2));
+        assert_eq!(128, div_round(255u8, 2));
+
+        assert_eq!(-3, div_ceil(-7i32, 2));
+        assert_eq!(4, div_ceil(-7i32, -2));
+        assert_eq!(-3, div_ceil(7i32, -2));
+        assert_eq!(-64, div_ceil(i8::MIN, 2));
+        assert_eq!(-63, div_ceil(i8::MIN + 1, 2));
+
+        assert_eq!(-3, div_round(-7i32, 2));
+        assert_eq!(-2, div_round(-7i32, 3));
+        assert_eq!(-3, div_round(8i32, -3));
+        assert_eq!(3, div_round(-8i32, -3));
+        assert_eq!(-1, div_round(i8::MIN, i8::MAX));
+        assert_eq!(-127, div_round(i8::MIN + 1, 1));
+    }
+
+    #[test]
+    fn qc_div() {
+        fn prop(dividend: u32, divisor: u32) -> TestResult {
+            if divisor == 0 { return TestResult::discard(); }
+
+            let (a, b) = (dividend as u64, divisor as u64);
+            TestResult::from_bool(
+                div_ceil(dividend, divisor) as u64 == a.div_ceil(b)
+                    && div_round(dividend, divisor) as u64 == (2 * a + b) / (2 * b))
+        }
+
+        quickcheck(prop as fn(u32, u32) -> TestResult);
+    }
+
+    #[test]
+    fn qc_div_signed() {
+        fn prop(dividend: i32, divisor: i32) -> TestResult {
+            if divisor == 0 || (dividend == i32::MIN && divisor == -1) {
+                return TestResult::discard();
+            }
+
+            // Exact in `f64`, with halves landing exactly on `.5`.
+            let exact = dividend as f64 / divisor as f64;
+            TestResult::from_bool(
+                div_ceil(dividend, divisor) as f64 == exact.ceil()
+                    && div_round(dividend, divisor) as f64 == (exact + 0.5).floor())
+        }
+
+        quickcheck(prop as fn(i32, i32) -> TestResult);
+    }
+
+    #[test]
+    fn zigzag() {
+        assert_eq!(0, zigzag_encode(0));
+        assert_eq!(1, zigzag_encode(-1));
+        assert_eq!(2, zigzag_encode(1));
+        assert_eq!(!0, zigzag_encode(i64::MIN));
+        assert_eq!(!0 - 1, zigzag_encode(i64::MAX));
+
+        fn prop(n: i64) -> bool {
+            zigzag_decode(zigzag_encode(n)) == n
+                && zigzag_encode(n) == if n < 0 {
+                    2 * (-(n + 1)) as u64 + 1
+                } else {
+                    2 * n as u64
+                }
+        }
+
+        quickcheck(prop as fn(i64) -> bool);
+    }
+
+    #[test]
+    fn qc_bits_to_represent() {
+        fn prop(n: u64) -> bool {
+            let bits = bits_to_represent(n);
+            bits == 64 || n < 1 << bits
+        }
+
+        quickcheck(prop as fn(u64) -> bool);
+    }
+}
@@ -0,0 +1,263 @@
+use bit_vec::{BitVec, BitVector, DynamicBitVector};
+use int_vec::IntVec;
+use rank::RankSupport;
+use select::{Select0Support, Select1Support, SelectSupport};
+use space_usage::SpaceUsage;
+use super::levels::{BitLevel, Level, LevelBits, Levels};
+
+/// A wavelet matrix that supports inserting and removing elements.
+///
+/// This is laid out like a [`WaveletMatrix`](struct.WaveletMatrix.html),
+/// with a [`DynamicBitVector`](../bit_vec/struct.DynamicBitVector.html)
+/// per level, so that an edit inserts or removes one bit per level at
+/// the positions a query would visit. The number of levels is fixed when
+/// the matrix is created, and bounds the values it can hold.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::wavelet::DynamicWaveletMatrix;
+///
+/// let mut wm = DynamicWaveletMatrix::new(4);
+/// for &x in &[3, 1, 4, 1, 5] {
+///     wm.push(x);
+/// }
+/// wm.insert(2, 9);
+/// assert_eq!(vec![3, 1, 9, 4, 1, 5], (0 .. 6).map(|i| wm.get(i)).collect::<Vec<_>>());
+/// assert_eq!(2, wm.rank_before(6, 1));
+///
+/// assert_eq!(1, wm.remove(1));
+/// assert_eq!(1, wm.rank_before(5, 1));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DynamicWaveletMatrix {
+    levels: Levels<BitLevel<DynamicBitVector>>,
+}
+
+impl LevelBits for DynamicBitVector {
+    fn from_bit_vector(bits: BitVector<u64>) -> Self {
+        DynamicBitVector::from_bits((0 .. bits.bit_len()).map(|i| bits.get_bit(i)))
+    }
+
+    fn bit(&self, index: u64) -> bool {
+        self.get_bit(index)
+    }
+
+    fn select_bit(&self, index: u64, bit: bool) -> Option<u64> {
+        if bit {self.select1(index)} else {self.select0(index)}
+    }
+}
+
+impl DynamicWaveletMatrix {
+    /// Creates an empty matrix for values of up to `levels` bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` is 0 or more than 64.
+    pub fn new(levels: usize) -> Self {
+        assert!(levels > 0 && levels <= 64,
+                "DynamicWaveletMatrix::new: bad number of levels");
+        DynamicWaveletMatrix {
+            levels: Levels {
+                len: 0,
+                levels: (0 .. levels).map(|_| BitLevel::default()).collect(),
+            },
+        }
+    }
+
+    /// The number of bits per element.
+    pub fn levels(&self) -> usize {
+        self.levels.levels.len()
+    }
+
+    /// Inserts `value` before position `position`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position > self.len()` or `value` needs more than
+    /// `levels()` bits.
+    pub fn insert(&mut self, position: u64, value: u64) {
+        assert!(position <= self.len(),
+                "DynamicWaveletMatrix::insert: out of bounds");
+        assert!(self.levels.fits(value), "DynamicWaveletMatrix::insert: value too wide");
+
+        let mut position = position;
+        for i in 0 .. self.levels() {
+            let bit = self.levels.digit_of(value, i);
+            let level = &mut self.levels.levels[i];
+            level.bits.insert(position, bit == 1);
+            if bit == 0 { level.zeros += 1; }
+            position = level.step(position, bit);
+        }
+
+        self.levels.len += 1;
+    }
+
+    /// Appends `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` needs more than `levels()` bits.
+    pub fn push(&mut self, value: u64) {
+        let len = self.len();
+        self.insert(len, value);
+    }
+
+    /// Removes and returns the element at `position`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position >= self.len()`.
+    pub fn remove(&mut self, position: u64) -> u64 {
+        assert!(position < self.len(),
+                "DynamicWaveletMatrix::remove: out of bounds");
+
+        let mut result = 0;
+        let mut position = position;
+        for level in &mut self.levels.levels {
+            let bit = level.digit(position);
+            result = result << 1 | bit;
+
+            let next = level.step(position, bit);
+            level.bits.remove(position);
+            if bit == 0 { level.zeros -= 1; }
+            position = next;
+        }
+
+        self.levels.len -= 1;
+        result
+    }
+
+    /// The number of occurrences of `value` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn rank_before(&self, end: u64, value: u64) -> u64 {
+        assert!(end <= self.len(),
+                "DynamicWaveletMatrix::rank_before: out of bounds");
+        self.levels.rank_before(end, value)
+    }
+
+    /// The number of elements less than `value` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn count_less(&self, end: u64, value: u64) -> u64 {
+        assert!(end <= self.len(),
+                "DynamicWaveletMatrix::count_less: out of bounds");
+        self.levels.count_less_range(0, end, value)
+    }
+}
+
+impl IntVec for DynamicWaveletMatrix {
+    type Block = u64;
+
+    fn len(&self) -> u64 {
+        self.levels.len
+    }
+
+    fn element_bits(&self) -> usize {
+        self.levels()
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        assert!(index < self.len(), "DynamicWaveletMatrix::get: out of bounds");
+        self.levels.get(index)
+    }
+}
+
+impl RankSupport for DynamicWaveletMatrix {
+    type Over = u64;
+
+    fn rank(&self, position: u64, value: u64) -> u64 {
+        assert!(position < self.len(), "DynamicWaveletMatrix::rank: out of bounds");
+        self.rank_before(position + 1, value)
+    }
+
+    fn limit(&self) -> u64 {
+        self.len()
+    }
+}
+
+impl SelectSupport for DynamicWaveletMatrix {
+    type Over = u64;
+
+    fn select(&self, index: u64, value: u64) -> Option<u64> {
+        self.levels.select(index, value)
+    }
+}
+
+impl SpaceUsage for DynamicWaveletMatrix {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        let levels = &self.levels.levels;
+        levels.iter()
+            .map(|level| level.bits.total_bytes())
+            .sum::<usize>()
+            + levels.capacity() * ::std::mem::size_of::<BitLevel<DynamicBitVector>>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let wm = DynamicWaveletMatrix::new(3);
+        assert_eq!(0, wm.len());
+        assert_eq!(0, wm.rank_before(0, 2));
+        assert_eq!(None, wm.select(0, 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_wide() {
+        DynamicWaveletMatrix::new(2).push(4);
+    }
+
+    #[test]
+    fn qc_matches_vec() {
+        // Inserts where the flag is set, removes otherwise.
+        fn prop(ops: Vec<(bool, u8, u8)>, probe: u8) -> bool {
+            let mut wm = DynamicWaveletMatrix::new(4);
+            let mut naive: Vec<u64> = Vec::new();
+
+            for (insert, position, value) in ops {
+                let value = value as u64 % 16;
+                if insert || naive.is_empty() {
+                    let position = position as usize % (naive.len() + 1);
+                    naive.insert(position, value);
+                    wm.insert(position as u64, value);
+                } else {
+                    let position = position as usize % naive.len();
+                    if naive.remove(position) != wm.remove(position as u64) {
+                        return false;
+                    }
+                }
+            }
+
+            let probe = probe as u64 % 17;
+            let found: Vec<u64> = (0 .. naive.len() as u64)
+                .filter(|&i| naive[i as usize] == probe).collect();
+
+            (0 .. naive.len()).all(|i| wm.get(i as u64) == naive[i])
+                && (0 .. naive.len() + 1).all(|i| {
+                    wm.rank_before(i as u64, probe)
+                        == naive[.. i].iter().filter(|&&x| x == probe).count() as u64
+                        && wm.count_less(i as u64, probe)
+                            == naive[.. i].iter().filter(|&&x| x < probe).count() as u64
+                })
+                && (0 .. found.len() + 1).all(|k| {
+                    wm.select(k as u64, probe) == found.get(k).cloned()
+                })
+        }
+
+        quickcheck(prop as fn(Vec<(bool, u8, u8)>, u8) -> bool);
+    }
+}
@@ -0,0 +1,197 @@
+use std::ops::Range;
+
+use int_vec::{IntVec, IntVector};
+use internal::search::binary_search_function;
+use select::SelectSupport;
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use super::WaveletMatrix;
+
+/// An immutable set of points in the plane, for counting and reporting
+/// the points in a rectangle.
+///
+/// Coordinates are arbitrary `u64`s. Building maps them to rank space:
+/// the points are sorted by *x*, which are kept in that order, and the
+/// distinct *y*s are kept sorted, each in an
+/// [`IntVector`](../int_vec/struct.IntVector.html) just as wide as its
+/// largest coordinate. A [`WaveletMatrix`](struct.WaveletMatrix.html)
+/// then holds the rank of each point’s *y*, in *x* order. A query
+/// binary searches its rectangle into rank space, which makes it a range
+/// of positions and a range of values in the matrix, so counting takes
+/// *O*(log *n*) time and reporting *O*(log *n*) more per point. Large
+/// coordinates such as fixed-point latitudes and longitudes need no
+/// quantizing first, and cost only their width in the coordinate
+/// vectors.
+///
+/// # Example
+///
+/// ```
+/// use succinct::wavelet::PointGrid;
+///
+/// let grid = PointGrid::new(vec![(10, 1 << 40), (20, 5), (30, 1 << 40), (1 << 50, 7)]);
+/// assert_eq!(3, grid.count(0 .. 100, 0 .. u64::MAX));
+/// assert_eq!(vec![(10, 1 << 40), (30, 1 << 40)],
+///            grid.report(0 .. 100, 1 << 20 .. 1 << 41));
+/// assert_eq!(vec![(1 << 50, 7)], grid.report(1 << 49 .. 1 << 51, 0 .. 10));
+/// ```
+#[derive(Clone, Debug)]
+pub struct PointGrid {
+    // The x of each point, in sorted order.
+    xs: IntVector<u64>,
+    // The distinct ys, in sorted order.
+    ys: IntVector<u64>,
+    // The rank in `ys` of each point’s y, in the order of `xs`.
+    matrix: WaveletMatrix,
+}
+
+impl PointGrid {
+    /// Creates a grid of the given points, in any order, with repeats
+    /// ignored.
+    pub fn new<I>(points: I) -> Self
+        where I: IntoIterator<Item = (u64, u64)> {
+
+        let mut points: Vec<(u64, u64)> = points.into_iter().collect();
+        points.sort();
+        points.dedup();
+
+        let mut distinct: Vec<u64> = points.iter().map(|point| point.1).collect();
+        distinct.sort();
+        distinct.dedup();
+
+        let widest = points.last().map_or(0, |point| point.0);
+        let mut xs = IntVector::with_capacity(bits_to_represent(widest), points.len() as u64);
+        for point in &points {
+            xs.push(point.0);
+        }
+
+        let widest = distinct.last().cloned().unwrap_or(0);
+        let mut ys = IntVector::with_capacity(bits_to_represent(widest), distinct.len() as u64);
+        for &y in &distinct {
+            ys.push(y);
+        }
+
+        let ranks: Vec<u64> = points.iter()
+            .map(|point| distinct.binary_search(&point.1).expect("PointGrid::new") as u64)
+            .collect();
+        PointGrid {
+            xs,
+            ys,
+            matrix: WaveletMatrix::new(ranks),
+        }
+    }
+
+    /// The number of points.
+    pub fn len(&self) -> u64 {
+        self.xs.len()
+    }
+
+    /// Are there no points?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The point at `index` in order of *x*, then *y*.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    pub fn get(&self, index: u64) -> (u64, u64) {
+        (self.xs.get(index), self.ys.get(self.matrix.get(index)))
+    }
+
+    // The positions and y ranks covering `x` and `y`.
+    fn to_rank_space(&self, x: Range<u64>, y: Range<u64>) -> (Range<u64>, Range<u64>) {
+        let lower_bound = |values: &IntVector<u64>, value| {
+            binary_search_function(0, values.len(), value, |i| values.get(i))
+                .unwrap_or(values.len())
+        };
+        let x = lower_bound(&self.xs, x.start) .. lower_bound(&self.xs, x.end.max(x.start));
+        let y = lower_bound(&self.ys, y.start) .. lower_bound(&self.ys, y.end.max(y.start));
+        (x, y)
+    }
+
+    /// The number of points with *x* in `x` and *y* in `y`.
+    pub fn count(&self, x: Range<u64>, y: Range<u64>) -> u64 {
+        let (x, y) = self.to_rank_space(x, y);
+        if x.start == x.end || y.start == y.end { return 0; }
+
+        self.matrix.count_less_range(x.start, x.end, y.end)
+            - self.matrix.count_less_range(x.start, x.end, y.start)
+    }
+
+    /// The points with *x* in `x` and *y* in `y`, in order of *x*, then
+    /// *y*.
+    pub fn report(&self, x: Range<u64>, y: Range<u64>) -> Vec<(u64, u64)> {
+        let (x, y) = self.to_rank_space(x, y);
+        let mut result = Vec::new();
+        if x.start == x.end { return result; }
+
+        for (rank, count) in self.matrix.distinct_values_in(x.start, x.end, y) {
+            let before = self.matrix.rank_before(x.start, rank);
+            let value = self.ys.get(rank);
+            for i in before .. before + count {
+                let position = self.matrix.select(i, rank).expect("PointGrid::report");
+                result.push((self.xs.get(position), value));
+            }
+        }
+        result.sort();
+        result
+    }
+}
+
+impl SpaceUsage for PointGrid {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.xs.heap_bytes() + self.ys.heap_bytes() + self.matrix.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn empty() {
+        let grid = PointGrid::new(vec![]);
+        assert!(grid.is_empty());
+        assert_eq!(0, grid.count(0 .. 10, 0 .. 10));
+        assert!(grid.report(0 .. u64::MAX, 0 .. u64::MAX).is_empty());
+    }
+
+    #[test]
+    fn wide_coordinates() {
+        let top = u64::MAX;
+        let grid = PointGrid::new(vec![(top - 1, 0), (0, top - 1), (top - 1, top - 1), (0, top - 1)]);
+        assert_eq!(3, grid.len());
+        assert_eq!((0, top - 1), grid.get(0));
+        assert_eq!(2, grid.count(top - 1 .. top, 0 .. top));
+        assert_eq!(vec![(0, top - 1), (top - 1, top - 1)], grid.report(0 .. top, top - 1 .. top));
+        let (low, high) = (5, 3);
+        assert_eq!(0, grid.count(low .. high, 0 .. top));
+    }
+
+    #[test]
+    fn qc_matches_filter() {
+        fn prop(points: Vec<(u64, u64)>, x: (u64, u64), y: (u64, u64)) -> bool {
+            // Shrink the coordinates now and then, so rectangles hit.
+            let points: Vec<(u64, u64)> = points.into_iter()
+                .map(|(a, b)| if a % 2 == 0 {(a % 16, b % 16)} else {(a, b)})
+                .collect();
+            let (x, y) = (x.0 % 20 .. x.1 % 20, y.0 % 20 .. y.1 % 20);
+            let grid = PointGrid::new(points.clone());
+
+            let mut expected: Vec<(u64, u64)> = points.into_iter()
+                .filter(|p| x.start <= p.0 && p.0 < x.end && y.start <= p.1 && p.1 < y.end)
+                .collect();
+            expected.sort();
+            expected.dedup();
+
+            grid.count(x.clone(), y.clone()) == expected.len() as u64
+                && grid.report(x, y) == expected
+        }
+
+        quickcheck(prop as fn(Vec<(u64, u64)>, (u64, u64), (u64, u64)) -> bool);
+    }
+}
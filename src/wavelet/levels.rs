@@ -0,0 +1,211 @@
+//! The walks the wavelet matrices share: down the levels with an
+//! element’s position or a value’s range, and back up.
+
+use bit_vec::BitVector;
+use rank::BitRankSupport;
+use space_usage::SpaceUsage;
+
+/// The bit vector at each level of a
+/// [`WaveletMatrix`](struct.WaveletMatrix.html): the queries that step an
+/// element down a level and back.
+///
+/// `select_bit` defaults to a binary search over `rank1`, for bit
+/// vectors without select support.
+pub trait LevelBits: BitRankSupport + SpaceUsage {
+    /// Builds a level from its bits.
+    fn from_bit_vector(bits: BitVector<u64>) -> Self;
+
+    /// The bit at `index`.
+    fn bit(&self, index: u64) -> bool;
+
+    /// The level’s bits, for saving it.
+    ///
+    /// This defaults to collecting them with `bit`.
+    fn to_bit_vector(&self) -> BitVector<u64> {
+        BitVector::from_bits((0 .. self.limit()).map(|index| self.bit(index)))
+    }
+
+    /// The position of the `index`th `bit`, if there is one.
+    fn select_bit(&self, index: u64, bit: bool) -> Option<u64> {
+        use internal::search::binary_search_function;
+
+        let len = self.limit();
+        if bit {
+            binary_search_function(0, len, index + 1, |i| self.rank1(i))
+        } else {
+            binary_search_function(0, len, index + 1, |i| self.rank0(i))
+        }
+    }
+}
+
+/// A level of a wavelet matrix, which holds a digit of `DIGIT_BITS` bits
+/// of every element and stably groups the elements by it for the next.
+pub trait Level {
+    /// The bits of the values each level resolves.
+    const DIGIT_BITS: usize;
+
+    /// The digit of the element at `index`.
+    fn digit(&self, index: u64) -> u64;
+
+    /// Where position `index` of this level goes on the next, for an
+    /// element with `digit`.
+    fn step(&self, index: u64, digit: u64) -> u64;
+
+    /// The inverse of `step`.
+    fn step_back(&self, index: u64, digit: u64) -> u64;
+
+    /// The number of positions in `start .. end` whose digits are less
+    /// than `digit`.
+    fn count_smaller(&self, start: u64, end: u64, digit: u64) -> u64;
+}
+
+/// A level of one bit per element, with the elements whose bit is 0
+/// moved ahead of those whose bit is 1.
+#[derive(Clone, Debug, Default)]
+pub struct BitLevel<Bits> {
+    pub bits: Bits,
+    pub zeros: u64,
+}
+
+impl<Bits: LevelBits> BitLevel<Bits> {
+    pub fn rank1_before(&self, end: u64) -> u64 {
+        if end == 0 {0} else {self.bits.rank1(end - 1)}
+    }
+}
+
+impl<Bits: LevelBits> Level for BitLevel<Bits> {
+    const DIGIT_BITS: usize = 1;
+
+    fn digit(&self, index: u64) -> u64 {
+        self.bits.bit(index) as u64
+    }
+
+    fn step(&self, index: u64, digit: u64) -> u64 {
+        let ones = self.rank1_before(index);
+        if digit == 1 {self.zeros + ones} else {index - ones}
+    }
+
+    fn step_back(&self, index: u64, digit: u64) -> u64 {
+        let found = if digit == 1 {
+            self.bits.select_bit(index - self.zeros, true)
+        } else {
+            self.bits.select_bit(index, false)
+        };
+        found.expect("wavelet matrix: inconsistent levels")
+    }
+
+    fn count_smaller(&self, start: u64, end: u64, digit: u64) -> u64 {
+        if digit == 0 { return 0; }
+        end - start - (self.rank1_before(end) - self.rank1_before(start))
+    }
+}
+
+/// The levels of a wavelet matrix over `len` elements, from the most
+/// significant digit down.
+#[derive(Clone, Debug)]
+pub struct Levels<L> {
+    pub len: u64,
+    pub levels: Vec<L>,
+}
+
+impl<L: Level> Levels<L> {
+    /// The digit of `value` that level `level` resolves.
+    pub fn digit_of(&self, value: u64, level: usize) -> u64 {
+        let shift = L::DIGIT_BITS * (self.levels.len() - 1 - level);
+        value >> shift & ((1 << L::DIGIT_BITS) - 1)
+    }
+
+    /// Whether `value` has no more bits than the levels resolve.
+    pub fn fits(&self, value: u64) -> bool {
+        value >> (L::DIGIT_BITS * self.levels.len() - 1) >> 1 == 0
+    }
+
+    pub fn get(&self, index: u64) -> u64 {
+        let mut result = 0;
+        let mut index = index;
+        for level in &self.levels {
+            let digit = level.digit(index);
+            result = result << L::DIGIT_BITS | digit;
+            index = level.step(index, digit);
+        }
+
+        result
+    }
+
+    pub fn rank_before(&self, end: u64, value: u64) -> u64 {
+        if !self.fits(value) { return 0; }
+
+        let mut start = 0;
+        let mut end = end;
+        for (i, level) in self.levels.iter().enumerate() {
+            let digit = self.digit_of(value, i);
+            start = level.step(start, digit);
+            end = level.step(end, digit);
+        }
+
+        end - start
+    }
+
+    pub fn count_less_range(&self, start: u64, end: u64, value: u64) -> u64 {
+        if !self.fits(value) { return end - start; }
+
+        let mut result = 0;
+        let mut start = start;
+        let mut end = end;
+        for (i, level) in self.levels.iter().enumerate() {
+            let digit = self.digit_of(value, i);
+            result += level.count_smaller(start, end, digit);
+            start = level.step(start, digit);
+            end = level.step(end, digit);
+        }
+
+        result
+    }
+
+    /// `get` for each of `indices`, a level at a time.
+    pub fn access_many(&self, indices: &[u64]) -> Vec<u64> {
+        let mut positions = indices.to_vec();
+        let mut result = vec![0; indices.len()];
+        for level in &self.levels {
+            for (position, value) in positions.iter_mut().zip(&mut result) {
+                let digit = level.digit(*position);
+                *value = *value << L::DIGIT_BITS | digit;
+                *position = level.step(*position, digit);
+            }
+        }
+
+        result
+    }
+
+    /// `rank_before` for each `(end, value)` of `queries`, a level at a
+    /// time.
+    pub fn rank_many(&self, queries: &[(u64, u64)]) -> Vec<u64> {
+        let mut ranges: Vec<(u64, u64)> = queries.iter().map(|&(end, _)| (0, end)).collect();
+        for (i, level) in self.levels.iter().enumerate() {
+            for (range, &(_, value)) in ranges.iter_mut().zip(queries) {
+                let digit = self.digit_of(value, i);
+                *range = (level.step(range.0, digit), level.step(range.1, digit));
+            }
+        }
+
+        ranges.iter().zip(queries)
+            .map(|(&(start, end), &(_, value))| if self.fits(value) {end - start} else {0})
+            .collect()
+    }
+
+    pub fn select(&self, index: u64, value: u64) -> Option<u64> {
+        if index >= self.rank_before(self.len, value) { return None; }
+
+        let mut position = 0;
+        for (i, level) in self.levels.iter().enumerate() {
+            position = level.step(position, self.digit_of(value, i));
+        }
+
+        position += index;
+        for (i, level) in self.levels.iter().enumerate().rev() {
+            position = level.step_back(position, self.digit_of(value, i));
+        }
+
+        Some(position)
+    }
+}
@@ -0,0 +1,550 @@
+use std::io::{self, Write};
+use std::ops::Range;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use bit_vec::{BitVec, BitVecPush, BitVector};
+use build::Monitor;
+use dump::{Elements, Snapshot};
+use error::Error;
+use int_vec::IntVec;
+use persist::{self, Component, Persist};
+use rank::{Rank9, RankSupport};
+use select::SelectSupport;
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use super::levels::{BitLevel, Level, LevelBits, Levels};
+
+/// A wavelet matrix: an immutable sequence of integers with access, rank
+/// and select in time proportional to their width.
+///
+/// There is one bit vector per bit of the widest value, from the most
+/// significant down. Each level records that bit of every element and
+/// then stably moves the elements with a 0 ahead of those with a 1, so a
+/// query follows its element, or value, down the levels with a rank at
+/// each. Unlike a wavelet tree, the space doesn’t depend on the
+/// alphabet’s shape: it is `levels()` bits per element plus the rank
+/// directories.
+///
+/// `Bits` is the bit vector of each level, a
+/// [`Rank9`](../rank/struct.Rank9.html) by default; any
+/// [`LevelBits`](trait.LevelBits.html) will do, such as the run-length
+/// compressed levels of a
+/// [`RunLengthWaveletMatrix`](struct.RunLengthWaveletMatrix.html).
+/// Construct with `WaveletMatrix::new_in` to pick one.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::rank::RankSupport;
+/// use succinct::select::SelectSupport;
+/// use succinct::wavelet::WaveletMatrix;
+///
+/// let wm = WaveletMatrix::new(vec![3, 1, 4, 1, 5, 9, 2, 6]);
+/// assert_eq!(4, wm.get(2));
+/// assert_eq!(2, wm.rank(3, 1));   // two 1s in positions 0 through 3
+/// assert_eq!(Some(3), wm.select(1, 1));
+/// assert_eq!(4, wm.count_less(8, 4));
+/// ```
+#[derive(Clone, Debug)]
+pub struct WaveletMatrix<Bits = Rank9<BitVector<u64>>> {
+    levels: Levels<BitLevel<Bits>>,
+}
+
+impl LevelBits for Rank9<BitVector<u64>> {
+    fn from_bit_vector(bits: BitVector<u64>) -> Self {
+        Rank9::new(bits)
+    }
+
+    fn bit(&self, index: u64) -> bool {
+        self.get_bit(index)
+    }
+
+    fn to_bit_vector(&self) -> BitVector<u64> {
+        self.inner().clone()
+    }
+}
+
+impl WaveletMatrix {
+    /// Creates a wavelet matrix holding the given values, with as many
+    /// levels as the largest of them needs.
+    pub fn new<I>(values: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        Self::new_in(values)
+    }
+
+    /// Creates a wavelet matrix from the elements of an integer vector.
+    pub fn from_int_vec<V>(source: &V) -> Self
+        where V: IntVec + ?Sized {
+
+        use num_traits::ToPrimitive;
+
+        Self::new((0 .. source.len()).map(|i| {
+            source.get(i).to_u64().expect("WaveletMatrix: block too wide")
+        }))
+    }
+
+    /// Creates a wavelet matrix with `levels` levels, which must be
+    /// enough for every value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` is 0 or more than 64, or a value needs more
+    /// than `levels` bits.
+    pub fn with_levels(levels: usize, values: Vec<u64>) -> Self {
+        Self::with_monitor(levels, values, &mut Monitor::new())
+            .unwrap_or_else(|error| panic!("WaveletMatrix::with_levels: {}", error))
+    }
+
+    /// Creates a wavelet matrix with `levels` levels, reporting each
+    /// level built to `monitor`, or returns `Error::ElementBits` if
+    /// `levels` is 0 or more than 64, `Error::ValueTooLarge` if a value
+    /// needs more than `levels` bits, `Error::OverBudget` if it would
+    /// need more memory than `monitor` allows, or `Error::Cancelled` if
+    /// `monitor` cancels.
+    pub fn with_monitor(levels: usize, values: Vec<u64>, monitor: &mut Monitor)
+                        -> Result<Self, Error> {
+        check_levels(levels, &values)?;
+        let len = values.len() as u64;
+        // Each level’s bits and rank directory, and the partition of
+        // the values into zeros and ones.
+        monitor.reserve(Self::estimate_size(len, levels) as u64 + 16 * len)?;
+        Self::build(levels, values, monitor)
+    }
+
+    /// The heap bytes of a wavelet matrix of `len` values of `levels`
+    /// bits, without building it.
+    pub fn estimate_size(len: u64, levels: usize) -> usize {
+        let level = BitVector::<u64>::estimate_size(len) + Rank9::estimate_size(len)
+            + ::std::mem::size_of::<Rank9<BitVector<u64>>>()
+            + ::std::mem::size_of::<BitLevel<Rank9<BitVector<u64>>>>();
+        levels * level
+    }
+}
+
+fn check_levels(levels: usize, values: &[u64]) -> Result<(), Error> {
+    if levels == 0 || levels > 64 { return Err(Error::ElementBits(levels)); }
+    if values.iter().any(|&value| value >> (levels - 1) >> 1 != 0) {
+        return Err(Error::ValueTooLarge);
+    }
+    Ok(())
+}
+
+impl<Bits: LevelBits> WaveletMatrix<Bits> {
+    /// Creates a wavelet matrix holding the given values, with as many
+    /// levels as the largest of them needs, each a `Bits`.
+    pub fn new_in<I>(values: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        let values: Vec<u64> = values.into_iter().collect();
+        let widest = values.iter().cloned().max().map_or(1, bits_to_represent);
+        Self::with_levels_in(widest, values)
+    }
+
+    /// Creates a wavelet matrix with `levels` levels, each a `Bits`,
+    /// which must be enough for every value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` is 0 or more than 64, or a value needs more
+    /// than `levels` bits.
+    pub fn with_levels_in(levels: usize, values: Vec<u64>) -> Self {
+        check_levels(levels, &values)
+            .and_then(|()| Self::build(levels, values, &mut Monitor::new()))
+            .unwrap_or_else(|error| panic!("WaveletMatrix::with_levels: {}", error))
+    }
+
+    fn build(levels: usize, mut values: Vec<u64>, monitor: &mut Monitor)
+             -> Result<Self, Error> {
+        let len = values.len() as u64;
+        let mut result = Vec::with_capacity(levels);
+        let mut ones = Vec::with_capacity(values.len());
+
+        for level in (0 .. levels).rev() {
+            monitor.report("wavelet levels", result.len() as u64, levels as u64)?;
+            let mut bits = BitVector::with_capacity(len);
+            let mut zeros = Vec::with_capacity(values.len());
+            ones.clear();
+
+            for &value in &values {
+                let bit = value >> level & 1 == 1;
+                bits.push_bit(bit);
+                if bit { ones.push(value) } else { zeros.push(value) }
+            }
+
+            result.push(BitLevel {
+                bits: Bits::from_bit_vector(bits),
+                zeros: zeros.len() as u64,
+            });
+
+            zeros.extend_from_slice(&ones);
+            values = zeros;
+        }
+
+        monitor.report("wavelet levels", levels as u64, levels as u64)?;
+        Ok(WaveletMatrix { levels: Levels { len, levels: result } })
+    }
+
+    /// The number of bits per element.
+    pub fn levels(&self) -> usize {
+        self.levels.levels.len()
+    }
+
+    /// The number of occurrences of `value` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn rank_before(&self, end: u64, value: u64) -> u64 {
+        assert!(end <= self.len(), "WaveletMatrix::rank_before: out of bounds");
+        self.levels.rank_before(end, value)
+    }
+
+    /// The elements at each of `indices`, in order.
+    ///
+    /// This answers the queries together a level at a time, rather than
+    /// one after another all the way down, so each level’s bits and
+    /// directory are loaded once for the whole batch where separate
+    /// `get`s would go back to each level for every query. For thousands
+    /// of queries on a matrix too large for the cache, that saves most
+    /// of the misses. Sorting the indices first helps further, since
+    /// nearby positions stay nearby on every level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index is out of bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use succinct::wavelet::WaveletMatrix;
+    ///
+    /// let wm = WaveletMatrix::new(vec![3, 1, 4, 1, 5, 9, 2, 6]);
+    /// assert_eq!(vec![9, 3, 1], wm.access_many(&[5, 0, 3]));
+    /// assert_eq!(vec![2, 0, 1], wm.rank_many(&[(4, 1), (8, 7), (8, 9)]));
+    /// ```
+    pub fn access_many(&self, indices: &[u64]) -> Vec<u64> {
+        assert!(indices.iter().all(|&index| index < self.len()),
+                "WaveletMatrix::access_many: out of bounds");
+        self.levels.access_many(indices)
+    }
+
+    /// The number of occurrences of each `value` before each `end`, for
+    /// the `(end, value)` pairs of `queries`, in order, answered a level
+    /// at a time like [`access_many`](#method.access_many).
+    ///
+    /// # Panics
+    ///
+    /// Panics if an `end > self.len()`.
+    pub fn rank_many(&self, queries: &[(u64, u64)]) -> Vec<u64> {
+        assert!(queries.iter().all(|&(end, _)| end <= self.len()),
+                "WaveletMatrix::rank_many: out of bounds");
+        self.levels.rank_many(queries)
+    }
+
+    /// The number of elements less than `value` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn count_less(&self, end: u64, value: u64) -> u64 {
+        assert!(end <= self.len(), "WaveletMatrix::count_less: out of bounds");
+        self.levels.count_less_range(0, end, value)
+    }
+
+    /// The number of elements less than `value` among positions
+    /// `start .. end`.
+    pub fn count_less_range(&self, start: u64, end: u64, value: u64) -> u64 {
+        assert!(start <= end && end <= self.len(),
+                "WaveletMatrix::count_less_range: out of bounds");
+        self.levels.count_less_range(start, end, value)
+    }
+
+    /// The distinct values among positions `start .. end`, in increasing
+    /// order, each with its number of occurrences there.
+    ///
+    /// This takes time proportional to the number of levels times the
+    /// number of distinct values.
+    pub fn distinct_values(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        assert!(start <= end && end <= self.len(),
+                "WaveletMatrix::distinct_values: out of bounds");
+
+        let mut result = Vec::new();
+        self.distinct_values_from(0, 0, start, end, &mut result);
+        result
+    }
+
+    // Descends both ways from `level`, where the values in `start .. end`
+    // all have the high bits `prefix`.
+    fn distinct_values_from(&self, level: usize, prefix: u64,
+                            start: u64, end: u64,
+                            result: &mut Vec<(u64, u64)>) {
+        if start == end { return; }
+        if level == self.levels() {
+            result.push((prefix, end - start));
+            return;
+        }
+
+        let bits = &self.levels.levels[level];
+        self.distinct_values_from(level + 1, prefix << 1,
+                                  bits.step(start, 0),
+                                  bits.step(end, 0),
+                                  result);
+        self.distinct_values_from(level + 1, prefix << 1 | 1,
+                                  bits.step(start, 1),
+                                  bits.step(end, 1),
+                                  result);
+    }
+
+    /// The distinct values in `values` among positions `start .. end`,
+    /// in increasing order, each with its number of occurrences there.
+    ///
+    /// This takes time proportional to the number of levels times the
+    /// number of values reported, skipping the others.
+    ///
+    /// ```
+    /// use succinct::wavelet::WaveletMatrix;
+    ///
+    /// let wm = WaveletMatrix::new(vec![3, 1, 4, 1, 5, 9, 2, 6]);
+    /// assert_eq!(vec![(1, 1), (2, 1), (4, 1), (5, 1)], wm.distinct_values_in(2, 7, 1 .. 6));
+    /// ```
+    pub fn distinct_values_in(&self, start: u64, end: u64, values: Range<u64>)
+                              -> Vec<(u64, u64)> {
+        assert!(start <= end && end <= self.len(),
+                "WaveletMatrix::distinct_values_in: out of bounds");
+
+        let mut result = Vec::new();
+        if values.start < values.end {
+            self.distinct_values_in_from(0, 0, start, end, &values, &mut result);
+        }
+        result
+    }
+
+    // Like `distinct_values_from`, skipping prefixes whose values all
+    // fall outside `values`.
+    fn distinct_values_in_from(&self, level: usize, prefix: u64,
+                               start: u64, end: u64, values: &Range<u64>,
+                               result: &mut Vec<(u64, u64)>) {
+        if start == end { return; }
+
+        // The values below this node are `low ..= high`.
+        let rest = self.levels() - level;
+        let low = if rest == 64 {0} else {prefix << rest};
+        let high = low | (if rest == 64 {!0} else {(1 << rest) - 1});
+        if high < values.start || low >= values.end { return; }
+
+        if level == self.levels() {
+            result.push((prefix, end - start));
+            return;
+        }
+
+        let bits = &self.levels.levels[level];
+        self.distinct_values_in_from(level + 1, prefix << 1,
+                                     bits.step(start, 0),
+                                     bits.step(end, 0),
+                                     values, result);
+        self.distinct_values_in_from(level + 1, prefix << 1 | 1,
+                                     bits.step(start, 1),
+                                     bits.step(end, 1),
+                                     values, result);
+    }
+}
+
+impl<Bits: LevelBits> IntVec for WaveletMatrix<Bits> {
+    type Block = u64;
+
+    fn len(&self) -> u64 {
+        self.levels.len
+    }
+
+    fn element_bits(&self) -> usize {
+        self.levels()
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        assert!(index < self.len(), "WaveletMatrix::get: out of bounds");
+        self.levels.get(index)
+    }
+}
+
+impl<Bits: LevelBits> RankSupport for WaveletMatrix<Bits> {
+    type Over = u64;
+
+    fn rank(&self, position: u64, value: u64) -> u64 {
+        assert!(position < self.len(), "WaveletMatrix::rank: out of bounds");
+        self.rank_before(position + 1, value)
+    }
+
+    fn limit(&self) -> u64 {
+        self.len()
+    }
+}
+
+impl<Bits: LevelBits> SelectSupport for WaveletMatrix<Bits> {
+    type Over = u64;
+
+    fn select(&self, index: u64, value: u64) -> Option<u64> {
+        self.levels.select(index, value)
+    }
+}
+
+/// Lists the values in order.
+impl<Bits: LevelBits> Snapshot for WaveletMatrix<Bits> {
+    type Item = u64;
+    type Iter<'a> = Elements<'a, Self> where Self: 'a;
+
+    fn dump_iter(&self) -> Elements<'_, Self> {
+        Elements::new(self)
+    }
+}
+
+/// The length and number of levels as `u64`s, then each level’s bits,
+/// from the most significant down. Loading builds each level’s `Bits`
+/// again from its bits.
+impl<Bits: LevelBits> Persist for WaveletMatrix<Bits> {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u64::<LittleEndian>(self.len())?;
+        sink.write_u64::<LittleEndian>(self.levels() as u64)?;
+        for level in &self.levels.levels {
+            persist::write_part(&level.bits.to_bit_vector(), sink)?;
+        }
+        Ok(())
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let who = "WaveletMatrix::read_from";
+        let bad = |what: &str| io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("{}: could not load: {}", who, what));
+        let len = bytes.read_u64::<LittleEndian>()?;
+        let count = bytes.read_u64::<LittleEndian>()?;
+        if count == 0 || count > 64 { return Err(bad("bad level count")); }
+
+        let mut levels = Vec::with_capacity(count as usize);
+        for _ in 0 .. count {
+            let bits: BitVector<u64> = persist::read_part(&mut bytes)?;
+            if bits.bit_len() != len { return Err(bad("wrong level length")); }
+            let mut level = BitLevel { bits: Bits::from_bit_vector(bits), zeros: 0 };
+            level.zeros = len - level.rank1_before(len);
+            levels.push(level);
+        }
+        persist::expect_end(who, bytes)?;
+
+        Ok(WaveletMatrix { levels: Levels { len, levels } })
+    }
+}
+
+impl Component for WaveletMatrix {
+    fn tag() -> String {
+        "WaveletMatrix".to_owned()
+    }
+}
+
+impl<Bits: LevelBits> SpaceUsage for WaveletMatrix<Bits> {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        let levels = &self.levels.levels;
+        levels.iter()
+            .map(|level| level.bits.total_bytes())
+            .sum::<usize>()
+            + levels.capacity() * ::std::mem::size_of::<BitLevel<Bits>>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::{quickcheck, TestResult};
+
+    #[test]
+    fn estimate_size() {
+        let values: Vec<u64> = (0 .. 10_000).map(|i| i * 7 % 1000).collect();
+        let wm = WaveletMatrix::new(values);
+        assert_eq!(wm.heap_bytes(), WaveletMatrix::estimate_size(10_000, 10));
+    }
+
+    #[test]
+    fn empty() {
+        let wm = WaveletMatrix::new(vec![]);
+        assert_eq!(0, wm.len());
+        assert_eq!(1, wm.levels());
+        assert_eq!(0, wm.rank_before(0, 0));
+        assert_eq!(None, wm.select(0, 0));
+    }
+
+    #[test]
+    fn small() {
+        let values = [5, 0, 7, 5, 2, 5, 0, 1];
+        let wm = WaveletMatrix::new(values.iter().cloned());
+        assert_eq!(3, wm.levels());
+
+        for (i, &x) in values.iter().enumerate() {
+            assert_eq!(x, wm.get(i as u64));
+        }
+
+        assert_eq!(3, wm.rank(7, 5));
+        assert_eq!(1, wm.rank(2, 5));
+        assert_eq!(0, wm.rank(7, 3));
+        assert_eq!(0, wm.rank(7, 100));
+
+        assert_eq!(Some(0), wm.select(0, 5));
+        assert_eq!(Some(5), wm.select(2, 5));
+        assert_eq!(None, wm.select(3, 5));
+        assert_eq!(Some(6), wm.select(1, 0));
+
+        assert_eq!(4, wm.count_less(8, 5));
+        assert_eq!(8, wm.count_less(8, 8));
+        assert_eq!(2, wm.count_less_range(3, 8, 2));
+
+        assert_eq!(vec![(0, 1), (2, 1), (5, 2)], wm.distinct_values(3, 7));
+        assert_eq!(Vec::<(u64, u64)>::new(), wm.distinct_values(4, 4));
+        assert_eq!(vec![(2, 1), (5, 2)], wm.distinct_values_in(3, 7, 1 .. 6));
+        assert_eq!(Vec::<(u64, u64)>::new(), wm.distinct_values_in(0, 8, 3 .. 3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_wide() {
+        WaveletMatrix::with_levels(2, vec![4]);
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(values: Vec<u16>, probe: u16, position: usize) -> TestResult {
+            if values.is_empty() { return TestResult::discard(); }
+
+            let values: Vec<u64> = values.into_iter().map(|x| x as u64 % 37)
+                                         .collect();
+            let probe = probe as u64 % 40;
+            let position = position % values.len();
+            let wm = WaveletMatrix::new(values.iter().cloned());
+
+            let rank = values[.. position + 1].iter()
+                                              .filter(|&&x| x == probe).count();
+            let less = values[.. position].iter().filter(|&&x| x < probe).count();
+            let select = values.iter().enumerate().filter(|&(_, &x)| x == probe)
+                               .map(|(i, _)| i as u64).collect::<Vec<_>>();
+
+            let indices: Vec<u64> = (0 .. values.len() as u64).rev().collect();
+            // Probe a value too wide for the matrix too.
+            let ends: Vec<(u64, u64)> = (0 .. values.len() as u64 + 1)
+                .flat_map(|end| vec![(end, probe), (end, 1 << 6)])
+                .collect();
+
+            TestResult::from_bool(
+                wm.access_many(&indices) == indices.iter().map(|&i| values[i as usize]).collect::<Vec<_>>()
+                    && wm.rank_many(&ends) == ends.iter().map(|&(end, value)| wm.rank_before(end, value))
+                                                  .collect::<Vec<_>>()
+                    && wm.get(position as u64) == values[position]
+                    && wm.rank(position as u64, probe) == rank as u64
+                    && wm.count_less(position as u64, probe) == less as u64
+                    && (0 .. select.len() + 1).all(|k| {
+                        wm.select(k as u64, probe) == select.get(k).cloned()
+                    }))
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u16, usize) -> TestResult);
+    }
+}
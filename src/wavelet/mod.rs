@@ -0,0 +1,19 @@
+//! Wavelet structures: rank and select over sequences of integers.
+
+mod dynamic;
+pub use self::dynamic::*;
+
+mod grid;
+pub use self::grid::*;
+
+mod levels;
+pub use self::levels::LevelBits;
+
+mod matrix;
+pub use self::matrix::*;
+
+mod quad;
+pub use self::quad::*;
+
+mod run_length;
+pub use self::run_length::*;
@@ -0,0 +1,467 @@
+use std::io::{self, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use bit_vec::BitVec;
+use broadword;
+use dump::{Elements, Snapshot};
+use int_vec::{IntVec, IntVector};
+use internal::search::binary_search_function;
+use persist::{self, Component, Persist};
+use rank::RankSupport;
+use select::SelectSupport;
+use space_usage::SpaceUsage;
+use util::bits_to_represent;
+use super::levels::{Level, Levels};
+
+/// The digits counted by each superblock entry.
+const SUPERBLOCK: u64 = 1 << 16;
+
+/// The digits counted by each block entry: eight words of 32.
+const BLOCK: u64 = 256;
+
+/// A 4-ary wavelet matrix: like a
+/// [`WaveletMatrix`](struct.WaveletMatrix.html), but each level resolves
+/// two bits of the values rather than one.
+///
+/// A level holds a 2-bit digit of every element, from the most
+/// significant down, and stably moves the elements into four groups by
+/// that digit for the next. That halves the number of levels, and so the
+/// dependent rank steps a query takes, which is what bounds its latency
+/// on large sequences, where each step is likely a cache miss. Each
+/// level ranks its digits with a directory of every digit’s count before
+/// each 64 Ki-digit superblock, in a `u64`, and before each 256-digit
+/// block, relative to its superblock, in a `u16`, then counts the rest of
+/// the block a word of 32 digits at a time. The directory costs an eighth
+/// of a bit per bit of digits, less than a `WaveletMatrix`’s
+/// [`Rank9`](../rank/struct.Rank9.html); what each step pays instead is
+/// that scan of up to eight words, and a count of each smaller digit in
+/// `count_less`.
+///
+/// # Example
+///
+/// ```
+/// use succinct::IntVec;
+/// use succinct::rank::RankSupport;
+/// use succinct::select::SelectSupport;
+/// use succinct::wavelet::QuadWaveletMatrix;
+///
+/// let wm = QuadWaveletMatrix::new(vec![3, 1, 4, 1, 5, 9, 2, 6]);
+/// assert_eq!(2, wm.levels());
+/// assert_eq!(4, wm.get(2));
+/// assert_eq!(2, wm.rank(3, 1));   // two 1s in positions 0 through 3
+/// assert_eq!(Some(3), wm.select(1, 1));
+/// assert_eq!(4, wm.count_less(8, 4));
+/// ```
+#[derive(Clone, Debug)]
+pub struct QuadWaveletMatrix {
+    levels: Levels<DigitLevel>,
+}
+
+#[derive(Clone, Debug)]
+struct DigitLevel {
+    digits: DigitRank,
+    // Where the elements with each digit start on the next level.
+    starts: [u64; 4],
+}
+
+// A sequence of 2-bit digits with rank for each of them.
+#[derive(Clone, Debug)]
+struct DigitRank {
+    digits: IntVector<u64>,
+    // The count of each digit before each superblock, four per entry.
+    superblocks: Vec<u64>,
+    // The count of each digit from its superblock’s start to each block.
+    blocks: Vec<u16>,
+}
+
+impl DigitRank {
+    fn new(source: &[u64]) -> Self {
+        let len = source.len() as u64;
+        let mut digits = IntVector::with_capacity(2, len);
+        let mut superblocks = Vec::with_capacity(4 * (len / SUPERBLOCK + 1) as usize);
+        let mut blocks = Vec::with_capacity(4 * (len / BLOCK + 1) as usize);
+        let mut counts = [0u64; 4];
+        let mut base = [0u64; 4];
+
+        for i in 0 ..= len {
+            if i % BLOCK == 0 {
+                if i % SUPERBLOCK == 0 {
+                    base = counts;
+                    superblocks.extend_from_slice(&counts);
+                }
+                blocks.extend((0 .. 4).map(|digit| (counts[digit] - base[digit]) as u16));
+            }
+            if i < len {
+                let digit = source[i as usize];
+                digits.push(digit);
+                counts[digit as usize] += 1;
+            }
+        }
+
+        DigitRank { digits, superblocks, blocks }
+    }
+
+    fn len(&self) -> u64 {
+        self.digits.len()
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        self.digits.get(index)
+    }
+
+    // The number of occurrences of `digit` before position `end`.
+    fn rank_before(&self, end: u64, digit: u64) -> u64 {
+        let block = end / BLOCK;
+        let superblock = end / SUPERBLOCK;
+        let mut result = self.superblocks[(4 * superblock + digit) as usize]
+            + self.blocks[(4 * block + digit) as usize] as u64;
+
+        // The high bit of each lane of `matches` says it holds `digit`.
+        let pattern = broadword::splat(digit, 2);
+        let matches = |word: usize| broadword::zero_lanes(self.digits.get_block(word) ^ pattern, 2);
+        let first = (block * BLOCK / 32) as usize;
+        let last = (end / 32) as usize;
+        for word in first .. last {
+            result += matches(word).count_ones() as u64;
+        }
+        let rest = end % 32;
+        if rest > 0 {
+            result += (matches(last) & ((1 << (2 * rest)) - 1)).count_ones() as u64;
+        }
+
+        result
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.digits.heap_bytes() + self.superblocks.heap_bytes() + self.blocks.heap_bytes()
+    }
+}
+
+impl DigitLevel {
+    fn new(digits: &[u64]) -> Self {
+        let mut counts = [0; 4];
+        for &digit in digits {
+            counts[digit as usize] += 1;
+        }
+
+        let mut starts = [0; 4];
+        for digit in 1 .. 4 {
+            starts[digit] = starts[digit - 1] + counts[digit - 1];
+        }
+        DigitLevel { digits: DigitRank::new(digits), starts }
+    }
+}
+
+impl Level for DigitLevel {
+    const DIGIT_BITS: usize = 2;
+
+    fn digit(&self, index: u64) -> u64 {
+        self.digits.get(index)
+    }
+
+    fn step(&self, index: u64, digit: u64) -> u64 {
+        self.starts[digit as usize] + self.digits.rank_before(index, digit)
+    }
+
+    fn step_back(&self, index: u64, digit: u64) -> u64 {
+        let target = index - self.starts[digit as usize] + 1;
+        binary_search_function(0, self.digits.len(), target,
+                               |i| self.digits.rank_before(i + 1, digit))
+            .expect("QuadWaveletMatrix: inconsistent levels")
+    }
+
+    fn count_smaller(&self, start: u64, end: u64, digit: u64) -> u64 {
+        (0 .. digit).map(|smaller| {
+            self.digits.rank_before(end, smaller) - self.digits.rank_before(start, smaller)
+        }).sum()
+    }
+}
+
+impl QuadWaveletMatrix {
+    /// Creates a 4-ary wavelet matrix holding the given values, with as
+    /// many levels as the largest of them needs.
+    pub fn new<I>(values: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        let values: Vec<u64> = values.into_iter().collect();
+        let widest = values.iter().cloned().max().map_or(1, bits_to_represent);
+        Self::with_levels(widest.div_ceil(2), values)
+    }
+
+    /// Creates a 4-ary wavelet matrix from the elements of an integer
+    /// vector.
+    pub fn from_int_vec<V>(source: &V) -> Self
+        where V: IntVec + ?Sized {
+
+        use num_traits::ToPrimitive;
+
+        Self::new((0 .. source.len()).map(|i| {
+            source.get(i).to_u64().expect("QuadWaveletMatrix: block too wide")
+        }))
+    }
+
+    /// Creates a 4-ary wavelet matrix with `levels` levels of two bits
+    /// each, which must be enough for every value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` is 0 or more than 32, or a value needs more
+    /// than `2 * levels` bits.
+    pub fn with_levels(levels: usize, mut values: Vec<u64>) -> Self {
+        assert!(levels > 0 && levels <= 32,
+                "QuadWaveletMatrix::with_levels: bad level count");
+        assert!(values.iter().all(|&value| value >> (2 * levels - 1) >> 1 == 0),
+                "QuadWaveletMatrix::with_levels: value too large");
+
+        let len = values.len() as u64;
+        let mut result = Vec::with_capacity(levels);
+        let mut digits = Vec::with_capacity(values.len());
+
+        for level in (0 .. levels).rev() {
+            digits.clear();
+            digits.extend(values.iter().map(|&value| value >> (2 * level) & 3));
+
+            let mut groups: [Vec<u64>; 4] = Default::default();
+            for (&value, &digit) in values.iter().zip(&digits) {
+                groups[digit as usize].push(value);
+            }
+            result.push(DigitLevel::new(&digits));
+
+            values.clear();
+            for group in &groups {
+                values.extend_from_slice(group);
+            }
+        }
+
+        QuadWaveletMatrix { levels: Levels { len, levels: result } }
+    }
+
+    /// The number of levels, each resolving two bits.
+    pub fn levels(&self) -> usize {
+        self.levels.levels.len()
+    }
+
+    /// The number of occurrences of `value` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn rank_before(&self, end: u64, value: u64) -> u64 {
+        assert!(end <= self.len(), "QuadWaveletMatrix::rank_before: out of bounds");
+        self.levels.rank_before(end, value)
+    }
+
+    /// The elements at each of `indices`, in order, as
+    /// [`WaveletMatrix::access_many`](struct.WaveletMatrix.html#method.access_many)
+    /// finds them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index is out of bounds.
+    pub fn access_many(&self, indices: &[u64]) -> Vec<u64> {
+        assert!(indices.iter().all(|&index| index < self.len()),
+                "QuadWaveletMatrix::access_many: out of bounds");
+        self.levels.access_many(indices)
+    }
+
+    /// The number of occurrences of each `value` before each `end`, as
+    /// [`WaveletMatrix::rank_many`](struct.WaveletMatrix.html#method.rank_many)
+    /// counts them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an `end > self.len()`.
+    pub fn rank_many(&self, queries: &[(u64, u64)]) -> Vec<u64> {
+        assert!(queries.iter().all(|&(end, _)| end <= self.len()),
+                "QuadWaveletMatrix::rank_many: out of bounds");
+        self.levels.rank_many(queries)
+    }
+
+    /// The number of elements less than `value` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn count_less(&self, end: u64, value: u64) -> u64 {
+        assert!(end <= self.len(), "QuadWaveletMatrix::count_less: out of bounds");
+        self.levels.count_less_range(0, end, value)
+    }
+}
+
+impl IntVec for QuadWaveletMatrix {
+    type Block = u64;
+
+    fn len(&self) -> u64 {
+        self.levels.len
+    }
+
+    fn element_bits(&self) -> usize {
+        2 * self.levels()
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        assert!(index < self.len(), "QuadWaveletMatrix::get: out of bounds");
+        self.levels.get(index)
+    }
+}
+
+impl RankSupport for QuadWaveletMatrix {
+    type Over = u64;
+
+    fn rank(&self, position: u64, value: u64) -> u64 {
+        assert!(position < self.len(), "QuadWaveletMatrix::rank: out of bounds");
+        self.rank_before(position + 1, value)
+    }
+
+    fn limit(&self) -> u64 {
+        self.len()
+    }
+}
+
+impl SelectSupport for QuadWaveletMatrix {
+    type Over = u64;
+
+    fn select(&self, index: u64, value: u64) -> Option<u64> {
+        self.levels.select(index, value)
+    }
+}
+
+/// Lists the values in order.
+impl Snapshot for QuadWaveletMatrix {
+    type Item = u64;
+    type Iter<'a> = Elements<'a, Self> where Self: 'a;
+
+    fn dump_iter(&self) -> Elements<'_, Self> {
+        Elements::new(self)
+    }
+}
+
+/// The length and number of levels as `u64`s, then each level’s digits
+/// as an `IntVector` of 2-bit elements, from the most significant down.
+/// Loading counts the digits again for the directories.
+impl Persist for QuadWaveletMatrix {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        sink.write_u64::<LittleEndian>(self.len())?;
+        sink.write_u64::<LittleEndian>(self.levels() as u64)?;
+        for level in &self.levels.levels {
+            persist::write_part(&level.digits.digits, sink)?;
+        }
+        Ok(())
+    }
+
+    fn read_from(mut bytes: &[u8]) -> io::Result<Self> {
+        let who = "QuadWaveletMatrix::read_from";
+        let bad = |what: &str| io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("{}: could not load: {}", who, what));
+        let len = bytes.read_u64::<LittleEndian>()?;
+        let count = bytes.read_u64::<LittleEndian>()?;
+        if count == 0 || count > 32 { return Err(bad("bad level count")); }
+
+        let mut levels = Vec::with_capacity(count as usize);
+        for _ in 0 .. count {
+            let digits: IntVector<u64> = persist::read_part(&mut bytes)?;
+            if digits.element_bits() != 2 || digits.len() != len {
+                return Err(bad("malformed level"));
+            }
+            let digits: Vec<u64> = digits.iter().collect();
+            levels.push(DigitLevel::new(&digits));
+        }
+        persist::expect_end(who, bytes)?;
+
+        Ok(QuadWaveletMatrix { levels: Levels { len, levels } })
+    }
+}
+
+impl Component for QuadWaveletMatrix {
+    fn tag() -> String {
+        "QuadWaveletMatrix".to_owned()
+    }
+}
+
+impl SpaceUsage for QuadWaveletMatrix {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        let levels = &self.levels.levels;
+        levels.iter()
+            .map(|level| level.digits.heap_bytes())
+            .sum::<usize>()
+            + levels.capacity() * ::std::mem::size_of::<DigitLevel>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wavelet::WaveletMatrix;
+    use quickcheck::{quickcheck, TestResult};
+
+    #[test]
+    fn empty() {
+        let wm = QuadWaveletMatrix::new(vec![]);
+        assert_eq!(0, wm.len());
+        assert_eq!(1, wm.levels());
+        assert_eq!(0, wm.rank_before(0, 0));
+        assert_eq!(None, wm.select(0, 0));
+    }
+
+    #[test]
+    fn across_blocks() {
+        // Enough elements to need a second superblock.
+        let values: Vec<u64> = (0 .. 70_000).map(|i| i * 7 % 13).collect();
+        let wm = QuadWaveletMatrix::new(values.iter().cloned());
+        let plain = WaveletMatrix::new(values.iter().cloned());
+        assert_eq!(2, wm.levels());
+
+        for &end in &[0, 1, 255, 256, 257, 65_535, 65_536, 65_600, 70_000] {
+            for value in 0 .. 14 {
+                assert_eq!(plain.rank_before(end, value), wm.rank_before(end, value));
+                assert_eq!(plain.count_less(end, value), wm.count_less(end, value));
+            }
+        }
+        assert_eq!(plain.select(5_000, 3), wm.select(5_000, 3));
+        assert_eq!(values[66_000], wm.get(66_000));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_wide() {
+        QuadWaveletMatrix::with_levels(1, vec![4]);
+    }
+
+    #[test]
+    fn qc_matches_naive() {
+        fn prop(values: Vec<u16>, probe: u16, position: usize) -> TestResult {
+            if values.is_empty() { return TestResult::discard(); }
+
+            let values: Vec<u64> = values.into_iter().map(|x| x as u64 % 37)
+                                         .collect();
+            let probe = probe as u64 % 70;
+            let position = position % values.len();
+            let wm = QuadWaveletMatrix::new(values.iter().cloned());
+
+            let rank = values[.. position + 1].iter()
+                                              .filter(|&&x| x == probe).count();
+            let less = values[.. position].iter().filter(|&&x| x < probe).count();
+            let select = values.iter().enumerate().filter(|&(_, &x)| x == probe)
+                               .map(|(i, _)| i as u64).collect::<Vec<_>>();
+
+            let indices: Vec<u64> = (0 .. values.len() as u64).rev().collect();
+            let ends: Vec<(u64, u64)> = (0 .. values.len() as u64 + 1).map(|end| (end, probe)).collect();
+
+            TestResult::from_bool(
+                wm.access_many(&indices) == indices.iter().map(|&i| values[i as usize]).collect::<Vec<_>>()
+                    && wm.rank_many(&ends) == ends.iter().map(|&(end, value)| wm.rank_before(end, value))
+                                                  .collect::<Vec<_>>()
+                    && wm.get(position as u64) == values[position]
+                    && wm.rank(position as u64, probe) == rank as u64
+                    && wm.count_less(position as u64, probe) == less as u64
+                    && (0 .. select.len() + 1).all(|k| {
+                        wm.select(k as u64, probe) == select.get(k).cloned()
+                    }))
+        }
+
+        quickcheck(prop as fn(Vec<u16>, u16, usize) -> TestResult);
+    }
+}
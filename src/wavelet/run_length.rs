@@ -0,0 +1,258 @@
+use std::io::{self, Write};
+
+use bit_vec::{BitVec, BitVector, HybridBitmap};
+use dump::{Elements, Snapshot};
+use int_vec::IntVec;
+use persist::{Component, Persist};
+use rank::RankSupport;
+use select::{Select0Support, Select1Support, SelectSupport};
+use space_usage::SpaceUsage;
+use super::{LevelBits, WaveletMatrix};
+
+/// A wavelet matrix for sequences with long runs of equal values, whose
+/// levels are run-length compressed.
+///
+/// It is a [`WaveletMatrix`](struct.WaveletMatrix.html) whose levels are
+/// [`HybridBitmap`](../bit_vec/struct.HybridBitmap.html)s, which also
+/// counts the runs.
+/// The stable partition at each level keeps equal values together, so
+/// a run in the input stays a run of equal bits at every level, and the
+/// bitmaps store those as runs. The space then grows with the number of
+/// runs rather than the length: a read-depth track or the BWT of a
+/// repetitive text shrinks by about its average run length. Queries are
+/// the same as for a `WaveletMatrix`, with a bitmap rank or select in
+/// place of a `Rank9` one at each level.
+///
+/// # Example
+///
+/// ```
+/// use succinct::{IntVec, SpaceUsage};
+/// use succinct::rank::RankSupport;
+/// use succinct::select::SelectSupport;
+/// use succinct::wavelet::RunLengthWaveletMatrix;
+///
+/// // Long stretches at a few depths.
+/// let depths = (0 .. 1_000_000u64).map(|i| [3, 4, 4, 7, 0][(i / 20_000 % 5) as usize]);
+/// let wm = RunLengthWaveletMatrix::new(depths);
+///
+/// assert_eq!(40, wm.runs());
+/// assert_eq!(7, wm.get(70_000));
+/// assert_eq!(200_000, wm.rank(999_999, 3));
+/// assert_eq!(Some(100_000), wm.select(20_000, 3));
+/// assert!(wm.heap_bytes() < 20_000);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RunLengthWaveletMatrix {
+    runs: u64,
+    matrix: WaveletMatrix<HybridBitmap>,
+}
+
+impl LevelBits for HybridBitmap {
+    fn from_bit_vector(bits: BitVector<u64>) -> Self {
+        HybridBitmap::new(bits.bit_len(), (0 .. bits.bit_len()).filter(|&i| bits.get_bit(i)))
+    }
+
+    fn bit(&self, index: u64) -> bool {
+        self.get(index)
+    }
+
+    fn select_bit(&self, index: u64, bit: bool) -> Option<u64> {
+        if bit {self.select1(index)} else {self.select0(index)}
+    }
+}
+
+impl RunLengthWaveletMatrix {
+    /// Creates a matrix holding the given values, with as many levels as
+    /// the largest of them needs.
+    pub fn new<I>(values: I) -> Self
+        where I: IntoIterator<Item = u64> {
+
+        let values: Vec<u64> = values.into_iter().collect();
+        let runs = (0 .. values.len())
+            .filter(|&i| i == 0 || values[i] != values[i - 1])
+            .count() as u64;
+
+        RunLengthWaveletMatrix { runs, matrix: WaveletMatrix::new_in(values) }
+    }
+
+    /// Creates a matrix from the elements of an integer vector.
+    pub fn from_int_vec<V>(source: &V) -> Self
+        where V: IntVec + ?Sized {
+
+        use num_traits::ToPrimitive;
+
+        Self::new((0 .. source.len()).map(|i| {
+            source.get(i).to_u64().expect("RunLengthWaveletMatrix: block too wide")
+        }))
+    }
+
+    /// The number of runs of equal values.
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    /// Borrows the underlying matrix, for the queries of a
+    /// `WaveletMatrix` that this doesn’t repeat.
+    pub fn inner(&self) -> &WaveletMatrix<HybridBitmap> {
+        &self.matrix
+    }
+
+    /// Returns the underlying matrix.
+    pub fn into_inner(self) -> WaveletMatrix<HybridBitmap> {
+        self.matrix
+    }
+
+    /// The number of bits per element.
+    pub fn levels(&self) -> usize {
+        self.matrix.levels()
+    }
+
+    /// The number of occurrences of `value` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn rank_before(&self, end: u64, value: u64) -> u64 {
+        self.matrix.rank_before(end, value)
+    }
+
+    /// The elements at each of `indices`, in order, as
+    /// [`WaveletMatrix::access_many`](struct.WaveletMatrix.html#method.access_many)
+    /// finds them.
+    pub fn access_many(&self, indices: &[u64]) -> Vec<u64> {
+        self.matrix.access_many(indices)
+    }
+
+    /// The number of occurrences of each `value` before each `end`, as
+    /// [`WaveletMatrix::rank_many`](struct.WaveletMatrix.html#method.rank_many)
+    /// counts them.
+    pub fn rank_many(&self, queries: &[(u64, u64)]) -> Vec<u64> {
+        self.matrix.rank_many(queries)
+    }
+
+    /// The number of elements less than `value` before position `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end > self.len()`.
+    pub fn count_less(&self, end: u64, value: u64) -> u64 {
+        self.matrix.count_less(end, value)
+    }
+}
+
+impl IntVec for RunLengthWaveletMatrix {
+    type Block = u64;
+
+    fn len(&self) -> u64 {
+        self.matrix.len()
+    }
+
+    fn element_bits(&self) -> usize {
+        self.matrix.element_bits()
+    }
+
+    fn get(&self, index: u64) -> u64 {
+        self.matrix.get(index)
+    }
+}
+
+impl RankSupport for RunLengthWaveletMatrix {
+    impl_rank_support_adapter!(u64, matrix);
+}
+
+impl SelectSupport for RunLengthWaveletMatrix {
+    impl_select_support_adapter!(u64, matrix);
+}
+
+/// Lists the values in order.
+impl Snapshot for RunLengthWaveletMatrix {
+    type Item = u64;
+    type Iter<'a> = Elements<'a, Self> where Self: 'a;
+
+    fn dump_iter(&self) -> Elements<'_, Self> {
+        Elements::new(self)
+    }
+}
+
+/// The matrix, as a `WaveletMatrix` writes it. Loading counts the runs
+/// again.
+impl Persist for RunLengthWaveletMatrix {
+    fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        self.matrix.write_to(sink)
+    }
+
+    fn read_from(bytes: &[u8]) -> io::Result<Self> {
+        let matrix = WaveletMatrix::<HybridBitmap>::read_from(bytes)?;
+        let mut previous = None;
+        let runs = matrix.dump_iter()
+            .filter(|&value| previous.replace(value) != Some(value))
+            .count() as u64;
+        Ok(RunLengthWaveletMatrix { runs, matrix })
+    }
+}
+
+impl Component for RunLengthWaveletMatrix {
+    fn tag() -> String {
+        "RunLengthWaveletMatrix".to_owned()
+    }
+}
+
+impl SpaceUsage for RunLengthWaveletMatrix {
+    fn is_stack_only() -> bool { false }
+
+    fn heap_bytes(&self) -> usize {
+        self.matrix.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck::{quickcheck, TestResult};
+
+    #[test]
+    fn empty() {
+        let wm = RunLengthWaveletMatrix::new(vec![]);
+        assert_eq!(0, wm.len());
+        assert_eq!(0, wm.runs());
+        assert_eq!(0, wm.rank_before(0, 0));
+        assert_eq!(None, wm.select(0, 0));
+    }
+
+    #[test]
+    fn smaller_than_plain() {
+        let values: Vec<u64> = (0 .. 200_000).map(|i| (i / 1000) * 37 % 256).collect();
+        let rl = RunLengthWaveletMatrix::new(values.iter().cloned());
+        let plain = WaveletMatrix::new(values.iter().cloned());
+        assert_eq!(200, rl.runs());
+        assert!(rl.heap_bytes() * 10 < plain.heap_bytes());
+    }
+
+    #[test]
+    fn qc_matches_wavelet_matrix() {
+        fn prop(runs: Vec<(u8, u8)>, probe: u8, position: usize) -> TestResult {
+            let values: Vec<u64> = runs.iter()
+                .flat_map(|&(value, len)| vec![value as u64 % 19; len as usize % 40])
+                .collect();
+            if values.is_empty() { return TestResult::discard(); }
+
+            let rl = RunLengthWaveletMatrix::new(values.iter().cloned());
+            let wm = WaveletMatrix::new(values.iter().cloned());
+            let probe = probe as u64 % 20;
+            let position = position as u64 % rl.len();
+
+            TestResult::from_bool(
+                rl.get(position) == wm.get(position)
+                    && rl.access_many(&[position, 0]) == wm.access_many(&[position, 0])
+                    && rl.rank_many(&[(position, probe), (rl.len(), probe)])
+                        == wm.rank_many(&[(position, probe), (wm.len(), probe)])
+                    && rl.rank(position, probe) == wm.rank(position, probe)
+                    && rl.count_less(position, probe) == wm.count_less(position, probe)
+                    && (0 .. rl.rank_before(rl.len(), probe) + 1).all(|k| {
+                        rl.select(k, probe) == wm.select(k, probe)
+                    }))
+        }
+
+        quickcheck(prop as fn(Vec<(u8, u8)>, u8, usize) -> TestResult);
+    }
+}